@@ -0,0 +1,47 @@
+-- Staff accounts, the audit trail of their actions, and counterfeit reports
+-- awaiting review. Lives in the indexer's database, next to the dispute
+-- service's arbitrators, which the admin API also manages.
+
+CREATE TABLE staff (
+    name         TEXT PRIMARY KEY,
+    -- SHA-256 of the API token; the token is shown once when added.
+    token_sha256 BYTEA NOT NULL UNIQUE,
+    -- Permissions, from `Permission` in src/auth.rs.
+    roles        TEXT[] NOT NULL,
+    active       BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Every mutating request, whether it succeeded or not.
+CREATE TABLE admin_actions (
+    id         BIGSERIAL PRIMARY KEY,
+    actor      TEXT NOT NULL,
+    action     TEXT NOT NULL,
+    -- What was acted on: a principal, order id, report id or arbitrator.
+    target     TEXT,
+    request    JSONB NOT NULL,
+    succeeded  BOOLEAN NOT NULL,
+    -- The resulting transaction signature, or the error.
+    detail     TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX admin_actions_actor ON admin_actions (actor, id);
+
+CREATE TABLE counterfeit_reports (
+    id            BIGSERIAL PRIMARY KEY,
+    serial_number TEXT NOT NULL,
+    order_id      TEXT,
+    -- Who reported it, as given by the filing staff member: a customer
+    -- email, a principal or a retailer.
+    reporter      TEXT NOT NULL,
+    description   TEXT NOT NULL,
+    filed_by      TEXT NOT NULL REFERENCES staff (name),
+    status        TEXT NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'confirmed', 'dismissed')),
+    reviewed_by   TEXT REFERENCES staff (name),
+    review_note   TEXT,
+    reviewed_at   TIMESTAMPTZ,
+    created_at    TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX counterfeit_reports_open ON counterfeit_reports (created_at) WHERE status = 'open';
@@ -0,0 +1,213 @@
+//! Partner API keys for the gateway's `/verify` (`gateway/`), managed
+//! through the same tables it reads.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use axum::Json;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::audit;
+use crate::auth::{authenticate, token_digest, ApiError, Permission};
+use crate::AppState;
+
+#[derive(Serialize)]
+pub struct ApiKey {
+    pub name: String,
+    pub contact: String,
+    pub requests_per_minute: i32,
+    pub daily_quota: Option<i64>,
+    pub active: bool,
+    pub created_by: String,
+    /// Requests and 429s so far today, UTC.
+    pub requests_today: i64,
+    pub throttled_today: i64,
+}
+
+/// `GET /api-keys`
+pub async fn list(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Result<Json<Vec<ApiKey>>, ApiError> {
+    authenticate(&state.db, &headers).await?;
+    let rows = state
+        .db
+        .query(
+            "SELECT k.name, k.contact, k.requests_per_minute, k.daily_quota, k.active, k.created_by,
+                    COALESCE(u.requests, 0) AS requests_today, COALESCE(u.throttled, 0) AS throttled_today
+             FROM api_keys k
+             LEFT JOIN api_key_usage u ON u.key_id = k.id AND u.day = (now() AT TIME ZONE 'UTC')::date
+             ORDER BY k.name",
+            &[],
+        )
+        .await?;
+    Ok(Json(
+        rows.iter()
+            .map(|row| ApiKey {
+                name: row.get("name"),
+                contact: row.get("contact"),
+                requests_per_minute: row.get("requests_per_minute"),
+                daily_quota: row.get("daily_quota"),
+                active: row.get("active"),
+                created_by: row.get("created_by"),
+                requests_today: row.get("requests_today"),
+                throttled_today: row.get("throttled_today"),
+            })
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Limits {
+    #[serde(default = "default_requests_per_minute")]
+    requests_per_minute: i32,
+    /// `null` leaves the key uncapped.
+    #[serde(default)]
+    daily_quota: Option<i64>,
+}
+
+impl Limits {
+    fn check(&self) -> Result<(), ApiError> {
+        if self.requests_per_minute < 1 || self.daily_quota.is_some_and(|quota| quota < 1) {
+            return Err(ApiError::bad_request("requests_per_minute and daily_quota must be positive"));
+        }
+        Ok(())
+    }
+}
+
+fn default_requests_per_minute() -> i32 {
+    600
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct NewKey {
+    /// The partner, e.g. `pricecheck-eu`.
+    name: String,
+    /// Who to reach about the key's traffic.
+    contact: String,
+    #[serde(flatten)]
+    limits: Limits,
+}
+
+/// `POST /api-keys`: returns the key, once.
+pub async fn issue(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<NewKey>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let staff = authenticate(&state.db, &headers).await?;
+    staff.require(Permission::ApiKeys)?;
+    if request.name.trim().is_empty() || request.contact.trim().is_empty() {
+        return Err(ApiError::bad_request("A name and a contact are required"));
+    }
+    request.limits.check()?;
+    let mut key = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut key);
+    let key = format!("pk_{}", hex::encode(key));
+    let result = state
+        .db
+        .execute(
+            "INSERT INTO api_keys (name, contact, key_sha256, requests_per_minute, daily_quota, created_by)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+            &[
+                &request.name,
+                &request.contact,
+                &token_digest(&key),
+                &request.limits.requests_per_minute,
+                &request.limits.daily_quota,
+                &staff.name,
+            ],
+        )
+        .await
+        .map_err(|e| match e.code() {
+            Some(code) if *code == tokio_postgres::error::SqlState::UNIQUE_VIOLATION => {
+                ApiError::conflict(format!("API key {} already exists", request.name))
+            }
+            _ => ApiError::from(e),
+        });
+    audit::record(&state, &staff, "issue_api_key", &request.name, json!(request), result, |_| None).await?;
+    Ok(Json(json!({"name": request.name, "key": key})))
+}
+
+/// `POST /api-keys/{name}/limits`: replace the key's limits. Gateways pick
+/// them up within a minute.
+pub async fn set_limits(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Json(request): Json<Limits>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let staff = authenticate(&state.db, &headers).await?;
+    staff.require(Permission::ApiKeys)?;
+    request.check()?;
+    let result = match state
+        .db
+        .execute(
+            "UPDATE api_keys SET requests_per_minute = $2, daily_quota = $3 WHERE name = $1",
+            &[&name, &request.requests_per_minute, &request.daily_quota],
+        )
+        .await
+    {
+        Ok(0) => Err(ApiError::not_found(format!("No API key {}", name))),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.into()),
+    };
+    audit::record(&state, &staff, "set_api_key_limits", &name, json!(request), result, |_| None).await?;
+    Ok(Json(json!({"name": name, "requests_per_minute": request.requests_per_minute, "daily_quota": request.daily_quota})))
+}
+
+/// `DELETE /api-keys/{name}`: revoke the key. Its usage history stays.
+pub async fn revoke(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let staff = authenticate(&state.db, &headers).await?;
+    staff.require(Permission::ApiKeys)?;
+    let result = match state
+        .db
+        .execute("UPDATE api_keys SET active = FALSE WHERE name = $1", &[&name])
+        .await
+    {
+        Ok(0) => Err(ApiError::not_found(format!("No API key {}", name))),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.into()),
+    };
+    audit::record(&state, &staff, "revoke_api_key", &name, json!({}), result, |_| None).await?;
+    Ok(Json(json!({"name": name, "active": false})))
+}
+
+#[derive(Serialize)]
+pub struct DayUsage {
+    /// `YYYY-MM-DD`, UTC.
+    pub day: String,
+    pub requests: i64,
+    pub throttled: i64,
+}
+
+/// `GET /api-keys/{name}/usage`: the last 90 days, newest first.
+pub async fn usage(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<DayUsage>>, ApiError> {
+    authenticate(&state.db, &headers).await?;
+    let key = state
+        .db
+        .query_opt("SELECT id FROM api_keys WHERE name = $1", &[&name])
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("No API key {}", name)))?;
+    let rows = state
+        .db
+        .query(
+            "SELECT day::text AS day, requests, throttled FROM api_key_usage
+             WHERE key_id = $1 ORDER BY day DESC LIMIT 90",
+            &[&key.get::<_, i64>("id")],
+        )
+        .await?;
+    Ok(Json(
+        rows.iter()
+            .map(|row| DayUsage { day: row.get("day"), requests: row.get("requests"), throttled: row.get("throttled") })
+            .collect(),
+    ))
+}
@@ -0,0 +1,113 @@
+//! The dispute service's arbitrators (`disputes/`), managed through the
+//! same table it reads.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use axum::Json;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::audit;
+use crate::auth::{authenticate, token_digest, ApiError, Permission};
+use crate::AppState;
+
+#[derive(Serialize)]
+pub struct Arbitrator {
+    pub name: String,
+    pub active: bool,
+    pub max_open_cases: i32,
+    /// Cases assigned and not yet decided.
+    pub open_cases: i64,
+}
+
+/// `GET /arbitrators`
+pub async fn list(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Result<Json<Vec<Arbitrator>>, ApiError> {
+    authenticate(&state.db, &headers).await?;
+    let rows = state
+        .db
+        .query(
+            "SELECT a.name, a.active, a.max_open_cases, count(c.order_id) AS open_cases
+             FROM arbitrators a
+             LEFT JOIN dispute_cases c ON c.arbitrator = a.name AND c.status = 'assigned'
+             GROUP BY a.name ORDER BY a.name",
+            &[],
+        )
+        .await?;
+    Ok(Json(
+        rows.iter()
+            .map(|row| Arbitrator {
+                name: row.get("name"),
+                active: row.get("active"),
+                max_open_cases: row.get("max_open_cases"),
+                open_cases: row.get("open_cases"),
+            })
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct NewArbitrator {
+    name: String,
+    #[serde(default = "default_max_open_cases")]
+    max_open_cases: i32,
+}
+
+fn default_max_open_cases() -> i32 {
+    20
+}
+
+/// `POST /arbitrators`: returns the arbitrator's API token, once.
+pub async fn add(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<NewArbitrator>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let staff = authenticate(&state.db, &headers).await?;
+    staff.require(Permission::Arbitrators)?;
+    if request.name.trim().is_empty() || request.max_open_cases < 1 {
+        return Err(ApiError::bad_request("A name and a positive max_open_cases are required"));
+    }
+    let mut token = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut token);
+    let token = format!("arb_{}", hex::encode(token));
+    let result = state
+        .db
+        .execute(
+            "INSERT INTO arbitrators (name, token_sha256, max_open_cases) VALUES ($1, $2, $3)",
+            &[&request.name, &token_digest(&token), &request.max_open_cases],
+        )
+        .await
+        .map_err(|e| match e.code() {
+            Some(code) if *code == tokio_postgres::error::SqlState::UNIQUE_VIOLATION => {
+                ApiError::conflict(format!("Arbitrator {} already exists", request.name))
+            }
+            _ => ApiError::from(e),
+        });
+    audit::record(&state, &staff, "add_arbitrator", &request.name, json!(request), result, |_| None).await?;
+    Ok(Json(json!({"name": request.name, "token": token})))
+}
+
+/// `DELETE /arbitrators/{name}`: revoke the token and stop assignments.
+/// Undecided cases stay with the arbitrator until reassigned.
+pub async fn deactivate(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let staff = authenticate(&state.db, &headers).await?;
+    staff.require(Permission::Arbitrators)?;
+    let result = match state
+        .db
+        .execute("UPDATE arbitrators SET active = FALSE WHERE name = $1", &[&name])
+        .await
+    {
+        Ok(0) => Err(ApiError::not_found(format!("No arbitrator {}", name))),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.into()),
+    };
+    audit::record(&state, &staff, "deactivate_arbitrator", &name, json!({}), result, |_| None).await?;
+    Ok(Json(json!({"name": name, "active": false})))
+}
@@ -0,0 +1,97 @@
+//! The audit trail: every change requested through the API, by whom, and
+//! how it ended.
+
+use std::sync::Arc;
+
+use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{authenticate, ApiError, Permission, Staff};
+use crate::AppState;
+
+/// Record the outcome of an action and pass it through. `detail` describes
+/// a success, e.g. with a transaction signature.
+pub async fn record<T>(
+    state: &AppState,
+    staff: &Staff,
+    action: &str,
+    target: &str,
+    request: serde_json::Value,
+    result: Result<T, ApiError>,
+    detail: impl FnOnce(&T) -> Option<String>,
+) -> Result<T, ApiError> {
+    let (succeeded, detail) = match &result {
+        Ok(value) => (true, detail(value)),
+        Err(e) => (false, Some(e.message.clone())),
+    };
+    state
+        .db
+        .execute(
+            "INSERT INTO admin_actions (actor, action, target, request, succeeded, detail)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+            &[&staff.name, &action, &target, &request, &succeeded, &detail],
+        )
+        .await?;
+    result
+}
+
+#[derive(Serialize)]
+pub struct Action {
+    pub id: i64,
+    pub actor: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub request: serde_json::Value,
+    pub succeeded: bool,
+    pub detail: Option<String>,
+    /// Unix seconds.
+    pub at: i64,
+}
+
+#[derive(Deserialize)]
+pub struct ActionFilter {
+    actor: Option<String>,
+    action: Option<String>,
+    target: Option<String>,
+    /// Only actions with a smaller id, to page back in time.
+    before: Option<i64>,
+}
+
+/// `GET /audit`: newest first, 200 at a time.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(filter): Query<ActionFilter>,
+) -> Result<Json<Vec<Action>>, ApiError> {
+    authenticate(&state.db, &headers).await?.require(Permission::Audit)?;
+    let rows = state
+        .db
+        .query(
+            "SELECT id, actor, action, target, request, succeeded, detail,
+                    extract(epoch FROM created_at)::bigint AS at
+             FROM admin_actions
+             WHERE ($1::text IS NULL OR actor = $1)
+               AND ($2::text IS NULL OR action = $2)
+               AND ($3::text IS NULL OR target = $3)
+               AND ($4::bigint IS NULL OR id < $4)
+             ORDER BY id DESC LIMIT 200",
+            &[&filter.actor, &filter.action, &filter.target, &filter.before],
+        )
+        .await?;
+    Ok(Json(
+        rows.iter()
+            .map(|row| Action {
+                id: row.get("id"),
+                actor: row.get("actor"),
+                action: row.get("action"),
+                target: row.get("target"),
+                request: row.get("request"),
+                succeeded: row.get("succeeded"),
+                detail: row.get("detail"),
+                at: row.get("at"),
+            })
+            .collect(),
+    ))
+}
@@ -0,0 +1,178 @@
+//! Staff tokens and permissions.
+//!
+//! Each staff member holds a bearer token and a set of permissions. Reads
+//! are open to every active staff member; each kind of change needs its own
+//! permission, so nobody holds more than their job needs.
+
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use sha2::{Digest, Sha256};
+use tokio_postgres::Client;
+
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, message)
+    }
+
+    /// A failed canister or Solana call.
+    pub fn upstream(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_GATEWAY, message)
+    }
+}
+
+impl From<tokio_postgres::Error> for ApiError {
+    fn from(e: tokio_postgres::Error) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Database: {}", e))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(serde_json::json!({"error": self.message}))).into_response()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Permission {
+    /// Pause and resume the NFT canister.
+    Pause,
+    /// Register manufacturers, set mint quotas, grant and revoke canister
+    /// roles.
+    Minters,
+    /// Add and deactivate dispute arbitrators.
+    Arbitrators,
+    /// File counterfeit reports on behalf of customers and retailers.
+    Reports,
+    /// Review counterfeit reports and restore revoked verifications.
+    Moderate,
+    /// Send `resolve_refund` or `resolve_release` for a disputed escrow.
+    Resolve,
+    /// Read the audit trail.
+    Audit,
+    /// Issue, limit and revoke partner API keys for the gateway.
+    ApiKeys,
+}
+
+pub const PERMISSIONS: &[Permission] = &[
+    Permission::Pause,
+    Permission::Minters,
+    Permission::Arbitrators,
+    Permission::Reports,
+    Permission::Moderate,
+    Permission::Resolve,
+    Permission::Audit,
+    Permission::ApiKeys,
+];
+
+impl Permission {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Permission::Pause => "pause",
+            Permission::Minters => "minters",
+            Permission::Arbitrators => "arbitrators",
+            Permission::Reports => "reports",
+            Permission::Moderate => "moderate",
+            Permission::Resolve => "resolve",
+            Permission::Audit => "audit",
+            Permission::ApiKeys => "api_keys",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        PERMISSIONS.iter().copied().find(|permission| permission.as_str() == name)
+    }
+}
+
+/// An authenticated staff member.
+pub struct Staff {
+    pub name: String,
+    pub roles: Vec<String>,
+}
+
+impl Staff {
+    pub fn require(&self, permission: Permission) -> Result<(), ApiError> {
+        if self.roles.iter().any(|role| role == permission.as_str()) {
+            Ok(())
+        } else {
+            Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                format!("{} lacks the {} permission", self.name, permission.as_str()),
+            ))
+        }
+    }
+}
+
+pub fn token_digest(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
+
+pub async fn authenticate(db: &Client, headers: &HeaderMap) -> Result<Staff, ApiError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, "Missing API token"))?;
+    db.query_opt(
+        "SELECT name, roles FROM staff WHERE token_sha256 = $1 AND active",
+        &[&token_digest(token)],
+    )
+    .await?
+    .map(|row| Staff { name: row.get("name"), roles: row.get("roles") })
+    .ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, "Unknown API token"))
+}
+
+/// Check a comma-separated permission list from the command line.
+pub fn parse_roles(roles: &[String]) -> Result<Vec<String>, String> {
+    for role in roles {
+        if Permission::parse(role).is_none() {
+            let known: Vec<&str> = PERMISSIONS.iter().map(|p| p.as_str()).collect();
+            return Err(format!("Unknown permission {}; expected one of {}", role, known.join(", ")));
+        }
+    }
+    Ok(roles.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permission_names_round_trip() {
+        for permission in PERMISSIONS {
+            assert_eq!(Permission::parse(permission.as_str()), Some(*permission));
+        }
+        assert_eq!(Permission::parse("superadmin"), None);
+    }
+
+    #[test]
+    fn staff_need_the_exact_permission() {
+        let staff = Staff { name: "sam".into(), roles: vec!["reports".into()] };
+        assert!(staff.require(Permission::Reports).is_ok());
+        assert_eq!(staff.require(Permission::Moderate).unwrap_err().status, StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn unknown_roles_are_rejected() {
+        assert!(parse_roles(&["pause".into(), "audit".into()]).is_ok());
+        assert!(parse_roles(&["pause".into(), "root".into()]).is_err());
+    }
+}
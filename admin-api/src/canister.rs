@@ -0,0 +1,234 @@
+//! NFT canister administration: pausing, manufacturers (the minter
+//! registry) and canister roles. Calls are made as the service's identity,
+//! which holds SuperAdmin; staff never hold that key.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::http::HeaderMap;
+use axum::Json;
+use candid::Principal;
+use proofcart_icp_client::types::{Manufacturer, PauseState, Role};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::audit;
+use crate::auth::{authenticate, ApiError, Permission};
+use crate::AppState;
+
+fn principal(text: &str) -> Result<Principal, ApiError> {
+    Principal::from_text(text).map_err(|e| ApiError::bad_request(format!("Invalid principal {}: {}", text, e)))
+}
+
+/// Roles staff may grant. SuperAdmin moves only through the canister's own
+/// propose/accept handover.
+fn role(name: &str) -> Result<Role, ApiError> {
+    match name {
+        "Verifier" => Ok(Role::Verifier),
+        "Support" => Ok(Role::Support),
+        "Marketplace" => Ok(Role::Marketplace),
+        "Distributor" => Ok(Role::Distributor),
+        "ServiceCenter" => Ok(Role::ServiceCenter),
+        "Recycler" => Ok(Role::Recycler),
+        "Auditor" => Ok(Role::Auditor),
+        _ => Err(ApiError::bad_request(format!("Role {} cannot be granted here", name))),
+    }
+}
+
+#[derive(Serialize)]
+pub struct Pause {
+    pub paused: bool,
+    pub changed_by: Option<String>,
+    /// Nanoseconds since the epoch, as the canister reports it.
+    pub changed_at: Option<u64>,
+}
+
+impl From<PauseState> for Pause {
+    fn from(state: PauseState) -> Self {
+        Self {
+            paused: state.paused,
+            changed_by: state.changed_by.map(|p| p.to_text()),
+            changed_at: state.changed_at,
+        }
+    }
+}
+
+/// `GET /pause`
+pub async fn pause_state(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Result<Json<Pause>, ApiError> {
+    authenticate(&state.db, &headers).await?;
+    let pause = state.nft.get_pause_state().await.map_err(|e| ApiError::upstream(e.to_string()))?;
+    Ok(Json(pause.into()))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SetPause {
+    paused: bool,
+    /// Why, for the audit trail.
+    reason: String,
+}
+
+/// `POST /pause`: pause or resume the canister's update methods.
+pub async fn set_pause(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<SetPause>,
+) -> Result<Json<Pause>, ApiError> {
+    let staff = authenticate(&state.db, &headers).await?;
+    staff.require(Permission::Pause)?;
+    if request.reason.trim().is_empty() {
+        return Err(ApiError::bad_request("A reason is required"));
+    }
+    let result = state.nft.set_paused(request.paused).await.map_err(|e| ApiError::upstream(e.to_string()));
+    let action = if request.paused { "pause" } else { "resume" };
+    let target = state.nft.canister_id().to_text();
+    let pause = audit::record(&state, &staff, action, &target, json!(request), result, |_| None).await?;
+    Ok(Json(pause.into()))
+}
+
+#[derive(Serialize)]
+pub struct Minter {
+    pub principal: String,
+    pub name: String,
+    pub mint_quota: Option<u64>,
+    pub minted: Option<u64>,
+    pub serial_format: Option<String>,
+}
+
+impl From<Manufacturer> for Minter {
+    fn from(manufacturer: Manufacturer) -> Self {
+        Self {
+            principal: manufacturer.principal.to_text(),
+            name: manufacturer.name,
+            mint_quota: manufacturer.mint_quota,
+            minted: manufacturer.minted,
+            serial_format: manufacturer.serial_format,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Page {
+    #[serde(default)]
+    offset: u64,
+    limit: Option<u64>,
+}
+
+/// `GET /manufacturers?offset=&limit=`
+pub async fn list_manufacturers(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(page): Query<Page>,
+) -> Result<Json<Vec<Minter>>, ApiError> {
+    authenticate(&state.db, &headers).await?;
+    let manufacturers = state
+        .nft
+        .list_manufacturers(page.offset, page.limit.unwrap_or(100).min(100))
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    Ok(Json(manufacturers.into_iter().map(Minter::from).collect()))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Registration {
+    principal: String,
+    name: String,
+}
+
+/// `POST /manufacturers`: register a manufacturer, or rename one.
+pub async fn register_manufacturer(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<Registration>,
+) -> Result<Json<Minter>, ApiError> {
+    let staff = authenticate(&state.db, &headers).await?;
+    staff.require(Permission::Minters)?;
+    let principal = principal(&request.principal)?;
+    let result = state
+        .nft
+        .register_manufacturer(principal, &request.name)
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()));
+    let manufacturer = audit::record(
+        &state,
+        &staff,
+        "register_manufacturer",
+        &request.principal,
+        json!(request),
+        result,
+        |_| None,
+    )
+    .await?;
+    Ok(Json(manufacturer.into()))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Quota {
+    /// `null` lifts the cap.
+    quota: Option<u64>,
+}
+
+/// `POST /manufacturers/{principal}/quota`
+pub async fn set_mint_quota(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(manufacturer): Path<String>,
+    Json(request): Json<Quota>,
+) -> Result<Json<Minter>, ApiError> {
+    let staff = authenticate(&state.db, &headers).await?;
+    staff.require(Permission::Minters)?;
+    let principal = principal(&manufacturer)?;
+    let result = state
+        .nft
+        .set_mint_quota(principal, request.quota)
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()));
+    let manufacturer =
+        audit::record(&state, &staff, "set_mint_quota", &manufacturer, json!(request), result, |_| None).await?;
+    Ok(Json(manufacturer.into()))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RoleChange {
+    principal: String,
+    /// As in the canister's `Role`, e.g. `Marketplace`.
+    role: String,
+}
+
+/// `POST /roles/grant`
+pub async fn grant_role(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<RoleChange>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    change_role(&state, &headers, request, true).await
+}
+
+/// `POST /roles/revoke`
+pub async fn revoke_role(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<RoleChange>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    change_role(&state, &headers, request, false).await
+}
+
+async fn change_role(
+    state: &AppState,
+    headers: &HeaderMap,
+    request: RoleChange,
+    grant: bool,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let staff = authenticate(&state.db, headers).await?;
+    staff.require(Permission::Minters)?;
+    let principal = principal(&request.principal)?;
+    let role = role(&request.role)?;
+    let result = if grant {
+        state.nft.grant_role(principal, role).await
+    } else {
+        state.nft.revoke_role(principal, role).await
+    }
+    .map_err(|e| ApiError::upstream(e.to_string()));
+    let action = if grant { "grant_role" } else { "revoke_role" };
+    audit::record(state, &staff, action, &request.principal, json!(request), result, |_| None).await?;
+    Ok(Json(json!({"principal": request.principal, "role": request.role, "granted": grant})))
+}
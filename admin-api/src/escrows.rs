@@ -0,0 +1,75 @@
+//! Settling a disputed escrow as the program's admin. The keypair stays
+//! with the service; staff with the `resolve` permission only name the
+//! order and the resolution. A case in the dispute service closes once the
+//! indexer records the resolution.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use axum::Json;
+use proofcart_solana_client::{EscrowStatus, Resolution};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::audit;
+use crate::auth::{authenticate, ApiError, Permission};
+use crate::AppState;
+
+#[derive(Deserialize, Serialize)]
+pub struct Resolve {
+    /// `refund` or `release`.
+    resolution: String,
+    /// Why, for the audit trail.
+    reason: String,
+}
+
+/// `POST /escrows/{order_id}/resolve`
+pub async fn resolve(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(order_id): Path<String>,
+    Json(request): Json<Resolve>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let staff = authenticate(&state.db, &headers).await?;
+    staff.require(Permission::Resolve)?;
+    let resolution = match request.resolution.as_str() {
+        "refund" => Resolution::Refund,
+        "release" => Resolution::Release,
+        _ => return Err(ApiError::bad_request("resolution must be refund or release")),
+    };
+    if request.reason.trim().is_empty() {
+        return Err(ApiError::bad_request("A reason is required"));
+    }
+    let admin = state
+        .escrow_admin
+        .as_ref()
+        .ok_or_else(|| ApiError::conflict("No escrow admin keypair is configured; resolve through the arbiter canister"))?;
+
+    let result = async {
+        let escrow = state
+            .escrow
+            .fetch_escrow(&order_id)
+            .await
+            .map_err(|e| ApiError::upstream(e.to_string()))?
+            .ok_or_else(|| ApiError::not_found(format!("No escrow for order {}", order_id)))?;
+        if escrow.status != EscrowStatus::Locked {
+            return Err(ApiError::conflict(format!("Escrow {} is {:?}, not disputed", order_id, escrow.status)));
+        }
+        state
+            .escrow
+            .resolve(admin, &order_id, resolution)
+            .await
+            .map_err(|e| ApiError::upstream(e.to_string()))
+    }
+    .await;
+    let signature = audit::record(&state, &staff, "resolve_escrow", &order_id, json!(request), result, |signature| {
+        Some(signature.to_string())
+    })
+    .await?;
+    Ok(Json(json!({
+        "order_id": order_id,
+        "resolution": request.resolution,
+        "signature": signature.to_string(),
+    })))
+}
@@ -0,0 +1,221 @@
+//! `proofcart-admin-api`: the staff dashboard's backend. Staff act through
+//! permissioned API tokens; the service holds the NFT canister's SuperAdmin
+//! identity and the escrow admin keypair, and logs every action.
+//!
+//! ```text
+//! GET  /me
+//! GET  /pause                               POST /pause                    {"paused", "reason"}        pause
+//! GET  /manufacturers                       POST /manufacturers            {"principal", "name"}       minters
+//! POST /manufacturers/{principal}/quota     {"quota"}                                                  minters
+//! POST /roles/grant, /roles/revoke          {"principal", "role"}                                      minters
+//! GET  /arbitrators                         POST /arbitrators              {"name", "max_open_cases"}  arbitrators
+//! DELETE /arbitrators/{name}                                                                           arbitrators
+//! GET  /reports                             POST /reports                  {"serial_number", ...}      reports
+//! POST /reports/{id}/review                 {"verdict", "note"}                                        moderate
+//! POST /escrows/{order_id}/resolve          {"resolution", "reason"}                                   resolve
+//! GET  /api-keys                           POST /api-keys                 {"name", "contact", ...}    api_keys
+//! POST /api-keys/{name}/limits              {"requests_per_minute", "daily_quota"}                     api_keys
+//! DELETE /api-keys/{name}                   GET /api-keys/{name}/usage                                 api_keys
+//! GET  /audit?actor=&action=&target=&before=                                                           audit
+//!
+//! proofcart-admin-api add-staff sam --roles reports
+//! proofcart-admin-api serve --identity admin.pem --escrow-admin-keypair escrow-admin.json
+//! ```
+
+mod api_keys;
+mod arbitrators;
+mod audit;
+mod auth;
+mod canister;
+mod db;
+mod escrows;
+mod reports;
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use candid::Principal;
+use clap::{Parser, Subcommand};
+use proofcart_icp_client::{identity, NftClient};
+use proofcart_solana_client::EscrowClient;
+use rand::RngCore;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair};
+use tokio_postgres::Client;
+
+use auth::{authenticate, ApiError};
+
+#[derive(Parser)]
+#[clap(name = "proofcart-admin-api", version, about = "Staff administration of ProofCart")]
+struct Cli {
+    /// Postgres URL of the indexer database
+    #[clap(long, env = "DATABASE_URL")]
+    database_url: String,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve the API
+    Serve {
+        #[clap(long, env = "ADMIN_API_ADDR", default_value = "0.0.0.0:8096")]
+        listen_addr: SocketAddr,
+        /// PEM file of a principal with the SuperAdmin and Verifier roles
+        #[clap(long, env = "ADMIN_IDENTITY")]
+        identity: PathBuf,
+        #[clap(long, env = "IC_URL", default_value = "https://ic0.app")]
+        ic_url: String,
+        #[clap(long, env = "ICP_CANISTER_ID")]
+        canister_id: Principal,
+        #[clap(long, env = "SOLANA_RPC_URL", default_value = "https://api.devnet.solana.com")]
+        rpc_url: String,
+        #[clap(long, env = "SOLANA_PROGRAM_ID")]
+        program_id: Pubkey,
+        /// Keypair file of the escrow admin; unset disables resolutions,
+        /// e.g. when the arbiter canister is the admin
+        #[clap(long, env = "ESCROW_ADMIN_KEYPAIR")]
+        escrow_admin_keypair: Option<PathBuf>,
+    },
+    /// Add a staff member and print their API token
+    AddStaff {
+        name: String,
+        /// Permissions: pause, minters, arbitrators, reports, moderate,
+        /// resolve, audit, api_keys
+        #[clap(long, use_value_delimiter = true, required = true)]
+        roles: Vec<String>,
+    },
+    /// Replace a staff member's permissions
+    SetRoles {
+        name: String,
+        #[clap(long, use_value_delimiter = true, required = true)]
+        roles: Vec<String>,
+    },
+    /// Revoke a staff member's token
+    DeactivateStaff { name: String },
+}
+
+/// Shared by every handler.
+pub struct AppState {
+    pub db: Client,
+    pub nft: NftClient,
+    pub escrow: EscrowClient,
+    pub escrow_admin: Option<Keypair>,
+}
+
+fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/me", get(me))
+        .route("/pause", get(canister::pause_state).post(canister::set_pause))
+        .route(
+            "/manufacturers",
+            get(canister::list_manufacturers).post(canister::register_manufacturer),
+        )
+        .route("/manufacturers/:principal/quota", post(canister::set_mint_quota))
+        .route("/roles/grant", post(canister::grant_role))
+        .route("/roles/revoke", post(canister::revoke_role))
+        .route("/arbitrators", get(arbitrators::list).post(arbitrators::add))
+        .route("/arbitrators/:name", delete(arbitrators::deactivate))
+        .route("/reports", get(reports::list).post(reports::file))
+        .route("/reports/:id/review", post(reports::review))
+        .route("/escrows/:order_id/resolve", post(escrows::resolve))
+        .route("/api-keys", get(api_keys::list).post(api_keys::issue))
+        .route("/api-keys/:name", delete(api_keys::revoke))
+        .route("/api-keys/:name/limits", post(api_keys::set_limits))
+        .route("/api-keys/:name/usage", get(api_keys::usage))
+        .route("/audit", get(audit::list))
+        .with_state(state)
+}
+
+/// `GET /me`: the caller and their permissions, for the dashboard to show
+/// only what they can do.
+async fn me(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Result<Json<serde_json::Value>, ApiError> {
+    let staff = authenticate(&state.db, &headers).await?;
+    Ok(Json(serde_json::json!({"name": staff.name, "roles": staff.roles})))
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run() -> Result<(), String> {
+    let cli = Cli::parse();
+    let mut db = db::connect(&cli.database_url).await?;
+    db::migrate(&mut db).await.map_err(|e| e.to_string())?;
+
+    match cli.command {
+        Command::Serve { listen_addr, identity, ic_url, canister_id, rpc_url, program_id, escrow_admin_keypair } => {
+            let identity = identity::from_pem_file(&identity).map_err(|e| e.to_string())?;
+            let nft = NftClient::connect(&ic_url, identity, canister_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            let escrow_admin = match &escrow_admin_keypair {
+                Some(path) => Some(read_keypair_file(path).map_err(|e| format!("{}: {}", path.display(), e))?),
+                None => None,
+            };
+            let rpc = RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed());
+            let state = Arc::new(AppState { db, nft, escrow: EscrowClient::new(rpc, program_id), escrow_admin });
+            let listener = tokio::net::TcpListener::bind(listen_addr)
+                .await
+                .map_err(|e| format!("{}: {}", listen_addr, e))?;
+            println!("Listening on {}", listen_addr);
+            axum::serve(listener, router(state)).await.map_err(|e| e.to_string())
+        }
+        Command::AddStaff { name, roles } => {
+            let roles = auth::parse_roles(&roles)?;
+            let mut token = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut token);
+            let token = format!("staff_{}", hex::encode(token));
+            db.execute(
+                "INSERT INTO staff (name, token_sha256, roles) VALUES ($1, $2, $3)",
+                &[&name, &auth::token_digest(&token), &roles],
+            )
+            .await
+            .map_err(|e| match e.code() {
+                Some(code) if *code == tokio_postgres::error::SqlState::UNIQUE_VIOLATION => {
+                    format!("Staff member {} already exists", name)
+                }
+                _ => e.to_string(),
+            })?;
+            println!("Staff  {}", name);
+            println!("Token  {}", token);
+            println!("Roles  {}", roles.join(", "));
+        }
+        Command::SetRoles { name, roles } => {
+            let roles = auth::parse_roles(&roles)?;
+            let updated = db
+                .execute("UPDATE staff SET roles = $2 WHERE name = $1", &[&name, &roles])
+                .await
+                .map_err(|e| e.to_string())?;
+            if updated == 0 {
+                return Err(format!("No staff member {}", name));
+            }
+        }
+        Command::DeactivateStaff { name } => {
+            let updated = db
+                .execute("UPDATE staff SET active = FALSE WHERE name = $1", &[&name])
+                .await
+                .map_err(|e| e.to_string())?;
+            if updated == 0 {
+                return Err(format!("No staff member {}", name));
+            }
+        }
+    }
+    Ok(())
+}
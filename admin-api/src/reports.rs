@@ -0,0 +1,209 @@
+//! Counterfeit reports: filed by support staff, reviewed by a moderator.
+//! Confirming a report revokes the product's verification on the NFT
+//! canister with reason `Counterfeit`.
+//!
+//! The reviewer must be someone other than the staff member who filed the
+//! report.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use proofcart_icp_client::types::RevocationReason;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio_postgres::Row;
+
+use crate::audit;
+use crate::auth::{authenticate, ApiError, Permission};
+use crate::AppState;
+
+const COLUMNS: &str = "id, serial_number, order_id, reporter, description, filed_by, status, reviewed_by,
+    review_note, extract(epoch FROM created_at)::bigint AS filed_at,
+    extract(epoch FROM reviewed_at)::bigint AS reviewed_at";
+
+/// A report; times are Unix seconds.
+#[derive(Serialize)]
+pub struct Report {
+    pub id: i64,
+    pub serial_number: String,
+    pub order_id: Option<String>,
+    pub reporter: String,
+    pub description: String,
+    pub filed_by: String,
+    /// `open`, `confirmed` or `dismissed`.
+    pub status: String,
+    pub reviewed_by: Option<String>,
+    pub review_note: Option<String>,
+    pub filed_at: i64,
+    pub reviewed_at: Option<i64>,
+}
+
+impl From<&Row> for Report {
+    fn from(row: &Row) -> Self {
+        Self {
+            id: row.get("id"),
+            serial_number: row.get("serial_number"),
+            order_id: row.get("order_id"),
+            reporter: row.get("reporter"),
+            description: row.get("description"),
+            filed_by: row.get("filed_by"),
+            status: row.get("status"),
+            reviewed_by: row.get("reviewed_by"),
+            review_note: row.get("review_note"),
+            filed_at: row.get("filed_at"),
+            reviewed_at: row.get("reviewed_at"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ReportFilter {
+    status: Option<String>,
+    serial_number: Option<String>,
+}
+
+/// `GET /reports?status=&serial_number=`: oldest first, so the queue is
+/// worked in order.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(filter): Query<ReportFilter>,
+) -> Result<Json<Vec<Report>>, ApiError> {
+    authenticate(&state.db, &headers).await?;
+    let query = format!(
+        "SELECT {} FROM counterfeit_reports
+         WHERE ($1::text IS NULL OR status = $1) AND ($2::text IS NULL OR serial_number = $2)
+         ORDER BY id LIMIT 500",
+        COLUMNS
+    );
+    let rows = state.db.query(&query, &[&filter.status, &filter.serial_number]).await?;
+    Ok(Json(rows.iter().map(Report::from).collect()))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct NewReport {
+    serial_number: String,
+    order_id: Option<String>,
+    reporter: String,
+    description: String,
+}
+
+/// `POST /reports`
+pub async fn file(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<NewReport>,
+) -> Result<Json<Report>, ApiError> {
+    let staff = authenticate(&state.db, &headers).await?;
+    staff.require(Permission::Reports)?;
+    if request.reporter.trim().is_empty() || request.description.trim().is_empty() {
+        return Err(ApiError::bad_request("reporter and description are required"));
+    }
+    // Reports against serials the canister does not know go nowhere.
+    state
+        .nft
+        .verify_product(&request.serial_number)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{}: {}", request.serial_number, e)))?;
+    let query = format!(
+        "INSERT INTO counterfeit_reports (serial_number, order_id, reporter, description, filed_by)
+         VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+        COLUMNS
+    );
+    let result = state
+        .db
+        .query_one(
+            &query,
+            &[&request.serial_number, &request.order_id, &request.reporter, &request.description, &staff.name],
+        )
+        .await
+        .map(|row| Report::from(&row))
+        .map_err(ApiError::from);
+    let report = audit::record(
+        &state,
+        &staff,
+        "file_report",
+        &request.serial_number,
+        json!(request),
+        result,
+        |report| Some(format!("report {}", report.id)),
+    )
+    .await?;
+    Ok(Json(report))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Review {
+    /// `confirmed` or `dismissed`.
+    verdict: String,
+    note: String,
+}
+
+/// `POST /reports/{id}/review`
+pub async fn review(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    Json(request): Json<Review>,
+) -> Result<Json<Report>, ApiError> {
+    let staff = authenticate(&state.db, &headers).await?;
+    staff.require(Permission::Moderate)?;
+    if !matches!(request.verdict.as_str(), "confirmed" | "dismissed") {
+        return Err(ApiError::bad_request("verdict must be confirmed or dismissed"));
+    }
+    if request.note.trim().is_empty() {
+        return Err(ApiError::bad_request("A review note is required"));
+    }
+    let report = state
+        .db
+        .query_opt(&format!("SELECT {} FROM counterfeit_reports WHERE id = $1", COLUMNS), &[&id])
+        .await?
+        .map(|row| Report::from(&row))
+        .ok_or_else(|| ApiError::not_found(format!("No report {}", id)))?;
+    if report.status != "open" {
+        return Err(ApiError::conflict(format!("Report {} is already {}", id, report.status)));
+    }
+    if report.filed_by == staff.name {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "Reports are reviewed by someone other than their filer"));
+    }
+
+    let result = conclude(&state, &report, &request, &staff.name).await;
+    let target = id.to_string();
+    let report = audit::record(&state, &staff, "review_report", &target, json!(request), result, |report| {
+        Some(format!("{} {}", report.status, report.serial_number))
+    })
+    .await?;
+    Ok(Json(report))
+}
+
+/// Revoke the product if confirmed, then close the report.
+async fn conclude(state: &AppState, report: &Report, review: &Review, reviewer: &str) -> Result<Report, ApiError> {
+    if review.verdict == "confirmed" {
+        let nft = state
+            .nft
+            .verify_product(&report.serial_number)
+            .await
+            .map_err(|e| ApiError::upstream(e.to_string()))?;
+        // Another report, or a dispute, may have revoked it already.
+        if !nft.revoked {
+            state
+                .nft
+                .revoke_verification(nft.nft_id, RevocationReason::Counterfeit)
+                .await
+                .map_err(|e| ApiError::upstream(e.to_string()))?;
+        }
+    }
+    let query = format!(
+        "UPDATE counterfeit_reports SET status = $2, reviewed_by = $3, review_note = $4, reviewed_at = now()
+         WHERE id = $1 AND status = 'open' RETURNING {}",
+        COLUMNS
+    );
+    state
+        .db
+        .query_opt(&query, &[&report.id, &review.verdict, &reviewer, &review.note])
+        .await?
+        .map(|row| Report::from(&row))
+        .ok_or_else(|| ApiError::conflict(format!("Report {} was reviewed meanwhile", report.id)))
+}
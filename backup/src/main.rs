@@ -0,0 +1,295 @@
+//! `proofcart-backup`: scheduled snapshots of ProofCart's state to
+//! S3-compatible storage, each file hashed into a manifest, and the restore
+//! that checks those hashes before touching anything.
+//!
+//! ```text
+//! proofcart-backup run --interval-hours 6 --keep 28
+//! proofcart-backup snapshot
+//! proofcart-backup list
+//! proofcart-backup verify 20240610T120000Z
+//! proofcart-backup restore 20240610T120000Z --to restore/ --database-url postgres://...
+//! ```
+
+mod manifest;
+mod metrics;
+mod restore;
+mod snapshot;
+mod store;
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use candid::Principal;
+use clap::{Args, Parser, Subcommand};
+use proofcart_icp_client::{identity, NftClient};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+
+use manifest::Manifest;
+use snapshot::Sources;
+use store::{Store, StoreArgs};
+
+#[derive(Parser)]
+#[clap(name = "proofcart-backup", version, about = "Back up and restore ProofCart state")]
+struct Cli {
+    #[clap(flatten)]
+    store: StoreArgs,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Take a snapshot every interval, verify it and prune old ones, until stopped
+    Run {
+        #[clap(flatten)]
+        sources: SourceArgs,
+        #[clap(long, default_value = "6")]
+        interval_hours: u64,
+        /// Complete snapshots to keep
+        #[clap(long, env = "BACKUP_KEEP", default_value = "28")]
+        keep: usize,
+        #[clap(long, env = "BACKUP_WORK_DIR", default_value = "backup-work")]
+        work_dir: PathBuf,
+        #[clap(long, env = "METRICS_ADDR", default_value = "0.0.0.0:9105")]
+        metrics_addr: SocketAddr,
+    },
+    /// Take one snapshot now
+    Snapshot {
+        #[clap(flatten)]
+        sources: SourceArgs,
+        #[clap(long, env = "BACKUP_WORK_DIR", default_value = "backup-work")]
+        work_dir: PathBuf,
+    },
+    /// List snapshots, oldest first
+    List,
+    /// Download a snapshot and check every file against its manifest
+    Verify {
+        id: String,
+        #[clap(long, env = "BACKUP_WORK_DIR", default_value = "backup-work")]
+        work_dir: PathBuf,
+    },
+    /// Download and verify a snapshot, then restore the parts given a target.
+    /// Targets are never read from the environment, so a restore only ever
+    /// writes where it was explicitly pointed.
+    Restore {
+        id: String,
+        /// Where the verified files, and the escrow accounts as
+        /// `solana-test-validator --account-dir` files, are written
+        #[clap(long)]
+        to: PathBuf,
+        /// Database to restore the dump into; its tables are replaced
+        #[clap(long)]
+        database_url: Option<String>,
+        /// Freshly installed NFT canister to import the registry into
+        #[clap(long, requires = "identity")]
+        canister_id: Option<Principal>,
+        /// PEM file of a SuperAdmin of that canister
+        #[clap(long)]
+        identity: Option<PathBuf>,
+        #[clap(long, default_value = "https://ic0.app")]
+        ic_url: String,
+        #[clap(long, default_value = "500")]
+        batch_size: usize,
+    },
+}
+
+/// What to back up. Each source is included when configured; at least one
+/// must be.
+#[derive(Args)]
+struct SourceArgs {
+    /// Postgres URL of the indexer database
+    #[clap(long, env = "DATABASE_URL", hide_env_values = true)]
+    database_url: Option<String>,
+    #[clap(long, env = "SOLANA_RPC_URL", default_value = "https://api.devnet.solana.com")]
+    rpc_url: String,
+    #[clap(long, env = "SOLANA_PROGRAM_ID")]
+    program_id: Option<Pubkey>,
+    #[clap(long, env = "IC_URL", default_value = "https://ic0.app")]
+    ic_url: String,
+    #[clap(long, env = "ICP_CANISTER_ID", requires = "identity")]
+    canister_id: Option<Principal>,
+    /// PEM file of a SuperAdmin, which `export_nfts` requires
+    #[clap(long, env = "BACKUP_IDENTITY")]
+    identity: Option<PathBuf>,
+}
+
+impl SourceArgs {
+    async fn connect(self) -> Result<Sources, String> {
+        if self.database_url.is_none() && self.program_id.is_none() && self.canister_id.is_none() {
+            return Err("Nothing to back up: set DATABASE_URL, SOLANA_PROGRAM_ID or ICP_CANISTER_ID".to_string());
+        }
+        let nft = match (self.canister_id, &self.identity) {
+            (Some(canister_id), Some(path)) => Some(connect_nft(&self.ic_url, canister_id, path).await?),
+            _ => None,
+        };
+        let solana = self.program_id.map(|program_id| {
+            (RpcClient::new_with_commitment(self.rpc_url, CommitmentConfig::finalized()), program_id)
+        });
+        Ok(Sources { database_url: self.database_url, nft, solana })
+    }
+}
+
+async fn connect_nft(ic_url: &str, canister_id: Principal, identity: &Path) -> Result<NftClient, String> {
+    let identity = identity::from_pem_file(identity).map_err(|e| e.to_string())?;
+    NftClient::connect(ic_url, identity, canister_id).await.map_err(|e| e.to_string())
+}
+
+fn describe(manifest: &Manifest) -> String {
+    let mut parts = vec![format!("{} bytes", manifest.total_bytes())];
+    if manifest.artifact(manifest::POSTGRES_DUMP).is_some() {
+        parts.push("postgres".to_string());
+    }
+    if let Some(canister) = &manifest.canister {
+        parts.push(format!("{} NFT(s)", canister.nfts));
+    }
+    if let Some(solana) = &manifest.solana {
+        parts.push(format!("{} account(s) at slot {}", solana.accounts, solana.slot));
+    }
+    parts.join(", ")
+}
+
+/// Snapshot, verify the upload by downloading it again, then prune.
+async fn run_once(sources: &Sources, store: &Store, work_dir: &Path, keep: usize) -> Result<Manifest, String> {
+    let manifest = snapshot::take(sources, store, work_dir).await?;
+    let check_dir = work_dir.join(format!("{}-verify", manifest.id));
+    let verified = snapshot::fetch(store, &manifest, &check_dir).await;
+    let _ = std::fs::remove_dir_all(&check_dir);
+    verified?;
+
+    let listing = store.list().await?;
+    for id in manifest::expired(&listing.complete, &listing.incomplete, keep) {
+        store.delete(&id).await?;
+        println!("Pruned snapshot {}", id);
+    }
+    Ok(manifest)
+}
+
+async fn snapshot_until_stopped(
+    sources: &Sources,
+    store: &Store,
+    work_dir: &Path,
+    keep: usize,
+    interval: Duration,
+    metrics: &metrics::Metrics,
+) -> Result<(), String> {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        match run_once(sources, store, work_dir, keep).await {
+            Ok(manifest) => {
+                metrics.runs.inc(&["ok"]);
+                metrics.last_success.store(metrics::now(), Ordering::Relaxed);
+                metrics.last_bytes.store(manifest.total_bytes(), Ordering::Relaxed);
+                println!("Snapshot {}: {}", manifest.id, describe(&manifest));
+            }
+            Err(e) => {
+                metrics.runs.inc(&["failed"]);
+                eprintln!("Snapshot failed: {}", e);
+            }
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run() -> Result<(), String> {
+    let cli = Cli::parse();
+    let store = Store::new(&cli.store)?;
+
+    match cli.command {
+        Command::Run { sources, interval_hours, keep, work_dir, metrics_addr } => {
+            if keep == 0 || interval_hours == 0 {
+                return Err("--keep and --interval-hours must be at least 1".to_string());
+            }
+            let sources = sources.connect().await?;
+            let interval = Duration::from_secs(interval_hours * 3600);
+            let metrics = Arc::new(metrics::Metrics::default());
+            // A missed run is tolerated; two in a row are not.
+            let max_age = 2 * interval.as_secs() + 3600;
+            let (rendered, probed) = (metrics.clone(), metrics.clone());
+            let telemetry = async {
+                proofcart_telemetry::serve(
+                    metrics_addr,
+                    move || rendered.render(),
+                    move || {
+                        let probed = probed.clone();
+                        async move { probed.health(max_age) }
+                    },
+                )
+                .await
+                .map_err(|e| format!("Metrics server on {}: {}", metrics_addr, e))
+            };
+            tokio::try_join!(snapshot_until_stopped(&sources, &store, &work_dir, keep, interval, &metrics), telemetry)?;
+        }
+        Command::Snapshot { sources, work_dir } => {
+            let sources = sources.connect().await?;
+            let manifest = snapshot::take(&sources, &store, &work_dir).await?;
+            println!("Snapshot {}: {}", manifest.id, describe(&manifest));
+        }
+        Command::List => {
+            let listing = store.list().await?;
+            for id in &listing.complete {
+                let manifest = store.manifest(id).await?;
+                println!("{}  {}", id, describe(&manifest));
+            }
+            for id in &listing.incomplete {
+                println!("{}  incomplete", id);
+            }
+        }
+        Command::Verify { id, work_dir } => {
+            let manifest = store.manifest(&id).await?;
+            let dir = work_dir.join(format!("{}-verify", id));
+            let verified = snapshot::fetch(&store, &manifest, &dir).await;
+            let _ = std::fs::remove_dir_all(&dir);
+            verified?;
+            println!("Snapshot {} verified: {}", id, describe(&manifest));
+        }
+        Command::Restore { id, to, database_url, canister_id, identity, ic_url, batch_size } => {
+            if batch_size == 0 || batch_size > restore::MAX_BATCH {
+                return Err(format!("--batch-size must be 1 to {}", restore::MAX_BATCH));
+            }
+            let manifest = store.manifest(&id).await?;
+            // Refuse a target the snapshot cannot serve before writing anything.
+            if database_url.is_some() && manifest.artifact(manifest::POSTGRES_DUMP).is_none() {
+                return Err(format!("Snapshot {} has no database dump", id));
+            }
+            if canister_id.is_some() && manifest.canister.is_none() {
+                return Err(format!("Snapshot {} has no NFT export", id));
+            }
+            snapshot::fetch(&store, &manifest, &to).await?;
+            println!("Snapshot {} downloaded and verified into {}", id, to.display());
+
+            if manifest.solana.is_some() {
+                let written = restore::accounts(&to)?;
+                println!("Wrote {} escrow account(s) to {}", written, to.join("accounts").display());
+            }
+            if let Some(database_url) = &database_url {
+                restore::postgres(&to, database_url).await?;
+                println!("Restored the database");
+            }
+            if let (Some(canister_id), Some(path)) = (canister_id, &identity) {
+                let nft = connect_nft(&ic_url, canister_id, path).await?;
+                let imported = restore::canister(&to, &nft, batch_size).await?;
+                println!("Imported {} NFT(s) into {} and finished the import", imported, canister_id);
+            }
+        }
+    }
+    Ok(())
+}
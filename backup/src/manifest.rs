@@ -0,0 +1,168 @@
+//! What a snapshot holds, and the digests its files are checked against.
+//!
+//! A snapshot lives under `<prefix>/<id>/`. Its `manifest.json` is uploaded
+//! after every other file, so a snapshot without one never finished and is
+//! not restored from.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub const MANIFEST: &str = "manifest.json";
+pub const POSTGRES_DUMP: &str = "postgres.dump";
+pub const NFTS: &str = "nfts.jsonl";
+pub const SOLANA_ACCOUNTS: &str = "solana-accounts.jsonl";
+
+/// Bumped when the layout changes in a way `restore` must know about.
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Manifest {
+    pub version: u32,
+    pub id: String,
+    /// Unix seconds when the snapshot started.
+    pub created_at: u64,
+    pub canister: Option<CanisterSource>,
+    pub solana: Option<SolanaSource>,
+    pub artifacts: Vec<Artifact>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CanisterSource {
+    pub canister_id: String,
+    pub nfts: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SolanaSource {
+    pub program_id: String,
+    /// Slot read just before the accounts; they are at least this recent.
+    pub slot: u64,
+    pub accounts: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Artifact {
+    pub name: String,
+    pub bytes: u64,
+    /// Hex SHA-256 of the file.
+    pub sha256: String,
+}
+
+impl Artifact {
+    /// Describe the file at `path`, stored as `name`.
+    pub fn of_file(name: &str, path: &Path) -> Result<Self, String> {
+        let (bytes, sha256) = sha256_file(path)?;
+        Ok(Self { name: name.to_string(), bytes, sha256 })
+    }
+
+    /// Whether the file at `path` is this artifact, byte for byte.
+    pub fn check(&self, path: &Path) -> Result<(), String> {
+        let (bytes, sha256) = sha256_file(path)?;
+        if bytes != self.bytes || sha256 != self.sha256 {
+            return Err(format!(
+                "{}: expected {} bytes with SHA-256 {}, got {} bytes with {}",
+                self.name, self.bytes, self.sha256, bytes, sha256
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Manifest {
+    pub fn total_bytes(&self) -> u64 {
+        self.artifacts.iter().map(|artifact| artifact.bytes).sum()
+    }
+
+    pub fn artifact(&self, name: &str) -> Option<&Artifact> {
+        self.artifacts.iter().find(|artifact| artifact.name == name)
+    }
+}
+
+/// Size and hex SHA-256 of a file, read in a streaming fashion.
+pub fn sha256_file(path: &Path) -> Result<(u64, String), String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let bytes = io::copy(&mut file, &mut hasher).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok((bytes, hex::encode(hasher.finalize())))
+}
+
+/// Snapshot id for a start time: its UTC time as `20240610T120000Z`, which
+/// sorts the same as the times themselves.
+pub fn snapshot_id(unix_secs: u64) -> String {
+    let days = (unix_secs / 86_400) as i64;
+    let secs = unix_secs % 86_400;
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+}
+
+/// Snapshots to delete so that only the newest `keep` complete ones are
+/// left. Incomplete snapshots older than the newest complete one are
+/// leftovers of failed runs and go too; newer ones may still be uploading.
+pub fn expired(complete: &[String], incomplete: &[String], keep: usize) -> Vec<String> {
+    let mut complete = complete.to_vec();
+    complete.sort_unstable_by(|a, b| b.cmp(a));
+    let mut expired = complete.split_off(keep.min(complete.len()));
+    if let Some(newest) = complete.first() {
+        expired.extend(incomplete.iter().filter(|id| *id < newest).cloned());
+    }
+    expired.sort();
+    expired
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_ids_are_utc_times() {
+        assert_eq!(snapshot_id(0), "19700101T000000Z");
+        assert_eq!(snapshot_id(951_782_400), "20000229T000000Z");
+        assert_eq!(snapshot_id(1_718_020_845), "20240610T120045Z");
+    }
+
+    #[test]
+    fn retention_keeps_newest_complete_snapshots() {
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let complete = ids(&["20240601T000000Z", "20240603T000000Z", "20240602T000000Z"]);
+        let incomplete = ids(&["20240602T060000Z", "20240604T000000Z"]);
+        assert_eq!(
+            expired(&complete, &incomplete, 2),
+            ids(&["20240601T000000Z", "20240602T060000Z"])
+        );
+        // Nothing complete yet: a failed first run is kept for inspection.
+        assert!(expired(&[], &incomplete, 2).is_empty());
+    }
+
+    #[test]
+    fn check_catches_a_changed_file() {
+        let path = std::env::temp_dir().join(format!("proofcart-backup-{}", std::process::id()));
+        std::fs::write(&path, b"COPY escrows").unwrap();
+        let artifact = Artifact::of_file(POSTGRES_DUMP, &path).unwrap();
+        assert_eq!(artifact.bytes, 12);
+        artifact.check(&path).unwrap();
+        std::fs::write(&path, b"COPY escrowz").unwrap();
+        assert!(artifact.check(&path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,60 @@
+//! Snapshot counters served on `GET /metrics`, with a freshness check on
+//! `GET /healthz`, so a backup that quietly stopped is noticed.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use proofcart_telemetry::health::{Check, Report};
+use proofcart_telemetry::{Exposition, Family};
+
+pub struct Metrics {
+    /// Snapshot runs by outcome: `ok` or `failed`.
+    pub runs: Family,
+    /// Unix seconds of the last uploaded and verified snapshot.
+    pub last_success: AtomicU64,
+    pub last_bytes: AtomicU64,
+    /// When the daemon started; stands in for `last_success` until then.
+    started: u64,
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            runs: Family::new(&["outcome"]),
+            last_success: AtomicU64::default(),
+            last_bytes: AtomicU64::default(),
+            started: now(),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn render(&self) -> String {
+        let mut out = Exposition::new();
+        out.family("proofcart_backup_runs_total", "Snapshot runs by outcome: ok or failed.", &self.runs)
+            .gauge(
+                "proofcart_backup_last_success_timestamp_seconds",
+                "Unix time of the last uploaded and verified snapshot; 0 before the first.",
+                self.last_success.load(Ordering::Relaxed),
+            )
+            .gauge(
+                "proofcart_backup_last_snapshot_bytes",
+                "Size of the last successful snapshot.",
+                self.last_bytes.load(Ordering::Relaxed),
+            );
+        out.finish()
+    }
+
+    /// Unhealthy once no snapshot has succeeded for `max_age_secs`.
+    pub fn health(&self, max_age_secs: u64) -> Report {
+        let since = match self.last_success.load(Ordering::Relaxed) {
+            0 => self.started,
+            last => last,
+        };
+        Report::new(vec![Check::lag("snapshot", now().saturating_sub(since), max_age_secs, "seconds")])
+    }
+}
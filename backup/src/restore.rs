@@ -0,0 +1,98 @@
+//! Restoring a verified snapshot.
+//!
+//! Postgres is restored with `pg_restore`, replacing the tables the dump
+//! holds. The NFT registry goes back through `import_nfts`, which only a
+//! freshly installed canister accepts. Escrow accounts are written out as
+//! `solana-test-validator --account-dir` files: the chain itself is the
+//! source of truth for them, so they serve to rebuild a test cluster and to
+//! reconcile, never to overwrite anything.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use proofcart_icp_client::types::ProductNFT;
+use proofcart_icp_client::NftClient;
+use serde::de::DeserializeOwned;
+use solana_client::rpc_response::RpcKeyedAccount;
+
+use crate::manifest;
+
+/// `import_nfts` takes at most this many tokens per call.
+pub const MAX_BATCH: usize = 500;
+
+fn read_lines<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let line = line.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            serde_json::from_str(&line).map_err(|e| format!("{} line {}: {}", path.display(), i + 1, e))
+        })
+        .collect()
+}
+
+pub async fn postgres(dir: &Path, database_url: &str) -> Result<(), String> {
+    let status = tokio::process::Command::new("pg_restore")
+        .args(["--clean", "--if-exists", "--no-owner", "--no-privileges", "--single-transaction", "--dbname"])
+        .arg(database_url)
+        .arg(dir.join(manifest::POSTGRES_DUMP))
+        .status()
+        .await
+        .map_err(|e| format!("Failed to run pg_restore: {}", e))?;
+    if !status.success() {
+        return Err(format!("pg_restore failed: {}", status));
+    }
+    Ok(())
+}
+
+/// Import the registry into a fresh canister, resuming after what an
+/// earlier run imported, then close the import.
+pub async fn canister(dir: &Path, nft: &NftClient, batch_size: usize) -> Result<u64, String> {
+    let mut nfts: Vec<ProductNFT> = read_lines(&dir.join(manifest::NFTS))?;
+    nfts.sort_by_key(|nft| nft.nft_id);
+    let state = nft.get_import_state().await.map_err(|e| e.to_string())?;
+    if state.finished {
+        return Err("The canister's import is already finished; restore into a freshly installed canister".to_string());
+    }
+    // Batches are applied whole and in id order, so the count imported so
+    // far is where to resume.
+    let done = state.imported as usize;
+    if done > nfts.len() {
+        return Err(format!(
+            "The canister has imported {} token(s) but the snapshot has {}; wrong snapshot?",
+            done,
+            nfts.len()
+        ));
+    }
+    if done > 0 {
+        println!("Resuming after {} imported token(s)", done);
+    }
+    for batch in nfts[done..].chunks(batch_size) {
+        let imported = nft.import_nfts(batch).await.map_err(|e| {
+            format!(
+                "Batch of ids {}..={}: {}; re-run to resume",
+                batch[0].nft_id,
+                batch[batch.len() - 1].nft_id,
+                e
+            )
+        })?;
+        println!("Imported {} of {}", imported, nfts.len());
+    }
+    let state = nft.finish_import().await.map_err(|e| e.to_string())?;
+    Ok(state.imported)
+}
+
+/// Write each escrow account to `<dir>/accounts/<address>.json`.
+pub fn accounts(dir: &Path) -> Result<usize, String> {
+    let accounts: Vec<RpcKeyedAccount> = read_lines(&dir.join(manifest::SOLANA_ACCOUNTS))?;
+    let out = dir.join("accounts");
+    std::fs::create_dir_all(&out).map_err(|e| format!("Failed to create {}: {}", out.display(), e))?;
+    for account in &accounts {
+        let path = out.join(format!("{}.json", account.pubkey));
+        let json = serde_json::to_string_pretty(account).map_err(|e| e.to_string())?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(accounts.len())
+}
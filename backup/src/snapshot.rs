@@ -0,0 +1,149 @@
+//! Taking a snapshot: each configured source is written to a file in a
+//! local work directory and hashed, then everything is uploaded and the
+//! manifest last.
+//!
+//! - Postgres: `pg_dump --format=custom` of the whole database, which holds
+//!   the indexer's tables and those of the services sharing it.
+//! - NFT canister: `export_nfts`, one `ProductNFT` as JSON per line, which is
+//!   what `import_nfts` takes back.
+//! - Solana: every account owned by the escrow program, one per line in the
+//!   `solana account --output json` format.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use proofcart_icp_client::NftClient;
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_response::RpcKeyedAccount;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::manifest::{self, Artifact, CanisterSource, Manifest, SolanaSource};
+use crate::store::Store;
+
+/// What to back up; at least one source is set.
+pub struct Sources {
+    pub database_url: Option<String>,
+    pub nft: Option<NftClient>,
+    pub solana: Option<(RpcClient, Pubkey)>,
+}
+
+async fn dump_postgres(database_url: &str, path: &Path) -> Result<(), String> {
+    let status = tokio::process::Command::new("pg_dump")
+        .args(["--format=custom", "--no-owner", "--no-privileges", "--file"])
+        .arg(path)
+        .arg(database_url)
+        .status()
+        .await
+        .map_err(|e| format!("Failed to run pg_dump: {}", e))?;
+    if !status.success() {
+        return Err(format!("pg_dump failed: {}", status));
+    }
+    Ok(())
+}
+
+fn write_lines<T: serde::Serialize>(path: &Path, items: &[T]) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut writer = BufWriter::new(file);
+    for item in items {
+        let line = serde_json::to_string(item).map_err(|e| e.to_string())?;
+        writeln!(writer, "{}", line).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    writer.flush().map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+async fn dump_nfts(nft: &NftClient, path: &Path) -> Result<CanisterSource, String> {
+    let nfts = nft.export_all().await.map_err(|e| format!("export_nfts: {}", e))?;
+    write_lines(path, &nfts)?;
+    Ok(CanisterSource { canister_id: nft.canister_id().to_text(), nfts: nfts.len() as u64 })
+}
+
+async fn dump_accounts(rpc: &RpcClient, program_id: &Pubkey, path: &Path) -> Result<SolanaSource, String> {
+    let slot = rpc.get_slot().await.map_err(|e| e.to_string())?;
+    let config = RpcProgramAccountsConfig {
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(rpc.commitment()),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+    let mut accounts = rpc
+        .get_program_accounts_with_config(program_id, config)
+        .await
+        .map_err(|e| format!("getProgramAccounts: {}", e))?;
+    accounts.sort_by_key(|(address, _)| *address);
+    let keyed: Vec<RpcKeyedAccount> = accounts
+        .iter()
+        .map(|(address, account)| RpcKeyedAccount {
+            pubkey: address.to_string(),
+            account: UiAccount::encode(address, account, UiAccountEncoding::Base64, None, None),
+        })
+        .collect();
+    write_lines(path, &keyed)?;
+    Ok(SolanaSource { program_id: program_id.to_string(), slot, accounts: keyed.len() as u64 })
+}
+
+/// Write every source to `<work_dir>/<id>/` and describe the files.
+async fn collect(sources: &Sources, dir: &Path, id: String, created_at: u64) -> Result<Manifest, String> {
+    let mut manifest =
+        Manifest { version: manifest::VERSION, id, created_at, canister: None, solana: None, artifacts: Vec::new() };
+    if let Some(database_url) = &sources.database_url {
+        let path = dir.join(manifest::POSTGRES_DUMP);
+        dump_postgres(database_url, &path).await?;
+        manifest.artifacts.push(Artifact::of_file(manifest::POSTGRES_DUMP, &path)?);
+    }
+    if let Some(nft) = &sources.nft {
+        let path = dir.join(manifest::NFTS);
+        manifest.canister = Some(dump_nfts(nft, &path).await?);
+        manifest.artifacts.push(Artifact::of_file(manifest::NFTS, &path)?);
+    }
+    if let Some((rpc, program_id)) = &sources.solana {
+        let path = dir.join(manifest::SOLANA_ACCOUNTS);
+        manifest.solana = Some(dump_accounts(rpc, program_id, &path).await?);
+        manifest.artifacts.push(Artifact::of_file(manifest::SOLANA_ACCOUNTS, &path)?);
+    }
+    Ok(manifest)
+}
+
+/// Take one snapshot and upload it. The local copy is removed afterwards,
+/// whether or not the upload succeeded.
+pub async fn take(sources: &Sources, store: &Store, work_dir: &Path) -> Result<Manifest, String> {
+    let created_at = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|e| e.to_string())?.as_secs();
+    let id = manifest::snapshot_id(created_at);
+    let dir: PathBuf = work_dir.join(&id);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let result = async {
+        let manifest = collect(sources, &dir, id, created_at).await?;
+        for artifact in &manifest.artifacts {
+            store.put_file(&manifest.id, &artifact.name, &dir.join(&artifact.name)).await?;
+        }
+        store.put_manifest(&manifest).await?;
+        Ok(manifest)
+    }
+    .await;
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+/// Download every file of a snapshot into `dir` and check it against the
+/// manifest. Nothing downloaded is trusted before this passes.
+pub async fn fetch(store: &Store, manifest: &Manifest, dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let mut failures = Vec::new();
+    for artifact in &manifest.artifacts {
+        let path = dir.join(&artifact.name);
+        store.get_file(&manifest.id, &artifact.name, &path).await?;
+        if let Err(e) = artifact.check(&path) {
+            failures.push(e);
+        }
+    }
+    if !failures.is_empty() {
+        return Err(format!("Snapshot {} failed verification: {}", manifest.id, failures.join("; ")));
+    }
+    Ok(())
+}
@@ -0,0 +1,175 @@
+//! The S3-compatible bucket snapshots are kept in: AWS S3, or any endpoint
+//! speaking its API (MinIO, Cloudflare R2, Backblaze B2, ...).
+
+use std::path::Path;
+
+use clap::Args;
+use s3::creds::Credentials;
+use s3::{Bucket, Region};
+
+use crate::manifest::{self, Manifest};
+
+#[derive(Args)]
+pub struct StoreArgs {
+    #[clap(long, env = "BACKUP_S3_BUCKET")]
+    bucket: String,
+    /// Endpoint of an S3-compatible service; AWS when unset
+    #[clap(long, env = "BACKUP_S3_ENDPOINT")]
+    endpoint: Option<String>,
+    #[clap(long, env = "BACKUP_S3_REGION", default_value = "us-east-1")]
+    region: String,
+    /// Key prefix snapshots are stored under
+    #[clap(long, env = "BACKUP_S3_PREFIX", default_value = "proofcart")]
+    prefix: String,
+    #[clap(long, env = "AWS_ACCESS_KEY_ID", hide_env_values = true)]
+    access_key_id: String,
+    #[clap(long, env = "AWS_SECRET_ACCESS_KEY", hide_env_values = true)]
+    secret_access_key: String,
+}
+
+/// Snapshots in the bucket, by id.
+#[derive(Default)]
+pub struct Listing {
+    pub complete: Vec<String>,
+    /// Ids with files but no manifest: failed, or still uploading.
+    pub incomplete: Vec<String>,
+}
+
+pub struct Store {
+    bucket: Bucket,
+    prefix: String,
+}
+
+/// rust-s3 is built without `fail-on-err`, so statuses are checked here.
+fn check(status: u16, what: &str) -> Result<(), String> {
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(format!("{}: HTTP {}", what, status))
+    }
+}
+
+impl Store {
+    pub fn new(args: &StoreArgs) -> Result<Self, String> {
+        let region = match &args.endpoint {
+            Some(endpoint) => Region::Custom { region: args.region.clone(), endpoint: endpoint.clone() },
+            None => args.region.parse().map_err(|e| format!("--region {}: {}", args.region, e))?,
+        };
+        let credentials =
+            Credentials::new(Some(&args.access_key_id), Some(&args.secret_access_key), None, None, None)
+                .map_err(|e| e.to_string())?;
+        let bucket = Bucket::new(&args.bucket, region, credentials).map_err(|e| e.to_string())?;
+        // Most S3-compatible services do not serve virtual-hosted buckets.
+        let bucket = if args.endpoint.is_some() { bucket.with_path_style() } else { bucket };
+        Ok(Self { bucket, prefix: args.prefix.trim_end_matches('/').to_string() })
+    }
+
+    fn key(&self, id: &str, name: &str) -> String {
+        format!("{}/{}/{}", self.prefix, id, name)
+    }
+
+    pub async fn put_file(&self, id: &str, name: &str, path: &Path) -> Result<(), String> {
+        let key = self.key(id, name);
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let status = self
+            .bucket
+            .put_object_stream(&mut file, &key)
+            .await
+            .map_err(|e| format!("Upload of {}: {}", key, e))?;
+        check(status, &format!("Upload of {}", key))
+    }
+
+    pub async fn get_file(&self, id: &str, name: &str, path: &Path) -> Result<(), String> {
+        let key = self.key(id, name);
+        let mut file = tokio::fs::File::create(path)
+            .await
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let status = self
+            .bucket
+            .get_object_to_writer(&key, &mut file)
+            .await
+            .map_err(|e| format!("Download of {}: {}", key, e))?;
+        check(status, &format!("Download of {}", key))
+    }
+
+    /// Upload the manifest, which marks the snapshot complete.
+    pub async fn put_manifest(&self, manifest: &Manifest) -> Result<(), String> {
+        let key = self.key(&manifest.id, manifest::MANIFEST);
+        let json = serde_json::to_vec_pretty(manifest).map_err(|e| e.to_string())?;
+        let response = self
+            .bucket
+            .put_object_with_content_type(&key, &json, "application/json")
+            .await
+            .map_err(|e| format!("Upload of {}: {}", key, e))?;
+        check(response.status_code(), &format!("Upload of {}", key))
+    }
+
+    pub async fn manifest(&self, id: &str) -> Result<Manifest, String> {
+        let key = self.key(id, manifest::MANIFEST);
+        let response = self.bucket.get_object(&key).await.map_err(|e| format!("Download of {}: {}", key, e))?;
+        match response.status_code() {
+            404 => return Err(format!("Snapshot {} has no manifest; it is incomplete or does not exist", id)),
+            status => check(status, &format!("Download of {}", key))?,
+        }
+        let manifest: Manifest =
+            serde_json::from_slice(response.bytes()).map_err(|e| format!("Invalid {}: {}", key, e))?;
+        if manifest.version > manifest::VERSION {
+            return Err(format!(
+                "Snapshot {} has layout version {}; this build reads up to {}",
+                id,
+                manifest.version,
+                manifest::VERSION
+            ));
+        }
+        Ok(manifest)
+    }
+
+    async fn keys(&self, prefix: String, delimiter: Option<String>) -> Result<(Vec<String>, Vec<String>), String> {
+        let pages = self
+            .bucket
+            .list(prefix.clone(), delimiter)
+            .await
+            .map_err(|e| format!("Listing {}: {}", prefix, e))?;
+        let mut keys = Vec::new();
+        let mut prefixes = Vec::new();
+        for page in pages {
+            keys.extend(page.contents.into_iter().map(|object| object.key));
+            prefixes.extend(page.common_prefixes.unwrap_or_default().into_iter().map(|common| common.prefix));
+        }
+        Ok((keys, prefixes))
+    }
+
+    /// Every snapshot, oldest first.
+    pub async fn list(&self) -> Result<Listing, String> {
+        let root = format!("{}/", self.prefix);
+        let (_, prefixes) = self.keys(root.clone(), Some("/".to_string())).await?;
+        let mut listing = Listing::default();
+        for prefix in prefixes {
+            let id = prefix.trim_start_matches(&root).trim_end_matches('/').to_string();
+            let (keys, _) = self.keys(prefix, None).await?;
+            if keys.contains(&self.key(&id, manifest::MANIFEST)) {
+                listing.complete.push(id);
+            } else {
+                listing.incomplete.push(id);
+            }
+        }
+        listing.complete.sort();
+        listing.incomplete.sort();
+        Ok(listing)
+    }
+
+    /// Remove a snapshot, its manifest first so that it stops counting as
+    /// complete even if the rest fails.
+    pub async fn delete(&self, id: &str) -> Result<(), String> {
+        let manifest_key = self.key(id, manifest::MANIFEST);
+        let (mut keys, _) = self.keys(format!("{}/{}/", self.prefix, id), None).await?;
+        keys.sort_by_key(|key| *key != manifest_key);
+        for key in keys {
+            let response = self.bucket.delete_object(&key).await.map_err(|e| format!("Delete of {}: {}", key, e))?;
+            check(response.status_code(), &format!("Delete of {}", key))?;
+        }
+        Ok(())
+    }
+}
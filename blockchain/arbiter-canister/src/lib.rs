@@ -0,0 +1,483 @@
+//! Arbiter for the Solana escrow program.
+//!
+//! The escrow program's `Config.admin` is an Ed25519 key held by this
+//! canister through the IC's threshold Schnorr API, so no one holds the
+//! private key. Arbiters vote on disputed orders; once `threshold` of them
+//! agree, the canister signs the matching `resolve_refund` or
+//! `resolve_release` transaction, which anyone can then submit.
+//!
+//! Only the platform's own escrows have cases here: a partner marketplace's
+//! escrows are resolved by the marketplace's admin, whose key this canister
+//! does not hold.
+
+mod solana;
+
+use candid::{CandidType, Decode, Encode, Principal};
+use ic_cdk::api::call::call_with_payment128;
+use ic_cdk::caller;
+use ic_cdk_macros::{init, query, update};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell, Storable};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+/// Derivation path of the arbiter key under the canister's threshold key.
+const DERIVATION_PATH: &[u8] = b"escrow-arbiter";
+
+/// Cycles attached to `sign_with_schnorr`, enough for `key_1` on the
+/// fiduciary subnet. Unused cycles are refunded.
+const DEFAULT_SIGN_CYCLES: u128 = 26_153_846_153;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// Return the funds to the buyer (`resolve_refund`).
+    Refund,
+    /// Pay the seller (`resolve_release`).
+    Release,
+}
+
+impl Resolution {
+    fn instruction(self) -> &'static str {
+        match self {
+            Resolution::Refund => "resolve_refund",
+            Resolution::Release => "resolve_release",
+        }
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Vote {
+    pub arbiter: Principal,
+    pub resolution: Resolution,
+    pub voted_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Case {
+    pub order_id: String,
+    /// Base58 Solana addresses, as recorded in the escrow.
+    pub buyer: String,
+    pub seller: String,
+    pub opened_by: Principal,
+    pub opened_at: u64,
+    pub votes: Vec<Vote>,
+    /// Set once `threshold` votes agree; never changes afterwards.
+    pub decision: Option<Resolution>,
+    pub decided_at: Option<u64>,
+    /// Base58 signature of the last resolution transaction signed.
+    pub last_signature: Option<String>,
+}
+
+impl Storable for Case {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).expect("Failed to encode case"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).expect("Failed to decode case")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Config {
+    pub admin: Principal,
+    pub arbiters: Vec<Principal>,
+    /// Agreeing votes needed to decide a case.
+    pub threshold: u32,
+    /// Threshold Schnorr key: `dfx_test_key` locally, `test_key_1` or `key_1`
+    /// on mainnet.
+    pub key_name: String,
+    /// Base58 id of the escrow program.
+    pub program_id: String,
+    pub sign_cycles: u128,
+    /// The arbiter's Ed25519 public key, once fetched.
+    pub public_key: Option<Vec<u8>>,
+}
+
+impl Storable for Config {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).expect("Failed to encode config"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).expect("Failed to decode config")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct InitArgs {
+    pub arbiters: Vec<Principal>,
+    pub threshold: u32,
+    pub key_name: String,
+    pub program_id: String,
+    pub sign_cycles: Option<u128>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct OpenCaseRequest {
+    pub order_id: String,
+    pub buyer: String,
+    pub seller: String,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SignedResolution {
+    pub order_id: String,
+    pub resolution: Resolution,
+    /// Wire bytes of the signed transaction, ready for `sendTransaction`
+    /// (base64-encode them for JSON-RPC).
+    pub transaction: Vec<u8>,
+    /// Base58 transaction signature.
+    pub signature: String,
+}
+
+// Management canister types for threshold Schnorr.
+
+#[derive(CandidType, Deserialize, Clone, Copy)]
+enum SchnorrAlgorithm {
+    #[serde(rename = "ed25519")]
+    Ed25519,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+struct SchnorrKeyId {
+    algorithm: SchnorrAlgorithm,
+    name: String,
+}
+
+#[derive(CandidType, Deserialize)]
+struct SchnorrPublicKeyArgs {
+    canister_id: Option<Principal>,
+    derivation_path: Vec<Vec<u8>>,
+    key_id: SchnorrKeyId,
+}
+
+#[derive(CandidType, Deserialize)]
+struct SchnorrPublicKeyResult {
+    public_key: Vec<u8>,
+    chain_code: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct SignWithSchnorrArgs {
+    message: Vec<u8>,
+    derivation_path: Vec<Vec<u8>>,
+    key_id: SchnorrKeyId,
+}
+
+#[derive(CandidType, Deserialize)]
+struct SignWithSchnorrResult {
+    signature: Vec<u8>,
+}
+
+thread_local! {
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+    static CONFIG: RefCell<StableCell<Config, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))),
+            Config {
+                admin: Principal::anonymous(),
+                arbiters: vec![],
+                threshold: 1,
+                key_name: String::new(),
+                program_id: String::new(),
+                sign_cycles: DEFAULT_SIGN_CYCLES,
+                public_key: None,
+            },
+        ).expect("Failed to initialize config")
+    );
+
+    static CASES: RefCell<StableBTreeMap<String, Case, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))),
+        )
+    );
+}
+
+fn check_threshold(arbiters: &[Principal], threshold: u32) -> Result<(), String> {
+    if threshold == 0 || threshold as usize > arbiters.len() {
+        return Err(format!("Threshold must be 1-{} for {} arbiters", arbiters.len(), arbiters.len()));
+    }
+    Ok(())
+}
+
+#[init]
+fn init(args: InitArgs) {
+    let mut arbiters = args.arbiters;
+    arbiters.sort();
+    arbiters.dedup();
+    if let Err(e) = check_threshold(&arbiters, args.threshold) {
+        ic_cdk::trap(&e);
+    }
+    if let Err(e) = solana::decode_pubkey(&args.program_id) {
+        ic_cdk::trap(&e);
+    }
+    set_config(Config {
+        admin: caller(),
+        arbiters,
+        threshold: args.threshold,
+        key_name: args.key_name,
+        program_id: args.program_id,
+        sign_cycles: args.sign_cycles.unwrap_or(DEFAULT_SIGN_CYCLES),
+        public_key: None,
+    });
+}
+
+fn config() -> Config {
+    CONFIG.with(|c| c.borrow().get().clone())
+}
+
+fn set_config(config: Config) {
+    CONFIG.with(|c| {
+        c.borrow_mut().set(config).expect("Failed to persist config");
+    });
+}
+
+fn require_admin() -> Result<(), String> {
+    if config().admin == caller() {
+        Ok(())
+    } else {
+        Err("Only admin can perform this action".to_string())
+    }
+}
+
+fn require_arbiter() -> Result<(), String> {
+    if config().arbiters.contains(&caller()) {
+        Ok(())
+    } else {
+        Err("Only an arbiter can perform this action".to_string())
+    }
+}
+
+fn get_case_inner(order_id: &str) -> Result<Case, String> {
+    CASES.with(|cases| {
+        cases.borrow().get(&order_id.to_string())
+            .ok_or_else(|| format!("No case for order {}", order_id))
+    })
+}
+
+fn save_case(case: Case) -> Case {
+    CASES.with(|cases| {
+        cases.borrow_mut().insert(case.order_id.clone(), case.clone());
+    });
+    case
+}
+
+fn key_id(config: &Config) -> SchnorrKeyId {
+    SchnorrKeyId {
+        algorithm: SchnorrAlgorithm::Ed25519,
+        name: config.key_name.clone(),
+    }
+}
+
+/// The arbiter's public key, fetched from the management canister once.
+async fn public_key() -> Result<solana::Pubkey, String> {
+    let key = match config().public_key {
+        Some(key) => key,
+        None => {
+            let args = SchnorrPublicKeyArgs {
+                canister_id: None,
+                derivation_path: vec![DERIVATION_PATH.to_vec()],
+                key_id: key_id(&config()),
+            };
+            let (result,): (SchnorrPublicKeyResult,) =
+                ic_cdk::call(Principal::management_canister(), "schnorr_public_key", (args,))
+                    .await
+                    .map_err(|(code, msg)| format!("schnorr_public_key failed: {:?} {}", code, msg))?;
+            let mut config = config();
+            config.public_key = Some(result.public_key.clone());
+            set_config(config);
+            result.public_key
+        }
+    };
+    key.try_into()
+        .map_err(|_| "The threshold key is not an Ed25519 key".to_string())
+}
+
+async fn sign(message: Vec<u8>) -> Result<[u8; 64], String> {
+    let config = config();
+    let args = SignWithSchnorrArgs {
+        message,
+        derivation_path: vec![DERIVATION_PATH.to_vec()],
+        key_id: key_id(&config),
+    };
+    let (result,): (SignWithSchnorrResult,) = call_with_payment128(
+        Principal::management_canister(),
+        "sign_with_schnorr",
+        (args,),
+        config.sign_cycles,
+    )
+    .await
+    .map_err(|(code, msg)| format!("sign_with_schnorr failed: {:?} {}", code, msg))?;
+    result
+        .signature
+        .try_into()
+        .map_err(|_| "The threshold signature is not 64 bytes".to_string())
+}
+
+/// The arbiter's base58 Solana address. Set it as the escrow program's admin
+/// with `initialize_config`, and fund it: it pays the resolution fees.
+#[update]
+async fn arbiter_address() -> Result<String, String> {
+    Ok(solana::encode_pubkey(&public_key().await?))
+}
+
+/// Arbiter: open a case for a disputed escrow order
+#[update]
+fn open_case(request: OpenCaseRequest) -> Result<Case, String> {
+    require_arbiter()?;
+    if request.order_id.is_empty() || request.order_id.len() > solana::MAX_SEED_LEN {
+        return Err(format!("Order id must be 1-{} bytes", solana::MAX_SEED_LEN));
+    }
+    solana::decode_pubkey(&request.buyer)?;
+    solana::decode_pubkey(&request.seller)?;
+    if get_case_inner(&request.order_id).is_ok() {
+        return Err(format!("A case for order {} is already open", request.order_id));
+    }
+    Ok(save_case(Case {
+        order_id: request.order_id,
+        buyer: request.buyer,
+        seller: request.seller,
+        opened_by: caller(),
+        opened_at: ic_cdk::api::time(),
+        votes: vec![],
+        decision: None,
+        decided_at: None,
+        last_signature: None,
+    }))
+}
+
+/// Arbiter: vote on a case, replacing any earlier vote. The case is decided
+/// as soon as `threshold` votes agree
+#[update]
+fn vote(order_id: String, resolution: Resolution) -> Result<Case, String> {
+    require_arbiter()?;
+    let mut case = get_case_inner(&order_id)?;
+    if case.decision.is_some() {
+        return Err(format!("The case for order {} is already decided", order_id));
+    }
+    let arbiter = caller();
+    let now = ic_cdk::api::time();
+    case.votes.retain(|vote| vote.arbiter != arbiter);
+    case.votes.push(Vote { arbiter, resolution, voted_at: now });
+
+    // Votes of removed arbiters no longer count.
+    let config = config();
+    let agreeing = case
+        .votes
+        .iter()
+        .filter(|vote| vote.resolution == resolution && config.arbiters.contains(&vote.arbiter))
+        .count();
+    if agreeing >= config.threshold as usize {
+        case.decision = Some(resolution);
+        case.decided_at = Some(now);
+    }
+    Ok(save_case(case))
+}
+
+/// Arbiter: sign the resolution transaction of a decided case.
+/// `recent_blockhash` (base58) comes from the caller's RPC node; a
+/// transaction whose blockhash expires can be signed again
+#[update]
+async fn sign_resolution(order_id: String, recent_blockhash: String) -> Result<SignedResolution, String> {
+    require_arbiter()?;
+    let case = get_case_inner(&order_id)?;
+    let resolution = case
+        .decision
+        .ok_or_else(|| format!("The case for order {} is not decided", order_id))?;
+    let blockhash = solana::decode_pubkey(&recent_blockhash)
+        .map_err(|_| format!("Invalid blockhash {}", recent_blockhash))?;
+
+    let config = config();
+    let program_id = solana::decode_pubkey(&config.program_id)?;
+    // The platform's escrow; see the module docs for partner marketplaces.
+    let (escrow, _) = solana::find_program_address(&[solana::ESCROW_SEED, order_id.as_bytes()], &program_id)
+        .ok_or_else(|| "No escrow address for this order".to_string())?;
+    let (program_config, _) = solana::find_program_address(&[solana::CONFIG_SEED], &program_id)
+        .ok_or_else(|| "No config address for the program".to_string())?;
+    let arbiter = public_key().await?;
+
+    // Accounts in the order of the program's `ResolveDispute`.
+    let meta = |pubkey, is_signer, is_writable| solana::AccountMeta { pubkey, is_signer, is_writable };
+    let instruction = solana::Instruction {
+        program_id,
+        accounts: vec![
+            meta(escrow, false, true),
+            meta(arbiter, true, true),
+            meta(solana::decode_pubkey(&case.buyer)?, false, true),
+            meta(solana::decode_pubkey(&case.seller)?, false, true),
+            meta(escrow, false, true),
+            meta(solana::SYSTEM_PROGRAM, false, false),
+            meta(program_config, false, false),
+        ],
+        data: solana::discriminator(resolution.instruction()).to_vec(),
+    };
+    let message = solana::compile_message(&arbiter, &instruction, &blockhash);
+    let signature = sign(message.clone()).await?;
+
+    let mut case = get_case_inner(&order_id)?;
+    case.last_signature = Some(bs58::encode(signature).into_string());
+    save_case(case);
+
+    Ok(SignedResolution {
+        order_id,
+        resolution,
+        transaction: solana::signed_transaction(&signature, &message),
+        signature: bs58::encode(signature).into_string(),
+    })
+}
+
+/// Get a case by order id
+#[query]
+fn get_case(order_id: String) -> Result<Case, String> {
+    get_case_inner(&order_id)
+}
+
+/// Cases in order id order, optionally only undecided ones
+#[query]
+fn list_cases(undecided_only: bool, offset: u64, limit: u64) -> Vec<Case> {
+    CASES.with(|cases| {
+        cases.borrow()
+            .iter()
+            .map(|(_, case)| case)
+            .filter(|case| !undecided_only || case.decision.is_none())
+            .skip(offset as usize)
+            .take(limit.min(100) as usize)
+            .collect()
+    })
+}
+
+/// Admin: replace the arbiter set and threshold. Votes already cast by
+/// removed arbiters stop counting
+#[update]
+fn set_arbiters(arbiters: Vec<Principal>, threshold: u32) -> Result<Config, String> {
+    require_admin()?;
+    let mut arbiters = arbiters;
+    arbiters.sort();
+    arbiters.dedup();
+    check_threshold(&arbiters, threshold)?;
+    let mut config = config();
+    config.arbiters = arbiters;
+    config.threshold = threshold;
+    set_config(config.clone());
+    Ok(config)
+}
+
+/// Current configuration
+#[query]
+fn get_config() -> Config {
+    config()
+}
+
+ic_cdk::export_candid!();
+
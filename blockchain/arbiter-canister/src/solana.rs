@@ -0,0 +1,191 @@
+//! Just enough of Solana to build the escrow program's `resolve_*`
+//! transaction: addresses, program-derived addresses and the legacy
+//! transaction wire format.
+
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use sha2::{Digest, Sha256};
+
+pub type Pubkey = [u8; 32];
+
+/// The system program, `11111111111111111111111111111111`.
+pub const SYSTEM_PROGRAM: Pubkey = [0; 32];
+
+/// Seed prefix of the escrow PDA (`["escrow", order_id]`).
+pub const ESCROW_SEED: &[u8] = b"escrow";
+/// Only seed of the escrow program's `Config` PDA.
+pub const CONFIG_SEED: &[u8] = b"config";
+/// Longest seed the runtime accepts, which bounds order ids.
+pub const MAX_SEED_LEN: usize = 32;
+
+pub fn decode_pubkey(address: &str) -> Result<Pubkey, String> {
+    let bytes = bs58::decode(address)
+        .into_vec()
+        .map_err(|e| format!("Invalid Solana address {}: {}", address, e))?;
+    bytes
+        .try_into()
+        .map_err(|_| format!("Invalid Solana address {}: not 32 bytes", address))
+}
+
+pub fn encode_pubkey(key: &Pubkey) -> String {
+    bs58::encode(key).into_string()
+}
+
+/// `Pubkey::find_program_address`: the first bump, counting down from 255,
+/// whose derived address is off the Ed25519 curve.
+pub fn find_program_address(seeds: &[&[u8]], program_id: &Pubkey) -> Option<(Pubkey, u8)> {
+    (0..=u8::MAX).rev().find_map(|bump| {
+        let mut hasher = Sha256::new();
+        for seed in seeds {
+            hasher.update(seed);
+        }
+        hasher.update([bump]);
+        hasher.update(program_id);
+        hasher.update(b"ProgramDerivedAddress");
+        let address: Pubkey = hasher.finalize().into();
+        let on_curve = CompressedEdwardsY(address).decompress().is_some();
+        (!on_curve).then_some((address, bump))
+    })
+}
+
+/// Anchor instruction discriminator: the first 8 bytes of
+/// `sha256("global:<name>")`.
+pub fn discriminator(name: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("global:{}", name));
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash[..8]);
+    discriminator
+}
+
+pub struct AccountMeta {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+pub struct Instruction {
+    pub program_id: Pubkey,
+    pub accounts: Vec<AccountMeta>,
+    pub data: Vec<u8>,
+}
+
+/// Solana's compact-u16 length prefix.
+fn push_compact_u16(out: &mut Vec<u8>, mut n: u16) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Serialized legacy message of a transaction with one instruction, paid for
+/// by `payer`. Keys are ordered as the runtime requires: writable signers,
+/// read-only signers, writable non-signers, read-only non-signers.
+pub fn compile_message(payer: &Pubkey, instruction: &Instruction, recent_blockhash: &[u8; 32]) -> Vec<u8> {
+    // (key, is_signer, is_writable), payer first and each key once.
+    let mut keys: Vec<(Pubkey, bool, bool)> = vec![(*payer, true, true)];
+    let metas = instruction
+        .accounts
+        .iter()
+        .map(|meta| (meta.pubkey, meta.is_signer, meta.is_writable))
+        .chain([(instruction.program_id, false, false)]);
+    for (pubkey, is_signer, is_writable) in metas {
+        match keys.iter_mut().find(|(key, _, _)| *key == pubkey) {
+            Some(entry) => {
+                entry.1 |= is_signer;
+                entry.2 |= is_writable;
+            }
+            None => keys.push((pubkey, is_signer, is_writable)),
+        }
+    }
+    // Stable, so the payer stays first among the writable signers.
+    keys.sort_by_key(|(_, is_signer, is_writable)| (!is_signer, !is_writable));
+
+    let signers = keys.iter().filter(|(_, is_signer, _)| *is_signer).count();
+    let readonly_signed = keys.iter().filter(|(_, s, w)| *s && !*w).count();
+    let readonly_unsigned = keys.iter().filter(|(_, s, w)| !*s && !*w).count();
+    let index = |pubkey: &Pubkey| keys.iter().position(|(key, _, _)| key == pubkey).unwrap() as u8;
+
+    let mut out = vec![signers as u8, readonly_signed as u8, readonly_unsigned as u8];
+    push_compact_u16(&mut out, keys.len() as u16);
+    for (key, _, _) in &keys {
+        out.extend_from_slice(key);
+    }
+    out.extend_from_slice(recent_blockhash);
+    push_compact_u16(&mut out, 1);
+    out.push(index(&instruction.program_id));
+    push_compact_u16(&mut out, instruction.accounts.len() as u16);
+    for meta in &instruction.accounts {
+        out.push(index(&meta.pubkey));
+    }
+    push_compact_u16(&mut out, instruction.data.len() as u16);
+    out.extend_from_slice(&instruction.data);
+    out
+}
+
+/// Wire bytes of a transaction signed by its one signer.
+pub fn signed_transaction(signature: &[u8; 64], message: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(1 + 64 + message.len());
+    push_compact_u16(&mut out, 1);
+    out.extend_from_slice(signature);
+    out.extend_from_slice(message);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_u16_matches_the_runtime() {
+        for (n, expected) in [(0u16, vec![0x00]), (0x7f, vec![0x7f]), (0x80, vec![0x80, 0x01]), (0x3fff, vec![0xff, 0x7f])] {
+            let mut out = Vec::new();
+            push_compact_u16(&mut out, n);
+            assert_eq!(out, expected);
+        }
+    }
+
+    #[test]
+    fn system_program_address() {
+        assert_eq!(encode_pubkey(&SYSTEM_PROGRAM), "11111111111111111111111111111111");
+        assert_eq!(decode_pubkey("11111111111111111111111111111111"), Ok(SYSTEM_PROGRAM));
+    }
+
+    #[test]
+    fn program_addresses_are_off_curve() {
+        let (address, bump) = find_program_address(&[ESCROW_SEED, b"ORD-1"], &[7; 32]).unwrap();
+        assert!(CompressedEdwardsY(address).decompress().is_none());
+        assert_eq!(find_program_address(&[ESCROW_SEED, b"ORD-1"], &[7; 32]), Some((address, bump)));
+    }
+
+    #[test]
+    fn message_orders_and_dedups_keys() {
+        let payer = [1; 32];
+        let escrow = [2; 32];
+        let program = [9; 32];
+        let instruction = Instruction {
+            program_id: program,
+            accounts: vec![
+                AccountMeta { pubkey: escrow, is_signer: false, is_writable: true },
+                AccountMeta { pubkey: payer, is_signer: true, is_writable: true },
+                AccountMeta { pubkey: escrow, is_signer: false, is_writable: true },
+                AccountMeta { pubkey: SYSTEM_PROGRAM, is_signer: false, is_writable: false },
+            ],
+            data: vec![0xaa; 8],
+        };
+        let message = compile_message(&payer, &instruction, &[5; 32]);
+
+        assert_eq!(&message[..4], &[1, 0, 2, 4]);
+        assert_eq!(&message[4..36], &payer);
+        assert_eq!(&message[36..68], &escrow);
+        assert_eq!(&message[68..100], &SYSTEM_PROGRAM);
+        assert_eq!(&message[100..132], &program);
+        assert_eq!(&message[132..164], &[5; 32]);
+        // One instruction: program 3, accounts [1, 0, 1, 2], 8 bytes of data.
+        assert_eq!(&message[164..172], &[1, 3, 4, 1, 0, 1, 2, 8]);
+        assert_eq!(&message[172..], &[0xaa; 8]);
+    }
+}
@@ -0,0 +1,126 @@
+//! PocketIC harness for the ProofCart canisters.
+//!
+//! Tests install the release wasm that `cargo build` produced for each
+//! canister, so build those first (see the README). Set
+//! `PROOFCART_NFT_WASM` or `PROOFCART_REGISTRY_WASM` to test another build.
+
+use std::path::PathBuf;
+
+use candid::utils::ArgumentEncoder;
+use candid::{CandidType, Principal};
+use pocket_ic::{PocketIc, WasmResult};
+use serde::de::DeserializeOwned;
+
+/// Cycles given to every installed canister, enough for the NFT canister's
+/// low-cycles guard.
+pub const CANISTER_CYCLES: u128 = 100_000_000_000_000;
+
+/// The wasm of one of the canisters under `blockchain/`: `$env_var` if set,
+/// otherwise the crate's release build.
+pub fn wasm(env_var: &str, crate_dir: &str, file_name: &str) -> Vec<u8> {
+    let path = std::env::var_os(env_var).map(PathBuf::from).unwrap_or_else(|| {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join(crate_dir)
+            .join("target/wasm32-unknown-unknown/release")
+            .join(file_name)
+    });
+    std::fs::read(&path).unwrap_or_else(|e| {
+        panic!(
+            "Failed to read {} ({}); build it with `cargo build --release --target wasm32-unknown-unknown` in blockchain/{} or set {}",
+            path.display(),
+            e,
+            crate_dir,
+            env_var
+        )
+    })
+}
+
+pub fn nft_wasm() -> Vec<u8> {
+    wasm("PROOFCART_NFT_WASM", "icp-nft", "proofcart_nft.wasm")
+}
+
+pub fn registry_wasm() -> Vec<u8> {
+    wasm("PROOFCART_REGISTRY_WASM", "registry-canister", "proofcart_registry.wasm")
+}
+
+/// A distinct, non-anonymous test principal.
+pub fn user(n: u8) -> Principal {
+    Principal::from_slice(&[0xAB, n, 0x01])
+}
+
+/// One installed canister on its own PocketIC instance.
+pub struct Canister {
+    pub pic: PocketIc,
+    pub id: Principal,
+    /// Installed the code, so it is the controller and the principal the
+    /// canister's `init` sees as its caller.
+    pub controller: Principal,
+}
+
+impl Canister {
+    pub fn install(wasm: Vec<u8>, arg: Vec<u8>) -> Self {
+        Self::install_on(PocketIc::new(), wasm, arg)
+    }
+
+    pub fn install_on(pic: PocketIc, wasm: Vec<u8>, arg: Vec<u8>) -> Self {
+        let controller = user(0);
+        let id = pic.create_canister_with_settings(Some(controller), None);
+        pic.add_cycles(id, CANISTER_CYCLES);
+        pic.install_canister(id, wasm, arg, Some(controller));
+        Self { pic, id, controller }
+    }
+
+    pub fn upgrade(&self, wasm: Vec<u8>, arg: Vec<u8>) -> Result<(), String> {
+        self.pic
+            .upgrade_canister(self.id, wasm, arg, Some(self.controller))
+            .map_err(|e| format!("{:?}", e))
+    }
+
+    /// Raw reply of an update call, so callers can compare replies byte for
+    /// byte.
+    pub fn update_raw<A: ArgumentEncoder>(&self, sender: Principal, method: &str, args: A) -> Vec<u8> {
+        let payload = candid::encode_args(args).expect("Failed to encode arguments");
+        reply(method, self.pic.update_call(self.id, sender, method, payload))
+    }
+
+    pub fn query_raw<A: ArgumentEncoder>(&self, sender: Principal, method: &str, args: A) -> Vec<u8> {
+        let payload = candid::encode_args(args).expect("Failed to encode arguments");
+        reply(method, self.pic.query_call(self.id, sender, method, payload))
+    }
+
+    pub fn update<A: ArgumentEncoder, R: CandidType + DeserializeOwned>(
+        &self,
+        sender: Principal,
+        method: &str,
+        args: A,
+    ) -> R {
+        decode(method, &self.update_raw(sender, method, args))
+    }
+
+    pub fn query<A: ArgumentEncoder, R: CandidType + DeserializeOwned>(
+        &self,
+        sender: Principal,
+        method: &str,
+        args: A,
+    ) -> R {
+        decode(method, &self.query_raw(sender, method, args))
+    }
+
+    /// The candid interface compiled into the wasm.
+    pub fn candid_interface(&self) -> String {
+        self.query(Principal::anonymous(), "__get_candid_interface_tmp_hack", ())
+    }
+}
+
+fn reply<E: std::fmt::Debug>(method: &str, result: Result<WasmResult, E>) -> Vec<u8> {
+    match result {
+        Ok(WasmResult::Reply(bytes)) => bytes,
+        Ok(WasmResult::Reject(message)) => panic!("{} rejected: {}", method, message),
+        Err(e) => panic!("{} failed: {:?}", method, e),
+    }
+}
+
+fn decode<R: CandidType + DeserializeOwned>(method: &str, bytes: &[u8]) -> R {
+    candid::decode_one(bytes).unwrap_or_else(|e| panic!("Failed to decode {} reply: {}", method, e))
+}
@@ -0,0 +1,138 @@
+//! Mint, transfer, verify and upgrade scenarios against the NFT canister
+//! wasm.
+
+use candid::{Encode, Principal};
+use candid_parser::utils::{service_compatible, service_equal, CandidSource};
+use proofcart_canister_tests::{nft_wasm, user, Canister};
+use proofcart_icp_client::types::{ApiVersion, MintRequest, ProductNFT, Role, SalePrice, TransactionType};
+use std::path::PathBuf;
+
+fn install() -> Canister {
+    Canister::install(nft_wasm(), Encode!().unwrap())
+}
+
+fn mint_request(serial_number: &str) -> MintRequest {
+    MintRequest {
+        serial_number: serial_number.to_string(),
+        product_name: "Redmi Note 14 Pro".to_string(),
+        manufacturer: "Xiaomi".to_string(),
+        manufacture_date: "2025-01-15".to_string(),
+        category: "Electronics".to_string(),
+        description: "256GB, Midnight Black".to_string(),
+        specifications: "{}".to_string(),
+        warranty_info: "12 months".to_string(),
+        ipfs_metadata_uri: "ipfs://bafy-test".to_string(),
+        ..Default::default()
+    }
+}
+
+fn mint(canister: &Canister, minter: Principal, serial_number: &str) -> Result<ProductNFT, String> {
+    canister.update(minter, "mint_product_nft", (mint_request(serial_number),))
+}
+
+fn transfer(
+    canister: &Canister,
+    owner: Principal,
+    nft_id: u64,
+    new_owner: Principal,
+    price: Option<SalePrice>,
+) -> Result<ProductNFT, String> {
+    let reason = price.as_ref().map(|_| TransactionType::Sale);
+    canister.update(owner, "transfer_nft", (nft_id, new_owner, reason, None::<String>, price))
+}
+
+fn verify(canister: &Canister, serial_number: &str) -> Result<ProductNFT, String> {
+    canister.query(Principal::anonymous(), "verify_product", (serial_number,))
+}
+
+fn total_supply(canister: &Canister) -> u64 {
+    canister.query(Principal::anonymous(), "get_total_supply", ())
+}
+
+#[test]
+fn mint_transfer_and_verify() {
+    let canister = install();
+    let manufacturer = user(1);
+    let buyer = user(2);
+
+    let nft = mint(&canister, manufacturer, "SN-1001").unwrap();
+    assert_eq!(nft.owner, manufacturer);
+    assert_eq!(nft.ownership_history.len(), 1);
+    assert_eq!(nft.ownership_history[0].transaction_type, TransactionType::Mint);
+
+    // Serials are normalized on the way in and on lookup.
+    let verified = verify(&canister, "sn 1001").unwrap();
+    assert_eq!(verified.nft_id, nft.nft_id);
+    assert_eq!(verified.metadata.product_name, "Redmi Note 14 Pro");
+    assert!(mint(&canister, manufacturer, "SN-1001").is_err());
+
+    let price = SalePrice { amount: 250_000_000, currency: "SOL".to_string(), order_id: None };
+    let sold = transfer(&canister, manufacturer, nft.nft_id, buyer, Some(price.clone())).unwrap();
+    assert_eq!(sold.owner, buyer);
+    let sale = sold.ownership_history.last().unwrap();
+    assert_eq!(sale.owner, buyer);
+    assert_eq!(sale.transaction_type, TransactionType::Sale);
+    assert_eq!(sale.price, Some(price));
+
+    // Only the owner can transfer.
+    assert!(transfer(&canister, manufacturer, nft.nft_id, manufacturer, None).is_err());
+
+    let owned: Vec<ProductNFT> = canister.query(buyer, "get_nfts_by_owner", (buyer,));
+    assert_eq!(owned.iter().map(|nft| nft.nft_id).collect::<Vec<_>>(), vec![nft.nft_id]);
+    assert_eq!(verify(&canister, "SN-1001").unwrap().owner, buyer);
+    assert!(verify(&canister, "SN-404").is_err());
+}
+
+/// Upgrade regression: tokens, the serial index and roles must survive, and
+/// the id counter, which lives on the heap, must resume after the highest id
+/// instead of reusing ids.
+#[test]
+fn upgrades_keep_the_registry() {
+    let canister = install();
+    let manufacturer = user(1);
+    let buyer = user(2);
+    let ids: Vec<u64> = ["SN-1", "SN-2", "SN-3"]
+        .iter()
+        .map(|serial| mint(&canister, manufacturer, serial).unwrap().nft_id)
+        .collect();
+    transfer(&canister, manufacturer, ids[1], buyer, None).unwrap();
+
+    let snapshot = |canister: &Canister| -> Vec<Vec<u8>> {
+        ids.iter()
+            .map(|id| canister.query_raw(Principal::anonymous(), "get_nft", (*id,)))
+            .collect()
+    };
+    let before = snapshot(&canister);
+
+    for _ in 0..2 {
+        canister.upgrade(nft_wasm(), Encode!().unwrap()).unwrap();
+        assert_eq!(snapshot(&canister), before);
+        assert_eq!(total_supply(&canister), 3);
+    }
+
+    assert_eq!(verify(&canister, "SN-2").unwrap().owner, buyer);
+    assert!(mint(&canister, manufacturer, "SN-3").is_err());
+    let next = mint(&canister, manufacturer, "SN-4").unwrap();
+    assert!(ids.iter().all(|id| next.nft_id > *id));
+    assert_eq!(verify(&canister, "SN-1").unwrap().nft_id, ids[0]);
+    assert_eq!(total_supply(&canister), 4);
+
+    // Roles are stable too: the installer is still the SuperAdmin.
+    let roles: Vec<Role> = canister.query(canister.controller, "get_roles", (canister.controller,));
+    assert_eq!(roles, vec![Role::SuperAdmin]);
+}
+
+#[test]
+fn deployed_interface_matches_committed_did() {
+    let canister = install();
+    let deployed = canister.candid_interface();
+    let committed = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../icp-nft/src/proofcart_nft.did");
+
+    service_compatible(CandidSource::Text(&deployed), CandidSource::File(&committed))
+        .expect("The wasm's interface breaks clients of proofcart_nft.did");
+    service_equal(CandidSource::Text(&deployed), CandidSource::File(&committed))
+        .expect("The wasm was built from a different interface than proofcart_nft.did; rebuild it");
+
+    let version: ApiVersion = canister.query(Principal::anonymous(), "api_version", ());
+    assert_eq!(version.implementation, "proofcart-nft");
+}
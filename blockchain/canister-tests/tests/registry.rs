@@ -0,0 +1,108 @@
+//! The registry spawning NFT canister shards from the NFT wasm and routing
+//! verification to them.
+
+use candid::{CandidType, Encode, Principal};
+use proofcart_canister_tests::{nft_wasm, registry_wasm, user, Canister};
+use proofcart_icp_client::types::{MintRequest, ProductNFT};
+use serde::Deserialize;
+
+const SHARD_CYCLES: u128 = 5_000_000_000_000;
+
+#[derive(CandidType)]
+struct InitArgs {
+    shard_count: u32,
+}
+
+#[derive(CandidType, Deserialize)]
+struct Shard {
+    index: u32,
+    canister_id: Principal,
+    ready: bool,
+}
+
+#[derive(CandidType, Deserialize)]
+struct ProductSummary {
+    nft_id: u64,
+    serial_number: String,
+    owner: Principal,
+}
+
+#[derive(CandidType, Deserialize)]
+struct ShardedProduct {
+    shard: Principal,
+    product: ProductSummary,
+}
+
+fn spawn(shard_count: u32) -> (Canister, Vec<Shard>) {
+    let registry = Canister::install(registry_wasm(), Encode!(&InitArgs { shard_count }).unwrap());
+    let admin = registry.controller;
+    let uploaded: Result<(), String> = registry.update(admin, "set_shard_wasm", (nft_wasm(),));
+    uploaded.unwrap();
+    let shards: Result<Vec<Shard>, String> = registry.update(admin, "spawn_shards", (SHARD_CYCLES,));
+    (registry, shards.unwrap())
+}
+
+fn home(registry: &Canister, serial_number: &str) -> Principal {
+    let shard: Result<Principal, String> =
+        registry.query(Principal::anonymous(), "shard_for_serial", (serial_number,));
+    shard.unwrap()
+}
+
+fn mint_on(registry: &Canister, shard: Principal, minter: Principal, serial_number: &str) -> Result<ProductNFT, String> {
+    let request = MintRequest {
+        serial_number: serial_number.to_string(),
+        product_name: "Redmi Note 14 Pro".to_string(),
+        manufacturer: "Xiaomi".to_string(),
+        ..Default::default()
+    };
+    let payload = Encode!(&request).unwrap();
+    match registry.pic.update_call(shard, minter, "mint_product_nft", payload) {
+        Ok(pocket_ic::WasmResult::Reply(bytes)) => candid::decode_one(&bytes).unwrap(),
+        other => panic!("mint_product_nft on {} failed: {:?}", shard, other),
+    }
+}
+
+#[test]
+fn shards_are_spawned_and_serials_routed() {
+    let (registry, shards) = spawn(2);
+    assert_eq!(shards.iter().map(|shard| shard.index).collect::<Vec<_>>(), vec![0, 1]);
+    assert!(shards.iter().all(|shard| shard.ready));
+
+    // One serial homed on each shard.
+    let serials: Vec<String> = (0..2)
+        .map(|index| {
+            (0..)
+                .map(|n| format!("SN-{}", n))
+                .find(|serial| home(&registry, serial) == shards[index].canister_id)
+                .unwrap()
+        })
+        .collect();
+
+    let manufacturer = user(1);
+    for (index, serial) in serials.iter().enumerate() {
+        let home = shards[index].canister_id;
+        let other = shards[1 - index].canister_id;
+        assert!(mint_on(&registry, other, manufacturer, serial).is_err());
+        let nft = mint_on(&registry, home, manufacturer, serial).unwrap();
+
+        let verified: Result<ShardedProduct, String> =
+            registry.query(Principal::anonymous(), "verify_product", (serial.as_str(),));
+        let verified = verified.unwrap();
+        assert_eq!(verified.shard, home);
+        assert_eq!(verified.product.nft_id, nft.nft_id);
+        assert_eq!(verified.product.serial_number, nft.serial_number);
+        assert_eq!(verified.product.owner, manufacturer);
+    }
+}
+
+#[test]
+fn spawning_again_keeps_ready_shards() {
+    let (registry, shards) = spawn(2);
+    let again: Result<Vec<Shard>, String> = registry.update(registry.controller, "spawn_shards", (SHARD_CYCLES,));
+    let again = again.unwrap();
+    let ids = |shards: &[Shard]| shards.iter().map(|shard| shard.canister_id).collect::<Vec<_>>();
+    assert_eq!(ids(&again), ids(&shards));
+
+    let stranger: Result<Vec<Shard>, String> = registry.update(user(9), "spawn_shards", (SHARD_CYCLES,));
+    assert!(stranger.is_err());
+}
@@ -0,0 +1,36 @@
+//! Arguments of every method in `proofcart_nft.did`, decoded against the
+//! declared types. The first byte picks the method.
+
+#![no_main]
+
+use candid::types::{Type, TypeEnv};
+use candid_parser::utils::CandidSource;
+use candid_parser::IDLArgs;
+use libfuzzer_sys::fuzz_target;
+use proofcart_nft::fuzzing::endpoint_decoder_config;
+use std::sync::OnceLock;
+
+fn methods() -> &'static (TypeEnv, Vec<Vec<Type>>) {
+    static METHODS: OnceLock<(TypeEnv, Vec<Vec<Type>>)> = OnceLock::new();
+    METHODS.get_or_init(|| {
+        let did = include_str!("../../src/proofcart_nft.did");
+        let (env, actor) = CandidSource::Text(did).load().expect("proofcart_nft.did does not parse");
+        let actor = actor.expect("proofcart_nft.did has no service");
+        let args = env
+            .as_service(&actor)
+            .expect("not a service")
+            .iter()
+            .map(|(_, method)| env.as_func(method).expect("not a method").args.clone())
+            .collect();
+        (env, args)
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&pick, bytes)) = data.split_first() else {
+        return;
+    };
+    let (env, methods) = methods();
+    let args = &methods[pick as usize % methods.len()];
+    let _ = IDLArgs::from_bytes_with_types_with_config(bytes, env, args, &endpoint_decoder_config());
+});
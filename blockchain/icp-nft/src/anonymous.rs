@@ -0,0 +1,78 @@
+//! What the anonymous principal may do.
+//!
+//! Queries are open to everyone, anonymous callers included, so products can
+//! be verified without a wallet. Update calls from the anonymous principal
+//! are rejected in `inspect_message` unless the SuperAdmin lists the method in
+//! the policy. Independently of the policy, the anonymous principal can never
+//! hold a token: anyone can call as anonymous, so such a token would belong
+//! to everyone. Mints, transfers and claims check this on every replica.
+
+use candid::{CandidType, Principal};
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableCell;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::pause::not_paused;
+use crate::roles::require_admin;
+use crate::{audit, inspect, memory, Memory};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AnonymousPolicy {
+    /// Update methods the anonymous principal may call; none by default.
+    pub allowed_updates: Vec<String>,
+}
+
+candid_storable!(AnonymousPolicy);
+
+thread_local! {
+    static POLICY: RefCell<StableCell<AnonymousPolicy, Memory>> = RefCell::new(
+        StableCell::init(memory(46), AnonymousPolicy::default())
+            .expect("Failed to initialize anonymous policy")
+    );
+}
+
+fn policy() -> AnonymousPolicy {
+    POLICY.with(|p| p.borrow().get().clone())
+}
+
+/// Fail if the policy does not let the anonymous principal call `method`.
+pub fn check_update(method: &str) -> Result<(), String> {
+    if policy().allowed_updates.iter().any(|allowed| allowed == method) {
+        Ok(())
+    } else {
+        Err(format!("Anonymous callers cannot call {}", method))
+    }
+}
+
+/// Fail if `principal` is anonymous; tokens cannot be held by it.
+pub fn ensure_can_own(principal: Principal) -> Result<(), String> {
+    if principal == Principal::anonymous() {
+        return Err("The anonymous principal cannot own tokens".to_string());
+    }
+    Ok(())
+}
+
+/// SuperAdmin: set which update methods anonymous callers may use
+#[update(guard = "not_paused")]
+fn set_anonymous_policy(mut policy: AnonymousPolicy) -> Result<AnonymousPolicy, String> {
+    require_admin()?;
+    policy.allowed_updates.sort();
+    policy.allowed_updates.dedup();
+    if let Some(unknown) = policy.allowed_updates.iter().find(|method| !inspect::is_update_method(method)) {
+        return Err(format!("{} is not an update method", unknown));
+    }
+    POLICY.with(|p| {
+        p.borrow_mut()
+            .set(policy.clone())
+            .map_err(|e| format!("Failed to persist anonymous policy: {:?}", e))
+    })?;
+    audit::record("set_anonymous_policy");
+    Ok(policy)
+}
+
+/// The current anonymous-caller policy
+#[query]
+fn get_anonymous_policy() -> AnonymousPolicy {
+    policy()
+}
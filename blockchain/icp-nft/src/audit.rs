@@ -0,0 +1,87 @@
+//! Append-only audit log of privileged actions.
+//!
+//! Every successful call to a method gated on the SuperAdmin or a
+//! privileged role (revocations, recalls, role and configuration changes,
+//! imports) is recorded with its caller, time and the SHA-256 of its
+//! candid-encoded arguments, so a compliance review can match each entry
+//! against the request that produced it. Entries are never modified or
+//! removed.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::query;
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+use crate::roles::{require_role, Role};
+use crate::{memory, Memory};
+
+const MAX_ENTRIES_PER_CALL: u64 = 100;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct AuditEntry {
+    pub index: u64,
+    pub method: String,
+    pub caller: Principal,
+    pub timestamp: u64,
+    /// SHA-256 of the call's candid-encoded arguments.
+    pub args_hash: Vec<u8>,
+}
+
+candid_storable!(AuditEntry);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct AuditLogPage {
+    pub entries: Vec<AuditEntry>,
+    pub total: u64,
+}
+
+thread_local! {
+    static LOG: RefCell<StableBTreeMap<u64, AuditEntry, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(45)));
+}
+
+/// Hash of the current call's arguments. Take it before the first await in
+/// async methods; afterwards the argument data is no longer available.
+pub fn args_hash() -> Vec<u8> {
+    Sha256::digest(ic_cdk::api::call::arg_data_raw()).to_vec()
+}
+
+/// Record a privileged call made by `caller` whose arguments hash to `args_hash`.
+pub fn append(method: &str, caller: Principal, args_hash: Vec<u8>) {
+    LOG.with(|log| {
+        let mut log = log.borrow_mut();
+        let index = log.len();
+        log.insert(
+            index,
+            AuditEntry {
+                index,
+                method: method.to_string(),
+                caller,
+                timestamp: ic_cdk::api::time(),
+                args_hash,
+            },
+        );
+    });
+}
+
+/// Record the current (synchronous) privileged call.
+pub fn record(method: &str) {
+    append(method, caller(), args_hash());
+}
+
+/// Support or SuperAdmin: privileged actions, oldest first, at most 100 per call
+#[query]
+fn get_audit_log(offset: u64, limit: u64) -> Result<AuditLogPage, String> {
+    require_role(&[Role::Support])?;
+    LOG.with(|log| {
+        let log = log.borrow();
+        let end = offset.saturating_add(limit.min(MAX_ENTRIES_PER_CALL));
+        Ok(AuditLogPage {
+            entries: log.range(offset..end).map(|(_, entry)| entry).collect(),
+            total: log.len(),
+        })
+    })
+}
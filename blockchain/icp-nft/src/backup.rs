@@ -0,0 +1,185 @@
+//! Off-chain backup and disaster-recovery restore of the registry.
+//!
+//! `export_nfts` pages through every token. `import_nfts` rehydrates a fresh
+//! canister from such an export: it is only accepted while the registry is
+//! empty or an import is already in progress, and `finish_import` closes the
+//! import for good. Minting is blocked while an import is open. Imported
+//! tokens count against their minter's quota and their collection's supply
+//! like minted ones.
+//!
+//! `import_legacy_nfts` takes tokens read from the retired `nft_canister`
+//! (its `get_nft` for every id below `get_total_nfts`) through the same path.
+
+use candid::CandidType;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableCell;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::legacy::{self, NftCanisterNFT};
+use crate::limits;
+use crate::pause::not_paused;
+use crate::roles::require_admin;
+use crate::{
+    audit, burns, certification, collections, manufacturers, memory, search, serials, stats, txlog, warranty, Memory,
+    ProductNFT, NFTS, NFT_COUNTER, SERIAL_TO_NFT,
+};
+
+const MAX_EXPORT_PAGE: u64 = 500;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ImportState {
+    pub in_progress: bool,
+    pub finished: bool,
+    pub imported: u64,
+}
+
+candid_storable!(ImportState);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ExportPage {
+    pub nfts: Vec<ProductNFT>,
+    /// Pass as `start` to fetch the next page; `None` when done.
+    pub next_start: Option<u64>,
+    pub total: u64,
+}
+
+thread_local! {
+    static IMPORT_STATE: RefCell<StableCell<ImportState, Memory>> = RefCell::new(
+        StableCell::init(memory(15), ImportState::default())
+            .expect("Failed to initialize import state")
+    );
+}
+
+fn state() -> ImportState {
+    IMPORT_STATE.with(|s| s.borrow().get().clone())
+}
+
+fn set_state(state: ImportState) {
+    IMPORT_STATE.with(|s| {
+        s.borrow_mut().set(state).expect("Failed to persist import state");
+    });
+}
+
+/// Fail while a restore is in progress.
+pub fn ensure_not_importing() -> Result<(), String> {
+    if state().in_progress {
+        return Err("Registry restore in progress; minting is disabled".to_string());
+    }
+    Ok(())
+}
+
+/// Admin: export NFTs with `nft_id >= start`, in id order
+#[query]
+fn export_nfts(start: u64, limit: u64) -> Result<ExportPage, String> {
+    require_admin()?;
+    let limit = limit.clamp(1, MAX_EXPORT_PAGE) as usize;
+
+    NFTS.with(|nfts| {
+        let nfts = nfts.borrow();
+        let mut page: Vec<ProductNFT> = nfts.range(start..).take(limit + 1).map(|(_, nft)| nft).collect();
+        let next_start = if page.len() > limit { page.pop().map(|nft| nft.nft_id) } else { None };
+        Ok(ExportPage { nfts: page, next_start, total: nfts.len() })
+    })
+}
+
+/// Admin: restore exported NFTs into an empty canister (may be called in batches)
+#[update(guard = "not_paused")]
+fn import_nfts(batch: Vec<ProductNFT>) -> Result<u64, String> {
+    require_admin()?;
+    let imported = import_batch(batch)?;
+    audit::record("import_nfts");
+    Ok(imported)
+}
+
+/// Admin: import tokens exported from the legacy `nft_canister` (same rules as `import_nfts`)
+#[update(guard = "not_paused")]
+fn import_legacy_nfts(batch: Vec<NftCanisterNFT>) -> Result<u64, String> {
+    require_admin()?;
+    let imported = import_batch(batch.into_iter().map(legacy::from_nft_canister).collect())?;
+    audit::record("import_legacy_nfts");
+    Ok(imported)
+}
+
+fn import_batch(batch: Vec<ProductNFT>) -> Result<u64, String> {
+    let mut state = state();
+    if state.finished {
+        return Err("Import already finished".to_string());
+    }
+    if !state.in_progress && NFTS.with(|nfts| !nfts.borrow().is_empty()) {
+        return Err("Import is only allowed into an empty registry".to_string());
+    }
+    limits::check_batch("import", batch.len(), limits::current().max_import_batch)?;
+
+    // Validate the whole batch before writing anything. Legacy serials were
+    // stored verbatim, so two of them may normalize to the same one.
+    let mut seen = std::collections::BTreeSet::new();
+    for nft in &batch {
+        limits::check_nft(nft).map_err(|e| format!("NFT {}: {}", nft.nft_id, e))?;
+        let serial = serials::normalize(&nft.serial_number);
+        if NFTS.with(|nfts| nfts.borrow().contains_key(&nft.nft_id)) {
+            return Err(format!("NFT {} already imported", nft.nft_id));
+        }
+        if SERIAL_TO_NFT.with(|map| map.borrow().contains_key(&serial)) || !seen.insert(serial.clone()) {
+            return Err(format!("Serial {} already imported", serial));
+        }
+    }
+
+    state.in_progress = true;
+    for mut nft in batch {
+        nft.serial_number = serials::normalize(&nft.serial_number);
+        nft.metadata.serial_number = nft.serial_number.clone();
+
+        NFTS.with(|nfts| {
+            nfts.borrow_mut().insert(nft.nft_id, nft.clone());
+        });
+        SERIAL_TO_NFT.with(|map| {
+            map.borrow_mut().insert(nft.serial_number.clone(), nft.nft_id);
+        });
+        certification::certify_nft(&nft);
+        search::index_nft(&nft);
+        stats::record_mint(&nft);
+        manufacturers::record_mint(nft.minter());
+        collections::record_import(&nft);
+        warranty::schedule(&nft);
+        txlog::append(
+            txlog::TxKind::Mint,
+            nft.nft_id,
+            &nft.serial_number,
+            None,
+            Some(nft.owner),
+            Some("import".to_string()),
+        );
+        state.imported += 1;
+    }
+    set_state(state.clone());
+
+    Ok(state.imported)
+}
+
+/// Admin: close the import and re-enable minting
+#[update(guard = "not_paused")]
+fn finish_import() -> Result<ImportState, String> {
+    require_admin()?;
+    let mut state = state();
+    if !state.in_progress {
+        return Err("No import in progress".to_string());
+    }
+    state.in_progress = false;
+    state.finished = true;
+    set_state(state.clone());
+
+    // Resume after the highest stored or burned id, as `post_upgrade` does
+    let last_id = NFTS.with(|nfts| nfts.borrow().last_key_value().map(|(id, _)| id));
+    let next_id = last_id.max(burns::last_burned_id()).map_or(0, |id| id + 1);
+    NFT_COUNTER.with(|counter| *counter.borrow_mut() = next_id);
+
+    audit::record("finish_import");
+    Ok(state)
+}
+
+/// Import progress
+#[query]
+fn get_import_state() -> ImportState {
+    state()
+}
@@ -0,0 +1,130 @@
+//! Burned tokens and their tombstones.
+//!
+//! Burning removes the full token record, but the serial stays mapped to its
+//! token id and a compact tombstone is kept under that id. Lookups can then
+//! tell a serial that was destroyed or found counterfeit apart from one that
+//! was never registered, and the serial can never be minted again.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+
+use crate::pause::not_paused;
+use crate::roles::{has_any_role, Role};
+use crate::{
+    audit, certification, claims, components, events, get_nft, icrc37, memory, sale_lock, search, serials, stats,
+    txlog, Memory, NFTS, SERIAL_TO_NFT,
+};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BurnReason {
+    /// The physical item was destroyed.
+    Destroyed,
+    /// The item was found to be counterfeit.
+    Counterfeit,
+    /// The token was minted in error for a serial already covered elsewhere.
+    Duplicate,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Tombstone {
+    pub nft_id: u64,
+    pub serial_number: String,
+    pub reason: BurnReason,
+    pub burned_by: Principal,
+    pub burned_at: u64,
+}
+
+candid_storable!(Tombstone);
+
+impl fmt::Display for Tombstone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.reason {
+            BurnReason::Destroyed => "destroyed",
+            BurnReason::Counterfeit => "counterfeit",
+            BurnReason::Duplicate => "a duplicate",
+        };
+        write!(
+            f,
+            "Serial {} was registered as NFT {} but burned as {} at {}",
+            self.serial_number, self.nft_id, reason, self.burned_at
+        )
+    }
+}
+
+thread_local! {
+    // Burned token id -> tombstone
+    static TOMBSTONES: RefCell<StableBTreeMap<u64, Tombstone, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(47)));
+}
+
+/// Tombstone of a burned token, if `nft_id` was burned.
+pub fn tombstone(nft_id: u64) -> Option<Tombstone> {
+    TOMBSTONES.with(|t| t.borrow().get(&nft_id))
+}
+
+/// Tombstone of the token once registered under `serial_number`, if burned.
+pub fn find(serial_number: &str) -> Option<Tombstone> {
+    SERIAL_TO_NFT
+        .with(|map| map.borrow().get(&serials::normalize(serial_number)))
+        .and_then(tombstone)
+}
+
+/// Highest burned token id, so ids are not reissued after an upgrade.
+pub fn last_burned_id() -> Option<u64> {
+    TOMBSTONES.with(|t| t.borrow().last_key_value().map(|(id, _)| id))
+}
+
+/// Minter while it still holds the token, or a Verifier or SuperAdmin: burn
+/// a token, keeping only a tombstone for its serial
+#[update(guard = "not_paused")]
+fn burn_nft(nft_id: u64, reason: BurnReason) -> Result<Tombstone, String> {
+    let caller = caller();
+    let nft = get_nft(nft_id)?;
+    let holding_minter = nft.minter() == caller && nft.owner == caller;
+    if !holding_minter && !has_any_role(caller, &[Role::Verifier]) {
+        return Err("Only the minter while it holds the token, a verifier or an admin can burn it".to_string());
+    }
+    sale_lock::ensure_unlocked(&nft)?;
+    components::ensure_unattached(&nft)?;
+
+    let tombstone = Tombstone {
+        nft_id,
+        serial_number: nft.serial_number.clone(),
+        reason,
+        burned_by: caller,
+        burned_at: ic_cdk::api::time(),
+    };
+    NFTS.with(|nfts| nfts.borrow_mut().remove(&nft_id));
+    TOMBSTONES.with(|t| t.borrow_mut().insert(nft_id, tombstone.clone()));
+
+    certification::uncertify(&nft.serial_number);
+    search::unindex_nft(&nft);
+    stats::record_burn(&nft);
+    claims::clear(nft_id);
+    icrc37::clear_approvals(nft_id);
+    txlog::append(
+        txlog::TxKind::Burn,
+        nft_id,
+        &nft.serial_number,
+        Some(nft.owner),
+        None,
+        Some(format!("{:?}", reason).to_lowercase()),
+    );
+    events::emit(events::EventKind::Burn, Some(nft_id), nft.serial_number.clone());
+    if !holding_minter {
+        audit::record("burn_nft");
+    }
+
+    Ok(tombstone)
+}
+
+/// The tombstone left for a burned serial, if any
+#[query]
+fn get_tombstone(serial_number: String) -> Option<Tombstone> {
+    find(&serial_number)
+}
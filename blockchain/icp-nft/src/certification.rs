@@ -0,0 +1,126 @@
+//! Certified view of the registry.
+//!
+//! Every NFT has a leaf in a Merkle tree keyed by serial number. The tree's
+//! root is published as the canister's certified data, so any query response
+//! carrying the subnet certificate plus a witness for a serial can be checked
+//! against the IC root key without trusting the replica that answered.
+
+use candid::Principal;
+use ic_certified_map::{fork, labeled, labeled_hash, AsHashTree, Hash, HashTree, RbTree};
+use proofcart_qr_payload::certified::leaf_hash;
+use serde::Serialize;
+use std::cell::RefCell;
+
+use crate::{ProductNFT, NFTS};
+
+/// Label under which the NFT tree is published in the certified data.
+pub const NFT_TREE_LABEL: &[u8] = b"nfts";
+
+thread_local! {
+    static CERT_TREE: RefCell<RbTree<String, Hash>> = RefCell::new(RbTree::new());
+}
+
+/// SHA-256 of the owner's principal bytes (see `certified::owner_hash` in
+/// `proofcart-qr-payload`, which scanners use too).
+pub fn owner_hash(owner: &Principal) -> Hash {
+    proofcart_qr_payload::certified::owner_hash(owner.as_slice())
+}
+
+fn nft_leaf(nft: &ProductNFT) -> Hash {
+    leaf_hash(nft.nft_id, &owner_hash(&nft.owner), !nft.revoked)
+}
+
+/// Publish the tree root as the canister's certified data.
+pub fn publish_root() {
+    CERT_TREE.with(|tree| {
+        let root = labeled_hash(NFT_TREE_LABEL, &tree.borrow().root_hash());
+        ic_cdk::api::set_certified_data(&root);
+    });
+}
+
+/// Insert or refresh the leaf for an NFT and republish the root.
+/// Must be called from update calls after every change to the stored token.
+pub fn certify_nft(nft: &ProductNFT) {
+    CERT_TREE.with(|tree| {
+        tree.borrow_mut().insert(nft.serial_number.clone(), nft_leaf(nft));
+    });
+    publish_root();
+}
+
+/// Drop the leaf for a burned serial and republish the root.
+pub fn uncertify(serial_number: &str) {
+    CERT_TREE.with(|tree| tree.borrow_mut().delete(serial_number.as_bytes()));
+    publish_root();
+}
+
+/// Recompute the whole tree from stable storage (used after upgrades, since
+/// the tree itself lives on the heap).
+pub fn rebuild() {
+    CERT_TREE.with(|tree| {
+        let mut tree = tree.borrow_mut();
+        *tree = RbTree::new();
+        NFTS.with(|nfts| {
+            for (_, nft) in nfts.borrow().iter() {
+                tree.insert(nft.serial_number.clone(), nft_leaf(&nft));
+            }
+        });
+    });
+    publish_root();
+}
+
+/// CBOR-encoded, self-describing witness for a single serial (or its
+/// absence), wrapped in the tree label.
+pub fn witness(serial_number: &str) -> Vec<u8> {
+    CERT_TREE.with(|tree| {
+        let tree = tree.borrow();
+        let witness = labeled(NFT_TREE_LABEL, tree.witness(serial_number.as_bytes()));
+        encode_tree(&witness)
+    })
+}
+
+/// A single CBOR witness covering every serial in `serial_numbers` (present
+/// or absent), built by merging the per-serial witnesses.
+pub fn batch_witness(serial_numbers: &[String]) -> Vec<u8> {
+    CERT_TREE.with(|tree| {
+        let tree = tree.borrow();
+        let merged = serial_numbers
+            .iter()
+            .map(|serial| tree.witness(serial.as_bytes()))
+            .reduce(merge_witnesses)
+            .unwrap_or_else(|| HashTree::Pruned(tree.root_hash()));
+        encode_tree(&labeled(NFT_TREE_LABEL, merged))
+    })
+}
+
+/// Merge two witnesses of the same tree: wherever one side is pruned, keep
+/// the other side's revealed subtree.
+fn merge_witnesses<'a>(lhs: HashTree<'a>, rhs: HashTree<'a>) -> HashTree<'a> {
+    match (lhs, rhs) {
+        (HashTree::Pruned(_), rhs) => rhs,
+        (lhs, HashTree::Pruned(_)) => lhs,
+        (HashTree::Fork(lhs), HashTree::Fork(rhs)) => {
+            let (ll, lr) = *lhs;
+            let (rl, rr) = *rhs;
+            fork(merge_witnesses(ll, rl), merge_witnesses(lr, rr))
+        }
+        (HashTree::Labeled(label, lhs), HashTree::Labeled(_, rhs)) => {
+            HashTree::Labeled(label, Box::new(merge_witnesses(*lhs, *rhs)))
+        }
+        // Empty and leaf nodes are identical on both sides.
+        (lhs, _) => lhs,
+    }
+}
+
+pub fn encode_tree<T: Serialize>(tree: &T) -> Vec<u8> {
+    let mut serializer = serde_cbor::ser::Serializer::new(vec![]);
+    serializer.self_describe().expect("CBOR self-describe tag");
+    tree.serialize(&mut serializer).expect("CBOR witness encoding");
+    serializer.into_inner()
+}
+
+/// Certificate for the current certified data. Only available in
+/// non-replicated query calls.
+pub fn data_certificate() -> Result<Vec<u8>, String> {
+    ic_cdk::api::data_certificate()
+        .ok_or_else(|| "Certificate is only available in query calls".to_string())
+}
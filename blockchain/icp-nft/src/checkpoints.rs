@@ -0,0 +1,150 @@
+//! Supply-chain checkpoints (chain of custody).
+//!
+//! Logistics partners (the `Distributor` role) and the minter record each
+//! hand-off of a unit from factory to customer. Every checkpoint stores the
+//! hash of the one before it, and its own hash covers all of its fields, so
+//! rewriting any earlier entry breaks every later link. Locations are stored
+//! as hashes so routes are not disclosed.
+
+use candid::{CandidType, Encode, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+use crate::pause::not_paused;
+use crate::roles::{has_any_role, Role};
+use crate::{find_by_serial, memory, txlog, Memory};
+
+const MAX_HANDLER_LEN: usize = 128;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckpointEvent {
+    LeftFactory,
+    ArrivedAtWarehouse,
+    LeftWarehouse,
+    ArrivedAtRetailer,
+    DeliveredToCustomer,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Checkpoint {
+    pub sequence: u64,
+    pub event_type: CheckpointEvent,
+    /// SHA-256 of the off-chain location description.
+    pub location_hash: Vec<u8>,
+    pub handler: String,
+    pub recorded_by: Principal,
+    pub recorded_at: u64,
+    /// `hash` of the previous checkpoint; `None` for the first one.
+    pub prev_hash: Option<Vec<u8>>,
+    pub hash: Vec<u8>,
+}
+
+candid_storable!(Checkpoint);
+
+thread_local! {
+    static CHECKPOINTS: RefCell<StableBTreeMap<(u64, u64), Checkpoint, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(29)));
+}
+
+fn trail(nft_id: u64) -> Vec<Checkpoint> {
+    CHECKPOINTS.with(|c| {
+        c.borrow()
+            .range((nft_id, 0)..=(nft_id, u64::MAX))
+            .map(|(_, checkpoint)| checkpoint)
+            .collect()
+    })
+}
+
+/// Hash over every field except `hash` itself, chained to the token.
+fn checkpoint_hash(nft_id: u64, checkpoint: &Checkpoint) -> Vec<u8> {
+    let bytes = Encode!(
+        &nft_id,
+        &checkpoint.sequence,
+        &checkpoint.event_type,
+        &checkpoint.location_hash,
+        &checkpoint.handler,
+        &checkpoint.recorded_by,
+        &checkpoint.recorded_at,
+        &checkpoint.prev_hash
+    )
+    .expect("Failed to encode checkpoint");
+    Sha256::digest(bytes).to_vec()
+}
+
+/// Logistics partner or minter: record a custody checkpoint for a unit
+#[update(guard = "not_paused")]
+fn add_checkpoint(
+    serial_number: String,
+    location_hash: Vec<u8>,
+    handler: String,
+    event_type: CheckpointEvent,
+) -> Result<Checkpoint, String> {
+    let caller = caller();
+    let nft = find_by_serial(&serial_number)?;
+    if caller != nft.minter() && !has_any_role(caller, &[Role::Distributor]) {
+        return Err("Only the minter or a distributor can record checkpoints".to_string());
+    }
+    if location_hash.len() != 32 {
+        return Err("Location hash must be a 32-byte SHA-256 digest".to_string());
+    }
+    if handler.trim().is_empty() || handler.len() > MAX_HANDLER_LEN {
+        return Err(format!("Handler must be 1-{} bytes", MAX_HANDLER_LEN));
+    }
+
+    let last = CHECKPOINTS.with(|c| {
+        c.borrow()
+            .range((nft.nft_id, 0)..=(nft.nft_id, u64::MAX))
+            .last()
+            .map(|(_, checkpoint)| checkpoint)
+    });
+    let mut checkpoint = Checkpoint {
+        sequence: last.as_ref().map_or(0, |prev| prev.sequence + 1),
+        event_type,
+        location_hash,
+        handler,
+        recorded_by: caller,
+        recorded_at: ic_cdk::api::time(),
+        prev_hash: last.map(|prev| prev.hash),
+        hash: Vec::new(),
+    };
+    checkpoint.hash = checkpoint_hash(nft.nft_id, &checkpoint);
+    CHECKPOINTS.with(|c| {
+        c.borrow_mut().insert((nft.nft_id, checkpoint.sequence), checkpoint.clone());
+    });
+    txlog::append(
+        txlog::TxKind::MetadataUpdate,
+        nft.nft_id,
+        &nft.serial_number,
+        None,
+        None,
+        Some(format!("checkpoint {:?}", event_type)),
+    );
+
+    Ok(checkpoint)
+}
+
+/// Chain-of-custody trail of a unit, oldest first
+#[query]
+fn get_checkpoints(serial_number: String) -> Result<Vec<Checkpoint>, String> {
+    let nft = find_by_serial(&serial_number)?;
+    Ok(trail(nft.nft_id))
+}
+
+/// Recompute the checkpoint hash chain; `Err` names the first broken link
+#[query]
+fn verify_checkpoints(serial_number: String) -> Result<u64, String> {
+    let nft = find_by_serial(&serial_number)?;
+    let trail = trail(nft.nft_id);
+    let mut prev_hash: Option<Vec<u8>> = None;
+    for checkpoint in &trail {
+        if checkpoint.prev_hash != prev_hash || checkpoint_hash(nft.nft_id, checkpoint) != checkpoint.hash {
+            return Err(format!("Checkpoint {} does not match the chain", checkpoint.sequence));
+        }
+        prev_hash = Some(checkpoint.hash.clone());
+    }
+    Ok(trail.len() as u64)
+}
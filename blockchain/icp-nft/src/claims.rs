@@ -0,0 +1,203 @@
+//! One-time claim vouchers for end customers.
+//!
+//! A manufacturer mints to itself and attaches the SHA-256 of a claim code
+//! printed inside the product box. Whoever presents the code with
+//! `claim_nft` becomes the owner, so the manufacturer never needs to know the
+//! buyer's principal. Only the hash is stored, and never returned by a query.
+//! A voucher is void once the token leaves the principal that issued it, and
+//! is locked after repeated wrong codes.
+//!
+//! When the maintenance job purges a voucher that outlived its TTL while the
+//! issuer still holds the token, the unit never reached a customer; it is
+//! marked reclaimable so the manufacturer can find it and issue a new code.
+//! The mark goes away with `set_claim_code` or when the token changes hands.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+use crate::pause::not_paused;
+use crate::roles::require_admin;
+use crate::{anonymous, apply_transfer, collections, components, find_by_serial, get_nft, memory, sale_lock, stolen, Memory, ProductNFT, TransactionType};
+
+const MAX_FAILED_ATTEMPTS: u32 = 10;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct ClaimVoucher {
+    code_hash: Vec<u8>,
+    issued_by: Principal,
+    issued_at: u64,
+    failed_attempts: u32,
+}
+
+candid_storable!(ClaimVoucher);
+
+/// A token whose voucher expired unclaimed while its issuer still held it.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ReclaimableUnit {
+    pub nft_id: u64,
+    pub serial_number: String,
+    pub issued_by: Principal,
+    pub voucher_issued_at: u64,
+    pub expired_at: u64,
+}
+
+candid_storable!(ReclaimableUnit);
+
+thread_local! {
+    static VOUCHERS: RefCell<StableBTreeMap<u64, ClaimVoucher, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(18)));
+
+    static RECLAIMABLE: RefCell<StableBTreeMap<u64, ReclaimableUnit, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(43)));
+}
+
+pub fn validate_hash(code_hash: &[u8]) -> Result<(), String> {
+    if code_hash.len() != 32 {
+        return Err("Claim code hash must be a 32-byte SHA-256 digest".to_string());
+    }
+    Ok(())
+}
+
+/// Attach a voucher to a token held by `issued_by`; the hash must be valid.
+pub fn issue(nft_id: u64, issued_by: Principal, code_hash: Vec<u8>) {
+    let voucher = ClaimVoucher {
+        code_hash,
+        issued_by,
+        issued_at: ic_cdk::api::time(),
+        failed_attempts: 0,
+    };
+    VOUCHERS.with(|v| {
+        v.borrow_mut().insert(nft_id, voucher);
+    });
+    RECLAIMABLE.with(|r| r.borrow_mut().remove(&nft_id));
+}
+
+/// Drop any voucher on a token (called whenever it changes hands).
+pub fn clear(nft_id: u64) {
+    VOUCHERS.with(|v| {
+        v.borrow_mut().remove(&nft_id);
+    });
+    RECLAIMABLE.with(|r| r.borrow_mut().remove(&nft_id));
+}
+
+/// Outcome of one `purge_issued_before` pass.
+pub struct Purge {
+    pub purged: usize,
+    /// Purged vouchers whose token the issuer still held.
+    pub reclaimable: usize,
+    /// Where to resume; `None` once the scan wrapped around.
+    pub next: Option<u64>,
+}
+
+/// Remove vouchers issued before `cutoff`, scanning at most `max` vouchers
+/// after `cursor`, and mark the tokens still held by their issuer reclaimable.
+pub fn purge_issued_before(cutoff: u64, cursor: Option<u64>, max: usize) -> Purge {
+    let start = cursor.map_or(0, |c| c.saturating_add(1));
+    let scanned: Vec<(u64, ClaimVoucher)> = VOUCHERS.with(|v| v.borrow().range(start..).take(max).collect());
+    let now = ic_cdk::api::time();
+    let mut purge = Purge { purged: 0, reclaimable: 0, next: None };
+    for (nft_id, voucher) in &scanned {
+        if voucher.issued_at >= cutoff {
+            continue;
+        }
+        clear(*nft_id);
+        purge.purged += 1;
+        let Ok(nft) = get_nft(*nft_id) else { continue };
+        if nft.owner == voucher.issued_by {
+            let unit = ReclaimableUnit {
+                nft_id: *nft_id,
+                serial_number: nft.serial_number,
+                issued_by: voucher.issued_by,
+                voucher_issued_at: voucher.issued_at,
+                expired_at: now,
+            };
+            RECLAIMABLE.with(|r| r.borrow_mut().insert(*nft_id, unit));
+            purge.reclaimable += 1;
+        }
+    }
+    if scanned.len() == max {
+        purge.next = scanned.last().map(|(nft_id, _)| *nft_id);
+    }
+    purge
+}
+
+fn voucher(nft: &ProductNFT) -> Option<ClaimVoucher> {
+    VOUCHERS
+        .with(|v| v.borrow().get(&nft.nft_id))
+        .filter(|voucher| voucher.issued_by == nft.owner && voucher.failed_attempts < MAX_FAILED_ATTEMPTS)
+}
+
+/// Owner: attach, replace (which also resets failed attempts) or cancel
+/// (`None`) the claim voucher on an NFT
+#[update(guard = "not_paused")]
+fn set_claim_code(nft_id: u64, code_hash: Option<Vec<u8>>) -> Result<(), String> {
+    let nft = get_nft(nft_id)?;
+    if nft.owner != caller() {
+        return Err("Only the owner can set a claim code".to_string());
+    }
+    match code_hash {
+        Some(code_hash) => {
+            validate_hash(&code_hash)?;
+            issue(nft_id, nft.owner, code_hash);
+        }
+        None => clear(nft_id),
+    }
+    Ok(())
+}
+
+/// Claim an NFT with the code from the product packaging
+#[update(guard = "not_paused")]
+fn claim_nft(serial_number: String, code: String) -> Result<ProductNFT, String> {
+    let mut nft = find_by_serial(&serial_number)?;
+    let mut voucher = voucher(&nft).ok_or_else(|| format!("Serial {} has no active claim code", nft.serial_number))?;
+
+    let code_hash = Sha256::digest(code.trim().as_bytes()).to_vec();
+    if code_hash != voucher.code_hash {
+        voucher.failed_attempts += 1;
+        VOUCHERS.with(|v| {
+            v.borrow_mut().insert(nft.nft_id, voucher);
+        });
+        return Err("Invalid claim code".to_string());
+    }
+
+    anonymous::ensure_can_own(caller())?;
+    sale_lock::ensure_unlocked(&nft)?;
+    stolen::ensure_not_reported(&nft)?;
+    collections::ensure_not_frozen(&nft)?;
+    components::ensure_transferable(&nft)?;
+
+    apply_transfer(&mut nft, caller(), TransactionType::Sale, Some("claimed with voucher".to_string()), None);
+
+    Ok(nft)
+}
+
+/// Whether a serial carries an active claim voucher
+#[query]
+fn is_claimable(serial_number: String) -> Result<bool, String> {
+    let nft = find_by_serial(&serial_number)?;
+    Ok(voucher(&nft).is_some())
+}
+
+/// Tokens whose vouchers expired unclaimed, for `issued_by` (the caller by
+/// default; other issuers are admin only)
+#[query]
+fn list_reclaimable(issued_by: Option<Principal>, offset: u64, limit: u64) -> Result<Vec<ReclaimableUnit>, String> {
+    let issued_by = issued_by.unwrap_or_else(caller);
+    if issued_by != caller() {
+        require_admin()?;
+    }
+    Ok(RECLAIMABLE.with(|r| {
+        r.borrow()
+            .iter()
+            .map(|(_, unit)| unit)
+            .filter(|unit| unit.issued_by == issued_by)
+            .skip(offset as usize)
+            .take(limit.min(100) as usize)
+            .collect()
+    }))
+}
@@ -0,0 +1,275 @@
+//! Manufacturer collections (product lines) that NFTs are minted into.
+//!
+//! A collection owner can freeze transfers of every token in the collection
+//! (a recall, a legal hold) with `freeze_collection`. The freeze shows on the
+//! tokens' verification responses. The SuperAdmin can freeze any collection
+//! and lift any freeze; a freeze it imposed can only be lifted by it.
+//!
+//! A collection created in a partner marketplace (see `marketplaces`) needs
+//! its owner to be one of the marketplace's registered minters, both to
+//! create it and for every mint into it.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::pause::not_paused;
+use crate::roles::is_admin;
+use crate::royalties::{self, RoyaltyInfo};
+use crate::{audit, limits, marketplaces, memory, Memory, ProductNFT};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Collection {
+    pub collection_id: u64,
+    pub owner: Principal,
+    pub manufacturer: String,
+    pub product_line: String,
+    pub description: String,
+    pub logo_uri: String,
+    pub created_at: u64,
+    pub supply: u64,
+    pub transfer_count: u64,
+    pub royalty: Option<RoyaltyInfo>,
+    /// Set while transfers of the collection's tokens are frozen.
+    pub freeze: Option<CollectionFreeze>,
+    /// The partner marketplace the collection belongs to; `None` for
+    /// ProofCart's own.
+    pub marketplace_id: Option<String>,
+}
+
+candid_storable!(Collection);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CollectionFreeze {
+    pub collection_id: u64,
+    pub frozen_by: Principal,
+    pub frozen_at: u64,
+    pub reason: String,
+    /// Imposed by an admin over the owner; only an admin can lift it.
+    pub admin_hold: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize)]
+pub struct CreateCollectionRequest {
+    pub manufacturer: String,
+    pub product_line: String,
+    pub description: String,
+    pub logo_uri: String,
+    pub royalty: Option<RoyaltyInfo>,
+    /// Create the collection in this partner marketplace, whose minter the
+    /// caller must be.
+    pub marketplace_id: Option<String>,
+}
+
+thread_local! {
+    static COLLECTIONS: RefCell<StableBTreeMap<u64, Collection, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(4)));
+}
+
+pub fn get(collection_id: u64) -> Result<Collection, String> {
+    COLLECTIONS.with(|c| {
+        c.borrow().get(&collection_id)
+            .ok_or_else(|| format!("Collection {} not found", collection_id))
+    })
+}
+
+fn save(collection: Collection) {
+    COLLECTIONS.with(|c| {
+        c.borrow_mut().insert(collection.collection_id, collection);
+    });
+}
+
+/// Apply `f` to a collection on behalf of its owner.
+pub fn update<F>(collection_id: u64, owner: Principal, f: F) -> Result<(), String>
+where
+    F: FnOnce(&mut Collection),
+{
+    let mut collection = get(collection_id)?;
+    if collection.owner != owner {
+        return Err("Only the collection owner can modify this collection".to_string());
+    }
+    f(&mut collection);
+    save(collection);
+    Ok(())
+}
+
+/// Check that `minter` may mint into the collection: it must own it and,
+/// in a partner marketplace, still be one of the marketplace's minters.
+pub fn authorize_mint(collection_id: u64, minter: Principal) -> Result<Collection, String> {
+    let collection = get(collection_id)?;
+    if collection.owner != minter {
+        return Err("Only the collection owner can mint into this collection".to_string());
+    }
+    if let Some(marketplace_id) = &collection.marketplace_id {
+        marketplaces::ensure_minter(marketplace_id, minter)?;
+    }
+    Ok(collection)
+}
+
+pub fn record_mint(collection_id: u64) {
+    if let Ok(mut collection) = get(collection_id) {
+        collection.supply += 1;
+        save(collection);
+    }
+}
+
+/// Count a restored token, and the transfers its history still records.
+pub fn record_import(nft: &ProductNFT) {
+    let Some(collection_id) = nft.collection_id else { return };
+    if let Ok(mut collection) = get(collection_id) {
+        collection.supply += 1;
+        collection.transfer_count += nft.ownership_history.len().saturating_sub(1) as u64;
+        save(collection);
+    }
+}
+
+pub fn record_transfer(collection_id: u64) {
+    if let Ok(mut collection) = get(collection_id) {
+        collection.transfer_count += 1;
+        save(collection);
+    }
+}
+
+/// The partner marketplace of the token's collection, if any.
+pub fn marketplace_of(nft: &ProductNFT) -> Option<String> {
+    nft.collection_id.and_then(|id| get(id).ok()).and_then(|collection| collection.marketplace_id)
+}
+
+/// The freeze on the token's collection, if any.
+pub fn freeze_of(nft: &ProductNFT) -> Option<CollectionFreeze> {
+    nft.collection_id.and_then(|id| get(id).ok()).and_then(|collection| collection.freeze)
+}
+
+/// Fail if the token's collection is frozen.
+pub fn ensure_not_frozen(nft: &ProductNFT) -> Result<(), String> {
+    match freeze_of(nft) {
+        Some(freeze) => Err(format!(
+            "Transfers in collection {} are frozen: {}",
+            freeze.collection_id, freeze.reason
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Create a collection owned by the caller
+#[update(guard = "not_paused")]
+fn create_collection(request: CreateCollectionRequest) -> Result<Collection, String> {
+    if request.manufacturer.trim().is_empty() || request.product_line.trim().is_empty() {
+        return Err("Manufacturer and product line are required".to_string());
+    }
+    if let Some(royalty) = &request.royalty {
+        royalties::validate(royalty)?;
+    }
+    if let Some(marketplace_id) = &request.marketplace_id {
+        marketplaces::ensure_minter(marketplace_id, caller())?;
+    }
+
+    let collection = Collection {
+        collection_id: COLLECTIONS.with(|c| c.borrow().len()),
+        owner: caller(),
+        manufacturer: request.manufacturer,
+        product_line: request.product_line,
+        description: request.description,
+        logo_uri: request.logo_uri,
+        created_at: ic_cdk::api::time(),
+        supply: 0,
+        transfer_count: 0,
+        royalty: request.royalty,
+        freeze: None,
+        marketplace_id: request.marketplace_id,
+    };
+    save(collection.clone());
+
+    Ok(collection)
+}
+
+/// Collection owner or admin: freeze transfers of every token in a collection
+#[update(guard = "not_paused")]
+fn freeze_collection(collection_id: u64, reason: String) -> Result<Collection, String> {
+    let caller = caller();
+    let mut collection = get(collection_id)?;
+    if collection.owner != caller && !is_admin(caller) {
+        return Err("Only the collection owner or an admin can freeze this collection".to_string());
+    }
+    let reason = reason.trim().to_string();
+    if reason.is_empty() {
+        return Err("A reason is required".to_string());
+    }
+    limits::check_len("reason", &reason, limits::MAX_NOTE_BYTES)?;
+    if collection.freeze.is_some() {
+        return Err(format!("Collection {} is already frozen", collection_id));
+    }
+
+    collection.freeze = Some(CollectionFreeze {
+        collection_id,
+        frozen_by: caller,
+        frozen_at: ic_cdk::api::time(),
+        reason,
+        admin_hold: collection.owner != caller,
+    });
+    save(collection.clone());
+    audit::record("freeze_collection");
+    Ok(collection)
+}
+
+/// Collection owner or admin: lift a freeze (one imposed by an admin needs an admin)
+#[update(guard = "not_paused")]
+fn unfreeze_collection(collection_id: u64) -> Result<Collection, String> {
+    let caller = caller();
+    let mut collection = get(collection_id)?;
+    let freeze = collection
+        .freeze
+        .as_ref()
+        .ok_or_else(|| format!("Collection {} is not frozen", collection_id))?;
+    let by_owner = collection.owner == caller && !freeze.admin_hold;
+    if !by_owner && !is_admin(caller) {
+        return Err("Only the collection owner or an admin can lift this freeze".to_string());
+    }
+
+    collection.freeze = None;
+    save(collection.clone());
+    audit::record("unfreeze_collection");
+    Ok(collection)
+}
+
+/// Get a collection by ID
+#[query]
+fn get_collection(collection_id: u64) -> Result<Collection, String> {
+    get(collection_id)
+}
+
+/// The collections of a partner marketplace
+#[query]
+fn list_marketplace_collections(marketplace_id: String, offset: u64, limit: u64) -> Vec<Collection> {
+    COLLECTIONS.with(|c| {
+        c.borrow()
+            .iter()
+            .map(|(_, collection)| collection)
+            .filter(|collection| collection.marketplace_id.as_deref() == Some(marketplace_id.as_str()))
+            .skip(offset as usize)
+            .take(limit.min(100) as usize)
+            .collect()
+    })
+}
+
+/// List collections, optionally only those of one manufacturer
+#[query]
+fn list_collections(manufacturer: Option<String>, offset: u64, limit: u64) -> Vec<Collection> {
+    COLLECTIONS.with(|c| {
+        c.borrow()
+            .iter()
+            .map(|(_, collection)| collection)
+            .filter(|collection| {
+                manufacturer
+                    .as_ref()
+                    .map(|m| collection.manufacturer.eq_ignore_ascii_case(m))
+                    .unwrap_or(true)
+            })
+            .skip(offset as usize)
+            .take(limit.min(100) as usize)
+            .collect()
+    })
+}
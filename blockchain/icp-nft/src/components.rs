@@ -0,0 +1,250 @@
+//! Bundles of component NFTs.
+//!
+//! A product made of separately certified parts (a bicycle with its frame,
+//! fork and wheelset) is a tree of tokens: the owner of both attaches a
+//! component token to its parent. Attached components cannot change hands on
+//! their own; they move with their parent and must be detached first.
+//! `verify_bundle` walks the tree so a buyer can check that every part is
+//! genuine, not just the top-level product.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::pause::not_paused;
+use crate::verification::VerificationLevel;
+use crate::{apply_transfer, collections, find_by_serial, get_nft, memory, sale_lock, stolen, txlog, Memory, ProductNFT, TransactionType};
+
+/// Tokens in one bundle, the top-level product included.
+const MAX_BUNDLE_SIZE: usize = 64;
+/// Levels below the top-level product.
+const MAX_BUNDLE_DEPTH: u32 = 4;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ComponentStatus {
+    pub nft_id: u64,
+    pub serial_number: String,
+    pub product_name: String,
+    pub manufacturer: String,
+    /// `None` for the token `verify_bundle` started from.
+    pub parent_id: Option<u64>,
+    pub depth: u32,
+    pub verification_level: VerificationLevel,
+    pub revoked: bool,
+    pub recalled: bool,
+    pub reported_stolen: bool,
+    /// Owned by the same principal as the token `verify_bundle` started from.
+    pub owner_matches: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct BundleVerification {
+    pub nft_id: u64,
+    /// The token this one is attached to, if any.
+    pub attached_to: Option<u64>,
+    /// No part is revoked or reported stolen or lost, and one principal owns them all.
+    pub authentic: bool,
+    /// The token and its components, depth first.
+    pub components: Vec<ComponentStatus>,
+}
+
+thread_local! {
+    // Component -> the token it is attached to
+    static PARENTS: RefCell<StableBTreeMap<u64, u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(40)));
+
+    // (parent, component) for every attached component
+    static CHILDREN: RefCell<StableBTreeMap<(u64, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(41)));
+}
+
+fn parent_of(nft_id: u64) -> Option<u64> {
+    PARENTS.with(|p| p.borrow().get(&nft_id))
+}
+
+fn children_of(nft_id: u64) -> Vec<u64> {
+    CHILDREN.with(|c| {
+        c.borrow()
+            .range((nft_id, 0)..=(nft_id, u64::MAX))
+            .map(|((_, child), _)| child)
+            .collect()
+    })
+}
+
+/// `nft_id` and everything attached below it, depth first, with each
+/// token's depth relative to `nft_id`.
+fn subtree(nft_id: u64) -> Vec<(u64, u32)> {
+    let mut nodes = Vec::new();
+    let mut stack = vec![(nft_id, 0)];
+    while let Some((id, depth)) = stack.pop() {
+        nodes.push((id, depth));
+        stack.extend(children_of(id).into_iter().rev().map(|child| (child, depth + 1)));
+    }
+    nodes
+}
+
+/// Top of the bundle `nft_id` belongs to, and how deep `nft_id` sits in it.
+fn root_of(mut nft_id: u64) -> (u64, u32) {
+    let mut depth = 0;
+    while let Some(parent) = parent_of(nft_id) {
+        nft_id = parent;
+        depth += 1;
+    }
+    (nft_id, depth)
+}
+
+/// Fail if the token is attached to a parent (it moves with the parent), or
+/// if a component below it cannot change hands.
+pub fn ensure_transferable(nft: &ProductNFT) -> Result<(), String> {
+    if let Some(parent) = parent_of(nft.nft_id) {
+        return Err(format!(
+            "NFT {} is a component of NFT {}; detach it first",
+            nft.nft_id, parent
+        ));
+    }
+    for (id, _) in subtree(nft.nft_id).into_iter().skip(1) {
+        let component = get_nft(id)?;
+        stolen::ensure_not_reported(&component)?;
+        collections::ensure_not_frozen(&component)?;
+    }
+    Ok(())
+}
+
+/// Fail if the token is part of a bundle, as a component or as a parent.
+pub fn ensure_unattached(nft: &ProductNFT) -> Result<(), String> {
+    if let Some(parent) = parent_of(nft.nft_id) {
+        return Err(format!("NFT {} is a component of NFT {}; detach it first", nft.nft_id, parent));
+    }
+    if !children_of(nft.nft_id).is_empty() {
+        return Err(format!("NFT {} has components attached; detach them first", nft.nft_id));
+    }
+    Ok(())
+}
+
+/// Move the components attached to `nft` to its new owner. Called by
+/// `apply_transfer` once `nft` itself has moved.
+pub fn follow_parent(nft: &ProductNFT, transaction_type: TransactionType) {
+    for child in children_of(nft.nft_id) {
+        let Ok(mut component) = get_nft(child) else { continue };
+        if component.owner != nft.owner {
+            apply_transfer(
+                &mut component,
+                nft.owner,
+                transaction_type,
+                Some(format!("bundled with {}", nft.serial_number)),
+                None,
+            );
+        }
+    }
+}
+
+/// Owner: attach the token registered under `component_serial` to `parent_id`.
+/// The caller must own both.
+#[update(guard = "not_paused")]
+fn attach_component(parent_id: u64, component_serial: String) -> Result<(), String> {
+    let caller = caller();
+    let parent = get_nft(parent_id)?;
+    let component = find_by_serial(&component_serial)?;
+    if parent.owner != caller || component.owner != caller {
+        return Err("Only the owner of both tokens can attach a component".to_string());
+    }
+    if component.nft_id == parent.nft_id {
+        return Err("A token cannot be its own component".to_string());
+    }
+    if let Some(current) = parent_of(component.nft_id) {
+        return Err(format!("NFT {} is already a component of NFT {}", component.nft_id, current));
+    }
+    for nft in [&parent, &component] {
+        sale_lock::ensure_unlocked(nft)?;
+        stolen::ensure_not_reported(nft)?;
+    }
+
+    let (root, parent_depth) = root_of(parent_id);
+    if root == component.nft_id {
+        return Err(format!("NFT {} already contains NFT {}", component.nft_id, parent_id));
+    }
+    let added = subtree(component.nft_id);
+    let added_depth = added.iter().map(|(_, depth)| *depth).max().unwrap_or(0);
+    if parent_depth + 1 + added_depth > MAX_BUNDLE_DEPTH {
+        return Err(format!("Bundles can be at most {} levels deep", MAX_BUNDLE_DEPTH));
+    }
+    if subtree(root).len() + added.len() > MAX_BUNDLE_SIZE {
+        return Err(format!("A bundle can hold at most {} tokens", MAX_BUNDLE_SIZE));
+    }
+
+    PARENTS.with(|p| p.borrow_mut().insert(component.nft_id, parent_id));
+    CHILDREN.with(|c| c.borrow_mut().insert((parent_id, component.nft_id), ()));
+    txlog::append(
+        txlog::TxKind::MetadataUpdate,
+        parent.nft_id,
+        &parent.serial_number,
+        None,
+        None,
+        Some(format!("component {} attached", component.serial_number)),
+    );
+    Ok(())
+}
+
+/// Owner: detach a component from the token it is attached to
+#[update(guard = "not_paused")]
+fn detach_component(component_id: u64) -> Result<(), String> {
+    let component = get_nft(component_id)?;
+    if component.owner != caller() {
+        return Err("Only the owner can detach a component".to_string());
+    }
+    let parent_id = parent_of(component_id)
+        .ok_or_else(|| format!("NFT {} is not attached to another token", component_id))?;
+    let parent = get_nft(parent_id)?;
+    sale_lock::ensure_unlocked(&parent)?;
+
+    PARENTS.with(|p| p.borrow_mut().remove(&component_id));
+    CHILDREN.with(|c| c.borrow_mut().remove(&(parent_id, component_id)));
+    txlog::append(
+        txlog::TxKind::MetadataUpdate,
+        parent.nft_id,
+        &parent.serial_number,
+        None,
+        None,
+        Some(format!("component {} detached", component.serial_number)),
+    );
+    Ok(())
+}
+
+fn status(nft: &ProductNFT, parent_id: Option<u64>, depth: u32, owner: Principal) -> ComponentStatus {
+    ComponentStatus {
+        nft_id: nft.nft_id,
+        serial_number: nft.serial_number.clone(),
+        product_name: nft.metadata.product_name.clone(),
+        manufacturer: nft.metadata.manufacturer.clone(),
+        parent_id,
+        depth,
+        verification_level: nft.verification_level,
+        revoked: nft.revoked,
+        recalled: nft.recall.is_some(),
+        reported_stolen: nft.stolen.is_some(),
+        owner_matches: nft.owner == owner,
+    }
+}
+
+/// Verify a product and, recursively, every component attached to it
+#[query]
+fn verify_bundle(serial_number: String) -> Result<BundleVerification, String> {
+    let top = find_by_serial(&serial_number)?;
+    let mut components = Vec::new();
+    for (id, depth) in subtree(top.nft_id) {
+        let nft = get_nft(id)?;
+        let parent_id = if depth == 0 { None } else { parent_of(id) };
+        components.push(status(&nft, parent_id, depth, top.owner));
+    }
+    Ok(BundleVerification {
+        nft_id: top.nft_id,
+        attached_to: parent_of(top.nft_id),
+        authentic: components
+            .iter()
+            .all(|c| !c.revoked && !c.reported_stolen && c.owner_matches),
+        components,
+    })
+}
@@ -0,0 +1,149 @@
+//! Temporary custodian delegation.
+//!
+//! An owner can hand a unit to a repair shop or consignment store without
+//! transferring the token: `delegate_custody` names a custodian until a
+//! deadline. While the delegation is active the custodian can add service
+//! records and is reported as an authorized holder; it cannot transfer the
+//! token. Delegations end when they expire, when the owner or custodian
+//! revokes them, or when the token changes hands. Expired delegations are
+//! treated as absent immediately and cleared from the token by the
+//! maintenance job.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::pause::not_paused;
+use crate::{get_nft, memory, privacy, save_nft, stolen, txlog, Memory, ProductNFT};
+
+/// Longest delegation `delegate_custody` accepts.
+const MAX_CUSTODY_NANOS: u64 = 365 * 24 * 60 * 60 * 1_000_000_000;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Custody {
+    pub custodian: Principal,
+    pub delegated_by: Principal,
+    pub delegated_at: u64,
+    pub expires_at: u64,
+}
+
+thread_local! {
+    // (expires_at, nft_id) for delegations not yet cleared
+    static EXPIRY_INDEX: RefCell<StableBTreeMap<(u64, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(39)));
+}
+
+/// The token's delegation, if it has not expired.
+pub fn active(nft: &ProductNFT) -> Option<&Custody> {
+    nft.custody
+        .as_ref()
+        .filter(|custody| custody.expires_at > ic_cdk::api::time())
+}
+
+/// Whether `principal` currently holds the token as its custodian.
+pub fn is_custodian(nft: &ProductNFT, principal: Principal) -> bool {
+    active(nft).is_some_and(|custody| custody.custodian == principal)
+}
+
+/// Drop the token's delegation (it is changing hands). Does not save.
+pub fn clear(nft: &mut ProductNFT) {
+    if let Some(custody) = nft.custody.take() {
+        unschedule(nft.nft_id, &custody);
+    }
+}
+
+fn unschedule(nft_id: u64, custody: &Custody) {
+    EXPIRY_INDEX.with(|index| index.borrow_mut().remove(&(custody.expires_at, nft_id)));
+}
+
+fn log(nft: &ProductNFT, memo: String) {
+    txlog::append(txlog::TxKind::MetadataUpdate, nft.nft_id, &nft.serial_number, None, None, Some(memo));
+}
+
+/// Clear up to `max` delegations that expired by `now`; returns how many.
+pub fn expire_due(now: u64, max: usize) -> usize {
+    let due: Vec<(u64, u64)> = EXPIRY_INDEX.with(|index| {
+        index.borrow()
+            .range(..(now, u64::MAX))
+            .take(max)
+            .map(|(key, _)| key)
+            .collect()
+    });
+
+    for key in &due {
+        EXPIRY_INDEX.with(|index| index.borrow_mut().remove(key));
+        let Ok(mut nft) = get_nft(key.1) else { continue };
+        if nft.custody.as_ref().is_some_and(|custody| custody.expires_at == key.0) {
+            let custody = nft.custody.take();
+            save_nft(&nft);
+            if let Some(custody) = custody {
+                log(&nft, format!("custody of {} expired", custody.custodian));
+            }
+        }
+    }
+    due.len()
+}
+
+/// Owner: let `custodian` hold the item until `expires_at` (nanoseconds since
+/// the epoch), replacing any current delegation
+#[update(guard = "not_paused")]
+fn delegate_custody(nft_id: u64, custodian: Principal, expires_at: u64) -> Result<ProductNFT, String> {
+    let caller = caller();
+    let mut nft = get_nft(nft_id)?;
+    if nft.owner != caller {
+        return Err("Only the owner can delegate custody".to_string());
+    }
+    if custodian == nft.owner || custodian == Principal::anonymous() {
+        return Err("The custodian must be another, authenticated principal".to_string());
+    }
+    let now = ic_cdk::api::time();
+    if expires_at <= now {
+        return Err("Custody must expire in the future".to_string());
+    }
+    if expires_at - now > MAX_CUSTODY_NANOS {
+        return Err("Custody can be delegated for at most 365 days".to_string());
+    }
+    stolen::ensure_not_reported(&nft)?;
+
+    clear(&mut nft);
+    nft.custody = Some(Custody {
+        custodian,
+        delegated_by: caller,
+        delegated_at: now,
+        expires_at,
+    });
+    EXPIRY_INDEX.with(|index| index.borrow_mut().insert((expires_at, nft_id), ()));
+    save_nft(&nft);
+    log(&nft, format!("custody delegated to {}", custodian));
+
+    Ok(privacy::view(caller, nft))
+}
+
+/// Owner or custodian: end a delegation early (the item was returned)
+#[update(guard = "not_paused")]
+fn revoke_custody(nft_id: u64) -> Result<ProductNFT, String> {
+    let caller = caller();
+    let mut nft = get_nft(nft_id)?;
+    let custodian = active(&nft)
+        .map(|custody| custody.custodian)
+        .ok_or_else(|| format!("NFT {} has no active custodian", nft_id))?;
+    if caller != nft.owner && caller != custodian {
+        return Err("Only the owner or the custodian can end custody".to_string());
+    }
+
+    clear(&mut nft);
+    save_nft(&nft);
+    log(&nft, format!("custody of {} ended", custodian));
+
+    Ok(privacy::view(caller, nft))
+}
+
+/// Whether `principal` may present the item: its owner or an active custodian
+#[query]
+fn is_authorized_holder(nft_id: u64, principal: Principal) -> Result<bool, String> {
+    let nft = get_nft(nft_id)?;
+    Ok(nft.owner == principal || is_custodian(&nft, principal))
+}
@@ -0,0 +1,121 @@
+//! Dispute outcomes from marketplace arbitration.
+//!
+//! When a sale through the Solana escrow is disputed, the marketplace that
+//! arbitrated it (`Marketplace` role) records the outcome against the
+//! product, keyed by `(nft_id, sequence)` like service records. The history
+//! is public and stays with the unit across owners, so a product that keeps
+//! turning up in counterfeit disputes is visible to later buyers. Evidence
+//! stays off-chain; only its SHA-256 is stored.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::pause::not_paused;
+use crate::roles::{require_role, Role};
+use crate::sale_lock::MAX_ORDER_ID_LEN;
+use crate::{audit, find_by_serial, memory, txlog, Memory};
+
+pub use proofcart_types::DisputeOutcome;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DisputeRecord {
+    pub sequence: u64,
+    pub order_id: String,
+    pub outcome: DisputeOutcome,
+    /// SHA-256 of the off-chain evidence bundle.
+    pub evidence_hash: Vec<u8>,
+    pub marketplace: Principal,
+    pub recorded_at: u64,
+}
+
+candid_storable!(DisputeRecord);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DisputeHistory {
+    pub records: Vec<DisputeRecord>,
+    /// Records with outcome `CounterfeitConfirmed`.
+    pub counterfeit_findings: u64,
+}
+
+thread_local! {
+    static RECORDS: RefCell<StableBTreeMap<(u64, u64), DisputeRecord, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(42)));
+}
+
+fn history(nft_id: u64) -> Vec<DisputeRecord> {
+    RECORDS.with(|r| {
+        r.borrow()
+            .range((nft_id, 0)..=(nft_id, u64::MAX))
+            .map(|(_, record)| record)
+            .collect()
+    })
+}
+
+fn count_counterfeit(records: &[DisputeRecord]) -> u64 {
+    records
+        .iter()
+        .filter(|record| record.outcome == DisputeOutcome::CounterfeitConfirmed)
+        .count() as u64
+}
+
+/// How many disputes over a token found it counterfeit.
+pub fn counterfeit_findings(nft_id: u64) -> u64 {
+    count_counterfeit(&history(nft_id))
+}
+
+/// Marketplace: record the outcome of a disputed escrow order for a product
+#[update(guard = "not_paused")]
+fn record_dispute_outcome(
+    serial_number: String,
+    order_id: String,
+    outcome: DisputeOutcome,
+    evidence_hash: Vec<u8>,
+) -> Result<DisputeRecord, String> {
+    require_role(&[Role::Marketplace])?;
+    let nft = find_by_serial(&serial_number)?;
+    if order_id.is_empty() || order_id.len() > MAX_ORDER_ID_LEN {
+        return Err(format!("Order id must be 1-{} bytes", MAX_ORDER_ID_LEN));
+    }
+    if evidence_hash.len() != 32 {
+        return Err("Evidence hash must be a 32-byte SHA-256 digest".to_string());
+    }
+
+    let records = history(nft.nft_id);
+    if records.iter().any(|record| record.order_id == order_id) {
+        return Err(format!("A dispute outcome for order {} is already recorded", order_id));
+    }
+    let record = DisputeRecord {
+        sequence: records.last().map_or(0, |record| record.sequence + 1),
+        order_id,
+        outcome,
+        evidence_hash,
+        marketplace: caller(),
+        recorded_at: ic_cdk::api::time(),
+    };
+    RECORDS.with(|r| {
+        r.borrow_mut().insert((nft.nft_id, record.sequence), record.clone());
+    });
+    txlog::append(
+        txlog::TxKind::MetadataUpdate,
+        nft.nft_id,
+        &nft.serial_number,
+        None,
+        None,
+        Some(format!("dispute {}: {:?}", record.order_id, outcome)),
+    );
+
+    audit::record("record_dispute_outcome");
+    Ok(record)
+}
+
+/// Dispute outcomes recorded for a product, oldest first
+#[query]
+fn get_dispute_history(serial_number: String) -> Result<DisputeHistory, String> {
+    let nft = find_by_serial(&serial_number)?;
+    let records = history(nft.nft_id);
+    Ok(DisputeHistory { counterfeit_findings: count_counterfeit(&records), records })
+}
@@ -0,0 +1,271 @@
+//! On-chain confirmation of Solana escrows before a sale settles.
+//!
+//! `transfer_from` reads the order's escrow account from a Solana RPC node
+//! with an HTTPS outcall and only moves the token once the escrow is
+//! `Released`. Until an admin configures the check, no sale settles. The marketplace still drives settlement, but a
+//! buggy or compromised marketplace can no longer hand a token to a buyer
+//! whose payment was refunded or never made. The RPC node is trusted to
+//! report finalized state; every replica queries it and must agree.
+//!
+//! Tokens in a partner marketplace's collections are sold through that
+//! marketplace's escrows, whose order ids live in its own namespace.
+
+use base64ct::{Base64, Encoding};
+use candid::{CandidType, Nat};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use ic_cdk::api::management_canister::http_request::{
+    self as outcall, CanisterHttpRequestArgument, HttpHeader, HttpMethod, TransformArgs, TransformContext,
+};
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableCell;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+use crate::pause::not_paused;
+use crate::roles::require_admin;
+use crate::{audit, memory, Memory};
+
+/// An escrow account is under 150 bytes; the RPC envelope adds a few hundred.
+const MAX_RESPONSE_BYTES: u64 = 4_096;
+/// Enough for a 4KB response on a 34-node subnet; unused cycles are refunded.
+const OUTCALL_CYCLES: u128 = 2_000_000_000;
+
+/// Position of `Released` in the program's `EscrowStatus`.
+const STATUS_RELEASED: u8 = 2;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EscrowCheckConfig {
+    /// HTTPS JSON-RPC endpoint, e.g. `https://api.mainnet-beta.solana.com`.
+    /// With `None`, `transfer_from` fails.
+    pub rpc_url: Option<String>,
+    /// Base58 id of the escrow program.
+    pub program_id: String,
+}
+
+candid_storable!(EscrowCheckConfig);
+
+thread_local! {
+    static CONFIG: RefCell<StableCell<EscrowCheckConfig, Memory>> = RefCell::new(
+        StableCell::init(memory(48), EscrowCheckConfig::default())
+            .expect("Failed to initialize escrow check config")
+    );
+}
+
+fn config() -> EscrowCheckConfig {
+    CONFIG.with(|c| c.borrow().get().clone())
+}
+
+fn decode_pubkey(address: &str) -> Result<[u8; 32], String> {
+    bs58::decode(address)
+        .into_vec()
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("Invalid Solana address {}", address))
+}
+
+/// The PDA of `seeds` under the program: the first bump from 255 down whose
+/// address is off the Ed25519 curve.
+fn program_address(program_id: &[u8; 32], seeds: &[&[u8]]) -> Option<[u8; 32]> {
+    (0..=u8::MAX).rev().find_map(|bump| {
+        let mut hasher = Sha256::new();
+        for seed in seeds {
+            hasher.update(seed);
+        }
+        let address: [u8; 32] = hasher
+            .chain_update([bump])
+            .chain_update(program_id)
+            .chain_update(b"ProgramDerivedAddress")
+            .finalize()
+            .into();
+        CompressedEdwardsY(address).decompress().is_none().then_some(address)
+    })
+}
+
+/// The escrow PDA: `["escrow", order_id]` for ProofCart's own orders,
+/// `["escrow", marketplace, order_id]` for a partner marketplace's, where
+/// `marketplace` is the PDA `["marketplace", marketplace_id]`.
+fn escrow_address(program_id: &[u8; 32], marketplace_id: Option<&str>, order_id: &str) -> Option<[u8; 32]> {
+    match marketplace_id {
+        None => program_address(program_id, &[b"escrow", order_id.as_bytes()]),
+        Some(marketplace_id) => {
+            let marketplace = program_address(program_id, &[b"marketplace", marketplace_id.as_bytes()])?;
+            program_address(program_id, &[b"escrow", &marketplace, order_id.as_bytes()])
+        }
+    }
+}
+
+/// Status byte of an `Escrow` account's data, after checking it is one and
+/// belongs to `order_id`. Layout: discriminator, buyer, seller, order id
+/// (u32 length + bytes), amount, status, bump.
+fn escrow_status(data: &[u8], order_id: &str) -> Result<u8, String> {
+    let discriminator = &Sha256::digest(b"account:Escrow")[..8];
+    if data.get(..8) != Some(discriminator) {
+        return Err("Account is not an escrow".to_string());
+    }
+    let len_at = 8 + 32 + 32;
+    let len = data
+        .get(len_at..len_at + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+        .ok_or_else(|| "Escrow account is truncated".to_string())?;
+    let id_at = len_at + 4;
+    if data.get(id_at..id_at + len) != Some(order_id.as_bytes()) {
+        return Err(format!("Escrow account is not for order {}", order_id));
+    }
+    data.get(id_at + len + 8)
+        .copied()
+        .ok_or_else(|| "Escrow account is truncated".to_string())
+}
+
+/// Fail unless the escrow of `order_id`, in the partner marketplace
+/// `marketplace_id` if given, is finalized as `Released`. Fails when no RPC
+/// URL is configured.
+pub async fn ensure_released(order_id: &str, marketplace_id: Option<&str>) -> Result<(), String> {
+    let config = config();
+    let Some(rpc_url) = config.rpc_url else {
+        return Err("Sales cannot settle until an admin configures the escrow check".to_string());
+    };
+    let program_id = decode_pubkey(&config.program_id)?;
+    let address = escrow_address(&program_id, marketplace_id, order_id)
+        .ok_or_else(|| format!("No escrow address for order {}", order_id))?;
+
+    let body = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getAccountInfo",
+        "params": [
+            bs58::encode(address).into_string(),
+            {"encoding": "base64", "commitment": "finalized"},
+        ],
+    });
+    let request = CanisterHttpRequestArgument {
+        url: rpc_url,
+        max_response_bytes: Some(MAX_RESPONSE_BYTES),
+        method: HttpMethod::POST,
+        headers: vec![HttpHeader {
+            name: "Content-Type".to_string(),
+            value: "application/json".to_string(),
+        }],
+        body: Some(body.to_string().into_bytes()),
+        transform: Some(TransformContext::from_name("transform_escrow_response".to_string(), vec![])),
+    };
+    let (response,) = outcall::http_request(request, OUTCALL_CYCLES)
+        .await
+        .map_err(|(code, msg)| format!("Escrow check failed: {:?} {}", code, msg))?;
+    if response.status != 200u64 {
+        return Err(format!("Escrow check returned HTTP {}", response.status));
+    }
+
+    let account: Value = serde_json::from_slice(&response.body)
+        .map_err(|_| "Escrow check returned an unreadable response".to_string())?;
+    if account.is_null() {
+        return Err(format!("No finalized escrow for order {}", order_id));
+    }
+    if account["owner"].as_str() != Some(config.program_id.as_str()) {
+        return Err(format!("Escrow account of order {} is not owned by the escrow program", order_id));
+    }
+    let data = account["data"]
+        .as_str()
+        .and_then(|data| Base64::decode_vec(data).ok())
+        .ok_or_else(|| "Escrow check returned unreadable account data".to_string())?;
+    if escrow_status(&data, order_id)? != STATUS_RELEASED {
+        return Err(format!("The escrow of order {} is not released", order_id));
+    }
+    Ok(())
+}
+
+/// Keep only the account's owner and data, so replicas that saw different
+/// slots or response headers still agree. Anything unexpected becomes a 502.
+#[query]
+fn transform_escrow_response(args: TransformArgs) -> outcall::HttpResponse {
+    let account = serde_json::from_slice::<Value>(&args.response.body)
+        .ok()
+        .and_then(|body| body.get("result").map(|result| result["value"].clone()))
+        .map(|value| match value {
+            Value::Null => Value::Null,
+            value => json!({"owner": value["owner"], "data": value["data"][0]}),
+        });
+    match account {
+        Some(account) if args.response.status == 200u64 => outcall::HttpResponse {
+            status: args.response.status,
+            headers: vec![],
+            body: account.to_string().into_bytes(),
+        },
+        _ => outcall::HttpResponse {
+            status: Nat::from(502u64),
+            headers: vec![],
+            body: vec![],
+        },
+    }
+}
+
+/// Admin: set the RPC node that confirms released Solana escrows before
+/// `transfer_from` (`rpc_url = None` stops all settlements)
+#[update(guard = "not_paused")]
+fn set_escrow_check(config: EscrowCheckConfig) -> Result<EscrowCheckConfig, String> {
+    require_admin()?;
+    if let Some(url) = &config.rpc_url {
+        if !url.starts_with("https://") {
+            return Err("RPC URL must use https://".to_string());
+        }
+        decode_pubkey(&config.program_id)?;
+    }
+    CONFIG.with(|c| {
+        c.borrow_mut()
+            .set(config.clone())
+            .expect("Failed to persist escrow check config");
+    });
+    audit::record("set_escrow_check");
+    Ok(config)
+}
+
+/// Solana escrow check configuration
+#[query]
+fn get_escrow_check() -> EscrowCheckConfig {
+    config()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escrow_data(order_id: &str, status: u8) -> Vec<u8> {
+        let mut data = Sha256::digest(b"account:Escrow")[..8].to_vec();
+        data.extend_from_slice(&[1; 32]);
+        data.extend_from_slice(&[2; 32]);
+        data.extend_from_slice(&(order_id.len() as u32).to_le_bytes());
+        data.extend_from_slice(order_id.as_bytes());
+        data.extend_from_slice(&250_000_000u64.to_le_bytes());
+        data.push(status);
+        data.push(254);
+        data
+    }
+
+    #[test]
+    fn reads_the_status_of_the_right_order() {
+        assert_eq!(escrow_status(&escrow_data("ORD-1", STATUS_RELEASED), "ORD-1"), Ok(STATUS_RELEASED));
+        assert_eq!(escrow_status(&escrow_data("ORD-1", 3), "ORD-1"), Ok(3));
+        assert!(escrow_status(&escrow_data("ORD-1", STATUS_RELEASED), "ORD-2").is_err());
+        assert!(escrow_status(&escrow_data("ORD-1", STATUS_RELEASED)[..80], "ORD-1").is_err());
+        let mut other = escrow_data("ORD-1", STATUS_RELEASED);
+        other[0] ^= 1;
+        assert!(escrow_status(&other, "ORD-1").is_err());
+    }
+
+    #[test]
+    fn escrow_addresses_are_off_curve() {
+        let address = escrow_address(&[7; 32], None, "ORD-1").unwrap();
+        assert!(CompressedEdwardsY(address).decompress().is_none());
+        assert_ne!(escrow_address(&[7; 32], None, "ORD-2"), Some(address));
+    }
+
+    #[test]
+    fn marketplace_escrows_have_their_own_addresses() {
+        let platform = escrow_address(&[7; 32], None, "ORD-1").unwrap();
+        let acme = escrow_address(&[7; 32], Some("acme"), "ORD-1").unwrap();
+        assert!(CompressedEdwardsY(acme).decompress().is_none());
+        assert_ne!(acme, platform);
+        assert_ne!(escrow_address(&[7; 32], Some("globex"), "ORD-1"), Some(acme));
+    }
+}
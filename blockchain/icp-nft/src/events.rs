@@ -0,0 +1,87 @@
+//! Structured operational events.
+//!
+//! Mints, transfers, burns, failed mints and transfers, and rate-limit hits are
+//! written to the canister log as JSON lines (`dfx canister logs`) and kept in
+//! a heap ring buffer of the latest events that Support can query, so an
+//! incident can be investigated without shipping temporary logging. The
+//! buffer does not survive upgrades; the canister log does.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::query;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+
+use crate::roles::{require_role, Role};
+
+const MAX_BUFFERED_EVENTS: usize = 1_000;
+const MAX_EVENTS_PER_CALL: u64 = 200;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    Mint,
+    Transfer,
+    Burn,
+    Failure,
+    RateLimited,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CanisterEvent {
+    /// Increases by one per event since the last upgrade.
+    pub seq: u64,
+    pub timestamp: u64,
+    pub kind: EventKind,
+    pub caller: Principal,
+    pub nft_id: Option<u64>,
+    pub message: String,
+}
+
+thread_local! {
+    static BUFFER: RefCell<VecDeque<CanisterEvent>> = RefCell::new(VecDeque::with_capacity(MAX_BUFFERED_EVENTS));
+    static NEXT_SEQ: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Log an event and keep it in the ring buffer.
+pub fn emit(kind: EventKind, nft_id: Option<u64>, message: String) {
+    let event = CanisterEvent {
+        seq: NEXT_SEQ.with(|seq| seq.replace(seq.get() + 1)),
+        timestamp: ic_cdk::api::time(),
+        kind,
+        caller: caller(),
+        nft_id,
+        message,
+    };
+    if let Ok(line) = serde_json::to_string(&event) {
+        ic_cdk::println!("{}", line);
+    }
+    BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        if buffer.len() == MAX_BUFFERED_EVENTS {
+            buffer.pop_front();
+        }
+        buffer.push_back(event);
+    });
+}
+
+/// Log a failed call to `method`.
+pub fn failure(method: &str, nft_id: Option<u64>, error: &str) {
+    emit(EventKind::Failure, nft_id, format!("{}: {}", method, error));
+}
+
+/// Support or SuperAdmin: buffered events with `seq` after `after`, oldest first, at most 200
+#[query]
+fn get_events(after: Option<u64>, limit: u64) -> Result<Vec<CanisterEvent>, String> {
+    require_role(&[Role::Support])?;
+    let first = after.map_or(0, |after| after.saturating_add(1));
+    Ok(BUFFER.with(|buffer| {
+        buffer
+            .borrow()
+            .iter()
+            .filter(|event| event.seq >= first)
+            .take(limit.min(MAX_EVENTS_PER_CALL) as usize)
+            .cloned()
+            .collect()
+    }))
+}
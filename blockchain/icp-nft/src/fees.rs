@@ -0,0 +1,165 @@
+//! Registry fees paid in an ICRC-1 token (ICP, ckUSDC, ...), collected with
+//! `icrc2_transfer_from` from an allowance the payer granted the canister.
+//!
+//! A mint that fails after its fee was collected leaves the minter a credit,
+//! which pays for its next mint instead of the ledger.
+
+use candid::{CandidType, Nat, Principal};
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::{StableBTreeMap, StableCell};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::ledger;
+use crate::pause::not_paused;
+use crate::roles::require_admin;
+use crate::{audit, memory, Memory, ProductNFT};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FeeConfig {
+    /// Ledger the fees are paid on; no fees are charged while unset.
+    pub ledger: Option<Principal>,
+    pub mint_fee: Nat,
+    /// Manufacturers that mint for free.
+    pub exempt_minters: Vec<Principal>,
+    /// Charged on `transfer_nft`, except for the minter's first transfer.
+    /// `None` (or zero) charges nothing.
+    pub transfer_fee: Option<Nat>,
+}
+
+candid_storable!(FeeConfig);
+
+thread_local! {
+    static FEE_CONFIG: RefCell<StableCell<FeeConfig, Memory>> = RefCell::new(
+        StableCell::init(memory(9), FeeConfig::default())
+            .expect("Failed to initialize fee config")
+    );
+
+    // Minter -> mint fees paid for mints that then failed
+    static MINT_CREDITS: RefCell<StableBTreeMap<Principal, u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(52)));
+}
+
+pub fn config() -> FeeConfig {
+    FEE_CONFIG.with(|c| c.borrow().get().clone())
+}
+
+fn set_config(config: FeeConfig) {
+    FEE_CONFIG.with(|c| {
+        c.borrow_mut().set(config).expect("Failed to persist fee config");
+    });
+}
+
+/// Collect the mint fee from `minter` unless fees are off or it is exempt,
+/// using up one of its credits first. Returns whether a fee was paid.
+pub async fn charge_mint_fee(minter: Principal, serial_number: &str) -> Result<bool, String> {
+    let config = config();
+    let ledger = match config.ledger {
+        Some(ledger) if config.mint_fee > Nat::from(0u64) && !config.exempt_minters.contains(&minter) => ledger,
+        _ => return Ok(false),
+    };
+    if take_mint_credit(minter) {
+        return Ok(true);
+    }
+    ledger::collect(ledger, minter, config.mint_fee, &format!("mint:{}", serial_number)).await?;
+    Ok(true)
+}
+
+/// Credit `minter` with a paid mint fee whose mint then failed.
+pub fn credit_mint_fee(minter: Principal) {
+    MINT_CREDITS.with(|c| {
+        let mut credits = c.borrow_mut();
+        let count = credits.get(&minter).unwrap_or(0);
+        credits.insert(minter, count + 1);
+    });
+}
+
+fn take_mint_credit(minter: Principal) -> bool {
+    MINT_CREDITS.with(|c| {
+        let mut credits = c.borrow_mut();
+        match credits.get(&minter) {
+            Some(count) if count > 1 => {
+                credits.insert(minter, count - 1);
+                true
+            }
+            Some(_) => {
+                credits.remove(&minter);
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// Collect the transfer fee from `owner` unless fees are off or this is the
+/// manufacturer handing a never-transferred token to its first owner.
+pub async fn charge_transfer_fee(owner: Principal, nft: &ProductNFT) -> Result<(), String> {
+    let config = config();
+    let first_sale = owner == nft.minter() && nft.ownership_history.len() == 1;
+    let (ledger, fee) = match (config.ledger, config.transfer_fee) {
+        (Some(ledger), Some(fee)) if fee > 0u64 && !first_sale => (ledger, fee),
+        _ => return Ok(()),
+    };
+    ledger::collect(ledger, owner, fee, &format!("transfer:{}", nft.nft_id)).await?;
+    Ok(())
+}
+
+/// Admin: configure the fee ledger and mint fee (ledger `None` disables fees)
+#[update(guard = "not_paused")]
+fn set_mint_fee(ledger: Option<Principal>, mint_fee: Nat) -> Result<(), String> {
+    require_admin()?;
+    let mut config = config();
+    config.ledger = ledger;
+    config.mint_fee = mint_fee;
+    set_config(config);
+    audit::record("set_mint_fee");
+    Ok(())
+}
+
+/// Admin: set the transfer fee, charged on the ledger set with `set_mint_fee`
+/// (zero disables it)
+#[update(guard = "not_paused")]
+fn set_transfer_fee(transfer_fee: Nat) -> Result<(), String> {
+    require_admin()?;
+    let mut config = config();
+    config.transfer_fee = Some(transfer_fee);
+    set_config(config);
+    audit::record("set_transfer_fee");
+    Ok(())
+}
+
+/// Admin: add or remove a manufacturer from the fee allowlist
+#[update(guard = "not_paused")]
+fn set_fee_exemption(minter: Principal, exempt: bool) -> Result<(), String> {
+    require_admin()?;
+    let mut config = config();
+    config.exempt_minters.retain(|p| *p != minter);
+    if exempt {
+        config.exempt_minters.push(minter);
+    }
+    set_config(config);
+    audit::record("set_fee_exemption");
+    Ok(())
+}
+
+/// Current fee configuration
+#[query]
+fn get_fee_config() -> FeeConfig {
+    config()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_mints_leave_one_credit_each() {
+        let minter = Principal::from_slice(&[7; 29]);
+        assert!(!take_mint_credit(minter));
+        credit_mint_fee(minter);
+        credit_mint_fee(minter);
+        assert!(take_mint_credit(minter));
+        assert!(take_mint_credit(minter));
+        assert!(!take_mint_credit(minter));
+    }
+}
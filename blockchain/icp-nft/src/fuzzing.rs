@@ -0,0 +1,43 @@
+//! Entry points for the cargo-fuzz targets in `fuzz/`, built only with the
+//! `fuzzing` feature.
+//!
+//! Each function runs one of the canister's decoders over untrusted bytes.
+//! They must return an error rather than panic: a panic inside a canister
+//! traps and rolls back the whole call.
+
+use candid::DecoderConfig;
+use candid::utils::decode_args_with_config;
+
+use crate::payload::VerificationPayload;
+use crate::{limits, migrations, MintRequest};
+
+/// Decoder limits the `#[update]` / `#[query]` macros of ic-cdk 0.13 apply to
+/// endpoint arguments.
+pub fn endpoint_decoder_config() -> DecoderConfig {
+    let mut config = DecoderConfig::new();
+    config.set_skipping_quota(10_000);
+    config
+}
+
+/// Decode `mint_product_nft` arguments and run the checks `inspect_message`
+/// applies to them.
+pub fn mint_request(bytes: &[u8]) -> Result<(), String> {
+    let (request,): (MintRequest,) =
+        decode_args_with_config(bytes, &endpoint_decoder_config()).map_err(|e| e.to_string())?;
+    limits::check_mint_request(&request)?;
+    Ok(())
+}
+
+/// Decode a verification payload as `verify_payload` does. A payload that
+/// decodes must encode back to the same bytes.
+pub fn verification_payload(bytes: &[u8]) -> Result<(), String> {
+    let payload = VerificationPayload::decode(bytes)?;
+    assert_eq!(payload.encode()?, bytes, "payload did not round-trip");
+    Ok(())
+}
+
+/// Decode a stored token in any known layout, as `Storable::from_bytes` does
+/// before trapping on the error.
+pub fn stored_nft(bytes: &[u8]) -> Result<(), String> {
+    migrations::decode_nft(bytes).map(|_| ())
+}
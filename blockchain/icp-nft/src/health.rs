@@ -0,0 +1,153 @@
+//! Cycles and storage health.
+
+use candid::CandidType;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::Memory as _;
+use ic_stable_structures::StableCell;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::pause::not_paused;
+use crate::roles::require_admin;
+use crate::{audit, limits, memory, Memory, LAST_MEMORY_ID, NFTS};
+
+/// Default reserve below which mints are refused (0.2T cycles).
+const DEFAULT_LOW_CYCLES_THRESHOLD: u128 = 200_000_000_000;
+const WASM_PAGE_SIZE: u64 = 65_536;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct HealthState {
+    pub last_upgrade_at: Option<u64>,
+    pub low_cycles_threshold: u128,
+}
+
+impl Default for HealthState {
+    fn default() -> Self {
+        HealthState {
+            last_upgrade_at: None,
+            low_cycles_threshold: DEFAULT_LOW_CYCLES_THRESHOLD,
+        }
+    }
+}
+
+candid_storable!(HealthState);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CanisterStatusSummary {
+    pub cycles_balance: u128,
+    pub low_cycles_threshold: u128,
+    pub low_cycles: bool,
+    pub stable_memory_bytes: u64,
+    pub heap_memory_bytes: u64,
+    pub nft_count: u64,
+    pub last_upgrade_at: Option<u64>,
+}
+
+/// Bytes held by one virtual memory.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MemoryRegion {
+    pub memory_id: u8,
+    pub bytes: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MemoryUsage {
+    pub stable_bytes: u64,
+    pub heap_bytes: u64,
+    pub regions: Vec<MemoryRegion>,
+    pub nft_count: u64,
+    /// Bound on one stored token; `nft_count * max_nft_bytes` bounds the
+    /// token map's growth.
+    pub max_nft_bytes: u64,
+}
+
+thread_local! {
+    static HEALTH: RefCell<StableCell<HealthState, Memory>> = RefCell::new(
+        StableCell::init(memory(10), HealthState::default())
+            .expect("Failed to initialize health state")
+    );
+}
+
+fn state() -> HealthState {
+    HEALTH.with(|h| h.borrow().get().clone())
+}
+
+fn set_state(state: HealthState) {
+    HEALTH.with(|h| {
+        h.borrow_mut().set(state).expect("Failed to persist health state");
+    });
+}
+
+pub fn record_upgrade() {
+    let mut state = state();
+    state.last_upgrade_at = Some(ic_cdk::api::time());
+    set_state(state);
+}
+
+/// Refuse work that would eat into the cycles reserve.
+pub fn ensure_cycles() -> Result<(), String> {
+    let threshold = state().low_cycles_threshold;
+    let balance = ic_cdk::api::canister_balance128();
+    if balance < threshold {
+        return Err(format!(
+            "Canister cycles are low ({} < {}); minting is paused until it is topped up",
+            balance, threshold
+        ));
+    }
+    Ok(())
+}
+
+fn heap_memory_bytes() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        core::arch::wasm32::memory_size(0) as u64 * WASM_PAGE_SIZE
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        0
+    }
+}
+
+/// Cycles, memory and registry size at a glance
+#[query]
+fn canister_status_summary() -> CanisterStatusSummary {
+    let state = state();
+    let cycles_balance = ic_cdk::api::canister_balance128();
+    CanisterStatusSummary {
+        cycles_balance,
+        low_cycles_threshold: state.low_cycles_threshold,
+        low_cycles: cycles_balance < state.low_cycles_threshold,
+        stable_memory_bytes: ic_cdk::api::stable::stable64_size() * WASM_PAGE_SIZE,
+        heap_memory_bytes: heap_memory_bytes(),
+        nft_count: NFTS.with(|nfts| nfts.borrow().len()),
+        last_upgrade_at: state.last_upgrade_at,
+    }
+}
+
+/// Stable memory per structure, for capacity planning
+#[query]
+fn memory_usage() -> MemoryUsage {
+    MemoryUsage {
+        stable_bytes: ic_cdk::api::stable::stable64_size() * WASM_PAGE_SIZE,
+        heap_bytes: heap_memory_bytes(),
+        regions: (0..=LAST_MEMORY_ID)
+            .map(|memory_id| MemoryRegion {
+                memory_id,
+                bytes: memory(memory_id).size() * WASM_PAGE_SIZE,
+            })
+            .collect(),
+        nft_count: NFTS.with(|nfts| nfts.borrow().len()),
+        max_nft_bytes: u64::from(limits::MAX_NFT_BYTES),
+    }
+}
+
+/// Admin: set the cycles reserve below which mints are refused
+#[update(guard = "not_paused")]
+fn set_low_cycles_threshold(threshold: u128) -> Result<(), String> {
+    require_admin()?;
+    let mut state = state();
+    state.low_cycles_threshold = threshold;
+    set_state(state);
+    audit::record("set_low_cycles_threshold");
+    Ok(())
+}
@@ -0,0 +1,257 @@
+//! Public HTTP gateway interface.
+//!
+//! `GET /verify/<serial>` answers with a JSON verification result, or a small
+//! HTML page when the client asks for `text/html` (browsers following a
+//! printed QR code). Responses embed the data certificate and witness for the
+//! serial so clients can verify them independently of the boundary node.
+//! Responses carry no `IC-Certificate` header, because their bodies vary per
+//! request and cannot be certified ahead of time, so they are served over the
+//! `raw` domain.
+
+use candid::{CandidType, Deserialize};
+use ic_cdk_macros::query;
+use serde_json::json;
+
+use crate::burns::BurnReason;
+use crate::certification::{self, owner_hash};
+use crate::{burns, collections, disputes, find_by_serial, localization};
+use crate::stolen::LossKind;
+use crate::verification::VerificationLevel;
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    #[serde(with = "serde_bytes")]
+    pub body: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    #[serde(with = "serde_bytes")]
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    fn new(status_code: u16, content_type: &str, body: Vec<u8>) -> Self {
+        HttpResponse {
+            status_code,
+            headers: vec![
+                ("Content-Type".to_string(), content_type.to_string()),
+                ("Access-Control-Allow-Origin".to_string(), "*".to_string()),
+                ("Cache-Control".to_string(), "no-store".to_string()),
+            ],
+            body,
+        }
+    }
+
+    fn json(status_code: u16, value: serde_json::Value) -> Self {
+        Self::new(status_code, "application/json", value.to_string().into_bytes())
+    }
+
+    fn html(status_code: u16, page: String) -> Self {
+        Self::new(status_code, "text/html; charset=utf-8", page.into_bytes())
+    }
+}
+
+fn wants_html(req: &HttpRequest) -> bool {
+    if req.url.contains("format=html") {
+        return true;
+    }
+    if req.url.contains("format=json") {
+        return false;
+    }
+    req.headers.iter().any(|(name, value)| {
+        name.eq_ignore_ascii_case("accept") && value.contains("text/html")
+    })
+}
+
+/// Language from a `lang=` query parameter, else the first `Accept-Language` tag.
+fn requested_lang(req: &HttpRequest) -> Option<String> {
+    let query = req.url.split_once('?').map(|(_, query)| query).unwrap_or("");
+    let from_query = query.split('&').find_map(|pair| pair.strip_prefix("lang="));
+    let from_header = || {
+        req.headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("accept-language"))
+            .and_then(|(_, value)| value.split([',', ';']).next())
+    };
+    from_query
+        .or_else(from_header)
+        .and_then(|lang| localization::normalize_lang(lang).ok())
+}
+
+/// Minimal percent-decoding for the serial path segment.
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = segment.get(i + 1..i + 3)?;
+                out.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn burned_response(tombstone: burns::Tombstone, html: bool) -> HttpResponse {
+    if html {
+        let status = match tombstone.reason {
+            BurnReason::Destroyed => "Destroyed",
+            BurnReason::Counterfeit => "Counterfeit",
+            BurnReason::Duplicate => "Withdrawn (duplicate registration)",
+        };
+        return HttpResponse::html(
+            410,
+            format!(
+                "<!doctype html><html><head><meta charset=\"utf-8\"><title>ProofCart - {serial}</title></head><body>\
+                 <h1>{status}</h1>\
+                 <p>Serial: {serial}<br>Token #{id} was burned and is no longer registered.</p>\
+                 </body></html>",
+                status = status,
+                serial = escape_html(&tombstone.serial_number),
+                id = tombstone.nft_id,
+            ),
+        );
+    }
+    HttpResponse::json(
+        410,
+        json!({
+            "serial_number": tombstone.serial_number,
+            "found": false,
+            "burned": true,
+            "nft_id": tombstone.nft_id,
+            "burn_reason": format!("{:?}", tombstone.reason),
+            "burned_at": tombstone.burned_at,
+        }),
+    )
+}
+
+fn verify_response(serial_number: &str, html: bool, lang: Option<String>) -> HttpResponse {
+    if let Some(tombstone) = burns::find(serial_number) {
+        return burned_response(tombstone, html);
+    }
+    let mut nft = match find_by_serial(serial_number) {
+        Ok(nft) => nft,
+        Err(e) => {
+            return if html {
+                HttpResponse::html(
+                    404,
+                    format!(
+                        "<!doctype html><html><head><title>ProofCart</title></head><body>\
+                         <h1>Not registered</h1><p>{}</p></body></html>",
+                        escape_html(&e)
+                    ),
+                )
+            } else {
+                HttpResponse::json(404, json!({ "serial_number": serial_number, "found": false, "error": e }))
+            };
+        }
+    };
+
+    if let Some(lang) = lang {
+        nft.metadata = localization::localize(nft.metadata, &lang);
+    }
+
+    let reported = nft.stolen.as_ref().map(|report| report.kind);
+    let frozen = collections::freeze_of(&nft).is_some();
+
+    if html {
+        let status = match reported {
+            Some(LossKind::Stolen) => "Reported stolen",
+            Some(LossKind::Lost) => "Reported lost",
+            None if nft.revoked => "Verification revoked",
+            None if frozen => "Transfers frozen by the manufacturer",
+            None => match nft.verification_level {
+                VerificationLevel::SelfAttested => "Registered (self-attested)",
+                VerificationLevel::ManufacturerVerified => "Authentic (manufacturer verified)",
+                VerificationLevel::ThirdPartyAudited => "Authentic (third-party audited)",
+            },
+        };
+        return HttpResponse::html(
+            200,
+            format!(
+                "<!doctype html><html><head><meta charset=\"utf-8\"><title>ProofCart - {serial}</title></head><body>\
+                 <h1>{status}</h1>\
+                 <p><strong>{product}</strong> by {manufacturer}</p>\
+                 <p>Serial: {serial}<br>Token #{id}<br>Manufactured: {date}</p>\
+                 </body></html>",
+                status = status,
+                product = escape_html(&nft.metadata.product_name),
+                manufacturer = escape_html(&nft.metadata.manufacturer),
+                serial = escape_html(&nft.serial_number),
+                id = nft.nft_id,
+                date = escape_html(&nft.metadata.manufacture_date),
+            ),
+        );
+    }
+
+    HttpResponse::json(
+        200,
+        json!({
+            "serial_number": nft.serial_number,
+            "found": true,
+            "nft_id": nft.nft_id,
+            "verified": !nft.revoked,
+            "verification_level": format!("{:?}", nft.verification_level),
+            "reported": reported.map(|kind| format!("{:?}", kind)),
+            "transfers_frozen": frozen,
+            "counterfeit_disputes": disputes::counterfeit_findings(nft.nft_id),
+            "product_name": nft.metadata.product_name,
+            "manufacturer": nft.metadata.manufacturer,
+            "category": nft.metadata.category,
+            "manufacture_date": nft.metadata.manufacture_date,
+            "minted_at": nft.minted_at,
+            "owner_hash": hex(&owner_hash(&nft.owner)),
+            "certificate": certification::data_certificate().ok().map(|c| hex(&c)),
+            "witness": hex(&certification::witness(&nft.serial_number)),
+        }),
+    )
+}
+
+/// Serve public verification pages over the HTTP gateway
+#[query]
+fn http_request(req: HttpRequest) -> HttpResponse {
+    if req.method != "GET" && req.method != "HEAD" {
+        return HttpResponse::json(405, json!({ "error": "Method not allowed" }));
+    }
+
+    let path = req.url.split('?').next().unwrap_or("");
+    let html = wants_html(&req);
+    let lang = requested_lang(&req);
+
+    match path.strip_prefix("/verify/") {
+        Some(segment) if !segment.is_empty() && !segment.contains('/') => match percent_decode(segment) {
+            Some(serial_number) => verify_response(&serial_number, html, lang),
+            None => HttpResponse::json(400, json!({ "error": "Malformed serial number" })),
+        },
+        _ => HttpResponse::json(404, json!({ "error": "Not found", "usage": "/verify/<serial_number>" })),
+    }
+}
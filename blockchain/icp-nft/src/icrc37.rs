@@ -0,0 +1,437 @@
+//! ICRC-37 token approvals, so standard marketplaces can resell certificates.
+//!
+//! Token ids are the canister's `nft_id`s. Owners hold tokens on their
+//! default account only, so `from`/`to` accounts with a non-zero subaccount
+//! are rejected. Collection-level approvals are not supported, and requests
+//! are not deduplicated by `created_at_time`. All approvals on a token are
+//! dropped whenever it changes hands.
+
+use candid::{CandidType, Nat, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::ledger::Account;
+use crate::pause::not_paused;
+use crate::rate_limit::{self, RateLimitedMethod};
+use crate::{
+    apply_transfer, collections, components, fees, get_nft, memory, sale_lock, stolen, txlog, Memory, ProductNFT,
+    TransactionType, PENDING_TRANSFERS,
+};
+
+const MAX_APPROVALS_PER_TOKEN: u64 = 10;
+const MAX_REVOKE_APPROVALS: u64 = 100;
+const MAX_BATCH: usize = 100;
+const TX_WINDOW_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+const PERMITTED_DRIFT_NANOS: u64 = 2 * 60 * 1_000_000_000;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ApprovalInfo {
+    pub spender: Account,
+    pub from_subaccount: Option<Vec<u8>>,
+    pub expires_at: Option<u64>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TokenApproval {
+    pub token_id: Nat,
+    pub approval_info: ApprovalInfo,
+}
+
+candid_storable!(TokenApproval);
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ApproveTokenArg {
+    pub token_id: Nat,
+    pub approval_info: ApprovalInfo,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum ApproveTokenError {
+    InvalidSpender,
+    Unauthorized,
+    NonExistingTokenId,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RevokeTokenApprovalArg {
+    pub spender: Option<Account>,
+    pub from_subaccount: Option<Vec<u8>>,
+    pub token_id: Nat,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum RevokeTokenApprovalError {
+    ApprovalDoesNotExist,
+    Unauthorized,
+    NonExistingTokenId,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct IsApprovedArg {
+    pub spender: Account,
+    pub from_subaccount: Option<Vec<u8>>,
+    pub token_id: Nat,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TransferFromArg {
+    pub spender_subaccount: Option<Vec<u8>>,
+    pub from: Account,
+    pub to: Account,
+    pub token_id: Nat,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum TransferFromError {
+    InvalidRecipient,
+    Unauthorized,
+    NonExistingTokenId,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+/// Subset of the ICRC-3 `Value` type used by `icrc37_metadata` and the
+/// field projections in `projection`.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum Value {
+    Nat(Nat),
+    Text(String),
+    Array(Vec<Value>),
+}
+
+thread_local! {
+    // (token id, spender) -> approval
+    static APPROVALS: RefCell<StableBTreeMap<Vec<u8>, TokenApproval, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(22)));
+}
+
+fn token_prefix(nft_id: u64) -> Vec<u8> {
+    nft_id.to_be_bytes().to_vec()
+}
+
+fn approval_key(nft_id: u64, spender: Principal) -> Vec<u8> {
+    let mut key = token_prefix(nft_id);
+    key.extend_from_slice(spender.as_slice());
+    key
+}
+
+fn token_approvals(nft_id: u64) -> Vec<(Vec<u8>, TokenApproval)> {
+    let prefix = token_prefix(nft_id);
+    APPROVALS.with(|a| {
+        a.borrow()
+            .range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .collect()
+    })
+}
+
+/// Drop every approval on a token (called whenever it changes hands).
+pub fn clear_approvals(nft_id: u64) {
+    let keys: Vec<Vec<u8>> = token_approvals(nft_id).into_iter().map(|(key, _)| key).collect();
+    APPROVALS.with(|a| {
+        let mut approvals = a.borrow_mut();
+        for key in keys {
+            approvals.remove(&key);
+        }
+    });
+}
+
+fn is_default_subaccount(subaccount: &Option<Vec<u8>>) -> bool {
+    subaccount.as_ref().map_or(true, |s| s.iter().all(|b| *b == 0))
+}
+
+fn same_subaccount(a: &Option<Vec<u8>>, b: &Option<Vec<u8>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a == b,
+        (a, b) => is_default_subaccount(a) && is_default_subaccount(b),
+    }
+}
+
+pub fn parse_token_id(token_id: &Nat) -> Option<u64> {
+    u64::try_from(&token_id.0).ok()
+}
+
+enum TimeError {
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+}
+
+fn check_created_at(created_at_time: Option<u64>) -> Result<(), TimeError> {
+    let now = ic_cdk::api::time();
+    match created_at_time {
+        Some(t) if t.saturating_add(TX_WINDOW_NANOS + PERMITTED_DRIFT_NANOS) < now => Err(TimeError::TooOld),
+        Some(t) if t > now.saturating_add(PERMITTED_DRIFT_NANOS) => Err(TimeError::CreatedInFuture { ledger_time: now }),
+        _ => Ok(()),
+    }
+}
+
+fn active_approval(nft_id: u64, spender: &Account) -> Option<TokenApproval> {
+    APPROVALS
+        .with(|a| a.borrow().get(&approval_key(nft_id, spender.owner)))
+        .filter(|approval| same_subaccount(&approval.approval_info.spender.subaccount, &spender.subaccount))
+        .filter(|approval| approval.approval_info.expires_at.map_or(true, |t| t > ic_cdk::api::time()))
+}
+
+/// Whether the owner has approved `spender`'s default account for the token.
+pub fn is_approved(nft_id: u64, spender: Principal) -> bool {
+    active_approval(nft_id, &Account { owner: spender, subaccount: None }).is_some()
+}
+
+fn generic(message: &str) -> (Nat, String) {
+    (Nat::from(0u64), message.to_string())
+}
+
+fn approve_token(arg: ApproveTokenArg) -> Result<Nat, ApproveTokenError> {
+    let caller = caller();
+    let info = &arg.approval_info;
+    match check_created_at(Some(info.created_at_time)) {
+        Err(TimeError::TooOld) => return Err(ApproveTokenError::TooOld),
+        Err(TimeError::CreatedInFuture { ledger_time }) => {
+            return Err(ApproveTokenError::CreatedInFuture { ledger_time })
+        }
+        Ok(()) => {}
+    }
+    let nft_id = parse_token_id(&arg.token_id).ok_or(ApproveTokenError::NonExistingTokenId)?;
+    let nft = get_nft(nft_id).map_err(|_| ApproveTokenError::NonExistingTokenId)?;
+    if nft.owner != caller || !is_default_subaccount(&info.from_subaccount) {
+        return Err(ApproveTokenError::Unauthorized);
+    }
+    if info.spender.owner == caller || info.spender.owner == Principal::anonymous() {
+        return Err(ApproveTokenError::InvalidSpender);
+    }
+
+    let key = approval_key(nft_id, info.spender.owner);
+    let existing = token_approvals(nft_id);
+    if !existing.iter().any(|(k, _)| *k == key) && existing.len() as u64 >= MAX_APPROVALS_PER_TOKEN {
+        let (error_code, message) = generic("Too many approvals on this token");
+        return Err(ApproveTokenError::GenericError { error_code, message });
+    }
+
+    APPROVALS.with(|a| {
+        a.borrow_mut().insert(key, TokenApproval { token_id: arg.token_id, approval_info: arg.approval_info.clone() });
+    });
+    let index = txlog::append(
+        txlog::TxKind::Approve,
+        nft_id,
+        &nft.serial_number,
+        Some(caller),
+        Some(info.spender.owner),
+        None,
+    );
+    Ok(Nat::from(index))
+}
+
+fn revoke_token_approvals(arg: RevokeTokenApprovalArg) -> Result<Nat, RevokeTokenApprovalError> {
+    let caller = caller();
+    match check_created_at(arg.created_at_time) {
+        Err(TimeError::TooOld) => return Err(RevokeTokenApprovalError::TooOld),
+        Err(TimeError::CreatedInFuture { ledger_time }) => {
+            return Err(RevokeTokenApprovalError::CreatedInFuture { ledger_time })
+        }
+        Ok(()) => {}
+    }
+    let nft_id = parse_token_id(&arg.token_id).ok_or(RevokeTokenApprovalError::NonExistingTokenId)?;
+    let nft = get_nft(nft_id).map_err(|_| RevokeTokenApprovalError::NonExistingTokenId)?;
+    if nft.owner != caller || !is_default_subaccount(&arg.from_subaccount) {
+        return Err(RevokeTokenApprovalError::Unauthorized);
+    }
+
+    let spender = match &arg.spender {
+        Some(spender) => {
+            let key = approval_key(nft_id, spender.owner);
+            let removed = APPROVALS.with(|a| a.borrow_mut().remove(&key));
+            if removed.is_none() {
+                return Err(RevokeTokenApprovalError::ApprovalDoesNotExist);
+            }
+            Some(spender.owner)
+        }
+        None => {
+            if token_approvals(nft_id).is_empty() {
+                return Err(RevokeTokenApprovalError::ApprovalDoesNotExist);
+            }
+            clear_approvals(nft_id);
+            None
+        }
+    };
+    let index = txlog::append(txlog::TxKind::Revoke, nft_id, &nft.serial_number, Some(caller), spender, None);
+    Ok(Nat::from(index))
+}
+
+/// Transfer on behalf of an approved spender. Like `transfer_nft`, the owner
+/// pays the transfer fee and the token is held in `PENDING_TRANSFERS` while
+/// the payment is in flight.
+async fn transfer_from(arg: TransferFromArg) -> Result<Nat, TransferFromError> {
+    let caller = caller();
+    match check_created_at(arg.created_at_time) {
+        Err(TimeError::TooOld) => return Err(TransferFromError::TooOld),
+        Err(TimeError::CreatedInFuture { ledger_time }) => {
+            return Err(TransferFromError::CreatedInFuture { ledger_time })
+        }
+        Ok(()) => {}
+    }
+    let nft_id = parse_token_id(&arg.token_id).ok_or(TransferFromError::NonExistingTokenId)?;
+    if let Err(e) = rate_limit::check(caller, RateLimitedMethod::Transfer) {
+        let (error_code, message) = generic(&e);
+        return Err(TransferFromError::GenericError { error_code, message });
+    }
+    let nft = validate_transfer_from(nft_id, &arg, caller)?;
+
+    let reserved = PENDING_TRANSFERS.with(|p| p.borrow_mut().insert(nft_id));
+    if !reserved {
+        let (error_code, message) = generic(&format!("A transfer of NFT {} is already in progress", nft_id));
+        return Err(TransferFromError::GenericError { error_code, message });
+    }
+
+    let result = async {
+        if let Err(e) = fees::charge_transfer_fee(nft.owner, &nft).await {
+            let (error_code, message) = generic(&e);
+            return Err(TransferFromError::GenericError { error_code, message });
+        }
+        validate_transfer_from(nft_id, &arg, caller)
+    }
+    .await;
+
+    PENDING_TRANSFERS.with(|p| p.borrow_mut().remove(&nft_id));
+
+    let mut nft = result?;
+    let index = apply_transfer(&mut nft, arg.to.owner, TransactionType::Sale, Some("icrc37".to_string()), None);
+    Ok(Nat::from(index))
+}
+
+/// Checks that must pass before an ICRC-37 transfer (and again after the fee
+/// is paid); returns the token
+fn validate_transfer_from(
+    nft_id: u64,
+    arg: &TransferFromArg,
+    caller: Principal,
+) -> Result<ProductNFT, TransferFromError> {
+    let nft = get_nft(nft_id).map_err(|_| TransferFromError::NonExistingTokenId)?;
+
+    let spender = Account { owner: caller, subaccount: arg.spender_subaccount.clone() };
+    if arg.from.owner != nft.owner || !is_default_subaccount(&arg.from.subaccount) {
+        return Err(TransferFromError::Unauthorized);
+    }
+    if active_approval(nft_id, &spender).is_none() {
+        return Err(TransferFromError::Unauthorized);
+    }
+    if arg.to.owner == Principal::anonymous() || !is_default_subaccount(&arg.to.subaccount) {
+        return Err(TransferFromError::InvalidRecipient);
+    }
+    if let Err(e) = sale_lock::ensure_unlocked(&nft)
+        .and_then(|_| stolen::ensure_not_reported(&nft))
+        .and_then(|_| collections::ensure_not_frozen(&nft))
+        .and_then(|_| components::ensure_transferable(&nft))
+    {
+        let (error_code, message) = generic(&e);
+        return Err(TransferFromError::GenericError { error_code, message });
+    }
+    Ok(nft)
+}
+
+fn batch<A, T, E>(args: Vec<A>, batch_error: E, f: impl Fn(A) -> Result<T, E>) -> Vec<Option<Result<T, E>>> {
+    if args.len() > MAX_BATCH {
+        return vec![Some(Err(batch_error))];
+    }
+    args.into_iter().map(|arg| Some(f(arg))).collect()
+}
+
+fn batch_too_large() -> (Nat, String) {
+    generic(&format!("At most {} entries per batch", MAX_BATCH))
+}
+
+#[update(guard = "not_paused")]
+fn icrc37_approve_tokens(args: Vec<ApproveTokenArg>) -> Vec<Option<Result<Nat, ApproveTokenError>>> {
+    let (error_code, message) = batch_too_large();
+    batch(args, ApproveTokenError::GenericBatchError { error_code, message }, approve_token)
+}
+
+#[update(guard = "not_paused")]
+fn icrc37_revoke_token_approvals(
+    args: Vec<RevokeTokenApprovalArg>,
+) -> Vec<Option<Result<Nat, RevokeTokenApprovalError>>> {
+    let (error_code, message) = batch_too_large();
+    batch(args, RevokeTokenApprovalError::GenericBatchError { error_code, message }, revoke_token_approvals)
+}
+
+/// Transfers run one after another, since each may wait on the fee ledger.
+#[update(guard = "not_paused")]
+async fn icrc37_transfer_from(args: Vec<TransferFromArg>) -> Vec<Option<Result<Nat, TransferFromError>>> {
+    if args.len() > MAX_BATCH {
+        let (error_code, message) = batch_too_large();
+        return vec![Some(Err(TransferFromError::GenericBatchError { error_code, message }))];
+    }
+    let mut results = Vec::with_capacity(args.len());
+    for arg in args {
+        results.push(Some(transfer_from(arg).await));
+    }
+    results
+}
+
+#[query]
+fn icrc37_is_approved(args: Vec<IsApprovedArg>) -> Vec<bool> {
+    args.into_iter()
+        .map(|arg| {
+            let Some(nft_id) = parse_token_id(&arg.token_id) else { return false };
+            get_nft(nft_id).is_ok()
+                && is_default_subaccount(&arg.from_subaccount)
+                && active_approval(nft_id, &arg.spender).is_some()
+        })
+        .collect()
+}
+
+#[query]
+fn icrc37_get_token_approvals(token_id: Nat, prev: Option<TokenApproval>, take: Option<Nat>) -> Vec<TokenApproval> {
+    let Some(nft_id) = parse_token_id(&token_id) else { return vec![] };
+    let take = take
+        .and_then(|t| u64::try_from(&t.0).ok())
+        .unwrap_or(MAX_REVOKE_APPROVALS)
+        .min(MAX_REVOKE_APPROVALS) as usize;
+    let after = prev.map(|p| approval_key(nft_id, p.approval_info.spender.owner));
+    token_approvals(nft_id)
+        .into_iter()
+        .filter(|(key, _)| after.as_ref().map_or(true, |after| key > after))
+        .take(take)
+        .map(|(_, approval)| approval)
+        .collect()
+}
+
+#[query]
+fn icrc37_max_approvals_per_token_or_collection() -> Option<Nat> {
+    Some(Nat::from(MAX_APPROVALS_PER_TOKEN))
+}
+
+#[query]
+fn icrc37_max_revoke_approvals() -> Option<Nat> {
+    Some(Nat::from(MAX_REVOKE_APPROVALS))
+}
+
+#[query]
+fn icrc37_metadata() -> Vec<(String, Value)> {
+    vec![
+        (
+            "icrc37:max_approvals_per_token_or_collection".to_string(),
+            Value::Nat(Nat::from(MAX_APPROVALS_PER_TOKEN)),
+        ),
+        ("icrc37:max_revoke_approvals".to_string(), Value::Nat(Nat::from(MAX_REVOKE_APPROVALS))),
+    ]
+}
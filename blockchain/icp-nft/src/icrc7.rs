@@ -0,0 +1,29 @@
+//! ICRC-7 token metadata.
+//!
+//! Generic ICP NFT tooling (wallets, explorers) reads token attributes as the
+//! standard `Vec<(String, Value)>` map rather than our typed `ProductNFT`.
+//! Keys are the `projection` field names under a `proofcart:` namespace and
+//! do not change between versions; new fields only add keys.
+
+use candid::Nat;
+use ic_cdk::caller;
+use ic_cdk_macros::query;
+
+use crate::icrc37::{parse_token_id, Value};
+use crate::{get_nft, privacy, projection};
+
+const MAX_QUERY_BATCH: usize = 100;
+
+/// Metadata map of each token, `None` for unknown ids; at most 100 ids are read
+#[query]
+fn icrc7_token_metadata(token_ids: Vec<Nat>) -> Vec<Option<Vec<(String, Value)>>> {
+    let caller = caller();
+    token_ids
+        .iter()
+        .take(MAX_QUERY_BATCH)
+        .map(|token_id| {
+            let nft = get_nft(parse_token_id(token_id)?).ok()?;
+            Some(projection::metadata_map(&privacy::view(caller, nft)))
+        })
+        .collect()
+}
@@ -0,0 +1,59 @@
+//! Deduplication of retried mints.
+//!
+//! A minter may attach an idempotency key to a `MintRequest`. Retrying with the
+//! same key returns the token minted the first time instead of a "serial
+//! already exists" error, so integrations can tell a retry from a conflict.
+
+use candid::Principal;
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+
+use crate::{get_nft, memory, Memory, ProductNFT};
+
+pub const MAX_KEY_LEN: usize = 64;
+
+thread_local! {
+    static MINT_KEYS: RefCell<StableBTreeMap<Vec<u8>, u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(14)));
+}
+
+fn storage_key(minter: Principal, key: &str) -> Vec<u8> {
+    let principal = minter.as_slice();
+    let mut bytes = Vec::with_capacity(1 + principal.len() + key.len());
+    bytes.push(principal.len() as u8);
+    bytes.extend_from_slice(principal);
+    bytes.extend_from_slice(key.as_bytes());
+    bytes
+}
+
+pub fn validate_key(key: &str) -> Result<(), String> {
+    if key.is_empty() || key.len() > MAX_KEY_LEN {
+        return Err(format!("Idempotency key must be 1-{} bytes", MAX_KEY_LEN));
+    }
+    Ok(())
+}
+
+/// The token previously minted by `minter` under `key`, if any. Fails if the
+/// key was used for a different serial.
+pub fn lookup(minter: Principal, key: &str, serial_number: &str) -> Result<Option<ProductNFT>, String> {
+    let nft_id = MINT_KEYS.with(|keys| keys.borrow().get(&storage_key(minter, key)));
+    match nft_id {
+        Some(nft_id) => {
+            let nft = get_nft(nft_id)?;
+            if nft.serial_number != serial_number {
+                return Err(format!(
+                    "Idempotency key {} was already used to mint serial {}",
+                    key, nft.serial_number
+                ));
+            }
+            Ok(Some(nft))
+        }
+        None => Ok(None),
+    }
+}
+
+pub fn record(minter: Principal, key: &str, nft_id: u64) {
+    MINT_KEYS.with(|keys| {
+        keys.borrow_mut().insert(storage_key(minter, key), nft_id);
+    });
+}
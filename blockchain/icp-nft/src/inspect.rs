@@ -0,0 +1,154 @@
+//! Ingress filtering.
+//!
+//! `canister_inspect_message` runs on a single replica before an update call
+//! is accepted into a block, so rejecting junk here costs us no cycles. It is
+//! not a security boundary (a malicious node can skip it); every method still
+//! does its own authorization.
+
+use candid::Principal;
+use ic_cdk::api::call::{accept_message, arg_data, arg_data_raw_size, method_name};
+use ic_cdk_macros::inspect_message;
+
+use crate::{anonymous, limits, pause, MintRequest};
+
+/// Update methods accepted from ingress. Queries called as updates and
+/// unknown names are dropped.
+const UPDATE_METHODS: &[&str] = &[
+    "accept_super_admin",
+    "add_checkpoint",
+    "add_service_record",
+    "archive_transactions",
+    "attach_component",
+    "attest_for_solana",
+    "burn_nft",
+    "claim_nft",
+    "clear_stolen",
+    "create_collection",
+    "create_marketplace",
+    "delegate_custody",
+    "detach_component",
+    "endorse",
+    "finish_import",
+    "freeze_collection",
+    "grant_role",
+    "icrc37_approve_tokens",
+    "icrc37_revoke_token_approvals",
+    "icrc37_transfer_from",
+    "import_legacy_nfts",
+    "import_nfts",
+    "link_solana_address",
+    "lock_for_sale",
+    "mint_product_nft",
+    "propose_super_admin",
+    "prove_ownership",
+    "recall_products",
+    "record_dispute_outcome",
+    "register_manufacturer",
+    "register_marketplace_minter",
+    "register_retailer",
+    "release_serial_prefix",
+    "remove_marketplace_minter",
+    "report_stolen",
+    "request_ownership_challenge",
+    "reserve_serial_prefix",
+    "retailer_verify_batch",
+    "restore_verification",
+    "revoke_custody",
+    "revoke_role",
+    "revoke_verification",
+    "run_jobs_now",
+    "set_anonymous_policy",
+    "set_claim_code",
+    "set_collection_royalty",
+    "set_escrow_check",
+    "set_fee_exemption",
+    "set_jobs_config",
+    "set_limits",
+    "set_localized_metadata",
+    "set_marketplace_admins",
+    "set_low_cycles_threshold",
+    "set_mint_fee",
+    "set_mint_quota",
+    "set_paused",
+    "set_rate_limit",
+    "set_restricted_fields",
+    "set_retailer_active",
+    "set_serial_format",
+    "set_shard_assignment",
+    "set_solana_attester",
+    "set_transfer_fee",
+    "set_webhook",
+    "solana_attester_address",
+    "solana_link_challenge",
+    "transfer_from",
+    "transition_lifecycle",
+    "transfer_nft",
+    "unfreeze_collection",
+    "unlink_solana_address",
+    "unlock",
+    "verify_metadata_integrity",
+];
+
+/// Whether `method` is an update method accepted from ingress.
+pub fn is_update_method(method: &str) -> bool {
+    UPDATE_METHODS.contains(&method)
+}
+
+/// Hard cap on any update payload.
+const MAX_ARG_BYTES: usize = 256 * 1024;
+
+fn inspect(method: &str, caller: Principal) -> Result<(), String> {
+    if !is_update_method(method) {
+        return Err(format!("Method {} is not callable as an update", method));
+    }
+    if caller == Principal::anonymous() {
+        anonymous::check_update(method)?;
+    }
+    if !pause::PAUSE_EXEMPT_METHODS.contains(&method) {
+        pause::not_paused()?;
+    }
+    if arg_data_raw_size() > MAX_ARG_BYTES {
+        return Err(format!("Payload exceeds {} bytes", MAX_ARG_BYTES));
+    }
+    if method == "mint_product_nft" {
+        let (request,): (MintRequest,) = arg_data();
+        limits::check_mint_request(&request)?;
+    }
+    Ok(())
+}
+
+#[inspect_message]
+fn inspect_message() {
+    let method = method_name();
+    match inspect(&method, ic_cdk::caller()) {
+        Ok(()) => accept_message(),
+        Err(e) => ic_cdk::trap(&format!("Rejected {}: {}", method, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::types::FuncMode;
+    use candid_parser::utils::CandidSource;
+    use std::path::PathBuf;
+
+    #[test]
+    fn every_update_method_is_accepted_on_ingress() {
+        let did = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/proofcart_nft.did");
+        let (env, actor) = CandidSource::File(&did).load().expect("Failed to load proofcart_nft.did");
+        let actor = actor.expect("proofcart_nft.did has no service");
+
+        let missing: Vec<&String> = env
+            .as_service(&actor)
+            .expect("proofcart_nft.did service")
+            .iter()
+            .filter(|(name, _)| {
+                let func = env.get_method(&actor, name).unwrap_or_else(|e| panic!("{}: {}", name, e));
+                !func.modes.contains(&FuncMode::Query) && !is_update_method(name)
+            })
+            .map(|(name, _)| name)
+            .collect();
+        assert!(missing.is_empty(), "Update methods missing from UPDATE_METHODS: {:?}", missing);
+    }
+}
@@ -0,0 +1,147 @@
+//! Integrity of the off-chain metadata document behind `ipfs_metadata_uri`.
+//!
+//! At mint the document can be fetched with an HTTPS outcall and its SHA-256
+//! stored on the token. `verify_metadata_integrity` fetches it again and
+//! records whether it is still intact, was changed, or can no longer be
+//! retrieved. `ipfs://` URIs are resolved through a public gateway; the
+//! gateway is trusted only to return bytes, which are checked by hash.
+
+use candid::{CandidType, Nat, Principal};
+use ic_cdk::api::management_canister::http_request::{
+    self as outcall, CanisterHttpRequestArgument, HttpMethod, TransformArgs, TransformContext,
+};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::limits;
+use crate::pause::not_paused;
+use crate::roles::{self, Role};
+use crate::{get_nft, save_nft};
+
+const IPFS_GATEWAY: &str = "https://ipfs.io/ipfs/";
+const MAX_METADATA_BYTES: u64 = 1_000_000;
+/// Enough for a 1MB response on a 13-node subnet; unused cycles are refunded.
+const OUTCALL_CYCLES: u128 = 20_000_000_000;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntegrityStatus {
+    Intact,
+    /// The document no longer matches the hash recorded at mint.
+    Tampered,
+    /// The document could not be fetched.
+    Unavailable,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MetadataIntegrity {
+    #[serde(with = "serde_bytes")]
+    pub sha256: Vec<u8>,
+    pub recorded_at: u64,
+    pub status: IntegrityStatus,
+    pub last_checked_at: u64,
+    pub last_error: Option<String>,
+}
+
+/// HTTPS URL to fetch a metadata URI from.
+fn fetch_url(uri: &str) -> Result<String, String> {
+    let uri = uri.trim();
+    if let Some(path) = uri.strip_prefix("ipfs://") {
+        let path = path.trim_start_matches("ipfs/");
+        if path.is_empty() {
+            return Err("IPFS URI has no content identifier".to_string());
+        }
+        Ok(format!("{}{}", IPFS_GATEWAY, path))
+    } else if uri.starts_with("https://") {
+        Ok(uri.to_string())
+    } else {
+        Err(format!("Unsupported metadata URI {}; expected ipfs:// or https://", uri))
+    }
+}
+
+/// Fetch the document at `uri` and return its SHA-256.
+async fn fetch_hash(uri: &str) -> Result<Vec<u8>, String> {
+    let request = CanisterHttpRequestArgument {
+        url: fetch_url(uri)?,
+        max_response_bytes: Some(MAX_METADATA_BYTES),
+        method: HttpMethod::GET,
+        headers: vec![],
+        body: None,
+        transform: Some(TransformContext::from_name("transform_metadata_response".to_string(), vec![])),
+    };
+    let (response,) = outcall::http_request(request, OUTCALL_CYCLES)
+        .await
+        .map_err(|(code, msg)| format!("Metadata fetch failed: {:?} {}", code, msg))?;
+    if response.status != Nat::from(200u64) {
+        return Err(format!("Metadata fetch returned HTTP {}", response.status));
+    }
+    Ok(Sha256::digest(&response.body).to_vec())
+}
+
+/// Fetch and hash the metadata document for a new mint.
+pub async fn record(uri: &str) -> Result<MetadataIntegrity, String> {
+    let sha256 = fetch_hash(uri).await?;
+    let now = ic_cdk::api::time();
+    Ok(MetadataIntegrity {
+        sha256,
+        recorded_at: now,
+        status: IntegrityStatus::Intact,
+        last_checked_at: now,
+        last_error: None,
+    })
+}
+
+/// Strip headers so every replica sees the same response.
+#[query]
+fn transform_metadata_response(args: TransformArgs) -> outcall::HttpResponse {
+    outcall::HttpResponse {
+        status: args.response.status,
+        headers: vec![],
+        body: args.response.body,
+    }
+}
+
+fn can_check(caller: Principal, owner: Principal, minter: Principal) -> bool {
+    caller == owner || caller == minter || roles::has_any_role(caller, &[Role::Verifier, Role::Support])
+}
+
+/// Re-fetch an NFT's metadata document and compare it to the hash stored at
+/// mint (owner, minter, Verifier or Support)
+#[update(guard = "not_paused")]
+async fn verify_metadata_integrity(nft_id: u64) -> Result<MetadataIntegrity, String> {
+    let nft = get_nft(nft_id)?;
+    if !can_check(caller(), nft.owner, nft.minter()) {
+        return Err("Not authorized to check this NFT's metadata".to_string());
+    }
+    if nft.metadata_integrity.is_none() {
+        return Err(format!("NFT {} has no recorded metadata hash", nft_id));
+    }
+
+    let fetched = fetch_hash(&nft.metadata.ipfs_metadata_uri).await;
+
+    // Reload: the token may have changed while the outcall was in flight.
+    let mut nft = get_nft(nft_id)?;
+    let mut integrity = nft
+        .metadata_integrity
+        .clone()
+        .ok_or_else(|| format!("NFT {} has no recorded metadata hash", nft_id))?;
+    integrity.last_checked_at = ic_cdk::api::time();
+    match fetched {
+        Ok(sha256) if sha256 == integrity.sha256 => {
+            integrity.status = IntegrityStatus::Intact;
+            integrity.last_error = None;
+        }
+        Ok(_) => {
+            integrity.status = IntegrityStatus::Tampered;
+            integrity.last_error = None;
+        }
+        Err(e) => {
+            integrity.status = IntegrityStatus::Unavailable;
+            integrity.last_error = Some(limits::truncate_note(e));
+        }
+    }
+    nft.metadata_integrity = Some(integrity.clone());
+    save_nft(&nft);
+    Ok(integrity)
+}
@@ -0,0 +1,190 @@
+//! Periodic maintenance driven by canister timers.
+//!
+//! Each run expires due warranties and custody delegations, purges claim vouchers left unclaimed past
+//! their TTL (marking units that never left the manufacturer reclaimable), republishes the certified root and rolls new transaction-log
+//! blocks up into per-day statistics. Every job is bounded per run and picks
+//! up where it left off. Timers do not survive upgrades, so `start` is called
+//! from both `init` and `post_upgrade`.
+
+use candid::CandidType;
+use ic_cdk_macros::{query, update};
+use ic_cdk_timers::TimerId;
+use ic_stable_structures::{StableBTreeMap, StableCell};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
+
+use crate::pause::not_paused;
+use crate::roles::require_admin;
+use crate::{audit, certification, claims, custody, memory, txlog, warranty, Memory};
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+/// Upper bound on items each job handles per run.
+const MAX_ITEMS_PER_JOB: usize = 500;
+const MIN_INTERVAL_SECS: u64 = 60;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct JobsConfig {
+    pub interval_secs: u64,
+    /// Claim vouchers older than this are removed; `None` keeps them forever.
+    pub unclaimed_voucher_ttl_secs: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct JobsState {
+    pub config: JobsConfig,
+    pub last_run_at: Option<u64>,
+    pub runs: u64,
+    pub warranties_expired: u64,
+    pub vouchers_purged: u64,
+    /// Next transaction-log index to roll up.
+    pub next_rollup_index: u64,
+    /// Resume point of the voucher scan.
+    pub voucher_cursor: Option<u64>,
+    /// Purged vouchers whose token was still held by the issuer.
+    pub units_reclaimable: Option<u64>,
+}
+
+impl Default for JobsState {
+    fn default() -> Self {
+        JobsState {
+            config: JobsConfig { interval_secs: 60 * 60, unclaimed_voucher_ttl_secs: None },
+            last_run_at: None,
+            runs: 0,
+            warranties_expired: 0,
+            vouchers_purged: 0,
+            next_rollup_index: 0,
+            voucher_cursor: None,
+            units_reclaimable: None,
+        }
+    }
+}
+
+candid_storable!(JobsState);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DailyStats {
+    pub mints: u64,
+    pub transfers: u64,
+    pub burns: u64,
+    pub metadata_updates: u64,
+}
+
+candid_storable!(DailyStats);
+
+thread_local! {
+    static STATE: RefCell<StableCell<JobsState, Memory>> = RefCell::new(
+        StableCell::init(memory(23), JobsState::default())
+            .expect("Failed to initialize jobs state")
+    );
+
+    // Day number (days since the Unix epoch) -> activity that day
+    static DAILY_STATS: RefCell<StableBTreeMap<u64, DailyStats, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(25)));
+
+    static TIMER: Cell<Option<TimerId>> = const { Cell::new(None) };
+}
+
+fn state() -> JobsState {
+    STATE.with(|s| s.borrow().get().clone())
+}
+
+fn set_state(state: JobsState) {
+    STATE.with(|s| {
+        s.borrow_mut().set(state).expect("Failed to persist jobs state");
+    });
+}
+
+/// (Re)start the periodic timer with the configured interval.
+pub fn start() {
+    if let Some(timer) = TIMER.with(|t| t.take()) {
+        ic_cdk_timers::clear_timer(timer);
+    }
+    let interval = Duration::from_secs(state().config.interval_secs);
+    let timer = ic_cdk_timers::set_timer_interval(interval, run);
+    TIMER.with(|t| t.set(Some(timer)));
+}
+
+fn roll_up(state: &mut JobsState) {
+    for block in txlog::blocks_from(state.next_rollup_index, MAX_ITEMS_PER_JOB) {
+        let day = block.timestamp / NANOS_PER_SEC / SECS_PER_DAY;
+        DAILY_STATS.with(|stats| {
+            let mut stats = stats.borrow_mut();
+            let mut entry = stats.get(&day).unwrap_or_default();
+            match block.kind {
+                txlog::TxKind::Mint => entry.mints += 1,
+                txlog::TxKind::Transfer => entry.transfers += 1,
+                txlog::TxKind::Burn => entry.burns += 1,
+                txlog::TxKind::MetadataUpdate => entry.metadata_updates += 1,
+                txlog::TxKind::Approve | txlog::TxKind::Revoke => {}
+            }
+            stats.insert(day, entry);
+        });
+        state.next_rollup_index = block.index + 1;
+    }
+}
+
+fn run() {
+    let now = ic_cdk::api::time();
+    let mut state = state();
+
+    state.warranties_expired += warranty::expire_due(now, MAX_ITEMS_PER_JOB) as u64;
+    custody::expire_due(now, MAX_ITEMS_PER_JOB);
+
+    if let Some(ttl) = state.config.unclaimed_voucher_ttl_secs {
+        let cutoff = now.saturating_sub(ttl.saturating_mul(NANOS_PER_SEC));
+        let purge = claims::purge_issued_before(cutoff, state.voucher_cursor, MAX_ITEMS_PER_JOB);
+        state.vouchers_purged += purge.purged as u64;
+        state.units_reclaimable = Some(state.units_reclaimable.unwrap_or(0) + purge.reclaimable as u64);
+        state.voucher_cursor = purge.next;
+    }
+
+    certification::publish_root();
+    roll_up(&mut state);
+
+    state.last_run_at = Some(now);
+    state.runs += 1;
+    set_state(state);
+}
+
+/// Admin: change the job interval and voucher TTL
+#[update(guard = "not_paused")]
+fn set_jobs_config(config: JobsConfig) -> Result<JobsState, String> {
+    require_admin()?;
+    if config.interval_secs < MIN_INTERVAL_SECS {
+        return Err(format!("Job interval must be at least {}s", MIN_INTERVAL_SECS));
+    }
+    let mut state = state();
+    state.config = config;
+    set_state(state.clone());
+    start();
+    audit::record("set_jobs_config");
+    Ok(state)
+}
+
+/// Admin: run the maintenance jobs immediately
+#[update(guard = "not_paused")]
+fn run_jobs_now() -> Result<JobsState, String> {
+    require_admin()?;
+    run();
+    audit::record("run_jobs_now");
+    Ok(state())
+}
+
+/// Job configuration and progress
+#[query]
+fn get_jobs_state() -> JobsState {
+    state()
+}
+
+/// Rolled-up activity for days `from_day..=to_day`; days without activity are omitted.
+pub fn daily_stats(from_day: u64, to_day: u64) -> Vec<(u64, DailyStats)> {
+    DAILY_STATS.with(|stats| stats.borrow().range(from_day..=to_day).collect())
+}
+
+/// Daily activity for days `from_day..=to_day` (days since the Unix epoch), at most 366
+#[query]
+fn get_daily_stats(from_day: u64, to_day: u64) -> Vec<(u64, DailyStats)> {
+    daily_stats(from_day, to_day.min(from_day.saturating_add(365)))
+}
@@ -0,0 +1,71 @@
+//! Minimal ICRC-1/ICRC-2 ledger client.
+
+use candid::{CandidType, Nat, Principal};
+use serde::{Deserialize, Serialize};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Account {
+    pub owner: Principal,
+    pub subaccount: Option<Vec<u8>>,
+}
+
+impl Account {
+    pub fn of(owner: Principal) -> Self {
+        Account { owner, subaccount: None }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TransferFromArgs {
+    pub spender_subaccount: Option<Vec<u8>>,
+    pub from: Account,
+    pub to: Account,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum TransferFromError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    InsufficientAllowance { allowance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+/// Pull `amount` from `from` into this canister's default account using the
+/// allowance `from` granted to the canister (`icrc2_approve`). Returns the
+/// ledger block index.
+pub async fn collect(ledger: Principal, from: Principal, amount: Nat, memo: &str) -> Result<Nat, String> {
+    let args = TransferFromArgs {
+        spender_subaccount: None,
+        from: Account::of(from),
+        to: Account::of(ic_cdk::api::id()),
+        amount,
+        fee: None,
+        memo: Some(memo.as_bytes().to_vec()),
+        created_at_time: Some(ic_cdk::api::time()),
+    };
+
+    let (result,): (Result<Nat, TransferFromError>,) =
+        ic_cdk::call(ledger, "icrc2_transfer_from", (args,))
+            .await
+            .map_err(|(code, msg)| format!("Ledger call failed: {:?} {}", code, msg))?;
+
+    result.map_err(|e| match e {
+        TransferFromError::InsufficientAllowance { allowance } => format!(
+            "Insufficient allowance ({}); approve the canister on the ledger first",
+            allowance
+        ),
+        TransferFromError::InsufficientFunds { balance } => {
+            format!("Insufficient funds (balance {})", balance)
+        }
+        other => format!("Fee payment failed: {:?}", other),
+    })
+}
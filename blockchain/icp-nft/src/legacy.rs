@@ -0,0 +1,209 @@
+//! Stored layouts that predate the current types, converted on read.
+//!
+//! Each layout corresponds to a schema version in `migrations`.
+
+use candid::{CandidType, Decode, Principal};
+use serde::Deserialize;
+
+use crate::integrity::MetadataIntegrity;
+use crate::lifecycle::Lifecycle;
+use crate::recalls::RecallInfo;
+use crate::royalties::RoyaltyInfo;
+use crate::sale_lock::SaleLock;
+use crate::verification;
+use crate::warranty::Warranty;
+use crate::{limits, NFTMetadata, OwnershipRecord, ProductNFT, TransactionType};
+
+/// Ownership record with the original free-form `transaction_type`.
+#[derive(CandidType, Deserialize)]
+struct LegacyOwnershipRecord {
+    owner: Principal,
+    timestamp: u64,
+    transaction_type: String,
+}
+
+#[derive(CandidType, Deserialize)]
+struct LegacyProductNFT {
+    nft_id: u64,
+    serial_number: String,
+    owner: Principal,
+    metadata: NFTMetadata,
+    minted_at: u64,
+    verified: bool,
+    ownership_history: Vec<LegacyOwnershipRecord>,
+    collection_id: Option<u64>,
+    royalty: Option<RoyaltyInfo>,
+    recall: Option<RecallInfo>,
+    sale_lock: Option<SaleLock>,
+}
+
+/// Layout with a boolean `verified` flag instead of a verification level.
+#[derive(CandidType, Deserialize)]
+struct FlagProductNFT {
+    nft_id: u64,
+    serial_number: String,
+    owner: Principal,
+    metadata: NFTMetadata,
+    minted_at: u64,
+    verified: bool,
+    ownership_history: Vec<OwnershipRecord>,
+    collection_id: Option<u64>,
+    royalty: Option<RoyaltyInfo>,
+    recall: Option<RecallInfo>,
+    sale_lock: Option<SaleLock>,
+    metadata_integrity: Option<MetadataIntegrity>,
+    warranty: Option<Warranty>,
+    lifecycle: Option<Lifecycle>,
+}
+
+/// Token of the retired `icp-nft/src/nft_canister` canister, as returned by
+/// its `get_nft` query.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct NftCanisterNFT {
+    pub id: u64,
+    pub serial_number: String,
+    pub product_name: String,
+    pub manufacturer: String,
+    pub metadata_uri: String,
+    pub owner: Principal,
+    pub minted_at: u64,
+    pub transfer_history: Vec<NftCanisterTransfer>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct NftCanisterTransfer {
+    pub from: Principal,
+    pub to: Principal,
+    pub timestamp: u64,
+}
+
+/// Convert a `nft_canister` token. Its history only lists transfers, so the
+/// mint record is rebuilt from the first sender (or the owner if it was never
+/// transferred). Fields that canister did not have are left empty.
+pub fn from_nft_canister(nft: NftCanisterNFT) -> ProductNFT {
+    let minter = nft.transfer_history.first().map_or(nft.owner, |transfer| transfer.from);
+    let mut ownership_history = vec![OwnershipRecord {
+        owner: minter,
+        timestamp: nft.minted_at,
+        transaction_type: TransactionType::Mint,
+        memo: None,
+        price: None,
+    }];
+    ownership_history.extend(nft.transfer_history.into_iter().map(|transfer| OwnershipRecord {
+        owner: transfer.to,
+        timestamp: transfer.timestamp,
+        transaction_type: TransactionType::Transfer,
+        memo: None,
+        price: None,
+    }));
+    limits::trim_history(&mut ownership_history);
+
+    ProductNFT {
+        nft_id: nft.id,
+        serial_number: nft.serial_number.clone(),
+        owner: nft.owner,
+        metadata: NFTMetadata {
+            serial_number: nft.serial_number,
+            product_name: nft.product_name,
+            manufacturer: nft.manufacturer,
+            manufacture_date: String::new(),
+            category: String::new(),
+            description: String::new(),
+            specifications: String::new(),
+            warranty_info: String::new(),
+            certifications: vec![],
+            ipfs_metadata_uri: nft.metadata_uri,
+            arweave_tx_id: None,
+            localized: None,
+        },
+        minted_at: nft.minted_at,
+        verification_level: verification::level_for_minter(minter),
+        revoked: false,
+        endorsement: None,
+        ownership_history,
+        collection_id: None,
+        royalty: None,
+        recall: None,
+        sale_lock: None,
+        metadata_integrity: None,
+        warranty: None,
+        lifecycle: None,
+        restricted_fields: None,
+        stolen: None,
+        custody: None,
+        collection_freeze: None,
+    }
+}
+
+fn transaction_type(legacy: &str) -> TransactionType {
+    match legacy {
+        "mint" => TransactionType::Mint,
+        "sale" => TransactionType::Sale,
+        _ => TransactionType::Transfer,
+    }
+}
+
+/// Decode a token stored in any earlier layout, newest first, together with
+/// the schema version of the layout it was found in.
+pub fn decode_nft(bytes: &[u8]) -> Result<(u32, ProductNFT), String> {
+    Decode!(bytes, FlagProductNFT)
+        .map(|nft| (2, from_flag_layout(nft)))
+        .or_else(|_| Decode!(bytes, LegacyProductNFT).map(|nft| (1, from_legacy_layout(nft))))
+        .map_err(|e| e.to_string())
+}
+
+fn from_flag_layout(nft: FlagProductNFT) -> ProductNFT {
+    let minter = nft.ownership_history.first().map_or(nft.owner, |record| record.owner);
+    ProductNFT {
+        nft_id: nft.nft_id,
+        serial_number: nft.serial_number,
+        owner: nft.owner,
+        metadata: nft.metadata,
+        minted_at: nft.minted_at,
+        verification_level: verification::level_for_minter(minter),
+        revoked: !nft.verified,
+        endorsement: None,
+        ownership_history: nft.ownership_history,
+        collection_id: nft.collection_id,
+        royalty: nft.royalty,
+        recall: nft.recall,
+        sale_lock: nft.sale_lock,
+        metadata_integrity: nft.metadata_integrity,
+        warranty: nft.warranty,
+        lifecycle: nft.lifecycle,
+        restricted_fields: None,
+        stolen: None,
+        custody: None,
+        collection_freeze: None,
+    }
+}
+
+/// Convert a token stored with string transaction types.
+fn from_legacy_layout(legacy: LegacyProductNFT) -> ProductNFT {
+    from_flag_layout(FlagProductNFT {
+        nft_id: legacy.nft_id,
+        serial_number: legacy.serial_number,
+        owner: legacy.owner,
+        metadata: legacy.metadata,
+        minted_at: legacy.minted_at,
+        verified: legacy.verified,
+        ownership_history: legacy
+            .ownership_history
+            .into_iter()
+            .map(|record| OwnershipRecord {
+                owner: record.owner,
+                timestamp: record.timestamp,
+                transaction_type: transaction_type(&record.transaction_type),
+                memo: None,
+                price: None,
+            })
+            .collect(),
+        collection_id: legacy.collection_id,
+        royalty: legacy.royalty,
+        recall: legacy.recall,
+        sale_lock: legacy.sale_lock,
+        metadata_integrity: None,
+        warranty: None,
+        lifecycle: None,
+    })
+}
@@ -1,27 +1,112 @@
-use candid::{CandidType, Decode, Encode, Principal};
+use candid::{CandidType, Encode, Principal};
 use ic_cdk::{caller, trap};
-use ic_cdk_macros::{init, query, update};
+use ic_cdk_macros::{init, post_upgrade, query, update};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 
-type Memory = VirtualMemory<DefaultMemoryImpl>;
+use collections::CollectionFreeze;
+use custody::Custody;
+use integrity::MetadataIntegrity;
+use lifecycle::Lifecycle;
+use localization::LocalizedText;
+use pause::not_paused;
+use prices::SalePrice;
+use privacy::MetadataField;
+use rate_limit::RateLimitedMethod;
+use recalls::RecallInfo;
+use royalties::RoyaltyInfo;
+use sale_lock::SaleLock;
+use stolen::StolenReport;
+use verification::{Endorsement, VerificationLevel};
+use warranty::Warranty;
 
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
-pub struct NFTMetadata {
-    pub serial_number: String,
-    pub product_name: String,
-    pub manufacturer: String,
-    pub manufacture_date: String,
-    pub category: String,
-    pub description: String,
-    pub specifications: String,
-    pub warranty_info: String,
-    pub certifications: Vec<String>,
-    pub ipfs_metadata_uri: String,
+/// Store a candid-encodable type in stable structures.
+macro_rules! candid_storable {
+    ($t:ty) => {
+        impl ic_stable_structures::Storable for $t {
+            fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+                std::borrow::Cow::Owned(candid::Encode!(self).unwrap_or_else(|e| {
+                    ic_cdk::trap(&format!("Failed to encode {}: {}", stringify!($t), e))
+                }))
+            }
+
+            fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+                candid::Decode!(bytes.as_ref(), Self).unwrap_or_else(|e| {
+                    ic_cdk::trap(&format!("Failed to decode {}: {}", stringify!($t), e))
+                })
+            }
+
+            const BOUND: ic_stable_structures::storable::Bound =
+                ic_stable_structures::storable::Bound::Unbounded;
+        }
+    };
 }
 
+mod anonymous;
+mod audit;
+mod backup;
+mod burns;
+mod certification;
+mod checkpoints;
+mod claims;
+mod collections;
+mod components;
+mod custody;
+mod disputes;
+mod escrow_check;
+mod events;
+mod fees;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+mod health;
+mod http;
+mod icrc37;
+mod icrc7;
+mod idempotency;
+mod inspect;
+mod integrity;
+mod jobs;
+mod ledger;
+mod legacy;
+mod lifecycle;
+mod limits;
+mod localization;
+mod manufacturers;
+mod marketplaces;
+mod migrations;
+mod moderation;
+mod payload;
+mod pause;
+mod prices;
+mod privacy;
+mod projection;
+mod profiles;
+mod proofs;
+mod rate_limit;
+mod recalls;
+mod retailers;
+mod roles;
+mod royalties;
+mod sale_lock;
+mod search;
+mod serials;
+mod service;
+mod sharding;
+mod solana_attestation;
+mod stats;
+mod stolen;
+mod txlog;
+mod verification;
+mod version;
+mod warranty;
+mod webhooks;
+
+pub use proofcart_types::{NFTMetadata, OwnershipRecord, TransactionType};
+
+type Memory = VirtualMemory<DefaultMemoryImpl>;
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct ProductNFT {
     pub nft_id: u64,
@@ -29,15 +114,64 @@ pub struct ProductNFT {
     pub owner: Principal,
     pub metadata: NFTMetadata,
     pub minted_at: u64,
-    pub verified: bool,
+    pub verification_level: VerificationLevel,
+    /// Set by `revoke_verification`; overrides the level.
+    pub revoked: bool,
+    pub endorsement: Option<Endorsement>,
     pub ownership_history: Vec<OwnershipRecord>,
+    pub collection_id: Option<u64>,
+    pub royalty: Option<RoyaltyInfo>,
+    pub recall: Option<RecallInfo>,
+    pub sale_lock: Option<SaleLock>,
+    /// Hash of the off-chain metadata document, when checked at mint.
+    pub metadata_integrity: Option<MetadataIntegrity>,
+    pub warranty: Option<Warranty>,
+    pub lifecycle: Option<Lifecycle>,
+    /// Metadata fields blanked in public reads (see `privacy`).
+    pub restricted_fields: Option<Vec<MetadataField>>,
+    /// Set while the owner reports the item stolen or lost (see `stolen`).
+    pub stolen: Option<StolenReport>,
+    /// Temporary custodian named by the owner (see `custody`).
+    pub custody: Option<Custody>,
+    /// Freeze on the token's collection, filled in on reads (see `collections`).
+    pub collection_freeze: Option<CollectionFreeze>,
 }
 
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
-pub struct OwnershipRecord {
-    pub owner: Principal,
-    pub timestamp: u64,
-    pub transaction_type: String, // "mint", "transfer", "sale"
+impl ic_stable_structures::Storable for ProductNFT {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        let bytes = Encode!(self).unwrap_or_else(|e| {
+            trap(&format!("Failed to encode ProductNFT: {}", e))
+        });
+        // Unreachable while `limits` is enforced on every write path.
+        if bytes.len() > limits::MAX_NFT_BYTES as usize {
+            trap(&format!(
+                "NFT {} encodes to {} bytes, over the {} byte bound",
+                self.nft_id,
+                bytes.len(),
+                limits::MAX_NFT_BYTES
+            ));
+        }
+        std::borrow::Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        // Records written in an earlier layout are converted on read.
+        migrations::decode_nft(bytes.as_ref())
+            .map(|(_, nft)| nft)
+            .unwrap_or_else(|e| trap(&format!("Failed to decode ProductNFT: {}", e)))
+    }
+
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: limits::MAX_NFT_BYTES,
+        is_fixed_size: false,
+    };
+}
+
+impl ProductNFT {
+    /// Principal that minted the token (the first recorded owner).
+    pub fn minter(&self) -> Principal {
+        self.ownership_history.first().map(|record| record.owner).unwrap_or(self.owner)
+    }
 }
 
 #[derive(CandidType, Serialize, Deserialize)]
@@ -52,6 +186,40 @@ pub struct MintRequest {
     pub warranty_info: String,
     pub certifications: Vec<String>,
     pub ipfs_metadata_uri: String,
+    /// Arweave id of the certification documents (see `ipfs-pinner`).
+    pub arweave_tx_id: Option<String>,
+    pub collection_id: Option<u64>,
+    pub royalty: Option<RoyaltyInfo>,
+    pub idempotency_key: Option<String>,
+    /// Fetch `ipfs_metadata_uri` and store its hash for later integrity checks.
+    pub verify_metadata: Option<bool>,
+    /// SHA-256 of a one-time claim code for the end customer (see `claims`).
+    pub claim_code_hash: Option<Vec<u8>>,
+    /// End of the warranty period (nanoseconds since the epoch).
+    pub warranty_expires_at: Option<u64>,
+    /// Whether the warranty survives resale (default true; see `warranty`).
+    pub warranty_transfers: Option<bool>,
+    pub localized: Option<Vec<(String, LocalizedText)>>,
+}
+
+impl MintRequest {
+    /// The metadata fields of the request, for the schema checks.
+    pub fn metadata(&self) -> NFTMetadata {
+        NFTMetadata {
+            serial_number: self.serial_number.clone(),
+            product_name: self.product_name.clone(),
+            manufacturer: self.manufacturer.clone(),
+            manufacture_date: self.manufacture_date.clone(),
+            category: self.category.clone(),
+            description: self.description.clone(),
+            specifications: self.specifications.clone(),
+            warranty_info: self.warranty_info.clone(),
+            certifications: self.certifications.clone(),
+            ipfs_metadata_uri: self.ipfs_metadata_uri.clone(),
+            arweave_tx_id: self.arweave_tx_id.clone(),
+            localized: self.localized.clone(),
+        }
+    }
 }
 
 thread_local! {
@@ -72,21 +240,193 @@ thread_local! {
     
     static NFT_COUNTER: RefCell<u64> = RefCell::new(0);
     
-    static ADMIN: RefCell<Principal> = RefCell::new(Principal::anonymous());
+    // Serials with a mint in flight (awaiting fee payment)
+    static PENDING_SERIALS: RefCell<std::collections::BTreeSet<String>> = RefCell::new(Default::default());
+    
+    // Tokens with a `transfer_nft` in flight (awaiting fee payment)
+    static PENDING_TRANSFERS: RefCell<std::collections::BTreeSet<u64>> = RefCell::new(Default::default());
+}
+
+/// Virtual memory for a stable structure.
+///
+/// Allocated ids: 0 NFTS, 1 SERIAL_TO_NFT, 2 manufacturer index, 3 category index,
+/// 4 collections, 5-6 transaction log, 7-8 roles, 9 fee config, 10 health,
+/// 11-12 rate limits, 13 manufacturers, 14 mint idempotency keys, 15 import state,
+/// 16 manufacturer stats, 17 retailers, 18 claim vouchers, 19-21 owner profiles,
+/// 22 ICRC-37 approvals, 23 jobs state, 24 warranty expiry index, 25 daily stats,
+/// 26-27 webhooks, 28 service records,
+/// 29 custody checkpoints, 30 moderation log, 31 product name index,
+/// 32 shard assignment, 33 schema version, 34 serial prefix reservations,
+/// 35 holdings per principal, 36-37 ownership proofs, 38 limits,
+/// 39 custody expiry index, 40-41 bundle components, 42 dispute outcomes,
+/// 43 reclaimable units, 44 pause switch, 45 audit log, 46 anonymous policy,
+/// 47 burn tombstones, 48 escrow check config, 49 Solana attester config,
+/// 50-51 partner marketplaces and their minters, 52 mint fee credits.
+fn memory(id: u8) -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(id)))
+}
+
+/// Highest allocated memory id; keep in step with the list above.
+const LAST_MEMORY_ID: u8 = 52;
+
+#[derive(CandidType, Deserialize)]
+pub struct UpgradeArgs {
+    /// SuperAdmin to install if no principal holds the role (recovery).
+    pub super_admin: Option<Principal>,
 }
 
 #[init]
 fn init() {
-    ADMIN.with(|admin| {
-        *admin.borrow_mut() = caller();
+    migrations::init();
+    roles::bootstrap(caller());
+    jobs::start();
+    webhooks::start();
+}
+
+#[post_upgrade]
+fn post_upgrade(args: Option<UpgradeArgs>) {
+    // The counter lives on the heap; resume after the highest stored or burned id
+    let last_id = NFTS.with(|nfts| nfts.borrow().last_key_value().map(|(id, _)| id));
+    let next_id = last_id.max(burns::last_burned_id()).map_or(0, |id| id + 1);
+    NFT_COUNTER.with(|counter| *counter.borrow_mut() = next_id);
+    
+    migrations::run();
+    normalize_stored_serials();
+    
+    if let Some(super_admin) = args.and_then(|a| a.super_admin) {
+        roles::bootstrap(super_admin);
+    }
+    
+    certification::rebuild();
+    search::backfill();
+    stats::backfill();
+    health::record_upgrade();
+    jobs::start();
+    webhooks::start();
+}
+
+/// Rewrite serials stored before normalization was introduced. If two legacy
+/// serials collapse to the same canonical form, the older token keeps it and
+/// the newer one stays reachable under its original spelling.
+fn normalize_stored_serials() {
+    let legacy: Vec<(String, u64)> = SERIAL_TO_NFT.with(|map| {
+        map.borrow()
+            .iter()
+            .filter(|(serial, _)| *serial != serials::normalize(serial))
+            .collect()
+    });
+    
+    for (serial, nft_id) in legacy {
+        let normalized = serials::normalize(&serial);
+        let taken = SERIAL_TO_NFT.with(|map| map.borrow().contains_key(&normalized));
+        if taken {
+            ic_cdk::println!("Serial {} (NFT {}) collides with {}; left unnormalized", serial, nft_id, normalized);
+            continue;
+        }
+        
+        SERIAL_TO_NFT.with(|map| {
+            let mut map = map.borrow_mut();
+            map.remove(&serial);
+            map.insert(normalized.clone(), nft_id);
+        });
+        NFTS.with(|nfts| {
+            let mut nfts = nfts.borrow_mut();
+            if let Some(mut nft) = nfts.get(&nft_id) {
+                nft.serial_number = normalized.clone();
+                nft.metadata.serial_number = normalized;
+                nfts.insert(nft_id, nft);
+            }
+        });
+    }
+}
+
+/// Persist an updated NFT and refresh its certified leaf
+fn save_nft(nft: &ProductNFT) {
+    NFTS.with(|nfts| {
+        nfts.borrow_mut().insert(nft.nft_id, nft.clone());
     });
+    certification::certify_nft(nft);
+}
+
+/// Look up the NFT registered under a serial number
+fn find_by_serial(serial_number: &str) -> Result<ProductNFT, String> {
+    let nft_id = SERIAL_TO_NFT.with(|map| {
+        map.borrow().get(&serials::normalize(serial_number))
+    });
+    
+    match nft_id {
+        Some(id) => get_nft(id),
+        None => Err(format!("No NFT found for serial number: {}", serial_number))
+    }
 }
 
 /// Mint a new product NFT
-#[update]
-fn mint_product_nft(request: MintRequest) -> Result<ProductNFT, String> {
+#[update(guard = "not_paused")]
+async fn mint_product_nft(request: MintRequest) -> Result<ProductNFT, String> {
+    let result = mint(request).await;
+    if let Err(e) = &result {
+        events::failure("mint_product_nft", None, e);
+    }
+    result
+}
+
+async fn mint(mut request: MintRequest) -> Result<ProductNFT, String> {
     let owner = caller();
     
+    request.serial_number = serials::normalize(&request.serial_number);
+    
+    rate_limit::check(owner, RateLimitedMethod::Mint)?;
+    
+    // A retry of an earlier successful mint returns the original token
+    if let Some(key) = &request.idempotency_key {
+        idempotency::validate_key(key)?;
+        if let Some(nft) = idempotency::lookup(owner, key, &request.serial_number)? {
+            return Ok(nft);
+        }
+    }
+    
+    validate_mint(&request, owner)?;
+    
+    // Reserve the serial while the fee payment is in flight
+    let reserved = PENDING_SERIALS.with(|p| p.borrow_mut().insert(request.serial_number.clone()));
+    if !reserved {
+        return Err(format!("A mint for serial number {} is already in progress", request.serial_number));
+    }
+    
+    let result: Result<Option<MetadataIntegrity>, String> = async {
+        let metadata_integrity = match request.verify_metadata {
+            Some(true) => Some(integrity::record(&request.ipfs_metadata_uri).await?),
+            _ => None,
+        };
+        let paid = fees::charge_mint_fee(owner, &request.serial_number).await?;
+        // The state may have changed while the fee was collected
+        if let Err(e) = validate_mint(&request, owner) {
+            if paid {
+                fees::credit_mint_fee(owner);
+                return Err(format!("{}; the fee paid is credited to your next mint", e));
+            }
+            return Err(e);
+        }
+        Ok(metadata_integrity)
+    }
+    .await;
+    
+    PENDING_SERIALS.with(|p| p.borrow_mut().remove(&request.serial_number));
+    
+    result.map(|metadata_integrity| mint_inner(request, owner, metadata_integrity))
+}
+
+/// Checks that must pass before a mint (and again after any await point)
+fn validate_mint(request: &MintRequest, owner: Principal) -> Result<(), String> {
+    health::ensure_cycles()?;
+    backup::ensure_not_importing()?;
+    anonymous::ensure_can_own(owner)?;
+    
+    limits::check_mint_request(request)?;
+    if let Some(entries) = &request.localized {
+        localization::normalize_entries(entries.clone())?;
+    }
+    
     // Check if serial number already exists
     let serial_exists = SERIAL_TO_NFT.with(|map| {
         map.borrow().get(&request.serial_number).is_some()
@@ -96,6 +436,35 @@ fn mint_product_nft(request: MintRequest) -> Result<ProductNFT, String> {
         return Err(format!("NFT with serial number {} already exists", request.serial_number));
     }
     
+    sharding::check_serial(&request.serial_number)?;
+    manufacturers::check_serial_format(owner, &request.serial_number)?;
+    manufacturers::check_serial_namespace(owner, &request.serial_number)?;
+    manufacturers::check_mint_quota(owner)?;
+    
+    if let Some(collection_id) = request.collection_id {
+        collections::authorize_mint(collection_id, owner)?;
+    }
+    
+    if let Some(royalty) = &request.royalty {
+        royalties::validate(royalty)?;
+    }
+    
+    if let Some(code_hash) = &request.claim_code_hash {
+        claims::validate_hash(code_hash)?;
+    }
+    
+    if request.warranty_transfers.is_some() && request.warranty_expires_at.is_none() {
+        return Err("warranty_transfers requires warranty_expires_at".to_string());
+    }
+    
+    Ok(())
+}
+
+/// Create and store the NFT; `validate_mint` must have passed
+fn mint_inner(request: MintRequest, owner: Principal, metadata_integrity: Option<MetadataIntegrity>) -> ProductNFT {
+    let idempotency_key = request.idempotency_key;
+    let claim_code_hash = request.claim_code_hash;
+    
     // Generate new NFT ID
     let nft_id = NFT_COUNTER.with(|counter| {
         let id = *counter.borrow();
@@ -116,12 +485,20 @@ fn mint_product_nft(request: MintRequest) -> Result<ProductNFT, String> {
         warranty_info: request.warranty_info,
         certifications: request.certifications,
         ipfs_metadata_uri: request.ipfs_metadata_uri,
+        arweave_tx_id: request.arweave_tx_id,
+        // Validated in `validate_mint`.
+        localized: request
+            .localized
+            .and_then(|entries| localization::normalize_entries(entries).ok())
+            .filter(|entries| !entries.is_empty()),
     };
     
     let ownership_record = OwnershipRecord {
         owner,
         timestamp,
-        transaction_type: "mint".to_string(),
+        transaction_type: TransactionType::Mint,
+        memo: None,
+        price: None,
     };
     
     let nft = ProductNFT {
@@ -130,8 +507,26 @@ fn mint_product_nft(request: MintRequest) -> Result<ProductNFT, String> {
         owner,
         metadata,
         minted_at: timestamp,
-        verified: true,
+        verification_level: verification::level_for_minter(owner),
+        revoked: false,
+        endorsement: None,
         ownership_history: vec![ownership_record],
+        collection_id: request.collection_id,
+        royalty: request.royalty,
+        recall: None,
+        sale_lock: None,
+        metadata_integrity,
+        warranty: request.warranty_expires_at.map(|expires_at| Warranty {
+            expires_at,
+            expired: expires_at <= timestamp,
+            transfers: request.warranty_transfers,
+            voided: None,
+        }),
+        lifecycle: Some(Lifecycle::new(owner, timestamp)),
+        restricted_fields: None,
+        stolen: None,
+        custody: None,
+        collection_freeze: None,
     };
     
     // Store NFT
@@ -144,51 +539,182 @@ fn mint_product_nft(request: MintRequest) -> Result<ProductNFT, String> {
         map.borrow_mut().insert(request.serial_number, nft_id);
     });
     
-    Ok(nft)
+    certification::certify_nft(&nft);
+    search::index_nft(&nft);
+    stats::record_mint(&nft);
+    manufacturers::record_mint(owner);
+    events::emit(events::EventKind::Mint, Some(nft.nft_id), nft.serial_number.clone());
+    warranty::schedule(&nft);
+    
+    if let Some(collection_id) = nft.collection_id {
+        collections::record_mint(collection_id);
+    }
+    
+    txlog::append(txlog::TxKind::Mint, nft.nft_id, &nft.serial_number, None, Some(owner), None);
+    
+    if let Some(key) = idempotency_key {
+        idempotency::record(owner, &key, nft.nft_id);
+    }
+    
+    if let Some(code_hash) = claim_code_hash {
+        claims::issue(nft.nft_id, owner, code_hash);
+    }
+    
+    nft
 }
 
 /// Verify product authenticity by serial number
 #[query]
 fn verify_product(serial_number: String) -> Result<ProductNFT, String> {
-    let nft_id = SERIAL_TO_NFT.with(|map| {
-        map.borrow().get(&serial_number)
-    });
-    
-    match nft_id {
-        Some(id) => {
-            NFTS.with(|nfts| {
-                nfts.borrow().get(&id)
-                    .ok_or_else(|| "NFT not found".to_string())
-            })
-        },
-        None => Err(format!("No NFT found for serial number: {}", serial_number))
-    }
+    find_by_serial(&serial_number).map(|nft| privacy::view(caller(), nft))
 }
 
-/// Get NFT by ID
+/// Batch lookup result with one certified witness for all serials
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct BatchVerification {
+    pub results: Vec<(String, Option<ProductNFT>)>,
+    /// Subnet certificate over the certified data; `None` in replicated calls.
+    pub certificate: Option<Vec<u8>>,
+    /// CBOR hash tree covering every requested serial (present or absent).
+    pub witness: Vec<u8>,
+}
+
+/// Verify multiple serial numbers at once, with a certified witness
 #[query]
+fn batch_verify_nfts(serial_numbers: Vec<String>) -> Result<BatchVerification, String> {
+    if serial_numbers.is_empty() {
+        return Err("At least one serial number is required".to_string());
+    }
+    limits::check_batch("batch_verify_nfts", serial_numbers.len(), limits::current().max_batch_verify)?;
+    
+    let normalized: Vec<String> = serial_numbers.iter().map(|serial| serials::normalize(serial)).collect();
+    Ok(BatchVerification {
+        witness: certification::batch_witness(&normalized),
+        certificate: certification::data_certificate().ok(),
+        results: verify_serials(serial_numbers),
+    })
+}
+
+/// Look up each serial, pairing it with its token (as the caller may see
+/// it) if one exists.
+fn verify_serials(serial_numbers: Vec<String>) -> Vec<(String, Option<ProductNFT>)> {
+    let caller = caller();
+    serial_numbers
+        .into_iter()
+        .map(|serial| {
+            let nft = find_by_serial(&serial).ok().map(|nft| privacy::view(caller, nft));
+            (serial, nft)
+        })
+        .collect()
+}
+
+/// Look up a stored NFT by ID
 fn get_nft(nft_id: u64) -> Result<ProductNFT, String> {
     NFTS.with(|nfts| {
-        nfts.borrow().get(&nft_id)
-            .ok_or_else(|| format!("NFT {} not found", nft_id))
+        nfts.borrow().get(&nft_id).ok_or_else(|| match burns::tombstone(nft_id) {
+            Some(tombstone) => tombstone.to_string(),
+            None => format!("NFT {} not found", nft_id),
+        })
     })
 }
 
-/// Transfer NFT ownership
-#[update]
-fn transfer_nft(nft_id: u64, new_owner: Principal) -> Result<ProductNFT, String> {
+/// Get NFT by ID
+#[query(name = "get_nft")]
+fn get_nft_query(nft_id: u64) -> Result<ProductNFT, String> {
+    get_nft(nft_id).map(|nft| privacy::view(caller(), nft))
+}
+
+/// Transfer NFT ownership, optionally classified by `reason` (default
+/// `Transfer`). A `Sale` may record the price paid. Charges the transfer fee
+/// when one is configured.
+#[update(guard = "not_paused")]
+async fn transfer_nft(
+    nft_id: u64,
+    new_owner: Principal,
+    reason: Option<TransactionType>,
+    memo: Option<String>,
+    price: Option<SalePrice>,
+) -> Result<ProductNFT, String> {
+    let result = transfer(nft_id, new_owner, reason, memo, price).await;
+    if let Err(e) = &result {
+        events::failure("transfer_nft", Some(nft_id), e);
+    }
+    result
+}
+
+async fn transfer(
+    nft_id: u64,
+    new_owner: Principal,
+    reason: Option<TransactionType>,
+    memo: Option<String>,
+    price: Option<SalePrice>,
+) -> Result<ProductNFT, String> {
     let caller = caller();
     
-    let mut nft = NFTS.with(|nfts| {
-        nfts.borrow().get(&nft_id)
-            .ok_or_else(|| format!("NFT {} not found", nft_id))
-    })?;
+    rate_limit::check(caller, RateLimitedMethod::Transfer)?;
+    
+    anonymous::ensure_can_own(new_owner)?;
+    
+    let reason = reason.unwrap_or(TransactionType::Transfer);
+    if reason == TransactionType::Mint {
+        return Err("Mint is not a valid transfer reason".to_string());
+    }
+    if let Some(memo) = &memo {
+        limits::check_len("memo", memo, limits::MAX_NOTE_BYTES)?;
+    }
+    let price = price.map(|price| prices::validate(price, reason)).transpose()?;
+    
+    let nft = validate_transfer(nft_id, caller)?;
+    
+    // Hold the token while the fee payment is in flight
+    let reserved = PENDING_TRANSFERS.with(|p| p.borrow_mut().insert(nft_id));
+    if !reserved {
+        return Err(format!("A transfer of NFT {} is already in progress", nft_id));
+    }
+    
+    let result = async {
+        fees::charge_transfer_fee(caller, &nft).await?;
+        validate_transfer(nft_id, caller)
+    }
+    .await;
+    
+    PENDING_TRANSFERS.with(|p| p.borrow_mut().remove(&nft_id));
+    
+    let mut nft = result?;
+    apply_transfer(&mut nft, new_owner, reason, memo, price);
+    
+    Ok(nft)
+}
+
+/// Checks that must pass before `transfer_nft` (and again after the fee is
+/// paid); returns the token
+fn validate_transfer(nft_id: u64, caller: Principal) -> Result<ProductNFT, String> {
+    let nft = get_nft(nft_id)?;
     
     // Only current owner can transfer
     if nft.owner != caller {
         return Err("Only the owner can transfer this NFT".to_string());
     }
     
+    sale_lock::ensure_unlocked(&nft)?;
+    stolen::ensure_not_reported(&nft)?;
+    collections::ensure_not_frozen(&nft)?;
+    components::ensure_transferable(&nft)?;
+    
+    Ok(nft)
+}
+
+/// Move an NFT (and any components attached to it) to `new_owner`, recording
+/// history, counters and the log entry, and return the log index. Callers are
+/// responsible for authorization and lock checks.
+fn apply_transfer(
+    nft: &mut ProductNFT,
+    new_owner: Principal,
+    transaction_type: TransactionType,
+    memo: Option<String>,
+    price: Option<SalePrice>,
+) -> u64 {
+    let previous_owner = nft.owner;
     let timestamp = ic_cdk::api::time();
     
     // Update ownership
@@ -198,26 +724,104 @@ fn transfer_nft(nft_id: u64, new_owner: Principal) -> Result<ProductNFT, String>
     nft.ownership_history.push(OwnershipRecord {
         owner: new_owner,
         timestamp,
-        transaction_type: "transfer".to_string(),
+        transaction_type,
+        memo: memo.clone(),
+        price,
     });
+    limits::trim_history(&mut nft.ownership_history);
+    custody::clear(nft);
+    warranty::on_transfer(nft, previous_owner);
     
     // Update storage
-    NFTS.with(|nfts| {
-        nfts.borrow_mut().insert(nft_id, nft.clone());
-    });
+    save_nft(nft);
+    claims::clear(nft.nft_id);
+    icrc37::clear_approvals(nft.nft_id);
     
-    Ok(nft)
+    if let Some(collection_id) = nft.collection_id {
+        collections::record_transfer(collection_id);
+    }
+    stats::record_transfer(nft, previous_owner);
+    webhooks::notify_transfer(nft, previous_owner, new_owner);
+    events::emit(
+        events::EventKind::Transfer,
+        Some(nft.nft_id),
+        format!("{} -> {} ({:?})", previous_owner, new_owner, transaction_type),
+    );
+    
+    let index = txlog::append(
+        txlog::TxKind::Transfer,
+        nft.nft_id,
+        &nft.serial_number,
+        Some(previous_owner),
+        Some(new_owner),
+        memo,
+    );
+    components::follow_parent(nft, transaction_type);
+    index
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum SortOrder {
+    MintedAsc,
+    MintedDesc,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct NFTPage {
+    pub items: Vec<ProductNFT>,
+    pub total: u64,
+    pub offset: u64,
+    pub limit: u64,
+}
+
+/// Maximum page size for `list_nfts`
+const MAX_LIST_LIMIT: u64 = 100;
+
+/// List all NFTs in mint order (token ids are assigned in mint order)
+#[query]
+fn list_nfts(offset: u64, limit: u64, sort: SortOrder) -> NFTPage {
+    let limit = limit.clamp(1, MAX_LIST_LIMIT);
+    let caller = caller();
+    
+    NFTS.with(|nfts| {
+        let nfts = nfts.borrow();
+        let total = nfts.len();
+        let count = limit.min(total.saturating_sub(offset));
+        
+        let items = match sort {
+            SortOrder::MintedAsc => nfts
+                .iter()
+                .skip(offset as usize)
+                .take(count as usize)
+                .map(|(_, nft)| privacy::view(caller, nft))
+                .collect(),
+            SortOrder::MintedDesc => {
+                let start = total.saturating_sub(offset + count);
+                let mut items: Vec<ProductNFT> = nfts
+                    .iter()
+                    .skip(start as usize)
+                    .take(count as usize)
+                    .map(|(_, nft)| privacy::view(caller, nft))
+                    .collect();
+                items.reverse();
+                items
+            }
+        };
+        
+        NFTPage { items, total, offset, limit }
+    })
 }
 
 /// Get all NFTs owned by a principal
 #[query]
 fn get_nfts_by_owner(owner: Principal) -> Vec<ProductNFT> {
+    let caller = caller();
     NFTS.with(|nfts| {
         nfts.borrow()
             .iter()
             .filter_map(|(_, nft)| {
                 if nft.owner == owner {
-                    Some(nft.clone())
+                    Some(privacy::view(caller, nft))
                 } else {
                     None
                 }
@@ -229,29 +833,49 @@ fn get_nfts_by_owner(owner: Principal) -> Vec<ProductNFT> {
 /// Get NFT metadata by serial number
 #[query]
 fn get_metadata(serial_number: String) -> Result<NFTMetadata, String> {
-    let nft_id = SERIAL_TO_NFT.with(|map| {
-        map.borrow().get(&serial_number)
-    });
-    
-    match nft_id {
-        Some(id) => {
-            NFTS.with(|nfts| {
-                nfts.borrow().get(&id)
-                    .map(|nft| nft.metadata.clone())
-                    .ok_or_else(|| "NFT not found".to_string())
-            })
-        },
-        None => Err(format!("No NFT found for serial number: {}", serial_number))
-    }
+    find_by_serial(&serial_number).map(|nft| privacy::view(caller(), nft).metadata)
 }
 
-/// Get ownership history for an NFT
+/// Get ownership history for an NFT (the mint and the latest transfers; see
+/// `get_ownership_history_paged`)
 #[query]
 fn get_ownership_history(nft_id: u64) -> Result<Vec<OwnershipRecord>, String> {
-    NFTS.with(|nfts| {
-        nfts.borrow().get(&nft_id)
-            .map(|nft| nft.ownership_history.clone())
-            .ok_or_else(|| format!("NFT {} not found", nft_id))
+    get_nft(nft_id).map(|nft| privacy::view(caller(), nft).ownership_history)
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct OwnershipHistoryPage {
+    pub items: Vec<OwnershipRecord>,
+    /// Records held in the token.
+    pub total: u64,
+    pub offset: u64,
+    pub limit: u64,
+    /// The token has reached the history cap, so transfers between the mint
+    /// and the first record after it may only be in the transaction log.
+    pub capped: bool,
+}
+
+/// Maximum page size for `get_ownership_history_paged`
+const MAX_HISTORY_PAGE: u64 = 50;
+
+/// Page through an NFT's ownership history, oldest first
+#[query]
+fn get_ownership_history_paged(nft_id: u64, offset: u64, limit: u64) -> Result<OwnershipHistoryPage, String> {
+    let limit = limit.clamp(1, MAX_HISTORY_PAGE);
+    let nft = privacy::view(caller(), get_nft(nft_id)?);
+    let history = nft.ownership_history;
+    let total = history.len() as u64;
+    let items = history
+        .into_iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .collect();
+    Ok(OwnershipHistoryPage {
+        items,
+        total,
+        offset,
+        limit,
+        capped: total >= u64::from(limits::current().max_history_entries),
     })
 }
 
@@ -261,30 +885,5 @@ fn get_total_supply() -> u64 {
     NFT_COUNTER.with(|counter| *counter.borrow())
 }
 
-/// Admin: Revoke NFT verification (for counterfeit products)
-#[update]
-fn revoke_verification(nft_id: u64) -> Result<ProductNFT, String> {
-    let caller = caller();
-    
-    // Check if caller is admin
-    let is_admin = ADMIN.with(|admin| *admin.borrow() == caller);
-    if !is_admin {
-        return Err("Only admin can revoke verification".to_string());
-    }
-    
-    let mut nft = NFTS.with(|nfts| {
-        nfts.borrow().get(&nft_id)
-            .ok_or_else(|| format!("NFT {} not found", nft_id))
-    })?;
-    
-    nft.verified = false;
-    
-    NFTS.with(|nfts| {
-        nfts.borrow_mut().insert(nft_id, nft.clone());
-    });
-    
-    Ok(nft)
-}
-
 /// Export candid interface
 ic_cdk::export_candid!();
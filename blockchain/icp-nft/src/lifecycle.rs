@@ -0,0 +1,152 @@
+//! Product lifecycle state machine.
+//!
+//! Independent of who owns the certificate, each unit moves through
+//! `ProductState`s recorded by supply-chain partners. Allowed transitions
+//! and who may make them:
+//!
+//! | from                      | to               | by                         |
+//! |---------------------------|------------------|----------------------------|
+//! | Manufactured              | InDistribution   | minter, Distributor        |
+//! | Manufactured, InDistribution | Sold          | minter, Distributor, Marketplace |
+//! | Sold                      | InService        | ServiceCenter              |
+//! | InService                 | Sold             | ServiceCenter              |
+//! | Sold, InService           | Returned         | owner, minter              |
+//! | Returned                  | InDistribution   | minter                     |
+//! | Sold, InService, Returned | Recycled         | Recycler                   |
+//! | any but Destroyed         | Destroyed        | minter, Recycler           |
+//!
+//! The SuperAdmin may make any allowed transition.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use serde::{Deserialize, Serialize};
+
+use crate::limits;
+use crate::pause::not_paused;
+use crate::roles::{self, Role};
+use crate::{get_nft, save_nft, txlog, ProductNFT};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProductState {
+    Manufactured,
+    InDistribution,
+    Sold,
+    InService,
+    Returned,
+    Recycled,
+    Destroyed,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LifecycleRecord {
+    pub state: ProductState,
+    pub changed_by: Principal,
+    pub changed_at: u64,
+    pub note: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Lifecycle {
+    pub state: ProductState,
+    pub history: Vec<LifecycleRecord>,
+}
+
+impl Lifecycle {
+    pub fn new(minter: Principal, at: u64) -> Self {
+        Lifecycle {
+            state: ProductState::Manufactured,
+            history: vec![LifecycleRecord {
+                state: ProductState::Manufactured,
+                changed_by: minter,
+                changed_at: at,
+                note: None,
+            }],
+        }
+    }
+}
+
+enum Actor {
+    Minter,
+    Owner,
+    Role(Role),
+}
+
+/// Who may move a unit from `from` to `to`; `None` if the transition is not allowed.
+fn allowed_actors(from: ProductState, to: ProductState) -> Option<&'static [Actor]> {
+    use ProductState::*;
+    let actors: &'static [Actor] = match (from, to) {
+        (Manufactured, InDistribution) => &[Actor::Minter, Actor::Role(Role::Distributor)],
+        (Manufactured | InDistribution, Sold) => &[
+            Actor::Minter,
+            Actor::Role(Role::Distributor),
+            Actor::Role(Role::Marketplace),
+        ],
+        (Sold, InService) | (InService, Sold) => &[Actor::Role(Role::ServiceCenter)],
+        (Sold | InService, Returned) => &[Actor::Owner, Actor::Minter],
+        (Returned, InDistribution) => &[Actor::Minter],
+        (Sold | InService | Returned, Recycled) => &[Actor::Role(Role::Recycler)],
+        (from, Destroyed) if from != Destroyed => &[Actor::Minter, Actor::Role(Role::Recycler)],
+        _ => return None,
+    };
+    Some(actors)
+}
+
+fn authorized(caller: Principal, nft: &ProductNFT, actors: &[Actor]) -> bool {
+    roles::is_admin(caller)
+        || actors.iter().any(|actor| match actor {
+            Actor::Minter => caller == nft.minter(),
+            Actor::Owner => caller == nft.owner,
+            Actor::Role(role) => roles::has_role(caller, *role),
+        })
+}
+
+/// Current lifecycle of a token; tokens minted before lifecycles were
+/// tracked start as `Manufactured`.
+pub fn lifecycle_of(nft: &ProductNFT) -> Lifecycle {
+    nft.lifecycle.clone().unwrap_or_else(|| Lifecycle::new(nft.minter(), nft.minted_at))
+}
+
+/// Move a product to a new lifecycle state
+#[update(guard = "not_paused")]
+fn transition_lifecycle(nft_id: u64, to: ProductState, note: Option<String>) -> Result<ProductNFT, String> {
+    let caller = caller();
+    let mut nft = get_nft(nft_id)?;
+    if let Some(note) = &note {
+        limits::check_len("lifecycle note", note, limits::MAX_NOTE_BYTES)?;
+    }
+
+    let mut lifecycle = lifecycle_of(&nft);
+    let actors = allowed_actors(lifecycle.state, to)
+        .ok_or_else(|| format!("Cannot move from {:?} to {:?}", lifecycle.state, to))?;
+    if !authorized(caller, &nft, actors) {
+        return Err(format!("Not authorized to move from {:?} to {:?}", lifecycle.state, to));
+    }
+
+    lifecycle.state = to;
+    lifecycle.history.push(LifecycleRecord {
+        state: to,
+        changed_by: caller,
+        changed_at: ic_cdk::api::time(),
+        note,
+    });
+    limits::trim_front(&mut lifecycle.history, limits::MAX_LIFECYCLE_ENTRIES);
+    nft.lifecycle = Some(lifecycle);
+    save_nft(&nft);
+    txlog::append(
+        txlog::TxKind::MetadataUpdate,
+        nft.nft_id,
+        &nft.serial_number,
+        None,
+        None,
+        Some(format!("lifecycle: {:?}", to)),
+    );
+
+    Ok(nft)
+}
+
+/// Lifecycle state and history of a product
+#[query]
+fn get_lifecycle(nft_id: u64) -> Result<Lifecycle, String> {
+    get_nft(nft_id).map(|nft| lifecycle_of(&nft))
+}
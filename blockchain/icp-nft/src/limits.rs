@@ -0,0 +1,293 @@
+//! Size caps on stored tokens and request batches.
+//!
+//! `ProductNFT` is stored with a bounded `Storable`, so every variable-length
+//! part of it has a cap. The caps are checked where data enters the canister
+//! (mint, import, transfer memos, notes) and histories are trimmed to a fixed
+//! length; `MAX_NFT_BYTES` is the resulting upper bound on an encoded token.
+//!
+//! The metadata caps come from `proofcart-metadata-schema`, which the CLI
+//! and the gateway check against too.
+//!
+//! The most commonly tuned caps are also held in an admin-configurable
+//! `Limits`. The constants below are their ceilings: `set_limits` can lower a
+//! limit but never raise it past the constant the storage bound relies on.
+
+use candid::CandidType;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableCell;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+
+use crate::pause::not_paused;
+use crate::roles::require_admin;
+use crate::localization::LocalizedText;
+use crate::prices;
+use crate::sale_lock::MAX_ORDER_ID_LEN;
+use crate::{audit, memory, Memory, MintRequest, OwnershipRecord, ProductNFT};
+use proofcart_metadata_schema::{self as schema, Caps, SchemaError};
+
+pub use proofcart_metadata_schema::{
+    MAX_ARWEAVE_TX_ID_BYTES, MAX_CATEGORY_BYTES, MAX_CERTIFICATIONS, MAX_CERTIFICATION_BYTES, MAX_DESCRIPTION_BYTES,
+    MAX_LOCALES, MAX_LOCALIZED_DESCRIPTION_BYTES, MAX_LOCALIZED_WARRANTY_INFO_BYTES, MAX_MANUFACTURER_BYTES,
+    MAX_MANUFACTURE_DATE_BYTES, MAX_PRODUCT_NAME_BYTES, MAX_SERIAL_BYTES, MAX_SPECIFICATIONS_BYTES, MAX_URI_BYTES,
+    MAX_WARRANTY_INFO_BYTES,
+};
+/// Memos, notes and stored error messages.
+pub const MAX_NOTE_BYTES: usize = 256;
+/// Ownership records kept in the token: the mint plus the latest transfers.
+/// Every transfer is also in the transaction log.
+pub const MAX_HISTORY_ENTRIES: usize = 100;
+/// Lifecycle records kept in the token (the latest ones).
+pub const MAX_LIFECYCLE_ENTRIES: usize = 64;
+
+/// Ceilings on request batch sizes.
+pub const MAX_BATCH_VERIFY: usize = 100;
+pub const MAX_IMPORT_BATCH: usize = 500;
+pub const MAX_RECALL_BATCH: usize = 1_000;
+pub const MAX_RETAILER_BATCH: usize = 1_000;
+
+/// Upper bound on a candid-encoded `ProductNFT` under the caps above:
+/// about 28 KiB of metadata, 27 KiB of translations, 37 KiB of ownership
+/// history with sale prices, 19 KiB of lifecycle history and the candid type
+/// table, rounded up.
+pub const MAX_NFT_BYTES: u32 = 160 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LimitError {
+    /// A text field is longer than its cap, in bytes.
+    TooLong { field: &'static str, max: usize, actual: usize },
+    /// A list has more entries than its cap.
+    TooMany { field: &'static str, max: usize, actual: usize },
+    /// A request carries more items than one call may process.
+    BatchTooLarge { method: &'static str, max: usize, actual: usize },
+    /// `set_limits` asked for more than the built-in ceiling.
+    AboveCeiling { limit: &'static str, ceiling: usize, requested: usize },
+    /// `set_limits` asked for less than the limit can go.
+    BelowMinimum { limit: &'static str, minimum: usize, requested: usize },
+    /// Token metadata breaks a rule of the shared schema.
+    Metadata(SchemaError),
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::TooLong { field, max, actual } => {
+                write!(f, "{} is {} bytes; the limit is {}", field, actual, max)
+            }
+            LimitError::TooMany { field, max, actual } => {
+                write!(f, "{} has {} entries; the limit is {}", field, actual, max)
+            }
+            LimitError::BatchTooLarge { method, max, actual } => {
+                write!(f, "{} got {} items; at most {} per call", method, actual, max)
+            }
+            LimitError::AboveCeiling { limit, ceiling, requested } => {
+                write!(f, "{} cannot exceed {} (requested {})", limit, ceiling, requested)
+            }
+            LimitError::BelowMinimum { limit, minimum, requested } => {
+                write!(f, "{} must be at least {} (requested {})", limit, minimum, requested)
+            }
+            LimitError::Metadata(e) => e.fmt(f),
+        }
+    }
+}
+
+impl From<SchemaError> for LimitError {
+    fn from(e: SchemaError) -> LimitError {
+        LimitError::Metadata(e)
+    }
+}
+
+impl From<LimitError> for String {
+    fn from(e: LimitError) -> String {
+        e.to_string()
+    }
+}
+
+/// Admin-tunable limits, each at most its ceiling constant.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub max_description_bytes: u32,
+    pub max_specifications_bytes: u32,
+    pub max_warranty_info_bytes: u32,
+    pub max_certifications: u32,
+    /// Ownership records kept in a token, including the mint record.
+    pub max_history_entries: u32,
+    pub max_batch_verify: u32,
+    pub max_import_batch: u32,
+    pub max_recall_batch: u32,
+    pub max_retailer_batch: u32,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_description_bytes: MAX_DESCRIPTION_BYTES as u32,
+            max_specifications_bytes: MAX_SPECIFICATIONS_BYTES as u32,
+            max_warranty_info_bytes: MAX_WARRANTY_INFO_BYTES as u32,
+            max_certifications: MAX_CERTIFICATIONS as u32,
+            max_history_entries: MAX_HISTORY_ENTRIES as u32,
+            max_batch_verify: MAX_BATCH_VERIFY as u32,
+            max_import_batch: MAX_IMPORT_BATCH as u32,
+            max_recall_batch: MAX_RECALL_BATCH as u32,
+            max_retailer_batch: MAX_RETAILER_BATCH as u32,
+        }
+    }
+}
+
+impl Limits {
+    /// The metadata caps among these limits.
+    pub fn caps(&self) -> Caps {
+        Caps {
+            description_bytes: self.max_description_bytes as usize,
+            specifications_bytes: self.max_specifications_bytes as usize,
+            warranty_info_bytes: self.max_warranty_info_bytes as usize,
+            certifications: self.max_certifications as usize,
+        }
+    }
+}
+
+candid_storable!(Limits);
+
+thread_local! {
+    static LIMITS: RefCell<StableCell<Limits, Memory>> = RefCell::new(
+        StableCell::init(memory(38), Limits::default())
+            .expect("Failed to initialize limits")
+    );
+}
+
+/// The limits in force.
+pub fn current() -> Limits {
+    LIMITS.with(|l| l.borrow().get().clone())
+}
+
+fn check_setting(limit: &'static str, requested: u32, minimum: usize, ceiling: usize) -> Result<(), LimitError> {
+    let requested = requested as usize;
+    if requested > ceiling {
+        return Err(LimitError::AboveCeiling { limit, ceiling, requested });
+    }
+    if requested < minimum {
+        return Err(LimitError::BelowMinimum { limit, minimum, requested });
+    }
+    Ok(())
+}
+
+fn validate(limits: &Limits) -> Result<(), LimitError> {
+    check_setting("max_description_bytes", limits.max_description_bytes, 0, MAX_DESCRIPTION_BYTES)?;
+    check_setting("max_specifications_bytes", limits.max_specifications_bytes, 0, MAX_SPECIFICATIONS_BYTES)?;
+    check_setting("max_warranty_info_bytes", limits.max_warranty_info_bytes, 0, MAX_WARRANTY_INFO_BYTES)?;
+    check_setting("max_certifications", limits.max_certifications, 0, MAX_CERTIFICATIONS)?;
+    // The mint record plus at least one transfer.
+    check_setting("max_history_entries", limits.max_history_entries, 2, MAX_HISTORY_ENTRIES)?;
+    check_setting("max_batch_verify", limits.max_batch_verify, 1, MAX_BATCH_VERIFY)?;
+    check_setting("max_import_batch", limits.max_import_batch, 1, MAX_IMPORT_BATCH)?;
+    check_setting("max_recall_batch", limits.max_recall_batch, 1, MAX_RECALL_BATCH)?;
+    check_setting("max_retailer_batch", limits.max_retailer_batch, 1, MAX_RETAILER_BATCH)
+}
+
+/// Refuse a request batch over its limit.
+pub fn check_batch(method: &'static str, actual: usize, max: u32) -> Result<(), LimitError> {
+    if actual > max as usize {
+        return Err(LimitError::BatchTooLarge { method, max: max as usize, actual });
+    }
+    Ok(())
+}
+
+pub fn check_len(field: &'static str, value: &str, max: usize) -> Result<(), LimitError> {
+    if value.len() > max {
+        return Err(LimitError::TooLong { field, max, actual: value.len() });
+    }
+    Ok(())
+}
+
+pub fn check_count<T>(field: &'static str, items: &[T], max: usize) -> Result<(), LimitError> {
+    if items.len() > max {
+        return Err(LimitError::TooMany { field, max, actual: items.len() });
+    }
+    Ok(())
+}
+
+/// Caps on a token's translations.
+pub fn check_localized(entries: &[(String, LocalizedText)]) -> Result<(), LimitError> {
+    Ok(schema::check_localized(entries)?)
+}
+
+/// Every schema rule for the metadata of a new token.
+pub fn check_mint_request(request: &MintRequest) -> Result<(), LimitError> {
+    Ok(schema::validate(&request.metadata(), &current().caps())?)
+}
+
+/// Caps on a whole token, for records that arrive fully formed (imports).
+pub fn check_nft(nft: &ProductNFT) -> Result<(), LimitError> {
+    let limits = current();
+    check_len("serial_number", &nft.serial_number, MAX_SERIAL_BYTES)?;
+    schema::check_lengths(&nft.metadata, &limits.caps())?;
+    check_count("ownership_history", &nft.ownership_history, limits.max_history_entries as usize)?;
+    for record in &nft.ownership_history {
+        check_len("memo", record.memo.as_deref().unwrap_or_default(), MAX_NOTE_BYTES)?;
+        if let Some(price) = &record.price {
+            check_len("price currency", &price.currency, prices::MAX_CURRENCY_LEN)?;
+            check_len("price order_id", price.order_id.as_deref().unwrap_or_default(), MAX_ORDER_ID_LEN)?;
+        }
+    }
+    if let Some(lifecycle) = &nft.lifecycle {
+        check_count("lifecycle history", &lifecycle.history, MAX_LIFECYCLE_ENTRIES)?;
+        for record in &lifecycle.history {
+            check_len("lifecycle note", record.note.as_deref().unwrap_or_default(), MAX_NOTE_BYTES)?;
+        }
+    }
+    if let Some(recall) = &nft.recall {
+        check_len("recall notice_uri", &recall.notice_uri, MAX_URI_BYTES)?;
+    }
+    if let Some(integrity) = &nft.metadata_integrity {
+        check_len("integrity error", integrity.last_error.as_deref().unwrap_or_default(), MAX_NOTE_BYTES)?;
+    }
+    Ok(())
+}
+
+/// Keep the mint record and the latest transfers.
+pub fn trim_history(history: &mut Vec<OwnershipRecord>) {
+    let max = current().max_history_entries as usize;
+    if history.len() > max {
+        history.drain(1..=history.len() - max);
+    }
+}
+
+/// Keep the latest `max` entries.
+pub fn trim_front<T>(entries: &mut Vec<T>, max: usize) {
+    if entries.len() > max {
+        entries.drain(..entries.len() - max);
+    }
+}
+
+/// Cut a stored message to `MAX_NOTE_BYTES` on a character boundary.
+pub fn truncate_note(mut note: String) -> String {
+    if note.len() > MAX_NOTE_BYTES {
+        let mut end = MAX_NOTE_BYTES;
+        while !note.is_char_boundary(end) {
+            end -= 1;
+        }
+        note.truncate(end);
+    }
+    note
+}
+
+/// Admin: tune the size and batch limits (each at most its built-in ceiling)
+#[update(guard = "not_paused")]
+fn set_limits(limits: Limits) -> Result<Limits, String> {
+    require_admin()?;
+    validate(&limits)?;
+    LIMITS.with(|l| {
+        l.borrow_mut()
+            .set(limits.clone())
+            .map_err(|e| format!("Failed to persist limits: {:?}", e))
+    })?;
+    audit::record("set_limits");
+    Ok(limits)
+}
+
+/// The size and batch limits in force
+#[query]
+fn get_limits() -> Limits {
+    current()
+}
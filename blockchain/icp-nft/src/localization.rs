@@ -0,0 +1,118 @@
+//! Localized product metadata.
+//!
+//! `NFTMetadata::localized` optionally carries translations of the customer-
+//! facing text (name, description, warranty terms) keyed by a lowercase
+//! language tag such as `"fr"` or `"pt-br"`. Lookups fall back from the full
+//! tag to its primary language and then to the default metadata, field by
+//! field, so a partial translation is still useful.
+
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+
+use crate::pause::not_paused;
+use crate::roles::is_admin;
+use crate::{find_by_serial, get_nft, limits, privacy, save_nft, txlog, NFTMetadata, ProductNFT};
+
+pub use proofcart_types::LocalizedText;
+
+/// Canonical language tag: trimmed, lowercase, `_` written as `-`. A tag is
+/// 2-3 letters optionally followed by `-` and up to 8 letters or digits.
+pub fn normalize_lang(lang: &str) -> Result<String, String> {
+    let lang = lang.trim().to_lowercase().replace('_', "-");
+    let mut parts = lang.splitn(2, '-');
+    let primary = parts.next().unwrap_or_default();
+    let valid_primary = (2..=3).contains(&primary.len()) && primary.chars().all(|c| c.is_ascii_lowercase());
+    let valid_region = parts
+        .next()
+        .map_or(true, |region| (1..=8).contains(&region.len()) && region.chars().all(|c| c.is_ascii_alphanumeric()));
+    if !valid_primary || !valid_region {
+        return Err(format!("Invalid language tag: {}", lang));
+    }
+    Ok(lang)
+}
+
+/// Normalize and deduplicate the tags of a translation set.
+pub fn normalize_entries(entries: Vec<(String, LocalizedText)>) -> Result<Vec<(String, LocalizedText)>, String> {
+    let mut normalized: Vec<(String, LocalizedText)> = Vec::with_capacity(entries.len());
+    for (lang, text) in entries {
+        let lang = normalize_lang(&lang)?;
+        if normalized.iter().any(|(existing, _)| *existing == lang) {
+            return Err(format!("Language {} is listed twice", lang));
+        }
+        normalized.push((lang, text));
+    }
+    normalized.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(normalized)
+}
+
+fn translation<'a>(metadata: &'a NFTMetadata, lang: &str) -> Option<&'a LocalizedText> {
+    metadata
+        .localized
+        .as_ref()?
+        .iter()
+        .find(|(tag, _)| tag == lang)
+        .map(|(_, text)| text)
+}
+
+/// `metadata` with its customer-facing text in `lang` where available.
+pub fn localize(mut metadata: NFTMetadata, lang: &str) -> NFTMetadata {
+    let primary = lang.split('-').next().unwrap_or(lang);
+    let candidates = [translation(&metadata, lang), translation(&metadata, primary)];
+    let pick = |field: fn(&LocalizedText) -> &Option<String>| {
+        candidates.iter().flatten().find_map(|text| field(text).clone())
+    };
+    let product_name = pick(|t| &t.product_name);
+    let description = pick(|t| &t.description);
+    let warranty_info = pick(|t| &t.warranty_info);
+
+    if let Some(product_name) = product_name {
+        metadata.product_name = product_name;
+    }
+    if let Some(description) = description {
+        metadata.description = description;
+    }
+    if let Some(warranty_info) = warranty_info {
+        metadata.warranty_info = warranty_info;
+    }
+    metadata
+}
+
+/// Minter: add, replace (`Some`) or remove (`None`) the translation for one language
+#[update(guard = "not_paused")]
+fn set_localized_metadata(nft_id: u64, lang: String, text: Option<LocalizedText>) -> Result<ProductNFT, String> {
+    let caller = caller();
+    let mut nft = get_nft(nft_id)?;
+    if caller != nft.minter() && !is_admin(caller) {
+        return Err("Only the minter can change localized metadata".to_string());
+    }
+    let lang = normalize_lang(&lang)?;
+
+    let mut entries = nft.metadata.localized.take().unwrap_or_default();
+    entries.retain(|(tag, _)| *tag != lang);
+    if let Some(text) = text {
+        entries.push((lang.clone(), text));
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+    limits::check_localized(&entries)?;
+    nft.metadata.localized = if entries.is_empty() { None } else { Some(entries) };
+
+    save_nft(&nft);
+    txlog::append(
+        txlog::TxKind::MetadataUpdate,
+        nft.nft_id,
+        &nft.serial_number,
+        None,
+        None,
+        Some(format!("localized metadata changed ({})", lang)),
+    );
+    Ok(nft)
+}
+
+/// Metadata for a serial in `lang`, falling back to the primary language and
+/// then to the default text
+#[query]
+fn get_metadata_localized(serial_number: String, lang: String) -> Result<NFTMetadata, String> {
+    let lang = normalize_lang(&lang)?;
+    let nft = find_by_serial(&serial_number)?;
+    Ok(localize(privacy::view(caller(), nft).metadata, &lang))
+}
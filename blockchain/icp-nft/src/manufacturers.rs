@@ -0,0 +1,308 @@
+//! Registered manufacturers and their per-brand minting rules.
+//!
+//! Besides a serial format, a manufacturer can reserve serial prefixes
+//! (namespaces such as `"ACME-"`). Only the holder of a reservation may mint
+//! serials that start with it, so nobody can squat a brand's serial range
+//! ahead of the brand. Tokens minted before a reservation are not touched.
+//!
+//! An admin can also cap how many tokens a manufacturer may mint (say, its
+//! declared production volume), so a compromised manufacturer key cannot
+//! flood the registry with fake serials.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+
+use crate::pause::not_paused;
+use crate::roles::{is_admin, require_admin};
+use crate::{audit, limits, serials};
+use crate::{memory, Memory};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Manufacturer {
+    pub principal: Principal,
+    pub name: String,
+    pub registered_at: u64,
+    /// Pattern every serial minted by this manufacturer must match
+    /// (see `serials::matches_pattern`).
+    pub serial_format: Option<String>,
+    /// Most tokens this manufacturer may mint; `None` is unlimited.
+    pub mint_quota: Option<u64>,
+    /// Tokens minted since quotas were introduced.
+    pub minted: Option<u64>,
+}
+
+candid_storable!(Manufacturer);
+
+/// A mint refused because the manufacturer used up its quota.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    pub manufacturer: String,
+    pub quota: u64,
+    pub minted: u64,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} has minted {} of its quota of {} tokens; ask an admin to raise it",
+            self.manufacturer, self.minted, self.quota
+        )
+    }
+}
+
+impl From<QuotaExceeded> for String {
+    fn from(e: QuotaExceeded) -> String {
+        e.to_string()
+    }
+}
+
+/// Shortest prefix that can be reserved, after normalization.
+const MIN_PREFIX_LEN: usize = 3;
+const MAX_PREFIXES_PER_MANUFACTURER: usize = 20;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SerialPrefix {
+    /// Normalized prefix.
+    pub prefix: String,
+    pub manufacturer: Principal,
+    pub reserved_at: u64,
+}
+
+candid_storable!(SerialPrefix);
+
+thread_local! {
+    static MANUFACTURERS: RefCell<StableBTreeMap<Principal, Manufacturer, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(13)));
+
+    // Normalized prefix -> reservation
+    static SERIAL_PREFIXES: RefCell<StableBTreeMap<String, SerialPrefix, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(34)));
+}
+
+pub fn get(principal: Principal) -> Option<Manufacturer> {
+    MANUFACTURERS.with(|m| m.borrow().get(&principal))
+}
+
+fn save(manufacturer: Manufacturer) {
+    MANUFACTURERS.with(|m| {
+        m.borrow_mut().insert(manufacturer.principal, manufacturer);
+    });
+}
+
+/// Enforce the minter's serial format, if it is a registered manufacturer
+/// with one configured. `serial_number` must already be normalized.
+pub fn check_serial_format(minter: Principal, serial_number: &str) -> Result<(), String> {
+    if let Some(Manufacturer { name, serial_format: Some(format), .. }) = get(minter) {
+        if !serials::matches_pattern(serial_number, &format) {
+            return Err(format!(
+                "Serial {} does not match {}'s format {}",
+                serial_number, name, format
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Fail if the minter is a registered manufacturer with no quota left.
+pub fn check_mint_quota(minter: Principal) -> Result<(), QuotaExceeded> {
+    if let Some(Manufacturer { name, mint_quota: Some(quota), minted, .. }) = get(minter) {
+        let minted = minted.unwrap_or(0);
+        if minted >= quota {
+            return Err(QuotaExceeded { manufacturer: name, quota, minted });
+        }
+    }
+    Ok(())
+}
+
+/// Count a mint against the minter's quota, if it is a registered manufacturer.
+pub fn record_mint(minter: Principal) {
+    if let Some(mut manufacturer) = get(minter) {
+        manufacturer.minted = Some(manufacturer.minted.unwrap_or(0) + 1);
+        save(manufacturer);
+    }
+}
+
+/// The reservation covering a normalized serial, if any. Reservations never
+/// overlap, so at most one prefix of the serial is reserved.
+fn reservation_for(serial_number: &str) -> Option<SerialPrefix> {
+    SERIAL_PREFIXES.with(|prefixes| {
+        let prefixes = prefixes.borrow();
+        serial_number
+            .char_indices()
+            .map(|(i, c)| &serial_number[..i + c.len_utf8()])
+            .find_map(|prefix| prefixes.get(&prefix.to_string()))
+    })
+}
+
+/// Refuse a normalized serial inside another manufacturer's namespace.
+pub fn check_serial_namespace(minter: Principal, serial_number: &str) -> Result<(), String> {
+    match reservation_for(serial_number) {
+        Some(reservation) if reservation.manufacturer != minter => {
+            let holder = get(reservation.manufacturer).map_or_else(|| reservation.manufacturer.to_text(), |m| m.name);
+            Err(format!(
+                "Serial {} is in the {} namespace reserved by {}",
+                serial_number, reservation.prefix, holder
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn prefixes_of(manufacturer: Principal) -> Vec<SerialPrefix> {
+    SERIAL_PREFIXES.with(|prefixes| {
+        prefixes
+            .borrow()
+            .iter()
+            .map(|(_, reservation)| reservation)
+            .filter(|reservation| reservation.manufacturer == manufacturer)
+            .collect()
+    })
+}
+
+/// Admin: register a manufacturer principal
+#[update(guard = "not_paused")]
+fn register_manufacturer(principal: Principal, name: String) -> Result<Manufacturer, String> {
+    require_admin()?;
+    if name.trim().is_empty() {
+        return Err("Manufacturer name is required".to_string());
+    }
+    let manufacturer = match get(principal) {
+        Some(existing) => Manufacturer { name, ..existing },
+        None => Manufacturer {
+            principal,
+            name,
+            registered_at: ic_cdk::api::time(),
+            serial_format: None,
+            mint_quota: None,
+            minted: Some(0),
+        },
+    };
+    save(manufacturer.clone());
+    audit::record("register_manufacturer");
+    Ok(manufacturer)
+}
+
+/// Admin: cap the tokens a manufacturer may mint in total (`None` lifts the cap)
+#[update(guard = "not_paused")]
+fn set_mint_quota(principal: Principal, quota: Option<u64>) -> Result<Manufacturer, String> {
+    require_admin()?;
+    let mut manufacturer = get(principal).ok_or_else(|| format!("Manufacturer {} not found", principal))?;
+    manufacturer.mint_quota = quota;
+    save(manufacturer.clone());
+    audit::record("set_mint_quota");
+    Ok(manufacturer)
+}
+
+/// Manufacturer: set or clear the format rule for serials it mints
+#[update(guard = "not_paused")]
+fn set_serial_format(format: Option<String>) -> Result<Manufacturer, String> {
+    let mut manufacturer = get(caller())
+        .ok_or_else(|| "Caller is not a registered manufacturer".to_string())?;
+    if let Some(format) = &format {
+        serials::validate_pattern(format)?;
+    }
+    manufacturer.serial_format = format;
+    save(manufacturer.clone());
+    Ok(manufacturer)
+}
+
+/// Manufacturer: reserve a serial prefix so only it can mint serials in it
+#[update(guard = "not_paused")]
+fn reserve_serial_prefix(prefix: String) -> Result<SerialPrefix, String> {
+    let caller = caller();
+    let manufacturer = get(caller).ok_or_else(|| "Caller is not a registered manufacturer".to_string())?;
+    let prefix = serials::normalize(&prefix);
+    if prefix.chars().count() < MIN_PREFIX_LEN {
+        return Err(format!("Serial prefixes must be at least {} characters", MIN_PREFIX_LEN));
+    }
+    limits::check_len("prefix", &prefix, limits::MAX_SERIAL_BYTES)?;
+
+    // Reservations may not nest: neither a shorter one covering this prefix
+    // nor a longer one inside it may belong to someone else.
+    if let Some(existing) = reservation_for(&prefix) {
+        if existing.manufacturer == caller {
+            return Ok(existing);
+        }
+        return Err(format!("{} is inside the {} namespace, which is already reserved", prefix, existing.prefix));
+    }
+    let nested = SERIAL_PREFIXES.with(|prefixes| {
+        prefixes
+            .borrow()
+            .range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .find(|(_, reservation)| reservation.manufacturer != caller)
+            .map(|(key, _)| key)
+    });
+    if let Some(nested) = nested {
+        return Err(format!("{} would cover {}, which is already reserved", prefix, nested));
+    }
+    if prefixes_of(caller).len() >= MAX_PREFIXES_PER_MANUFACTURER {
+        return Err(format!(
+            "{} already holds the maximum of {} serial prefixes",
+            manufacturer.name, MAX_PREFIXES_PER_MANUFACTURER
+        ));
+    }
+
+    let reservation = SerialPrefix { prefix: prefix.clone(), manufacturer: caller, reserved_at: ic_cdk::api::time() };
+    SERIAL_PREFIXES.with(|prefixes| {
+        prefixes.borrow_mut().insert(prefix, reservation.clone());
+    });
+    Ok(reservation)
+}
+
+/// Holder or admin: release a reserved serial prefix
+#[update(guard = "not_paused")]
+fn release_serial_prefix(prefix: String) -> Result<(), String> {
+    let caller = caller();
+    let prefix = serials::normalize(&prefix);
+    let reservation = SERIAL_PREFIXES
+        .with(|prefixes| prefixes.borrow().get(&prefix))
+        .ok_or_else(|| format!("Serial prefix {} is not reserved", prefix))?;
+    if reservation.manufacturer != caller && !is_admin(caller) {
+        return Err("Only the holder or an admin can release a serial prefix".to_string());
+    }
+    SERIAL_PREFIXES.with(|prefixes| {
+        prefixes.borrow_mut().remove(&prefix);
+    });
+    Ok(())
+}
+
+/// Serial prefixes, all or those held by one manufacturer
+#[query]
+fn list_serial_prefixes(manufacturer: Option<Principal>) -> Vec<SerialPrefix> {
+    match manufacturer {
+        Some(manufacturer) => prefixes_of(manufacturer),
+        None => SERIAL_PREFIXES.with(|prefixes| prefixes.borrow().iter().map(|(_, reservation)| reservation).collect()),
+    }
+}
+
+/// The reservation covering a serial, if any
+#[query]
+fn get_serial_namespace(serial_number: String) -> Option<SerialPrefix> {
+    reservation_for(&serials::normalize(&serial_number))
+}
+
+/// Get a registered manufacturer
+#[query]
+fn get_manufacturer(principal: Principal) -> Option<Manufacturer> {
+    get(principal)
+}
+
+/// List registered manufacturers
+#[query]
+fn list_manufacturers(offset: u64, limit: u64) -> Vec<Manufacturer> {
+    MANUFACTURERS.with(|m| {
+        m.borrow()
+            .iter()
+            .skip(offset as usize)
+            .take(limit.min(100) as usize)
+            .map(|(_, manufacturer)| manufacturer)
+            .collect()
+    })
+}
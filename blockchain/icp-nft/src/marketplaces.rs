@@ -0,0 +1,249 @@
+//! Partner marketplaces: white-labelled ProofCart storefronts sharing this
+//! canister.
+//!
+//! The SuperAdmin registers a marketplace under the same id as its account
+//! on the escrow program and names its admins. Those admins keep the
+//! marketplace's own minter registry: only registered minters can create
+//! collections in the marketplace, and removing a minter stops it minting
+//! into them. Sales of tokens in a marketplace's collections are checked
+//! against that marketplace's escrows (see `escrow_check`).
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::pause::not_paused;
+use crate::roles::{is_admin, require_admin};
+use crate::{audit, limits, memory, Memory};
+
+/// Same bound as the escrow program's marketplace seed.
+const MAX_MARKETPLACE_ID_LEN: usize = 32;
+const MAX_ADMINS: usize = 10;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Marketplace {
+    /// Lowercase letters, digits and dashes; matches the escrow program's id.
+    pub marketplace_id: String,
+    pub name: String,
+    /// Manage the marketplace's minters.
+    pub admins: Vec<Principal>,
+    pub created_at: u64,
+}
+
+candid_storable!(Marketplace);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MarketplaceMinter {
+    pub marketplace_id: String,
+    pub principal: Principal,
+    pub name: String,
+    pub registered_at: u64,
+}
+
+candid_storable!(MarketplaceMinter);
+
+thread_local! {
+    static MARKETPLACES: RefCell<StableBTreeMap<String, Marketplace, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(50)));
+
+    // minter_prefix(marketplace_id) ++ principal -> minter
+    static MINTERS: RefCell<StableBTreeMap<Vec<u8>, MarketplaceMinter, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(51)));
+}
+
+/// Length-prefixed, so one id's minters never share a prefix with another's.
+fn minter_prefix(marketplace_id: &str) -> Vec<u8> {
+    let mut prefix = vec![marketplace_id.len() as u8];
+    prefix.extend_from_slice(marketplace_id.as_bytes());
+    prefix
+}
+
+fn minter_key(marketplace_id: &str, principal: Principal) -> Vec<u8> {
+    let mut key = minter_prefix(marketplace_id);
+    key.extend_from_slice(principal.as_slice());
+    key
+}
+
+fn validate_id(marketplace_id: &str) -> Result<(), String> {
+    let valid = !marketplace_id.is_empty()
+        && marketplace_id.len() <= MAX_MARKETPLACE_ID_LEN
+        && marketplace_id.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-');
+    if !valid {
+        return Err("Marketplace ids are 1-32 lowercase letters, digits or dashes".to_string());
+    }
+    Ok(())
+}
+
+fn validate_admins(admins: &[Principal]) -> Result<(), String> {
+    if admins.is_empty() || admins.len() > MAX_ADMINS {
+        return Err(format!("A marketplace needs 1 to {} admins", MAX_ADMINS));
+    }
+    if admins.contains(&Principal::anonymous()) {
+        return Err("The anonymous principal cannot be a marketplace admin".to_string());
+    }
+    Ok(())
+}
+
+pub fn get(marketplace_id: &str) -> Result<Marketplace, String> {
+    MARKETPLACES.with(|m| {
+        m.borrow()
+            .get(&marketplace_id.to_string())
+            .ok_or_else(|| format!("Marketplace {} not found", marketplace_id))
+    })
+}
+
+fn save(marketplace: Marketplace) {
+    MARKETPLACES.with(|m| {
+        m.borrow_mut().insert(marketplace.marketplace_id.clone(), marketplace);
+    });
+}
+
+pub fn is_minter(marketplace_id: &str, principal: Principal) -> bool {
+    MINTERS.with(|m| m.borrow().contains_key(&minter_key(marketplace_id, principal)))
+}
+
+/// Fail unless `principal` is a registered minter of the marketplace.
+pub fn ensure_minter(marketplace_id: &str, principal: Principal) -> Result<(), String> {
+    let marketplace = get(marketplace_id)?;
+    if !is_minter(marketplace_id, principal) {
+        return Err(format!("Caller is not a registered minter of {}", marketplace.name));
+    }
+    Ok(())
+}
+
+/// Fail unless the caller administers the marketplace or is the SuperAdmin.
+fn require_marketplace_admin(marketplace_id: &str) -> Result<Marketplace, String> {
+    let marketplace = get(marketplace_id)?;
+    let caller = caller();
+    if !marketplace.admins.contains(&caller) && !is_admin(caller) {
+        return Err(format!("Only an admin of {} can manage its minters", marketplace.name));
+    }
+    Ok(marketplace)
+}
+
+/// Admin: register a partner marketplace and its admins
+#[update(guard = "not_paused")]
+fn create_marketplace(marketplace_id: String, name: String, admins: Vec<Principal>) -> Result<Marketplace, String> {
+    require_admin()?;
+    validate_id(&marketplace_id)?;
+    if name.trim().is_empty() {
+        return Err("Marketplace name is required".to_string());
+    }
+    limits::check_len("name", &name, limits::MAX_NOTE_BYTES)?;
+    validate_admins(&admins)?;
+    if get(&marketplace_id).is_ok() {
+        return Err(format!("Marketplace {} already exists", marketplace_id));
+    }
+
+    let marketplace = Marketplace { marketplace_id, name, admins, created_at: ic_cdk::api::time() };
+    save(marketplace.clone());
+    audit::record("create_marketplace");
+    Ok(marketplace)
+}
+
+/// Admin: replace a marketplace's admins
+#[update(guard = "not_paused")]
+fn set_marketplace_admins(marketplace_id: String, admins: Vec<Principal>) -> Result<Marketplace, String> {
+    require_admin()?;
+    validate_admins(&admins)?;
+    let mut marketplace = get(&marketplace_id)?;
+    marketplace.admins = admins;
+    save(marketplace.clone());
+    audit::record("set_marketplace_admins");
+    Ok(marketplace)
+}
+
+/// Marketplace admin: register a principal allowed to create collections in the marketplace
+#[update(guard = "not_paused")]
+fn register_marketplace_minter(
+    marketplace_id: String,
+    principal: Principal,
+    name: String,
+) -> Result<MarketplaceMinter, String> {
+    require_marketplace_admin(&marketplace_id)?;
+    if principal == Principal::anonymous() {
+        return Err("The anonymous principal cannot mint".to_string());
+    }
+    if name.trim().is_empty() {
+        return Err("Minter name is required".to_string());
+    }
+    limits::check_len("name", &name, limits::MAX_NOTE_BYTES)?;
+
+    let key = minter_key(&marketplace_id, principal);
+    let minter = match MINTERS.with(|m| m.borrow().get(&key)) {
+        Some(existing) => MarketplaceMinter { name, ..existing },
+        None => MarketplaceMinter { marketplace_id, principal, name, registered_at: ic_cdk::api::time() },
+    };
+    MINTERS.with(|m| {
+        m.borrow_mut().insert(key, minter.clone());
+    });
+    audit::record("register_marketplace_minter");
+    Ok(minter)
+}
+
+/// Marketplace admin: remove a minter; it can no longer mint into the marketplace's collections
+#[update(guard = "not_paused")]
+fn remove_marketplace_minter(marketplace_id: String, principal: Principal) -> Result<(), String> {
+    require_marketplace_admin(&marketplace_id)?;
+    MINTERS
+        .with(|m| m.borrow_mut().remove(&minter_key(&marketplace_id, principal)))
+        .ok_or_else(|| format!("{} is not a minter of {}", principal, marketplace_id))?;
+    audit::record("remove_marketplace_minter");
+    Ok(())
+}
+
+/// Get a partner marketplace
+#[query]
+fn get_marketplace(marketplace_id: String) -> Result<Marketplace, String> {
+    get(&marketplace_id)
+}
+
+/// List partner marketplaces
+#[query]
+fn list_marketplaces(offset: u64, limit: u64) -> Vec<Marketplace> {
+    MARKETPLACES.with(|m| {
+        m.borrow()
+            .iter()
+            .skip(offset as usize)
+            .take(limit.min(100) as usize)
+            .map(|(_, marketplace)| marketplace)
+            .collect()
+    })
+}
+
+/// The registered minters of a marketplace
+#[query]
+fn list_marketplace_minters(marketplace_id: String) -> Vec<MarketplaceMinter> {
+    let prefix = minter_prefix(&marketplace_id);
+    MINTERS.with(|m| {
+        m.borrow()
+            .range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .map(|(_, minter)| minter)
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minter_keys_do_not_cross_marketplaces() {
+        let principal = Principal::from_slice(&[7; 29]);
+        let acme = minter_prefix("acme");
+        assert!(minter_key("acme", principal).starts_with(&acme));
+        assert!(!minter_key("acme-2", principal).starts_with(&acme));
+    }
+
+    #[test]
+    fn marketplace_ids_match_the_escrow_program() {
+        assert!(validate_id("acme-2").is_ok());
+        for id in ["", "Acme", "acme shop", "acme_2", &"a".repeat(33)] {
+            assert!(validate_id(id).is_err(), "{:?}", id);
+        }
+    }
+}
@@ -0,0 +1,310 @@
+//! Stable schema versions and `ProductNFT` migrations.
+//!
+//! Stored tokens are candid records, and each incompatible change to
+//! `ProductNFT` bumps `CURRENT_SCHEMA_VERSION` and keeps a copy of the old
+//! layout in `legacy`. Reads accept every known layout and convert on the fly
+//! (the lazy path), so an upgrade never has to stop the canister. On upgrade
+//! `run` also rewrites stored tokens in the current layout (the eager path):
+//! a first batch inside `post_upgrade`, the rest on a timer, resuming from a
+//! cursor. Once every token has been rewritten the stored schema version is
+//! raised and the old layouts are no longer present in stable memory.
+//!
+//! Adding a field with `Option` type is compatible and needs no new version.
+//! For anything else: freeze the current layout in `legacy`, add it to
+//! `legacy::decode_nft`, bump `CURRENT_SCHEMA_VERSION`, describe it in
+//! `MIGRATIONS` and add a fixture to the tests below.
+
+use candid::{CandidType, Decode};
+use ic_cdk_macros::query;
+use ic_stable_structures::StableCell;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::time::Duration;
+
+use crate::{legacy, memory, Memory, ProductNFT, NFTS};
+
+/// Version of the layout `ProductNFT` is written in.
+pub const CURRENT_SCHEMA_VERSION: u32 = 3;
+
+/// Layout changes, oldest first. Version 1 is the original layout.
+pub const MIGRATIONS: &[(u32, &str)] = &[
+    (2, "ownership records use TransactionType and carry a memo"),
+    (3, "the verified flag becomes verification_level, revoked and endorsement"),
+];
+
+/// Tokens rewritten inside `post_upgrade`; the rest go through the timer.
+const EAGER_BATCH: usize = 1_000;
+/// Tokens rewritten per timer tick.
+const TIMER_BATCH: usize = 500;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SchemaState {
+    /// Layout every stored token is known to be in. 0 means the canister
+    /// predates schema tracking.
+    pub version: u32,
+    /// Set while tokens are being rewritten to `CURRENT_SCHEMA_VERSION`.
+    pub migrating_from: Option<u32>,
+    /// Next `nft_id` to rewrite.
+    pub cursor: Option<u64>,
+    /// Tokens rewritten by the current migration.
+    pub migrated: u64,
+}
+
+candid_storable!(SchemaState);
+
+thread_local! {
+    static STATE: RefCell<StableCell<SchemaState, Memory>> = RefCell::new(
+        StableCell::init(memory(33), SchemaState::default())
+            .expect("Failed to initialize schema state")
+    );
+}
+
+fn state() -> SchemaState {
+    STATE.with(|s| s.borrow().get().clone())
+}
+
+fn set_state(state: SchemaState) {
+    STATE.with(|s| {
+        s.borrow_mut().set(state).expect("Failed to persist schema state");
+    });
+}
+
+/// Decode a stored token in any known layout, returning the schema version of
+/// the layout it was found in.
+pub fn decode_nft(bytes: &[u8]) -> Result<(u32, ProductNFT), String> {
+    Decode!(bytes, ProductNFT)
+        .map(|nft| (CURRENT_SCHEMA_VERSION, nft))
+        .or_else(|current| {
+            legacy::decode_nft(bytes).map_err(|e| format!("{} (as current layout: {})", e, current))
+        })
+}
+
+/// A fresh canister starts at the current version.
+pub fn init() {
+    set_state(SchemaState { version: CURRENT_SCHEMA_VERSION, ..Default::default() });
+}
+
+/// Called from `post_upgrade`: start or resume the rewrite of stored tokens.
+pub fn run() {
+    let mut state = state();
+    if state.version >= CURRENT_SCHEMA_VERSION && state.migrating_from.is_none() {
+        return;
+    }
+    if NFTS.with(|nfts| nfts.borrow().is_empty()) {
+        init();
+        return;
+    }
+    if state.migrating_from.is_none() {
+        state.migrating_from = Some(state.version);
+        state.cursor = Some(0);
+        state.migrated = 0;
+        set_state(state);
+    }
+    if !migrate_batch(EAGER_BATCH) {
+        schedule();
+    }
+}
+
+fn schedule() {
+    ic_cdk_timers::set_timer(Duration::ZERO, || {
+        if !migrate_batch(TIMER_BATCH) {
+            schedule();
+        }
+    });
+}
+
+/// Rewrite up to `limit` tokens from the cursor. Returns true once done.
+fn migrate_batch(limit: usize) -> bool {
+    let mut state = state();
+    let Some(start) = state.cursor else {
+        return true;
+    };
+
+    // Reading converts from any older layout; writing back stores the
+    // current one.
+    let batch: Vec<(u64, ProductNFT)> = NFTS.with(|nfts| nfts.borrow().range(start..).take(limit).collect());
+    NFTS.with(|nfts| {
+        let mut nfts = nfts.borrow_mut();
+        for (id, nft) in &batch {
+            nfts.insert(*id, nft.clone());
+        }
+    });
+    state.migrated += batch.len() as u64;
+
+    let done = batch.len() < limit;
+    if done {
+        state.version = CURRENT_SCHEMA_VERSION;
+        state.migrating_from = None;
+        state.cursor = None;
+    } else {
+        state.cursor = batch.last().map(|(id, _)| id + 1);
+    }
+    set_state(state);
+    done
+}
+
+/// Stored schema version and migration progress
+#[query]
+fn get_schema_state() -> SchemaState {
+    state()
+}
+
+#[cfg(test)]
+mod tests {
+    //! Fixtures are encoded from frozen copies of every historical layout,
+    //! independent of the decoders in `legacy`.
+
+    use super::*;
+    use crate::verification::VerificationLevel;
+    use crate::{NFTMetadata, OwnershipRecord, TransactionType};
+    use candid::{Encode, Principal};
+
+    fn owner() -> Principal {
+        Principal::from_slice(&[7; 29])
+    }
+
+    fn buyer() -> Principal {
+        Principal::from_slice(&[9; 29])
+    }
+
+    fn metadata() -> NFTMetadata {
+        NFTMetadata {
+            serial_number: "SN1".to_string(),
+            product_name: "Widget".to_string(),
+            manufacturer: "Acme".to_string(),
+            manufacture_date: "2024-01-01".to_string(),
+            category: "Tools".to_string(),
+            description: "A widget".to_string(),
+            specifications: "{}".to_string(),
+            warranty_info: "1 year".to_string(),
+            certifications: vec!["CE".to_string()],
+            ipfs_metadata_uri: "ipfs://widget".to_string(),
+            arweave_tx_id: None,
+            localized: None,
+        }
+    }
+
+    mod v1 {
+        use candid::{CandidType, Principal};
+
+        #[derive(CandidType)]
+        pub struct OwnershipRecord {
+            pub owner: Principal,
+            pub timestamp: u64,
+            pub transaction_type: String,
+        }
+
+        #[derive(CandidType)]
+        pub struct ProductNFT {
+            pub nft_id: u64,
+            pub serial_number: String,
+            pub owner: Principal,
+            pub metadata: crate::NFTMetadata,
+            pub minted_at: u64,
+            pub verified: bool,
+            pub ownership_history: Vec<OwnershipRecord>,
+        }
+    }
+
+    mod v2 {
+        use candid::{CandidType, Principal};
+
+        #[derive(CandidType)]
+        pub struct ProductNFT {
+            pub nft_id: u64,
+            pub serial_number: String,
+            pub owner: Principal,
+            pub metadata: crate::NFTMetadata,
+            pub minted_at: u64,
+            pub verified: bool,
+            pub ownership_history: Vec<crate::OwnershipRecord>,
+            pub collection_id: Option<u64>,
+        }
+    }
+
+    fn v1_fixture(verified: bool) -> Vec<u8> {
+        Encode!(&v1::ProductNFT {
+            nft_id: 1,
+            serial_number: "SN1".to_string(),
+            owner: buyer(),
+            metadata: metadata(),
+            minted_at: 100,
+            verified,
+            ownership_history: vec![
+                v1::OwnershipRecord { owner: owner(), timestamp: 100, transaction_type: "mint".to_string() },
+                v1::OwnershipRecord { owner: buyer(), timestamp: 200, transaction_type: "sale".to_string() },
+            ],
+        })
+        .unwrap()
+    }
+
+    fn v2_fixture(verified: bool) -> Vec<u8> {
+        Encode!(&v2::ProductNFT {
+            nft_id: 2,
+            serial_number: "SN1".to_string(),
+            owner: buyer(),
+            metadata: metadata(),
+            minted_at: 100,
+            verified,
+            ownership_history: vec![
+                OwnershipRecord { owner: owner(), timestamp: 100, transaction_type: TransactionType::Mint, memo: None, price: None },
+                OwnershipRecord {
+                    owner: buyer(),
+                    timestamp: 200,
+                    transaction_type: TransactionType::Gift,
+                    memo: Some("birthday".to_string()),
+                    price: None,
+                },
+            ],
+            collection_id: Some(4),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn every_version_has_a_migration() {
+        let versions: Vec<u32> = MIGRATIONS.iter().map(|(version, _)| *version).collect();
+        assert_eq!(versions, (2..=CURRENT_SCHEMA_VERSION).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn decodes_v1_fixture() {
+        let (version, nft) = decode_nft(&v1_fixture(true)).unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(nft.nft_id, 1);
+        assert_eq!(nft.minter(), owner());
+        assert_eq!(nft.ownership_history[0].transaction_type, TransactionType::Mint);
+        assert_eq!(nft.ownership_history[1].transaction_type, TransactionType::Sale);
+        assert_eq!(nft.verification_level, VerificationLevel::SelfAttested);
+        assert!(!nft.revoked);
+        assert!(nft.collection_id.is_none());
+        assert!(nft.lifecycle.is_none());
+    }
+
+    #[test]
+    fn decodes_v2_fixture() {
+        let (version, nft) = decode_nft(&v2_fixture(false)).unwrap();
+        assert_eq!(version, 2);
+        assert_eq!(nft.collection_id, Some(4));
+        assert_eq!(nft.ownership_history[1].memo.as_deref(), Some("birthday"));
+        assert!(nft.revoked);
+        assert!(nft.endorsement.is_none());
+    }
+
+    #[test]
+    fn migrated_tokens_are_written_in_the_current_layout() {
+        for fixture in [v1_fixture(false), v2_fixture(true)] {
+            let (_, nft) = decode_nft(&fixture).unwrap();
+            let (version, reread) = decode_nft(&Encode!(&nft).unwrap()).unwrap();
+            assert_eq!(version, CURRENT_SCHEMA_VERSION);
+            assert_eq!(reread.nft_id, nft.nft_id);
+            assert_eq!(reread.revoked, nft.revoked);
+            assert_eq!(reread.ownership_history.len(), nft.ownership_history.len());
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_layouts() {
+        assert!(decode_nft(&Encode!(&"not a token").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! Verification moderation.
+//!
+//! Revoking a token's verification requires a structured reason, and a
+//! mistaken revocation can be undone with a written justification. Both
+//! actions are appended to a per-token moderation log that anyone can read,
+//! so every change to `revoked` is explained.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::roles::{require_role, Role};
+use crate::{audit, get_nft, memory, save_nft, stats, txlog, webhooks, Memory, ProductNFT};
+
+const MAX_JUSTIFICATION_LEN: usize = 1024;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RevocationReason {
+    Counterfeit,
+    Recalled,
+    Fraud,
+    DataError,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum ModerationAction {
+    Revoked(RevocationReason),
+    Restored,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ModerationEntry {
+    pub sequence: u64,
+    pub action: ModerationAction,
+    pub justification: Option<String>,
+    pub moderator: Principal,
+    pub timestamp: u64,
+}
+
+candid_storable!(ModerationEntry);
+
+thread_local! {
+    static LOG: RefCell<StableBTreeMap<(u64, u64), ModerationEntry, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(30)));
+}
+
+fn history(nft_id: u64) -> Vec<ModerationEntry> {
+    LOG.with(|log| {
+        log.borrow()
+            .range((nft_id, 0)..=(nft_id, u64::MAX))
+            .map(|(_, entry)| entry)
+            .collect()
+    })
+}
+
+fn append(nft: &ProductNFT, action: ModerationAction, justification: Option<String>) {
+    let memo = match &action {
+        ModerationAction::Revoked(reason) => format!("verification revoked: {:?}", reason),
+        ModerationAction::Restored => "verification restored".to_string(),
+    };
+    let sequence = LOG.with(|log| {
+        log.borrow()
+            .range((nft.nft_id, 0)..=(nft.nft_id, u64::MAX))
+            .last()
+            .map_or(0, |((_, sequence), _)| sequence + 1)
+    });
+    let entry = ModerationEntry {
+        sequence,
+        action,
+        justification,
+        moderator: caller(),
+        timestamp: ic_cdk::api::time(),
+    };
+    LOG.with(|log| {
+        log.borrow_mut().insert((nft.nft_id, sequence), entry);
+    });
+    txlog::append(txlog::TxKind::MetadataUpdate, nft.nft_id, &nft.serial_number, None, None, Some(memo));
+}
+
+/// Admin/Verifier: Revoke NFT verification, stating why
+#[update]
+fn revoke_verification(nft_id: u64, reason: RevocationReason) -> Result<ProductNFT, String> {
+    require_role(&[Role::Verifier])?;
+    let mut nft = get_nft(nft_id)?;
+    if nft.revoked {
+        return Err(format!("NFT {} is already revoked", nft_id));
+    }
+
+    nft.revoked = true;
+    save_nft(&nft);
+    stats::record_revocation(&nft);
+    webhooks::notify_revocation(&nft, reason);
+    append(&nft, ModerationAction::Revoked(reason), None);
+
+    audit::record("revoke_verification");
+    Ok(nft)
+}
+
+/// Admin/Verifier: Undo a revocation, with a justification for the record
+#[update]
+fn restore_verification(nft_id: u64, justification: String) -> Result<ProductNFT, String> {
+    require_role(&[Role::Verifier])?;
+    let justification = justification.trim().to_string();
+    if justification.is_empty() || justification.len() > MAX_JUSTIFICATION_LEN {
+        return Err(format!("Justification must be 1-{} bytes", MAX_JUSTIFICATION_LEN));
+    }
+    let mut nft = get_nft(nft_id)?;
+    if !nft.revoked {
+        return Err(format!("NFT {} is not revoked", nft_id));
+    }
+
+    nft.revoked = false;
+    save_nft(&nft);
+    stats::record_restoration(&nft);
+    webhooks::notify_restoration(&nft, &justification);
+    append(&nft, ModerationAction::Restored, Some(justification));
+
+    audit::record("restore_verification");
+    Ok(nft)
+}
+
+/// Revocations and restorations of a token, oldest first
+#[query]
+fn get_moderation_log(nft_id: u64) -> Result<Vec<ModerationEntry>, String> {
+    get_nft(nft_id)?;
+    Ok(history(nft_id))
+}
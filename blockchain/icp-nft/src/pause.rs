@@ -0,0 +1,79 @@
+//! Canister-wide pause switch for incident response.
+//!
+//! While paused, every update method rejects the call through the
+//! `not_paused` guard (and ingress is dropped in `inspect_message` before it
+//! costs cycles); queries keep working so products can still be verified. The
+//! SuperAdmin keeps the methods needed to respond: role management,
+//! verification moderation and `set_paused` itself.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableCell;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::roles::require_admin;
+use crate::{audit, memory, Memory};
+
+/// Update methods that stay callable while paused.
+pub const PAUSE_EXEMPT_METHODS: &[&str] = &[
+    "accept_super_admin",
+    "grant_role",
+    "propose_super_admin",
+    "restore_verification",
+    "revoke_role",
+    "revoke_verification",
+    "set_paused",
+];
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PauseState {
+    pub paused: bool,
+    pub changed_by: Option<Principal>,
+    pub changed_at: Option<u64>,
+}
+
+candid_storable!(PauseState);
+
+thread_local! {
+    static STATE: RefCell<StableCell<PauseState, Memory>> = RefCell::new(
+        StableCell::init(memory(44), PauseState::default())
+            .expect("Failed to initialize pause state")
+    );
+}
+
+pub fn is_paused() -> bool {
+    STATE.with(|s| s.borrow().get().paused)
+}
+
+/// Update guard: reject the call while the canister is paused.
+pub fn not_paused() -> Result<(), String> {
+    if is_paused() {
+        Err("The canister is paused; only queries are available".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// SuperAdmin: pause or resume all update methods except role management and moderation
+#[update]
+fn set_paused(paused: bool) -> Result<PauseState, String> {
+    require_admin()?;
+    let state = PauseState {
+        paused,
+        changed_by: Some(caller()),
+        changed_at: Some(ic_cdk::api::time()),
+    };
+    STATE.with(|s| {
+        s.borrow_mut().set(state.clone()).expect("Failed to persist pause state");
+    });
+    audit::record("set_paused");
+    Ok(state)
+}
+
+/// Whether update methods are paused, and by whom
+#[query]
+fn get_pause_state() -> PauseState {
+    STATE.with(|s| s.borrow().get().clone())
+}
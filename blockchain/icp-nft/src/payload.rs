@@ -0,0 +1,75 @@
+//! Compact, certified verification payloads for QR codes and POS scanners.
+//!
+//! The layout and its decoder live in `proofcart-qr-payload` (`certified`),
+//! shared with the browser verifier in `clients/wasm`.
+//!
+//! A scanner can check the payload offline by validating the certificate
+//! against the IC root key, looking up `nfts/<serial>` in the witness, and
+//! comparing it with `leaf_hash(nft_id, owner_hash, verified)`.
+
+use candid::CandidType;
+use ic_cdk_macros::query;
+use serde::{Deserialize, Serialize};
+
+use crate::certification::{self, owner_hash};
+use crate::{find_by_serial, ProductNFT};
+
+pub use proofcart_qr_payload::certified::{VerificationPayload, PAYLOAD_VERSION};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PayloadVerification {
+    pub serial_number: String,
+    pub nft_id: u64,
+    pub issued_at: u64,
+    /// The payload refers to the token currently registered under its serial.
+    pub authentic: bool,
+    /// The owner recorded in the payload still owns the token.
+    pub owner_unchanged: bool,
+    /// Current verification status of the token.
+    pub verified: bool,
+}
+
+/// Build a signed payload for the NFT currently registered under `serial_number`.
+pub fn build_payload(nft: &ProductNFT) -> Result<VerificationPayload, String> {
+    Ok(VerificationPayload {
+        nft_id: nft.nft_id,
+        issued_at: ic_cdk::api::time(),
+        owner_hash: owner_hash(&nft.owner),
+        verified: !nft.revoked,
+        serial_number: nft.serial_number.clone(),
+        certificate: certification::data_certificate()?,
+        witness: certification::witness(&nft.serial_number),
+    })
+}
+
+/// Generate a compact, canister-certified verification blob for a serial number
+#[query]
+fn generate_verification_payload(serial_number: String) -> Result<Vec<u8>, String> {
+    let nft = find_by_serial(&serial_number)?;
+    build_payload(&nft)?.encode()
+}
+
+/// Check a verification blob against the current registry state
+#[query]
+fn verify_payload(blob: Vec<u8>) -> Result<PayloadVerification, String> {
+    let payload = VerificationPayload::decode(&blob)?;
+
+    let current = find_by_serial(&payload.serial_number).ok();
+    let authentic = current
+        .as_ref()
+        .map(|nft| nft.nft_id == payload.nft_id)
+        .unwrap_or(false);
+
+    Ok(PayloadVerification {
+        serial_number: payload.serial_number,
+        nft_id: payload.nft_id,
+        issued_at: payload.issued_at,
+        authentic,
+        owner_unchanged: authentic
+            && current
+                .as_ref()
+                .map(|nft| owner_hash(&nft.owner) == payload.owner_hash)
+                .unwrap_or(false),
+        verified: authentic && current.map(|nft| !nft.revoked).unwrap_or(false),
+    })
+}
@@ -0,0 +1,78 @@
+//! Sale prices recorded on ownership records.
+//!
+//! A `Sale` transfer may carry the price paid, so a token's history doubles
+//! as provenance-backed resale data. Prices are private to the current owner
+//! and the manufacturer (the minter); everyone else sees the history without
+//! them.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::query;
+use serde::{Deserialize, Serialize};
+
+use crate::sale_lock::MAX_ORDER_ID_LEN;
+use crate::{get_nft, ProductNFT, TransactionType};
+
+/// Longest currency code (`"SOL"`, `"USDC"`, `"KES"`, ...).
+pub const MAX_CURRENCY_LEN: usize = 10;
+
+pub use proofcart_types::SalePrice;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PricePoint {
+    pub buyer: Principal,
+    pub timestamp: u64,
+    pub price: SalePrice,
+}
+
+/// Check a price for a transfer of type `transaction_type`, uppercasing the
+/// currency code.
+pub fn validate(price: SalePrice, transaction_type: TransactionType) -> Result<SalePrice, String> {
+    if transaction_type != TransactionType::Sale {
+        return Err("Only a Sale can record a price".to_string());
+    }
+    let currency = price.currency.trim().to_ascii_uppercase();
+    if currency.is_empty() || currency.len() > MAX_CURRENCY_LEN || !currency.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("Currency must be 1-{} letters or digits", MAX_CURRENCY_LEN));
+    }
+    if let Some(order_id) = &price.order_id {
+        if order_id.is_empty() || order_id.len() > MAX_ORDER_ID_LEN {
+            return Err(format!("Order id must be 1-{} bytes", MAX_ORDER_ID_LEN));
+        }
+    }
+    Ok(SalePrice { currency, ..price })
+}
+
+fn can_see_prices(caller: Principal, nft: &ProductNFT) -> bool {
+    caller == nft.owner || caller == nft.minter()
+}
+
+/// Drop the recorded prices unless `caller` owns or minted the token.
+pub fn redact(caller: Principal, nft: &mut ProductNFT) {
+    if !can_see_prices(caller, nft) {
+        for record in &mut nft.ownership_history {
+            record.price = None;
+        }
+    }
+}
+
+/// Owner or manufacturer: the prices recorded for a token's sales, oldest first
+#[query]
+fn get_price_history(nft_id: u64) -> Result<Vec<PricePoint>, String> {
+    let nft = get_nft(nft_id)?;
+    if !can_see_prices(caller(), &nft) {
+        return Err("Only the owner or the manufacturer can see sale prices".to_string());
+    }
+    Ok(nft
+        .ownership_history
+        .into_iter()
+        .filter_map(|record| {
+            record.price.map(|price| PricePoint {
+                buyer: record.owner,
+                timestamp: record.timestamp,
+                price,
+            })
+        })
+        .collect())
+}
+
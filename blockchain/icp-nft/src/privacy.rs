@@ -0,0 +1,109 @@
+//! Field-level metadata privacy.
+//!
+//! The minter can mark descriptive metadata fields as restricted. Public
+//! reads then return those fields blanked out, while the owner, the minter and
+//! the SuperAdmin keep seeing the full record. Identity fields needed to
+//! verify a product (serial, name, manufacturer, category, manufacture date)
+//! can never be restricted.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use serde::{Deserialize, Serialize};
+
+use crate::pause::not_paused;
+use crate::roles::is_admin;
+use crate::{collections, custody, find_by_serial, get_nft, prices, save_nft, txlog, NFTMetadata, ProductNFT};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MetadataField {
+    Description,
+    Specifications,
+    WarrantyInfo,
+    Certifications,
+    IpfsMetadataUri,
+}
+
+fn can_see_full(caller: Principal, nft: &ProductNFT) -> bool {
+    caller == nft.owner || caller == nft.minter() || is_admin(caller)
+}
+
+fn redact(metadata: &mut NFTMetadata, fields: &[MetadataField]) {
+    for field in fields {
+        match field {
+            MetadataField::Description => {
+                metadata.description.clear();
+                for (_, text) in metadata.localized.iter_mut().flatten() {
+                    text.description = None;
+                }
+            }
+            MetadataField::Specifications => metadata.specifications.clear(),
+            MetadataField::WarrantyInfo => {
+                metadata.warranty_info.clear();
+                for (_, text) in metadata.localized.iter_mut().flatten() {
+                    text.warranty_info = None;
+                }
+            }
+            MetadataField::Certifications => {
+                metadata.certifications.clear();
+                metadata.arweave_tx_id = None;
+            }
+            MetadataField::IpfsMetadataUri => metadata.ipfs_metadata_uri.clear(),
+        }
+    }
+}
+
+/// The token as `caller` may see it: complete for the owner, the minter and
+/// the SuperAdmin, with restricted fields blanked for everyone else. Sale
+/// prices are only shown to the owner and the minter (see `prices`), an
+/// expired custody delegation is left out and a collection freeze is filled in.
+pub fn view(caller: Principal, mut nft: ProductNFT) -> ProductNFT {
+    if custody::active(&nft).is_none() {
+        nft.custody = None;
+    }
+    nft.collection_freeze = collections::freeze_of(&nft);
+    if let Some(fields) = nft.restricted_fields.clone() {
+        if !can_see_full(caller, &nft) {
+            redact(&mut nft.metadata, &fields);
+        }
+    }
+    prices::redact(caller, &mut nft);
+    nft
+}
+
+/// Minter: choose which metadata fields are hidden from public reads
+#[update(guard = "not_paused")]
+fn set_restricted_fields(nft_id: u64, mut fields: Vec<MetadataField>) -> Result<ProductNFT, String> {
+    let caller = caller();
+    let mut nft = get_nft(nft_id)?;
+    if caller != nft.minter() && !is_admin(caller) {
+        return Err("Only the minter can restrict metadata fields".to_string());
+    }
+
+    fields.sort();
+    fields.dedup();
+    nft.restricted_fields = if fields.is_empty() { None } else { Some(fields) };
+    save_nft(&nft);
+    txlog::append(
+        txlog::TxKind::MetadataUpdate,
+        nft.nft_id,
+        &nft.serial_number,
+        None,
+        None,
+        Some("metadata privacy changed".to_string()),
+    );
+
+    Ok(nft)
+}
+
+/// Owner, minter or admin: the full, unredacted record for a serial
+#[query]
+fn get_product_details(serial_number: String) -> Result<ProductNFT, String> {
+    let caller = caller();
+    let mut nft = find_by_serial(&serial_number)?;
+    if !can_see_full(caller, &nft) {
+        return Err("Only the owner, the minter or an admin can see restricted fields".to_string());
+    }
+    prices::redact(caller, &mut nft);
+    Ok(nft)
+}
@@ -0,0 +1,148 @@
+//! Owner profiles and Solana wallet links.
+//!
+//! A principal proves control of a Solana wallet by signing a canister-issued
+//! challenge with it (ed25519, as wallets' `signMessage` does). The challenge
+//! names the principal and this canister, so a signature cannot be replayed
+//! to link the wallet elsewhere. Each wallet links to at most one principal,
+//! which ties the escrow program's buyer pubkey to the NFT owner.
+
+use candid::{CandidType, Principal};
+use ed25519_dalek::{Signature, VerifyingKey};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::pause::not_paused;
+use crate::{memory, Memory};
+
+/// How long an issued challenge can be signed.
+const CHALLENGE_TTL_NANOS: u64 = 5 * 60 * 1_000_000_000;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct Challenge {
+    message: String,
+    expires_at: u64,
+}
+
+candid_storable!(Challenge);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SolanaLink {
+    pub address: String,
+    pub linked_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct OwnerProfile {
+    pub principal: Principal,
+    pub solana: Option<SolanaLink>,
+}
+
+candid_storable!(OwnerProfile);
+
+thread_local! {
+    static CHALLENGES: RefCell<StableBTreeMap<Principal, Challenge, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(19)));
+
+    static PROFILES: RefCell<StableBTreeMap<Principal, OwnerProfile, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(20)));
+
+    // Solana address -> linked principal
+    static SOLANA_LINKS: RefCell<StableBTreeMap<String, Principal, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(21)));
+}
+
+fn get(principal: Principal) -> Option<OwnerProfile> {
+    PROFILES.with(|p| p.borrow().get(&principal))
+}
+
+/// Principal linked to a Solana address, if any.
+pub fn principal_for(address: &str) -> Option<Principal> {
+    SOLANA_LINKS.with(|links| links.borrow().get(&address.to_string()))
+}
+
+fn verifying_key(pubkey: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = bs58::decode(pubkey)
+        .into_vec()
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("Invalid Solana address: {}", pubkey))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| format!("Invalid Solana address: {}", pubkey))
+}
+
+/// Issue a challenge for the caller to sign with its Solana wallet
+#[update(guard = "not_paused")]
+fn solana_link_challenge() -> String {
+    let principal = caller();
+    let now = ic_cdk::api::time();
+    let expires_at = now + CHALLENGE_TTL_NANOS;
+    let message = format!(
+        "ProofCart: link this Solana wallet to ICP principal {} on canister {}. Nonce: {}. Expires: {}.",
+        principal,
+        ic_cdk::id(),
+        now,
+        expires_at
+    );
+    CHALLENGES.with(|c| {
+        c.borrow_mut().insert(principal, Challenge { message: message.clone(), expires_at });
+    });
+    message
+}
+
+/// Link the caller to `pubkey` with a signature over its pending challenge
+#[update(guard = "not_paused")]
+fn link_solana_address(signature: Vec<u8>, pubkey: String) -> Result<OwnerProfile, String> {
+    let principal = caller();
+    let challenge = CHALLENGES
+        .with(|c| c.borrow().get(&principal))
+        .ok_or_else(|| "No pending challenge; call solana_link_challenge first".to_string())?;
+    if ic_cdk::api::time() > challenge.expires_at {
+        return Err("Challenge expired; request a new one".to_string());
+    }
+
+    let key = verifying_key(&pubkey)?;
+    let signature: [u8; 64] = signature
+        .try_into()
+        .map_err(|_| "Signature must be 64 bytes".to_string())?;
+    key.verify_strict(challenge.message.as_bytes(), &Signature::from_bytes(&signature))
+        .map_err(|_| "Signature does not match the challenge".to_string())?;
+
+    if let Some(other) = principal_for(&pubkey).filter(|other| *other != principal) {
+        return Err(format!("Solana address {} is already linked to {}", pubkey, other));
+    }
+
+    CHALLENGES.with(|c| c.borrow_mut().remove(&principal));
+    let mut profile = get(principal).unwrap_or(OwnerProfile { principal, solana: None });
+    if let Some(previous) = profile.solana.take() {
+        SOLANA_LINKS.with(|links| links.borrow_mut().remove(&previous.address));
+    }
+    profile.solana = Some(SolanaLink { address: pubkey.clone(), linked_at: ic_cdk::api::time() });
+    SOLANA_LINKS.with(|links| links.borrow_mut().insert(pubkey, principal));
+    PROFILES.with(|p| p.borrow_mut().insert(principal, profile.clone()));
+    Ok(profile)
+}
+
+/// Remove the caller's Solana link
+#[update(guard = "not_paused")]
+fn unlink_solana_address() -> Result<OwnerProfile, String> {
+    let principal = caller();
+    let mut profile = get(principal).ok_or_else(|| "No linked Solana address".to_string())?;
+    let link = profile.solana.take().ok_or_else(|| "No linked Solana address".to_string())?;
+    SOLANA_LINKS.with(|links| links.borrow_mut().remove(&link.address));
+    PROFILES.with(|p| p.borrow_mut().insert(principal, profile.clone()));
+    Ok(profile)
+}
+
+/// Get an owner's profile
+#[query]
+fn get_profile(principal: Principal) -> Option<OwnerProfile> {
+    get(principal)
+}
+
+/// Principal linked to a Solana address
+#[query]
+fn principal_for_solana_address(address: String) -> Option<Principal> {
+    principal_for(&address)
+}
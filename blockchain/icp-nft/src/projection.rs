@@ -0,0 +1,97 @@
+//! Lightweight reads for point-of-sale integrations.
+//!
+//! A checkout scanning every item only needs to know whether a serial is
+//! registered, or a handful of its fields, not the whole `ProductNFT` with
+//! its ownership and lifecycle history. Fields are addressed by stable key
+//! names and returned as ICRC-3 values, after the same privacy redaction as
+//! `get_nft`.
+
+use candid::Nat;
+use ic_cdk::caller;
+use ic_cdk_macros::query;
+
+use crate::icrc37::Value;
+use crate::{find_by_serial, limits, privacy, serials, ProductNFT, SERIAL_TO_NFT};
+
+/// Keys accepted by `get_metadata_fields`.
+pub const FIELDS: &[&str] = &[
+    "serial_number",
+    "product_name",
+    "manufacturer",
+    "manufacture_date",
+    "category",
+    "description",
+    "specifications",
+    "warranty_info",
+    "certifications",
+    "ipfs_metadata_uri",
+    "arweave_tx_id",
+    "owner",
+    "minted_at",
+    "verification_level",
+    "revoked",
+];
+
+/// The value of one of `FIELDS` on a token. `revoked` is `1` or `0`, and a
+/// missing `arweave_tx_id` is empty.
+pub fn field(nft: &ProductNFT, name: &str) -> Option<Value> {
+    let metadata = &nft.metadata;
+    let text = |s: &String| Some(Value::Text(s.clone()));
+    match name {
+        "serial_number" => text(&nft.serial_number),
+        "product_name" => text(&metadata.product_name),
+        "manufacturer" => text(&metadata.manufacturer),
+        "manufacture_date" => text(&metadata.manufacture_date),
+        "category" => text(&metadata.category),
+        "description" => text(&metadata.description),
+        "specifications" => text(&metadata.specifications),
+        "warranty_info" => text(&metadata.warranty_info),
+        "certifications" => Some(Value::Array(metadata.certifications.iter().cloned().map(Value::Text).collect())),
+        "ipfs_metadata_uri" => text(&metadata.ipfs_metadata_uri),
+        "arweave_tx_id" => Some(Value::Text(metadata.arweave_tx_id.clone().unwrap_or_default())),
+        "owner" => Some(Value::Text(nft.owner.to_text())),
+        "minted_at" => Some(Value::Nat(Nat::from(nft.minted_at))),
+        "verification_level" => Some(Value::Text(format!("{:?}", nft.verification_level))),
+        "revoked" => Some(Value::Nat(Nat::from(u8::from(nft.revoked)))),
+        _ => None,
+    }
+}
+
+/// Every field of `FIELDS` under its `proofcart:` key, the ICRC-7 token
+/// metadata shape.
+pub fn metadata_map(nft: &ProductNFT) -> Vec<(String, Value)> {
+    FIELDS
+        .iter()
+        .filter_map(|name| field(nft, name).map(|value| (format!("proofcart:{}", name), value)))
+        .collect()
+}
+
+/// Whether each serial is registered, in request order
+#[query]
+fn batch_exists(serial_numbers: Vec<String>) -> Result<Vec<bool>, String> {
+    limits::check_batch("batch_exists", serial_numbers.len(), limits::current().max_batch_verify)?;
+    Ok(SERIAL_TO_NFT.with(|map| {
+        let map = map.borrow();
+        serial_numbers
+            .iter()
+            .map(|serial| map.contains_key(&serials::normalize(serial)))
+            .collect()
+    }))
+}
+
+/// Selected fields of a product, by key name, in request order
+#[query]
+fn get_metadata_fields(serial_number: String, fields: Vec<String>) -> Result<Vec<(String, Value)>, String> {
+    if fields.is_empty() {
+        return Err("At least one field is required".to_string());
+    }
+    limits::check_batch("get_metadata_fields", fields.len(), FIELDS.len() as u32)?;
+    let nft = privacy::view(caller(), find_by_serial(&serial_number)?);
+    fields
+        .into_iter()
+        .map(|name| match field(&nft, &name) {
+            Some(value) => Ok((name, value)),
+            None => Err(format!("Unknown field {}; expected one of {}", name, FIELDS.join(", "))),
+        })
+        .collect()
+}
@@ -0,0 +1,168 @@
+//! Ownership proofs.
+//!
+//! A holder proves "I currently own this token" to a third party without
+//! transferring it: it requests a random nonce for the token, then calls
+//! `prove_ownership` with it. Both are update calls, so the caller principal
+//! is authenticated by the IC. The canister returns an attestation naming the
+//! token, the owner and the nonce, valid for a short window and signed with an
+//! HMAC under a canister-held secret. The third party checks it with
+//! `verify_ownership_attestation`, which also reports whether the owner still
+//! holds the token.
+
+use candid::{CandidType, Encode, Principal};
+use hmac::{Hmac, Mac};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::{StableBTreeMap, StableCell};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::cell::RefCell;
+
+use crate::http::hex;
+use crate::pause::not_paused;
+use crate::{get_nft, memory, Memory};
+
+/// How long a nonce can be used to prove ownership.
+const CHALLENGE_TTL_NANOS: u64 = 5 * 60 * 1_000_000_000;
+/// How long an attestation stays valid after it is issued.
+const ATTESTATION_TTL_NANOS: u64 = 10 * 60 * 1_000_000_000;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct OwnershipChallenge {
+    nft_id: u64,
+    nonce: String,
+    expires_at: u64,
+}
+
+candid_storable!(OwnershipChallenge);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OwnershipAttestation {
+    pub canister_id: Principal,
+    pub nft_id: u64,
+    pub serial_number: String,
+    pub owner: Principal,
+    pub nonce: String,
+    pub issued_at: u64,
+    pub expires_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SignedOwnershipAttestation {
+    pub attestation: OwnershipAttestation,
+    /// Hex HMAC-SHA256 of the candid-encoded attestation.
+    pub signature: String,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct AttestationCheck {
+    /// Issued by this canister and unmodified.
+    pub authentic: bool,
+    pub expired: bool,
+    /// The attested owner still holds the token.
+    pub still_owner: bool,
+}
+
+thread_local! {
+    static SECRET: RefCell<StableCell<Vec<u8>, Memory>> = RefCell::new(
+        StableCell::init(memory(36), Vec::new())
+            .expect("Failed to initialize attestation secret")
+    );
+
+    // Owner -> its pending challenge
+    static CHALLENGES: RefCell<StableBTreeMap<Principal, OwnershipChallenge, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(37)));
+}
+
+async fn random_bytes() -> Result<Vec<u8>, String> {
+    let (bytes,) = ic_cdk::api::management_canister::main::raw_rand()
+        .await
+        .map_err(|(code, msg)| format!("raw_rand failed: {:?} {}", code, msg))?;
+    Ok(bytes)
+}
+
+fn secret() -> Vec<u8> {
+    SECRET.with(|s| s.borrow().get().clone())
+}
+
+fn sign(secret: &[u8], attestation: &OwnershipAttestation) -> String {
+    let body = Encode!(attestation).expect("Failed to encode attestation");
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&body);
+    hex(&mac.finalize().into_bytes())
+}
+
+fn require_owner(nft_id: u64, principal: Principal) -> Result<crate::ProductNFT, String> {
+    let nft = get_nft(nft_id)?;
+    if nft.owner != principal {
+        return Err("Only the current owner can prove ownership".to_string());
+    }
+    Ok(nft)
+}
+
+/// Owner: get a nonce to prove ownership of a token with
+#[update(guard = "not_paused")]
+async fn request_ownership_challenge(nft_id: u64) -> Result<String, String> {
+    let principal = caller();
+    require_owner(nft_id, principal)?;
+
+    if secret().is_empty() {
+        let secret = random_bytes().await?;
+        SECRET.with(|s| {
+            // Another call may have set it while this one awaited.
+            if s.borrow().get().is_empty() {
+                s.borrow_mut().set(secret).expect("Failed to persist attestation secret");
+            }
+        });
+    }
+    let nonce = hex(&random_bytes().await?);
+    let challenge = OwnershipChallenge {
+        nft_id,
+        nonce: nonce.clone(),
+        expires_at: ic_cdk::api::time() + CHALLENGE_TTL_NANOS,
+    };
+    CHALLENGES.with(|c| c.borrow_mut().insert(principal, challenge));
+    Ok(nonce)
+}
+
+/// Owner: exchange a pending nonce for a signed, short-lived ownership attestation
+#[update(guard = "not_paused")]
+fn prove_ownership(nft_id: u64, nonce: String) -> Result<SignedOwnershipAttestation, String> {
+    let principal = caller();
+    let challenge = CHALLENGES
+        .with(|c| c.borrow().get(&principal))
+        .filter(|challenge| challenge.nft_id == nft_id && challenge.nonce == nonce)
+        .ok_or_else(|| "No matching challenge; call request_ownership_challenge first".to_string())?;
+    let now = ic_cdk::api::time();
+    if now > challenge.expires_at {
+        return Err("Challenge expired; request a new one".to_string());
+    }
+    let nft = require_owner(nft_id, principal)?;
+
+    CHALLENGES.with(|c| c.borrow_mut().remove(&principal));
+    let attestation = OwnershipAttestation {
+        canister_id: ic_cdk::id(),
+        nft_id,
+        serial_number: nft.serial_number,
+        owner: principal,
+        nonce,
+        issued_at: now,
+        expires_at: now + ATTESTATION_TTL_NANOS,
+    };
+    Ok(SignedOwnershipAttestation { signature: sign(&secret(), &attestation), attestation })
+}
+
+/// Check an ownership attestation presented by a reseller
+#[query]
+fn verify_ownership_attestation(signed: SignedOwnershipAttestation) -> AttestationCheck {
+    let secret = secret();
+    let attestation = &signed.attestation;
+    let authentic = !secret.is_empty()
+        && attestation.canister_id == ic_cdk::id()
+        && sign(&secret, attestation) == signed.signature;
+    AttestationCheck {
+        authentic,
+        expired: ic_cdk::api::time() > attestation.expires_at,
+        still_owner: authentic && get_nft(attestation.nft_id).is_ok_and(|nft| nft.owner == attestation.owner),
+    }
+}
@@ -0,0 +1,224 @@
+//! Per-principal sliding-window rate limiting.
+//!
+//! Each (principal, method) pair keeps the call count of the current and the
+//! previous fixed window; the sliding estimate weights the previous window by
+//! how much of it still overlaps. Counters are in stable memory, whose writes
+//! a query discards, so limits are only checked on update calls. The
+//! `BatchVerify` limit applies to `retailer_verify_batch`; the public
+//! `batch_verify_nfts` query is bounded per call by `max_batch_verify` only.
+//!
+//! Active retailers get their `BatchVerify` limit multiplied by their quota.
+
+use candid::{CandidType, Principal};
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::{StableBTreeMap, StableCell};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::pause::not_paused;
+use crate::roles::{self, require_admin};
+use crate::{audit, events, memory, retailers, Memory};
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+/// Longest configurable window (30 days), far below where the window in
+/// nanoseconds would overflow.
+const MAX_WINDOW_SECS: u64 = 30 * 24 * 3600;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateLimitedMethod {
+    Mint,
+    Transfer,
+    BatchVerify,
+}
+
+impl RateLimitedMethod {
+    fn tag(self) -> u8 {
+        match self {
+            RateLimitedMethod::Mint => 0,
+            RateLimitedMethod::Transfer => 1,
+            RateLimitedMethod::BatchVerify => 2,
+        }
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct RateLimit {
+    pub max_calls: u32,
+    pub window_secs: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RateLimitConfig {
+    pub mint: Option<RateLimit>,
+    pub transfer: Option<RateLimit>,
+    pub batch_verify: Option<RateLimit>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            mint: Some(RateLimit { max_calls: 600, window_secs: 60 }),
+            transfer: Some(RateLimit { max_calls: 60, window_secs: 60 }),
+            batch_verify: Some(RateLimit { max_calls: 120, window_secs: 60 }),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    fn limit_for(&self, method: RateLimitedMethod) -> Option<RateLimit> {
+        match method {
+            RateLimitedMethod::Mint => self.mint,
+            RateLimitedMethod::Transfer => self.transfer,
+            RateLimitedMethod::BatchVerify => self.batch_verify,
+        }
+    }
+
+    fn set(&mut self, method: RateLimitedMethod, limit: Option<RateLimit>) {
+        match method {
+            RateLimitedMethod::Mint => self.mint = limit,
+            RateLimitedMethod::Transfer => self.transfer = limit,
+            RateLimitedMethod::BatchVerify => self.batch_verify = limit,
+        }
+    }
+}
+
+candid_storable!(RateLimitConfig);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+struct WindowCounter {
+    window_start: u64,
+    count: u32,
+    previous_count: u32,
+}
+
+candid_storable!(WindowCounter);
+
+thread_local! {
+    static COUNTERS: RefCell<StableBTreeMap<Vec<u8>, WindowCounter, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(11)));
+
+    static CONFIG: RefCell<StableCell<RateLimitConfig, Memory>> = RefCell::new(
+        StableCell::init(memory(12), RateLimitConfig::default())
+            .expect("Failed to initialize rate limit config")
+    );
+}
+
+fn counter_key(principal: Principal, method: RateLimitedMethod) -> Vec<u8> {
+    let mut key = vec![method.tag()];
+    key.extend_from_slice(principal.as_slice());
+    key
+}
+
+/// Roll the counter forward to the window containing `now`.
+fn roll(counter: &mut WindowCounter, now: u64, window: u64) {
+    let elapsed = now.saturating_sub(counter.window_start);
+    if elapsed >= 2 * window {
+        *counter = WindowCounter { window_start: now - now % window, count: 0, previous_count: 0 };
+    } else if elapsed >= window {
+        counter.previous_count = counter.count;
+        counter.count = 0;
+        counter.window_start += window;
+    }
+}
+
+fn estimate(counter: &WindowCounter, now: u64, window: u64) -> u64 {
+    let into_window = now.saturating_sub(counter.window_start).min(window);
+    let previous_weight = window - into_window;
+    counter.count as u64 + counter.previous_count as u64 * previous_weight / window
+}
+
+/// Count a call by `caller` to `method`, failing once its budget is exhausted.
+/// SuperAdmins are exempt.
+pub fn check(caller: Principal, method: RateLimitedMethod) -> Result<(), String> {
+    let limit = match CONFIG.with(|c| c.borrow().get().limit_for(method)) {
+        Some(limit) if limit.window_secs > 0 => limit,
+        _ => return Ok(()),
+    };
+    if roles::is_admin(caller) {
+        return Ok(());
+    }
+    let limit = if method == RateLimitedMethod::BatchVerify {
+        RateLimit {
+            max_calls: limit.max_calls.saturating_mul(retailers::quota_multiplier(caller)),
+            ..limit
+        }
+    } else {
+        limit
+    };
+
+    let now = ic_cdk::api::time();
+    let window = limit.window_secs * NANOS_PER_SEC;
+    let key = counter_key(caller, method);
+
+    COUNTERS.with(|counters| {
+        let mut counters = counters.borrow_mut();
+        let mut counter = counters.get(&key).unwrap_or(WindowCounter {
+            window_start: now - now % window,
+            count: 0,
+            previous_count: 0,
+        });
+        roll(&mut counter, now, window);
+
+        if estimate(&counter, now, window) >= limit.max_calls as u64 {
+            let error = format!(
+                "Rate limit exceeded for {:?}: {} calls per {}s",
+                method, limit.max_calls, limit.window_secs
+            );
+            events::emit(events::EventKind::RateLimited, None, error.clone());
+            return Err(error);
+        }
+
+        counter.count = counter.count.saturating_add(1);
+        counters.insert(key, counter);
+        Ok(())
+    })
+}
+
+fn validate(limit: &RateLimit) -> Result<(), String> {
+    if limit.window_secs == 0 || limit.window_secs > MAX_WINDOW_SECS {
+        return Err(format!("window_secs must be 1 to {}", MAX_WINDOW_SECS));
+    }
+    if limit.max_calls == 0 {
+        return Err("max_calls must be at least 1; pass no limit to disable it".to_string());
+    }
+    Ok(())
+}
+
+/// Admin: set or disable (`None`) the limit for a method
+#[update(guard = "not_paused")]
+fn set_rate_limit(method: RateLimitedMethod, limit: Option<RateLimit>) -> Result<(), String> {
+    require_admin()?;
+    if let Some(limit) = &limit {
+        validate(limit)?;
+    }
+    CONFIG.with(|c| {
+        let mut config = c.borrow().get().clone();
+        config.set(method, limit);
+        c.borrow_mut()
+            .set(config)
+            .map_err(|e| format!("Failed to persist rate limits: {:?}", e))
+    })?;
+    audit::record("set_rate_limit");
+    Ok(())
+}
+
+/// Current rate limits
+#[query]
+fn get_rate_limits() -> RateLimitConfig {
+    CONFIG.with(|c| c.borrow().get().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_are_validated() {
+        assert!(validate(&RateLimit { max_calls: 1, window_secs: 1 }).is_ok());
+        assert!(validate(&RateLimit { max_calls: 1, window_secs: MAX_WINDOW_SECS }).is_ok());
+        assert!(validate(&RateLimit { max_calls: 1, window_secs: 0 }).is_err());
+        assert!(validate(&RateLimit { max_calls: 1, window_secs: MAX_WINDOW_SECS + 1 }).is_err());
+        assert!(validate(&RateLimit { max_calls: 0, window_secs: 60 }).is_err());
+        assert!(MAX_WINDOW_SECS.checked_mul(NANOS_PER_SEC).is_some());
+    }
+}
@@ -0,0 +1,112 @@
+//! Product safety recalls.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use serde::{Deserialize, Serialize};
+
+use crate::limits;
+use crate::pause::not_paused;
+use crate::roles::{self, Role};
+use crate::search::{self, NFTFilter};
+use crate::{audit, find_by_serial, privacy, save_nft, txlog, webhooks, ProductNFT, NFTS};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RecallInfo {
+    pub notice_uri: String,
+    pub recalled_at: u64,
+    pub recalled_by: Principal,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum RecallTarget {
+    Serials(Vec<String>),
+    Filter(NFTFilter),
+}
+
+fn authorize(nft: &ProductNFT, caller: Principal) -> Result<(), String> {
+    if roles::has_any_role(caller, &[Role::Verifier]) || nft.minter() == caller {
+        Ok(())
+    } else {
+        Err(format!(
+            "Only an admin, a verifier or the manufacturer can recall {}",
+            nft.serial_number
+        ))
+    }
+}
+
+/// Admin/Verifier/manufacturer: flag products as recalled, returning the affected serials
+#[update(guard = "not_paused")]
+fn recall_products(target: RecallTarget, recall_notice_uri: String) -> Result<Vec<String>, String> {
+    let caller = caller();
+    if recall_notice_uri.trim().is_empty() {
+        return Err("A recall notice URI is required".to_string());
+    }
+    limits::check_len("recall_notice_uri", &recall_notice_uri, limits::MAX_URI_BYTES)?;
+
+    let nfts: Vec<ProductNFT> = match target {
+        RecallTarget::Serials(serials) => serials
+            .iter()
+            .map(|serial| find_by_serial(serial))
+            .collect::<Result<_, _>>()?,
+        RecallTarget::Filter(filter) => {
+            let ids = search::matching_ids(&filter);
+            NFTS.with(|nfts| {
+                let nfts = nfts.borrow();
+                ids.into_iter().filter_map(|id| nfts.get(&id)).collect()
+            })
+        }
+    };
+
+    limits::check_batch("recall_products", nfts.len(), limits::current().max_recall_batch)?;
+
+    // Validate everything before touching state so a recall is all-or-nothing.
+    for nft in &nfts {
+        authorize(nft, caller)?;
+    }
+
+    let now = ic_cdk::api::time();
+    let mut recalled = Vec::with_capacity(nfts.len());
+    for mut nft in nfts {
+        nft.recall = Some(RecallInfo {
+            notice_uri: recall_notice_uri.clone(),
+            recalled_at: now,
+            recalled_by: caller,
+        });
+        save_nft(&nft);
+        txlog::append(
+            txlog::TxKind::MetadataUpdate,
+            nft.nft_id,
+            &nft.serial_number,
+            None,
+            None,
+            Some(format!("recalled: {}", recall_notice_uri)),
+        );
+        webhooks::notify_recall(&nft, &recall_notice_uri);
+        recalled.push(nft.serial_number);
+    }
+
+    audit::record("recall_products");
+    Ok(recalled)
+}
+
+/// Check whether a product has been recalled
+#[query]
+fn is_recalled(serial_number: String) -> Result<bool, String> {
+    find_by_serial(&serial_number).map(|nft| nft.recall.is_some())
+}
+
+/// List recalled products of a manufacturer
+#[query]
+fn list_recalled(manufacturer: String) -> Vec<ProductNFT> {
+    let caller = caller();
+    let ids = search::ids_for_manufacturer(&manufacturer);
+    NFTS.with(|nfts| {
+        let nfts = nfts.borrow();
+        ids.into_iter()
+            .filter_map(|id| nfts.get(&id))
+            .filter(|nft| nft.recall.is_some())
+            .map(|nft| privacy::view(caller, nft))
+            .collect()
+    })
+}
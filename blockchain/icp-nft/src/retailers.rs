@@ -0,0 +1,141 @@
+//! Retail partners verifying directly against the canister.
+//!
+//! A registered retailer principal gets a multiplied `BatchVerify` rate limit
+//! and access to `retailer_verify_batch`, which takes larger batches than the
+//! public `batch_verify_nfts`. It is an update call so that the per-retailer
+//! usage counters are actually persisted.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::limits;
+use crate::pause::not_paused;
+use crate::rate_limit::{self, RateLimitedMethod};
+use crate::roles::require_admin;
+use crate::{audit, memory, verify_serials, Memory, ProductNFT};
+
+const MAX_QUOTA_MULTIPLIER: u32 = 100;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Retailer {
+    pub principal: Principal,
+    pub name: String,
+    pub registered_at: u64,
+    pub active: bool,
+    /// Factor applied to the `BatchVerify` rate limit for this retailer.
+    pub quota_multiplier: u32,
+    pub bulk_calls: u64,
+    pub serials_verified: u64,
+    pub last_used_at: Option<u64>,
+}
+
+candid_storable!(Retailer);
+
+thread_local! {
+    static RETAILERS: RefCell<StableBTreeMap<Principal, Retailer, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(17)));
+}
+
+fn get(principal: Principal) -> Option<Retailer> {
+    RETAILERS.with(|r| r.borrow().get(&principal))
+}
+
+fn save(retailer: Retailer) {
+    RETAILERS.with(|r| {
+        r.borrow_mut().insert(retailer.principal, retailer);
+    });
+}
+
+/// Rate-limit multiplier for `caller`: the retailer's quota if it is an
+/// active retailer, 1 otherwise.
+pub fn quota_multiplier(caller: Principal) -> u32 {
+    match get(caller) {
+        Some(retailer) if retailer.active => retailer.quota_multiplier,
+        _ => 1,
+    }
+}
+
+/// Admin: register a retailer or update its name and quota
+#[update(guard = "not_paused")]
+fn register_retailer(principal: Principal, name: String, quota_multiplier: u32) -> Result<Retailer, String> {
+    require_admin()?;
+    if name.trim().is_empty() {
+        return Err("Retailer name is required".to_string());
+    }
+    if quota_multiplier == 0 || quota_multiplier > MAX_QUOTA_MULTIPLIER {
+        return Err(format!("Quota multiplier must be 1-{}", MAX_QUOTA_MULTIPLIER));
+    }
+    let retailer = match get(principal) {
+        Some(existing) => Retailer { name, quota_multiplier, active: true, ..existing },
+        None => Retailer {
+            principal,
+            name,
+            registered_at: ic_cdk::api::time(),
+            active: true,
+            quota_multiplier,
+            bulk_calls: 0,
+            serials_verified: 0,
+            last_used_at: None,
+        },
+    };
+    save(retailer.clone());
+    audit::record("register_retailer");
+    Ok(retailer)
+}
+
+/// Admin: suspend or reactivate a retailer (usage history is kept)
+#[update(guard = "not_paused")]
+fn set_retailer_active(principal: Principal, active: bool) -> Result<Retailer, String> {
+    require_admin()?;
+    let mut retailer = get(principal).ok_or_else(|| format!("Retailer {} not found", principal))?;
+    retailer.active = active;
+    save(retailer.clone());
+    audit::record("set_retailer_active");
+    Ok(retailer)
+}
+
+/// Retailer: verify up to 1000 serials, counted against the caller's usage
+#[update(guard = "not_paused")]
+fn retailer_verify_batch(serial_numbers: Vec<String>) -> Result<Vec<(String, Option<ProductNFT>)>, String> {
+    let caller = caller();
+    let mut retailer = match get(caller) {
+        Some(retailer) if retailer.active => retailer,
+        _ => return Err("Caller is not an active retailer".to_string()),
+    };
+    limits::check_batch("retailer_verify_batch", serial_numbers.len(), limits::current().max_retailer_batch)?;
+    rate_limit::check(caller, RateLimitedMethod::BatchVerify)?;
+
+    retailer.bulk_calls += 1;
+    retailer.serials_verified += serial_numbers.len() as u64;
+    retailer.last_used_at = Some(ic_cdk::api::time());
+    save(retailer);
+
+    Ok(verify_serials(serial_numbers))
+}
+
+/// Get a retailer (the retailer itself or an admin)
+#[query]
+fn get_retailer(principal: Principal) -> Result<Retailer, String> {
+    if caller() != principal {
+        require_admin()?;
+    }
+    get(principal).ok_or_else(|| format!("Retailer {} not found", principal))
+}
+
+/// Admin: list retailers with their usage counters
+#[query]
+fn list_retailers(offset: u64, limit: u64) -> Result<Vec<Retailer>, String> {
+    require_admin()?;
+    Ok(RETAILERS.with(|r| {
+        r.borrow()
+            .iter()
+            .skip(offset as usize)
+            .take(limit.min(100) as usize)
+            .map(|(_, retailer)| retailer)
+            .collect()
+    }))
+}
@@ -0,0 +1,216 @@
+//! Role-based access control.
+//!
+//! Roles live in stable memory so they survive upgrades. There is exactly one
+//! `SuperAdmin`; it is handed over in two steps (propose, then accept by the
+//! new principal) so a typo cannot lock everyone out.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::{StableBTreeMap, StableCell};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::{audit, memory, Memory};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    SuperAdmin,
+    Verifier,
+    Support,
+    Marketplace,
+    /// Records distribution and sale in the product lifecycle.
+    Distributor,
+    /// Records units entering and leaving service.
+    ServiceCenter,
+    /// Records recycling and destruction.
+    Recycler,
+    /// Endorses tokens as third-party audited.
+    Auditor,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RoleSet(pub Vec<Role>);
+
+candid_storable!(RoleSet);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Handover {
+    pub pending_super_admin: Option<Principal>,
+    pub proposed_at: u64,
+}
+
+candid_storable!(Handover);
+
+thread_local! {
+    static ROLES: RefCell<StableBTreeMap<Principal, RoleSet, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(7)));
+
+    static HANDOVER: RefCell<StableCell<Handover, Memory>> = RefCell::new(
+        StableCell::init(memory(8), Handover::default())
+            .expect("Failed to initialize super-admin handover state")
+    );
+}
+
+pub fn roles_of(principal: Principal) -> Vec<Role> {
+    ROLES.with(|roles| roles.borrow().get(&principal).map(|set| set.0).unwrap_or_default())
+}
+
+pub fn has_role(principal: Principal, role: Role) -> bool {
+    roles_of(principal).contains(&role)
+}
+
+/// SuperAdmin or any of `roles`.
+pub fn has_any_role(principal: Principal, roles: &[Role]) -> bool {
+    roles_of(principal)
+        .iter()
+        .any(|role| *role == Role::SuperAdmin || roles.contains(role))
+}
+
+pub fn is_admin(principal: Principal) -> bool {
+    has_role(principal, Role::SuperAdmin)
+}
+
+/// Fail unless the caller is the SuperAdmin
+pub fn require_admin() -> Result<(), String> {
+    if is_admin(caller()) {
+        Ok(())
+    } else {
+        Err("Only admin can perform this action".to_string())
+    }
+}
+
+/// Fail unless the caller is the SuperAdmin or holds one of `roles`
+pub fn require_role(roles: &[Role]) -> Result<(), String> {
+    if has_any_role(caller(), roles) {
+        Ok(())
+    } else {
+        Err(format!("Caller lacks one of the required roles: {:?}", roles))
+    }
+}
+
+fn add_role(principal: Principal, role: Role) {
+    ROLES.with(|roles| {
+        let mut roles = roles.borrow_mut();
+        let mut set = roles.get(&principal).unwrap_or_default();
+        if !set.0.contains(&role) {
+            set.0.push(role);
+        }
+        roles.insert(principal, set);
+    });
+}
+
+fn remove_role(principal: Principal, role: Role) {
+    ROLES.with(|roles| {
+        let mut roles = roles.borrow_mut();
+        if let Some(mut set) = roles.get(&principal) {
+            set.0.retain(|r| *r != role);
+            if set.0.is_empty() {
+                roles.remove(&principal);
+            } else {
+                roles.insert(principal, set);
+            }
+        }
+    });
+}
+
+fn super_admin() -> Option<Principal> {
+    ROLES.with(|roles| {
+        roles.borrow()
+            .iter()
+            .find(|(_, set)| set.0.contains(&Role::SuperAdmin))
+            .map(|(principal, _)| principal)
+    })
+}
+
+/// Make `principal` SuperAdmin if nobody holds the role yet (install or recovery).
+pub fn bootstrap(principal: Principal) {
+    if super_admin().is_none() {
+        add_role(principal, Role::SuperAdmin);
+    }
+}
+
+/// SuperAdmin: grant a role (SuperAdmin itself is only transferable via handover)
+#[update]
+fn grant_role(principal: Principal, role: Role) -> Result<(), String> {
+    require_admin()?;
+    if role == Role::SuperAdmin {
+        return Err("SuperAdmin can only be transferred with propose_super_admin".to_string());
+    }
+    add_role(principal, role);
+    audit::record("grant_role");
+    Ok(())
+}
+
+/// SuperAdmin: revoke a role
+#[update]
+fn revoke_role(principal: Principal, role: Role) -> Result<(), String> {
+    require_admin()?;
+    if role == Role::SuperAdmin {
+        return Err("SuperAdmin can only be transferred with propose_super_admin".to_string());
+    }
+    remove_role(principal, role);
+    audit::record("revoke_role");
+    Ok(())
+}
+
+/// SuperAdmin: propose a new SuperAdmin (step 1 of 2); `None` cancels
+#[update]
+fn propose_super_admin(candidate: Option<Principal>) -> Result<(), String> {
+    require_admin()?;
+    if candidate == Some(Principal::anonymous()) {
+        return Err("The anonymous principal cannot be SuperAdmin".to_string());
+    }
+    HANDOVER.with(|h| {
+        h.borrow_mut()
+            .set(Handover {
+                pending_super_admin: candidate,
+                proposed_at: ic_cdk::api::time(),
+            })
+            .map_err(|e| format!("Failed to persist handover: {:?}", e))
+    })?;
+    audit::record("propose_super_admin");
+    Ok(())
+}
+
+/// Accept a pending SuperAdmin handover (step 2 of 2, called by the candidate)
+#[update]
+fn accept_super_admin() -> Result<(), String> {
+    let caller = caller();
+    let pending = HANDOVER.with(|h| h.borrow().get().pending_super_admin);
+    if pending != Some(caller) {
+        return Err("No pending SuperAdmin handover for caller".to_string());
+    }
+
+    if let Some(previous) = super_admin() {
+        remove_role(previous, Role::SuperAdmin);
+    }
+    add_role(caller, Role::SuperAdmin);
+
+    HANDOVER.with(|h| {
+        h.borrow_mut()
+            .set(Handover::default())
+            .map_err(|e| format!("Failed to persist handover: {:?}", e))
+    })?;
+    audit::record("accept_super_admin");
+    Ok(())
+}
+
+/// Roles held by a principal
+#[query]
+fn get_roles(principal: Principal) -> Vec<Role> {
+    roles_of(principal)
+}
+
+/// All principals holding at least one role, plus any pending SuperAdmin
+#[query]
+fn list_role_holders() -> (Vec<(Principal, Vec<Role>)>, Option<Principal>) {
+    let holders = ROLES.with(|roles| {
+        roles.borrow()
+            .iter()
+            .map(|(principal, set)| (principal, set.0))
+            .collect()
+    });
+    let pending = HANDOVER.with(|h| h.borrow().get().pending_super_admin);
+    (holders, pending)
+}
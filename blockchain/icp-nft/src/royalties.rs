@@ -0,0 +1,86 @@
+//! Manufacturer royalties on secondary sales.
+//!
+//! A royalty can be set on an individual NFT at mint time or on its
+//! collection; the NFT-level setting wins. Recipients may be ICP principals or
+//! Solana addresses, since resales settle through the Solana escrow.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use serde::{Deserialize, Serialize};
+
+use crate::pause::not_paused;
+use crate::{collections, get_nft};
+
+/// Basis points denominator (100% = 10_000 bps).
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum RoyaltyRecipient {
+    Principal(Principal),
+    Solana(String),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RoyaltyInfo {
+    pub recipient: RoyaltyRecipient,
+    pub bps: u16,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RoyaltyPayment {
+    pub recipient: RoyaltyRecipient,
+    pub bps: u16,
+    pub amount: u64,
+}
+
+pub fn validate(royalty: &RoyaltyInfo) -> Result<(), String> {
+    if royalty.bps as u64 > BPS_DENOMINATOR {
+        return Err(format!("Royalty of {} bps exceeds 100%", royalty.bps));
+    }
+    if let RoyaltyRecipient::Solana(address) = &royalty.recipient {
+        // Base58 public keys are 32 bytes, i.e. 32-44 characters.
+        let valid_chars = address.chars().all(|c| c.is_ascii_alphanumeric() && !"0OIl".contains(c));
+        if !(32..=44).contains(&address.len()) || !valid_chars {
+            return Err(format!("Invalid Solana address: {}", address));
+        }
+    }
+    Ok(())
+}
+
+/// Royalty owed on `sale_amount`, rounded down.
+pub fn compute(royalty: &RoyaltyInfo, sale_amount: u64) -> RoyaltyPayment {
+    let amount = (sale_amount as u128 * royalty.bps as u128 / BPS_DENOMINATOR as u128) as u64;
+    RoyaltyPayment {
+        recipient: royalty.recipient.clone(),
+        bps: royalty.bps,
+        amount,
+    }
+}
+
+/// Set or clear the default royalty of a collection (collection owner only)
+#[update(guard = "not_paused")]
+fn set_collection_royalty(collection_id: u64, royalty: Option<RoyaltyInfo>) -> Result<(), String> {
+    if let Some(royalty) = &royalty {
+        validate(royalty)?;
+    }
+    collections::update(collection_id, caller(), |collection| {
+        collection.royalty = royalty;
+    })
+}
+
+/// Royalty owed for a resale of an NFT at `sale_amount` (None if no royalty applies)
+#[query]
+fn royalty_info(nft_id: u64, sale_amount: u64) -> Result<Option<RoyaltyPayment>, String> {
+    let nft = get_nft(nft_id)?;
+
+    let royalty = match nft.royalty {
+        Some(royalty) => Some(royalty),
+        None => match nft.collection_id {
+            Some(collection_id) => collections::get(collection_id)?.royalty,
+            None => None,
+        },
+    };
+
+    Ok(royalty.map(|royalty| compute(&royalty, sale_amount)))
+}
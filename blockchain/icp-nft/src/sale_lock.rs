@@ -0,0 +1,161 @@
+//! Sale locks tying a token to a pending Solana escrow order.
+//!
+//! While a purchase is in escrow the token cannot be transferred, so the NFT
+//! ledger cannot diverge from the escrow while settlement is pending.
+//!
+//! The owner consents to a sale by locking the token, or by approving the
+//! marketplace for it with ICRC-37; the `Marketplace` role alone does not
+//! let a marketplace lock or settle someone else's token.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::update;
+use serde::{Deserialize, Serialize};
+
+use crate::pause::not_paused;
+use crate::roles::{self, Role};
+use crate::prices::{self, SalePrice};
+use crate::{
+    apply_transfer, collections, components, escrow_check, get_nft, icrc37, save_nft, stolen, ProductNFT, TransactionType,
+};
+
+/// Matches the order id capacity of the escrow account (`4 + 50` bytes).
+pub const MAX_ORDER_ID_LEN: usize = 50;
+
+/// Memo of a settled sale's transfer, followed by the order id, so indexers
+/// can join the transaction log to the escrow.
+pub const SALE_MEMO_PREFIX: &str = "sale for order ";
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SaleLock {
+    pub order_id: String,
+    pub locked_by: Principal,
+    pub locked_at: u64,
+}
+
+/// Fail if the token is locked for a pending sale.
+pub fn ensure_unlocked(nft: &ProductNFT) -> Result<(), String> {
+    match &nft.sale_lock {
+        Some(lock) => Err(format!(
+            "NFT {} is locked for escrow order {}",
+            nft.nft_id, lock.order_id
+        )),
+        None => Ok(()),
+    }
+}
+
+fn is_marketplace(principal: Principal) -> bool {
+    roles::has_any_role(principal, &[Role::Marketplace])
+}
+
+/// A marketplace the owner approved for the token (ICRC-37).
+fn is_approved_marketplace(nft_id: u64, principal: Principal) -> bool {
+    is_marketplace(principal) && icrc37::is_approved(nft_id, principal)
+}
+
+/// Owner, or a marketplace the owner approved for the token: lock an NFT
+/// while its Solana escrow order is pending
+#[update(guard = "not_paused")]
+fn lock_for_sale(nft_id: u64, order_id: String) -> Result<ProductNFT, String> {
+    let caller = caller();
+    let mut nft = get_nft(nft_id)?;
+
+    if nft.owner != caller && !is_approved_marketplace(nft_id, caller) {
+        return Err("Only the owner or a marketplace the owner approved can lock this NFT".to_string());
+    }
+    if order_id.is_empty() || order_id.len() > MAX_ORDER_ID_LEN {
+        return Err(format!("Order id must be 1-{} bytes", MAX_ORDER_ID_LEN));
+    }
+    ensure_unlocked(&nft)?;
+    stolen::ensure_not_reported(&nft)?;
+    collections::ensure_not_frozen(&nft)?;
+    components::ensure_transferable(&nft)?;
+
+    nft.sale_lock = Some(SaleLock {
+        order_id,
+        locked_by: caller,
+        locked_at: ic_cdk::api::time(),
+    });
+    save_nft(&nft);
+
+    Ok(nft)
+}
+
+/// Locker or marketplace: release a sale lock (escrow refunded or cancelled)
+#[update(guard = "not_paused")]
+fn unlock(nft_id: u64) -> Result<ProductNFT, String> {
+    let caller = caller();
+    let mut nft = get_nft(nft_id)?;
+
+    let lock = nft.sale_lock.as_ref()
+        .ok_or_else(|| format!("NFT {} is not locked", nft_id))?;
+    if lock.locked_by != caller && !is_marketplace(caller) {
+        return Err("Only the principal that locked this NFT or a marketplace can unlock it".to_string());
+    }
+
+    nft.sale_lock = None;
+    save_nft(&nft);
+
+    Ok(nft)
+}
+
+/// Marketplace: settle a locked sale by transferring the NFT to the buyer.
+/// The token must be owned by `from` and locked for `order_id`, by the
+/// caller or by the owner with the caller approved; the lock is cleared as
+/// part of the transfer. A `price` is recorded against `order_id`. The
+/// order's Solana escrow must be released, so the escrow check must be set.
+#[update(guard = "not_paused")]
+async fn transfer_from(
+    nft_id: u64,
+    from: Principal,
+    to: Principal,
+    order_id: String,
+    price: Option<SalePrice>,
+) -> Result<ProductNFT, String> {
+    let caller = caller();
+    if !is_marketplace(caller) {
+        return Err("Only a marketplace can settle sales".to_string());
+    }
+    // Before reading the token: it may change while the outcall is in flight.
+    // Its collection, and so the marketplace whose escrow to check, cannot.
+    let marketplace_id = get_nft(nft_id).ok().and_then(|nft| collections::marketplace_of(&nft));
+    escrow_check::ensure_released(&order_id, marketplace_id.as_deref()).await?;
+
+    let mut nft = get_nft(nft_id)?;
+    if nft.owner != from {
+        return Err(format!("NFT {} is not owned by {}", nft_id, from));
+    }
+    match &nft.sale_lock {
+        Some(lock) if lock.order_id == order_id => {
+            if lock.locked_by != caller && !icrc37::is_approved(nft_id, caller) {
+                return Err(format!("NFT {} was locked for a different marketplace", nft_id));
+            }
+        }
+        Some(lock) => {
+            return Err(format!("NFT {} is locked for a different order ({})", nft_id, lock.order_id));
+        }
+        None => return Err(format!("NFT {} is not locked for order {}", nft_id, order_id)),
+    }
+    if to == Principal::anonymous() {
+        return Err("Cannot transfer to the anonymous principal".to_string());
+    }
+    stolen::ensure_not_reported(&nft)?;
+    collections::ensure_not_frozen(&nft)?;
+    components::ensure_transferable(&nft)?;
+    let memo = format!("{}{}", SALE_MEMO_PREFIX, order_id);
+    let price = match price {
+        Some(price) if price.order_id.as_ref().is_some_and(|id| *id != order_id) => {
+            return Err(format!("Price is for a different order than {}", order_id));
+        }
+        Some(price) => Some(prices::validate(
+            SalePrice { order_id: Some(order_id), ..price },
+            TransactionType::Sale,
+        )?),
+        None => None,
+    };
+
+    nft.sale_lock = None;
+    apply_transfer(&mut nft, to, TransactionType::Sale, Some(memo), price);
+
+    Ok(nft)
+}
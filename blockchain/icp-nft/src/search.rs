@@ -0,0 +1,285 @@
+//! Secondary indexes and the catalog search API.
+//!
+//! Index keys are `lowercase(value) || 0x00 || nft_id_be`, so all tokens for
+//! one manufacturer or category form a contiguous range in the stable map.
+//! The name index holds one key per word suffix of the product name
+//! ("redmi note 14", "note 14", "14"), so a prefix range scan finds a product
+//! from any word of its name. Serial prefixes are scanned directly in the
+//! serial map, which is ordered by normalized serial.
+
+use candid::CandidType;
+use ic_cdk_macros::query;
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::{get_nft, memory, privacy, serials, Memory, ProductNFT, NFTS, SERIAL_TO_NFT};
+
+const MAX_PAGE_SIZE: u64 = 100;
+/// Words of a product name that get their own index entry.
+const MAX_NAME_WORDS: usize = 8;
+/// Index entries examined per prefix query, bounding the instructions used.
+const MAX_PREFIX_SCAN: usize = 10_000;
+/// Candidate tokens examined per `search_nfts` call, for the same reason.
+const MAX_SEARCH_SCAN: usize = 10_000;
+
+thread_local! {
+    static MANUFACTURER_INDEX: RefCell<StableBTreeMap<Vec<u8>, (), Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(2)));
+
+    static CATEGORY_INDEX: RefCell<StableBTreeMap<Vec<u8>, (), Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(3)));
+
+    static NAME_INDEX: RefCell<StableBTreeMap<Vec<u8>, (), Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(31)));
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct NFTFilter {
+    pub manufacturer: Option<String>,
+    pub category: Option<String>,
+    pub minted_after: Option<u64>,
+    pub minted_before: Option<u64>,
+    pub verified_only: Option<bool>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SearchResult {
+    pub items: Vec<ProductNFT>,
+    /// Matches among the candidates scanned; a lower bound when `has_more`.
+    pub total: u64,
+    pub page: u64,
+    pub limit: u64,
+    /// The scan stopped before examining every candidate.
+    pub has_more: bool,
+}
+
+fn index_prefix(value: &str) -> Vec<u8> {
+    let mut key = value.trim().to_lowercase().into_bytes();
+    key.push(0);
+    key
+}
+
+fn index_key(value: &str, nft_id: u64) -> Vec<u8> {
+    let mut key = index_prefix(value);
+    key.extend_from_slice(&nft_id.to_be_bytes());
+    key
+}
+
+fn ids_with_prefix(index: &StableBTreeMap<Vec<u8>, (), Memory>, value: &str, max: usize) -> Vec<u64> {
+    let prefix = index_prefix(value);
+    index
+        .range(prefix.clone()..)
+        .take_while(|(key, _)| key.starts_with(&prefix))
+        .take(max)
+        .filter_map(|(key, _)| {
+            let id_bytes: [u8; 8] = key[prefix.len()..].try_into().ok()?;
+            Some(u64::from_be_bytes(id_bytes))
+        })
+        .collect()
+}
+
+/// Word suffixes of a product name, lowercased: "Redmi Note 14" gives
+/// "redmi note 14", "note 14" and "14".
+fn name_suffixes(name: &str) -> Vec<String> {
+    let words: Vec<String> = name.split_whitespace().map(str::to_lowercase).collect();
+    (0..words.len().min(MAX_NAME_WORDS)).map(|i| words[i..].join(" ")).collect()
+}
+
+/// Add an NFT to the manufacturer, category and name indexes.
+pub fn index_nft(nft: &ProductNFT) {
+    NAME_INDEX.with(|index| {
+        let mut index = index.borrow_mut();
+        for suffix in name_suffixes(&nft.metadata.product_name) {
+            index.insert(index_key(&suffix, nft.nft_id), ());
+        }
+    });
+    MANUFACTURER_INDEX.with(|index| {
+        index.borrow_mut().insert(index_key(&nft.metadata.manufacturer, nft.nft_id), ());
+    });
+    CATEGORY_INDEX.with(|index| {
+        index.borrow_mut().insert(index_key(&nft.metadata.category, nft.nft_id), ());
+    });
+}
+
+/// Remove a burned NFT from the manufacturer, category and name indexes.
+pub fn unindex_nft(nft: &ProductNFT) {
+    NAME_INDEX.with(|index| {
+        let mut index = index.borrow_mut();
+        for suffix in name_suffixes(&nft.metadata.product_name) {
+            index.remove(&index_key(&suffix, nft.nft_id));
+        }
+    });
+    MANUFACTURER_INDEX.with(|index| {
+        index.borrow_mut().remove(&index_key(&nft.metadata.manufacturer, nft.nft_id));
+    });
+    CATEGORY_INDEX.with(|index| {
+        index.borrow_mut().remove(&index_key(&nft.metadata.category, nft.nft_id));
+    });
+}
+
+/// Build the indexes for tokens minted before they existed.
+pub fn backfill() {
+    let indexed = MANUFACTURER_INDEX.with(|index| index.borrow().len());
+    let names_indexed = NAME_INDEX.with(|index| !index.borrow().is_empty());
+    let total = NFTS.with(|nfts| nfts.borrow().len());
+    if indexed >= total && (names_indexed || total == 0) {
+        return;
+    }
+    NFTS.with(|nfts| {
+        for (_, nft) in nfts.borrow().iter() {
+            index_nft(&nft);
+        }
+    });
+}
+
+fn matches(nft: &ProductNFT, filter: &NFTFilter) -> bool {
+    if let Some(manufacturer) = &filter.manufacturer {
+        if nft.metadata.manufacturer.trim().to_lowercase() != manufacturer.trim().to_lowercase() {
+            return false;
+        }
+    }
+    if let Some(category) = &filter.category {
+        if nft.metadata.category.trim().to_lowercase() != category.trim().to_lowercase() {
+            return false;
+        }
+    }
+    if let Some(after) = filter.minted_after {
+        if nft.minted_at < after {
+            return false;
+        }
+    }
+    if let Some(before) = filter.minted_before {
+        if nft.minted_at > before {
+            return false;
+        }
+    }
+    if filter.verified_only.unwrap_or(false) && nft.revoked {
+        return false;
+    }
+    true
+}
+
+/// IDs of tokens matching `filter`, using an index when possible.
+pub fn matching_ids(filter: &NFTFilter) -> Vec<u64> {
+    let mut ids = Vec::new();
+    for_each_match(filter, usize::MAX, |nft| ids.push(nft.nft_id));
+    ids
+}
+
+/// IDs of all tokens of a manufacturer.
+pub fn ids_for_manufacturer(manufacturer: &str) -> Vec<u64> {
+    MANUFACTURER_INDEX.with(|index| ids_with_prefix(&index.borrow(), manufacturer, usize::MAX))
+}
+
+/// Call `f` for every match among the first `max_scan` candidates; returns
+/// whether candidates were left unexamined.
+fn for_each_match<F: FnMut(ProductNFT)>(filter: &NFTFilter, max_scan: usize, mut f: F) -> bool {
+    // Narrow the candidate set with an index when the filter allows it. One
+    // id past the scan is fetched to tell whether there are more.
+    let max_ids = max_scan.saturating_add(1);
+    let candidates: Option<Vec<u64>> = if let Some(manufacturer) = &filter.manufacturer {
+        Some(MANUFACTURER_INDEX.with(|index| ids_with_prefix(&index.borrow(), manufacturer, max_ids)))
+    } else {
+        filter
+            .category
+            .as_ref()
+            .map(|category| CATEGORY_INDEX.with(|index| ids_with_prefix(&index.borrow(), category, max_ids)))
+    };
+
+    NFTS.with(|nfts| {
+        let nfts = nfts.borrow();
+        let mut candidates: Box<dyn Iterator<Item = ProductNFT> + '_> = match candidates {
+            Some(ids) => Box::new(ids.into_iter().filter_map(|id| nfts.get(&id))),
+            None => Box::new(nfts.iter().map(|(_, nft)| nft)),
+        };
+        candidates
+            .by_ref()
+            .take(max_scan)
+            .filter(|nft| matches(nft, filter))
+            .for_each(&mut f);
+        candidates.next().is_some()
+    })
+}
+
+/// Search NFTs by manufacturer, category, mint time and verification status.
+/// At most `MAX_SEARCH_SCAN` candidates are examined per call.
+#[query]
+fn search_nfts(filter: NFTFilter, page: u64, limit: u64) -> SearchResult {
+    let limit = limit.clamp(1, MAX_PAGE_SIZE);
+    let start = page.saturating_mul(limit);
+    let mut total = 0u64;
+    let mut items = Vec::new();
+    let caller = ic_cdk::caller();
+
+    let has_more = for_each_match(&filter, MAX_SEARCH_SCAN, |nft| {
+        if total >= start && (items.len() as u64) < limit {
+            items.push(privacy::view(caller, nft));
+        }
+        total += 1;
+    });
+
+    SearchResult { items, total, page, limit, has_more }
+}
+
+fn non_empty(prefix: String) -> Result<String, String> {
+    if prefix.is_empty() {
+        return Err("Search prefix must not be empty".to_string());
+    }
+    Ok(prefix)
+}
+
+/// Products whose name has a word starting with `prefix` (case-insensitive)
+#[query]
+fn search_by_name(prefix: String, limit: u64) -> Result<Vec<ProductNFT>, String> {
+    let words: Vec<String> = prefix.split_whitespace().map(str::to_lowercase).collect();
+    let prefix = non_empty(words.join(" "))?.into_bytes();
+    let limit = limit.clamp(1, MAX_PAGE_SIZE) as usize;
+    let mut ids: Vec<u64> = Vec::new();
+    NAME_INDEX.with(|index| {
+        for (key, _) in index
+            .borrow()
+            .range(prefix.clone()..)
+            .take(MAX_PREFIX_SCAN)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+        {
+            let id_bytes: [u8; 8] = key[key.len() - 8..].try_into().expect("index keys end with the token id");
+            let nft_id = u64::from_be_bytes(id_bytes);
+            if !ids.contains(&nft_id) {
+                ids.push(nft_id);
+                if ids.len() == limit {
+                    break;
+                }
+            }
+        }
+    });
+
+    let caller = ic_cdk::caller();
+    Ok(ids
+        .into_iter()
+        .filter_map(|nft_id| get_nft(nft_id).ok())
+        .map(|nft| privacy::view(caller, nft))
+        .collect())
+}
+
+/// Products whose serial number starts with `prefix`, in serial order
+#[query]
+fn list_by_serial_prefix(prefix: String, limit: u64) -> Result<Vec<ProductNFT>, String> {
+    let prefix = non_empty(serials::normalize(&prefix))?;
+    let limit = limit.clamp(1, MAX_PAGE_SIZE) as usize;
+    let ids: Vec<u64> = SERIAL_TO_NFT.with(|map| {
+        map.borrow()
+            .range(prefix.clone()..)
+            .take_while(|(serial, _)| serial.starts_with(&prefix))
+            .take(limit)
+            .map(|(_, nft_id)| nft_id)
+            .collect()
+    });
+
+    let caller = ic_cdk::caller();
+    Ok(ids
+        .into_iter()
+        .filter_map(|nft_id| get_nft(nft_id).ok())
+        .map(|nft| privacy::view(caller, nft))
+        .collect())
+}
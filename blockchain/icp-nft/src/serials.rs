@@ -0,0 +1,56 @@
+//! Serial number normalization and format patterns.
+
+/// Characters dropped from serials before storage and lookup.
+const SEPARATORS: &[char] = &['-', '_', ' ', '.', '/', ':'];
+
+/// Canonical form of a serial: trimmed, separators removed, uppercase.
+/// `" abc-123 "`, `"ABC123"` and `"abc_1.23"` all normalize to `"ABC123"`.
+pub fn normalize(serial_number: &str) -> String {
+    serial_number
+        .trim()
+        .chars()
+        .filter(|c| !SEPARATORS.contains(c))
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// Check a format pattern is well-formed (non-empty after normalization).
+pub fn validate_pattern(pattern: &str) -> Result<(), String> {
+    if normalize(pattern).is_empty() {
+        return Err("Serial format pattern is empty".to_string());
+    }
+    Ok(())
+}
+
+/// Match a normalized serial against a format pattern.
+///
+/// Pattern syntax (applied after normalizing the pattern itself):
+/// `#` a digit, `@` a letter, `?` a letter or digit, `*` any run of letters
+/// and digits (including none); anything else must match literally.
+/// Example: `"ACME-####-@@"` accepts `"ACME0042XY"`.
+pub fn matches_pattern(serial_number: &str, pattern: &str) -> bool {
+    let serial: Vec<char> = normalize(serial_number).chars().collect();
+    let pattern: Vec<char> = normalize(pattern).chars().collect();
+    match_from(&serial, &pattern)
+}
+
+fn match_from(serial: &[char], pattern: &[char]) -> bool {
+    match pattern.split_first() {
+        None => serial.is_empty(),
+        Some(('*', rest)) => (0..=serial.len())
+            .take_while(|&i| serial[..i].iter().all(|c| c.is_ascii_alphanumeric()))
+            .any(|i| match_from(&serial[i..], rest)),
+        Some((&p, rest)) => match serial.split_first() {
+            Some((&c, serial_rest)) => {
+                let ok = match p {
+                    '#' => c.is_ascii_digit(),
+                    '@' => c.is_alphabetic(),
+                    '?' => c.is_alphanumeric(),
+                    literal => literal == c,
+                };
+                ok && match_from(serial_rest, rest)
+            }
+            None => false,
+        },
+    }
+}
@@ -0,0 +1,119 @@
+//! Service and repair history.
+//!
+//! Principals holding the `ServiceCenter` role, and the token's active
+//! custodian (see `custody`), append repair records to a token. Records are append-only and keyed by `(nft_id, sequence)`, so a
+//! product's full repair log can be read back in order and survives
+//! ownership changes. The work description itself stays off-chain; only its
+//! SHA-256 is stored.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::pause::not_paused;
+use crate::roles::{require_role, Role};
+use crate::{custody, find_by_serial, get_nft, memory, txlog, Memory};
+
+const MAX_PARTS: usize = 50;
+const MAX_FIELD_LEN: usize = 128;
+
+/// A repair as submitted by a service center.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ServiceRecord {
+    /// When the work was carried out (nanoseconds since the Unix epoch).
+    pub serviced_at: u64,
+    /// SHA-256 of the off-chain work description.
+    pub work_description_hash: Vec<u8>,
+    pub parts_replaced: Vec<String>,
+    pub technician_id: String,
+}
+
+/// A stored service record with who recorded it and when.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ServiceEntry {
+    pub sequence: u64,
+    pub record: ServiceRecord,
+    pub service_center: Principal,
+    pub recorded_at: u64,
+}
+
+candid_storable!(ServiceEntry);
+
+thread_local! {
+    static RECORDS: RefCell<StableBTreeMap<(u64, u64), ServiceEntry, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(28)));
+}
+
+fn history(nft_id: u64) -> Vec<ServiceEntry> {
+    RECORDS.with(|r| {
+        r.borrow()
+            .range((nft_id, 0)..=(nft_id, u64::MAX))
+            .map(|(_, entry)| entry)
+            .collect()
+    })
+}
+
+fn validate(record: &ServiceRecord) -> Result<(), String> {
+    if record.work_description_hash.len() != 32 {
+        return Err("Work description hash must be a 32-byte SHA-256 digest".to_string());
+    }
+    if record.serviced_at > ic_cdk::api::time() {
+        return Err("Service date cannot be in the future".to_string());
+    }
+    if record.technician_id.trim().is_empty() || record.technician_id.len() > MAX_FIELD_LEN {
+        return Err(format!("Technician id must be 1-{} bytes", MAX_FIELD_LEN));
+    }
+    if record.parts_replaced.len() > MAX_PARTS {
+        return Err(format!("At most {} replaced parts per record", MAX_PARTS));
+    }
+    if record.parts_replaced.iter().any(|part| part.trim().is_empty() || part.len() > MAX_FIELD_LEN) {
+        return Err(format!("Part identifiers must be 1-{} bytes", MAX_FIELD_LEN));
+    }
+    Ok(())
+}
+
+/// Service center or custodian: append a repair record to a product
+#[update(guard = "not_paused")]
+fn add_service_record(nft_id: u64, record: ServiceRecord) -> Result<ServiceEntry, String> {
+    let nft = get_nft(nft_id)?;
+    if !custody::is_custodian(&nft, caller()) {
+        require_role(&[Role::ServiceCenter])?;
+    }
+    validate(&record)?;
+
+    let sequence = RECORDS.with(|r| {
+        r.borrow()
+            .range((nft_id, 0)..=(nft_id, u64::MAX))
+            .last()
+            .map_or(0, |((_, sequence), _)| sequence + 1)
+    });
+    let entry = ServiceEntry {
+        sequence,
+        record,
+        service_center: caller(),
+        recorded_at: ic_cdk::api::time(),
+    };
+    RECORDS.with(|r| {
+        r.borrow_mut().insert((nft_id, sequence), entry.clone());
+    });
+    txlog::append(
+        txlog::TxKind::MetadataUpdate,
+        nft.nft_id,
+        &nft.serial_number,
+        None,
+        None,
+        Some(format!("service record {}", sequence)),
+    );
+
+    Ok(entry)
+}
+
+/// Repair history of a product, oldest first
+#[query]
+fn get_service_history(serial_number: String) -> Result<Vec<ServiceEntry>, String> {
+    let nft = find_by_serial(&serial_number)?;
+    Ok(history(nft.nft_id))
+}
@@ -0,0 +1,91 @@
+//! Shard membership when deployed behind the registry canister.
+//!
+//! The registry (`blockchain/registry-canister`) spreads serials over a fixed
+//! number of NFT canisters by `shard_of`. Each shard is told its index once,
+//! before its first mint, and from then on refuses serials that belong to
+//! another shard, so a serial can only ever exist on its home shard. A
+//! standalone canister has no assignment and accepts every serial.
+
+use candid::CandidType;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::StableCell;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+use crate::pause::not_paused;
+use crate::roles::require_admin;
+use crate::{audit, memory, Memory, NFTS};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShardAssignment {
+    pub index: u32,
+    pub count: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+struct ShardState {
+    assignment: Option<ShardAssignment>,
+}
+
+candid_storable!(ShardState);
+
+thread_local! {
+    static STATE: RefCell<StableCell<ShardState, Memory>> = RefCell::new(
+        StableCell::init(memory(32), ShardState::default())
+            .expect("Failed to initialize shard state")
+    );
+}
+
+fn assignment() -> Option<ShardAssignment> {
+    STATE.with(|s| s.borrow().get().assignment)
+}
+
+/// Home shard of a normalized serial: the first 8 bytes of its SHA-256,
+/// big-endian, modulo the shard count. The registry uses the same function.
+pub fn shard_of(serial_number: &str, count: u32) -> u32 {
+    let digest = Sha256::digest(serial_number.as_bytes());
+    let prefix: [u8; 8] = digest[..8].try_into().expect("SHA-256 digests are 32 bytes");
+    (u64::from_be_bytes(prefix) % u64::from(count)) as u32
+}
+
+/// Refuse a normalized serial that belongs to another shard.
+pub fn check_serial(serial_number: &str) -> Result<(), String> {
+    match assignment() {
+        Some(ShardAssignment { index, count }) if shard_of(serial_number, count) != index => Err(format!(
+            "Serial {} belongs to shard {} of {}, not this shard ({})",
+            serial_number,
+            shard_of(serial_number, count),
+            count,
+            index
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Admin: set this canister's shard index, before anything is minted
+#[update(guard = "not_paused")]
+fn set_shard_assignment(assignment: Option<ShardAssignment>) -> Result<(), String> {
+    require_admin()?;
+    if let Some(ShardAssignment { index, count }) = assignment {
+        if count == 0 || index >= count {
+            return Err(format!("Shard index {} is out of range for {} shards", index, count));
+        }
+    }
+    if NFTS.with(|nfts| !nfts.borrow().is_empty()) {
+        return Err("The shard assignment can only change while the registry is empty".to_string());
+    }
+    STATE.with(|s| {
+        s.borrow_mut()
+            .set(ShardState { assignment })
+            .map_err(|e| format!("Failed to persist shard state: {:?}", e))
+    })?;
+    audit::record("set_shard_assignment");
+    Ok(())
+}
+
+/// This canister's shard index, if it is part of a sharded registry
+#[query]
+fn get_shard_assignment() -> Option<ShardAssignment> {
+    assignment()
+}
@@ -0,0 +1,286 @@
+//! Authenticity attestations for Solana.
+//!
+//! The canister signs a product's serial hash, verified flag and owner hash
+//! with an Ed25519 key held through the IC's threshold Schnorr API. A relayer
+//! posts the signature to the attestation program
+//! (`blockchain/solana-attestation`), which only accepts this key, so Solana
+//! programs can require that a product exists and is verified before funds
+//! lock. The message layout is fixed by that program.
+
+use candid::{CandidType, Principal};
+use ic_cdk::api::call::call_with_payment128;
+use ic_cdk_macros::update;
+use ic_stable_structures::StableCell;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+use crate::certification::owner_hash;
+use crate::pause::not_paused;
+use crate::roles::{require_admin, require_role, Role};
+use crate::verification::VerificationLevel;
+use crate::{audit, find_by_serial, memory, Memory, ProductNFT};
+
+/// Derivation path of the attester key under the canister's threshold key.
+const DERIVATION_PATH: &[u8] = b"solana-attester";
+/// Cycles attached to `sign_with_schnorr`, enough for `key_1`. Unused cycles
+/// are refunded.
+const SIGN_CYCLES: u128 = 26_153_846_153;
+/// Domain separator of the signed message.
+const MESSAGE_DOMAIN: &[u8] = b"proofcart-attestation-v1";
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+struct AttesterConfig {
+    /// Threshold Schnorr key: `dfx_test_key` locally, `test_key_1` or `key_1`
+    /// on mainnet. `None` disables attestations.
+    key_name: Option<String>,
+    /// Cached once fetched.
+    public_key: Option<Vec<u8>>,
+}
+
+candid_storable!(AttesterConfig);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SolanaAttestation {
+    /// Normalized serial number.
+    pub serial_number: String,
+    pub serial_hash: Vec<u8>,
+    /// The token is at least `ManufacturerVerified`, its verification is not
+    /// revoked, and it is neither reported stolen or lost nor recalled.
+    pub verified: bool,
+    pub owner_hash: Vec<u8>,
+    /// Nanoseconds since the epoch.
+    pub issued_at: u64,
+    /// The bytes that were signed.
+    pub message: Vec<u8>,
+    /// 64-byte Ed25519 signature over `message`.
+    pub signature: Vec<u8>,
+    /// Base58 Solana address of the attester key.
+    pub attester: String,
+}
+
+// Management canister types for threshold Schnorr.
+
+#[derive(CandidType, Deserialize, Clone, Copy)]
+enum SchnorrAlgorithm {
+    #[serde(rename = "ed25519")]
+    Ed25519,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+struct SchnorrKeyId {
+    algorithm: SchnorrAlgorithm,
+    name: String,
+}
+
+#[derive(CandidType, Deserialize)]
+struct SchnorrPublicKeyArgs {
+    canister_id: Option<Principal>,
+    derivation_path: Vec<Vec<u8>>,
+    key_id: SchnorrKeyId,
+}
+
+#[derive(CandidType, Deserialize)]
+struct SchnorrPublicKeyResult {
+    public_key: Vec<u8>,
+    chain_code: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct SignWithSchnorrArgs {
+    message: Vec<u8>,
+    derivation_path: Vec<Vec<u8>>,
+    key_id: SchnorrKeyId,
+}
+
+#[derive(CandidType, Deserialize)]
+struct SignWithSchnorrResult {
+    signature: Vec<u8>,
+}
+
+thread_local! {
+    static CONFIG: RefCell<StableCell<AttesterConfig, Memory>> = RefCell::new(
+        StableCell::init(memory(49), AttesterConfig::default())
+            .expect("Failed to initialize Solana attester config")
+    );
+}
+
+fn config() -> AttesterConfig {
+    CONFIG.with(|c| c.borrow().get().clone())
+}
+
+fn set_config(config: AttesterConfig) {
+    CONFIG.with(|c| {
+        c.borrow_mut()
+            .set(config)
+            .expect("Failed to persist Solana attester config");
+    });
+}
+
+fn key_id() -> Result<SchnorrKeyId, String> {
+    let name = config()
+        .key_name
+        .ok_or_else(|| "Solana attestations are not configured".to_string())?;
+    Ok(SchnorrKeyId { algorithm: SchnorrAlgorithm::Ed25519, name })
+}
+
+/// `domain || serial_hash || verified || owner_hash || issued_at (LE)`.
+fn attestation_message(serial_hash: &[u8; 32], verified: bool, owner_hash: &[u8; 32], issued_at: u64) -> Vec<u8> {
+    let mut message = MESSAGE_DOMAIN.to_vec();
+    message.extend_from_slice(serial_hash);
+    message.push(verified as u8);
+    message.extend_from_slice(owner_hash);
+    message.extend_from_slice(&issued_at.to_le_bytes());
+    message
+}
+
+/// The attester's public key, fetched from the management canister once.
+async fn public_key() -> Result<Vec<u8>, String> {
+    if let Some(key) = config().public_key {
+        return Ok(key);
+    }
+    let key_id = key_id()?;
+    let args = SchnorrPublicKeyArgs {
+        canister_id: None,
+        derivation_path: vec![DERIVATION_PATH.to_vec()],
+        key_id: key_id.clone(),
+    };
+    let (result,): (SchnorrPublicKeyResult,) =
+        ic_cdk::call(Principal::management_canister(), "schnorr_public_key", (args,))
+            .await
+            .map_err(|(code, msg)| format!("schnorr_public_key failed: {:?} {}", code, msg))?;
+    let mut config = config();
+    // The key name may have changed while this call awaited.
+    if config.key_name.as_ref() == Some(&key_id.name) {
+        config.public_key = Some(result.public_key.clone());
+        set_config(config);
+    }
+    Ok(result.public_key)
+}
+
+async fn sign(message: Vec<u8>) -> Result<Vec<u8>, String> {
+    let args = SignWithSchnorrArgs {
+        message,
+        derivation_path: vec![DERIVATION_PATH.to_vec()],
+        key_id: key_id()?,
+    };
+    let (result,): (SignWithSchnorrResult,) = call_with_payment128(
+        Principal::management_canister(),
+        "sign_with_schnorr",
+        (args,),
+        SIGN_CYCLES,
+    )
+    .await
+    .map_err(|(code, msg)| format!("sign_with_schnorr failed: {:?} {}", code, msg))?;
+    Ok(result.signature)
+}
+
+/// Whether an attestation may vouch for the token. Solana programs lock funds
+/// on this flag, so anything a buyer should not pay for reads as unverified.
+fn attests_verified(nft: &ProductNFT) -> bool {
+    !nft.revoked
+        && nft.verification_level >= VerificationLevel::ManufacturerVerified
+        && nft.stolen.is_none()
+        && nft.recall.is_none()
+}
+
+/// Admin: set the threshold key that signs Solana attestations
+/// (`None` turns attestations off)
+#[update(guard = "not_paused")]
+fn set_solana_attester(key_name: Option<String>) -> Result<(), String> {
+    require_admin()?;
+    set_config(AttesterConfig { key_name, public_key: None });
+    audit::record("set_solana_attester");
+    Ok(())
+}
+
+/// Base58 Solana address of the attester key, for the attestation program's
+/// `initialize_config`
+#[update]
+async fn solana_attester_address() -> Result<String, String> {
+    Ok(bs58::encode(public_key().await?).into_string())
+}
+
+/// Marketplace: sign the current authenticity of a serial for the Solana
+/// attestation program
+#[update(guard = "not_paused")]
+async fn attest_for_solana(serial_number: String) -> Result<SolanaAttestation, String> {
+    require_role(&[Role::Marketplace])?;
+    let attester = bs58::encode(public_key().await?).into_string();
+
+    // Read after the key lookup awaited; a change while signing gets a later
+    // `issued_at`.
+    let nft = find_by_serial(&serial_number)?;
+    let serial_hash: [u8; 32] = Sha256::digest(nft.serial_number.as_bytes()).into();
+    let verified = attests_verified(&nft);
+    let owner_hash = owner_hash(&nft.owner);
+    let issued_at = ic_cdk::api::time();
+    let message = attestation_message(&serial_hash, verified, &owner_hash, issued_at);
+    let signature = sign(message.clone()).await?;
+
+    Ok(SolanaAttestation {
+        serial_number: nft.serial_number,
+        serial_hash: serial_hash.to_vec(),
+        verified,
+        owner_hash: owner_hash.to_vec(),
+        issued_at,
+        message,
+        signature,
+        attester,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::legacy::{from_nft_canister, NftCanisterNFT};
+    use crate::recalls::RecallInfo;
+    use crate::stolen::{LossKind, StolenReport};
+
+    #[test]
+    fn only_sellable_tokens_attest_as_verified() {
+        let owner = Principal::from_slice(&[7; 29]);
+        let mut nft = from_nft_canister(NftCanisterNFT {
+            id: 0,
+            serial_number: "SN-1".to_string(),
+            product_name: "Watch".to_string(),
+            manufacturer: "Acme".to_string(),
+            metadata_uri: String::new(),
+            owner,
+            minted_at: 0,
+            transfer_history: vec![],
+        });
+        assert_eq!(nft.verification_level, VerificationLevel::SelfAttested);
+        assert!(!attests_verified(&nft));
+
+        nft.verification_level = VerificationLevel::ManufacturerVerified;
+        assert!(attests_verified(&nft));
+
+        let stolen = StolenReport { kind: LossKind::Stolen, reported_by: owner, reported_at: 0 };
+        let recall = RecallInfo {
+            notice_uri: "https://acme.example/recall".to_string(),
+            recalled_at: 0,
+            recalled_by: owner,
+        };
+        for flag in 0..3 {
+            let mut flagged = nft.clone();
+            match flag {
+                0 => flagged.revoked = true,
+                1 => flagged.stolen = Some(stolen.clone()),
+                _ => flagged.recall = Some(recall.clone()),
+            }
+            assert!(!attests_verified(&flagged), "{}", flag);
+        }
+    }
+
+    #[test]
+    fn message_layout_matches_the_solana_program() {
+        let message = attestation_message(&[1; 32], true, &[2; 32], 0x0102);
+        assert_eq!(message.len(), 24 + 32 + 1 + 32 + 8);
+        assert_eq!(&message[..24], b"proofcart-attestation-v1");
+        assert_eq!(&message[24..56], &[1; 32]);
+        assert_eq!(message[56], 1);
+        assert_eq!(&message[57..89], &[2; 32]);
+        assert_eq!(&message[89..], &[2, 1, 0, 0, 0, 0, 0, 0]);
+    }
+}
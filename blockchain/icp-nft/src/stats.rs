@@ -0,0 +1,164 @@
+//! Incrementally maintained per-manufacturer and registry-wide statistics.
+
+use candid::{CandidType, Principal};
+use ic_cdk_macros::query;
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::{jobs, memory, Memory, ProductNFT, NFTS};
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+const MAX_STATS_DAYS: u64 = 366;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ManufacturerStats {
+    pub minted: u64,
+    pub transferred: u64,
+    pub revoked: u64,
+    pub first_mint_at: Option<u64>,
+    pub last_mint_at: Option<u64>,
+}
+
+candid_storable!(ManufacturerStats);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RegistryStats {
+    pub total_nfts: u64,
+    /// Principals currently holding at least one token.
+    pub unique_holders: u64,
+    /// Tokens minted per manufacturer (lowercased name).
+    pub nfts_per_manufacturer: Vec<(String, u64)>,
+    /// Mints per day (days since the Unix epoch), oldest first. Filled in by
+    /// the periodic job roll-up, so the current day may lag.
+    pub mints_per_day: Vec<(u64, u64)>,
+    pub generated_at: u64,
+}
+
+thread_local! {
+    static MANUFACTURER_STATS: RefCell<StableBTreeMap<String, ManufacturerStats, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(16)));
+
+    // Holder -> number of tokens held
+    static HOLDINGS: RefCell<StableBTreeMap<Principal, u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(35)));
+}
+
+fn stats_key(manufacturer: &str) -> String {
+    manufacturer.trim().to_lowercase()
+}
+
+fn update<F: FnOnce(&mut ManufacturerStats)>(manufacturer: &str, f: F) {
+    let key = stats_key(manufacturer);
+    MANUFACTURER_STATS.with(|stats| {
+        let mut stats = stats.borrow_mut();
+        let mut entry = stats.get(&key).unwrap_or_default();
+        f(&mut entry);
+        stats.insert(key, entry);
+    });
+}
+
+fn add_holding(holder: Principal) {
+    HOLDINGS.with(|h| {
+        let mut h = h.borrow_mut();
+        let held = h.get(&holder).unwrap_or(0);
+        h.insert(holder, held + 1);
+    });
+}
+
+fn remove_holding(holder: Principal) {
+    HOLDINGS.with(|h| {
+        let mut h = h.borrow_mut();
+        match h.get(&holder).unwrap_or(0) {
+            0 | 1 => {
+                h.remove(&holder);
+            }
+            held => {
+                h.insert(holder, held - 1);
+            }
+        }
+    });
+}
+
+pub fn record_mint(nft: &ProductNFT) {
+    add_holding(nft.owner);
+    let minted_at = nft.minted_at;
+    update(&nft.metadata.manufacturer, |s| {
+        s.minted += 1;
+        s.first_mint_at = Some(s.first_mint_at.map_or(minted_at, |t| t.min(minted_at)));
+        s.last_mint_at = Some(s.last_mint_at.map_or(minted_at, |t| t.max(minted_at)));
+    });
+}
+
+pub fn record_transfer(nft: &ProductNFT, previous_owner: Principal) {
+    remove_holding(previous_owner);
+    add_holding(nft.owner);
+    update(&nft.metadata.manufacturer, |s| s.transferred += 1);
+}
+
+pub fn record_revocation(nft: &ProductNFT) {
+    update(&nft.metadata.manufacturer, |s| s.revoked += 1);
+}
+
+pub fn record_burn(nft: &ProductNFT) {
+    remove_holding(nft.owner);
+}
+
+pub fn record_restoration(nft: &ProductNFT) {
+    update(&nft.metadata.manufacturer, |s| s.revoked = s.revoked.saturating_sub(1));
+}
+
+/// Compute statistics for tokens minted before they were tracked.
+pub fn backfill() {
+    let holders_empty = HOLDINGS.with(|h| h.borrow().is_empty());
+    let empty = MANUFACTURER_STATS.with(|stats| stats.borrow().is_empty());
+    if holders_empty && !empty {
+        NFTS.with(|nfts| {
+            for (_, nft) in nfts.borrow().iter() {
+                add_holding(nft.owner);
+            }
+        });
+    }
+    if !empty {
+        return;
+    }
+    NFTS.with(|nfts| {
+        for (_, nft) in nfts.borrow().iter() {
+            record_mint(&nft);
+            let transfers = nft.ownership_history.len().saturating_sub(1) as u64;
+            let revoked = nft.revoked;
+            update(&nft.metadata.manufacturer, |s| {
+                s.transferred += transfers;
+                s.revoked += revoked as u64;
+            });
+        }
+    });
+}
+
+/// Minted/transferred/revoked counts and mint time range for a manufacturer
+#[query]
+fn get_manufacturer_stats(manufacturer: String) -> ManufacturerStats {
+    MANUFACTURER_STATS.with(|stats| stats.borrow().get(&stats_key(&manufacturer)).unwrap_or_default())
+}
+
+/// Registry-wide figures for transparency reports, with mints per day for the
+/// last `days` days (at most 366)
+#[query]
+fn get_registry_stats(days: u64) -> RegistryStats {
+    let now = ic_cdk::api::time();
+    let today = now / NANOS_PER_SEC / SECS_PER_DAY;
+    let days = days.clamp(1, MAX_STATS_DAYS);
+    let from_day = today.saturating_sub(days - 1);
+    RegistryStats {
+        total_nfts: NFTS.with(|nfts| nfts.borrow().len()),
+        unique_holders: HOLDINGS.with(|h| h.borrow().len()),
+        nfts_per_manufacturer: MANUFACTURER_STATS
+            .with(|stats| stats.borrow().iter().map(|(name, s)| (name, s.minted)).collect()),
+        mints_per_day: jobs::daily_stats(from_day, today)
+            .into_iter()
+            .map(|(day, stats)| (day, stats.mints))
+            .collect(),
+        generated_at: now,
+    }
+}
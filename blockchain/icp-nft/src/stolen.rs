@@ -0,0 +1,102 @@
+//! Stolen and lost reports.
+//!
+//! The owner of a token (or the SuperAdmin) can flag it as stolen or lost.
+//! While flagged the token cannot be transferred, claimed or locked for sale,
+//! and `verify_product` shows the report, so a stolen item cannot be passed
+//! on through the registry until the owner or the SuperAdmin clears it.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::update;
+use serde::{Deserialize, Serialize};
+
+use crate::pause::not_paused;
+use crate::roles::is_admin;
+use crate::{get_nft, privacy, save_nft, txlog, ProductNFT};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LossKind {
+    Stolen,
+    Lost,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StolenReport {
+    pub kind: LossKind,
+    pub reported_by: Principal,
+    pub reported_at: u64,
+}
+
+/// Fail if the token is flagged as stolen or lost.
+pub fn ensure_not_reported(nft: &ProductNFT) -> Result<(), String> {
+    match &nft.stolen {
+        Some(report) => Err(format!(
+            "NFT {} is reported {}",
+            nft.nft_id,
+            match report.kind {
+                LossKind::Stolen => "stolen",
+                LossKind::Lost => "lost",
+            }
+        )),
+        None => Ok(()),
+    }
+}
+
+fn authorize(nft: &ProductNFT, caller: Principal) -> Result<(), String> {
+    if nft.owner == caller || is_admin(caller) {
+        Ok(())
+    } else {
+        Err("Only the owner or an admin can report this NFT stolen or lost".to_string())
+    }
+}
+
+/// Owner or admin: flag an NFT as stolen (the default) or lost, blocking transfers
+#[update(guard = "not_paused")]
+fn report_stolen(nft_id: u64, kind: Option<LossKind>) -> Result<ProductNFT, String> {
+    let caller = caller();
+    let mut nft = get_nft(nft_id)?;
+    authorize(&nft, caller)?;
+    ensure_not_reported(&nft)?;
+
+    let kind = kind.unwrap_or(LossKind::Stolen);
+    nft.stolen = Some(StolenReport {
+        kind,
+        reported_by: caller,
+        reported_at: ic_cdk::api::time(),
+    });
+    save_nft(&nft);
+    txlog::append(
+        txlog::TxKind::MetadataUpdate,
+        nft.nft_id,
+        &nft.serial_number,
+        None,
+        None,
+        Some(format!("reported {:?}", kind).to_lowercase()),
+    );
+
+    Ok(privacy::view(caller, nft))
+}
+
+/// Owner or admin: clear a stolen/lost flag once the item is recovered
+#[update(guard = "not_paused")]
+fn clear_stolen(nft_id: u64) -> Result<ProductNFT, String> {
+    let caller = caller();
+    let mut nft = get_nft(nft_id)?;
+    authorize(&nft, caller)?;
+    if nft.stolen.is_none() {
+        return Err(format!("NFT {} is not reported stolen or lost", nft_id));
+    }
+
+    nft.stolen = None;
+    save_nft(&nft);
+    txlog::append(
+        txlog::TxKind::MetadataUpdate,
+        nft.nft_id,
+        &nft.serial_number,
+        None,
+        None,
+        Some("stolen/lost report cleared".to_string()),
+    );
+
+    Ok(privacy::view(caller, nft))
+}
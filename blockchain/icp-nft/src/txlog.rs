@@ -0,0 +1,215 @@
+//! ICRC-3-style append-only transaction log.
+//!
+//! Every state change is appended as a `Block` chained to its predecessor by
+//! hash. Indexers page through `get_transactions`; old blocks can be moved to
+//! an archive canister (which must implement `append_blocks(vec Block)` and
+//! `get_transactions(nat64, nat64)`), after which they are reported as
+//! `archived` ranges.
+
+use candid::{CandidType, Encode, Principal};
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::{StableBTreeMap, StableCell};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+use crate::pause::not_paused;
+use crate::roles::require_admin;
+use crate::{audit, memory, Memory};
+
+const MAX_TRANSACTIONS_PER_CALL: u64 = 1_000;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum TxKind {
+    Mint,
+    Transfer,
+    Burn,
+    MetadataUpdate,
+    Approve,
+    Revoke,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Block {
+    pub index: u64,
+    pub kind: TxKind,
+    pub nft_id: u64,
+    pub serial_number: String,
+    pub from: Option<Principal>,
+    pub to: Option<Principal>,
+    pub caller: Principal,
+    pub timestamp: u64,
+    pub memo: Option<String>,
+    pub parent_hash: Option<Vec<u8>>,
+}
+
+candid_storable!(Block);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ArchivedRange {
+    pub canister_id: Principal,
+    pub start: u64,
+    pub length: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LogState {
+    pub next_index: u64,
+    pub last_hash: Option<Vec<u8>>,
+    pub archives: Vec<ArchivedRange>,
+}
+
+candid_storable!(LogState);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GetTransactionsResponse {
+    pub log_length: u64,
+    pub first_index: u64,
+    pub transactions: Vec<Block>,
+    pub archived: Vec<ArchivedRange>,
+}
+
+thread_local! {
+    static BLOCKS: RefCell<StableBTreeMap<u64, Block, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(5)));
+
+    static LOG_STATE: RefCell<StableCell<LogState, Memory>> = RefCell::new(
+        StableCell::init(memory(6), LogState::default())
+            .expect("Failed to initialize transaction log state")
+    );
+}
+
+fn state() -> LogState {
+    LOG_STATE.with(|s| s.borrow().get().clone())
+}
+
+fn set_state(state: LogState) {
+    LOG_STATE.with(|s| {
+        s.borrow_mut().set(state).expect("Failed to persist transaction log state");
+    });
+}
+
+pub fn block_hash(block: &Block) -> Vec<u8> {
+    let bytes = Encode!(block).expect("Failed to encode block");
+    Sha256::digest(bytes).to_vec()
+}
+
+/// Append a block and return its index.
+pub fn append(
+    kind: TxKind,
+    nft_id: u64,
+    serial_number: &str,
+    from: Option<Principal>,
+    to: Option<Principal>,
+    memo: Option<String>,
+) -> u64 {
+    let mut log = state();
+    let block = Block {
+        index: log.next_index,
+        kind,
+        nft_id,
+        serial_number: serial_number.to_string(),
+        from,
+        to,
+        caller: ic_cdk::caller(),
+        timestamp: ic_cdk::api::time(),
+        memo,
+        parent_hash: log.last_hash.clone(),
+    };
+
+    log.last_hash = Some(block_hash(&block));
+    log.next_index += 1;
+    BLOCKS.with(|blocks| {
+        blocks.borrow_mut().insert(block.index, block.clone());
+    });
+    set_state(log);
+
+    block.index
+}
+
+/// Up to `max` locally held blocks starting at `start`.
+pub fn blocks_from(start: u64, max: usize) -> Vec<Block> {
+    BLOCKS.with(|blocks| blocks.borrow().range(start..).take(max).map(|(_, block)| block).collect())
+}
+
+/// Page through the transaction log
+#[query]
+fn get_transactions(start: u64, length: u64) -> GetTransactionsResponse {
+    let log = state();
+    let length = length.min(MAX_TRANSACTIONS_PER_CALL);
+    let end = start.saturating_add(length);
+
+    let first_local = BLOCKS.with(|blocks| blocks.borrow().first_key_value().map(|(index, _)| index))
+        .unwrap_or(log.next_index);
+
+    let transactions = BLOCKS.with(|blocks| {
+        blocks.borrow()
+            .range(start.max(first_local)..end)
+            .map(|(_, block)| block)
+            .collect()
+    });
+
+    // Report the archived parts of the requested range.
+    let archived = log.archives
+        .iter()
+        .filter_map(|range| {
+            let lo = range.start.max(start);
+            let hi = (range.start + range.length).min(end);
+            (lo < hi).then(|| ArchivedRange {
+                canister_id: range.canister_id,
+                start: lo,
+                length: hi - lo,
+            })
+        })
+        .collect();
+
+    GetTransactionsResponse {
+        log_length: log.next_index,
+        first_index: first_local,
+        transactions,
+        archived,
+    }
+}
+
+/// Admin: move up to `count` of the oldest local blocks to an archive canister
+#[update(guard = "not_paused")]
+async fn archive_transactions(archive: Principal, count: u64) -> Result<ArchivedRange, String> {
+    require_admin()?;
+    let (caller, args_hash) = (ic_cdk::caller(), audit::args_hash());
+
+    let blocks: Vec<Block> = BLOCKS.with(|blocks| {
+        blocks.borrow()
+            .iter()
+            .take(count.min(MAX_TRANSACTIONS_PER_CALL) as usize)
+            .map(|(_, block)| block)
+            .collect()
+    });
+    let (start, length) = match (blocks.first(), blocks.last()) {
+        (Some(first), Some(last)) => (first.index, last.index - first.index + 1),
+        _ => return Err("No local blocks to archive".to_string()),
+    };
+
+    ic_cdk::call::<(Vec<Block>,), ()>(archive, "append_blocks", (blocks,))
+        .await
+        .map_err(|(code, msg)| format!("Archive call failed: {:?} {}", code, msg))?;
+
+    BLOCKS.with(|blocks| {
+        let mut blocks = blocks.borrow_mut();
+        for index in start..start + length {
+            blocks.remove(&index);
+        }
+    });
+
+    let range = ArchivedRange { canister_id: archive, start, length };
+    let mut log = state();
+    match log.archives.last_mut() {
+        Some(last) if last.canister_id == archive && last.start + last.length == start => {
+            last.length += length;
+        }
+        _ => log.archives.push(range.clone()),
+    }
+    set_state(log);
+    audit::append("archive_transactions", caller, args_hash);
+
+    Ok(range)
+}
@@ -0,0 +1,66 @@
+//! Verification levels.
+//!
+//! A token's level says who stands behind it: anyone can mint a
+//! `SelfAttested` certificate, tokens minted by a registered manufacturer are
+//! `ManufacturerVerified`, and an independent auditor (the `Auditor` role) can
+//! raise a token to `ThirdPartyAudited` with `endorse`. Revocation is tracked
+//! separately and overrides the level.
+
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::update;
+use serde::{Deserialize, Serialize};
+
+use crate::pause::not_paused;
+use crate::roles::{require_role, Role};
+use crate::{audit, get_nft, manufacturers, save_nft, txlog, ProductNFT};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum VerificationLevel {
+    SelfAttested,
+    ManufacturerVerified,
+    ThirdPartyAudited,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Endorsement {
+    pub auditor: Principal,
+    pub endorsed_at: u64,
+}
+
+/// Level a token starts with when minted by `minter`.
+pub fn level_for_minter(minter: Principal) -> VerificationLevel {
+    if manufacturers::get(minter).is_some() {
+        VerificationLevel::ManufacturerVerified
+    } else {
+        VerificationLevel::SelfAttested
+    }
+}
+
+/// Auditor: endorse a token, raising it to `ThirdPartyAudited`
+#[update(guard = "not_paused")]
+fn endorse(nft_id: u64) -> Result<ProductNFT, String> {
+    require_role(&[Role::Auditor])?;
+    let mut nft = get_nft(nft_id)?;
+    if nft.revoked {
+        return Err(format!("NFT {} is revoked and cannot be endorsed", nft_id));
+    }
+    if nft.endorsement.is_some() {
+        return Err(format!("NFT {} is already endorsed", nft_id));
+    }
+
+    nft.verification_level = VerificationLevel::ThirdPartyAudited;
+    nft.endorsement = Some(Endorsement { auditor: caller(), endorsed_at: ic_cdk::api::time() });
+    save_nft(&nft);
+    txlog::append(
+        txlog::TxKind::MetadataUpdate,
+        nft.nft_id,
+        &nft.serial_number,
+        None,
+        None,
+        Some("endorsed by auditor".to_string()),
+    );
+
+    audit::record("endorse");
+    Ok(nft)
+}
@@ -0,0 +1,56 @@
+//! Interface versioning.
+//!
+//! `src/proofcart_nft.did` is the committed candid interface. The test below
+//! regenerates the interface from the code and fails if it is not backward
+//! compatible with the committed file, or if the file is out of date. After a
+//! deliberate change, regenerate the file with
+//! `UPDATE_CANDID=1 cargo test candid_interface` and bump `API_VERSION`: the
+//! major on incompatible changes, the minor otherwise.
+
+use candid::CandidType;
+use ic_cdk_macros::query;
+use serde::{Deserialize, Serialize};
+
+pub const API_VERSION: (u16, u16) = (4, 34);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ApiVersion {
+    /// Distinguishes this canister from the legacy `nft_canister`.
+    pub implementation: String,
+    pub major: u16,
+    pub minor: u16,
+}
+
+/// Version of this canister's candid interface
+#[query]
+fn api_version() -> ApiVersion {
+    ApiVersion {
+        implementation: "proofcart-nft".to_string(),
+        major: API_VERSION.0,
+        minor: API_VERSION.1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candid_parser::utils::{service_compatible, service_equal, CandidSource};
+    use std::path::PathBuf;
+
+    #[test]
+    fn candid_interface_matches_committed_did() {
+        let generated = crate::__export_service();
+        let committed = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/proofcart_nft.did");
+
+        if std::env::var_os("UPDATE_CANDID").is_some() {
+            std::fs::write(&committed, &generated).expect("Failed to write proofcart_nft.did");
+            return;
+        }
+
+        service_compatible(CandidSource::Text(&generated), CandidSource::File(&committed)).expect(
+            "Candid interface is not backward compatible with src/proofcart_nft.did; \
+             bump the API_VERSION major and regenerate with UPDATE_CANDID=1",
+        );
+        service_equal(CandidSource::Text(&generated), CandidSource::File(&committed))
+            .expect("src/proofcart_nft.did is out of date; regenerate with UPDATE_CANDID=1");
+    }
+}
@@ -0,0 +1,120 @@
+//! Warranty expiry.
+//!
+//! Tokens minted with `warranty_expires_at` carry a `Warranty`. Pending
+//! expiries are indexed by time so the scheduled job only touches tokens
+//! that are actually due.
+//!
+//! A manufacturer whose warranty covers only the first buyer mints with
+//! `warranty_transfers = false`. The first transfer away from the minter
+//! keeps the warranty; any later one voids it, so second-hand buyers see the
+//! manufacturer's policy in `get_warranty_status`.
+
+use candid::{CandidType, Principal};
+use ic_cdk_macros::query;
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::{get_nft, memory, save_nft, txlog, Memory, ProductNFT};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Warranty {
+    pub expires_at: u64,
+    pub expired: bool,
+    /// Whether the warranty survives resale; `None` (older tokens) means it does.
+    pub transfers: Option<bool>,
+    /// Set when a resale voided a non-transferable warranty.
+    pub voided: Option<WarrantyVoided>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct WarrantyVoided {
+    pub voided_at: u64,
+    /// Owner whose transfer voided the warranty.
+    pub transferred_by: Principal,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct WarrantyStatus {
+    pub expires_at: u64,
+    pub expired: bool,
+    pub transfers: bool,
+    pub voided: Option<WarrantyVoided>,
+    /// Neither expired nor voided.
+    pub valid: bool,
+}
+
+thread_local! {
+    // (expires_at, nft_id) for warranties not yet marked expired
+    static EXPIRY_INDEX: RefCell<StableBTreeMap<(u64, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(24)));
+}
+
+/// Index a token's warranty for the expiry job.
+pub fn schedule(nft: &ProductNFT) {
+    if let Some(Warranty { expires_at, expired: false, .. }) = nft.warranty {
+        EXPIRY_INDEX.with(|index| {
+            index.borrow_mut().insert((expires_at, nft.nft_id), ());
+        });
+    }
+}
+
+/// Mark up to `max` warranties that expired by `now`; returns how many.
+pub fn expire_due(now: u64, max: usize) -> usize {
+    let due: Vec<(u64, u64)> = EXPIRY_INDEX.with(|index| {
+        index.borrow()
+            .range(..(now, u64::MAX))
+            .take(max)
+            .map(|(key, _)| key)
+            .collect()
+    });
+
+    for key in &due {
+        EXPIRY_INDEX.with(|index| index.borrow_mut().remove(key));
+        let Ok(mut nft) = get_nft(key.1) else { continue };
+        if let Some(warranty) = nft.warranty.as_mut().filter(|w| w.expires_at == key.0 && !w.expired) {
+            warranty.expired = true;
+            save_nft(&nft);
+            txlog::append(
+                txlog::TxKind::MetadataUpdate,
+                nft.nft_id,
+                &nft.serial_number,
+                None,
+                None,
+                Some("warranty expired".to_string()),
+            );
+        }
+    }
+    due.len()
+}
+
+/// Void a non-transferable warranty when its token is resold by someone
+/// other than the minter. Does not save.
+pub fn on_transfer(nft: &mut ProductNFT, previous_owner: Principal) {
+    if previous_owner == nft.minter() {
+        return;
+    }
+    if let Some(warranty) = nft.warranty.as_mut() {
+        if warranty.transfers == Some(false) && warranty.voided.is_none() {
+            warranty.voided = Some(WarrantyVoided {
+                voided_at: ic_cdk::api::time(),
+                transferred_by: previous_owner,
+            });
+        }
+    }
+}
+
+/// Warranty of a token, with its transfer policy and whether it still applies
+#[query]
+fn get_warranty_status(nft_id: u64) -> Result<WarrantyStatus, String> {
+    let nft = get_nft(nft_id)?;
+    let warranty = nft.warranty.ok_or_else(|| format!("NFT {} has no warranty", nft_id))?;
+    let expired = warranty.expired || warranty.expires_at <= ic_cdk::api::time();
+    Ok(WarrantyStatus {
+        expires_at: warranty.expires_at,
+        expired,
+        transfers: warranty.transfers.unwrap_or(true),
+        valid: !expired && warranty.voided.is_none(),
+        voided: warranty.voided,
+    })
+}
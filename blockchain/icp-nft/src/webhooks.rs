@@ -0,0 +1,337 @@
+//! Outbound webhook notifications.
+//!
+//! Transfers, revocations, restorations and recalls are queued in stable memory and POSTed
+//! as JSON to the admin-configured URL with HTTPS outcalls. Each request
+//! carries `X-ProofCart-Event-Id` and `X-ProofCart-Signature: sha256=<hex>`,
+//! an HMAC-SHA256 of the body under the shared secret. Failed deliveries are
+//! retried with exponential backoff and dropped after `max_attempts`.
+//!
+//! Outcalls are made by every replica of the subnet, so receivers see the
+//! same event several times and must deduplicate on the event id.
+
+use candid::{CandidType, Nat, Principal};
+use hmac::{Hmac, Mac};
+use ic_cdk::api::management_canister::http_request::{
+    self as outcall, CanisterHttpRequestArgument, HttpHeader, HttpMethod, TransformArgs, TransformContext,
+};
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::{StableBTreeMap, StableCell};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
+
+use crate::http::hex;
+use crate::moderation::RevocationReason;
+use crate::pause::not_paused;
+use crate::roles::require_admin;
+use crate::{audit, memory, Memory, ProductNFT};
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+const DELIVERY_INTERVAL_SECS: u64 = 30;
+const BASE_BACKOFF_SECS: u64 = 30;
+const MAX_BACKOFF_SECS: u64 = 60 * 60;
+/// Deliveries attempted per timer tick.
+const MAX_DELIVERIES_PER_TICK: usize = 10;
+const MAX_QUEUE_LEN: u64 = 10_000;
+const OUTCALL_CYCLES: u128 = 2_000_000_000;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebhookEventKind {
+    Transfer,
+    Revocation,
+    Restoration,
+    Recall,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct WebhookEvent {
+    pub id: u64,
+    pub kind: WebhookEventKind,
+    pub nft_id: u64,
+    pub serial_number: String,
+    pub from: Option<Principal>,
+    pub to: Option<Principal>,
+    pub detail: Option<String>,
+    pub timestamp: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PendingDelivery {
+    pub event: WebhookEvent,
+    pub attempts: u32,
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+}
+
+candid_storable!(PendingDelivery);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+struct WebhookState {
+    url: Option<String>,
+    secret: Vec<u8>,
+    max_attempts: u32,
+    next_event_id: u64,
+    delivered: u64,
+    dropped: u64,
+    last_error: Option<String>,
+}
+
+candid_storable!(WebhookState);
+
+/// Webhook configuration as reported to admins (the secret is never returned).
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct WebhookStatus {
+    pub url: Option<String>,
+    pub max_attempts: u32,
+    pub queued: u64,
+    pub delivered: u64,
+    pub dropped: u64,
+    pub last_error: Option<String>,
+}
+
+thread_local! {
+    static QUEUE: RefCell<StableBTreeMap<u64, PendingDelivery, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(26)));
+
+    static STATE: RefCell<StableCell<WebhookState, Memory>> = RefCell::new(
+        StableCell::init(memory(27), WebhookState::default())
+            .expect("Failed to initialize webhook state")
+    );
+
+    static DELIVERING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn state() -> WebhookState {
+    STATE.with(|s| s.borrow().get().clone())
+}
+
+fn set_state(state: WebhookState) {
+    STATE.with(|s| {
+        s.borrow_mut().set(state).expect("Failed to persist webhook state");
+    });
+}
+
+/// Start the delivery timer (timers do not survive upgrades).
+pub fn start() {
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(DELIVERY_INTERVAL_SECS), || {
+        ic_cdk::spawn(deliver_due())
+    });
+}
+
+fn enqueue(
+    kind: WebhookEventKind,
+    nft: &ProductNFT,
+    from: Option<Principal>,
+    to: Option<Principal>,
+    detail: Option<String>,
+) {
+    let mut state = state();
+    if state.url.is_none() {
+        return;
+    }
+    if QUEUE.with(|q| q.borrow().len()) >= MAX_QUEUE_LEN {
+        state.dropped += 1;
+        state.last_error = Some("Webhook queue full; event dropped".to_string());
+        set_state(state);
+        return;
+    }
+    let now = ic_cdk::api::time();
+    let event = WebhookEvent {
+        id: state.next_event_id,
+        kind,
+        nft_id: nft.nft_id,
+        serial_number: nft.serial_number.clone(),
+        from,
+        to,
+        detail,
+        timestamp: now,
+    };
+    state.next_event_id += 1;
+    QUEUE.with(|q| {
+        q.borrow_mut().insert(
+            event.id,
+            PendingDelivery { event, attempts: 0, next_attempt_at: now, last_error: None },
+        );
+    });
+    set_state(state);
+}
+
+pub fn notify_transfer(nft: &ProductNFT, from: Principal, to: Principal) {
+    enqueue(WebhookEventKind::Transfer, nft, Some(from), Some(to), None);
+}
+
+pub fn notify_revocation(nft: &ProductNFT, reason: RevocationReason) {
+    enqueue(WebhookEventKind::Revocation, nft, None, None, Some(format!("{:?}", reason)));
+}
+
+pub fn notify_restoration(nft: &ProductNFT, justification: &str) {
+    enqueue(WebhookEventKind::Restoration, nft, None, None, Some(justification.to_string()));
+}
+
+pub fn notify_recall(nft: &ProductNFT, notice_uri: &str) {
+    enqueue(WebhookEventKind::Recall, nft, None, None, Some(notice_uri.to_string()));
+}
+
+fn body(event: &WebhookEvent) -> Vec<u8> {
+    json!({
+        "id": event.id,
+        "event": match event.kind {
+            WebhookEventKind::Transfer => "transfer",
+            WebhookEventKind::Revocation => "revocation",
+            WebhookEventKind::Restoration => "restoration",
+            WebhookEventKind::Recall => "recall",
+        },
+        "nft_id": event.nft_id,
+        "serial_number": event.serial_number,
+        "from": event.from.map(|p| p.to_text()),
+        "to": event.to.map(|p| p.to_text()),
+        "detail": event.detail,
+        "timestamp": event.timestamp,
+    })
+    .to_string()
+    .into_bytes()
+}
+
+fn signature(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex(&mac.finalize().into_bytes()))
+}
+
+async fn post(url: &str, secret: &[u8], event: &WebhookEvent) -> Result<(), String> {
+    let body = body(event);
+    let request = CanisterHttpRequestArgument {
+        url: url.to_string(),
+        max_response_bytes: Some(1024),
+        method: HttpMethod::POST,
+        headers: vec![
+            HttpHeader { name: "Content-Type".to_string(), value: "application/json".to_string() },
+            HttpHeader { name: "X-ProofCart-Event-Id".to_string(), value: event.id.to_string() },
+            HttpHeader { name: "X-ProofCart-Signature".to_string(), value: signature(secret, &body) },
+        ],
+        body: Some(body),
+        transform: Some(TransformContext::from_name("transform_webhook_response".to_string(), vec![])),
+    };
+    let (response,) = outcall::http_request(request, OUTCALL_CYCLES)
+        .await
+        .map_err(|(code, msg)| format!("{:?} {}", code, msg))?;
+    if response.status < Nat::from(200u64) || response.status >= Nat::from(300u64) {
+        return Err(format!("HTTP {}", response.status));
+    }
+    Ok(())
+}
+
+fn backoff_nanos(attempts: u32) -> u64 {
+    let secs = BASE_BACKOFF_SECS.saturating_mul(1u64 << attempts.min(16)).min(MAX_BACKOFF_SECS);
+    secs * NANOS_PER_SEC
+}
+
+async fn deliver_due() {
+    // One delivery pass at a time; ticks overlapping a slow pass are skipped.
+    if DELIVERING.with(|d| d.replace(true)) {
+        return;
+    }
+    let config = state();
+    if let Some(url) = config.url {
+        let now = ic_cdk::api::time();
+        let due: Vec<PendingDelivery> = QUEUE.with(|q| {
+            q.borrow()
+                .iter()
+                .map(|(_, pending)| pending)
+                .filter(|pending| pending.next_attempt_at <= now)
+                .take(MAX_DELIVERIES_PER_TICK)
+                .collect()
+        });
+
+        for mut pending in due {
+            let result = post(&url, &config.secret, &pending.event).await;
+            let mut state = state();
+            match result {
+                Ok(()) => {
+                    QUEUE.with(|q| q.borrow_mut().remove(&pending.event.id));
+                    state.delivered += 1;
+                }
+                Err(e) => {
+                    let event_id = pending.event.id;
+                    state.last_error = Some(format!("Event {}: {}", event_id, e));
+                    pending.attempts += 1;
+                    if pending.attempts >= state.max_attempts {
+                        QUEUE.with(|q| q.borrow_mut().remove(&event_id));
+                        state.dropped += 1;
+                    } else {
+                        pending.next_attempt_at = ic_cdk::api::time() + backoff_nanos(pending.attempts);
+                        pending.last_error = Some(e);
+                        QUEUE.with(|q| q.borrow_mut().insert(event_id, pending));
+                    }
+                }
+            }
+            set_state(state);
+        }
+    }
+    DELIVERING.with(|d| d.set(false));
+}
+
+/// Keep only the status so every replica agrees on the response.
+#[query]
+fn transform_webhook_response(args: TransformArgs) -> outcall::HttpResponse {
+    outcall::HttpResponse { status: args.response.status, headers: vec![], body: vec![] }
+}
+
+/// Admin: set the webhook URL and HMAC secret, or disable webhooks (`url = None`)
+#[update(guard = "not_paused")]
+fn set_webhook(url: Option<String>, secret: Vec<u8>, max_attempts: u32) -> Result<WebhookStatus, String> {
+    require_admin()?;
+    if let Some(url) = &url {
+        if !url.starts_with("https://") {
+            return Err("Webhook URL must use https://".to_string());
+        }
+        if secret.len() < 16 {
+            return Err("Webhook secret must be at least 16 bytes".to_string());
+        }
+    }
+    if max_attempts == 0 {
+        return Err("max_attempts must be at least 1".to_string());
+    }
+    let mut state = state();
+    state.url = url;
+    state.secret = secret;
+    state.max_attempts = max_attempts;
+    set_state(state);
+    audit::record("set_webhook");
+    Ok(status())
+}
+
+fn status() -> WebhookStatus {
+    let state = state();
+    WebhookStatus {
+        url: state.url,
+        max_attempts: state.max_attempts,
+        queued: QUEUE.with(|q| q.borrow().len()),
+        delivered: state.delivered,
+        dropped: state.dropped,
+        last_error: state.last_error,
+    }
+}
+
+/// Admin: webhook configuration and delivery counters
+#[query]
+fn get_webhook_status() -> Result<WebhookStatus, String> {
+    require_admin()?;
+    Ok(status())
+}
+
+/// Admin: queued deliveries, oldest first
+#[query]
+fn list_pending_webhooks(offset: u64, limit: u64) -> Result<Vec<PendingDelivery>, String> {
+    require_admin()?;
+    Ok(QUEUE.with(|q| {
+        q.borrow()
+            .iter()
+            .skip(offset as usize)
+            .take(limit.min(100) as usize)
+            .map(|(_, pending)| pending)
+            .collect()
+    }))
+}
@@ -0,0 +1,356 @@
+use candid::{CandidType, Principal};
+use ic_cdk::caller;
+use ic_cdk_macros::{init, query, update};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell, Storable};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+/// Order ids must fit the escrow account's order id field.
+const MAX_ORDER_ID_LEN: usize = 50;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum OrderStatus {
+    /// NFT locked on ICP, waiting for the Solana escrow to settle
+    Locked,
+    /// Settlement observed, NFT transfer in flight
+    Settling,
+    /// NFT transferred to the buyer
+    Settled,
+    /// Escrow refunded, NFT unlocked for the seller
+    Refunded,
+    /// Last settlement attempt failed; can be retried
+    Failed,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum EscrowOutcome {
+    Released,
+    Refunded,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Order {
+    pub order_id: String,
+    pub nft_id: u64,
+    pub seller: Principal,
+    pub buyer: Principal,
+    pub amount: u64,
+    pub status: OrderStatus,
+    pub outcome: Option<EscrowOutcome>,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub last_error: Option<String>,
+}
+
+impl Storable for Order {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(candid::Encode!(self).expect("Failed to encode order"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::Decode!(bytes.as_ref(), Self).expect("Failed to decode order")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Config {
+    pub admin: Principal,
+    pub nft_canister: Principal,
+    pub relayers: Vec<Principal>,
+}
+
+impl Storable for Config {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(candid::Encode!(self).expect("Failed to encode config"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::Decode!(bytes.as_ref(), Self).expect("Failed to decode config")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct InitArgs {
+    pub nft_canister: Principal,
+    pub relayers: Vec<Principal>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct CreateOrderRequest {
+    pub order_id: String,
+    pub nft_id: u64,
+    pub buyer: Principal,
+    pub amount: u64,
+}
+
+/// Subset of the NFT canister's `ProductNFT` this canister needs
+/// (see `candid/proofcart_market.did`).
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct NftSummary {
+    pub nft_id: u64,
+    pub serial_number: String,
+    pub owner: Principal,
+}
+
+/// Price recorded on the NFT's ownership history when a sale settles.
+/// Escrow amounts are in lamports.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SalePrice {
+    pub amount: u64,
+    pub currency: String,
+    pub order_id: Option<String>,
+}
+
+thread_local! {
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+    static CONFIG: RefCell<StableCell<Config, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))),
+            Config {
+                admin: Principal::anonymous(),
+                nft_canister: Principal::anonymous(),
+                relayers: vec![],
+            },
+        ).expect("Failed to initialize config")
+    );
+
+    static ORDERS: RefCell<StableBTreeMap<String, Order, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))),
+        )
+    );
+
+    // Order ids with a `create_order` in flight (awaiting the NFT canister)
+    static PENDING_ORDERS: RefCell<std::collections::BTreeSet<String>> = RefCell::new(Default::default());
+}
+
+#[init]
+fn init(args: InitArgs) {
+    set_config(Config {
+        admin: caller(),
+        nft_canister: args.nft_canister,
+        relayers: args.relayers,
+    });
+}
+
+fn config() -> Config {
+    CONFIG.with(|c| c.borrow().get().clone())
+}
+
+fn set_config(config: Config) {
+    CONFIG.with(|c| {
+        c.borrow_mut().set(config).expect("Failed to persist config");
+    });
+}
+
+fn require_admin() -> Result<(), String> {
+    if config().admin == caller() {
+        Ok(())
+    } else {
+        Err("Only admin can perform this action".to_string())
+    }
+}
+
+fn require_relayer() -> Result<(), String> {
+    let config = config();
+    let caller = caller();
+    if config.admin == caller || config.relayers.contains(&caller) {
+        Ok(())
+    } else {
+        Err("Only a settlement relayer can report escrow outcomes".to_string())
+    }
+}
+
+fn get_order_inner(order_id: &str) -> Result<Order, String> {
+    ORDERS.with(|orders| {
+        orders.borrow().get(&order_id.to_string())
+            .ok_or_else(|| format!("Order {} not found", order_id))
+    })
+}
+
+fn save_order(mut order: Order) -> Order {
+    order.updated_at = ic_cdk::api::time();
+    ORDERS.with(|orders| {
+        orders.borrow_mut().insert(order.order_id.clone(), order.clone());
+    });
+    order
+}
+
+async fn call_nft<A, R>(method: &str, args: A) -> Result<R, String>
+where
+    A: candid::utils::ArgumentEncoder,
+    R: for<'a> Deserialize<'a> + CandidType,
+{
+    let (result,): (Result<R, String>,) = ic_cdk::call(config().nft_canister, method, args)
+        .await
+        .map_err(|(code, msg)| format!("NFT canister call {} failed: {:?} {}", method, code, msg))?;
+    result
+}
+
+/// Seller: open an order for an NFT and lock it for the Solana escrow
+#[update]
+async fn create_order(request: CreateOrderRequest) -> Result<Order, String> {
+    let seller = caller();
+    if request.order_id.is_empty() || request.order_id.len() > MAX_ORDER_ID_LEN {
+        return Err(format!("Order id must be 1-{} bytes", MAX_ORDER_ID_LEN));
+    }
+    if request.buyer == Principal::anonymous() || request.buyer == seller {
+        return Err("Invalid buyer".to_string());
+    }
+    if get_order_inner(&request.order_id).is_ok() {
+        return Err(format!("Order {} already exists", request.order_id));
+    }
+
+    // Reserve the order id while the NFT canister calls are in flight
+    let reserved = PENDING_ORDERS.with(|p| p.borrow_mut().insert(request.order_id.clone()));
+    if !reserved {
+        return Err(format!("Order {} is already being created", request.order_id));
+    }
+
+    let result: Result<(), String> = async {
+        let nft: NftSummary = call_nft("get_nft", (request.nft_id,)).await?;
+        if nft.owner != seller {
+            return Err("Only the NFT owner can open an order for it".to_string());
+        }
+        let _: NftSummary = call_nft("lock_for_sale", (request.nft_id, request.order_id.clone())).await?;
+        Ok(())
+    }
+    .await;
+
+    PENDING_ORDERS.with(|p| p.borrow_mut().remove(&request.order_id));
+    result?;
+
+    let now = ic_cdk::api::time();
+    Ok(save_order(Order {
+        order_id: request.order_id,
+        nft_id: request.nft_id,
+        seller,
+        buyer: request.buyer,
+        amount: request.amount,
+        status: OrderStatus::Locked,
+        outcome: None,
+        created_at: now,
+        updated_at: now,
+        last_error: None,
+    }))
+}
+
+async fn settle(mut order: Order) -> Result<Order, String> {
+    let outcome = order.outcome.clone()
+        .ok_or_else(|| "No escrow outcome recorded".to_string())?;
+
+    order.status = OrderStatus::Settling;
+    order = save_order(order);
+
+    let result: Result<NftSummary, String> = match outcome {
+        EscrowOutcome::Released => {
+            call_nft(
+                "transfer_from",
+                (
+                    order.nft_id,
+                    order.seller,
+                    order.buyer,
+                    order.order_id.clone(),
+                    Some(SalePrice {
+                        amount: order.amount,
+                        currency: "SOL".to_string(),
+                        order_id: Some(order.order_id.clone()),
+                    }),
+                ),
+            )
+            .await
+        }
+        EscrowOutcome::Refunded => call_nft("unlock", (order.nft_id,)).await,
+    };
+
+    match result {
+        Ok(_) => {
+            order.status = match outcome {
+                EscrowOutcome::Released => OrderStatus::Settled,
+                EscrowOutcome::Refunded => OrderStatus::Refunded,
+            };
+            order.last_error = None;
+        }
+        Err(e) => {
+            order.status = OrderStatus::Failed;
+            order.last_error = Some(e);
+        }
+    }
+
+    Ok(save_order(order))
+}
+
+/// Relayer: report the final state of the Solana escrow for an order
+#[update]
+async fn report_escrow_outcome(order_id: String, outcome: EscrowOutcome) -> Result<Order, String> {
+    require_relayer()?;
+    let mut order = get_order_inner(&order_id)?;
+    if order.status != OrderStatus::Locked {
+        return Err(format!("Order {} is {:?}, expected Locked", order_id, order.status));
+    }
+    order.outcome = Some(outcome);
+    settle(order).await
+}
+
+/// Relayer: retry a failed settlement
+#[update]
+async fn retry_settlement(order_id: String) -> Result<Order, String> {
+    require_relayer()?;
+    let order = get_order_inner(&order_id)?;
+    if order.status != OrderStatus::Failed {
+        return Err(format!("Order {} is {:?}, expected Failed", order_id, order.status));
+    }
+    settle(order).await
+}
+
+/// Get an order by id
+#[query]
+fn get_order(order_id: String) -> Result<Order, String> {
+    get_order_inner(&order_id)
+}
+
+/// List orders where the principal is buyer or seller
+#[query]
+fn list_orders(participant: Principal, offset: u64, limit: u64) -> Vec<Order> {
+    ORDERS.with(|orders| {
+        orders.borrow()
+            .iter()
+            .map(|(_, order)| order)
+            .filter(|order| order.buyer == participant || order.seller == participant)
+            .skip(offset as usize)
+            .take(limit.min(100) as usize)
+            .collect()
+    })
+}
+
+/// Admin: register or remove a settlement relayer
+#[update]
+fn set_relayer(relayer: Principal, enabled: bool) -> Result<(), String> {
+    require_admin()?;
+    let mut config = config();
+    config.relayers.retain(|r| *r != relayer);
+    if enabled {
+        config.relayers.push(relayer);
+    }
+    set_config(config);
+    Ok(())
+}
+
+/// Current configuration
+#[query]
+fn get_config() -> Config {
+    config()
+}
+
+ic_cdk::export_candid!();
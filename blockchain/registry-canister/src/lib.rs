@@ -0,0 +1,353 @@
+use candid::{CandidType, Encode, Principal};
+use ic_cdk::api::management_canister::main::{
+    create_canister, install_code, CanisterInstallMode, CanisterSettings, CreateCanisterArgument,
+    InstallCodeArgument,
+};
+use ic_cdk::caller;
+use ic_cdk_macros::{init, query, update};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{DefaultMemoryImpl, StableCell, Storable};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+const MAX_SHARDS: u32 = 256;
+/// Serials per `batch_verify` call, across all shards.
+const MAX_BATCH_VERIFY: usize = 1000;
+/// Serials per call to one shard (its `batch_verify_nfts` limit).
+const SHARD_BATCH: usize = 100;
+/// Characters dropped from serials; must match the NFT canister's `serials`.
+const SEPARATORS: &[char] = &['-', '_', ' ', '.', '/', ':'];
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Shard {
+    pub index: u32,
+    pub canister_id: Principal,
+    /// Code installed, shard index assigned and SuperAdmin handover proposed.
+    pub ready: bool,
+    pub created_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Config {
+    pub admin: Principal,
+    /// Fixed at init: changing it would move serials between shards.
+    pub shard_count: u32,
+    pub shards: Vec<Shard>,
+}
+
+impl Storable for Config {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(candid::Encode!(self).expect("Failed to encode config"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::Decode!(bytes.as_ref(), Self).expect("Failed to decode config")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct InitArgs {
+    pub shard_count: u32,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy)]
+struct ShardAssignment {
+    index: u32,
+    count: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerificationLevel {
+    SelfAttested,
+    ManufacturerVerified,
+    ThirdPartyAudited,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ProductMetadata {
+    pub product_name: String,
+    pub manufacturer: String,
+    pub category: String,
+    pub manufacture_date: String,
+}
+
+/// Subset of the NFT canister's `ProductNFT` the registry forwards
+/// (see `candid/proofcart_shard.did`).
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ProductSummary {
+    pub nft_id: u64,
+    pub serial_number: String,
+    pub owner: Principal,
+    pub metadata: ProductMetadata,
+    pub minted_at: u64,
+    pub verification_level: VerificationLevel,
+    pub revoked: bool,
+}
+
+/// A product together with the shard canister that holds it.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ShardedProduct {
+    pub shard: Principal,
+    pub product: ProductSummary,
+}
+
+#[derive(CandidType, Deserialize)]
+struct ShardBatchVerification {
+    results: Vec<(String, Option<ProductSummary>)>,
+}
+
+thread_local! {
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+    static CONFIG: RefCell<StableCell<Config, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))),
+            Config { admin: Principal::anonymous(), shard_count: 0, shards: vec![] },
+        ).expect("Failed to initialize config")
+    );
+
+    // Wasm module installed into new shards
+    static SHARD_WASM: RefCell<StableCell<Vec<u8>, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))),
+            Vec::new(),
+        ).expect("Failed to initialize shard wasm")
+    );
+
+    // Set while a `spawn_shards` run is awaiting the management canister
+    static SPAWNING: RefCell<bool> = RefCell::new(false);
+}
+
+#[init]
+fn init(args: InitArgs) {
+    if args.shard_count == 0 || args.shard_count > MAX_SHARDS {
+        ic_cdk::trap(&format!("shard_count must be 1-{}", MAX_SHARDS));
+    }
+    set_config(Config { admin: caller(), shard_count: args.shard_count, shards: vec![] });
+}
+
+fn config() -> Config {
+    CONFIG.with(|c| c.borrow().get().clone())
+}
+
+fn set_config(config: Config) {
+    CONFIG.with(|c| {
+        c.borrow_mut().set(config).expect("Failed to persist config");
+    });
+}
+
+fn require_admin() -> Result<(), String> {
+    if config().admin == caller() {
+        Ok(())
+    } else {
+        Err("Only admin can perform this action".to_string())
+    }
+}
+
+/// Canonical serial, identical to the NFT canister's `serials::normalize`.
+fn normalize(serial_number: &str) -> String {
+    serial_number
+        .trim()
+        .chars()
+        .filter(|c| !SEPARATORS.contains(c))
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// Home shard of a normalized serial, identical to the NFT canister's
+/// `sharding::shard_of`.
+fn shard_of(serial_number: &str, count: u32) -> u32 {
+    let digest = Sha256::digest(serial_number.as_bytes());
+    let prefix: [u8; 8] = digest[..8].try_into().expect("SHA-256 digests are 32 bytes");
+    (u64::from_be_bytes(prefix) % u64::from(count)) as u32
+}
+
+/// The ready shard canister holding `serial_number`.
+fn route(config: &Config, serial_number: &str) -> Result<Principal, String> {
+    let index = shard_of(&normalize(serial_number), config.shard_count);
+    config
+        .shards
+        .iter()
+        .find(|shard| shard.index == index && shard.ready)
+        .map(|shard| shard.canister_id)
+        .ok_or_else(|| format!("Shard {} is not deployed yet", index))
+}
+
+async fn call_shard<A, R>(shard: Principal, method: &str, args: A) -> Result<R, String>
+where
+    A: candid::utils::ArgumentEncoder,
+    R: for<'a> Deserialize<'a> + CandidType,
+{
+    let (result,): (Result<R, String>,) = ic_cdk::call(shard, method, args)
+        .await
+        .map_err(|(code, msg)| format!("Shard call {} failed: {:?} {}", method, code, msg))?;
+    result
+}
+
+/// Admin: set the NFT canister wasm installed into new shards
+#[update]
+fn set_shard_wasm(wasm_module: Vec<u8>) -> Result<(), String> {
+    require_admin()?;
+    if wasm_module.is_empty() {
+        return Err("Wasm module is empty".to_string());
+    }
+    SHARD_WASM.with(|w| {
+        w.borrow_mut()
+            .set(wasm_module)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to persist shard wasm: {:?}", e))
+    })
+}
+
+/// Create, install and configure one shard. The canister is recorded as soon
+/// as it exists so a failed install is retried on it rather than leaked.
+async fn deploy_shard(index: u32, cycles: u128, wasm_module: &[u8]) -> Result<Shard, String> {
+    let config = config();
+    let existing = config.shards.iter().find(|shard| shard.index == index).cloned();
+    let (shard, mode) = match existing {
+        Some(shard) if shard.ready => return Ok(shard),
+        // Never reached `ready`, so nothing was routed to it; start over.
+        Some(shard) => (shard, CanisterInstallMode::Reinstall),
+        None => {
+            let settings = CanisterSettings {
+                controllers: Some(vec![ic_cdk::id(), config.admin]),
+                ..Default::default()
+            };
+            let (record,) = create_canister(CreateCanisterArgument { settings: Some(settings) }, cycles)
+                .await
+                .map_err(|(code, msg)| format!("create_canister failed: {:?} {}", code, msg))?;
+            let shard = Shard {
+                index,
+                canister_id: record.canister_id,
+                ready: false,
+                created_at: ic_cdk::api::time(),
+            };
+            save_shard(shard.clone());
+            (shard, CanisterInstallMode::Install)
+        }
+    };
+
+    install_code(InstallCodeArgument {
+        mode,
+        canister_id: shard.canister_id,
+        wasm_module: wasm_module.to_vec(),
+        arg: Encode!().expect("Failed to encode empty init args"),
+    })
+    .await
+    .map_err(|(code, msg)| format!("install_code on shard {} failed: {:?} {}", index, code, msg))?;
+
+    // The registry installed the code, so it is the shard's SuperAdmin until
+    // the admin accepts the handover.
+    let assignment = ShardAssignment { index, count: config.shard_count };
+    call_shard::<_, ()>(shard.canister_id, "set_shard_assignment", (Some(assignment),)).await?;
+    call_shard::<_, ()>(shard.canister_id, "propose_super_admin", (Some(config.admin),)).await?;
+
+    let shard = Shard { ready: true, ..shard };
+    save_shard(shard.clone());
+    Ok(shard)
+}
+
+fn save_shard(shard: Shard) {
+    let mut config = config();
+    config.shards.retain(|s| s.index != shard.index);
+    config.shards.push(shard);
+    config.shards.sort_by_key(|s| s.index);
+    set_config(config);
+}
+
+/// Admin: create every missing shard, funding each with `cycles_per_shard`.
+/// Safe to call again after a failure; ready shards are left alone. Only one
+/// run at a time, so two runs cannot create a canister for the same index.
+#[update]
+async fn spawn_shards(cycles_per_shard: u128) -> Result<Vec<Shard>, String> {
+    require_admin()?;
+    let wasm_module = SHARD_WASM.with(|w| w.borrow().get().clone());
+    if wasm_module.is_empty() {
+        return Err("Upload the shard wasm with set_shard_wasm first".to_string());
+    }
+    if SPAWNING.with(|s| s.replace(true)) {
+        return Err("spawn_shards is already running".to_string());
+    }
+
+    let result = async {
+        for index in 0..config().shard_count {
+            deploy_shard(index, cycles_per_shard, &wasm_module).await?;
+        }
+        Ok(())
+    }
+    .await;
+
+    SPAWNING.with(|s| *s.borrow_mut() = false);
+    result.map(|()| config().shards)
+}
+
+/// Shard canister that holds (or will mint) a serial
+#[query]
+fn shard_for_serial(serial_number: String) -> Result<Principal, String> {
+    route(&config(), &serial_number)
+}
+
+/// All shards and their deployment state
+#[query]
+fn list_shards() -> Vec<Shard> {
+    config().shards
+}
+
+/// Verify a product on its home shard
+#[query(composite = true)]
+async fn verify_product(serial_number: String) -> Result<ShardedProduct, String> {
+    let shard = route(&config(), &serial_number)?;
+    let product: ProductSummary = call_shard(shard, "verify_product", (serial_number,)).await?;
+    Ok(ShardedProduct { shard, product })
+}
+
+/// Verify serials spread over any number of shards, in request order
+#[query(composite = true)]
+async fn batch_verify(serial_numbers: Vec<String>) -> Result<Vec<(String, Option<ShardedProduct>)>, String> {
+    if serial_numbers.is_empty() || serial_numbers.len() > MAX_BATCH_VERIFY {
+        return Err(format!("Batch must contain 1-{} serial numbers", MAX_BATCH_VERIFY));
+    }
+    let config = config();
+
+    let mut by_shard: BTreeMap<Principal, Vec<String>> = BTreeMap::new();
+    for serial in &serial_numbers {
+        by_shard.entry(route(&config, serial)?).or_default().push(serial.clone());
+    }
+
+    let mut found: BTreeMap<String, ShardedProduct> = BTreeMap::new();
+    for (shard, serials) in by_shard {
+        for chunk in serials.chunks(SHARD_BATCH) {
+            let batch: ShardBatchVerification = call_shard(shard, "batch_verify_nfts", (chunk.to_vec(),)).await?;
+            for (serial, product) in batch.results {
+                if let Some(product) = product {
+                    found.insert(serial, ShardedProduct { shard, product });
+                }
+            }
+        }
+    }
+
+    Ok(serial_numbers
+        .into_iter()
+        .map(|serial| {
+            let product = found.get(&serial).cloned();
+            (serial, product)
+        })
+        .collect())
+}
+
+/// Current configuration
+#[query]
+fn get_config() -> Config {
+    config()
+}
+
+ic_cdk::export_candid!();
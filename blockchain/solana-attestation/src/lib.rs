@@ -0,0 +1,250 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar::instructions::{self as instructions_sysvar, load_current_index_checked, load_instruction_at_checked};
+
+declare_id!("PrfCartAttest111111111111111111111111111111");
+
+/// Seed of the program's single `Config` account.
+pub const CONFIG_SEED: &[u8] = b"config";
+/// Seed prefix of an attestation PDA (`["attestation", sha256(serial)]`).
+pub const ATTESTATION_SEED: &[u8] = b"attestation";
+/// Domain separator of the signed message, shared with the NFT canister.
+pub const MESSAGE_DOMAIN: &[u8] = b"proofcart-attestation-v1";
+
+/// Key of a serial's attestation: `sha256` of the normalized serial number,
+/// as the NFT canister stores it.
+pub fn serial_hash(serial_number: &str) -> [u8; 32] {
+    hash(serial_number.as_bytes()).to_bytes()
+}
+
+/// The attestation PDA of a serial.
+pub fn attestation_address(serial_number: &str) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ATTESTATION_SEED, &serial_hash(serial_number)], &ID)
+}
+
+/// Bytes the attester signs: domain, serial hash, verified flag, owner hash
+/// and the canister's issue time (nanoseconds, little-endian).
+pub fn attestation_message(serial_hash: &[u8; 32], verified: bool, owner_hash: &[u8; 32], issued_at: u64) -> Vec<u8> {
+    let mut message = Vec::with_capacity(MESSAGE_DOMAIN.len() + 32 + 1 + 32 + 8);
+    message.extend_from_slice(MESSAGE_DOMAIN);
+    message.extend_from_slice(serial_hash);
+    message.push(verified as u8);
+    message.extend_from_slice(owner_hash);
+    message.extend_from_slice(&issued_at.to_le_bytes());
+    message
+}
+
+#[program]
+pub mod proofcart_attestation {
+    use super::*;
+
+    /// Record the key whose signatures are accepted. Only the program's
+    /// upgrade authority can call this, once, after deployment.
+    pub fn initialize_config(ctx: Context<InitializeConfig>, attester: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.attester = attester;
+        config.bump = ctx.bumps.config;
+
+        msg!("Attester set to: {}", attester);
+
+        Ok(())
+    }
+
+    /// Replace the attester key, e.g. after the canister's key is rotated.
+    /// Attestations already posted stay valid.
+    pub fn set_attester(ctx: Context<SetAttester>, attester: Pubkey) -> Result<()> {
+        ctx.accounts.config.attester = attester;
+
+        msg!("Attester changed to: {}", attester);
+
+        Ok(())
+    }
+
+    /// Store an attestation signed by the attester. The signature is checked
+    /// by an Ed25519 program instruction placed just before this one in the
+    /// same transaction. Anyone can relay; an attestation only replaces one
+    /// issued earlier.
+    pub fn post_attestation(
+        ctx: Context<PostAttestation>,
+        serial_hash: [u8; 32],
+        verified: bool,
+        owner_hash: [u8; 32],
+        issued_at: u64,
+    ) -> Result<()> {
+        let current = load_current_index_checked(&ctx.accounts.instructions)? as usize;
+        require!(current > 0, AttestationError::MissingSignature);
+        let signature = load_instruction_at_checked(current - 1, &ctx.accounts.instructions)?;
+        let message = attestation_message(&serial_hash, verified, &owner_hash, issued_at);
+        check_signature(&signature, &ctx.accounts.config.attester, &message)?;
+
+        let attestation = &mut ctx.accounts.attestation;
+        // A fresh account has `issued_at == 0`.
+        require!(issued_at > attestation.issued_at, AttestationError::StaleAttestation);
+
+        attestation.serial_hash = serial_hash;
+        attestation.verified = verified;
+        attestation.owner_hash = owner_hash;
+        attestation.issued_at = issued_at;
+        attestation.posted_at = Clock::get()?.unix_timestamp;
+        attestation.bump = ctx.bumps.attestation;
+
+        msg!("Attestation posted, verified: {}", verified);
+
+        Ok(())
+    }
+}
+
+/// Check that `instruction` makes the Ed25519 program verify one signature by
+/// `signer` over exactly `message`. The runtime has already checked the
+/// signature itself; what remains is that it is the right key and message,
+/// carried in that instruction's own data.
+fn check_signature(instruction: &Instruction, signer: &Pubkey, message: &[u8]) -> Result<()> {
+    require_keys_eq!(instruction.program_id, ed25519_program::ID, AttestationError::MissingSignature);
+
+    let data = &instruction.data;
+    require!(data.len() >= 16 && data[0] == 1, AttestationError::MissingSignature);
+    let read = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let (key_offset, message_offset, message_size) = (read(6), read(10), read(12));
+    // Signature, key and message must all sit in this instruction.
+    require!(
+        [read(4), read(8), read(14)].iter().all(|index| *index == u16::MAX),
+        AttestationError::MissingSignature
+    );
+
+    let slice = |offset: u16, len: usize| data.get(offset as usize..offset as usize + len);
+    require!(slice(key_offset, 32) == Some(signer.as_ref()), AttestationError::WrongAttester);
+    require!(
+        message_size as usize == message.len() && slice(message_offset, message.len()) == Some(message),
+        AttestationError::MessageMismatch
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Config::LEN,
+        seeds = [CONFIG_SEED],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The program's data account, which names its upgrade authority
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = anchor_lang::solana_program::bpf_loader_upgradeable::ID,
+        constraint = program_data.upgrade_authority_address == Some(authority.key()) @ AttestationError::UnauthorizedAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetAttester<'info> {
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+
+    pub authority: Signer<'info>,
+
+    /// The program's data account, which names its upgrade authority
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = anchor_lang::solana_program::bpf_loader_upgradeable::ID,
+        constraint = program_data.upgrade_authority_address == Some(authority.key()) @ AttestationError::UnauthorizedAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+}
+
+#[derive(Accounts)]
+#[instruction(serial_hash: [u8; 32])]
+pub struct PostAttestation<'info> {
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = 8 + Attestation::LEN,
+        seeds = [ATTESTATION_SEED, serial_hash.as_ref()],
+        bump
+    )]
+    pub attestation: Account<'info, Attestation>,
+
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: The instructions sysvar, to find the signature instruction
+    #[account(address = instructions_sysvar::ID)]
+    pub instructions: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// The latest attestation of one serial, at `attestation_address(serial)`.
+/// Other programs take it as `Account<'info, Attestation>` (with the `cpi`
+/// feature), which checks this program owns it, and constrain its seeds.
+#[account]
+pub struct Attestation {
+    pub serial_hash: [u8; 32],
+    /// The token is at least manufacturer-verified, not revoked, not reported
+    /// stolen or lost and not recalled.
+    pub verified: bool,
+    /// `sha256` of the owner's principal bytes.
+    pub owner_hash: [u8; 32],
+    /// When the canister signed it, in nanoseconds since the epoch.
+    pub issued_at: u64,
+    /// When it was posted, in Solana unix time.
+    pub posted_at: i64,
+    pub bump: u8,
+}
+
+impl Attestation {
+    pub const LEN: usize = 32 + 1 + 32 + 8 + 8 + 1;
+}
+
+/// Program-wide settings, at the `CONFIG_SEED` PDA.
+#[account]
+pub struct Config {
+    /// The NFT canister's threshold Ed25519 key
+    pub attester: Pubkey,
+    pub bump: u8,
+}
+
+impl Config {
+    pub const LEN: usize = 32 + 1;
+}
+
+#[error_code]
+pub enum AttestationError {
+    #[msg("Only the program's upgrade authority can perform this action")]
+    UnauthorizedAuthority,
+
+    #[msg("The previous instruction must verify one Ed25519 signature")]
+    MissingSignature,
+
+    #[msg("The signature is not by the configured attester")]
+    WrongAttester,
+
+    #[msg("The signed message does not match the attestation")]
+    MessageMismatch,
+
+    #[msg("A newer attestation is already posted")]
+    StaleAttestation,
+}
@@ -0,0 +1,261 @@
+//! Posting attestations: configuration, signature checks and replay. The
+//! program runs in-process with `solana-program-test`; the Ed25519
+//! precompile runs as on a validator.
+
+use anchor_lang::solana_program::account_info::AccountInfo;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use proofcart_attestation::{
+    accounts, attestation_address, attestation_message, instruction, serial_hash, Attestation, AttestationError,
+    CONFIG_SEED,
+};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::account::Account;
+use solana_sdk::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+const PROGRAM_ID: Pubkey = proofcart_attestation::ID;
+const SOL: u64 = 1_000_000_000;
+const SERIAL: &str = "ABC1234";
+const OWNER: [u8; 32] = [7; 32];
+
+// See `solana-escrow/tests/common`: `processor!` cannot express the
+// lifetimes of Anchor's `entry`.
+fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    proofcart_attestation::entry(program_id, accounts, data)
+}
+
+fn config_address() -> Pubkey {
+    Pubkey::find_program_address(&[CONFIG_SEED], &PROGRAM_ID).0
+}
+
+fn program_data_address() -> Pubkey {
+    Pubkey::find_program_address(&[PROGRAM_ID.as_ref()], &bpf_loader_upgradeable::id()).0
+}
+
+/// An Ed25519 program instruction verifying `signer`'s signature over
+/// `message`, with the key, signature and message in its own data.
+fn ed25519_instruction(signer: &Keypair, message: &[u8]) -> Instruction {
+    let signature = signer.sign_message(message);
+    let mut data = vec![1, 0];
+    // Offsets: signature, key, message; each in this instruction (u16::MAX).
+    for value in [48, u16::MAX, 16, u16::MAX, 112, message.len() as u16, u16::MAX] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(signer.pubkey().as_ref());
+    data.extend_from_slice(signature.as_ref());
+    data.extend_from_slice(message);
+    Instruction { program_id: solana_sdk::ed25519_program::id(), accounts: vec![], data }
+}
+
+struct Harness {
+    context: ProgramTestContext,
+    upgrade_authority: Keypair,
+    attester: Keypair,
+}
+
+impl Harness {
+    /// A bank with the program deployed and the attester configured.
+    async fn configured() -> Self {
+        let upgrade_authority = Keypair::new();
+        let attester = Keypair::new();
+
+        let mut test = ProgramTest::new("proofcart_attestation", PROGRAM_ID, processor!(process_instruction));
+        test.prefer_bpf(false);
+        let state = UpgradeableLoaderState::ProgramData {
+            slot: 0,
+            upgrade_authority_address: Some(upgrade_authority.pubkey()),
+        };
+        test.add_account(
+            program_data_address(),
+            Account {
+                lamports: SOL,
+                data: bincode::serialize(&state).unwrap(),
+                owner: bpf_loader_upgradeable::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        test.add_account(
+            upgrade_authority.pubkey(),
+            Account::new(10 * SOL, 0, &solana_sdk::system_program::id()),
+        );
+
+        let mut harness = Self { context: test.start_with_context().await, upgrade_authority, attester };
+        let initialize = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: accounts::InitializeConfig {
+                config: config_address(),
+                authority: harness.upgrade_authority.pubkey(),
+                program_data: program_data_address(),
+                system_program: solana_sdk::system_program::id(),
+            }
+            .to_account_metas(None),
+            data: instruction::InitializeConfig { attester: harness.attester.pubkey() }.data(),
+        };
+        let authority = harness.upgrade_authority.insecure_clone();
+        harness.send(&[initialize], &[&authority]).await.unwrap();
+        harness
+    }
+
+    async fn send(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<(), TransactionError> {
+        let blockhash = self.context.get_new_latest_blockhash().await.unwrap();
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction =
+            Transaction::new_signed_with_payer(instructions, Some(&self.context.payer.pubkey()), &all_signers, blockhash);
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|e| e.unwrap())
+    }
+
+    fn post_instruction(&self, verified: bool, issued_at: u64) -> Instruction {
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: accounts::PostAttestation {
+                attestation: attestation_address(SERIAL).0,
+                relayer: self.context.payer.pubkey(),
+                config: config_address(),
+                instructions: solana_sdk::sysvar::instructions::id(),
+                system_program: solana_sdk::system_program::id(),
+            }
+            .to_account_metas(None),
+            data: instruction::PostAttestation {
+                serial_hash: serial_hash(SERIAL),
+                verified,
+                owner_hash: OWNER,
+                issued_at,
+            }
+            .data(),
+        }
+    }
+
+    /// Post an attestation signed by `signer` over `signed`, claiming `posted`.
+    async fn post_signed(
+        &mut self,
+        signer: &Keypair,
+        signed: (bool, u64),
+        posted: (bool, u64),
+    ) -> Result<(), TransactionError> {
+        let message = attestation_message(&serial_hash(SERIAL), signed.0, &OWNER, signed.1);
+        let instructions = [ed25519_instruction(signer, &message), self.post_instruction(posted.0, posted.1)];
+        self.send(&instructions, &[]).await
+    }
+
+    async fn post(&mut self, verified: bool, issued_at: u64) -> Result<(), TransactionError> {
+        let attester = self.attester.insecure_clone();
+        self.post_signed(&attester, (verified, issued_at), (verified, issued_at)).await
+    }
+
+    async fn attestation(&mut self) -> Option<Attestation> {
+        let account = self
+            .context
+            .banks_client
+            .get_account(attestation_address(SERIAL).0)
+            .await
+            .unwrap()?;
+        Some(Attestation::try_deserialize(&mut account.data.as_slice()).unwrap())
+    }
+}
+
+fn program_error(index: u8, error: AttestationError) -> TransactionError {
+    TransactionError::InstructionError(index, InstructionError::Custom(error.into()))
+}
+
+#[tokio::test]
+async fn posts_a_signed_attestation() {
+    let mut harness = Harness::configured().await;
+
+    harness.post(true, 1_000).await.unwrap();
+
+    let attestation = harness.attestation().await.unwrap();
+    assert_eq!(attestation.serial_hash, serial_hash(SERIAL));
+    assert!(attestation.verified);
+    assert_eq!(attestation.owner_hash, OWNER);
+    assert_eq!(attestation.issued_at, 1_000);
+    assert_eq!(attestation.bump, attestation_address(SERIAL).1);
+}
+
+#[tokio::test]
+async fn newer_attestations_replace_older_ones() {
+    let mut harness = Harness::configured().await;
+    harness.post(true, 1_000).await.unwrap();
+
+    harness.post(false, 2_000).await.unwrap();
+    let attestation = harness.attestation().await.unwrap();
+    assert!(!attestation.verified);
+    assert_eq!(attestation.issued_at, 2_000);
+
+    // Replaying the older, verified attestation cannot undo a revocation.
+    assert_eq!(harness.post(true, 1_000).await, Err(program_error(1, AttestationError::StaleAttestation)));
+    assert_eq!(harness.post(false, 2_000).await, Err(program_error(1, AttestationError::StaleAttestation)));
+    assert!(!harness.attestation().await.unwrap().verified);
+}
+
+#[tokio::test]
+async fn rejects_other_signers() {
+    let mut harness = Harness::configured().await;
+    let stranger = Keypair::new();
+
+    assert_eq!(
+        harness.post_signed(&stranger, (true, 1_000), (true, 1_000)).await,
+        Err(program_error(1, AttestationError::WrongAttester))
+    );
+    assert!(harness.attestation().await.is_none());
+}
+
+#[tokio::test]
+async fn rejects_a_message_other_than_the_one_signed() {
+    let mut harness = Harness::configured().await;
+    let attester = harness.attester.insecure_clone();
+
+    assert_eq!(
+        harness.post_signed(&attester, (false, 1_000), (true, 1_000)).await,
+        Err(program_error(1, AttestationError::MessageMismatch))
+    );
+    assert_eq!(
+        harness.post_signed(&attester, (true, 1_000), (true, 2_000)).await,
+        Err(program_error(1, AttestationError::MessageMismatch))
+    );
+}
+
+#[tokio::test]
+async fn requires_the_signature_instruction() {
+    let mut harness = Harness::configured().await;
+
+    let post = harness.post_instruction(true, 1_000);
+    assert_eq!(harness.send(&[post], &[]).await, Err(program_error(0, AttestationError::MissingSignature)));
+}
+
+#[tokio::test]
+async fn only_the_upgrade_authority_changes_the_attester() {
+    let mut harness = Harness::configured().await;
+    let stranger = Keypair::new();
+    let rotated = Keypair::new();
+    let set_attester = |authority: &Keypair| Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts::SetAttester {
+            config: config_address(),
+            authority: authority.pubkey(),
+            program_data: program_data_address(),
+        }
+        .to_account_metas(None),
+        data: instruction::SetAttester { attester: rotated.pubkey() }.data(),
+    };
+
+    assert_eq!(
+        harness.send(&[set_attester(&stranger)], &[&stranger]).await,
+        Err(program_error(0, AttestationError::UnauthorizedAuthority))
+    );
+
+    let authority = harness.upgrade_authority.insecure_clone();
+    harness.send(&[set_attester(&authority)], &[&authority]).await.unwrap();
+    assert_eq!(harness.post(true, 1_000).await, Err(program_error(1, AttestationError::WrongAttester)));
+    harness.post_signed(&rotated, (true, 1_000), (true, 1_000)).await.unwrap();
+}
@@ -0,0 +1,26 @@
+//! The off-chain decoder the indexer uses must agree with the program on
+//! which instruction the data encodes and, for `create_escrow`, on its
+//! arguments.
+
+#![no_main]
+
+use anchor_lang::AnchorDeserialize;
+use libfuzzer_sys::fuzz_target;
+use proofcart_escrow::instruction::CreateEscrow;
+use proofcart_solana_client::instructions::{self, discriminator, EscrowInstruction};
+
+fuzz_target!(|data: &[u8]| {
+    let decoded = instructions::decode(data);
+    if data.len() < 8 || data[..8] != discriminator("create_escrow") {
+        return;
+    }
+
+    let program = CreateEscrow::deserialize(&mut &data[8..]);
+    match (decoded, program) {
+        (Ok(EscrowInstruction::CreateEscrow { order_id, amount, bump }), Ok(args)) => {
+            assert_eq!((order_id, amount, bump), (args.order_id, args.amount, args.bump));
+        }
+        (Err(_), Err(_)) => {}
+        (client, program) => panic!("client decoded {:?}, program {:?}", client.is_ok(), program.is_ok()),
+    }
+});
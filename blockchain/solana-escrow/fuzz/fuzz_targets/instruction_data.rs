@@ -0,0 +1,12 @@
+//! Arbitrary instruction data through the program's entrypoint. Anchor
+//! dispatches on the discriminator and deserializes the arguments before it
+//! looks at the accounts, so with no accounts every input must fail cleanly.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let result = proofcart_escrow::entry(&proofcart_escrow::ID, &[], data);
+    assert!(result.is_err(), "instruction succeeded without accounts");
+});
@@ -1,35 +1,106 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token::{self, Token, TokenAccount, Transfer};
 
-declare_id!("Your Program ID Here - Will be generated on deployment");
+declare_id!("PrfCartEscrow111111111111111111111111111111");
+
+/// Seed of the program's single `Config` account.
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// First seed of a `Marketplace` account; the second is its id.
+pub const MARKETPLACE_SEED: &[u8] = b"marketplace";
+
+/// Highest marketplace fee, in basis points of the escrowed amount.
+pub const MAX_FEE_BPS: u16 = 1_000;
+
+/// Most payment mints a marketplace can list.
+pub const MAX_MINTS: usize = 4;
 
 #[program]
 pub mod proofcart_escrow {
     use super::*;
 
-    /// Initialize a new escrow account for an order
+    /// Record the admin allowed to resolve disputes. Only the program's
+    /// upgrade authority can call this, once, after deployment.
+    pub fn initialize_config(ctx: Context<InitializeConfig>, admin: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.admin = admin;
+        config.bump = ctx.bumps.config;
+
+        msg!("Escrow admin set to: {}", admin);
+
+        Ok(())
+    }
+
+    /// Register a partner marketplace with its own dispute admin, fee and
+    /// payment mints. Signed by the platform admin.
+    pub fn create_marketplace(
+        ctx: Context<CreateMarketplace>,
+        marketplace_id: String,
+        settings: MarketplaceSettings,
+    ) -> Result<()> {
+        require!(valid_marketplace_id(&marketplace_id), EscrowError::InvalidMarketplaceId);
+        let marketplace = &mut ctx.accounts.marketplace;
+        marketplace.marketplace_id = marketplace_id;
+        marketplace.bump = ctx.bumps.marketplace;
+        marketplace.apply(settings)?;
+
+        msg!("Marketplace created: {}", marketplace.marketplace_id);
+
+        Ok(())
+    }
+
+    /// Change a marketplace's admin, fee or mints. Signed by the platform
+    /// admin. Open escrows keep the fee they were created with.
+    pub fn update_marketplace(ctx: Context<UpdateMarketplace>, settings: MarketplaceSettings) -> Result<()> {
+        let marketplace = &mut ctx.accounts.marketplace;
+        marketplace.apply(settings)?;
+
+        msg!("Marketplace updated: {}", marketplace.marketplace_id);
+
+        Ok(())
+    }
+
+    /// Initialize a new escrow account for an order and deposit the buyer's funds
     pub fn create_escrow(
         ctx: Context<CreateEscrow>,
         order_id: String,
         amount: u64,
         bump: u8,
     ) -> Result<()> {
-        let escrow = &mut ctx.accounts.escrow;
-        
-        escrow.buyer = ctx.accounts.buyer.key();
-        escrow.seller = ctx.accounts.seller.key();
-        escrow.order_id = order_id;
-        escrow.amount = amount;
-        escrow.status = EscrowStatus::Created;
-        escrow.bump = bump;
-        escrow.created_at = Clock::get()?.unix_timestamp;
-        
-        msg!("Escrow created for order: {}", escrow.order_id);
-        msg!("Amount: {} lamports", amount);
-        msg!("Buyer: {}", escrow.buyer);
-        msg!("Seller: {}", escrow.seller);
-        
-        Ok(())
+        require!(bump == ctx.bumps.escrow, EscrowError::InvalidBump);
+
+        let accounts = ctx.accounts;
+        open_escrow(
+            &mut accounts.escrow,
+            &accounts.buyer,
+            &accounts.seller,
+            &accounts.escrow_account,
+            &accounts.system_program,
+            (order_id, amount, bump),
+            None,
+        )
+    }
+
+    /// `create_escrow` for an order of a partner marketplace. The order id
+    /// only has to be unique within the marketplace.
+    pub fn create_marketplace_escrow(
+        ctx: Context<CreateMarketplaceEscrow>,
+        order_id: String,
+        amount: u64,
+        bump: u8,
+    ) -> Result<()> {
+        require!(bump == ctx.bumps.escrow, EscrowError::InvalidBump);
+
+        let accounts = ctx.accounts;
+        open_escrow(
+            &mut accounts.escrow,
+            &accounts.buyer,
+            &accounts.seller,
+            &accounts.escrow_account,
+            &accounts.system_program,
+            (order_id, amount, bump),
+            Some(&accounts.marketplace),
+        )
     }
 
     /// Confirm delivery and release funds to seller
@@ -48,24 +119,8 @@ pub mod proofcart_escrow {
             EscrowError::InvalidEscrowStatus
         );
         
-        // Transfer funds from escrow to seller
-        let seeds = &[
-            b"escrow".as_ref(),
-            escrow.order_id.as_bytes(),
-            &[escrow.bump],
-        ];
-        let signer = &[&seeds[..]];
-        
-        let cpi_context = CpiContext::new_with_signer(
-            ctx.accounts.system_program.to_account_info(),
-            anchor_lang::system_program::Transfer {
-                from: ctx.accounts.escrow_account.to_account_info(),
-                to: ctx.accounts.seller.to_account_info(),
-            },
-            signer,
-        );
-        
-        anchor_lang::system_program::transfer(cpi_context, escrow.amount)?;
+        // Transfer funds from escrow to seller, less the marketplace's fee
+        release(escrow, &ctx.accounts.seller, &ctx.accounts.marketplace, &ctx.accounts.fee_recipient)?;
         
         // Update escrow status
         escrow.status = EscrowStatus::Released;
@@ -103,6 +158,7 @@ pub mod proofcart_escrow {
     /// Resolve dispute by admin (refund buyer)
     pub fn resolve_refund(ctx: Context<ResolveDispute>) -> Result<()> {
         let escrow = &mut ctx.accounts.escrow;
+        check_admin(escrow, &ctx.accounts.admin, &ctx.accounts.config, &ctx.accounts.marketplace)?;
         
         require!(
             escrow.status == EscrowStatus::Locked,
@@ -110,23 +166,7 @@ pub mod proofcart_escrow {
         );
         
         // Transfer funds from escrow back to buyer
-        let seeds = &[
-            b"escrow".as_ref(),
-            escrow.order_id.as_bytes(),
-            &[escrow.bump],
-        ];
-        let signer = &[&seeds[..]];
-        
-        let cpi_context = CpiContext::new_with_signer(
-            ctx.accounts.system_program.to_account_info(),
-            anchor_lang::system_program::Transfer {
-                from: ctx.accounts.escrow_account.to_account_info(),
-                to: ctx.accounts.buyer.to_account_info(),
-            },
-            signer,
-        );
-        
-        anchor_lang::system_program::transfer(cpi_context, escrow.amount)?;
+        pay_out(&escrow.to_account_info(), &ctx.accounts.buyer.to_account_info(), escrow.amount)?;
         
         escrow.status = EscrowStatus::Refunded;
         escrow.resolved_at = Some(Clock::get()?.unix_timestamp);
@@ -139,30 +179,15 @@ pub mod proofcart_escrow {
     /// Resolve dispute by admin (release to seller)
     pub fn resolve_release(ctx: Context<ResolveDispute>) -> Result<()> {
         let escrow = &mut ctx.accounts.escrow;
+        check_admin(escrow, &ctx.accounts.admin, &ctx.accounts.config, &ctx.accounts.marketplace)?;
         
         require!(
             escrow.status == EscrowStatus::Locked,
             EscrowError::EscrowNotLocked
         );
         
-        // Transfer funds from escrow to seller
-        let seeds = &[
-            b"escrow".as_ref(),
-            escrow.order_id.as_bytes(),
-            &[escrow.bump],
-        ];
-        let signer = &[&seeds[..]];
-        
-        let cpi_context = CpiContext::new_with_signer(
-            ctx.accounts.system_program.to_account_info(),
-            anchor_lang::system_program::Transfer {
-                from: ctx.accounts.escrow_account.to_account_info(),
-                to: ctx.accounts.seller.to_account_info(),
-            },
-            signer,
-        );
-        
-        anchor_lang::system_program::transfer(cpi_context, escrow.amount)?;
+        // Transfer funds from escrow to seller, less the marketplace's fee
+        release(escrow, &ctx.accounts.seller, &ctx.accounts.marketplace, &ctx.accounts.fee_recipient)?;
         
         escrow.status = EscrowStatus::Released;
         escrow.resolved_at = Some(Clock::get()?.unix_timestamp);
@@ -173,6 +198,192 @@ pub mod proofcart_escrow {
     }
 }
 
+/// Move `amount` lamports out of the escrow account. The program owns the
+/// account, so it debits it directly; the System program can only debit
+/// accounts it owns.
+fn pay_out(escrow: &AccountInfo, to: &AccountInfo, amount: u64) -> Result<()> {
+    let remaining = escrow
+        .lamports()
+        .checked_sub(amount)
+        .ok_or(EscrowError::InsufficientFunds)?;
+    let credited = to
+        .lamports()
+        .checked_add(amount)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    **escrow.try_borrow_mut_lamports()? = remaining;
+    **to.try_borrow_mut_lamports()? = credited;
+    Ok(())
+}
+
+/// Fill in a new escrow and move the buyer's deposit into it. Escrows of a
+/// marketplace record it and lock in its current fee.
+fn open_escrow<'info>(
+    escrow: &mut Account<'info, Escrow>,
+    buyer: &Signer<'info>,
+    seller: &AccountInfo<'info>,
+    escrow_account: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    (order_id, amount, bump): (String, u64, u8),
+    marketplace: Option<&Account<'info, Marketplace>>,
+) -> Result<()> {
+    escrow.buyer = buyer.key();
+    escrow.seller = seller.key();
+    escrow.order_id = order_id;
+    escrow.amount = amount;
+    escrow.status = EscrowStatus::Created;
+    escrow.bump = bump;
+    escrow.created_at = Clock::get()?.unix_timestamp;
+    escrow.marketplace = marketplace.map(|marketplace| marketplace.key());
+    escrow.fee_bps = marketplace.map_or(0, |marketplace| marketplace.fee_bps);
+
+    // Funds sit in the escrow account itself, above its rent reserve
+    let cpi_context = CpiContext::new(
+        system_program.to_account_info(),
+        anchor_lang::system_program::Transfer {
+            from: buyer.to_account_info(),
+            to: escrow_account.to_account_info(),
+        },
+    );
+
+    anchor_lang::system_program::transfer(cpi_context, amount)?;
+
+    msg!("Escrow created for order: {}", escrow.order_id);
+    msg!("Amount: {} lamports", amount);
+    msg!("Buyer: {}", escrow.buyer);
+    msg!("Seller: {}", escrow.seller);
+    if let Some(marketplace) = marketplace {
+        msg!("Marketplace: {}", marketplace.marketplace_id);
+    }
+
+    Ok(())
+}
+
+/// The escrow's marketplace, which must have been passed when it has one.
+fn marketplace_of<'a, 'info>(
+    escrow: &Escrow,
+    marketplace: &'a Option<Account<'info, Marketplace>>,
+) -> Result<Option<&'a Account<'info, Marketplace>>> {
+    match escrow.marketplace {
+        None => Ok(None),
+        Some(key) => match marketplace {
+            Some(marketplace) if marketplace.key() == key => Ok(Some(marketplace)),
+            _ => err!(EscrowError::MarketplaceMismatch),
+        },
+    }
+}
+
+/// Resolutions are signed by the escrow's marketplace admin, or by the
+/// platform admin for escrows outside any marketplace.
+fn check_admin(
+    escrow: &Escrow,
+    admin: &Signer,
+    config: &Config,
+    marketplace: &Option<Account<Marketplace>>,
+) -> Result<()> {
+    let expected = match marketplace_of(escrow, marketplace)? {
+        Some(marketplace) => marketplace.admin,
+        None => config.admin,
+    };
+    require_keys_eq!(admin.key(), expected, EscrowError::UnauthorizedAdmin);
+    Ok(())
+}
+
+/// Pay out a released escrow: its marketplace's fee to the fee recipient,
+/// the rest to the seller.
+fn release<'info>(
+    escrow: &Account<'info, Escrow>,
+    seller: &AccountInfo<'info>,
+    marketplace: &Option<Account<'info, Marketplace>>,
+    fee_recipient: &Option<UncheckedAccount<'info>>,
+) -> Result<()> {
+    let fee = escrow.fee();
+    if fee > 0 {
+        let marketplace = marketplace_of(escrow, marketplace)?.ok_or(EscrowError::MarketplaceMismatch)?;
+        let recipient = fee_recipient
+            .as_ref()
+            .filter(|recipient| recipient.key() == marketplace.fee_recipient)
+            .ok_or(EscrowError::RecipientMismatch)?;
+        pay_out(&escrow.to_account_info(), &recipient.to_account_info(), fee)?;
+    }
+    pay_out(&escrow.to_account_info(), seller, escrow.amount - fee)
+}
+
+fn valid_marketplace_id(marketplace_id: &str) -> bool {
+    !marketplace_id.is_empty()
+        && marketplace_id.len() <= 32
+        && marketplace_id.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Config::LEN,
+        seeds = [CONFIG_SEED],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    /// The program's data account, which names its upgrade authority
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = anchor_lang::solana_program::bpf_loader_upgradeable::ID,
+        constraint = program_data.upgrade_authority_address == Some(authority.key()) @ EscrowError::UnauthorizedAdmin
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(marketplace_id: String)]
+pub struct CreateMarketplace<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Marketplace::LEN,
+        seeds = [MARKETPLACE_SEED, marketplace_id.as_bytes()],
+        bump
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::UnauthorizedAdmin
+    )]
+    pub config: Account<'info, Config>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMarketplace<'info> {
+    #[account(
+        mut,
+        seeds = [MARKETPLACE_SEED, marketplace.marketplace_id.as_bytes()],
+        bump = marketplace.bump
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    
+    pub admin: Signer<'info>,
+    
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::UnauthorizedAdmin
+    )]
+    pub config: Account<'info, Config>,
+}
+
 #[derive(Accounts)]
 #[instruction(order_id: String, amount: u64, bump: u8)]
 pub struct CreateEscrow<'info> {
@@ -191,18 +402,49 @@ pub struct CreateEscrow<'info> {
     /// CHECK: This is not dangerous because we don't read or write from this account
     pub seller: AccountInfo<'info>,
     
-    /// CHECK: Escrow PDA account
+    /// CHECK: The escrow PDA again, receiving the deposit
+    #[account(mut, address = escrow.key())]
+    pub escrow_account: AccountInfo<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_id: String, amount: u64, bump: u8)]
+pub struct CreateMarketplaceEscrow<'info> {
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + Escrow::LEN,
+        seeds = [b"escrow", marketplace.key().as_ref(), order_id.as_bytes()],
+        bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+    
     #[account(mut)]
+    pub buyer: Signer<'info>,
+    
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    pub seller: AccountInfo<'info>,
+    
+    /// CHECK: The escrow PDA again, receiving the deposit
+    #[account(mut, address = escrow.key())]
     pub escrow_account: AccountInfo<'info>,
     
     pub system_program: Program<'info, System>,
+    
+    #[account(
+        seeds = [MARKETPLACE_SEED, marketplace.marketplace_id.as_bytes()],
+        bump = marketplace.bump
+    )]
+    pub marketplace: Account<'info, Marketplace>,
 }
 
 #[derive(Accounts)]
 pub struct ConfirmDelivery<'info> {
     #[account(
         mut,
-        seeds = [b"escrow", escrow.order_id.as_bytes()],
+        seeds = [b"escrow", escrow.marketplace_seed(), escrow.order_id.as_bytes()],
         bump = escrow.bump
     )]
     pub escrow: Account<'info, Escrow>,
@@ -211,21 +453,28 @@ pub struct ConfirmDelivery<'info> {
     pub buyer: Signer<'info>,
     
     /// CHECK: Seller receiving funds
-    #[account(mut)]
+    #[account(mut, address = escrow.seller @ EscrowError::RecipientMismatch)]
     pub seller: AccountInfo<'info>,
     
-    /// CHECK: Escrow PDA account holding funds
-    #[account(mut)]
+    /// CHECK: The escrow PDA again, holding the funds
+    #[account(mut, address = escrow.key())]
     pub escrow_account: AccountInfo<'info>,
     
     pub system_program: Program<'info, System>,
+    
+    /// The escrow's marketplace; omitted for escrows outside one
+    pub marketplace: Option<Account<'info, Marketplace>>,
+    
+    /// CHECK: The marketplace's fee recipient, checked when a fee is due
+    #[account(mut)]
+    pub fee_recipient: Option<UncheckedAccount<'info>>,
 }
 
 #[derive(Accounts)]
 pub struct LockDispute<'info> {
     #[account(
         mut,
-        seeds = [b"escrow", escrow.order_id.as_bytes()],
+        seeds = [b"escrow", escrow.marketplace_seed(), escrow.order_id.as_bytes()],
         bump = escrow.bump
     )]
     pub escrow: Account<'info, Escrow>,
@@ -237,7 +486,7 @@ pub struct LockDispute<'info> {
 pub struct ResolveDispute<'info> {
     #[account(
         mut,
-        seeds = [b"escrow", escrow.order_id.as_bytes()],
+        seeds = [b"escrow", escrow.marketplace_seed(), escrow.order_id.as_bytes()],
         bump = escrow.bump
     )]
     pub escrow: Account<'info, Escrow>,
@@ -246,18 +495,30 @@ pub struct ResolveDispute<'info> {
     pub admin: Signer<'info>,
     
     /// CHECK: Buyer account
-    #[account(mut)]
+    #[account(mut, address = escrow.buyer @ EscrowError::RecipientMismatch)]
     pub buyer: AccountInfo<'info>,
     
     /// CHECK: Seller account
-    #[account(mut)]
+    #[account(mut, address = escrow.seller @ EscrowError::RecipientMismatch)]
     pub seller: AccountInfo<'info>,
     
-    /// CHECK: Escrow PDA account
-    #[account(mut)]
+    /// CHECK: The escrow PDA again, holding the funds
+    #[account(mut, address = escrow.key())]
     pub escrow_account: AccountInfo<'info>,
     
     pub system_program: Program<'info, System>,
+    
+    /// Names the admin of escrows outside a marketplace
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    /// The escrow's marketplace, whose admin resolves it; omitted for
+    /// escrows outside one
+    pub marketplace: Option<Account<'info, Marketplace>>,
+    
+    /// CHECK: The marketplace's fee recipient, checked when a fee is due
+    #[account(mut)]
+    pub fee_recipient: Option<UncheckedAccount<'info>>,
 }
 
 #[account]
@@ -272,10 +533,81 @@ pub struct Escrow {
     pub locked_at: Option<i64>,
     pub released_at: Option<i64>,
     pub resolved_at: Option<i64>,
+    // Escrows created before marketplaces read these as `None` and 0: the
+    // account has room for a 50-byte order id, seeds cap it at 32, and the
+    // spare bytes are zero.
+    /// The marketplace the order belongs to; `None` for the platform's own
+    pub marketplace: Option<Pubkey>,
+    /// The marketplace's fee when the escrow was created
+    pub fee_bps: u16,
 }
 
 impl Escrow {
-    pub const LEN: usize = 32 + 32 + (4 + 50) + 8 + 1 + 1 + 8 + (1 + 8) + (1 + 8) + (1 + 8);
+    pub const LEN: usize = 32 + 32 + (4 + 50) + 8 + 1 + 1 + 8 + (1 + 8) + (1 + 8) + (1 + 8) + (1 + 32) + 2;
+
+    /// The seed between `b"escrow"` and the order id: the marketplace's
+    /// address, or nothing, which keeps the original `["escrow", order_id]`
+    /// address for escrows outside a marketplace.
+    pub fn marketplace_seed(&self) -> &[u8] {
+        self.marketplace.as_ref().map_or(&[], |marketplace| marketplace.as_ref())
+    }
+
+    /// The marketplace's cut of the amount on release.
+    pub fn fee(&self) -> u64 {
+        (self.amount as u128 * self.fee_bps as u128 / 10_000) as u64
+    }
+}
+
+/// Program-wide settings, at the `CONFIG_SEED` PDA.
+#[account]
+pub struct Config {
+    /// The only key allowed to resolve disputes
+    pub admin: Pubkey,
+    pub bump: u8,
+}
+
+impl Config {
+    pub const LEN: usize = 32 + 1;
+}
+
+/// A partner marketplace, at `[MARKETPLACE_SEED, marketplace_id]`. Its
+/// escrows are resolved by its admin and pay it a fee on release.
+#[account]
+pub struct Marketplace {
+    /// Lowercase letters, digits and dashes, 1-32 bytes
+    pub marketplace_id: String,
+    /// The only key allowed to resolve the marketplace's disputes
+    pub admin: Pubkey,
+    /// Taken from the seller's payout on release, in basis points
+    pub fee_bps: u16,
+    pub fee_recipient: Pubkey,
+    /// SPL mints the marketplace's checkout accepts. Escrows hold SOL; the
+    /// list is for clients choosing what to offer.
+    pub mints: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+impl Marketplace {
+    pub const LEN: usize = (4 + 32) + 32 + 2 + 32 + (4 + 32 * MAX_MINTS) + 1;
+
+    fn apply(&mut self, settings: MarketplaceSettings) -> Result<()> {
+        require!(settings.fee_bps <= MAX_FEE_BPS, EscrowError::InvalidFee);
+        require!(settings.mints.len() <= MAX_MINTS, EscrowError::TooManyMints);
+        self.admin = settings.admin;
+        self.fee_bps = settings.fee_bps;
+        self.fee_recipient = settings.fee_recipient;
+        self.mints = settings.mints;
+        Ok(())
+    }
+}
+
+/// What the platform admin sets on a marketplace.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MarketplaceSettings {
+    pub admin: Pubkey,
+    pub fee_bps: u16,
+    pub fee_recipient: Pubkey,
+    pub mints: Vec<Pubkey>,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
@@ -299,4 +631,25 @@ pub enum EscrowError {
     
     #[msg("Insufficient funds in escrow")]
     InsufficientFunds,
+    
+    #[msg("Only the configured admin can perform this action")]
+    UnauthorizedAdmin,
+    
+    #[msg("Recipient is not the escrow's buyer or seller")]
+    RecipientMismatch,
+    
+    #[msg("Bump does not match the escrow PDA")]
+    InvalidBump,
+    
+    #[msg("Marketplace ids are 1-32 lowercase letters, digits or dashes")]
+    InvalidMarketplaceId,
+    
+    #[msg("Marketplace fees are at most 1000 basis points")]
+    InvalidFee,
+    
+    #[msg("A marketplace lists at most 4 mints")]
+    TooManyMints,
+    
+    #[msg("Marketplace account is not the escrow's")]
+    MarketplaceMismatch,
 }
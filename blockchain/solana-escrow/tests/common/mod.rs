@@ -0,0 +1,278 @@
+//! Shared `solana-program-test` harness: the program runs in-process, so
+//! neither a validator nor `anchor build` is needed.
+
+// Each test binary uses a different subset.
+#![allow(dead_code)]
+
+use anchor_lang::solana_program::account_info::AccountInfo;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use proofcart_escrow::EscrowError;
+use proofcart_solana_client::{instructions, pda, Escrow, MarketplaceSettings, Resolution, Tenant};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::account::Account;
+use solana_sdk::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+pub const PROGRAM_ID: Pubkey = proofcart_escrow::ID;
+pub const SOL: u64 = 1_000_000_000;
+pub const PRICE: u64 = SOL / 4;
+
+// Anchor's `entry` ties the slice to the accounts' lifetime, which
+// `processor!` cannot express. Leaking the copy is fine in a test.
+pub fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    proofcart_escrow::entry(program_id, accounts, data)
+}
+
+pub fn wallet() -> Account {
+    Account::new(10 * SOL, 0, &solana_sdk::system_program::id())
+}
+
+/// The ProgramData account an upgradeable deployment would have, naming
+/// `upgrade_authority`.
+pub fn program_data(upgrade_authority: &Pubkey) -> Account {
+    let state = UpgradeableLoaderState::ProgramData {
+        slot: 0,
+        upgrade_authority_address: Some(*upgrade_authority),
+    };
+    Account {
+        lamports: SOL,
+        data: bincode::serialize(&state).unwrap(),
+        owner: bpf_loader_upgradeable::id(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+pub struct Harness {
+    pub context: ProgramTestContext,
+    pub upgrade_authority: Keypair,
+    pub admin: Keypair,
+    pub buyer: Keypair,
+    pub seller: Keypair,
+    pub stranger: Keypair,
+    /// Dispute admin of the marketplaces `register` creates
+    pub tenant_admin: Keypair,
+    /// Fee recipient of the marketplaces `register` creates
+    pub fee_recipient: Keypair,
+}
+
+impl Harness {
+    /// A bank with the program deployed and funded wallets; the admin is
+    /// not configured yet.
+    pub async fn new() -> Self {
+        let upgrade_authority = Keypair::new();
+        let admin = Keypair::new();
+        let buyer = Keypair::new();
+        let seller = Keypair::new();
+        let stranger = Keypair::new();
+        let tenant_admin = Keypair::new();
+        let fee_recipient = Keypair::new();
+
+        let mut test = ProgramTest::new("proofcart_escrow", PROGRAM_ID, processor!(process_instruction));
+        test.prefer_bpf(false);
+        test.add_account(
+            pda::program_data_address(&PROGRAM_ID),
+            program_data(&upgrade_authority.pubkey()),
+        );
+        for key in [&upgrade_authority, &admin, &buyer, &seller, &stranger, &tenant_admin, &fee_recipient] {
+            test.add_account(key.pubkey(), wallet());
+        }
+
+        Self {
+            context: test.start_with_context().await,
+            upgrade_authority,
+            admin,
+            buyer,
+            seller,
+            stranger,
+            tenant_admin,
+            fee_recipient,
+        }
+    }
+
+    /// `new`, with the admin configured.
+    pub async fn configured() -> Self {
+        let mut harness = Self::new().await;
+        let instruction = instructions::initialize_config(
+            &PROGRAM_ID,
+            &harness.upgrade_authority.pubkey(),
+            &harness.admin.pubkey(),
+        );
+        let authority = harness.upgrade_authority.insecure_clone();
+        harness.send(instruction, &authority).await.unwrap();
+        harness
+    }
+
+    /// Send `instruction` signed by `signer`, with the context's payer
+    /// paying the fee so wallet balances move only by what the program does.
+    pub async fn send(&mut self, instruction: Instruction, signer: &Keypair) -> Result<(), TransactionError> {
+        let blockhash = self.context.get_new_latest_blockhash().await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, signer],
+            blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|e| e.unwrap())
+    }
+
+    pub async fn balance(&mut self, key: &Pubkey) -> u64 {
+        self.context.banks_client.get_balance(*key).await.unwrap()
+    }
+
+    /// Rent-exempt minimum of an escrow account, which stays in it after the
+    /// payout.
+    pub async fn escrow_reserve(&mut self) -> u64 {
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        rent.minimum_balance(8 + proofcart_escrow::Escrow::LEN)
+    }
+
+    pub async fn escrow(&mut self, order_id: &str) -> Option<Escrow> {
+        let (address, _) = pda::escrow_address(&PROGRAM_ID, order_id).unwrap();
+        let account = self.context.banks_client.get_account(address).await.unwrap()?;
+        Some(Escrow::try_from_account_data(&account.data).unwrap())
+    }
+
+    /// Settings naming the harness's tenant admin and fee recipient.
+    pub fn settings(&self, fee_bps: u16) -> MarketplaceSettings {
+        MarketplaceSettings {
+            admin: self.tenant_admin.pubkey(),
+            fee_bps,
+            fee_recipient: self.fee_recipient.pubkey(),
+            mints: vec![spl_token::native_mint::id()],
+        }
+    }
+
+    /// Register `marketplace_id` as the platform admin and return its
+    /// accounts.
+    pub async fn register(&mut self, marketplace_id: &str, fee_bps: u16) -> Tenant {
+        let settings = self.settings(fee_bps);
+        let instruction =
+            instructions::create_marketplace(&PROGRAM_ID, &self.admin.pubkey(), marketplace_id, &settings).unwrap();
+        let admin = self.admin.insecure_clone();
+        self.send(instruction, &admin).await.unwrap();
+        Tenant {
+            marketplace: pda::marketplace_address(&PROGRAM_ID, marketplace_id).unwrap().0,
+            fee_recipient: self.fee_recipient.pubkey(),
+        }
+    }
+
+    pub async fn escrow_in(&mut self, tenant: &Tenant, order_id: &str) -> Option<Escrow> {
+        let (address, _) = pda::escrow_address_in(&PROGRAM_ID, Some(&tenant.marketplace), order_id).unwrap();
+        let account = self.context.banks_client.get_account(address).await.unwrap()?;
+        Some(Escrow::try_from_account_data(&account.data).unwrap())
+    }
+
+    pub async fn create_in(&mut self, tenant: &Tenant, order_id: &str) -> Result<(), TransactionError> {
+        let instruction = instructions::create_escrow_in(
+            &PROGRAM_ID,
+            Some(&tenant.marketplace),
+            &self.buyer.pubkey(),
+            &self.seller.pubkey(),
+            order_id,
+            PRICE,
+        )
+        .unwrap();
+        let buyer = self.buyer.insecure_clone();
+        self.send(instruction, &buyer).await
+    }
+
+    pub async fn confirm_in(&mut self, tenant: &Tenant, order_id: &str) -> Result<(), TransactionError> {
+        let instruction = instructions::confirm_delivery_in(
+            &PROGRAM_ID,
+            Some(tenant),
+            &self.buyer.pubkey(),
+            &self.seller.pubkey(),
+            order_id,
+        )
+        .unwrap();
+        let buyer = self.buyer.insecure_clone();
+        self.send(instruction, &buyer).await
+    }
+
+    pub async fn dispute_in(&mut self, tenant: &Tenant, order_id: &str) -> Result<(), TransactionError> {
+        let instruction =
+            instructions::lock_dispute_in(&PROGRAM_ID, Some(&tenant.marketplace), &self.buyer.pubkey(), order_id)
+                .unwrap();
+        let buyer = self.buyer.insecure_clone();
+        self.send(instruction, &buyer).await
+    }
+
+    pub async fn resolve_in(
+        &mut self,
+        signer: &Keypair,
+        tenant: &Tenant,
+        order_id: &str,
+        resolution: Resolution,
+    ) -> Result<(), TransactionError> {
+        let instruction = instructions::resolve_in(
+            &PROGRAM_ID,
+            Some(tenant),
+            &signer.pubkey(),
+            &self.buyer.pubkey(),
+            &self.seller.pubkey(),
+            order_id,
+            resolution,
+        )
+        .unwrap();
+        self.send(instruction, signer).await
+    }
+
+    pub async fn create(&mut self, order_id: &str) -> Result<(), TransactionError> {
+        let instruction =
+            instructions::create_escrow(&PROGRAM_ID, &self.buyer.pubkey(), &self.seller.pubkey(), order_id, PRICE)
+                .unwrap();
+        let buyer = self.buyer.insecure_clone();
+        self.send(instruction, &buyer).await
+    }
+
+    pub async fn confirm(&mut self, order_id: &str) -> Result<(), TransactionError> {
+        let instruction =
+            instructions::confirm_delivery(&PROGRAM_ID, &self.buyer.pubkey(), &self.seller.pubkey(), order_id)
+                .unwrap();
+        let buyer = self.buyer.insecure_clone();
+        self.send(instruction, &buyer).await
+    }
+
+    pub async fn dispute(&mut self, order_id: &str) -> Result<(), TransactionError> {
+        let instruction = instructions::lock_dispute(&PROGRAM_ID, &self.buyer.pubkey(), order_id).unwrap();
+        let buyer = self.buyer.insecure_clone();
+        self.send(instruction, &buyer).await
+    }
+
+    pub async fn resolve_as(
+        &mut self,
+        signer: &Keypair,
+        order_id: &str,
+        resolution: Resolution,
+    ) -> Result<(), TransactionError> {
+        let instruction = instructions::resolve(
+            &PROGRAM_ID,
+            &signer.pubkey(),
+            &self.buyer.pubkey(),
+            &self.seller.pubkey(),
+            order_id,
+            resolution,
+        )
+        .unwrap();
+        self.send(instruction, signer).await
+    }
+
+    pub async fn resolve(&mut self, order_id: &str, resolution: Resolution) -> Result<(), TransactionError> {
+        let admin = self.admin.insecure_clone();
+        self.resolve_as(&admin, order_id, resolution).await
+    }
+}
+
+pub fn program_error(error: EscrowError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error.into()))
+}
+
@@ -0,0 +1,340 @@
+//! Every escrow instruction, state transition and error path. Instructions
+//! are built with `proofcart-solana-client`, which keeps the client's account
+//! lists honest too.
+
+mod common;
+
+use common::{program_error, Harness, PRICE, PROGRAM_ID, SOL};
+use proofcart_escrow::EscrowError;
+use proofcart_solana_client::{instructions, pda, EscrowStatus, Resolution};
+use solana_sdk::signature::Signer;
+use solana_sdk::system_instruction;
+
+#[tokio::test]
+async fn create_deposits_the_amount() {
+    let mut harness = Harness::configured().await;
+    let buyer_before = harness.balance(&harness.buyer.pubkey()).await;
+
+    harness.create("ORD-1").await.unwrap();
+
+    let escrow = harness.escrow("ORD-1").await.unwrap();
+    let (address, bump) = pda::escrow_address(&PROGRAM_ID, "ORD-1").unwrap();
+    assert_eq!(escrow.status, EscrowStatus::Created);
+    assert_eq!(escrow.buyer, harness.buyer.pubkey());
+    assert_eq!(escrow.seller, harness.seller.pubkey());
+    assert_eq!(escrow.order_id, "ORD-1");
+    assert_eq!(escrow.amount, PRICE);
+    assert_eq!(escrow.bump, bump);
+
+    let reserve = harness.escrow_reserve().await;
+    assert_eq!(harness.balance(&address).await, reserve + PRICE);
+    assert_eq!(harness.balance(&harness.buyer.pubkey()).await, buyer_before - reserve - PRICE);
+}
+
+#[tokio::test]
+async fn confirm_delivery_pays_the_seller() {
+    let mut harness = Harness::configured().await;
+    harness.create("ORD-1").await.unwrap();
+    let (address, _) = pda::escrow_address(&PROGRAM_ID, "ORD-1").unwrap();
+    let escrow_before = harness.balance(&address).await;
+    let seller_before = harness.balance(&harness.seller.pubkey()).await;
+
+    harness.confirm("ORD-1").await.unwrap();
+
+    let escrow = harness.escrow("ORD-1").await.unwrap();
+    assert_eq!(escrow.status, EscrowStatus::Released);
+    assert!(escrow.released_at.is_some());
+    assert_eq!(harness.balance(&harness.seller.pubkey()).await, seller_before + PRICE);
+    assert_eq!(harness.balance(&address).await, escrow_before - PRICE);
+}
+
+#[tokio::test]
+async fn buyer_can_confirm_a_disputed_escrow() {
+    let mut harness = Harness::configured().await;
+    harness.create("ORD-1").await.unwrap();
+    harness.dispute("ORD-1").await.unwrap();
+    let escrow = harness.escrow("ORD-1").await.unwrap();
+    assert_eq!(escrow.status, EscrowStatus::Locked);
+    assert!(escrow.locked_at.is_some());
+
+    harness.confirm("ORD-1").await.unwrap();
+    assert_eq!(harness.escrow("ORD-1").await.unwrap().status, EscrowStatus::Released);
+}
+
+#[tokio::test]
+async fn admin_refunds_a_dispute() {
+    let mut harness = Harness::configured().await;
+    harness.create("ORD-1").await.unwrap();
+    harness.dispute("ORD-1").await.unwrap();
+    let buyer_before = harness.balance(&harness.buyer.pubkey()).await;
+    let seller_before = harness.balance(&harness.seller.pubkey()).await;
+
+    harness.resolve("ORD-1", Resolution::Refund).await.unwrap();
+
+    let escrow = harness.escrow("ORD-1").await.unwrap();
+    assert_eq!(escrow.status, EscrowStatus::Refunded);
+    assert!(escrow.resolved_at.is_some());
+    assert_eq!(harness.balance(&harness.buyer.pubkey()).await, buyer_before + PRICE);
+    assert_eq!(harness.balance(&harness.seller.pubkey()).await, seller_before);
+}
+
+#[tokio::test]
+async fn admin_releases_a_dispute() {
+    let mut harness = Harness::configured().await;
+    harness.create("ORD-1").await.unwrap();
+    harness.dispute("ORD-1").await.unwrap();
+    let seller_before = harness.balance(&harness.seller.pubkey()).await;
+
+    harness.resolve("ORD-1", Resolution::Release).await.unwrap();
+
+    let escrow = harness.escrow("ORD-1").await.unwrap();
+    assert_eq!(escrow.status, EscrowStatus::Released);
+    assert!(escrow.resolved_at.is_some());
+    assert_eq!(harness.balance(&harness.seller.pubkey()).await, seller_before + PRICE);
+}
+
+#[tokio::test]
+async fn settled_escrows_accept_no_further_instructions() {
+    let mut harness = Harness::configured().await;
+    harness.create("ORD-1").await.unwrap();
+    harness.confirm("ORD-1").await.unwrap();
+    assert_eq!(
+        harness.confirm("ORD-1").await,
+        Err(program_error(EscrowError::InvalidEscrowStatus))
+    );
+    assert_eq!(
+        harness.dispute("ORD-1").await,
+        Err(program_error(EscrowError::InvalidEscrowStatus))
+    );
+
+    harness.create("ORD-2").await.unwrap();
+    harness.dispute("ORD-2").await.unwrap();
+    harness.resolve("ORD-2", Resolution::Refund).await.unwrap();
+    assert_eq!(
+        harness.resolve("ORD-2", Resolution::Release).await,
+        Err(program_error(EscrowError::EscrowNotLocked))
+    );
+    assert_eq!(
+        harness.confirm("ORD-2").await,
+        Err(program_error(EscrowError::InvalidEscrowStatus))
+    );
+    assert_eq!(harness.escrow("ORD-2").await.unwrap().status, EscrowStatus::Refunded);
+}
+
+#[tokio::test]
+async fn disputes_lock_only_once() {
+    let mut harness = Harness::configured().await;
+    harness.create("ORD-1").await.unwrap();
+    harness.dispute("ORD-1").await.unwrap();
+    assert_eq!(
+        harness.dispute("ORD-1").await,
+        Err(program_error(EscrowError::InvalidEscrowStatus))
+    );
+}
+
+#[tokio::test]
+async fn resolutions_need_a_locked_escrow() {
+    let mut harness = Harness::configured().await;
+    harness.create("ORD-1").await.unwrap();
+    for resolution in [Resolution::Refund, Resolution::Release] {
+        assert_eq!(
+            harness.resolve("ORD-1", resolution).await,
+            Err(program_error(EscrowError::EscrowNotLocked))
+        );
+    }
+}
+
+#[tokio::test]
+async fn only_the_buyer_confirms_or_disputes() {
+    let mut harness = Harness::configured().await;
+    harness.create("ORD-1").await.unwrap();
+    let stranger = harness.stranger.insecure_clone();
+
+    let confirm = instructions::confirm_delivery(
+        &PROGRAM_ID,
+        &stranger.pubkey(),
+        &harness.seller.pubkey(),
+        "ORD-1",
+    )
+    .unwrap();
+    assert_eq!(
+        harness.send(confirm, &stranger).await,
+        Err(program_error(EscrowError::UnauthorizedBuyer))
+    );
+
+    let seller = harness.seller.insecure_clone();
+    let dispute = instructions::lock_dispute(&PROGRAM_ID, &seller.pubkey(), "ORD-1").unwrap();
+    assert_eq!(
+        harness.send(dispute, &seller).await,
+        Err(program_error(EscrowError::UnauthorizedBuyer))
+    );
+    assert_eq!(harness.escrow("ORD-1").await.unwrap().status, EscrowStatus::Created);
+}
+
+#[tokio::test]
+async fn unauthorized_resolvers_are_rejected() {
+    let mut harness = Harness::configured().await;
+    harness.create("ORD-1").await.unwrap();
+    harness.dispute("ORD-1").await.unwrap();
+
+    let buyer = harness.buyer.insecure_clone();
+    let seller = harness.seller.insecure_clone();
+    let upgrade_authority = harness.upgrade_authority.insecure_clone();
+    for (signer, resolution) in [
+        (&buyer, Resolution::Refund),
+        (&seller, Resolution::Release),
+        (&upgrade_authority, Resolution::Refund),
+    ] {
+        assert_eq!(
+            harness.resolve_as(signer, "ORD-1", resolution).await,
+            Err(program_error(EscrowError::UnauthorizedAdmin))
+        );
+    }
+    assert_eq!(harness.escrow("ORD-1").await.unwrap().status, EscrowStatus::Locked);
+}
+
+#[tokio::test]
+async fn resolutions_fail_until_an_admin_is_configured() {
+    let mut harness = Harness::new().await;
+    harness.create("ORD-1").await.unwrap();
+    harness.dispute("ORD-1").await.unwrap();
+    assert!(harness.resolve("ORD-1", Resolution::Refund).await.is_err());
+    assert_eq!(harness.escrow("ORD-1").await.unwrap().status, EscrowStatus::Locked);
+}
+
+#[tokio::test]
+async fn only_the_upgrade_authority_configures_the_admin_once() {
+    let mut harness = Harness::new().await;
+    let stranger = harness.stranger.insecure_clone();
+    let instruction = instructions::initialize_config(&PROGRAM_ID, &stranger.pubkey(), &stranger.pubkey());
+    assert_eq!(
+        harness.send(instruction, &stranger).await,
+        Err(program_error(EscrowError::UnauthorizedAdmin))
+    );
+
+    let authority = harness.upgrade_authority.insecure_clone();
+    let admin = harness.admin.pubkey();
+    harness
+        .send(instructions::initialize_config(&PROGRAM_ID, &authority.pubkey(), &admin), &authority)
+        .await
+        .unwrap();
+    let again = instructions::initialize_config(&PROGRAM_ID, &authority.pubkey(), &stranger.pubkey());
+    assert!(harness.send(again, &authority).await.is_err());
+}
+
+#[tokio::test]
+async fn payouts_go_only_to_the_recorded_parties() {
+    let mut harness = Harness::configured().await;
+    harness.create("ORD-1").await.unwrap();
+    let buyer = harness.buyer.insecure_clone();
+    let stranger = harness.stranger.pubkey();
+
+    let confirm = instructions::confirm_delivery(&PROGRAM_ID, &buyer.pubkey(), &stranger, "ORD-1").unwrap();
+    assert_eq!(
+        harness.send(confirm, &buyer).await,
+        Err(program_error(EscrowError::RecipientMismatch))
+    );
+
+    harness.dispute("ORD-1").await.unwrap();
+    let admin = harness.admin.insecure_clone();
+    let refund = instructions::resolve(
+        &PROGRAM_ID,
+        &admin.pubkey(),
+        &stranger,
+        &harness.seller.pubkey(),
+        "ORD-1",
+        Resolution::Refund,
+    )
+    .unwrap();
+    assert_eq!(
+        harness.send(refund, &admin).await,
+        Err(program_error(EscrowError::RecipientMismatch))
+    );
+    let release = instructions::resolve(
+        &PROGRAM_ID,
+        &admin.pubkey(),
+        &buyer.pubkey(),
+        &stranger,
+        "ORD-1",
+        Resolution::Release,
+    )
+    .unwrap();
+    assert_eq!(
+        harness.send(release, &admin).await,
+        Err(program_error(EscrowError::RecipientMismatch))
+    );
+}
+
+#[tokio::test]
+async fn order_ids_cannot_be_reused() {
+    let mut harness = Harness::configured().await;
+    harness.create("ORD-1").await.unwrap();
+    let original = harness.escrow("ORD-1").await.unwrap();
+
+    // Same seeds from another buyer and seller: the PDA is taken.
+    let stranger = harness.stranger.insecure_clone();
+    let instruction =
+        instructions::create_escrow(&PROGRAM_ID, &stranger.pubkey(), &stranger.pubkey(), "ORD-1", 1).unwrap();
+    assert!(harness.send(instruction, &stranger).await.is_err());
+    assert_eq!(harness.escrow("ORD-1").await.unwrap(), original);
+}
+
+#[tokio::test]
+async fn prefunded_escrow_address_still_opens() {
+    let mut harness = Harness::configured().await;
+    let (address, _) = pda::escrow_address(&PROGRAM_ID, "ORD-1").unwrap();
+    let stranger = harness.stranger.insecure_clone();
+    harness
+        .send(system_instruction::transfer(&stranger.pubkey(), &address, SOL), &stranger)
+        .await
+        .unwrap();
+
+    harness.create("ORD-1").await.unwrap();
+    let seller_before = harness.balance(&harness.seller.pubkey()).await;
+    harness.confirm("ORD-1").await.unwrap();
+    assert_eq!(harness.balance(&harness.seller.pubkey()).await, seller_before + PRICE);
+}
+
+#[tokio::test]
+async fn non_canonical_bumps_are_rejected() {
+    let mut harness = Harness::configured().await;
+    let mut instruction = instructions::create_escrow(
+        &PROGRAM_ID,
+        &harness.buyer.pubkey(),
+        &harness.seller.pubkey(),
+        "ORD-1",
+        PRICE,
+    )
+    .unwrap();
+    // The bump is the last byte of the arguments.
+    let bump = instruction.data.last_mut().unwrap();
+    *bump = bump.wrapping_sub(1);
+    let buyer = harness.buyer.insecure_clone();
+    assert_eq!(
+        harness.send(instruction, &buyer).await,
+        Err(program_error(EscrowError::InvalidBump))
+    );
+    assert!(harness.escrow("ORD-1").await.is_none());
+}
+
+#[tokio::test]
+async fn escrow_accounts_must_match_their_order() {
+    let mut harness = Harness::configured().await;
+    harness.create("ORD-1").await.unwrap();
+    harness.create("ORD-2").await.unwrap();
+
+    // ORD-1's escrow passed as the account holding ORD-2's funds.
+    let (other, _) = pda::escrow_address(&PROGRAM_ID, "ORD-1").unwrap();
+    let mut instruction = instructions::confirm_delivery(
+        &PROGRAM_ID,
+        &harness.buyer.pubkey(),
+        &harness.seller.pubkey(),
+        "ORD-2",
+    )
+    .unwrap();
+    instruction.accounts[3].pubkey = other;
+    let buyer = harness.buyer.insecure_clone();
+    assert!(harness.send(instruction, &buyer).await.is_err());
+    assert_eq!(harness.escrow("ORD-1").await.unwrap().status, EscrowStatus::Created);
+    assert_eq!(harness.escrow("ORD-2").await.unwrap().status, EscrowStatus::Created);
+}
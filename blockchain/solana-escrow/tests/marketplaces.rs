@@ -0,0 +1,213 @@
+//! Partner marketplaces: registration by the platform admin, order ids
+//! namespaced per marketplace, the marketplace's fee on release and its own
+//! dispute admin.
+
+mod common;
+
+use common::{program_error, Harness, PRICE, PROGRAM_ID};
+use proofcart_escrow::EscrowError;
+use proofcart_solana_client::{instructions, pda, EscrowStatus, Marketplace, Resolution, Tenant};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn platform_admin_registers_marketplaces() {
+    let mut harness = Harness::configured().await;
+    harness.register("acme", 250).await;
+
+    let (address, bump) = pda::marketplace_address(&PROGRAM_ID, "acme").unwrap();
+    let account = harness.context.banks_client.get_account(address).await.unwrap().unwrap();
+    let marketplace = Marketplace::try_from_account_data(&account.data).unwrap();
+    assert_eq!(marketplace.marketplace_id, "acme");
+    assert_eq!(marketplace.admin, harness.tenant_admin.pubkey());
+    assert_eq!(marketplace.fee_bps, 250);
+    assert_eq!(marketplace.fee_recipient, harness.fee_recipient.pubkey());
+    assert_eq!(marketplace.mints, vec![spl_token::native_mint::id()]);
+    assert_eq!(marketplace.bump, bump);
+
+    // Neither a tenant admin nor anyone else can register or change one.
+    let settings = harness.settings(0);
+    for signer in [harness.tenant_admin.insecure_clone(), harness.stranger.insecure_clone()] {
+        let create = instructions::create_marketplace(&PROGRAM_ID, &signer.pubkey(), "globex", &settings).unwrap();
+        assert_eq!(
+            harness.send(create, &signer).await,
+            Err(program_error(EscrowError::UnauthorizedAdmin))
+        );
+        let update = instructions::update_marketplace(&PROGRAM_ID, &signer.pubkey(), "acme", &settings).unwrap();
+        assert_eq!(
+            harness.send(update, &signer).await,
+            Err(program_error(EscrowError::UnauthorizedAdmin))
+        );
+    }
+}
+
+#[tokio::test]
+async fn marketplace_settings_are_bounded() {
+    let mut harness = Harness::configured().await;
+    let admin = harness.admin.insecure_clone();
+
+    let mut settings = harness.settings(1_001);
+    let create = instructions::create_marketplace(&PROGRAM_ID, &admin.pubkey(), "acme", &settings).unwrap();
+    assert_eq!(
+        harness.send(create, &admin).await,
+        Err(program_error(EscrowError::InvalidFee))
+    );
+
+    settings.fee_bps = 1_000;
+    settings.mints = (0..5).map(|_| Pubkey::new_unique()).collect();
+    let create = instructions::create_marketplace(&PROGRAM_ID, &admin.pubkey(), "acme", &settings).unwrap();
+    assert_eq!(
+        harness.send(create, &admin).await,
+        Err(program_error(EscrowError::TooManyMints))
+    );
+
+    // The client refuses ids the program would, so build one by hand.
+    settings.mints.truncate(4);
+    let mut create = instructions::create_marketplace(&PROGRAM_ID, &admin.pubkey(), "acme", &settings).unwrap();
+    // The id follows the discriminator and its length prefix.
+    create.data[12..16].copy_from_slice(b"ACME");
+    create.accounts[0].pubkey = Pubkey::find_program_address(&[pda::MARKETPLACE_SEED, b"ACME"], &PROGRAM_ID).0;
+    assert_eq!(
+        harness.send(create, &admin).await,
+        Err(program_error(EscrowError::InvalidMarketplaceId))
+    );
+
+    let create = instructions::create_marketplace(&PROGRAM_ID, &admin.pubkey(), "acme", &settings).unwrap();
+    harness.send(create, &admin).await.unwrap();
+}
+
+#[tokio::test]
+async fn release_pays_the_marketplace_fee() {
+    let mut harness = Harness::configured().await;
+    let tenant = harness.register("acme", 250).await;
+    harness.create_in(&tenant, "ORD-1").await.unwrap();
+    let escrow = harness.escrow_in(&tenant, "ORD-1").await.unwrap();
+    assert_eq!(escrow.marketplace, Some(tenant.marketplace));
+    assert_eq!(escrow.fee_bps, 250);
+
+    let seller_before = harness.balance(&harness.seller.pubkey()).await;
+    let recipient_before = harness.balance(&tenant.fee_recipient).await;
+    harness.confirm_in(&tenant, "ORD-1").await.unwrap();
+
+    let fee = PRICE / 40;
+    assert_eq!(harness.balance(&tenant.fee_recipient).await, recipient_before + fee);
+    assert_eq!(harness.balance(&harness.seller.pubkey()).await, seller_before + PRICE - fee);
+    assert_eq!(harness.escrow_in(&tenant, "ORD-1").await.unwrap().status, EscrowStatus::Released);
+}
+
+#[tokio::test]
+async fn escrows_keep_the_fee_they_were_created_with() {
+    let mut harness = Harness::configured().await;
+    let tenant = harness.register("acme", 250).await;
+    harness.create_in(&tenant, "ORD-1").await.unwrap();
+
+    let admin = harness.admin.insecure_clone();
+    let settings = harness.settings(1_000);
+    let update = instructions::update_marketplace(&PROGRAM_ID, &admin.pubkey(), "acme", &settings).unwrap();
+    harness.send(update, &admin).await.unwrap();
+    harness.create_in(&tenant, "ORD-2").await.unwrap();
+    assert_eq!(harness.escrow_in(&tenant, "ORD-1").await.unwrap().fee_bps, 250);
+    assert_eq!(harness.escrow_in(&tenant, "ORD-2").await.unwrap().fee_bps, 1_000);
+
+    let recipient_before = harness.balance(&tenant.fee_recipient).await;
+    harness.confirm_in(&tenant, "ORD-1").await.unwrap();
+    assert_eq!(harness.balance(&tenant.fee_recipient).await, recipient_before + PRICE / 40);
+}
+
+#[tokio::test]
+async fn order_ids_are_namespaced_per_marketplace() {
+    let mut harness = Harness::configured().await;
+    let acme = harness.register("acme", 100).await;
+    let globex = harness.register("globex", 0).await;
+
+    harness.create("ORD-1").await.unwrap();
+    harness.create_in(&acme, "ORD-1").await.unwrap();
+    harness.create_in(&globex, "ORD-1").await.unwrap();
+
+    harness.dispute_in(&acme, "ORD-1").await.unwrap();
+    assert_eq!(harness.escrow("ORD-1").await.unwrap().status, EscrowStatus::Created);
+    assert_eq!(harness.escrow_in(&acme, "ORD-1").await.unwrap().status, EscrowStatus::Locked);
+    assert_eq!(harness.escrow_in(&globex, "ORD-1").await.unwrap().status, EscrowStatus::Created);
+    assert_eq!(harness.escrow("ORD-1").await.unwrap().marketplace, None);
+}
+
+#[tokio::test]
+async fn marketplace_admins_resolve_only_their_own_escrows() {
+    let mut harness = Harness::configured().await;
+    let tenant = harness.register("acme", 250).await;
+    harness.create("ORD-1").await.unwrap();
+    harness.dispute("ORD-1").await.unwrap();
+    harness.create_in(&tenant, "ORD-1").await.unwrap();
+    harness.dispute_in(&tenant, "ORD-1").await.unwrap();
+
+    let tenant_admin = harness.tenant_admin.insecure_clone();
+    let admin = harness.admin.insecure_clone();
+    assert_eq!(
+        harness.resolve_as(&tenant_admin, "ORD-1", Resolution::Refund).await,
+        Err(program_error(EscrowError::UnauthorizedAdmin))
+    );
+    assert_eq!(
+        harness.resolve_in(&admin, &tenant, "ORD-1", Resolution::Refund).await,
+        Err(program_error(EscrowError::UnauthorizedAdmin))
+    );
+
+    let seller_before = harness.balance(&harness.seller.pubkey()).await;
+    harness.resolve_in(&tenant_admin, &tenant, "ORD-1", Resolution::Release).await.unwrap();
+    let escrow = harness.escrow_in(&tenant, "ORD-1").await.unwrap();
+    assert_eq!(escrow.status, EscrowStatus::Released);
+    assert_eq!(harness.balance(&harness.seller.pubkey()).await, seller_before + PRICE - PRICE / 40);
+
+    harness.resolve("ORD-1", Resolution::Refund).await.unwrap();
+    assert_eq!(harness.escrow("ORD-1").await.unwrap().status, EscrowStatus::Refunded);
+}
+
+#[tokio::test]
+async fn releases_need_the_escrows_marketplace_and_fee_recipient() {
+    let mut harness = Harness::configured().await;
+    let acme = harness.register("acme", 250).await;
+    let globex = harness.register("globex", 250).await;
+    harness.create_in(&acme, "ORD-1").await.unwrap();
+    let buyer = harness.buyer.insecure_clone();
+    let seller = harness.seller.pubkey();
+    let (escrow, _) = pda::escrow_address_in(&PROGRAM_ID, Some(&acme.marketplace), "ORD-1").unwrap();
+
+    // Without the marketplace accounts, as a client predating them sends.
+    let mut missing = instructions::confirm_delivery_in(&PROGRAM_ID, Some(&acme), &buyer.pubkey(), &seller, "ORD-1")
+        .unwrap();
+    missing.accounts.truncate(5);
+    assert_eq!(
+        harness.send(missing, &buyer).await,
+        Err(program_error(EscrowError::MarketplaceMismatch))
+    );
+
+    // Another marketplace's account.
+    let mut wrong = instructions::confirm_delivery_in(&PROGRAM_ID, Some(&acme), &buyer.pubkey(), &seller, "ORD-1")
+        .unwrap();
+    wrong.accounts[5].pubkey = globex.marketplace;
+    assert_eq!(
+        harness.send(wrong, &buyer).await,
+        Err(program_error(EscrowError::MarketplaceMismatch))
+    );
+
+    // The fee routed anywhere but the marketplace's recipient.
+    let diverted = Tenant { marketplace: acme.marketplace, fee_recipient: buyer.pubkey() };
+    let instruction =
+        instructions::confirm_delivery_in(&PROGRAM_ID, Some(&diverted), &buyer.pubkey(), &seller, "ORD-1").unwrap();
+    assert_eq!(instruction.accounts[0].pubkey, escrow);
+    assert_eq!(
+        harness.send(instruction, &buyer).await,
+        Err(program_error(EscrowError::RecipientMismatch))
+    );
+
+    assert_eq!(harness.escrow_in(&acme, "ORD-1").await.unwrap().status, EscrowStatus::Created);
+    harness.confirm_in(&acme, "ORD-1").await.unwrap();
+}
+
+#[tokio::test]
+async fn marketplace_escrows_need_a_registered_marketplace() {
+    let mut harness = Harness::configured().await;
+    let (marketplace, _) = pda::marketplace_address(&PROGRAM_ID, "acme").unwrap();
+    let unregistered = Tenant { marketplace, fee_recipient: harness.fee_recipient.pubkey() };
+    assert!(harness.create_in(&unregistered, "ORD-1").await.is_err());
+    assert!(harness.escrow_in(&unregistered, "ORD-1").await.is_none());
+}
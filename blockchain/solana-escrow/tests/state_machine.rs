@@ -0,0 +1,214 @@
+//! Random instruction sequences from random actors, checked after every
+//! step against a model of the escrow state machine: each instruction
+//! succeeds exactly when the model allows it, lamports are conserved, an
+//! escrow pays out at most once, and settled escrows never change.
+//!
+//! Each case starts a fresh bank, so the default is 32 cases; raise it with
+//! `PROPTEST_CASES`.
+
+mod common;
+
+use common::{Harness, PRICE, PROGRAM_ID};
+use proofcart_solana_client::{instructions, pda, EscrowStatus, Resolution};
+use proptest::prelude::*;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+/// Two orders, so steps on one can be checked not to disturb the other.
+const ORDERS: [&str; 2] = ["ORD-A", "ORD-B"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Actor {
+    Buyer,
+    Seller,
+    Admin,
+    Stranger,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Step {
+    /// `buyer` opens the order's escrow, always to the seller.
+    Create { order: usize, buyer: Actor },
+    Confirm { order: usize, signer: Actor },
+    Dispute { order: usize, signer: Actor },
+    Resolve { order: usize, signer: Actor, resolution: Resolution },
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Order {
+    buyer: Actor,
+    status: EscrowStatus,
+}
+
+type Model = [Option<Order>; ORDERS.len()];
+
+fn actor() -> impl Strategy<Value = Actor> {
+    // Mostly the parties that can act, so sequences get past the first step.
+    prop_oneof![
+        3 => Just(Actor::Buyer),
+        2 => Just(Actor::Admin),
+        1 => Just(Actor::Seller),
+        1 => Just(Actor::Stranger),
+    ]
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    let order = 0..ORDERS.len();
+    let resolution = prop_oneof![Just(Resolution::Refund), Just(Resolution::Release)];
+    prop_oneof![
+        (order.clone(), actor()).prop_map(|(order, buyer)| Step::Create { order, buyer }),
+        (order.clone(), actor()).prop_map(|(order, signer)| Step::Confirm { order, signer }),
+        (order.clone(), actor()).prop_map(|(order, signer)| Step::Dispute { order, signer }),
+        (order, actor(), resolution).prop_map(|(order, signer, resolution)| Step::Resolve {
+            order,
+            signer,
+            resolution,
+        }),
+    ]
+}
+
+/// The order's state after `step`, or `None` if the program must reject it.
+fn expect(model: &Model, step: Step) -> Option<Order> {
+    match step {
+        Step::Create { order, buyer } => match model[order] {
+            None => Some(Order { buyer, status: EscrowStatus::Created }),
+            Some(_) => None,
+        },
+        Step::Confirm { order, signer } => model[order]
+            .filter(|o| o.buyer == signer && matches!(o.status, EscrowStatus::Created | EscrowStatus::Locked))
+            .map(|o| Order { status: EscrowStatus::Released, ..o }),
+        Step::Dispute { order, signer } => model[order]
+            .filter(|o| o.buyer == signer && o.status == EscrowStatus::Created)
+            .map(|o| Order { status: EscrowStatus::Locked, ..o }),
+        Step::Resolve { order, signer, resolution } => model[order]
+            .filter(|o| signer == Actor::Admin && o.status == EscrowStatus::Locked)
+            .map(|o| Order {
+                status: match resolution {
+                    Resolution::Refund => EscrowStatus::Refunded,
+                    Resolution::Release => EscrowStatus::Released,
+                },
+                ..o
+            }),
+    }
+}
+
+fn keypair(harness: &Harness, actor: Actor) -> Keypair {
+    match actor {
+        Actor::Buyer => harness.buyer.insecure_clone(),
+        Actor::Seller => harness.seller.insecure_clone(),
+        Actor::Admin => harness.admin.insecure_clone(),
+        Actor::Stranger => harness.stranger.insecure_clone(),
+    }
+}
+
+/// The step's instruction and signer. Recipients are the escrow's real buyer
+/// and seller, so failures come from the state machine, not from account
+/// checks.
+fn build(harness: &Harness, model: &Model, step: Step) -> (Instruction, Keypair) {
+    let seller = harness.seller.pubkey();
+    let buyer_of = |order: usize, signer: Actor| {
+        keypair(harness, model[order].map_or(signer, |o| o.buyer)).pubkey()
+    };
+    match step {
+        Step::Create { order, buyer } => {
+            let buyer = keypair(harness, buyer);
+            let ix = instructions::create_escrow(&PROGRAM_ID, &buyer.pubkey(), &seller, ORDERS[order], PRICE);
+            (ix.unwrap(), buyer)
+        }
+        Step::Confirm { order, signer } => {
+            let signer = keypair(harness, signer);
+            let ix = instructions::confirm_delivery(&PROGRAM_ID, &signer.pubkey(), &seller, ORDERS[order]);
+            (ix.unwrap(), signer)
+        }
+        Step::Dispute { order, signer } => {
+            let signer = keypair(harness, signer);
+            let ix = instructions::lock_dispute(&PROGRAM_ID, &signer.pubkey(), ORDERS[order]);
+            (ix.unwrap(), signer)
+        }
+        Step::Resolve { order, signer, resolution } => {
+            let buyer = buyer_of(order, signer);
+            let signer = keypair(harness, signer);
+            let ix = instructions::resolve(&PROGRAM_ID, &signer.pubkey(), &buyer, &seller, ORDERS[order], resolution);
+            (ix.unwrap(), signer)
+        }
+    }
+}
+
+/// Lamports held by the actors and both escrow accounts. Fees are paid by
+/// the harness payer, so this only changes if the program mints or burns.
+async fn total(harness: &mut Harness, escrows: &[Pubkey]) -> u64 {
+    let mut keys: Vec<Pubkey> = [&harness.buyer, &harness.seller, &harness.admin, &harness.stranger]
+        .iter()
+        .map(|key| key.pubkey())
+        .collect();
+    keys.extend_from_slice(escrows);
+    let mut total = 0;
+    for key in &keys {
+        total += harness.balance(key).await;
+    }
+    total
+}
+
+async fn run(steps: Vec<Step>) -> Result<(), TestCaseError> {
+    let mut harness = Harness::configured().await;
+    let reserve = harness.escrow_reserve().await;
+    let escrows: Vec<Pubkey> = ORDERS
+        .iter()
+        .map(|order_id| pda::escrow_address(&PROGRAM_ID, order_id).unwrap().0)
+        .collect();
+    let lamports = total(&mut harness, &escrows).await;
+    let mut model: Model = [None; ORDERS.len()];
+    // Statuses read from the chain, independent of the model.
+    let mut observed: [Option<EscrowStatus>; ORDERS.len()] = [None; ORDERS.len()];
+
+    for step in steps {
+        let (instruction, signer) = build(&harness, &model, step);
+        let result = harness.send(instruction, &signer).await;
+        let expected = expect(&model, step);
+        prop_assert_eq!(result.is_ok(), expected.is_some(), "{:?} gave {:?}", step, result);
+
+        if let Some(order) = expected {
+            let index = match step {
+                Step::Create { order, .. }
+                | Step::Confirm { order, .. }
+                | Step::Dispute { order, .. }
+                | Step::Resolve { order, .. } => order,
+            };
+            model[index] = Some(order);
+        }
+
+        for (index, order_id) in ORDERS.iter().enumerate() {
+            let status = harness.escrow(order_id).await.map(|escrow| escrow.status);
+            if let Some(settled) = observed[index].filter(|status| status.is_settled()) {
+                prop_assert_eq!(status, Some(settled), "settled {} changed by {:?}", order_id, step);
+            }
+            observed[index] = status;
+            prop_assert_eq!(status, model[index].map(|o| o.status), "{} after {:?}", order_id, step);
+
+            let held = harness.balance(&escrows[index]).await;
+            let expected_held = match model[index] {
+                None => 0,
+                Some(o) if o.status.is_settled() => reserve,
+                Some(_) => reserve + PRICE,
+            };
+            prop_assert_eq!(held, expected_held, "{} balance after {:?}", order_id, step);
+        }
+        prop_assert_eq!(total(&mut harness, &escrows).await, lamports, "lamports changed by {:?}", step);
+    }
+    Ok(())
+}
+
+fn cases() -> u32 {
+    std::env::var("PROPTEST_CASES").ok().and_then(|cases| cases.parse().ok()).unwrap_or(32)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(cases()))]
+
+    #[test]
+    fn escrow_state_machine(steps in prop::collection::vec(step(), 1..16)) {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(run(steps))?;
+    }
+}
@@ -0,0 +1,102 @@
+//! Network profiles.
+//!
+//! Profiles live in `~/.config/proofcart/cli.toml`:
+//!
+//! ```toml
+//! [profiles.devnet]
+//! program_id = "..."
+//! nft_canister_id = "..."
+//! identity = "minter"
+//!
+//! [profiles.mainnet]
+//! rpc_url = "https://my-rpc.example.com"
+//! program_id = "..."
+//! keypair = "usb://ledger"
+//! ```
+//!
+//! `devnet`, `mainnet` and `localnet` have default Solana RPC and IC URLs
+//! (`devnet` uses the IC mainnet, which has no test network). Command-line
+//! flags override the profile.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct Profile {
+    pub rpc_url: Option<String>,
+    /// Escrow program id (base58).
+    pub program_id: Option<String>,
+    /// Keypair file or `usb://ledger` URI.
+    pub keypair: Option<String>,
+    /// IC API URL.
+    pub ic_url: Option<String>,
+    /// NFT canister (or shard) id.
+    pub nft_canister_id: Option<String>,
+    /// dfx identity name, PEM file path or `anonymous`.
+    pub identity: Option<String>,
+    /// Indexer database URL, for `reconcile`.
+    pub database_url: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+fn home() -> PathBuf {
+    std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default()
+}
+
+pub fn config_path() -> PathBuf {
+    home().join(".config/proofcart/cli.toml")
+}
+
+/// Default keypair, as for the Solana CLI.
+pub fn default_keypair() -> String {
+    home().join(".config/solana/id.json").display().to_string()
+}
+
+fn default_ic_url(profile: &str) -> Option<&'static str> {
+    match profile {
+        "devnet" | "mainnet" => Some("https://ic0.app"),
+        "localnet" => Some("http://127.0.0.1:4943"),
+        _ => None,
+    }
+}
+
+fn default_rpc_url(profile: &str) -> Option<&'static str> {
+    match profile {
+        "devnet" => Some("https://api.devnet.solana.com"),
+        "mainnet" => Some("https://api.mainnet-beta.solana.com"),
+        "localnet" => Some("http://127.0.0.1:8899"),
+        _ => None,
+    }
+}
+
+pub fn load() -> Result<Config, String> {
+    let path = config_path();
+    match std::fs::read_to_string(&path) {
+        Ok(text) => toml::from_str(&text).map_err(|e| format!("Invalid {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// Profile `name` with default URLs and keypair filled in.
+pub fn profile(config: &Config, name: &str) -> Result<Profile, String> {
+    let mut profile = config.profiles.get(name).cloned().unwrap_or_default();
+    if profile.rpc_url.is_none() {
+        profile.rpc_url = default_rpc_url(name).map(str::to_string);
+    }
+    if profile.ic_url.is_none() {
+        profile.ic_url = default_ic_url(name).map(str::to_string);
+    }
+    if default_rpc_url(name).is_none() && !config.profiles.contains_key(name) {
+        return Err(format!("Unknown profile {}; define it in {}", name, config_path().display()));
+    }
+    profile.keypair.get_or_insert_with(default_keypair);
+    Ok(profile)
+}
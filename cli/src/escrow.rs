@@ -0,0 +1,213 @@
+//! `proofcart escrow ...`
+
+use clap::{ArgMatches, Subcommand, ValueEnum};
+use proofcart_solana_client::{pda, Escrow, EscrowClient, Resolution};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+
+use crate::Context;
+
+const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+#[derive(Subcommand)]
+pub enum EscrowCommand {
+    /// Open an escrow for an order, paid by the keypair
+    Create {
+        #[clap(long)]
+        order_id: String,
+        #[clap(long)]
+        seller: Pubkey,
+        /// Amount in SOL, e.g. 0.25
+        #[clap(long)]
+        amount: String,
+    },
+    /// Buyer: confirm delivery and pay the seller
+    Release {
+        #[clap(long)]
+        order_id: String,
+    },
+    /// Buyer: dispute the order, locking the escrow for an admin
+    Dispute {
+        #[clap(long)]
+        order_id: String,
+    },
+    /// Admin: settle a disputed escrow
+    Resolve {
+        #[clap(long)]
+        order_id: String,
+        #[clap(long, value_enum)]
+        outcome: Outcome,
+    },
+    /// Upgrade authority: name the admin that resolves disputes (once)
+    InitConfig {
+        #[clap(long)]
+        admin: Pubkey,
+    },
+    /// Show one escrow
+    Status {
+        #[clap(long)]
+        order_id: String,
+    },
+    /// List escrows, optionally of one buyer and/or seller
+    List {
+        #[clap(long)]
+        buyer: Option<Pubkey>,
+        #[clap(long)]
+        seller: Option<Pubkey>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum Outcome {
+    /// Return the funds to the buyer
+    Refund,
+    /// Pay the seller
+    Release,
+}
+
+/// Parse a SOL amount with up to 9 decimals into lamports, exactly.
+pub fn parse_sol(amount: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid SOL amount: {}", amount);
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if (whole.is_empty() && fraction.is_empty()) || fraction.len() > 9 {
+        return Err(invalid());
+    }
+    let digits = |s: &str| s.is_empty() || s.chars().all(|c| c.is_ascii_digit());
+    if !digits(whole) || !digits(fraction) {
+        return Err(invalid());
+    }
+    let whole: u64 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| invalid())? };
+    let fraction: u64 = format!("{:0<9}", fraction).parse().map_err(|_| invalid())?;
+    whole
+        .checked_mul(LAMPORTS_PER_SOL)
+        .and_then(|lamports| lamports.checked_add(fraction))
+        .ok_or_else(invalid)
+}
+
+pub fn format_sol(lamports: u64) -> String {
+    let fraction = format!("{:09}", lamports % LAMPORTS_PER_SOL);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        format!("{} SOL", lamports / LAMPORTS_PER_SOL)
+    } else {
+        format!("{}.{} SOL", lamports / LAMPORTS_PER_SOL, fraction)
+    }
+}
+
+fn format_time(timestamp: Option<i64>) -> String {
+    timestamp.map_or("-".to_string(), |t| t.to_string())
+}
+
+fn print_escrow(address: &Pubkey, escrow: &Escrow) {
+    println!("Order:     {}", escrow.order_id);
+    println!("Address:   {}", address);
+    println!("Status:    {:?}", escrow.status);
+    println!("Amount:    {} ({} lamports)", format_sol(escrow.amount), escrow.amount);
+    println!("Buyer:     {}", escrow.buyer);
+    println!("Seller:    {}", escrow.seller);
+    println!("Created:   {}", escrow.created_at);
+    println!("Locked:    {}", format_time(escrow.locked_at));
+    println!("Released:  {}", format_time(escrow.released_at));
+    println!("Resolved:  {}", format_time(escrow.resolved_at));
+}
+
+async fn print_status(client: &EscrowClient, order_id: &str) -> Result<(), String> {
+    let (address, _) = pda::escrow_address(&client.program_id(), order_id).map_err(|e| e.to_string())?;
+    match client.fetch_escrow(order_id).await.map_err(|e| e.to_string())? {
+        Some(escrow) => {
+            print_escrow(&address, &escrow);
+            Ok(())
+        }
+        None => Err(format!("No escrow for order {} (expected at {})", order_id, address)),
+    }
+}
+
+fn sent(signature: Signature) {
+    println!("Signature: {}", signature);
+    println!();
+}
+
+pub async fn run(command: &EscrowCommand, context: &Context, matches: &ArgMatches) -> Result<(), String> {
+    let client = EscrowClient::new(context.rpc()?, context.program_id()?);
+    match command {
+        EscrowCommand::Create { order_id, seller, amount } => {
+            let lamports = parse_sol(amount)?;
+            let signer = context.signer(matches)?;
+            let signature = client
+                .create_escrow(signer.as_ref(), *seller, order_id, lamports)
+                .await
+                .map_err(|e| e.to_string())?;
+            sent(signature);
+            print_status(&client, order_id).await
+        }
+        EscrowCommand::Release { order_id } => {
+            let signer = context.signer(matches)?;
+            let signature = client.release(signer.as_ref(), order_id).await.map_err(|e| e.to_string())?;
+            sent(signature);
+            print_status(&client, order_id).await
+        }
+        EscrowCommand::Dispute { order_id } => {
+            let signer = context.signer(matches)?;
+            let signature = client.dispute(signer.as_ref(), order_id).await.map_err(|e| e.to_string())?;
+            sent(signature);
+            print_status(&client, order_id).await
+        }
+        EscrowCommand::Resolve { order_id, outcome } => {
+            let resolution = match outcome {
+                Outcome::Refund => Resolution::Refund,
+                Outcome::Release => Resolution::Release,
+            };
+            let signer = context.signer(matches)?;
+            let signature = client
+                .resolve(signer.as_ref(), order_id, resolution)
+                .await
+                .map_err(|e| e.to_string())?;
+            sent(signature);
+            print_status(&client, order_id).await
+        }
+        EscrowCommand::InitConfig { admin } => {
+            let signer = context.signer(matches)?;
+            let signature = client
+                .initialize_config(signer.as_ref(), *admin)
+                .await
+                .map_err(|e| e.to_string())?;
+            sent(signature);
+            println!("Admin:     {}", admin);
+            Ok(())
+        }
+        EscrowCommand::Status { order_id } => print_status(&client, order_id).await,
+        EscrowCommand::List { buyer, seller } => {
+            let mut escrows = client.list_escrows(*buyer, *seller).await.map_err(|e| e.to_string())?;
+            escrows.sort_by_key(|(_, escrow)| escrow.created_at);
+            for (_, escrow) in &escrows {
+                println!(
+                    "{:<32}  {:<8}  {:>16}  buyer {}  seller {}",
+                    escrow.order_id,
+                    format!("{:?}", escrow.status),
+                    format_sol(escrow.amount),
+                    escrow.buyer,
+                    escrow.seller
+                );
+            }
+            println!("{} escrow(s)", escrows.len());
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sol_amounts_convert_exactly() {
+        assert_eq!(parse_sol("0.25"), Ok(250_000_000));
+        assert_eq!(parse_sol("1"), Ok(LAMPORTS_PER_SOL));
+        assert_eq!(parse_sol(".000000001"), Ok(1));
+        assert!(parse_sol("0.0000000001").is_err());
+        assert!(parse_sol("1e3").is_err());
+        assert!(parse_sol(".").is_err());
+        assert_eq!(format_sol(250_000_000), "0.25 SOL");
+        assert_eq!(format_sol(2 * LAMPORTS_PER_SOL), "2 SOL");
+    }
+}
@@ -0,0 +1,485 @@
+//! `proofcart fixtures ...`: a realistic test environment on devnet or
+//! localnet in one command, for QA and frontend work.
+//!
+//! `create` makes funded buyer and seller wallets, a test USDC mint with a
+//! balance in every wallet, registers the ICP identity as a manufacturer,
+//! mints sample products, and opens one escrow in each state. Everything it
+//! made is listed in `fixtures.json` next to the wallets' keypair files.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::{ArgMatches, Subcommand};
+use proofcart_icp_client::types::MintRequest;
+use proofcart_metadata_schema::Caps;
+use proofcart_solana_client::{EscrowClient, EscrowStatus, Resolution};
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, write_keypair_file, Keypair, Signature, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::Transaction;
+use spl_associated_token_account::get_associated_token_address;
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+
+use crate::escrow::{format_sol, parse_sol};
+use crate::Context;
+
+/// Test USDC has the real token's 6 decimals.
+const USDC_DECIMALS: u8 = 6;
+
+/// Largest airdrop asked for at once; the devnet faucet refuses more.
+const AIRDROP_CHUNK: u64 = 1_000_000_000;
+
+/// How long to wait for an airdrop before paying from the keypair instead.
+const AIRDROP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Order ids are escrow PDA seeds, at most 32 bytes; the longest suffix is
+/// `-admin-released`.
+const MAX_TAG_LEN: usize = 16;
+
+/// Sample products, one per category the metadata schema has rules for
+/// plus some without.
+const PRODUCTS: &[(&str, &str, &str)] = &[
+    ("Redmi Note 14 Pro", "Smartphones", r#"{"imei":"356938035643809","storage":"256GB"}"#),
+    ("Noise-Cancelling Headphones", "Electronics", r#"{"battery_hours":"30"}"#),
+    ("Vitamin C Serum", "Cosmetics", r#"{"batch_number":"VC-2024-117","volume":"30ml"}"#),
+    ("Leather Sneakers", "Apparel", r#"{"size":"42","material":"leather"}"#),
+    ("Mechanical Watch", "Watches", r#"{"movement":"automatic"}"#),
+    ("Espresso Machine", "Home Appliances", r#"{"power":"1350W"}"#),
+];
+
+#[derive(Subcommand)]
+pub enum FixturesCommand {
+    /// Create wallets, test USDC, a manufacturer, sample NFTs and escrows
+    /// in every state, paid by the keypair
+    Create {
+        /// Where the wallets' keypairs and fixtures.json are written
+        #[clap(long, default_value = "fixtures")]
+        out_dir: PathBuf,
+        /// Buyers to create, and as many sellers
+        #[clap(long, default_value = "3")]
+        wallets: usize,
+        /// SOL per wallet, e.g. 2
+        #[clap(long, default_value = "2")]
+        sol: String,
+        /// Test USDC minted to each wallet, in whole tokens
+        #[clap(long, default_value = "1000")]
+        usdc: u64,
+        /// Sample NFTs to mint
+        #[clap(long, default_value = "6")]
+        nfts: usize,
+        /// SOL held by each sample escrow
+        #[clap(long, default_value = "0.05")]
+        escrow_amount: String,
+        /// Name the ICP identity is registered under
+        #[clap(long, default_value = "ProofCart Test Manufacturer")]
+        manufacturer: String,
+        /// Escrow admin keypair, to also leave escrows refunded and released
+        /// by an admin
+        #[clap(long)]
+        escrow_admin_keypair: Option<PathBuf>,
+        /// Prefix of serials and order ids, at most 16 characters; unique
+        /// per run by default
+        #[clap(long)]
+        tag: Option<String>,
+        /// Only create the Solana fixtures
+        #[clap(long)]
+        skip_icp: bool,
+    },
+    /// Fund an address with SOL, by airdrop or else from the keypair, and
+    /// optionally with test USDC
+    Faucet {
+        address: Pubkey,
+        /// SOL to send, e.g. 2
+        #[clap(long, default_value = "2")]
+        sol: String,
+        /// Test USDC to mint, in whole tokens; the keypair must be the
+        /// mint's authority
+        #[clap(long, requires = "usdc_mint")]
+        usdc: Option<u64>,
+        /// The test USDC mint, from fixtures.json
+        #[clap(long)]
+        usdc_mint: Option<Pubkey>,
+    },
+}
+
+#[derive(Serialize)]
+struct Wallet {
+    role: &'static str,
+    address: String,
+    keypair: String,
+    usdc_account: String,
+}
+
+#[derive(Serialize)]
+struct SampleNft {
+    nft_id: u64,
+    serial_number: String,
+    product_name: String,
+    category: String,
+}
+
+#[derive(Serialize)]
+struct SampleEscrow {
+    order_id: String,
+    /// `created`, `locked`, `released` or `refunded`.
+    status: String,
+    /// How it got there, e.g. `dispute, resolve release`.
+    steps: &'static str,
+    buyer: String,
+    seller: String,
+    amount_lamports: u64,
+}
+
+/// `fixtures.json`.
+#[derive(Serialize)]
+struct Manifest {
+    tag: String,
+    rpc_url: String,
+    program_id: String,
+    usdc_mint: String,
+    usdc_mint_authority: String,
+    wallets: Vec<Wallet>,
+    /// `None` with `--skip-icp`.
+    nft_canister_id: Option<String>,
+    manufacturer: Option<String>,
+    nfts: Vec<SampleNft>,
+    escrows: Vec<SampleEscrow>,
+}
+
+/// Fixtures cost real money on mainnet, and would pollute its data.
+fn refuse_mainnet(rpc: &RpcClient) -> Result<(), String> {
+    if rpc.url().contains("mainnet") {
+        return Err(format!("{} is mainnet; fixtures are for devnet and localnet", rpc.url()));
+    }
+    Ok(())
+}
+
+fn default_tag() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("FX{}", now.as_secs())
+}
+
+fn status_name(status: EscrowStatus) -> String {
+    format!("{:?}", status).to_lowercase()
+}
+
+async fn send(rpc: &RpcClient, instructions: &[Instruction], signers: &[&dyn Signer]) -> Result<Signature, String> {
+    let blockhash = rpc.get_latest_blockhash().await.map_err(|e| e.to_string())?;
+    let payer = signers[0].pubkey();
+    let transaction = Transaction::new_signed_with_payer(instructions, Some(&payer), &signers.to_vec(), blockhash);
+    rpc.send_and_confirm_transaction(&transaction)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Airdrop `lamports` to `to`, one faucet-sized request at a time, and
+/// transfer whatever the faucet would not give from `payer`. Devnet's
+/// faucet is rate limited and often dry.
+async fn faucet(rpc: &RpcClient, payer: &dyn Signer, to: &Pubkey, lamports: u64) -> Result<(), String> {
+    let mut left = lamports;
+    while left > 0 {
+        let chunk = left.min(AIRDROP_CHUNK);
+        if !airdrop(rpc, to, chunk).await {
+            break;
+        }
+        left -= chunk;
+    }
+    if left > 0 {
+        println!("  faucet refused; paying {} from {}", format_sol(left), payer.pubkey());
+        send(rpc, &[system_instruction::transfer(&payer.pubkey(), to, left)], &[payer])
+            .await
+            .map_err(|e| format!("Funding {}: {}", to, e))?;
+    }
+    Ok(())
+}
+
+/// Whether an airdrop of `lamports` landed in time.
+async fn airdrop(rpc: &RpcClient, to: &Pubkey, lamports: u64) -> bool {
+    let Ok(signature) = rpc.request_airdrop(to, lamports).await else {
+        return false;
+    };
+    let started = std::time::Instant::now();
+    while started.elapsed() < AIRDROP_TIMEOUT {
+        if let Ok(true) = rpc.confirm_transaction(&signature).await {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    false
+}
+
+/// Create a test USDC mint with `authority` as its mint authority.
+async fn create_usdc_mint(rpc: &RpcClient, payer: &dyn Signer, authority: &Pubkey) -> Result<Pubkey, String> {
+    let mint = Keypair::new();
+    let rent = rpc
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)
+        .await
+        .map_err(|e| e.to_string())?;
+    let instructions = [
+        system_instruction::create_account(
+            &payer.pubkey(),
+            &mint.pubkey(),
+            rent,
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_mint2(&spl_token::id(), &mint.pubkey(), authority, None, USDC_DECIMALS)
+            .map_err(|e| e.to_string())?,
+    ];
+    send(rpc, &instructions, &[payer, &mint])
+        .await
+        .map_err(|e| format!("Creating the test USDC mint: {}", e))?;
+    Ok(mint.pubkey())
+}
+
+/// Mint `tokens` whole test USDC to `owner`'s associated token account,
+/// creating it if needed. `authority` pays and signs.
+async fn mint_usdc(
+    rpc: &RpcClient,
+    authority: &dyn Signer,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    tokens: u64,
+) -> Result<Pubkey, String> {
+    let account = get_associated_token_address(owner, mint);
+    let amount = tokens
+        .checked_mul(10u64.pow(USDC_DECIMALS as u32))
+        .ok_or_else(|| format!("{} USDC is too much", tokens))?;
+    let instructions = [
+        create_associated_token_account_idempotent(&authority.pubkey(), owner, mint, &spl_token::id()),
+        spl_token::instruction::mint_to(&spl_token::id(), mint, &account, &authority.pubkey(), &[], amount)
+            .map_err(|e| e.to_string())?,
+    ];
+    send(rpc, &instructions, &[authority])
+        .await
+        .map_err(|e| format!("Minting test USDC to {}: {}", owner, e))?;
+    Ok(account)
+}
+
+fn save_keypair(out_dir: &Path, name: &str, keypair: &Keypair) -> Result<String, String> {
+    let path = out_dir.join(format!("{}.json", name));
+    write_keypair_file(keypair, &path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path.display().to_string())
+}
+
+fn sample_mint(tag: &str, manufacturer: &str, i: usize) -> Result<MintRequest, String> {
+    let (product_name, category, specifications) = PRODUCTS[i % PRODUCTS.len()];
+    let serial_number = format!("{}-NFT-{}", tag, i + 1);
+    let request = MintRequest {
+        idempotency_key: Some(serial_number.clone()),
+        serial_number,
+        product_name: product_name.to_string(),
+        manufacturer: manufacturer.to_string(),
+        manufacture_date: "2024-06-01".to_string(),
+        category: category.to_string(),
+        description: format!("Sample {} for testing", product_name),
+        specifications: specifications.to_string(),
+        warranty_info: "12 months".to_string(),
+        ..MintRequest::default()
+    };
+    proofcart_metadata_schema::validate(&request.metadata(), &Caps::default()).map_err(|e| e.to_string())?;
+    Ok(request)
+}
+
+pub async fn run(command: &FixturesCommand, context: &Context, matches: &ArgMatches) -> Result<(), String> {
+    match command {
+        FixturesCommand::Create {
+            out_dir,
+            wallets,
+            sol,
+            usdc,
+            nfts,
+            escrow_amount,
+            manufacturer,
+            escrow_admin_keypair,
+            tag,
+            skip_icp,
+        } => {
+            let tag = tag.clone().unwrap_or_else(default_tag);
+            if tag.is_empty() || tag.len() > MAX_TAG_LEN {
+                return Err(format!("--tag must be 1 to {} characters", MAX_TAG_LEN));
+            }
+            if *wallets == 0 {
+                return Err("--wallets must be at least 1".to_string());
+            }
+            let lamports = parse_sol(sol)?;
+            let escrow_lamports = parse_sol(escrow_amount)?;
+            let escrow_admin = match escrow_admin_keypair {
+                Some(path) => Some(read_keypair_file(path).map_err(|e| format!("{}: {}", path.display(), e))?),
+                None => None,
+            };
+            let rpc = context.rpc()?;
+            refuse_mainnet(&rpc)?;
+            let program_id = context.program_id()?;
+            // Connect before spending anything, so a missing canister id
+            // fails early.
+            let nft = if *skip_icp { None } else { Some(context.nft_client().await?) };
+            let payer = context.signer(matches)?;
+            std::fs::create_dir_all(out_dir)
+                .map_err(|e| format!("Failed to create {}: {}", out_dir.display(), e))?;
+
+            println!("Wallets ({} each)", format_sol(lamports));
+            let mut keypairs = Vec::new();
+            for role in ["buyer", "seller"] {
+                for i in 1..=*wallets {
+                    let keypair = Keypair::new();
+                    let name = format!("{}-{}", role, i);
+                    let path = save_keypair(out_dir, &name, &keypair)?;
+                    faucet(&rpc, payer.as_ref(), &keypair.pubkey(), lamports).await?;
+                    println!("  {}  {}", name, keypair.pubkey());
+                    keypairs.push((role, keypair, path));
+                }
+            }
+
+            println!("Test USDC ({} each)", usdc);
+            let usdc_mint = create_usdc_mint(&rpc, payer.as_ref(), &payer.pubkey()).await?;
+            println!("  mint  {}", usdc_mint);
+            let mut manifest_wallets = Vec::new();
+            for (role, keypair, path) in &keypairs {
+                let account = mint_usdc(&rpc, payer.as_ref(), &usdc_mint, &keypair.pubkey(), *usdc).await?;
+                manifest_wallets.push(Wallet {
+                    role: *role,
+                    address: keypair.pubkey().to_string(),
+                    keypair: path.clone(),
+                    usdc_account: account.to_string(),
+                });
+            }
+
+            let mut sample_nfts = Vec::new();
+            if let Some(client) = &nft {
+                let principal = client.agent().get_principal()?;
+                client
+                    .register_manufacturer(principal, manufacturer)
+                    .await
+                    .map_err(|e| format!("Registering {} (needs an Admin identity): {}", principal, e))?;
+                println!("Manufacturer {} ({})", manufacturer, principal);
+                for i in 0..*nfts {
+                    let request = sample_mint(&tag, manufacturer, i)?;
+                    let minted = client.mint_product_nft(&request).await.map_err(|e| e.to_string())?;
+                    println!("  {}  #{}  {}", minted.serial_number, minted.nft_id, request.product_name);
+                    sample_nfts.push(SampleNft {
+                        nft_id: minted.nft_id,
+                        serial_number: minted.serial_number,
+                        product_name: request.product_name,
+                        category: request.category,
+                    });
+                }
+            }
+
+            println!("Escrows ({} each)", format_sol(escrow_lamports));
+            let escrow = EscrowClient::new(rpc, program_id);
+            let buyers: Vec<&Keypair> = keypairs.iter().filter(|k| k.0 == "buyer").map(|k| &k.1).collect();
+            let sellers: Vec<&Keypair> = keypairs.iter().filter(|k| k.0 == "seller").map(|k| &k.1).collect();
+            let mut plans = vec![
+                ("created", "create"),
+                ("released", "confirm delivery"),
+                ("disputed", "dispute"),
+            ];
+            if escrow_admin.is_some() {
+                plans.push(("refunded", "dispute, resolve refund"));
+                plans.push(("admin-released", "dispute, resolve release"));
+            } else {
+                println!("  no --escrow-admin-keypair: skipping refunded and admin-released escrows");
+            }
+            let mut sample_escrows = Vec::new();
+            for (i, (suffix, steps)) in plans.into_iter().enumerate() {
+                let order_id = format!("{}-{}", tag, suffix);
+                let buyer = buyers[i % buyers.len()];
+                let seller = sellers[i % sellers.len()];
+                let fail = |e: proofcart_solana_client::Error| format!("Escrow {}: {}", order_id, e);
+                escrow
+                    .create_escrow(buyer, seller.pubkey(), &order_id, escrow_lamports)
+                    .await
+                    .map_err(fail)?;
+                match suffix {
+                    "released" => {
+                        escrow.release(buyer, &order_id).await.map_err(fail)?;
+                    }
+                    "disputed" | "refunded" | "admin-released" => {
+                        escrow.dispute(buyer, &order_id).await.map_err(fail)?;
+                        if let Some(admin) = &escrow_admin {
+                            let resolution = match suffix {
+                                "refunded" => Some(Resolution::Refund),
+                                "admin-released" => Some(Resolution::Release),
+                                _ => None,
+                            };
+                            if let Some(resolution) = resolution {
+                                escrow.resolve(admin, &order_id, resolution).await.map_err(fail)?;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                let state = escrow
+                    .fetch_escrow(&order_id)
+                    .await
+                    .map_err(fail)?
+                    .ok_or_else(|| format!("Escrow {} vanished", order_id))?;
+                println!("  {}  {:?}", order_id, state.status);
+                sample_escrows.push(SampleEscrow {
+                    order_id,
+                    status: status_name(state.status),
+                    steps,
+                    buyer: buyer.pubkey().to_string(),
+                    seller: seller.pubkey().to_string(),
+                    amount_lamports: escrow_lamports,
+                });
+            }
+
+            let manifest = Manifest {
+                tag,
+                rpc_url: escrow.rpc().url(),
+                program_id: program_id.to_string(),
+                usdc_mint: usdc_mint.to_string(),
+                usdc_mint_authority: payer.pubkey().to_string(),
+                wallets: manifest_wallets,
+                nft_canister_id: nft.as_ref().map(|client| client.canister_id().to_text()),
+                manufacturer: nft.as_ref().map(|_| manufacturer.clone()),
+                nfts: sample_nfts,
+                escrows: sample_escrows,
+            };
+            let path = out_dir.join("fixtures.json");
+            let json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+            std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            println!("Wrote {}", path.display());
+            Ok(())
+        }
+        FixturesCommand::Faucet { address, sol, usdc, usdc_mint } => {
+            let lamports = parse_sol(sol)?;
+            let rpc = context.rpc()?;
+            refuse_mainnet(&rpc)?;
+            let payer = context.signer(matches)?;
+            faucet(&rpc, payer.as_ref(), address, lamports).await?;
+            println!("Sent {} to {}", format_sol(lamports), address);
+            if let (Some(tokens), Some(mint)) = (usdc, usdc_mint) {
+                let account = mint_usdc(&rpc, payer.as_ref(), mint, address, *tokens).await?;
+                println!("Minted {} test USDC to {}", tokens, account);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_products_pass_the_metadata_schema() {
+        for i in 0..PRODUCTS.len() {
+            let request = sample_mint("FX1718000000", "ProofCart Test Manufacturer", i).unwrap();
+            assert_eq!(request.serial_number, format!("FX1718000000-NFT-{}", i + 1));
+        }
+    }
+
+    #[test]
+    fn order_ids_fit_the_escrow_seed() {
+        let tag = "X".repeat(MAX_TAG_LEN);
+        assert!(format!("{}-admin-released", tag).len() <= 32);
+        assert!(default_tag().len() <= MAX_TAG_LEN);
+    }
+}
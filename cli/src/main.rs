@@ -0,0 +1,233 @@
+//! `proofcart`: operator CLI for the ProofCart escrow program and NFT
+//! canister.
+//!
+//! ```text
+//! proofcart escrow create --order-id ORD-1001 --seller <PUBKEY> --amount 0.25
+//! proofcart --profile mainnet -k usb://ledger escrow resolve --order-id ORD-1001 --outcome refund
+//! proofcart escrow status --order-id ORD-1001
+//! proofcart --identity minter nft mint --csv serials.csv
+//! proofcart nft verify SN-1001
+//! proofcart qr generate --serials run-42.txt --signing-key qr-key.json --out-dir labels/
+//! proofcart --profile localnet fixtures create --escrow-admin-keypair admin.json
+//! proofcart --identity admin migrate export --legacy-canister <OLD_CANISTER_ID> --out legacy.jsonl
+//! proofcart reconcile --orders orders-2024-06-10.csv --out close-2024-06-10.json
+//! ```
+
+mod config;
+mod escrow;
+mod fixtures;
+mod migrate;
+mod nft;
+mod qr;
+mod reconcile;
+
+use std::process::ExitCode;
+
+use candid::Principal;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use ic_agent::Identity;
+use proofcart_icp_client::{identity, NftClient};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+
+use config::Profile;
+use escrow::EscrowCommand;
+use fixtures::FixturesCommand;
+use migrate::MigrateCommand;
+use nft::NftCommand;
+use qr::QrCommand;
+use reconcile::ReconcileArgs;
+
+#[derive(Parser)]
+#[clap(name = "proofcart", version, about = "Operate ProofCart escrows and product NFTs")]
+struct Cli {
+    /// Network profile: devnet, mainnet, localnet or one defined in
+    /// ~/.config/proofcart/cli.toml
+    #[clap(long, global = true, default_value = "devnet")]
+    profile: String,
+
+    /// Solana RPC URL, overriding the profile
+    #[clap(long, global = true)]
+    url: Option<String>,
+
+    /// Escrow program id, overriding SOLANA_PROGRAM_ID and the profile
+    #[clap(long, global = true)]
+    program_id: Option<Pubkey>,
+
+    /// Keypair file, or usb://ledger to sign with a Ledger
+    #[clap(long, short = 'k', global = true)]
+    keypair: Option<String>,
+
+    /// IC API URL, overriding the profile
+    #[clap(long, global = true)]
+    ic_url: Option<String>,
+
+    /// NFT canister id, overriding ICP_CANISTER_ID and the profile
+    #[clap(long, global = true)]
+    canister_id: Option<Principal>,
+
+    /// dfx identity name, PEM file or "anonymous" for ICP calls
+    #[clap(long, global = true)]
+    identity: Option<String>,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create, settle and inspect escrows
+    #[clap(subcommand)]
+    Escrow(EscrowCommand),
+    /// Mint, verify, transfer and export product NFTs
+    #[clap(subcommand)]
+    Nft(NftCommand),
+    /// Generate and check signed product QR codes
+    #[clap(subcommand)]
+    Qr(QrCommand),
+    /// Compare marketplace orders with indexed escrows and NFT transfers
+    Reconcile(ReconcileArgs),
+    /// Set up test wallets, tokens, NFTs and escrows on devnet or localnet
+    #[clap(subcommand)]
+    Fixtures(FixturesCommand),
+    /// Move tokens from the retired nft_canister into the NFT canister
+    #[clap(subcommand)]
+    Migrate(MigrateCommand),
+}
+
+/// The selected profile with command-line overrides. Each setting is
+/// resolved when a command first needs it, so NFT commands do not require
+/// Solana settings and vice versa.
+pub struct Context {
+    profile_name: String,
+    profile: Profile,
+}
+
+impl Context {
+    fn new(cli: &Cli) -> Result<Self, String> {
+        let config = config::load()?;
+        let mut profile = config::profile(&config, &cli.profile)?;
+        if let Some(url) = &cli.url {
+            profile.rpc_url = Some(url.clone());
+        }
+        if let Some(program_id) = cli.program_id {
+            profile.program_id = Some(program_id.to_string());
+        } else if let Ok(program_id) = std::env::var("SOLANA_PROGRAM_ID") {
+            profile.program_id = Some(program_id);
+        }
+        if let Some(keypair) = &cli.keypair {
+            profile.keypair = Some(keypair.clone());
+        }
+        if let Some(ic_url) = &cli.ic_url {
+            profile.ic_url = Some(ic_url.clone());
+        }
+        if let Some(canister_id) = cli.canister_id {
+            profile.nft_canister_id = Some(canister_id.to_text());
+        } else if let Ok(canister_id) = std::env::var("ICP_CANISTER_ID") {
+            profile.nft_canister_id = Some(canister_id);
+        }
+        if let Ok(database_url) = std::env::var("DATABASE_URL") {
+            profile.database_url = Some(database_url);
+        }
+        if let Some(identity) = &cli.identity {
+            profile.identity = Some(identity.clone());
+        }
+        Ok(Self {
+            profile_name: cli.profile.clone(),
+            profile,
+        })
+    }
+
+    fn setting<'a>(&self, value: &'a Option<String>, name: &str, hint: &str) -> Result<&'a str, String> {
+        value
+            .as_deref()
+            .ok_or_else(|| format!("No {}: {} or add it to profile {}", name, hint, self.profile_name))
+    }
+
+    pub fn program_id(&self) -> Result<Pubkey, String> {
+        self.setting(
+            &self.profile.program_id,
+            "escrow program id",
+            "pass --program-id, set SOLANA_PROGRAM_ID",
+        )?
+        .parse()
+        .map_err(|e| format!("Invalid program id: {}", e))
+    }
+
+    pub fn rpc(&self) -> Result<RpcClient, String> {
+        let url = self.setting(&self.profile.rpc_url, "Solana RPC URL", "pass --url")?;
+        Ok(RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed()))
+    }
+
+    /// The keypair file or Ledger; a Ledger asks for confirmation on the device.
+    pub fn signer(&self, matches: &ArgMatches) -> Result<Box<dyn Signer>, String> {
+        let keypair = self.profile.keypair.clone().unwrap_or_else(config::default_keypair);
+        let mut wallet_manager = None;
+        solana_clap_v3_utils::keypair::signer_from_path(matches, &keypair, "keypair", &mut wallet_manager)
+            .map_err(|e| format!("Failed to load signer {}: {}", keypair, e))
+    }
+
+    /// ICP identity: the configured one, else dfx's `default` identity if
+    /// it exists, else anonymous.
+    fn identity(&self) -> Result<Box<dyn Identity>, String> {
+        match self.profile.identity.as_deref() {
+            Some("anonymous") => Ok(identity::anonymous()),
+            Some(path) if path.ends_with(".pem") || path.contains('/') => {
+                identity::from_pem_file(path).map_err(|e| e.to_string())
+            }
+            Some(name) => identity::from_dfx(name).map_err(|e| e.to_string()),
+            None => Ok(identity::from_dfx("default").unwrap_or_else(|_| identity::anonymous())),
+        }
+    }
+
+    pub fn nft_canister_id(&self) -> Result<Principal, String> {
+        let canister_id = self.setting(
+            &self.profile.nft_canister_id,
+            "NFT canister id",
+            "pass --canister-id, set ICP_CANISTER_ID",
+        )?;
+        Principal::from_text(canister_id).map_err(|e| format!("Invalid canister id: {}", e))
+    }
+
+    pub fn database_url(&self) -> Result<&str, String> {
+        self.setting(
+            &self.profile.database_url,
+            "indexer database",
+            "pass --database-url, set DATABASE_URL",
+        )
+    }
+
+    pub async fn nft_client(&self) -> Result<NftClient, String> {
+        let url = self.setting(&self.profile.ic_url, "IC URL", "pass --ic-url")?;
+        NftClient::connect(url, self.identity()?, self.nft_canister_id()?)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+async fn run() -> Result<(), String> {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).map_err(|e| e.to_string())?;
+    let context = Context::new(&cli)?;
+    match &cli.command {
+        Command::Escrow(command) => escrow::run(command, &context, &matches).await,
+        Command::Nft(command) => nft::run(command, &context).await,
+        Command::Qr(command) => qr::run(command, &context).await,
+        Command::Reconcile(args) => reconcile::run(args, &context).await,
+        Command::Fixtures(command) => fixtures::run(command, &context, &matches).await,
+        Command::Migrate(command) => migrate::run(command, &context).await,
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,421 @@
+//! `proofcart migrate ...`: move the tokens of the retired `nft_canister`
+//! into the NFT canister, keeping ids and serials.
+//!
+//! `export` snapshots the old canister to JSON lines, `import` replays the
+//! snapshot into a freshly installed canister in batches, `verify` compares
+//! the two token by token and writes a report, and `finish` closes the
+//! import. The snapshot is the single source for the last three, so what
+//! was imported is exactly what is verified.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use candid::Principal;
+use clap::Subcommand;
+use proofcart_icp_client::types::{ImportState, NftCanisterNFT, ProductNFT};
+use serde::Serialize;
+
+use crate::Context;
+
+/// `import_legacy_nfts` takes at most this many tokens per call.
+const MAX_BATCH: usize = 500;
+
+/// Characters the canister drops from serials, as its `serials::normalize`.
+const SERIAL_SEPARATORS: &[char] = &['-', '_', ' ', '.', '/', ':'];
+
+#[derive(Subcommand)]
+pub enum MigrateCommand {
+    /// Read every token of the old canister into a JSON lines snapshot
+    Export {
+        /// The retired nft_canister
+        #[clap(long)]
+        legacy_canister: Principal,
+        #[clap(long)]
+        out: PathBuf,
+    },
+    /// SuperAdmin: import a snapshot into the canister; re-run to resume
+    Import {
+        /// Snapshot written by `migrate export`
+        #[clap(long)]
+        from: PathBuf,
+        #[clap(long, default_value = "500")]
+        batch_size: usize,
+    },
+    /// SuperAdmin: compare the canister with a snapshot, token by token
+    Verify {
+        #[clap(long)]
+        from: PathBuf,
+        /// Report file; stdout if omitted
+        #[clap(long)]
+        out: Option<PathBuf>,
+    },
+    /// SuperAdmin: close the import for good and re-enable minting
+    Finish,
+}
+
+/// The canister's canonical serial: trimmed, separators removed, uppercase.
+fn normalize_serial(serial_number: &str) -> String {
+    serial_number
+        .trim()
+        .chars()
+        .filter(|c| !SERIAL_SEPARATORS.contains(c))
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// Serials that normalize alike, which the canister refuses to import.
+fn serial_collisions(nfts: &[NftCanisterNFT]) -> Vec<String> {
+    let mut by_serial: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for nft in nfts {
+        by_serial.entry(normalize_serial(&nft.serial_number)).or_default().push(&nft.serial_number);
+    }
+    by_serial
+        .into_iter()
+        .filter(|(_, originals)| originals.len() > 1)
+        .map(|(serial, originals)| format!("{} ({})", serial, originals.join(", ")))
+        .collect()
+}
+
+fn read_snapshot(path: &Path) -> Result<Vec<NftCanisterNFT>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut nfts = BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|(i, line)| {
+            let line = line.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            serde_json::from_str(&line).map_err(|e| format!("{} line {}: {}", path.display(), i + 1, e))
+        })
+        .collect::<Result<Vec<NftCanisterNFT>, String>>()?;
+    // Import resumes by position, so the order must not depend on the file.
+    nfts.sort_by_key(|nft| nft.id);
+    Ok(nfts)
+}
+
+/// What verification compares: the fields the old canister had, as the
+/// import stores them.
+#[derive(Debug, PartialEq, Eq)]
+struct Compared {
+    serial_number: String,
+    owner: Principal,
+    product_name: String,
+    manufacturer: String,
+    metadata_uri: String,
+    minted_at: u64,
+    /// Owner and time of the newest ownership record.
+    last_record: (Principal, u64),
+}
+
+impl Compared {
+    fn expected(nft: &NftCanisterNFT) -> Self {
+        // Without transfers, the newest record is the rebuilt mint record.
+        let last_record = nft
+            .transfer_history
+            .last()
+            .map_or((nft.owner, nft.minted_at), |transfer| (transfer.to, transfer.timestamp));
+        Self {
+            serial_number: normalize_serial(&nft.serial_number),
+            owner: nft.owner,
+            product_name: nft.product_name.clone(),
+            manufacturer: nft.manufacturer.clone(),
+            metadata_uri: nft.metadata_uri.clone(),
+            minted_at: nft.minted_at,
+            last_record,
+        }
+    }
+
+    fn imported(nft: &ProductNFT) -> Self {
+        Self {
+            serial_number: nft.serial_number.clone(),
+            owner: nft.owner,
+            product_name: nft.metadata.product_name.clone(),
+            manufacturer: nft.metadata.manufacturer.clone(),
+            metadata_uri: nft.metadata.ipfs_metadata_uri.clone(),
+            minted_at: nft.minted_at,
+            last_record: nft
+                .ownership_history
+                .last()
+                .map_or((nft.owner, nft.minted_at), |record| (record.owner, record.timestamp)),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+enum Kind {
+    /// In the snapshot but not in the canister.
+    Missing,
+    /// In the canister but not in the snapshot.
+    Unexpected,
+    SerialMismatch,
+    OwnerMismatch,
+    /// Product name, manufacturer or metadata URI differ.
+    MetadataMismatch,
+    MintedAtMismatch,
+    /// The newest ownership record is not the last transfer.
+    HistoryMismatch,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct Mismatch {
+    nft_id: u64,
+    kind: Kind,
+    detail: String,
+}
+
+/// Every way `imported` differs from `expected`.
+fn compare(nft_id: u64, expected: &Compared, imported: &Compared) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    let mut check = |kind, differs: bool, detail: String| {
+        if differs {
+            mismatches.push(Mismatch { nft_id, kind, detail });
+        }
+    };
+    check(
+        Kind::SerialMismatch,
+        expected.serial_number != imported.serial_number,
+        format!("expected {}, found {}", expected.serial_number, imported.serial_number),
+    );
+    check(
+        Kind::OwnerMismatch,
+        expected.owner != imported.owner,
+        format!("expected {}, found {}", expected.owner, imported.owner),
+    );
+    for (field, want, got) in [
+        ("product_name", &expected.product_name, &imported.product_name),
+        ("manufacturer", &expected.manufacturer, &imported.manufacturer),
+        ("metadata_uri", &expected.metadata_uri, &imported.metadata_uri),
+    ] {
+        check(Kind::MetadataMismatch, want != got, format!("{}: expected {:?}, found {:?}", field, want, got));
+    }
+    check(
+        Kind::MintedAtMismatch,
+        expected.minted_at != imported.minted_at,
+        format!("expected {}, found {}", expected.minted_at, imported.minted_at),
+    );
+    check(
+        Kind::HistoryMismatch,
+        expected.last_record != imported.last_record,
+        format!(
+            "expected {} at {}, found {} at {}",
+            expected.last_record.0, expected.last_record.1, imported.last_record.0, imported.last_record.1
+        ),
+    );
+    mismatches
+}
+
+#[derive(Serialize)]
+struct Report {
+    /// Unix seconds.
+    generated_at: u64,
+    canister_id: String,
+    snapshot_tokens: usize,
+    canister_tokens: usize,
+    matched: usize,
+    import_in_progress: bool,
+    import_finished: bool,
+    counts: BTreeMap<Kind, usize>,
+    mismatches: Vec<Mismatch>,
+}
+
+fn verify(snapshot: &[NftCanisterNFT], imported: &[ProductNFT]) -> (usize, Vec<Mismatch>) {
+    let by_id: HashMap<u64, &ProductNFT> = imported.iter().map(|nft| (nft.nft_id, nft)).collect();
+    let mut matched = 0;
+    let mut mismatches = Vec::new();
+    for nft in snapshot {
+        match by_id.get(&nft.id) {
+            Some(found) => {
+                let differences = compare(nft.id, &Compared::expected(nft), &Compared::imported(found));
+                if differences.is_empty() {
+                    matched += 1;
+                }
+                mismatches.extend(differences);
+            }
+            None => mismatches.push(Mismatch {
+                nft_id: nft.id,
+                kind: Kind::Missing,
+                detail: format!("serial {}", nft.serial_number),
+            }),
+        }
+    }
+    let expected: HashSet<u64> = snapshot.iter().map(|nft| nft.id).collect();
+    mismatches.extend(imported.iter().filter(|nft| !expected.contains(&nft.nft_id)).map(|nft| Mismatch {
+        nft_id: nft.nft_id,
+        kind: Kind::Unexpected,
+        detail: format!("serial {}", nft.serial_number),
+    }));
+    (matched, mismatches)
+}
+
+fn print_state(state: &ImportState) {
+    let status = match (state.in_progress, state.finished) {
+        (_, true) => "finished",
+        (true, false) => "in progress",
+        (false, false) => "not started",
+    };
+    println!("Import {}: {} token(s) imported", status, state.imported);
+}
+
+pub async fn run(command: &MigrateCommand, context: &Context) -> Result<(), String> {
+    let client = context.nft_client().await?;
+    match command {
+        MigrateCommand::Export { legacy_canister, out } => {
+            let total = client.legacy_total_nfts(*legacy_canister).await.map_err(|e| e.to_string())?;
+            let file = File::create(out).map_err(|e| format!("Failed to create {}: {}", out.display(), e))?;
+            let mut writer = BufWriter::new(file);
+            let mut nfts = Vec::new();
+            for id in 0..total {
+                match client.legacy_get_nft(*legacy_canister, id).await.map_err(|e| e.to_string())? {
+                    Some(nft) => {
+                        let line = serde_json::to_string(&nft).map_err(|e| e.to_string())?;
+                        writeln!(writer, "{}", line).map_err(|e| e.to_string())?;
+                        nfts.push(nft);
+                    }
+                    None => println!("Token {} not found; skipped", id),
+                }
+            }
+            writer.flush().map_err(|e| e.to_string())?;
+            println!("Exported {} of {} token(s) to {}", nfts.len(), total, out.display());
+            let collisions = serial_collisions(&nfts);
+            if !collisions.is_empty() {
+                println!("These serials normalize alike and will be refused by the import; fix them in the snapshot:");
+                for collision in &collisions {
+                    println!("  {}", collision);
+                }
+            }
+            Ok(())
+        }
+        MigrateCommand::Import { from, batch_size } => {
+            if *batch_size == 0 || *batch_size > MAX_BATCH {
+                return Err(format!("--batch-size must be 1 to {}", MAX_BATCH));
+            }
+            let nfts = read_snapshot(from)?;
+            let collisions = serial_collisions(&nfts);
+            if !collisions.is_empty() {
+                return Err(format!("Serials that normalize alike: {}", collisions.join("; ")));
+            }
+            let state = client.get_import_state().await.map_err(|e| e.to_string())?;
+            if state.finished {
+                return Err("The canister's import is already finished".to_string());
+            }
+            // Batches are applied whole and in snapshot order, so the count
+            // imported so far is where to resume.
+            let done = state.imported as usize;
+            if done > nfts.len() {
+                return Err(format!(
+                    "The canister has imported {} token(s) but the snapshot has {}; wrong snapshot?",
+                    done,
+                    nfts.len()
+                ));
+            }
+            if done > 0 {
+                println!("Resuming after {} imported token(s)", done);
+            }
+            for batch in nfts[done..].chunks(*batch_size) {
+                let imported = client.import_legacy_nfts(batch).await.map_err(|e| {
+                    format!(
+                        "Batch of ids {}..={}: {}; re-run to resume",
+                        batch[0].id,
+                        batch[batch.len() - 1].id,
+                        e
+                    )
+                })?;
+                println!("Imported {} of {}", imported, nfts.len());
+            }
+            println!("Run `proofcart migrate verify`, then `proofcart migrate finish`");
+            Ok(())
+        }
+        MigrateCommand::Verify { from, out } => {
+            let snapshot = read_snapshot(from)?;
+            let imported = client.export_all().await.map_err(|e| e.to_string())?;
+            let state = client.get_import_state().await.map_err(|e| e.to_string())?;
+            let (matched, mismatches) = verify(&snapshot, &imported);
+            let mut counts = BTreeMap::new();
+            for mismatch in &mismatches {
+                *counts.entry(mismatch.kind).or_insert(0) += 1;
+            }
+            let report = Report {
+                generated_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+                canister_id: client.canister_id().to_text(),
+                snapshot_tokens: snapshot.len(),
+                canister_tokens: imported.len(),
+                matched,
+                import_in_progress: state.in_progress,
+                import_finished: state.finished,
+                counts,
+                mismatches,
+            };
+            let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+            match out {
+                Some(path) => {
+                    std::fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?
+                }
+                None => println!("{}", json),
+            }
+            eprintln!("{} of {} token(s) match", matched, snapshot.len());
+            if !report.mismatches.is_empty() {
+                return Err(format!("{} mismatch(es)", report.mismatches.len()));
+            }
+            Ok(())
+        }
+        MigrateCommand::Finish => {
+            let state = client.finish_import().await.map_err(|e| e.to_string())?;
+            print_state(&state);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proofcart_icp_client::types::NftCanisterTransfer;
+
+    fn principal(byte: u8) -> Principal {
+        Principal::from_slice(&[byte; 29])
+    }
+
+    fn legacy(id: u64, serial_number: &str) -> NftCanisterNFT {
+        NftCanisterNFT {
+            id,
+            serial_number: serial_number.to_string(),
+            product_name: "Redmi Note 14 Pro".to_string(),
+            manufacturer: "Xiaomi".to_string(),
+            metadata_uri: "ipfs://bafy".to_string(),
+            owner: principal(2),
+            minted_at: 100,
+            transfer_history: vec![NftCanisterTransfer { from: principal(1), to: principal(2), timestamp: 200 }],
+        }
+    }
+
+    #[test]
+    fn serials_that_normalize_alike_collide() {
+        assert_eq!(normalize_serial(" sn-100.a "), "SN100A");
+        let nfts = [legacy(0, "SN-1"), legacy(1, "sn_1"), legacy(2, "SN-2")];
+        assert_eq!(serial_collisions(&nfts), vec!["SN1 (SN-1, sn_1)".to_string()]);
+    }
+
+    #[test]
+    fn comparison_reports_each_difference() {
+        let nft = legacy(7, "sn-7");
+        let expected = Compared::expected(&nft);
+        assert_eq!(expected.serial_number, "SN7");
+        assert_eq!(expected.last_record, (principal(2), 200));
+
+        assert!(compare(7, &expected, &Compared::expected(&nft)).is_empty());
+
+        let moved = Compared { owner: principal(3), last_record: (principal(3), 300), ..Compared::expected(&nft) };
+        let kinds: Vec<Kind> = compare(7, &expected, &moved).into_iter().map(|m| m.kind).collect();
+        assert_eq!(kinds, vec![Kind::OwnerMismatch, Kind::HistoryMismatch]);
+    }
+
+    #[test]
+    fn untransferred_tokens_expect_the_mint_record() {
+        let mut nft = legacy(1, "SN-1");
+        nft.transfer_history.clear();
+        assert_eq!(Compared::expected(&nft).last_record, (principal(2), 100));
+    }
+}
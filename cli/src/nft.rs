@@ -0,0 +1,230 @@
+//! `proofcart nft ...`
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use candid::Principal;
+use clap::{Subcommand, ValueEnum};
+use proofcart_icp_client::types::{MintRequest, ProductNFT, TransactionType};
+use proofcart_metadata_schema::Caps;
+use serde::Deserialize;
+
+use crate::Context;
+
+#[derive(Subcommand)]
+pub enum NftCommand {
+    /// Mint one token per row of a CSV file
+    Mint {
+        /// CSV with a header row; see the README for the columns
+        #[clap(long)]
+        csv: PathBuf,
+        /// Check the file without minting
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Look up a product by serial number
+    Verify { serial_number: String },
+    /// Transfer a token you own
+    Transfer {
+        #[clap(long)]
+        nft_id: u64,
+        #[clap(long)]
+        to: Principal,
+        #[clap(long, value_enum, default_value = "transfer")]
+        reason: Reason,
+        #[clap(long)]
+        memo: Option<String>,
+    },
+    /// Admin: write every token as JSON lines
+    Export {
+        /// Output file; stdout if omitted
+        #[clap(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum Reason {
+    Transfer,
+    Sale,
+    Gift,
+    ReturnToManufacturer,
+    WarrantyReplacement,
+}
+
+impl From<Reason> for TransactionType {
+    fn from(reason: Reason) -> Self {
+        match reason {
+            Reason::Transfer => TransactionType::Transfer,
+            Reason::Sale => TransactionType::Sale,
+            Reason::Gift => TransactionType::Gift,
+            Reason::ReturnToManufacturer => TransactionType::ReturnToManufacturer,
+            Reason::WarrantyReplacement => TransactionType::WarrantyReplacement,
+        }
+    }
+}
+
+/// One CSV row. Only the first three columns are required.
+#[derive(Deserialize)]
+struct MintRow {
+    serial_number: String,
+    product_name: String,
+    manufacturer: String,
+    #[serde(default)]
+    manufacture_date: String,
+    #[serde(default)]
+    category: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    specifications: String,
+    #[serde(default)]
+    warranty_info: String,
+    /// Separated by `;`.
+    #[serde(default)]
+    certifications: String,
+    #[serde(default)]
+    ipfs_metadata_uri: String,
+    /// From the pinner's `/certificates`.
+    #[serde(default)]
+    arweave_tx_id: Option<String>,
+    #[serde(default)]
+    collection_id: Option<u64>,
+    /// Nanoseconds since the epoch.
+    #[serde(default)]
+    warranty_expires_at: Option<u64>,
+}
+
+impl From<MintRow> for MintRequest {
+    fn from(row: MintRow) -> Self {
+        let serial_number = row.serial_number.trim().to_string();
+        MintRequest {
+            // The serial is the idempotency key, so re-running a partly
+            // minted file returns the tokens already minted.
+            idempotency_key: Some(serial_number.clone()),
+            serial_number,
+            product_name: row.product_name,
+            manufacturer: row.manufacturer,
+            manufacture_date: row.manufacture_date,
+            category: row.category,
+            description: row.description,
+            specifications: row.specifications,
+            warranty_info: row.warranty_info,
+            certifications: row
+                .certifications
+                .split(';')
+                .map(str::trim)
+                .filter(|c| !c.is_empty())
+                .map(str::to_string)
+                .collect(),
+            ipfs_metadata_uri: row.ipfs_metadata_uri,
+            arweave_tx_id: row.arweave_tx_id.filter(|id| !id.is_empty()),
+            collection_id: row.collection_id,
+            warranty_expires_at: row.warranty_expires_at,
+            ..MintRequest::default()
+        }
+    }
+}
+
+/// Rows are checked against the metadata schema the canister applies, at its
+/// default limits, so a bad row fails before anything is minted.
+fn read_csv(path: &PathBuf) -> Result<Vec<MintRequest>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    reader
+        .deserialize::<MintRow>()
+        .enumerate()
+        // Line 1 is the header.
+        .map(|(i, row)| {
+            let request = row.map(MintRequest::from).map_err(|e| format!("Line {}: {}", i + 2, e))?;
+            proofcart_metadata_schema::validate(&request.metadata(), &Caps::default())
+                .map_err(|e| format!("Line {}: {}", i + 2, e))?;
+            Ok(request)
+        })
+        .collect()
+}
+
+fn print_nft(nft: &ProductNFT) {
+    println!("Serial:        {}", nft.serial_number);
+    println!("Token:         #{}", nft.nft_id);
+    println!("Product:       {}", nft.metadata.product_name);
+    println!("Manufacturer:  {}", nft.metadata.manufacturer);
+    println!("Verification:  {:?}{}", nft.verification_level, if nft.revoked { " (revoked)" } else { "" });
+    if let Some(report) = &nft.stolen {
+        println!("Reported:      {:?} at {}", report.kind, report.reported_at);
+    }
+    if let Some(recall) = &nft.recall {
+        println!("Recalled:      {} ({})", recall.recalled_at, recall.notice_uri);
+    }
+    if let Some(lock) = &nft.sale_lock {
+        println!("Sale lock:     order {}", lock.order_id);
+    }
+    println!("Owner:         {}", nft.owner);
+    println!("Minted at:     {}", nft.minted_at);
+    println!("Transfers:     {}", nft.ownership_history.len().saturating_sub(1));
+}
+
+pub async fn run(command: &NftCommand, context: &Context) -> Result<(), String> {
+    match command {
+        NftCommand::Mint { csv, dry_run } => {
+            let requests = read_csv(csv)?;
+            if *dry_run {
+                println!("{} row(s) OK", requests.len());
+                return Ok(());
+            }
+            let client = context.nft_client().await?;
+            let mut failed = 0;
+            for request in &requests {
+                match client.mint_product_nft(request).await {
+                    Ok(nft) => println!("{}  minted #{}", request.serial_number, nft.nft_id),
+                    Err(e) => {
+                        failed += 1;
+                        println!("{}  FAILED: {}", request.serial_number, e);
+                    }
+                }
+            }
+            println!("{} minted, {} failed", requests.len() - failed, failed);
+            if failed > 0 {
+                return Err(format!("{} row(s) failed; fix them and re-run the file", failed));
+            }
+            Ok(())
+        }
+        NftCommand::Verify { serial_number } => {
+            let client = context.nft_client().await?;
+            let nft = client.verify_product(serial_number).await.map_err(|e| e.to_string())?;
+            print_nft(&nft);
+            Ok(())
+        }
+        NftCommand::Transfer { nft_id, to, reason, memo } => {
+            let client = context.nft_client().await?;
+            let nft = client
+                .transfer_nft(*nft_id, *to, Some((*reason).into()), memo.clone(), None)
+                .await
+                .map_err(|e| e.to_string())?;
+            print_nft(&nft);
+            Ok(())
+        }
+        NftCommand::Export { out } => {
+            let client = context.nft_client().await?;
+            let nfts = client.export_all().await.map_err(|e| e.to_string())?;
+            let mut writer: Box<dyn Write> = match out {
+                Some(path) => Box::new(BufWriter::new(
+                    File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?,
+                )),
+                None => Box::new(std::io::stdout().lock()),
+            };
+            for nft in &nfts {
+                let line = serde_json::to_string(nft).map_err(|e| e.to_string())?;
+                writeln!(writer, "{}", line).map_err(|e| e.to_string())?;
+            }
+            writer.flush().map_err(|e| e.to_string())?;
+            if out.is_some() {
+                println!("Exported {} token(s)", nfts.len());
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! `proofcart qr ...`
+
+use std::fs;
+use std::path::PathBuf;
+
+use clap::Subcommand;
+use proofcart_qr_payload::{QrPayload, SigningKey, VerifyingKey};
+use qrcode::{EcLevel, QrCode};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Signer};
+
+use crate::Context;
+
+#[derive(Subcommand)]
+pub enum QrCommand {
+    /// Write a signed QR code PNG for each serial of a production run
+    Generate {
+        /// Serial numbers, one per line; each must be minted
+        #[clap(long)]
+        serials: PathBuf,
+        /// Keypair file of the manufacturer's QR key (Solana keypair format)
+        #[clap(long)]
+        signing_key: PathBuf,
+        /// Directory the PNGs are written to
+        #[clap(long)]
+        out_dir: PathBuf,
+        /// Put the payload at the end of this URL, e.g.
+        /// https://proofcart.example/q/, so phone cameras open it
+        #[clap(long)]
+        url_prefix: Option<String>,
+        /// Smallest side of each image, in pixels
+        #[clap(long, default_value = "512")]
+        size: u32,
+    },
+    /// Decode a scanned QR code and check its signature
+    Verify {
+        /// The scanned text: the payload or a URL ending in it
+        text: String,
+        /// Public key of the manufacturer's QR key (base58)
+        #[clap(long)]
+        public_key: Pubkey,
+    },
+}
+
+fn signing_key(path: &PathBuf) -> Result<(SigningKey, Pubkey), String> {
+    let keypair = read_keypair_file(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let key = SigningKey::from_keypair_bytes(&keypair.to_bytes()).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok((key, keypair.pubkey()))
+}
+
+fn read_serials(path: &PathBuf) -> Result<Vec<String>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// A file name for `serial`: characters other than letters, digits, `-`
+/// and `_` become `_`.
+fn file_name(serial: &str) -> String {
+    let name: String = serial
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    format!("{}.png", name)
+}
+
+fn write_png(text: &str, size: u32, path: &PathBuf) -> Result<(), String> {
+    let code = QrCode::with_error_correction_level(text.as_bytes(), EcLevel::M).map_err(|e| e.to_string())?;
+    code.render::<image::Luma<u8>>()
+        .min_dimensions(size, size)
+        .build()
+        .save(path)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+pub async fn run(command: &QrCommand, context: &Context) -> Result<(), String> {
+    match command {
+        QrCommand::Generate { serials, signing_key: key_path, out_dir, url_prefix, size } => {
+            let (key, public_key) = signing_key(key_path)?;
+            let serials = read_serials(serials)?;
+            let canister_id = context.nft_canister_id()?;
+            let client = context.nft_client().await?;
+            fs::create_dir_all(out_dir).map_err(|e| format!("Failed to create {}: {}", out_dir.display(), e))?;
+            println!("Signing with QR key {}", public_key);
+
+            let mut failed = 0;
+            for serial in &serials {
+                let written = async {
+                    let nft = client.verify_product(serial).await.map_err(|e| e.to_string())?;
+                    let payload = QrPayload::sign(nft.nft_id, canister_id, &nft.serial_number, &key)?;
+                    let text = format!("{}{}", url_prefix.as_deref().unwrap_or_default(), payload.to_text()?);
+                    let path = out_dir.join(file_name(serial));
+                    write_png(&text, *size, &path)?;
+                    Ok::<_, String>((nft.nft_id, path))
+                }
+                .await;
+                match written {
+                    Ok((nft_id, path)) => println!("{}  #{}  {}", serial, nft_id, path.display()),
+                    Err(e) => {
+                        failed += 1;
+                        println!("{}  FAILED: {}", serial, e);
+                    }
+                }
+            }
+            println!("{} written, {} failed", serials.len() - failed, failed);
+            if failed > 0 {
+                return Err(format!("{} serial(s) failed; fix them and re-run the file", failed));
+            }
+            Ok(())
+        }
+        QrCommand::Verify { text, public_key } => {
+            let key = VerifyingKey::from_bytes(&public_key.to_bytes()).map_err(|e| e.to_string())?;
+            let payload = QrPayload::from_text(text)?;
+            println!("Serial:    {}", payload.serial_number);
+            println!("Token:     #{}", payload.nft_id);
+            println!("Canister:  {}", payload.canister_id);
+            payload.verify(&key)?;
+            println!("Signature: valid");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names_are_safe() {
+        assert_eq!(file_name("SN-1001_A"), "SN-1001_A.png");
+        assert_eq!(file_name("../IMEI 35/69"), "___IMEI_35_69.png");
+    }
+}
@@ -0,0 +1,455 @@
+//! `proofcart reconcile`: the marketplace's orders against the chains, for
+//! the daily finance close.
+//!
+//! Orders come from a CSV export of the marketplace database. Escrows and
+//! NFT transfers come from the indexer's database (`indexer/`), which
+//! mirrors the escrow program and the canister's transaction log, so the
+//! run makes no RPC or canister calls. Every disagreement is written to a
+//! JSON report.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::NoTls;
+
+use crate::Context;
+
+#[derive(Args)]
+pub struct ReconcileArgs {
+    /// Marketplace orders, as CSV with a header row; see the README
+    #[clap(long)]
+    orders: PathBuf,
+    /// Indexer database, overriding DATABASE_URL and the profile
+    #[clap(long)]
+    database_url: Option<String>,
+    /// Report escrows missing from the export only if created at or after
+    /// this Unix time, to match an export of a date range
+    #[clap(long)]
+    since: Option<i64>,
+    /// Report file; stdout if omitted
+    #[clap(long)]
+    out: Option<PathBuf>,
+}
+
+/// An order's status in the marketplace.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum OrderStatus {
+    Pending,
+    Paid,
+    Shipped,
+    Delivered,
+    Cancelled,
+    Refunded,
+}
+
+impl OrderStatus {
+    /// The buyer has paid and the order is going ahead.
+    fn is_paid(self) -> bool {
+        matches!(self, Self::Paid | Self::Shipped | Self::Delivered)
+    }
+
+    fn is_shipped(self) -> bool {
+        matches!(self, Self::Shipped | Self::Delivered)
+    }
+
+    fn is_cancelled(self) -> bool {
+        matches!(self, Self::Cancelled | Self::Refunded)
+    }
+}
+
+/// One row of the marketplace export. Empty optional columns are skipped.
+#[derive(Deserialize)]
+struct Order {
+    order_id: String,
+    status: OrderStatus,
+    /// Base58 Solana addresses.
+    buyer: String,
+    seller: String,
+    amount_lamports: u64,
+    #[serde(default)]
+    nft_id: Option<u64>,
+    /// The buyer's ICP principal; without it the sale transfer's recipient
+    /// is taken as the buyer.
+    #[serde(default)]
+    buyer_principal: Option<String>,
+}
+
+/// An escrow as the indexer has it.
+struct IndexedEscrow {
+    buyer: String,
+    seller: String,
+    amount_lamports: u64,
+    /// `created`, `locked`, `released` or `refunded`.
+    status: String,
+    created_at: Option<i64>,
+}
+
+/// What the indexer knows about an order's NFT.
+#[derive(Default)]
+struct Ledger {
+    escrows: HashMap<String, IndexedEscrow>,
+    /// Current owner of each token; `None` once burned.
+    owners: HashMap<u64, Option<String>>,
+    /// Recipient of each order's sale transfer.
+    sale_recipients: HashMap<String, String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+enum Kind {
+    /// The marketplace took the order as paid, but it has no escrow.
+    MissingEscrow,
+    /// An escrow whose order is not in the export.
+    EscrowWithoutOrder,
+    AmountMismatch,
+    /// The escrow's buyer or seller is not the order's.
+    PartyMismatch,
+    /// The seller has been paid for an order that has not shipped.
+    ReleasedUnshipped,
+    /// The buyer was refunded for an order that shipped.
+    RefundedButShipped,
+    /// The order was cancelled but its escrow still holds the funds.
+    CancelledEscrowFunded,
+    /// The order was cancelled but its escrow paid the seller.
+    CancelledButReleased,
+    /// The escrow paid the seller but the token never moved to the buyer.
+    NftNotTransferred,
+    /// The token is not owned by the order's buyer.
+    NftOwnerMismatch,
+}
+
+#[derive(Serialize, Debug)]
+struct Mismatch {
+    order_id: String,
+    kind: Kind,
+    detail: String,
+}
+
+#[derive(Serialize)]
+struct Report {
+    /// Unix seconds.
+    generated_at: i64,
+    orders: usize,
+    escrows: usize,
+    counts: BTreeMap<Kind, usize>,
+    mismatches: Vec<Mismatch>,
+}
+
+fn read_orders(path: &PathBuf) -> Result<Vec<Order>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut seen = HashSet::new();
+    reader
+        .deserialize::<Order>()
+        .enumerate()
+        // Line 1 is the header.
+        .map(|(i, row)| {
+            let mut order = row.map_err(|e| format!("Line {}: {}", i + 2, e))?;
+            if !seen.insert(order.order_id.clone()) {
+                return Err(format!("Line {}: order {} appears twice", i + 2, order.order_id));
+            }
+            order.buyer_principal = order.buyer_principal.filter(|p| !p.is_empty());
+            Ok(order)
+        })
+        .collect()
+}
+
+async fn load_ledger(database_url: &str, orders: &[Order]) -> Result<Ledger, String> {
+    let (db, connection) = tokio_postgres::connect(database_url, NoTls)
+        .await
+        .map_err(|e| format!("Database: {}", e))?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("Postgres connection closed: {}", e);
+        }
+    });
+    let db_error = |e: tokio_postgres::Error| format!("Database: {}", e);
+
+    let mut ledger = Ledger::default();
+    let rows = db
+        .query(
+"SELECT order_id, buyer, seller, amount_lamports, status, created_at FROM escrow_orders
+             WHERE marketplace = ''",
+            &[],
+        )
+        .await
+        .map_err(db_error)?;
+    for row in rows {
+        ledger.escrows.insert(
+            row.get("order_id"),
+            IndexedEscrow {
+                buyer: row.get("buyer"),
+                seller: row.get("seller"),
+                amount_lamports: row.get::<_, i64>("amount_lamports") as u64,
+                status: row.get("status"),
+                created_at: row.get("created_at"),
+            },
+        );
+    }
+
+    let nft_ids: Vec<i64> = orders.iter().filter_map(|o| o.nft_id).map(|id| id as i64).collect();
+    let rows = db
+        .query(
+            "SELECT nft_id, owner, burned_at FROM nft_tokens WHERE nft_id = ANY($1)",
+            &[&nft_ids],
+        )
+        .await
+        .map_err(db_error)?;
+    for row in rows {
+        let burned = row.get::<_, Option<i64>>("burned_at").is_some();
+        let owner: Option<String> = row.get("owner");
+        ledger
+            .owners
+            .insert(row.get::<_, i64>("nft_id") as u64, owner.filter(|_| !burned));
+    }
+
+    let order_ids: Vec<&str> = orders.iter().map(|o| o.order_id.as_str()).collect();
+    let rows = db
+        .query(
+            "SELECT order_id, to_owner FROM nft_transfers WHERE order_id = ANY($1) ORDER BY block_index",
+            &[&order_ids],
+        )
+        .await
+        .map_err(db_error)?;
+    for row in rows {
+        ledger.sale_recipients.insert(row.get("order_id"), row.get("to_owner"));
+    }
+    Ok(ledger)
+}
+
+fn reconcile(orders: &[Order], ledger: &Ledger, since: Option<i64>) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    let mut report = |order_id: &str, kind: Kind, detail: String| {
+        mismatches.push(Mismatch {
+            order_id: order_id.to_string(),
+            kind,
+            detail,
+        })
+    };
+
+    for order in orders {
+        let id = order.order_id.as_str();
+        let Some(escrow) = ledger.escrows.get(id) else {
+            if order.status.is_paid() {
+                report(id, Kind::MissingEscrow, format!("Order is {:?} but has no escrow", order.status));
+            }
+            continue;
+        };
+        if escrow.amount_lamports != order.amount_lamports {
+            report(
+                id,
+                Kind::AmountMismatch,
+                format!("Escrow holds {} lamports, order is for {}", escrow.amount_lamports, order.amount_lamports),
+            );
+        }
+        if escrow.buyer != order.buyer || escrow.seller != order.seller {
+            report(
+                id,
+                Kind::PartyMismatch,
+                format!(
+                    "Escrow is {} -> {}, order is {} -> {}",
+                    escrow.buyer, escrow.seller, order.buyer, order.seller
+                ),
+            );
+        }
+
+        match escrow.status.as_str() {
+            "released" if order.status.is_cancelled() => {
+                report(id, Kind::CancelledButReleased, format!("Order is {:?} but the seller was paid", order.status))
+            }
+            "released" if !order.status.is_shipped() => {
+                report(id, Kind::ReleasedUnshipped, format!("Seller paid while the order is {:?}", order.status))
+            }
+            "refunded" if order.status.is_shipped() => {
+                report(id, Kind::RefundedButShipped, format!("Buyer refunded while the order is {:?}", order.status))
+            }
+            "created" | "locked" if order.status.is_cancelled() => report(
+                id,
+                Kind::CancelledEscrowFunded,
+                format!("Order is {:?} but its escrow is still {}", order.status, escrow.status),
+            ),
+            _ => {}
+        }
+
+        let (Some(nft_id), "released") = (order.nft_id, escrow.status.as_str()) else {
+            continue;
+        };
+        let Some(recipient) = ledger.sale_recipients.get(id) else {
+            report(id, Kind::NftNotTransferred, format!("Token #{} has no sale transfer for the order", nft_id));
+            continue;
+        };
+        let buyer = order.buyer_principal.as_ref().unwrap_or(recipient);
+        if recipient != buyer {
+            report(
+                id,
+                Kind::NftOwnerMismatch,
+                format!("Token #{} was sold to {}, not the buyer {}", nft_id, recipient, buyer),
+            );
+            continue;
+        }
+        match ledger.owners.get(&nft_id) {
+            Some(Some(owner)) if owner == buyer => {}
+            Some(Some(owner)) => report(
+                id,
+                Kind::NftOwnerMismatch,
+                format!("Token #{} is owned by {}, not the buyer {} (resold?)", nft_id, owner, buyer),
+            ),
+            Some(None) => report(id, Kind::NftOwnerMismatch, format!("Token #{} has been burned", nft_id)),
+            None => report(id, Kind::NftOwnerMismatch, format!("Token #{} is not indexed", nft_id)),
+        }
+    }
+
+    let exported: HashSet<&str> = orders.iter().map(|o| o.order_id.as_str()).collect();
+    let mut orphans: Vec<(&String, &IndexedEscrow)> = ledger
+        .escrows
+        .iter()
+        .filter(|(id, escrow)| {
+            !exported.contains(id.as_str()) && since.map_or(true, |since| escrow.created_at.unwrap_or(i64::MAX) >= since)
+        })
+        .collect();
+    orphans.sort_by(|a, b| a.0.cmp(b.0));
+    for (id, escrow) in orphans {
+        report(
+            id,
+            Kind::EscrowWithoutOrder,
+            format!("{} escrow of {} lamports", escrow.status, escrow.amount_lamports),
+        );
+    }
+    mismatches
+}
+
+pub async fn run(args: &ReconcileArgs, context: &Context) -> Result<(), String> {
+    let database_url = match &args.database_url {
+        Some(url) => url.clone(),
+        None => context.database_url()?.to_string(),
+    };
+    let orders = read_orders(&args.orders)?;
+    let ledger = load_ledger(&database_url, &orders).await?;
+    let mismatches = reconcile(&orders, &ledger, args.since);
+
+    let mut counts = BTreeMap::new();
+    for mismatch in &mismatches {
+        *counts.entry(mismatch.kind).or_insert(0) += 1;
+    }
+    let report = Report {
+        generated_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default(),
+        orders: orders.len(),
+        escrows: ledger.escrows.len(),
+        counts,
+        mismatches,
+    };
+
+    let mut writer: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    serde_json::to_writer_pretty(&mut writer, &report).map_err(|e| e.to_string())?;
+    writeln!(writer).map_err(|e| e.to_string())?;
+    writer.flush().map_err(|e| e.to_string())?;
+
+    if report.mismatches.is_empty() {
+        eprintln!("{} order(s) reconciled, no mismatches", report.orders);
+        Ok(())
+    } else {
+        Err(format!("{} mismatch(es) in {} order(s)", report.mismatches.len(), report.orders))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(order_id: &str, status: OrderStatus, nft_id: Option<u64>) -> Order {
+        Order {
+            order_id: order_id.to_string(),
+            status,
+            buyer: "buyer".to_string(),
+            seller: "seller".to_string(),
+            amount_lamports: 100,
+            nft_id,
+            buyer_principal: None,
+        }
+    }
+
+    fn escrow(status: &str, created_at: i64) -> IndexedEscrow {
+        IndexedEscrow {
+            buyer: "buyer".to_string(),
+            seller: "seller".to_string(),
+            amount_lamports: 100,
+            status: status.to_string(),
+            created_at: Some(created_at),
+        }
+    }
+
+    fn kinds(mismatches: &[Mismatch]) -> Vec<(&str, Kind)> {
+        mismatches.iter().map(|m| (m.order_id.as_str(), m.kind)).collect()
+    }
+
+    #[test]
+    fn matching_orders_reconcile() {
+        let mut ledger = Ledger::default();
+        ledger.escrows.insert("A".into(), escrow("released", 10));
+        ledger.escrows.insert("B".into(), escrow("created", 10));
+        ledger.sale_recipients.insert("A".into(), "principal-a".into());
+        ledger.owners.insert(1, Some("principal-a".into()));
+        let orders = [
+            order("A", OrderStatus::Delivered, Some(1)),
+            order("B", OrderStatus::Paid, None),
+            order("C", OrderStatus::Pending, None),
+        ];
+        assert!(reconcile(&orders, &ledger, None).is_empty());
+    }
+
+    #[test]
+    fn reports_each_kind_of_mismatch() {
+        let mut ledger = Ledger::default();
+        ledger.escrows.insert("released".into(), escrow("released", 10));
+        ledger.escrows.insert("cancelled".into(), escrow("locked", 10));
+        ledger.escrows.insert(
+            "short".into(),
+            IndexedEscrow {
+                amount_lamports: 99,
+                ..escrow("created", 10)
+            },
+        );
+        ledger.escrows.insert("unsold".into(), escrow("released", 10));
+        ledger.escrows.insert("resold".into(), escrow("released", 10));
+        ledger.escrows.insert("old-orphan".into(), escrow("created", 5));
+        ledger.escrows.insert("orphan".into(), escrow("created", 20));
+        ledger.sale_recipients.insert("resold".into(), "principal-b".into());
+        ledger.owners.insert(2, Some("principal-c".into()));
+        let orders = [
+            order("missing", OrderStatus::Shipped, None),
+            order("released", OrderStatus::Paid, None),
+            order("cancelled", OrderStatus::Cancelled, None),
+            order("short", OrderStatus::Paid, None),
+            order("unsold", OrderStatus::Delivered, Some(1)),
+            order("resold", OrderStatus::Delivered, Some(2)),
+        ];
+        assert_eq!(
+            kinds(&reconcile(&orders, &ledger, Some(10))),
+            vec![
+                ("missing", Kind::MissingEscrow),
+                ("released", Kind::ReleasedUnshipped),
+                ("cancelled", Kind::CancelledEscrowFunded),
+                ("short", Kind::AmountMismatch),
+                ("unsold", Kind::NftNotTransferred),
+                ("resold", Kind::NftOwnerMismatch),
+                ("orphan", Kind::EscrowWithoutOrder),
+            ]
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    Solana(proofcart_solana_client::Error),
+    Icp(proofcart_icp_client::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Solana(e) => write!(f, "Solana: {}", e),
+            Error::Icp(e) => write!(f, "ICP: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Solana(e) => Some(e),
+            Error::Icp(e) => Some(e),
+        }
+    }
+}
+
+impl From<proofcart_solana_client::Error> for Error {
+    fn from(e: proofcart_solana_client::Error) -> Self {
+        Error::Solana(e)
+    }
+}
+
+impl From<proofcart_icp_client::Error> for Error {
+    fn from(e: proofcart_icp_client::Error) -> Self {
+        Error::Icp(e)
+    }
+}
@@ -0,0 +1,19 @@
+//! Cross-chain view of ProofCart orders.
+//!
+//! An order is paid through the Solana escrow program and the product it
+//! buys is an NFT on the ICP canister. [`ProofCart::order`] ties the two
+//! together by order id and serial number, and [`Order::report`] reads both
+//! chains and returns a [`ProvenanceReport`] with anything that does not line
+//! up, so integrators do not have to query and cross-check each chain
+//! themselves.
+
+mod error;
+mod order;
+mod report;
+
+pub use error::Error;
+pub use order::{Order, ProofCart};
+pub use report::{Finding, ProvenanceReport};
+
+pub use proofcart_icp_client as icp;
+pub use proofcart_solana_client as solana;
@@ -0,0 +1,83 @@
+use proofcart_icp_client::{Error as IcpError, NftClient};
+use proofcart_solana_client::EscrowClient;
+
+use crate::{Error, ProvenanceReport};
+
+/// Clients for both chains.
+pub struct ProofCart {
+    escrow: EscrowClient,
+    nft: NftClient,
+}
+
+impl ProofCart {
+    pub fn new(escrow: EscrowClient, nft: NftClient) -> Self {
+        Self { escrow, nft }
+    }
+
+    pub fn escrow_client(&self) -> &EscrowClient {
+        &self.escrow
+    }
+
+    pub fn nft_client(&self) -> &NftClient {
+        &self.nft
+    }
+
+    /// The order `order_id` for the product with `serial_number`.
+    pub fn order(&self, order_id: impl Into<String>, serial_number: impl Into<String>) -> Order<'_> {
+        Order {
+            proofcart: self,
+            order_id: order_id.into(),
+            serial_number: serial_number.into(),
+        }
+    }
+}
+
+/// One marketplace order: a Solana escrow and the product NFT it pays for.
+pub struct Order<'a> {
+    proofcart: &'a ProofCart,
+    order_id: String,
+    serial_number: String,
+}
+
+impl Order<'_> {
+    pub fn order_id(&self) -> &str {
+        &self.order_id
+    }
+
+    pub fn serial_number(&self) -> &str {
+        &self.serial_number
+    }
+
+    /// Read the escrow and the NFT (concurrently) and compare them.
+    /// Transport and RPC failures are errors; a missing escrow or an
+    /// unregistered serial are findings in the report.
+    pub async fn report(&self) -> Result<ProvenanceReport, Error> {
+        let (escrow, nft) = tokio::join!(
+            self.proofcart.escrow.fetch_escrow(&self.order_id),
+            self.proofcart.nft.verify_product(&self.serial_number),
+        );
+        let escrow = escrow?;
+        let nft = match nft {
+            Ok(nft) => Ok(nft),
+            Err(IcpError::Canister(message)) => Err(message),
+            Err(e) => return Err(e.into()),
+        };
+        let buyer_principal = match &escrow {
+            Some(escrow) => {
+                self.proofcart
+                    .nft
+                    .principal_for_solana_address(&escrow.buyer.to_string())
+                    .await?
+            }
+            None => None,
+        };
+
+        Ok(ProvenanceReport::build(
+            self.order_id.clone(),
+            self.serial_number.clone(),
+            escrow,
+            nft,
+            buyer_principal,
+        ))
+    }
+}
@@ -0,0 +1,128 @@
+use candid::Principal;
+use proofcart_icp_client::types::{LossKind, OwnershipRecord, ProductNFT};
+use proofcart_solana_client::{Escrow, EscrowStatus};
+
+/// Something about an order that does not line up across the two chains,
+/// or that a buyer should know.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// No escrow exists for the order on Solana.
+    EscrowNotFound,
+    /// The serial has no NFT; carries the canister's explanation (a burned
+    /// serial says so here).
+    NotRegistered(String),
+    /// The product's verification was revoked.
+    Revoked,
+    Reported(LossKind),
+    Recalled,
+    /// The NFT is locked for sale under a different order.
+    LockedForOtherOrder(String),
+    /// The escrow paid the seller but the NFT's history has no sale for the
+    /// order.
+    SaleNotRecorded,
+    /// The escrow refunded the buyer but the NFT was sold under the order.
+    SaleRecordedButRefunded,
+    /// The sale recorded for the order is in SOL for a different amount than
+    /// the escrow held, in lamports.
+    PriceMismatch { escrow: u64, recorded: u64 },
+    /// The escrow paid out, but the NFT is held by someone other than the
+    /// principal linked to the buyer's wallet.
+    OwnerIsNotBuyer { owner: Principal, buyer: Principal },
+}
+
+/// Both chains' state for one order, with the findings from comparing them.
+#[derive(Clone, Debug)]
+pub struct ProvenanceReport {
+    pub order_id: String,
+    pub serial_number: String,
+    pub escrow: Option<Escrow>,
+    /// The NFT as the caller may see it, if the serial is registered.
+    pub nft: Option<ProductNFT>,
+    /// ICP principal that linked the escrow buyer's wallet, if any.
+    pub buyer_principal: Option<Principal>,
+    pub findings: Vec<Finding>,
+}
+
+impl ProvenanceReport {
+    /// Compare the two chains' state. `nft` is the canister's answer to
+    /// `verify_product`.
+    pub fn build(
+        order_id: String,
+        serial_number: String,
+        escrow: Option<Escrow>,
+        nft: Result<ProductNFT, String>,
+        buyer_principal: Option<Principal>,
+    ) -> Self {
+        let mut findings = Vec::new();
+        if escrow.is_none() {
+            findings.push(Finding::EscrowNotFound);
+        }
+        let nft = match nft {
+            Ok(nft) => Some(nft),
+            Err(message) => {
+                findings.push(Finding::NotRegistered(message));
+                None
+            }
+        };
+
+        if let Some(nft) = &nft {
+            if nft.revoked {
+                findings.push(Finding::Revoked);
+            }
+            if let Some(report) = &nft.stolen {
+                findings.push(Finding::Reported(report.kind));
+            }
+            if nft.recall.is_some() {
+                findings.push(Finding::Recalled);
+            }
+            if let Some(lock) = nft.sale_lock.as_ref().filter(|lock| lock.order_id != order_id) {
+                findings.push(Finding::LockedForOtherOrder(lock.order_id.clone()));
+            }
+        }
+
+        if let (Some(escrow), Some(nft)) = (&escrow, &nft) {
+            let sale = sale_for_order(nft, &order_id);
+            match escrow.status {
+                EscrowStatus::Released => {
+                    if sale.is_none() {
+                        findings.push(Finding::SaleNotRecorded);
+                    }
+                    if let Some(buyer) = buyer_principal.filter(|buyer| *buyer != nft.owner) {
+                        findings.push(Finding::OwnerIsNotBuyer { owner: nft.owner, buyer });
+                    }
+                }
+                EscrowStatus::Refunded if sale.is_some() => findings.push(Finding::SaleRecordedButRefunded),
+                _ => {}
+            }
+            let recorded = sale.and_then(|record| record.price.as_ref()).filter(|price| price.currency == "SOL");
+            if let Some(price) = recorded.filter(|price| price.amount != escrow.amount) {
+                findings.push(Finding::PriceMismatch {
+                    escrow: escrow.amount,
+                    recorded: price.amount,
+                });
+            }
+        }
+
+        Self {
+            order_id,
+            serial_number,
+            escrow,
+            nft,
+            buyer_principal,
+            findings,
+        }
+    }
+
+    /// Whether both chains agree and nothing is flagged.
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+/// The latest ownership record priced under `order_id`.
+fn sale_for_order<'a>(nft: &'a ProductNFT, order_id: &str) -> Option<&'a OwnershipRecord> {
+    nft.ownership_history
+        .iter()
+        .rev()
+        .find(|record| record.price.as_ref().and_then(|price| price.order_id.as_deref()) == Some(order_id))
+}
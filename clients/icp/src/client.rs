@@ -0,0 +1,571 @@
+use candid::utils::ArgumentEncoder;
+use candid::{CandidType, Principal};
+use ic_agent::{Agent, Identity};
+use serde::de::DeserializeOwned;
+
+use crate::types::{
+    ApiVersion, Block, CanisterStatusSummary, Collection, CreateCollectionRequest, DisputeOutcome, DisputeRecord,
+    ExportPage, GetTransactionsResponse, ImportState, Manufacturer, Marketplace, MarketplaceMinter, MintRequest,
+    NFTFilter, NftCanisterNFT, PauseState, ProductNFT, RevocationReason, Role, SalePrice, SearchResult,
+    TransactionType,
+};
+use crate::{Error, MarketplaceClient, RetryPolicy};
+
+/// Largest page `search_nfts` and `export_nfts` return.
+const MAX_PAGE_SIZE: u64 = 100;
+
+/// Typed client for one NFT canister (or one shard).
+pub struct NftClient {
+    agent: Agent,
+    canister_id: Principal,
+    retry: RetryPolicy,
+}
+
+fn is_local(url: &str) -> bool {
+    url.contains("localhost") || url.contains("127.0.0.1")
+}
+
+impl NftClient {
+    pub fn new(agent: Agent, canister_id: Principal) -> Self {
+        Self {
+            agent,
+            canister_id,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Build an agent for `url` acting as `identity`. Against a local
+    /// replica the root key is fetched, since it is not the IC's.
+    pub async fn connect(url: &str, identity: Box<dyn Identity>, canister_id: Principal) -> Result<Self, Error> {
+        let agent = Agent::builder().with_url(url).with_boxed_identity(identity).build()?;
+        if is_local(url) {
+            agent.fetch_root_key().await?;
+        }
+        Ok(Self::new(agent, canister_id))
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn agent(&self) -> &Agent {
+        &self.agent
+    }
+
+    pub fn canister_id(&self) -> Principal {
+        self.canister_id
+    }
+
+    async fn query<A, R>(&self, method: &str, args: A) -> Result<R, Error>
+    where
+        A: ArgumentEncoder,
+        R: CandidType + DeserializeOwned,
+    {
+        self.query_canister(self.canister_id, method, args).await
+    }
+
+    async fn query_canister<A, R>(&self, canister_id: Principal, method: &str, args: A) -> Result<R, Error>
+    where
+        A: ArgumentEncoder,
+        R: CandidType + DeserializeOwned,
+    {
+        let arg = candid::encode_args(args)?;
+        let bytes = self
+            .retry
+            .run(|| self.agent.query(&canister_id, method).with_arg(arg.clone()).call())
+            .await?;
+        Ok(candid::decode_one(&bytes)?)
+    }
+
+    /// Each attempt is a new message, so only calls the canister
+    /// deduplicates (`idempotent`) are retried.
+    async fn update<A, R>(&self, method: &str, args: A, idempotent: bool) -> Result<R, Error>
+    where
+        A: ArgumentEncoder,
+        R: CandidType + DeserializeOwned,
+    {
+        let arg = candid::encode_args(args)?;
+        let retry = if idempotent { self.retry } else { RetryPolicy::none() };
+        let bytes = retry
+            .run(|| self.agent.update(&self.canister_id, method).with_arg(arg.clone()).call_and_wait())
+            .await?;
+        Ok(candid::decode_one(&bytes)?)
+    }
+
+    pub async fn api_version(&self) -> Result<ApiVersion, Error> {
+        self.query("api_version", ()).await
+    }
+
+    /// Mint a token. Retried on transient failures only when the request
+    /// carries an `idempotency_key`.
+    pub async fn mint_product_nft(&self, request: &MintRequest) -> Result<ProductNFT, Error> {
+        let idempotent = request.idempotency_key.is_some();
+        let result: Result<ProductNFT, String> = self.update("mint_product_nft", (request,), idempotent).await?;
+        result.map_err(Error::Canister)
+    }
+
+    pub async fn verify_product(&self, serial_number: &str) -> Result<ProductNFT, Error> {
+        let result: Result<ProductNFT, String> = self.query("verify_product", (serial_number,)).await?;
+        result.map_err(Error::Canister)
+    }
+
+    pub async fn get_nft(&self, nft_id: u64) -> Result<ProductNFT, Error> {
+        let result: Result<ProductNFT, String> = self.query("get_nft", (nft_id,)).await?;
+        result.map_err(Error::Canister)
+    }
+
+    /// Transfer a token; `reason` defaults to `Transfer` on the canister.
+    /// Never retried, since a repeated transfer could be charged twice.
+    pub async fn transfer_nft(
+        &self,
+        nft_id: u64,
+        new_owner: Principal,
+        reason: Option<TransactionType>,
+        memo: Option<String>,
+        price: Option<SalePrice>,
+    ) -> Result<ProductNFT, Error> {
+        let result: Result<ProductNFT, String> = self
+            .update("transfer_nft", (nft_id, new_owner, reason, memo, price), false)
+            .await?;
+        result.map_err(Error::Canister)
+    }
+
+    /// Lock a token for a pending escrow order, as its owner or a
+    /// marketplace. Never retried: a repeat fails once the first call has
+    /// locked the token.
+    pub async fn lock_for_sale(&self, nft_id: u64, order_id: &str) -> Result<ProductNFT, Error> {
+        let result: Result<ProductNFT, String> = self.update("lock_for_sale", (nft_id, order_id), false).await?;
+        result.map_err(Error::Canister)
+    }
+
+    /// Release a sale lock, as the principal that took it or a marketplace.
+    pub async fn unlock(&self, nft_id: u64) -> Result<ProductNFT, Error> {
+        let result: Result<ProductNFT, String> = self.update("unlock", (nft_id,), false).await?;
+        result.map_err(Error::Canister)
+    }
+
+    /// Marketplace: settle the sale `order_id` by transferring the token
+    /// locked for it from `from` to `to`. Never retried.
+    pub async fn transfer_from(
+        &self,
+        nft_id: u64,
+        from: Principal,
+        to: Principal,
+        order_id: &str,
+        price: Option<SalePrice>,
+    ) -> Result<ProductNFT, Error> {
+        let result: Result<ProductNFT, String> = self
+            .update("transfer_from", (nft_id, from, to, order_id, price), false)
+            .await?;
+        result.map_err(Error::Canister)
+    }
+
+    /// Marketplace: record the arbitration outcome of the disputed order
+    /// `order_id` against a product. Retried: the canister refuses a second
+    /// outcome for the same order.
+    pub async fn record_dispute_outcome(
+        &self,
+        serial_number: &str,
+        order_id: &str,
+        outcome: DisputeOutcome,
+        evidence_hash: [u8; 32],
+    ) -> Result<DisputeRecord, Error> {
+        let result: Result<DisputeRecord, String> = self
+            .update(
+                "record_dispute_outcome",
+                (serial_number, order_id, outcome, evidence_hash.to_vec()),
+                true,
+            )
+            .await?;
+        result.map_err(Error::Canister)
+    }
+
+    /// Admin: revoke a token's verification. Never retried: a repeat fails
+    /// once the token is revoked.
+    pub async fn revoke_verification(&self, nft_id: u64, reason: RevocationReason) -> Result<ProductNFT, Error> {
+        let result: Result<ProductNFT, String> = self.update("revoke_verification", (nft_id, reason), false).await?;
+        result.map_err(Error::Canister)
+    }
+
+    /// Admin: undo a mistaken revocation, with a written justification.
+    pub async fn restore_verification(&self, nft_id: u64, justification: &str) -> Result<ProductNFT, Error> {
+        let result: Result<ProductNFT, String> = self
+            .update("restore_verification", (nft_id, justification), false)
+            .await?;
+        result.map_err(Error::Canister)
+    }
+
+    pub async fn canister_status_summary(&self) -> Result<CanisterStatusSummary, Error> {
+        self.query("canister_status_summary", ()).await
+    }
+
+    pub async fn get_pause_state(&self) -> Result<PauseState, Error> {
+        self.query("get_pause_state", ()).await
+    }
+
+    /// SuperAdmin: pause or resume every update method except role
+    /// management and moderation.
+    pub async fn set_paused(&self, paused: bool) -> Result<PauseState, Error> {
+        let result: Result<PauseState, String> = self.update("set_paused", (paused,), true).await?;
+        result.map_err(Error::Canister)
+    }
+
+    /// SuperAdmin: grant a role; granting a held role is a no-op.
+    pub async fn grant_role(&self, principal: Principal, role: Role) -> Result<(), Error> {
+        let result: Result<(), String> = self.update("grant_role", (principal, role), true).await?;
+        result.map_err(Error::Canister)
+    }
+
+    pub async fn revoke_role(&self, principal: Principal, role: Role) -> Result<(), Error> {
+        let result: Result<(), String> = self.update("revoke_role", (principal, role), true).await?;
+        result.map_err(Error::Canister)
+    }
+
+    /// Admin: register a manufacturer, or rename a registered one.
+    pub async fn register_manufacturer(&self, principal: Principal, name: &str) -> Result<Manufacturer, Error> {
+        let result: Result<Manufacturer, String> =
+            self.update("register_manufacturer", (principal, name), true).await?;
+        result.map_err(Error::Canister)
+    }
+
+    /// Admin: cap the tokens a manufacturer may mint; `None` lifts the cap.
+    pub async fn set_mint_quota(&self, principal: Principal, quota: Option<u64>) -> Result<Manufacturer, Error> {
+        let result: Result<Manufacturer, String> = self.update("set_mint_quota", (principal, quota), true).await?;
+        result.map_err(Error::Canister)
+    }
+
+    pub async fn list_manufacturers(&self, offset: u64, limit: u64) -> Result<Vec<Manufacturer>, Error> {
+        self.query("list_manufacturers", (offset, limit)).await
+    }
+
+    /// A client scoped to the partner marketplace `marketplace_id`.
+    pub fn marketplace(&self, marketplace_id: &str) -> MarketplaceClient<'_> {
+        MarketplaceClient::new(self, marketplace_id)
+    }
+
+    /// Admin: register a partner marketplace and its admins. Not retried:
+    /// a repeat fails once the first attempt has registered it.
+    pub async fn create_marketplace(
+        &self,
+        marketplace_id: &str,
+        name: &str,
+        admins: &[Principal],
+    ) -> Result<Marketplace, Error> {
+        let result: Result<Marketplace, String> =
+            self.update("create_marketplace", (marketplace_id, name, admins), false).await?;
+        result.map_err(Error::Canister)
+    }
+
+    /// Admin: replace a partner marketplace's admins.
+    pub async fn set_marketplace_admins(
+        &self,
+        marketplace_id: &str,
+        admins: &[Principal],
+    ) -> Result<Marketplace, Error> {
+        let result: Result<Marketplace, String> =
+            self.update("set_marketplace_admins", (marketplace_id, admins), true).await?;
+        result.map_err(Error::Canister)
+    }
+
+    pub async fn get_marketplace(&self, marketplace_id: &str) -> Result<Marketplace, Error> {
+        let result: Result<Marketplace, String> = self.query("get_marketplace", (marketplace_id,)).await?;
+        result.map_err(Error::Canister)
+    }
+
+    pub async fn list_marketplaces(&self, offset: u64, limit: u64) -> Result<Vec<Marketplace>, Error> {
+        self.query("list_marketplaces", (offset, limit)).await
+    }
+
+    /// Marketplace admin: register a minter of the marketplace, or rename one.
+    pub async fn register_marketplace_minter(
+        &self,
+        marketplace_id: &str,
+        principal: Principal,
+        name: &str,
+    ) -> Result<MarketplaceMinter, Error> {
+        let result: Result<MarketplaceMinter, String> =
+            self.update("register_marketplace_minter", (marketplace_id, principal, name), true).await?;
+        result.map_err(Error::Canister)
+    }
+
+    /// Marketplace admin: remove a minter, which stops its mints into the
+    /// marketplace's collections.
+    pub async fn remove_marketplace_minter(&self, marketplace_id: &str, principal: Principal) -> Result<(), Error> {
+        let result: Result<(), String> =
+            self.update("remove_marketplace_minter", (marketplace_id, principal), false).await?;
+        result.map_err(Error::Canister)
+    }
+
+    pub async fn list_marketplace_minters(&self, marketplace_id: &str) -> Result<Vec<MarketplaceMinter>, Error> {
+        self.query("list_marketplace_minters", (marketplace_id,)).await
+    }
+
+    pub async fn list_marketplace_collections(
+        &self,
+        marketplace_id: &str,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<Collection>, Error> {
+        self.query("list_marketplace_collections", (marketplace_id, offset, limit)).await
+    }
+
+    /// Create a collection owned by the caller, in a partner marketplace
+    /// when `request.marketplace_id` is set. Not retried: each call creates
+    /// a new collection.
+    pub async fn create_collection(&self, request: &CreateCollectionRequest) -> Result<Collection, Error> {
+        let result: Result<Collection, String> = self.update("create_collection", (request,), false).await?;
+        result.map_err(Error::Canister)
+    }
+
+    pub async fn get_nfts_by_owner(&self, owner: Principal) -> Result<Vec<ProductNFT>, Error> {
+        self.query("get_nfts_by_owner", (owner,)).await
+    }
+
+    /// ICP principal that has linked `address` (base58) as its Solana wallet.
+    pub async fn principal_for_solana_address(&self, address: &str) -> Result<Option<Principal>, Error> {
+        self.query("principal_for_solana_address", (address,)).await
+    }
+
+    /// One page of `search_nfts`; `page` is zero-based.
+    pub async fn search_nfts(&self, filter: &NFTFilter, page: u64, limit: u64) -> Result<SearchResult, Error> {
+        self.query("search_nfts", (filter, page, limit)).await
+    }
+
+    /// Every token matching `filter`, fetched page by page.
+    pub async fn search_all(&self, filter: &NFTFilter) -> Result<Vec<ProductNFT>, Error> {
+        let mut nfts = Vec::new();
+        for page in 0.. {
+            let result = self.search_nfts(filter, page, MAX_PAGE_SIZE).await?;
+            let done = result.items.is_empty() || nfts.len() as u64 + result.items.len() as u64 >= result.total;
+            nfts.extend(result.items);
+            if done {
+                break;
+            }
+        }
+        Ok(nfts)
+    }
+
+    /// Admin: one page of tokens with `nft_id >= start`.
+    pub async fn export_nfts(&self, start: u64, limit: u64) -> Result<ExportPage, Error> {
+        let result: Result<ExportPage, String> = self.query("export_nfts", (start, limit)).await?;
+        result.map_err(Error::Canister)
+    }
+
+    /// Admin: every token, in id order.
+    pub async fn export_all(&self) -> Result<Vec<ProductNFT>, Error> {
+        let mut nfts = Vec::new();
+        let mut start = Some(0);
+        while let Some(from) = start {
+            let page = self.export_nfts(from, MAX_PAGE_SIZE).await?;
+            nfts.extend(page.nfts);
+            start = page.next_start;
+        }
+        Ok(nfts)
+    }
+
+    /// Admin: restore tokens from `export_nfts` into a fresh canister, on
+    /// the same terms as `import_legacy_nfts`. Never retried.
+    pub async fn import_nfts(&self, batch: &[ProductNFT]) -> Result<u64, Error> {
+        let result: Result<u64, String> = self.update("import_nfts", (batch,), false).await?;
+        result.map_err(Error::Canister)
+    }
+
+    /// Admin: import tokens read from the retired `nft_canister`, keeping
+    /// their ids. Only accepted into an empty registry or one whose import
+    /// is still open; a batch is applied whole or not at all. Never retried:
+    /// a repeated batch is refused as already imported.
+    pub async fn import_legacy_nfts(&self, batch: &[NftCanisterNFT]) -> Result<u64, Error> {
+        let result: Result<u64, String> = self.update("import_legacy_nfts", (batch,), false).await?;
+        result.map_err(Error::Canister)
+    }
+
+    /// Admin: close the import for good and re-enable minting.
+    pub async fn finish_import(&self) -> Result<ImportState, Error> {
+        let result: Result<ImportState, String> = self.update("finish_import", (), false).await?;
+        result.map_err(Error::Canister)
+    }
+
+    pub async fn get_import_state(&self) -> Result<ImportState, Error> {
+        self.query("get_import_state", ()).await
+    }
+
+    /// How many ids the retired `nft_canister` at `legacy` has handed out,
+    /// from its `get_total_nfts : () -> (nat64) query`.
+    pub async fn legacy_total_nfts(&self, legacy: Principal) -> Result<u64, Error> {
+        self.query_canister(legacy, "get_total_nfts", ()).await
+    }
+
+    /// Token `id` of the retired `nft_canister` at `legacy`, from its
+    /// `get_nft : (nat64) -> (opt NFT) query`.
+    pub async fn legacy_get_nft(&self, legacy: Principal, id: u64) -> Result<Option<NftCanisterNFT>, Error> {
+        self.query_canister(legacy, "get_nft", (id,)).await
+    }
+
+    /// Up to `length` transaction log blocks from `start` (the canister caps
+    /// a page at 1000), and which of the requested blocks are archived.
+    pub async fn get_transactions(&self, start: u64, length: u64) -> Result<GetTransactionsResponse, Error> {
+        self.query("get_transactions", (start, length)).await
+    }
+
+    /// Blocks held by the archive canister `archive`, as reported in
+    /// [`GetTransactionsResponse::archived`].
+    pub async fn get_archived_transactions(
+        &self,
+        archive: Principal,
+        start: u64,
+        length: u64,
+    ) -> Result<Vec<Block>, Error> {
+        self.query_canister(archive, "get_transactions", (start, length)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::types::subtype::{subtype_with_config, Gamma, OptReport};
+    use candid::types::{FuncMode, Type};
+    use candid::{CandidType, Principal};
+    use candid_parser::utils::CandidSource;
+    use std::path::PathBuf;
+
+    use crate::types::*;
+
+    type NftResult = Result<ProductNFT, String>;
+
+    /// Every method `NftClient` calls, with the argument and return types it
+    /// encodes and decodes, and whether it is called as a query.
+    fn client_methods() -> Vec<(&'static str, Vec<Type>, Vec<Type>, bool)> {
+        vec![
+            ("api_version", vec![], vec![ApiVersion::ty()], true),
+            ("mint_product_nft", vec![MintRequest::ty()], vec![NftResult::ty()], false),
+            ("verify_product", vec![String::ty()], vec![NftResult::ty()], true),
+            ("get_nft", vec![u64::ty()], vec![NftResult::ty()], true),
+            (
+                "transfer_nft",
+                vec![
+                    u64::ty(),
+                    Principal::ty(),
+                    Option::<TransactionType>::ty(),
+                    Option::<String>::ty(),
+                    Option::<SalePrice>::ty(),
+                ],
+                vec![NftResult::ty()],
+                false,
+            ),
+            ("lock_for_sale", vec![u64::ty(), String::ty()], vec![NftResult::ty()], false),
+            ("unlock", vec![u64::ty()], vec![NftResult::ty()], false),
+            (
+                "transfer_from",
+                vec![u64::ty(), Principal::ty(), Principal::ty(), String::ty(), Option::<SalePrice>::ty()],
+                vec![NftResult::ty()],
+                false,
+            ),
+            (
+                "record_dispute_outcome",
+                vec![String::ty(), String::ty(), DisputeOutcome::ty(), Vec::<u8>::ty()],
+                vec![Result::<DisputeRecord, String>::ty()],
+                false,
+            ),
+            ("revoke_verification", vec![u64::ty(), RevocationReason::ty()], vec![NftResult::ty()], false),
+            ("restore_verification", vec![u64::ty(), String::ty()], vec![NftResult::ty()], false),
+            ("canister_status_summary", vec![], vec![CanisterStatusSummary::ty()], true),
+            ("get_pause_state", vec![], vec![PauseState::ty()], true),
+            ("set_paused", vec![bool::ty()], vec![Result::<PauseState, String>::ty()], false),
+            ("grant_role", vec![Principal::ty(), Role::ty()], vec![Result::<(), String>::ty()], false),
+            ("revoke_role", vec![Principal::ty(), Role::ty()], vec![Result::<(), String>::ty()], false),
+            (
+                "register_manufacturer",
+                vec![Principal::ty(), String::ty()],
+                vec![Result::<Manufacturer, String>::ty()],
+                false,
+            ),
+            (
+                "set_mint_quota",
+                vec![Principal::ty(), Option::<u64>::ty()],
+                vec![Result::<Manufacturer, String>::ty()],
+                false,
+            ),
+            ("list_manufacturers", vec![u64::ty(), u64::ty()], vec![Vec::<Manufacturer>::ty()], true),
+            (
+                "create_marketplace",
+                vec![String::ty(), String::ty(), Vec::<Principal>::ty()],
+                vec![Result::<Marketplace, String>::ty()],
+                false,
+            ),
+            (
+                "set_marketplace_admins",
+                vec![String::ty(), Vec::<Principal>::ty()],
+                vec![Result::<Marketplace, String>::ty()],
+                false,
+            ),
+            ("get_marketplace", vec![String::ty()], vec![Result::<Marketplace, String>::ty()], true),
+            ("list_marketplaces", vec![u64::ty(), u64::ty()], vec![Vec::<Marketplace>::ty()], true),
+            (
+                "register_marketplace_minter",
+                vec![String::ty(), Principal::ty(), String::ty()],
+                vec![Result::<MarketplaceMinter, String>::ty()],
+                false,
+            ),
+            (
+                "remove_marketplace_minter",
+                vec![String::ty(), Principal::ty()],
+                vec![Result::<(), String>::ty()],
+                false,
+            ),
+            ("list_marketplace_minters", vec![String::ty()], vec![Vec::<MarketplaceMinter>::ty()], true),
+            (
+                "list_marketplace_collections",
+                vec![String::ty(), u64::ty(), u64::ty()],
+                vec![Vec::<Collection>::ty()],
+                true,
+            ),
+            (
+                "create_collection",
+                vec![CreateCollectionRequest::ty()],
+                vec![Result::<Collection, String>::ty()],
+                false,
+            ),
+            ("get_nfts_by_owner", vec![Principal::ty()], vec![Vec::<ProductNFT>::ty()], true),
+            (
+                "principal_for_solana_address",
+                vec![String::ty()],
+                vec![Option::<Principal>::ty()],
+                true,
+            ),
+            ("search_nfts", vec![NFTFilter::ty(), u64::ty(), u64::ty()], vec![SearchResult::ty()], true),
+            ("export_nfts", vec![u64::ty(), u64::ty()], vec![Result::<ExportPage, String>::ty()], true),
+            ("get_transactions", vec![u64::ty(), u64::ty()], vec![GetTransactionsResponse::ty()], true),
+            ("import_nfts", vec![Vec::<ProductNFT>::ty()], vec![Result::<u64, String>::ty()], false),
+            (
+                "import_legacy_nfts",
+                vec![Vec::<NftCanisterNFT>::ty()],
+                vec![Result::<u64, String>::ty()],
+                false,
+            ),
+            ("finish_import", vec![], vec![Result::<ImportState, String>::ty()], false),
+            ("get_import_state", vec![], vec![ImportState::ty()], true),
+        ]
+    }
+
+    #[test]
+    fn client_matches_canister_interface() {
+        let did = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../blockchain/icp-nft/src/proofcart_nft.did");
+        let (env, actor) = CandidSource::File(&did).load().expect("Failed to load proofcart_nft.did");
+        let actor = actor.expect("proofcart_nft.did has no service");
+
+        for (method, args, rets, query) in client_methods() {
+            let func = env.get_method(&actor, method).unwrap_or_else(|e| panic!("{}: {}", method, e));
+            assert_eq!(func.modes.contains(&FuncMode::Query), query, "{}: query mode", method);
+            assert_eq!(func.args.len(), args.len(), "{}: argument count", method);
+            // What we send must be accepted, and what comes back must decode.
+            for (ours, theirs) in args.iter().zip(&func.args) {
+                subtype_with_config(OptReport::Error, &mut Gamma::new(), &env, ours, theirs)
+                    .unwrap_or_else(|e| panic!("{}: argument: {}", method, e));
+            }
+            for (ours, theirs) in rets.iter().zip(&func.rets) {
+                subtype_with_config(OptReport::Error, &mut Gamma::new(), &env, theirs, ours)
+                    .unwrap_or_else(|e| panic!("{}: result: {}", method, e));
+            }
+        }
+    }
+}
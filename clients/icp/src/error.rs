@@ -0,0 +1,45 @@
+use std::fmt;
+
+use ic_agent::AgentError;
+
+#[derive(Debug)]
+pub enum Error {
+    Agent(AgentError),
+    Candid(candid::Error),
+    /// The canister rejected the call with this message.
+    Canister(String),
+    Identity(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Agent(e) => write!(f, "Agent error: {}", e),
+            Error::Candid(e) => write!(f, "Candid error: {}", e),
+            Error::Canister(message) => write!(f, "{}", message),
+            Error::Identity(message) => write!(f, "Identity error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Agent(e) => Some(e),
+            Error::Candid(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<AgentError> for Error {
+    fn from(e: AgentError) -> Self {
+        Error::Agent(e)
+    }
+}
+
+impl From<candid::Error> for Error {
+    fn from(e: candid::Error) -> Self {
+        Error::Candid(e)
+    }
+}
@@ -0,0 +1,37 @@
+//! Loading identities for the agent.
+
+use std::path::{Path, PathBuf};
+
+use ic_agent::identity::{AnonymousIdentity, BasicIdentity, Secp256k1Identity};
+use ic_agent::Identity;
+
+use crate::Error;
+
+/// Identity from a PEM file: a secp256k1 key (what `dfx identity new`
+/// creates) or an Ed25519 key.
+pub fn from_pem_file(path: impl AsRef<Path>) -> Result<Box<dyn Identity>, Error> {
+    let path = path.as_ref();
+    if let Ok(identity) = Secp256k1Identity::from_pem_file(path) {
+        return Ok(Box::new(identity));
+    }
+    BasicIdentity::from_pem_file(path)
+        .map(|identity| Box::new(identity) as Box<dyn Identity>)
+        .map_err(|e| Error::Identity(format!("Failed to load {}: {}", path.display(), e)))
+}
+
+/// Identity `name` from dfx's identity store
+/// (`~/.config/dfx/identity/<name>/identity.pem`). Encrypted identities are
+/// not supported; export them with `dfx identity export` instead.
+pub fn from_dfx(name: &str) -> Result<Box<dyn Identity>, Error> {
+    let home = std::env::var_os("HOME").ok_or_else(|| Error::Identity("HOME is not set".to_string()))?;
+    let path = PathBuf::from(home)
+        .join(".config/dfx/identity")
+        .join(name)
+        .join("identity.pem");
+    from_pem_file(path)
+}
+
+/// The anonymous identity, enough for queries such as `verify_product`.
+pub fn anonymous() -> Box<dyn Identity> {
+    Box::new(AnonymousIdentity)
+}
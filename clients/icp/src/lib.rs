@@ -0,0 +1,27 @@
+//! Rust agent client for the ProofCart NFT canister
+//! (`blockchain/icp-nft`) and its shards.
+//!
+//! [`NftClient`] wraps `ic-agent` with typed methods for minting, verifying,
+//! transferring and paging through tokens. The types in [`types`] mirror
+//! `blockchain/icp-nft/src/proofcart_nft.did`; a test checks every wrapped
+//! method against that file, so an interface change that would break the
+//! client fails the build here rather than at runtime.
+//!
+//! [`MarketplaceClient`], from [`NftClient::marketplace`], scopes the
+//! collection and minter calls to one partner marketplace.
+//!
+//! Identities are loaded from PEM files or dfx's identity store with
+//! [`identity`]. Transient transport failures are retried per
+//! [`RetryPolicy`], for queries and for updates that are safe to repeat.
+
+mod client;
+mod error;
+pub mod identity;
+mod marketplace;
+mod retry;
+pub mod types;
+
+pub use client::NftClient;
+pub use error::Error;
+pub use marketplace::MarketplaceClient;
+pub use retry::RetryPolicy;
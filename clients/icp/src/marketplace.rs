@@ -0,0 +1,59 @@
+//! [`MarketplaceClient`]: the calls of one partner marketplace, for the
+//! services and tools of a white-labelled storefront.
+
+use candid::Principal;
+
+use crate::types::{Collection, CreateCollectionRequest, Marketplace, MarketplaceMinter};
+use crate::{Error, NftClient};
+
+/// An [`NftClient`] scoped to one partner marketplace: collections are
+/// created in it and minters are managed in its registry. Minting into its
+/// collections and everything else goes through the `NftClient` as usual.
+pub struct MarketplaceClient<'a> {
+    client: &'a NftClient,
+    marketplace_id: String,
+}
+
+impl<'a> MarketplaceClient<'a> {
+    pub fn new(client: &'a NftClient, marketplace_id: &str) -> Self {
+        Self { client, marketplace_id: marketplace_id.to_string() }
+    }
+
+    pub fn marketplace_id(&self) -> &str {
+        &self.marketplace_id
+    }
+
+    pub fn nft(&self) -> &'a NftClient {
+        self.client
+    }
+
+    pub async fn details(&self) -> Result<Marketplace, Error> {
+        self.client.get_marketplace(&self.marketplace_id).await
+    }
+
+    /// Create a collection owned by the caller in the marketplace; the
+    /// caller must be one of its minters. Any `marketplace_id` already in
+    /// `request` is replaced.
+    pub async fn create_collection(&self, request: CreateCollectionRequest) -> Result<Collection, Error> {
+        let request = CreateCollectionRequest { marketplace_id: Some(self.marketplace_id.clone()), ..request };
+        self.client.create_collection(&request).await
+    }
+
+    pub async fn collections(&self, offset: u64, limit: u64) -> Result<Vec<Collection>, Error> {
+        self.client.list_marketplace_collections(&self.marketplace_id, offset, limit).await
+    }
+
+    /// Marketplace admin: register a minter, or rename one.
+    pub async fn register_minter(&self, principal: Principal, name: &str) -> Result<MarketplaceMinter, Error> {
+        self.client.register_marketplace_minter(&self.marketplace_id, principal, name).await
+    }
+
+    /// Marketplace admin: remove a minter.
+    pub async fn remove_minter(&self, principal: Principal) -> Result<(), Error> {
+        self.client.remove_marketplace_minter(&self.marketplace_id, principal).await
+    }
+
+    pub async fn minters(&self) -> Result<Vec<MarketplaceMinter>, Error> {
+        self.client.list_marketplace_minters(&self.marketplace_id).await
+    }
+}
@@ -0,0 +1,433 @@
+//! Candid types of the NFT canister, mirroring `proofcart_nft.did`.
+
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+pub use proofcart_types::{DisputeOutcome, LocalizedText, NFTMetadata, OwnershipRecord, SalePrice, TransactionType};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ApiVersion {
+    pub implementation: String,
+    pub major: u16,
+    pub minor: u16,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum RoyaltyRecipient {
+    Principal(Principal),
+    /// Base58 Solana address.
+    Solana(String),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RoyaltyInfo {
+    pub recipient: RoyaltyRecipient,
+    /// Basis points of the sale price.
+    pub bps: u16,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MintRequest {
+    pub serial_number: String,
+    pub product_name: String,
+    pub manufacturer: String,
+    pub manufacture_date: String,
+    pub category: String,
+    pub description: String,
+    pub specifications: String,
+    pub warranty_info: String,
+    pub certifications: Vec<String>,
+    pub ipfs_metadata_uri: String,
+    /// Arweave id of the certification documents, from the pinner's
+    /// `/certificates`.
+    pub arweave_tx_id: Option<String>,
+    /// Makes retries safe: a repeated mint with the same key returns the
+    /// original token.
+    pub idempotency_key: Option<String>,
+    pub verify_metadata: Option<bool>,
+    pub collection_id: Option<u64>,
+    pub royalty: Option<RoyaltyInfo>,
+    pub claim_code_hash: Option<Vec<u8>>,
+    pub warranty_expires_at: Option<u64>,
+    pub warranty_transfers: Option<bool>,
+    pub localized: Option<Vec<(String, LocalizedText)>>,
+}
+
+impl MintRequest {
+    /// The metadata the token would carry, as `proofcart-metadata-schema`
+    /// checks it.
+    pub fn metadata(&self) -> NFTMetadata {
+        NFTMetadata {
+            serial_number: self.serial_number.clone(),
+            product_name: self.product_name.clone(),
+            manufacturer: self.manufacturer.clone(),
+            manufacture_date: self.manufacture_date.clone(),
+            category: self.category.clone(),
+            description: self.description.clone(),
+            specifications: self.specifications.clone(),
+            warranty_info: self.warranty_info.clone(),
+            certifications: self.certifications.clone(),
+            ipfs_metadata_uri: self.ipfs_metadata_uri.clone(),
+            arweave_tx_id: self.arweave_tx_id.clone(),
+            localized: self.localized.clone(),
+        }
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    SuperAdmin,
+    Verifier,
+    Support,
+    Marketplace,
+    Distributor,
+    ServiceCenter,
+    Recycler,
+    Auditor,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerificationLevel {
+    SelfAttested,
+    ManufacturerVerified,
+    ThirdPartyAudited,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Endorsement {
+    pub auditor: Principal,
+    pub endorsed_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RecallInfo {
+    pub recalled_at: u64,
+    pub recalled_by: Principal,
+    pub notice_uri: String,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SaleLock {
+    pub order_id: String,
+    pub locked_by: Principal,
+    pub locked_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntegrityStatus {
+    Intact,
+    Tampered,
+    Unavailable,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MetadataIntegrity {
+    pub sha256: Vec<u8>,
+    pub recorded_at: u64,
+    pub last_checked_at: u64,
+    pub status: IntegrityStatus,
+    pub last_error: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct WarrantyVoided {
+    pub voided_at: u64,
+    pub transferred_by: Principal,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Warranty {
+    pub expires_at: u64,
+    pub expired: bool,
+    pub transfers: Option<bool>,
+    pub voided: Option<WarrantyVoided>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProductState {
+    Manufactured,
+    InDistribution,
+    Sold,
+    InService,
+    Returned,
+    Recycled,
+    Destroyed,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LifecycleRecord {
+    pub state: ProductState,
+    pub changed_by: Principal,
+    pub changed_at: u64,
+    pub note: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Lifecycle {
+    pub state: ProductState,
+    pub history: Vec<LifecycleRecord>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetadataField {
+    Description,
+    Specifications,
+    WarrantyInfo,
+    Certifications,
+    IpfsMetadataUri,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LossKind {
+    Stolen,
+    Lost,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StolenReport {
+    pub kind: LossKind,
+    pub reported_by: Principal,
+    pub reported_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Custody {
+    pub custodian: Principal,
+    pub delegated_by: Principal,
+    pub delegated_at: u64,
+    pub expires_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CollectionFreeze {
+    pub collection_id: u64,
+    pub frozen_by: Principal,
+    pub frozen_at: u64,
+    pub reason: String,
+    pub admin_hold: bool,
+}
+
+/// A manufacturer's product line, minted into with `MintRequest::collection_id`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Collection {
+    pub collection_id: u64,
+    pub owner: Principal,
+    pub manufacturer: String,
+    pub product_line: String,
+    pub description: String,
+    pub logo_uri: String,
+    pub created_at: u64,
+    pub supply: u64,
+    pub transfer_count: u64,
+    pub royalty: Option<RoyaltyInfo>,
+    pub freeze: Option<CollectionFreeze>,
+    /// The partner marketplace it belongs to; `None` for ProofCart's own.
+    pub marketplace_id: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CreateCollectionRequest {
+    pub manufacturer: String,
+    pub product_line: String,
+    pub description: String,
+    pub logo_uri: String,
+    pub royalty: Option<RoyaltyInfo>,
+    /// Create it in this partner marketplace; the caller must be its minter.
+    pub marketplace_id: Option<String>,
+}
+
+/// A white-labelled partner marketplace sharing the canister.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Marketplace {
+    pub marketplace_id: String,
+    pub name: String,
+    /// Manage the marketplace's minters.
+    pub admins: Vec<Principal>,
+    pub created_at: u64,
+}
+
+/// A principal allowed to create collections in a partner marketplace.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MarketplaceMinter {
+    pub marketplace_id: String,
+    pub principal: Principal,
+    pub name: String,
+    pub registered_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ProductNFT {
+    pub nft_id: u64,
+    pub serial_number: String,
+    pub owner: Principal,
+    pub metadata: NFTMetadata,
+    pub minted_at: u64,
+    pub verification_level: VerificationLevel,
+    pub revoked: bool,
+    pub endorsement: Option<Endorsement>,
+    pub ownership_history: Vec<OwnershipRecord>,
+    pub collection_id: Option<u64>,
+    pub royalty: Option<RoyaltyInfo>,
+    pub recall: Option<RecallInfo>,
+    pub sale_lock: Option<SaleLock>,
+    pub metadata_integrity: Option<MetadataIntegrity>,
+    pub warranty: Option<Warranty>,
+    pub lifecycle: Option<Lifecycle>,
+    /// Metadata fields hidden from the caller.
+    pub restricted_fields: Option<Vec<MetadataField>>,
+    pub stolen: Option<StolenReport>,
+    pub custody: Option<Custody>,
+    pub collection_freeze: Option<CollectionFreeze>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct NFTFilter {
+    pub manufacturer: Option<String>,
+    pub category: Option<String>,
+    pub minted_after: Option<u64>,
+    pub minted_before: Option<u64>,
+    pub verified_only: Option<bool>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SearchResult {
+    pub items: Vec<ProductNFT>,
+    pub total: u64,
+    pub page: u64,
+    pub limit: u64,
+    pub has_more: bool,
+}
+
+/// Progress of `import_nfts` / `import_legacy_nfts`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ImportState {
+    pub in_progress: bool,
+    /// `finish_import` was called; no more imports are accepted.
+    pub finished: bool,
+    pub imported: u64,
+}
+
+/// Token of the retired `nft_canister`, as its `get_nft` returns it and
+/// `import_legacy_nfts` takes it.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct NftCanisterNFT {
+    pub id: u64,
+    pub serial_number: String,
+    pub product_name: String,
+    pub manufacturer: String,
+    pub metadata_uri: String,
+    pub owner: Principal,
+    pub minted_at: u64,
+    pub transfer_history: Vec<NftCanisterTransfer>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct NftCanisterTransfer {
+    pub from: Principal,
+    pub to: Principal,
+    pub timestamp: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ExportPage {
+    pub nfts: Vec<ProductNFT>,
+    pub total: u64,
+    pub next_start: Option<u64>,
+}
+
+/// Kind of a transaction log block. `Revoke` removes an ICRC-37 approval;
+/// verification revocations are `MetadataUpdate` blocks.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TxKind {
+    Mint,
+    Transfer,
+    Burn,
+    MetadataUpdate,
+    Approve,
+    Revoke,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Block {
+    pub index: u64,
+    pub kind: TxKind,
+    pub nft_id: u64,
+    pub serial_number: String,
+    pub from: Option<Principal>,
+    pub to: Option<Principal>,
+    pub caller: Principal,
+    /// Nanoseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub memo: Option<String>,
+    pub parent_hash: Option<Vec<u8>>,
+}
+
+/// Blocks moved to an archive canister.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ArchivedRange {
+    pub canister_id: Principal,
+    pub start: u64,
+    pub length: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GetTransactionsResponse {
+    pub log_length: u64,
+    /// Index of the oldest block still held by the canister.
+    pub first_index: u64,
+    pub transactions: Vec<Block>,
+    pub archived: Vec<ArchivedRange>,
+}
+
+/// A marketplace's arbitration outcome, recorded against the product.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DisputeRecord {
+    pub sequence: u64,
+    pub order_id: String,
+    pub outcome: DisputeOutcome,
+    /// SHA-256 of the off-chain evidence bundle.
+    pub evidence_hash: Vec<u8>,
+    pub marketplace: Principal,
+    pub recorded_at: u64,
+}
+
+/// Cycles and memory of the canister, from `canister_status_summary`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CanisterStatusSummary {
+    pub cycles_balance: u128,
+    pub low_cycles_threshold: u128,
+    /// Mints are refused while set.
+    pub low_cycles: bool,
+    pub stable_memory_bytes: u64,
+    pub heap_memory_bytes: u64,
+    pub nft_count: u64,
+    pub last_upgrade_at: Option<u64>,
+}
+
+/// Whether the canister's update methods are paused, and by whom.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PauseState {
+    pub paused: bool,
+    pub changed_by: Option<Principal>,
+    pub changed_at: Option<u64>,
+}
+
+/// A registered manufacturer, allowed to mint under its serial namespace.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Manufacturer {
+    pub principal: Principal,
+    pub name: String,
+    pub registered_at: u64,
+    pub serial_format: Option<String>,
+    /// Most tokens it may mint; `None` is unlimited.
+    pub mint_quota: Option<u64>,
+    pub minted: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RevocationReason {
+    Counterfeit,
+    Recalled,
+    Fraud,
+    DataError,
+}
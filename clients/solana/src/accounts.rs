@@ -0,0 +1,135 @@
+//! Decoding of the program's accounts.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use sha2::{Digest, Sha256};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::Error;
+
+/// The program's `EscrowStatus`, shared with the other ProofCart crates.
+pub use proofcart_types::EscrowState as EscrowStatus;
+
+/// Mirrors the program's `Escrow` account. Timestamps are Unix seconds.
+#[derive(BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Escrow {
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub order_id: String,
+    pub amount: u64,
+    pub status: EscrowStatus,
+    pub bump: u8,
+    pub created_at: i64,
+    pub locked_at: Option<i64>,
+    pub released_at: Option<i64>,
+    pub resolved_at: Option<i64>,
+    /// The partner marketplace the order belongs to; `None` for the
+    /// platform's own orders, including every escrow created before
+    /// marketplaces existed.
+    pub marketplace: Option<Pubkey>,
+    /// The marketplace's fee, in basis points, locked in at creation.
+    pub fee_bps: u16,
+}
+
+/// Anchor account discriminator: the first 8 bytes of
+/// `sha256("account:<name>")`.
+fn account_discriminator(name: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("account:{}", name));
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash[..8]);
+    discriminator
+}
+
+impl Escrow {
+    /// Anchor account discriminator: the first 8 bytes of
+    /// `sha256("account:Escrow")`.
+    pub fn discriminator() -> [u8; 8] {
+        account_discriminator("Escrow")
+    }
+
+    /// The lamports paid to the marketplace's fee recipient on release;
+    /// the seller gets the rest.
+    pub fn fee(&self) -> u64 {
+        (self.amount as u128 * self.fee_bps as u128 / 10_000) as u64
+    }
+
+    /// Decode raw account data. Anchor allocates the account at its maximum
+    /// size, so bytes after the encoded fields are ignored.
+    pub fn try_from_account_data(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < 8 || data[..8] != Self::discriminator() {
+            return Err(Error::NotAnEscrow);
+        }
+        Escrow::deserialize(&mut &data[8..]).map_err(Error::Decode)
+    }
+}
+
+/// Mirrors the program's `Marketplace` account: a partner marketplace with
+/// its own dispute admin, fee and accepted mints.
+#[derive(BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Marketplace {
+    pub marketplace_id: String,
+    pub admin: Pubkey,
+    pub fee_bps: u16,
+    pub fee_recipient: Pubkey,
+    pub mints: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+impl Marketplace {
+    pub fn discriminator() -> [u8; 8] {
+        account_discriminator("Marketplace")
+    }
+
+    pub fn try_from_account_data(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < 8 || data[..8] != Self::discriminator() {
+            return Err(Error::NotAMarketplace);
+        }
+        Marketplace::deserialize(&mut &data[8..]).map_err(Error::Decode)
+    }
+
+    pub fn settings(&self) -> MarketplaceSettings {
+        MarketplaceSettings {
+            admin: self.admin,
+            fee_bps: self.fee_bps,
+            fee_recipient: self.fee_recipient,
+            mints: self.mints.clone(),
+        }
+    }
+}
+
+/// What the platform admin sets on a marketplace, the argument of
+/// `create_marketplace` and `update_marketplace`. The program caps the fee
+/// at 1000 basis points and the mints at 4.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq, Eq)]
+pub struct MarketplaceSettings {
+    pub admin: Pubkey,
+    pub fee_bps: u16,
+    pub fee_recipient: Pubkey,
+    pub mints: Vec<Pubkey>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escrows_from_before_marketplaces_decode_as_the_platforms() {
+        // A 32-byte order id leaves 18 zero bytes of the account's 50-byte
+        // allowance, which the two new fields read as `None` and 0.
+        let mut data = Escrow::discriminator().to_vec();
+        data.extend_from_slice(&[1; 32]);
+        data.extend_from_slice(&[2; 32]);
+        data.extend_from_slice(&32u32.to_le_bytes());
+        data.extend_from_slice(&[b'x'; 32]);
+        data.extend_from_slice(&42u64.to_le_bytes());
+        data.extend_from_slice(&[0, 255]);
+        data.extend_from_slice(&7i64.to_le_bytes());
+        data.extend_from_slice(&[0, 0, 0]);
+        data.resize(8 + 32 + 32 + 4 + 50 + 8 + 1 + 1 + 8 + 9 + 9 + 9, 0);
+
+        let escrow = Escrow::try_from_account_data(&data).unwrap();
+        assert_eq!(escrow.order_id, "x".repeat(32));
+        assert_eq!(escrow.marketplace, None);
+        assert_eq!(escrow.fee_bps, 0);
+        assert_eq!(escrow.fee(), 0);
+    }
+}
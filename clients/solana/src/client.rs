@@ -0,0 +1,234 @@
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Signature, Signer};
+use solana_sdk::transaction::Transaction;
+
+use crate::{instructions, pda, Error, Escrow, Marketplace, MarketplaceSettings, Resolution, Tenant};
+
+/// Async RPC wrapper around the escrow program. Each call sends one
+/// transaction, paid for and signed by the acting party (a keypair or a
+/// hardware wallet), and waits for confirmation at the RPC client's
+/// commitment.
+///
+/// A client acts on the platform's own escrows unless scoped to a partner
+/// marketplace with [`EscrowClient::with_marketplace`].
+pub struct EscrowClient {
+    rpc: RpcClient,
+    program_id: Pubkey,
+    /// The marketplace id and account address the client is scoped to.
+    marketplace: Option<(String, Pubkey)>,
+}
+
+impl EscrowClient {
+    pub fn new(rpc: RpcClient, program_id: Pubkey) -> Self {
+        Self { rpc, program_id, marketplace: None }
+    }
+
+    /// Scope the client to the partner marketplace `marketplace_id`: order
+    /// ids are looked up and created in its namespace, and releases and
+    /// resolutions pass the accounts its fee and admin check need.
+    pub fn with_marketplace(mut self, marketplace_id: &str) -> Result<Self, Error> {
+        let (address, _) = pda::marketplace_address(&self.program_id, marketplace_id)?;
+        self.marketplace = Some((marketplace_id.to_string(), address));
+        Ok(self)
+    }
+
+    pub fn program_id(&self) -> Pubkey {
+        self.program_id
+    }
+
+    /// The marketplace the client is scoped to, if any.
+    pub fn marketplace_id(&self) -> Option<&str> {
+        self.marketplace.as_ref().map(|(id, _)| id.as_str())
+    }
+
+    pub fn rpc(&self) -> &RpcClient {
+        &self.rpc
+    }
+
+    fn marketplace_address(&self) -> Option<&Pubkey> {
+        self.marketplace.as_ref().map(|(_, address)| address)
+    }
+
+    /// The marketplace `marketplace_id`, if it has been registered.
+    pub async fn fetch_marketplace(&self, marketplace_id: &str) -> Result<Option<Marketplace>, Error> {
+        let (address, _) = pda::marketplace_address(&self.program_id, marketplace_id)?;
+        let account = self
+            .rpc
+            .get_account_with_commitment(&address, self.rpc.commitment())
+            .await?
+            .value;
+        account.map(|account| Marketplace::try_from_account_data(&account.data)).transpose()
+    }
+
+    /// The accounts of the scoped marketplace that releases and resolutions
+    /// pass; `None` when the client is not scoped.
+    async fn tenant(&self) -> Result<Option<Tenant>, Error> {
+        let Some((marketplace_id, address)) = &self.marketplace else {
+            return Ok(None);
+        };
+        let marketplace = self
+            .fetch_marketplace(marketplace_id)
+            .await?
+            .ok_or_else(|| Error::MarketplaceNotFound(marketplace_id.clone()))?;
+        Ok(Some(Tenant { marketplace: *address, fee_recipient: marketplace.fee_recipient }))
+    }
+
+    /// The escrow for `order_id`, if it has been created.
+    pub async fn fetch_escrow(&self, order_id: &str) -> Result<Option<Escrow>, Error> {
+        let (address, _) = pda::escrow_address_in(&self.program_id, self.marketplace_address(), order_id)?;
+        let account = self
+            .rpc
+            .get_account_with_commitment(&address, self.rpc.commitment())
+            .await?
+            .value;
+        account.map(|account| Escrow::try_from_account_data(&account.data)).transpose()
+    }
+
+    /// Every escrow account of the program, optionally only those of one
+    /// buyer and/or seller, with their addresses. A scoped client only
+    /// returns its marketplace's escrows; an unscoped one returns all.
+    pub async fn list_escrows(
+        &self,
+        buyer: Option<Pubkey>,
+        seller: Option<Pubkey>,
+    ) -> Result<Vec<(Pubkey, Escrow)>, Error> {
+        // Field offsets after the 8-byte discriminator: buyer, then seller.
+        let mut filters = vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &Escrow::discriminator()))];
+        if let Some(buyer) = buyer {
+            filters.push(RpcFilterType::Memcmp(Memcmp::new_base58_encoded(8, buyer.as_ref())));
+        }
+        if let Some(seller) = seller {
+            filters.push(RpcFilterType::Memcmp(Memcmp::new_base58_encoded(40, seller.as_ref())));
+        }
+        let config = RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(self.rpc.commitment()),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        let mut escrows = self
+            .rpc
+            .get_program_accounts_with_config(&self.program_id, config)
+            .await?
+            .into_iter()
+            .map(|(address, account)| Ok((address, Escrow::try_from_account_data(&account.data)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        // The marketplace follows the variable-length order id, out of reach
+        // of a memcmp filter.
+        if let Some(marketplace) = self.marketplace_address() {
+            escrows.retain(|(_, escrow)| escrow.marketplace.as_ref() == Some(marketplace));
+        }
+        Ok(escrows)
+    }
+
+    async fn existing_escrow(&self, order_id: &str) -> Result<Escrow, Error> {
+        self.fetch_escrow(order_id)
+            .await?
+            .ok_or_else(|| Error::EscrowNotFound(order_id.to_string()))
+    }
+
+    async fn send(&self, instruction: Instruction, signer: &dyn Signer) -> Result<Signature, Error> {
+        let blockhash = self.rpc.get_latest_blockhash().await?;
+        let transaction =
+            Transaction::new_signed_with_payer(&[instruction], Some(&signer.pubkey()), &[signer], blockhash);
+        Ok(self.rpc.send_and_confirm_transaction(&transaction).await?)
+    }
+
+    /// Name the admin that resolves disputes. Signed by the program's
+    /// upgrade authority; fails if the admin is already set.
+    pub async fn initialize_config(&self, upgrade_authority: &dyn Signer, admin: Pubkey) -> Result<Signature, Error> {
+        let instruction = instructions::initialize_config(&self.program_id, &upgrade_authority.pubkey(), &admin);
+        self.send(instruction, upgrade_authority).await
+    }
+
+    /// Register the partner marketplace `marketplace_id`. Signed by the
+    /// platform admin named in the program's config.
+    pub async fn create_marketplace(
+        &self,
+        platform_admin: &dyn Signer,
+        marketplace_id: &str,
+        settings: &MarketplaceSettings,
+    ) -> Result<Signature, Error> {
+        let instruction =
+            instructions::create_marketplace(&self.program_id, &platform_admin.pubkey(), marketplace_id, settings)?;
+        self.send(instruction, platform_admin).await
+    }
+
+    /// Replace a marketplace's admin, fee and mints. Signed by the platform
+    /// admin; open escrows keep the fee they were created with.
+    pub async fn update_marketplace(
+        &self,
+        platform_admin: &dyn Signer,
+        marketplace_id: &str,
+        settings: &MarketplaceSettings,
+    ) -> Result<Signature, Error> {
+        let instruction =
+            instructions::update_marketplace(&self.program_id, &platform_admin.pubkey(), marketplace_id, settings)?;
+        self.send(instruction, platform_admin).await
+    }
+
+    /// Open an escrow of `amount` lamports for `order_id` from `buyer` to `seller`.
+    pub async fn create_escrow(
+        &self,
+        buyer: &dyn Signer,
+        seller: Pubkey,
+        order_id: &str,
+        amount: u64,
+    ) -> Result<Signature, Error> {
+        let instruction = instructions::create_escrow_in(
+            &self.program_id,
+            self.marketplace_address(),
+            &buyer.pubkey(),
+            &seller,
+            order_id,
+            amount,
+        )?;
+        self.send(instruction, buyer).await
+    }
+
+    /// Buyer confirms delivery, releasing the funds to the seller.
+    pub async fn release(&self, buyer: &dyn Signer, order_id: &str) -> Result<Signature, Error> {
+        let escrow = self.existing_escrow(order_id).await?;
+        let tenant = self.tenant().await?;
+        let instruction = instructions::confirm_delivery_in(
+            &self.program_id,
+            tenant.as_ref(),
+            &buyer.pubkey(),
+            &escrow.seller,
+            order_id,
+        )?;
+        self.send(instruction, buyer).await
+    }
+
+    /// Buyer disputes the order, locking the escrow.
+    pub async fn dispute(&self, buyer: &dyn Signer, order_id: &str) -> Result<Signature, Error> {
+        let instruction =
+            instructions::lock_dispute_in(&self.program_id, self.marketplace_address(), &buyer.pubkey(), order_id)?;
+        self.send(instruction, buyer).await
+    }
+
+    /// Admin settles a disputed escrow: the marketplace's admin for a scoped
+    /// client, the platform's otherwise.
+    pub async fn resolve(&self, admin: &dyn Signer, order_id: &str, resolution: Resolution) -> Result<Signature, Error> {
+        let escrow = self.existing_escrow(order_id).await?;
+        let tenant = self.tenant().await?;
+        let instruction = instructions::resolve_in(
+            &self.program_id,
+            tenant.as_ref(),
+            &admin.pubkey(),
+            &escrow.buyer,
+            &escrow.seller,
+            order_id,
+            resolution,
+        )?;
+        self.send(instruction, admin).await
+    }
+}
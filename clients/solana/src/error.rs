@@ -0,0 +1,60 @@
+use std::fmt;
+
+use solana_client::client_error::ClientError;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Order ids are PDA seeds, so they must be 1-32 bytes.
+    InvalidOrderId { len: usize },
+    /// Account data does not start with the `Escrow` discriminator.
+    NotAnEscrow,
+    /// Account data does not start with the `Marketplace` discriminator.
+    NotAMarketplace,
+    /// Marketplace ids are PDA seeds: 1-32 lowercase letters, digits or
+    /// dashes.
+    InvalidMarketplaceId(String),
+    /// Account or instruction data has the right discriminator but does not
+    /// decode.
+    Decode(std::io::Error),
+    /// Instruction data does not start with a known discriminator.
+    UnknownInstruction,
+    /// No escrow account exists for the order.
+    EscrowNotFound(String),
+    /// No marketplace account exists for the id.
+    MarketplaceNotFound(String),
+    Rpc(Box<ClientError>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidOrderId { len } => write!(f, "Order id must be 1-32 bytes, got {}", len),
+            Error::NotAnEscrow => write!(f, "Account is not an escrow account"),
+            Error::NotAMarketplace => write!(f, "Account is not a marketplace account"),
+            Error::InvalidMarketplaceId(id) => {
+                write!(f, "Marketplace id must be 1-32 lowercase letters, digits or dashes, got {:?}", id)
+            }
+            Error::Decode(e) => write!(f, "Failed to decode escrow data: {}", e),
+            Error::UnknownInstruction => write!(f, "Not an escrow program instruction"),
+            Error::EscrowNotFound(order_id) => write!(f, "No escrow found for order {}", order_id),
+            Error::MarketplaceNotFound(id) => write!(f, "No marketplace found with id {}", id),
+            Error::Rpc(e) => write!(f, "RPC error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Decode(e) => Some(e),
+            Error::Rpc(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<ClientError> for Error {
+    fn from(e: ClientError) -> Self {
+        Error::Rpc(Box::new(e))
+    }
+}
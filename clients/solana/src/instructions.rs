@@ -0,0 +1,400 @@
+//! Instruction builders.
+//!
+//! Each builder encodes the Anchor discriminator and arguments and lists the
+//! accounts in the order of the program's `Accounts` struct. The funds are
+//! held by the escrow PDA itself, which is also passed as `escrow_account`.
+//!
+//! The `_in` builders act on an escrow of a partner marketplace, or on the
+//! platform's own with `None`, which is what the plain builders do.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use sha2::{Digest, Sha256};
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_program;
+
+use crate::{pda, Error, MarketplaceSettings};
+
+/// How an admin settles a disputed escrow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// Return the funds to the buyer (`resolve_refund`).
+    Refund,
+    /// Pay the seller (`resolve_release`).
+    Release,
+}
+
+/// The marketplace accounts that releasing or resolving one of its escrows
+/// needs: its account, whose admin resolves, and the fee recipient it names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tenant {
+    pub marketplace: Pubkey,
+    pub fee_recipient: Pubkey,
+}
+
+impl Tenant {
+    fn accounts(&self) -> [AccountMeta; 2] {
+        [AccountMeta::new_readonly(self.marketplace, false), AccountMeta::new(self.fee_recipient, false)]
+    }
+}
+
+/// Anchor instruction discriminator: the first 8 bytes of
+/// `sha256("global:<name>")`.
+pub fn discriminator(name: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("global:{}", name));
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash[..8]);
+    discriminator
+}
+
+/// An escrow program instruction, decoded from its data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EscrowInstruction {
+    CreateEscrow { order_id: String, amount: u64, bump: u8 },
+    ConfirmDelivery,
+    LockDispute,
+    Resolve(Resolution),
+    /// The marketplace's account is the instruction's first.
+    CreateMarketplace { marketplace_id: String, settings: MarketplaceSettings },
+    UpdateMarketplace(MarketplaceSettings),
+}
+
+#[derive(BorshDeserialize)]
+struct CreateEscrowData {
+    order_id: String,
+    amount: u64,
+    bump: u8,
+}
+
+/// Decode instruction data sent to the escrow program. Fails on data that
+/// does not start with one of the program's discriminators.
+/// `create_marketplace_escrow` decodes as `CreateEscrow`; which marketplace
+/// it was is in its accounts.
+pub fn decode(data: &[u8]) -> Result<EscrowInstruction, Error> {
+    if data.len() < 8 {
+        return Err(Error::UnknownInstruction);
+    }
+    let (name, mut args) = data.split_at(8);
+    if name == discriminator("create_escrow") || name == discriminator("create_marketplace_escrow") {
+        let args = CreateEscrowData::deserialize(&mut args).map_err(Error::Decode)?;
+        Ok(EscrowInstruction::CreateEscrow {
+            order_id: args.order_id,
+            amount: args.amount,
+            bump: args.bump,
+        })
+    } else if name == discriminator("confirm_delivery") {
+        Ok(EscrowInstruction::ConfirmDelivery)
+    } else if name == discriminator("lock_dispute") {
+        Ok(EscrowInstruction::LockDispute)
+    } else if name == discriminator("resolve_refund") {
+        Ok(EscrowInstruction::Resolve(Resolution::Refund))
+    } else if name == discriminator("resolve_release") {
+        Ok(EscrowInstruction::Resolve(Resolution::Release))
+    } else if name == discriminator("create_marketplace") {
+        let marketplace_id = String::deserialize(&mut args).map_err(Error::Decode)?;
+        let settings = MarketplaceSettings::deserialize(&mut args).map_err(Error::Decode)?;
+        Ok(EscrowInstruction::CreateMarketplace { marketplace_id, settings })
+    } else if name == discriminator("update_marketplace") {
+        let settings = MarketplaceSettings::deserialize(&mut args).map_err(Error::Decode)?;
+        Ok(EscrowInstruction::UpdateMarketplace(settings))
+    } else {
+        Err(Error::UnknownInstruction)
+    }
+}
+
+#[derive(BorshSerialize)]
+struct CreateEscrowArgs<'a> {
+    order_id: &'a str,
+    amount: u64,
+    bump: u8,
+}
+
+fn data(name: &str, args: &[u8]) -> Vec<u8> {
+    let mut data = discriminator(name).to_vec();
+    data.extend_from_slice(args);
+    data
+}
+
+/// `initialize_config`: name the dispute admin. Signed by the program's
+/// upgrade authority, once after deployment.
+pub fn initialize_config(program_id: &Pubkey, upgrade_authority: &Pubkey, admin: &Pubkey) -> Instruction {
+    let (config, _) = pda::config_address(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(config, false),
+            AccountMeta::new(*upgrade_authority, true),
+            AccountMeta::new_readonly(pda::program_data_address(program_id), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: data("initialize_config", admin.as_ref()),
+    }
+}
+
+/// `create_marketplace`: register a partner marketplace. Signed by the
+/// platform admin, who pays its rent.
+pub fn create_marketplace(
+    program_id: &Pubkey,
+    platform_admin: &Pubkey,
+    marketplace_id: &str,
+    settings: &MarketplaceSettings,
+) -> Result<Instruction, Error> {
+    let (marketplace, _) = pda::marketplace_address(program_id, marketplace_id)?;
+    let mut args = marketplace_id.try_to_vec().expect("Serializing to a Vec cannot fail");
+    settings.serialize(&mut args).expect("Serializing to a Vec cannot fail");
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(marketplace, false),
+            AccountMeta::new(*platform_admin, true),
+            AccountMeta::new_readonly(pda::config_address(program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: data("create_marketplace", &args),
+    })
+}
+
+/// `update_marketplace`: replace a marketplace's settings. Signed by the
+/// platform admin.
+pub fn update_marketplace(
+    program_id: &Pubkey,
+    platform_admin: &Pubkey,
+    marketplace_id: &str,
+    settings: &MarketplaceSettings,
+) -> Result<Instruction, Error> {
+    let (marketplace, _) = pda::marketplace_address(program_id, marketplace_id)?;
+    let args = settings.try_to_vec().expect("Serializing to a Vec cannot fail");
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(marketplace, false),
+            AccountMeta::new_readonly(*platform_admin, true),
+            AccountMeta::new_readonly(pda::config_address(program_id).0, false),
+        ],
+        data: data("update_marketplace", &args),
+    })
+}
+
+/// `create_escrow`: open the escrow for `order_id`, paid by `buyer`.
+pub fn create_escrow(
+    program_id: &Pubkey,
+    buyer: &Pubkey,
+    seller: &Pubkey,
+    order_id: &str,
+    amount: u64,
+) -> Result<Instruction, Error> {
+    create_escrow_in(program_id, None, buyer, seller, order_id, amount)
+}
+
+/// `create_escrow`, or `create_marketplace_escrow` for an order of
+/// `marketplace` (its account address).
+pub fn create_escrow_in(
+    program_id: &Pubkey,
+    marketplace: Option<&Pubkey>,
+    buyer: &Pubkey,
+    seller: &Pubkey,
+    order_id: &str,
+    amount: u64,
+) -> Result<Instruction, Error> {
+    let (escrow, bump) = pda::escrow_address_in(program_id, marketplace, order_id)?;
+    let args = CreateEscrowArgs { order_id, amount, bump }
+        .try_to_vec()
+        .expect("Serializing to a Vec cannot fail");
+    let mut accounts = vec![
+        AccountMeta::new(escrow, false),
+        AccountMeta::new(*buyer, true),
+        AccountMeta::new_readonly(*seller, false),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    let name = match marketplace {
+        Some(marketplace) => {
+            accounts.push(AccountMeta::new_readonly(*marketplace, false));
+            "create_marketplace_escrow"
+        }
+        None => "create_escrow",
+    };
+    Ok(Instruction { program_id: *program_id, accounts, data: data(name, &args) })
+}
+
+/// `confirm_delivery`: the buyer releases the funds to the seller.
+pub fn confirm_delivery(
+    program_id: &Pubkey,
+    buyer: &Pubkey,
+    seller: &Pubkey,
+    order_id: &str,
+) -> Result<Instruction, Error> {
+    confirm_delivery_in(program_id, None, buyer, seller, order_id)
+}
+
+/// `confirm_delivery` for an escrow of `tenant`, which takes its fee.
+pub fn confirm_delivery_in(
+    program_id: &Pubkey,
+    tenant: Option<&Tenant>,
+    buyer: &Pubkey,
+    seller: &Pubkey,
+    order_id: &str,
+) -> Result<Instruction, Error> {
+    let (escrow, _) = pda::escrow_address_in(program_id, tenant.map(|tenant| &tenant.marketplace), order_id)?;
+    let mut accounts = vec![
+        AccountMeta::new(escrow, false),
+        AccountMeta::new(*buyer, true),
+        AccountMeta::new(*seller, false),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    // Optional trailing accounts, which the program allows to be left out.
+    accounts.extend(tenant.iter().flat_map(|tenant| tenant.accounts()));
+    Ok(Instruction { program_id: *program_id, accounts, data: data("confirm_delivery", &[]) })
+}
+
+/// `lock_dispute`: the buyer locks the escrow pending admin resolution.
+pub fn lock_dispute(program_id: &Pubkey, buyer: &Pubkey, order_id: &str) -> Result<Instruction, Error> {
+    lock_dispute_in(program_id, None, buyer, order_id)
+}
+
+/// `lock_dispute` for an escrow of `marketplace` (its account address).
+pub fn lock_dispute_in(
+    program_id: &Pubkey,
+    marketplace: Option<&Pubkey>,
+    buyer: &Pubkey,
+    order_id: &str,
+) -> Result<Instruction, Error> {
+    let (escrow, _) = pda::escrow_address_in(program_id, marketplace, order_id)?;
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![AccountMeta::new(escrow, false), AccountMeta::new_readonly(*buyer, true)],
+        data: data("lock_dispute", &[]),
+    })
+}
+
+/// `resolve_refund` or `resolve_release`: an admin settles a locked escrow.
+pub fn resolve(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    buyer: &Pubkey,
+    seller: &Pubkey,
+    order_id: &str,
+    resolution: Resolution,
+) -> Result<Instruction, Error> {
+    resolve_in(program_id, None, admin, buyer, seller, order_id, resolution)
+}
+
+/// `resolve` for an escrow of `tenant`, signed by the marketplace's admin
+/// rather than the platform's.
+pub fn resolve_in(
+    program_id: &Pubkey,
+    tenant: Option<&Tenant>,
+    admin: &Pubkey,
+    buyer: &Pubkey,
+    seller: &Pubkey,
+    order_id: &str,
+    resolution: Resolution,
+) -> Result<Instruction, Error> {
+    let (escrow, _) = pda::escrow_address_in(program_id, tenant.map(|tenant| &tenant.marketplace), order_id)?;
+    let name = match resolution {
+        Resolution::Refund => "resolve_refund",
+        Resolution::Release => "resolve_release",
+    };
+    let mut accounts = vec![
+        AccountMeta::new(escrow, false),
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(*buyer, false),
+        AccountMeta::new(*seller, false),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(pda::config_address(program_id).0, false),
+    ];
+    accounts.extend(tenant.iter().flat_map(|tenant| tenant.accounts()));
+    Ok(Instruction { program_id: *program_id, accounts, data: data(name, &[]) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_escrow_layout() {
+        let program_id = Pubkey::new_unique();
+        let buyer = Pubkey::new_unique();
+        let seller = Pubkey::new_unique();
+        let instruction = create_escrow(&program_id, &buyer, &seller, "ORD-1", 42).unwrap();
+        let (escrow, bump) = pda::escrow_address(&program_id, "ORD-1").unwrap();
+
+        let mut expected = discriminator("create_escrow").to_vec();
+        expected.extend_from_slice(&5u32.to_le_bytes());
+        expected.extend_from_slice(b"ORD-1");
+        expected.extend_from_slice(&42u64.to_le_bytes());
+        expected.push(bump);
+        assert_eq!(instruction.data, expected);
+
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(keys, vec![escrow, buyer, seller, escrow, system_program::id()]);
+        assert!(instruction.accounts[1].is_signer);
+
+        assert_eq!(
+            decode(&instruction.data).unwrap(),
+            EscrowInstruction::CreateEscrow {
+                order_id: "ORD-1".to_string(),
+                amount: 42,
+                bump,
+            }
+        );
+    }
+
+    #[test]
+    fn marketplace_escrows_append_the_marketplace() {
+        let program_id = Pubkey::new_unique();
+        let buyer = Pubkey::new_unique();
+        let seller = Pubkey::new_unique();
+        let tenant = Tenant { marketplace: Pubkey::new_unique(), fee_recipient: Pubkey::new_unique() };
+        let (escrow, bump) = pda::escrow_address_in(&program_id, Some(&tenant.marketplace), "ORD-1").unwrap();
+
+        let create =
+            create_escrow_in(&program_id, Some(&tenant.marketplace), &buyer, &seller, "ORD-1", 42).unwrap();
+        assert_eq!(create.data[..8], discriminator("create_marketplace_escrow"));
+        assert_eq!(create.accounts[0].pubkey, escrow);
+        assert_eq!(create.accounts[5].pubkey, tenant.marketplace);
+        assert_eq!(
+            decode(&create.data).unwrap(),
+            EscrowInstruction::CreateEscrow { order_id: "ORD-1".to_string(), amount: 42, bump }
+        );
+
+        let confirm = confirm_delivery_in(&program_id, Some(&tenant), &buyer, &seller, "ORD-1").unwrap();
+        let keys: Vec<Pubkey> = confirm.accounts.iter().map(|meta| meta.pubkey).collect();
+        let tail = [tenant.marketplace, tenant.fee_recipient];
+        assert_eq!(keys[..5], [escrow, buyer, seller, escrow, system_program::id()]);
+        assert_eq!(keys[5..], tail);
+        assert!(confirm.accounts[6].is_writable);
+
+        // Platform escrows leave the optional accounts out.
+        assert_eq!(confirm_delivery(&program_id, &buyer, &seller, "ORD-1").unwrap().accounts.len(), 5);
+    }
+
+    #[test]
+    fn marketplace_settings_round_trip() {
+        let program_id = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+        let settings = MarketplaceSettings {
+            admin: Pubkey::new_unique(),
+            fee_bps: 250,
+            fee_recipient: Pubkey::new_unique(),
+            mints: vec![Pubkey::new_unique()],
+        };
+        let create = create_marketplace(&program_id, &admin, "acme", &settings).unwrap();
+        assert_eq!(
+            decode(&create.data).unwrap(),
+            EscrowInstruction::CreateMarketplace { marketplace_id: "acme".to_string(), settings: settings.clone() }
+        );
+        let update = update_marketplace(&program_id, &admin, "acme", &settings).unwrap();
+        assert_eq!(decode(&update.data).unwrap(), EscrowInstruction::UpdateMarketplace(settings));
+    }
+
+    #[test]
+    fn rejects_unusable_order_ids() {
+        let program_id = Pubkey::new_unique();
+        let buyer = Pubkey::new_unique();
+        assert!(lock_dispute(&program_id, &buyer, "").is_err());
+        assert!(lock_dispute(&program_id, &buyer, &"x".repeat(33)).is_err());
+    }
+}
@@ -0,0 +1,33 @@
+//! Off-chain client for the ProofCart escrow program
+//! (`blockchain/solana-escrow`).
+//!
+//! Backend services use this crate instead of encoding Anchor instructions
+//! by hand:
+//!
+//! - [`pda`] derives the escrow address for an order, and a partner
+//!   marketplace's address.
+//! - [`instructions`] builds each program instruction with its discriminator
+//!   and account list, and decodes instruction data.
+//! - [`accounts`] decodes the on-chain `Escrow` and `Marketplace` accounts.
+//! - [`EscrowClient`] wraps the above in async RPC calls: `create_escrow`,
+//!   `release`, `dispute` and `resolve`, plus the one-time
+//!   `initialize_config`.
+//!
+//! Partner marketplaces share the program. [`EscrowClient::with_marketplace`]
+//! scopes a client to one: its order ids live in the marketplace's
+//! namespace, its disputes are resolved by the marketplace's admin, and
+//! releases pay the marketplace's fee.
+//!
+//! The program id is supplied by the caller, since it is assigned at
+//! deployment.
+
+pub mod accounts;
+mod client;
+mod error;
+pub mod instructions;
+pub mod pda;
+
+pub use accounts::{Escrow, EscrowStatus, Marketplace, MarketplaceSettings};
+pub use client::EscrowClient;
+pub use error::Error;
+pub use instructions::{EscrowInstruction, Resolution, Tenant};
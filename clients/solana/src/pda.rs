@@ -0,0 +1,104 @@
+//! Program-derived addresses.
+
+use solana_sdk::bpf_loader_upgradeable;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::Error;
+
+/// First seed of every escrow PDA. The order id is the last; escrows of a
+/// marketplace have the marketplace's address in between.
+pub const ESCROW_SEED: &[u8] = b"escrow";
+
+/// First seed of a marketplace PDA; the second is its id.
+pub const MARKETPLACE_SEED: &[u8] = b"marketplace";
+
+/// Only seed of the program's `Config` PDA, which names the admin.
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// Longest order id usable as a seed.
+pub const MAX_ORDER_ID_LEN: usize = 32;
+
+/// Longest marketplace id.
+pub const MAX_MARKETPLACE_ID_LEN: usize = 32;
+
+pub fn check_order_id(order_id: &str) -> Result<(), Error> {
+    if order_id.is_empty() || order_id.len() > MAX_ORDER_ID_LEN {
+        return Err(Error::InvalidOrderId { len: order_id.len() });
+    }
+    Ok(())
+}
+
+pub fn check_marketplace_id(marketplace_id: &str) -> Result<(), Error> {
+    let valid = !marketplace_id.is_empty()
+        && marketplace_id.len() <= MAX_MARKETPLACE_ID_LEN
+        && marketplace_id.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-');
+    if !valid {
+        return Err(Error::InvalidMarketplaceId(marketplace_id.to_string()));
+    }
+    Ok(())
+}
+
+/// Escrow account for the platform's own `order_id` and its bump seed.
+pub fn escrow_address(program_id: &Pubkey, order_id: &str) -> Result<(Pubkey, u8), Error> {
+    escrow_address_in(program_id, None, order_id)
+}
+
+/// Escrow account for `order_id` within `marketplace` (its account
+/// address), or the platform's own with `None`, and its bump seed.
+pub fn escrow_address_in(
+    program_id: &Pubkey,
+    marketplace: Option<&Pubkey>,
+    order_id: &str,
+) -> Result<(Pubkey, u8), Error> {
+    check_order_id(order_id)?;
+    let namespace: &[u8] = marketplace.map_or(&[], |marketplace| marketplace.as_ref());
+    Ok(Pubkey::find_program_address(&[ESCROW_SEED, namespace, order_id.as_bytes()], program_id))
+}
+
+/// The `Marketplace` account for `marketplace_id` and its bump seed.
+pub fn marketplace_address(program_id: &Pubkey, marketplace_id: &str) -> Result<(Pubkey, u8), Error> {
+    check_marketplace_id(marketplace_id)?;
+    Ok(Pubkey::find_program_address(&[MARKETPLACE_SEED, marketplace_id.as_bytes()], program_id))
+}
+
+/// The program's `Config` account and its bump seed.
+pub fn config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+/// The upgradeable loader's program data account of `program_id`, which
+/// holds its upgrade authority.
+pub fn program_data_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id()).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marketplaces_namespace_order_ids() {
+        let program_id = Pubkey::new_unique();
+        let (acme, _) = marketplace_address(&program_id, "acme").unwrap();
+        let (globex, _) = marketplace_address(&program_id, "globex").unwrap();
+
+        // No marketplace keeps the original address.
+        let (platform, _) = escrow_address(&program_id, "ORD-1").unwrap();
+        let legacy = Pubkey::find_program_address(&[ESCROW_SEED, b"ORD-1"], &program_id).0;
+        assert_eq!(platform, legacy);
+
+        let (in_acme, _) = escrow_address_in(&program_id, Some(&acme), "ORD-1").unwrap();
+        let (in_globex, _) = escrow_address_in(&program_id, Some(&globex), "ORD-1").unwrap();
+        assert_ne!(in_acme, platform);
+        assert_ne!(in_acme, in_globex);
+    }
+
+    #[test]
+    fn rejects_unusable_marketplace_ids() {
+        let program_id = Pubkey::new_unique();
+        for id in ["", "Acme", "acme shop", &"a".repeat(33)] {
+            assert!(marketplace_address(&program_id, id).is_err(), "{:?}", id);
+        }
+        assert!(marketplace_address(&program_id, "acme-2").is_ok());
+    }
+}
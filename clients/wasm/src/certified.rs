@@ -0,0 +1,164 @@
+//! Checking certified registry data against the IC root key.
+//!
+//! The canister publishes the root of its `nfts` tree as certified data
+//! (`certification.rs` in `blockchain/icp-nft`). A subnet certificate signs
+//! that root, and a witness proves a serial's leaf under it, so neither the
+//! replica that answered nor our API has to be trusted.
+
+use ic_cbor::{CertificateToCbor, HashTreeToCbor};
+use ic_certificate_verification::VerifyCertificate;
+use ic_certification::{Certificate, HashTree, LookupResult};
+
+/// Label of the NFT tree in the canister's certified data.
+const NFT_TREE_LABEL: &[u8] = b"nfts";
+
+/// The IC mainnet root key, without its DER prefix.
+pub const IC_ROOT_KEY: [u8; 96] = [
+    0x81, 0x4c, 0x0e, 0x6e, 0xc7, 0x1f, 0xab, 0x58, 0x3b, 0x08, 0xbd, 0x81,
+    0x37, 0x3c, 0x25, 0x5c, 0x3c, 0x37, 0x1b, 0x2e, 0x84, 0x86, 0x3c, 0x98,
+    0xa4, 0xf1, 0xe0, 0x8b, 0x74, 0x23, 0x5d, 0x14, 0xfb, 0x5d, 0x9c, 0x0c,
+    0xd5, 0x46, 0xd9, 0x68, 0x5f, 0x91, 0x3a, 0x0c, 0x0b, 0x2c, 0xc5, 0x34,
+    0x15, 0x83, 0xbf, 0x4b, 0x43, 0x92, 0xe4, 0x67, 0xdb, 0x96, 0xd6, 0x5b,
+    0x9b, 0xb4, 0xcb, 0x71, 0x71, 0x12, 0xf8, 0x47, 0x2e, 0x0d, 0x5a, 0x4d,
+    0x14, 0x50, 0x5f, 0xfd, 0x74, 0x84, 0xb0, 0x12, 0x91, 0x09, 0x1c, 0x5f,
+    0x87, 0xb9, 0x88, 0x83, 0x46, 0x3f, 0x98, 0x09, 0x1a, 0x0b, 0xaa, 0xae,
+];
+
+/// Length of the DER prefix on a BLS12-381 root key, as `agent.rootKey`
+/// holds it.
+const DER_PREFIX_LEN: usize = 37;
+
+/// Certificates may come from a replica whose clock is slightly ahead.
+const MAX_CLOCK_SKEW_NS: u64 = 5 * 60 * 1_000_000_000;
+
+/// What a certificate and witness prove about one serial.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CertifiedLeaf {
+    /// When the subnet signed the certificate, in ns since the epoch.
+    pub certified_at: u64,
+    /// The serial's leaf, or `None` if the witness proves it is absent.
+    pub leaf: Option<[u8; 32]>,
+}
+
+/// A root key as raw bytes: DER-encoded keys lose their prefix.
+pub fn raw_root_key(key: &[u8]) -> &[u8] {
+    if key.len() == DER_PREFIX_LEN + IC_ROOT_KEY.len() {
+        &key[DER_PREFIX_LEN..]
+    } else {
+        key
+    }
+}
+
+fn decode_leb128(bytes: &[u8]) -> Option<u64> {
+    let mut value: u64 = 0;
+    for (i, byte) in bytes.iter().enumerate() {
+        let bits = u64::from(byte & 0x7f);
+        value |= bits.checked_shl(7 * i as u32).filter(|_| i < 10)?;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// The witness's root must be `certified_data`; then look the serial up.
+pub fn check_witness(witness: &HashTree, certified_data: &[u8], serial_number: &str) -> Result<Option<[u8; 32]>, String> {
+    if witness.digest().as_slice() != certified_data {
+        return Err("Witness does not match the canister's certified data".to_string());
+    }
+    match witness.lookup_path([NFT_TREE_LABEL, serial_number.as_bytes()]) {
+        LookupResult::Found(leaf) => leaf
+            .try_into()
+            .map(Some)
+            .map_err(|_| "Certified leaf is not a 32-byte hash".to_string()),
+        LookupResult::Absent => Ok(None),
+        _ => Err(format!("Witness does not cover serial {}", serial_number)),
+    }
+}
+
+/// Verify `certificate` for `canister_id` against `root_key`, check that it
+/// is at most `max_age_ns` old at `now_ns`, and read the serial's leaf from
+/// `witness`.
+pub fn verify_leaf(
+    certificate: &[u8],
+    witness: &[u8],
+    canister_id: &[u8],
+    serial_number: &str,
+    root_key: &[u8],
+    now_ns: u64,
+    max_age_ns: u64,
+) -> Result<CertifiedLeaf, String> {
+    let certificate = Certificate::from_cbor(certificate).map_err(|e| format!("Invalid certificate: {}", e))?;
+    certificate
+        .verify(canister_id, raw_root_key(root_key))
+        .map_err(|e| format!("Certificate verification failed: {}", e))?;
+
+    let certified_at = match certificate.tree.lookup_path([b"time".as_slice()]) {
+        LookupResult::Found(time) => decode_leb128(time).ok_or("Invalid certificate time")?,
+        _ => return Err("Certificate has no time".to_string()),
+    };
+    if certified_at > now_ns.saturating_add(MAX_CLOCK_SKEW_NS) {
+        return Err("Certificate is from the future; check the device clock".to_string());
+    }
+    if now_ns.saturating_sub(certified_at) > max_age_ns {
+        return Err("Certificate is too old; fetch a fresh one".to_string());
+    }
+
+    let certified_data = match certificate
+        .tree
+        .lookup_path([b"canister".as_slice(), canister_id, b"certified_data".as_slice()])
+    {
+        LookupResult::Found(data) => data,
+        _ => return Err("Certificate has no certified data for the canister".to_string()),
+    };
+    let witness = HashTree::from_cbor(witness).map_err(|e| format!("Invalid witness: {}", e))?;
+    let leaf = check_witness(&witness, certified_data, serial_number)?;
+    Ok(CertifiedLeaf { certified_at, leaf })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_certified_map::{labeled, labeled_hash, AsHashTree, Hash, RbTree};
+    use serde::Serialize;
+
+    fn encode<T: Serialize>(tree: &T) -> Vec<u8> {
+        let mut serializer = serde_cbor::ser::Serializer::new(vec![]);
+        serializer.self_describe().unwrap();
+        tree.serialize(&mut serializer).unwrap();
+        serializer.into_inner()
+    }
+
+    /// A witness as the canister builds it, and its certified data.
+    fn witness(serial_number: &str) -> (HashTree, Hash) {
+        let mut tree: RbTree<String, Hash> = RbTree::new();
+        tree.insert("SN-1".to_string(), [1; 32]);
+        tree.insert("SN-2".to_string(), [2; 32]);
+        let certified_data = labeled_hash(NFT_TREE_LABEL, &tree.root_hash());
+        let bytes = encode(&labeled(NFT_TREE_LABEL, tree.witness(serial_number.as_bytes())));
+        (HashTree::from_cbor(&bytes).unwrap(), certified_data)
+    }
+
+    #[test]
+    fn reads_leaves_and_absences() {
+        let (present, root) = witness("SN-2");
+        assert_eq!(check_witness(&present, &root, "SN-2"), Ok(Some([2; 32])));
+
+        let (absent, root) = witness("SN-3");
+        assert_eq!(check_witness(&absent, &root, "SN-3"), Ok(None));
+    }
+
+    #[test]
+    fn rejects_mismatched_witnesses() {
+        let (witness, root) = witness("SN-1");
+        assert!(check_witness(&witness, &[0; 32], "SN-1").is_err());
+        assert!(check_witness(&witness, &root, "SN-2").is_err());
+    }
+
+    #[test]
+    fn decodes_certificate_times() {
+        assert_eq!(decode_leb128(&[0xe5, 0x8e, 0x26]), Some(624_485));
+        assert_eq!(decode_leb128(&[0x80]), None);
+        assert_eq!(raw_root_key(&[0; 133]).len(), 96);
+    }
+}
@@ -0,0 +1,170 @@
+//! `proofcart-verify`: the verification core, compiled to WebAssembly for
+//! the web frontend, so a buyer's browser checks authenticity itself
+//! instead of trusting our API.
+//!
+//! - `verifyQrCode`: a factory QR code's signature (`proofcart-qr-payload`)
+//! - `verifyPayload`: a certified verification payload from the canister's
+//!   `generate_verification_payload`
+//! - `verifyCertifiedRecord`: the `certificate` and `witness` of a
+//!   `/verify/<serial>` JSON response from the canister's HTTP interface
+//! - `metadataSha256`, `metadataMatches`: the off-chain metadata document
+//!   against the hash recorded at mint
+//!
+//! Certificates are checked against the IC mainnet root key unless another
+//! is passed (a local replica's `agent.rootKey`). Every function throws on a
+//! malformed input or a failed check.
+
+mod certified;
+
+use candid::Principal;
+use proofcart_qr_payload::certified::{leaf_hash, owner_hash, VerificationPayload};
+use proofcart_qr_payload::{QrPayload, VerifyingKey};
+use sha2::{Digest, Sha256};
+use wasm_bindgen::prelude::*;
+
+pub use certified::{verify_leaf, CertifiedLeaf, IC_ROOT_KEY};
+
+/// `Date.now()` in nanoseconds.
+fn now_ns() -> u64 {
+    (js_sys::Date::now() as u64).saturating_mul(1_000_000)
+}
+
+fn error(message: impl std::fmt::Display) -> JsError {
+    JsError::new(&message.to_string())
+}
+
+fn principal(text: &str) -> Result<Principal, JsError> {
+    Principal::from_text(text).map_err(|e| error(format!("Invalid canister id {}: {}", text, e)))
+}
+
+/// A factory QR code whose signature checked out.
+#[wasm_bindgen(getter_with_clone)]
+pub struct QrCode {
+    #[wasm_bindgen(js_name = serialNumber)]
+    pub serial_number: String,
+    #[wasm_bindgen(js_name = nftId)]
+    pub nft_id: u64,
+    /// Principal text of the canister holding the token.
+    #[wasm_bindgen(js_name = canisterId)]
+    pub canister_id: String,
+}
+
+/// Decode a scanned QR code (the payload or a URL ending in it) and check
+/// its signature against the manufacturer's 32-byte Ed25519 QR key.
+#[wasm_bindgen(js_name = verifyQrCode)]
+pub fn verify_qr_code(text: &str, public_key: &[u8]) -> Result<QrCode, JsError> {
+    let key: [u8; 32] = public_key.try_into().map_err(|_| error("The QR key must be 32 bytes"))?;
+    let key = VerifyingKey::from_bytes(&key).map_err(error)?;
+    let payload = QrPayload::from_text(text).map_err(error)?;
+    payload.verify(&key).map_err(error)?;
+    Ok(QrCode {
+        serial_number: payload.serial_number,
+        nft_id: payload.nft_id,
+        canister_id: payload.canister_id.to_text(),
+    })
+}
+
+/// What a certified verification payload proves.
+#[wasm_bindgen(getter_with_clone)]
+pub struct PayloadCheck {
+    #[wasm_bindgen(js_name = serialNumber)]
+    pub serial_number: String,
+    #[wasm_bindgen(js_name = nftId)]
+    pub nft_id: u64,
+    /// When the canister issued the payload, in ns since the epoch.
+    #[wasm_bindgen(js_name = issuedAt)]
+    pub issued_at: u64,
+    /// When the subnet signed its certificate, in ns since the epoch.
+    #[wasm_bindgen(js_name = certifiedAt)]
+    pub certified_at: u64,
+    /// The certified registry has this serial, as this token, owner and
+    /// status. False for a forged or altered payload.
+    pub authentic: bool,
+    /// Not revoked, as of `certifiedAt`.
+    pub verified: bool,
+}
+
+/// Check a payload from `generate_verification_payload` issued by
+/// `canister_id`: its certificate, its age, and that its token matches the
+/// certified leaf of its serial.
+#[wasm_bindgen(js_name = verifyPayload)]
+pub fn verify_payload(
+    blob: &[u8],
+    canister_id: &str,
+    max_age_seconds: u32,
+    root_key: Option<Vec<u8>>,
+) -> Result<PayloadCheck, JsError> {
+    let canister_id = principal(canister_id)?;
+    let payload = VerificationPayload::decode(blob).map_err(error)?;
+    let certified = verify_leaf(
+        &payload.certificate,
+        &payload.witness,
+        canister_id.as_slice(),
+        &payload.serial_number,
+        root_key.as_deref().unwrap_or(&IC_ROOT_KEY),
+        now_ns(),
+        u64::from(max_age_seconds) * 1_000_000_000,
+    )
+    .map_err(error)?;
+    let expected = leaf_hash(payload.nft_id, &payload.owner_hash, payload.verified);
+    Ok(PayloadCheck {
+        authentic: certified.leaf == Some(expected),
+        serial_number: payload.serial_number,
+        nft_id: payload.nft_id,
+        issued_at: payload.issued_at,
+        certified_at: certified.certified_at,
+        verified: payload.verified,
+    })
+}
+
+/// Check the `certificate` and `witness` of a `/verify/<serial>` response
+/// against the record it came with. `owner` is the owner's principal text
+/// or the response's 32-byte `owner_hash`. Returns whether the certified
+/// leaf of the serial is exactly this record.
+#[wasm_bindgen(js_name = verifyCertifiedRecord)]
+#[allow(clippy::too_many_arguments)]
+pub fn verify_certified_record(
+    certificate: &[u8],
+    witness: &[u8],
+    canister_id: &str,
+    serial_number: &str,
+    nft_id: u64,
+    owner: JsValue,
+    verified: bool,
+    max_age_seconds: u32,
+    root_key: Option<Vec<u8>>,
+) -> Result<bool, JsError> {
+    let canister_id = principal(canister_id)?;
+    let owner_hash: [u8; 32] = if let Some(text) = owner.as_string() {
+        owner_hash(principal(&text)?.as_slice())
+    } else {
+        js_sys::Uint8Array::new(&owner)
+            .to_vec()
+            .try_into()
+            .map_err(|_| error("owner must be principal text or a 32-byte hash"))?
+    };
+    let certified = verify_leaf(
+        certificate,
+        witness,
+        canister_id.as_slice(),
+        serial_number,
+        root_key.as_deref().unwrap_or(&IC_ROOT_KEY),
+        now_ns(),
+        u64::from(max_age_seconds) * 1_000_000_000,
+    )
+    .map_err(error)?;
+    Ok(certified.leaf == Some(leaf_hash(nft_id, &owner_hash, verified)))
+}
+
+/// SHA-256 of a metadata document, as the canister records it at mint
+/// (`metadata_integrity.sha256`).
+#[wasm_bindgen(js_name = metadataSha256)]
+pub fn metadata_sha256(document: &[u8]) -> Vec<u8> {
+    Sha256::digest(document).to_vec()
+}
+
+/// Whether a fetched metadata document is the one hashed at mint.
+#[wasm_bindgen(js_name = metadataMatches)]
+pub fn metadata_matches(document: &[u8], recorded_sha256: &[u8]) -> bool {
+    Sha256::digest(document).as_slice() == recorded_sha256
+}
@@ -0,0 +1,49 @@
+-- Purchase sagas, one per escrow order. Lives in the indexer's database:
+-- the coordinator follows escrows through the indexer's escrow_orders.
+
+CREATE TABLE sales (
+    order_id         TEXT PRIMARY KEY,
+    nft_id           BIGINT NOT NULL,
+    -- Base58 Solana addresses, as in escrow_orders.
+    buyer            TEXT NOT NULL,
+    seller           TEXT NOT NULL,
+    amount_lamports  BIGINT NOT NULL,
+    -- Owner of the token when it was locked; the sale transfers from it.
+    seller_principal TEXT,
+    -- Principal linked to the buyer's address, once known.
+    buyer_principal  TEXT,
+    step             TEXT NOT NULL DEFAULT 'lock' CHECK (step IN (
+                         'lock', 'await_escrow', 'await_settlement', 'transfer', 'unlock',
+                         'completed', 'refunded', 'cancelled')),
+    -- Where an `unlock` step ends: 'refunded' or 'cancelled'.
+    outcome          TEXT CHECK (outcome IN ('refunded', 'cancelled')),
+    -- Set after the last retry of a step fails; `retry` clears it.
+    failed           BOOLEAN NOT NULL DEFAULT FALSE,
+    attempts         INTEGER NOT NULL DEFAULT 0,
+    last_error       TEXT,
+    -- The buyer must fund the escrow by then, or the sale is cancelled.
+    escrow_deadline  TIMESTAMPTZ NOT NULL,
+    next_attempt_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    created_at       TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at       TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX sales_due ON sales (next_attempt_at)
+    WHERE NOT failed AND step NOT IN ('completed', 'refunded', 'cancelled');
+
+-- A token is in at most one open sale.
+CREATE UNIQUE INDEX sales_open_nft ON sales (nft_id)
+    WHERE step NOT IN ('completed', 'refunded', 'cancelled');
+
+-- Every step change and every failed attempt.
+CREATE TABLE sale_events (
+    id         BIGSERIAL PRIMARY KEY,
+    order_id   TEXT NOT NULL REFERENCES sales (order_id),
+    step       TEXT NOT NULL,
+    -- NULL for a failed attempt that stays in `step`.
+    next_step  TEXT,
+    error      TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX sale_events_order ON sale_events (order_id, id);
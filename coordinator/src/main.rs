@@ -0,0 +1,243 @@
+//! `proofcart-coordinator`: runs the purchase saga across the Solana escrow
+//! and the ICP product NFT, with its state in Postgres.
+//!
+//! ```text
+//! proofcart-coordinator start ORD-1001 --nft-id 42 --buyer <PUBKEY> --seller <PUBKEY> --amount-lamports 250000000
+//! proofcart-coordinator run --identity marketplace.pem
+//! proofcart-coordinator status ORD-1001
+//! ```
+
+mod db;
+mod metrics;
+mod saga;
+mod steps;
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use candid::Principal;
+use clap::{Parser, Subcommand};
+use proofcart_icp_client::{identity, NftClient};
+
+#[derive(Parser)]
+#[clap(name = "proofcart-coordinator", version, about = "Settle ProofCart purchases across Solana and ICP")]
+struct Cli {
+    /// Postgres URL of the indexer database
+    #[clap(long, env = "DATABASE_URL")]
+    database_url: String,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run due saga steps until stopped
+    Run {
+        /// PEM file of a principal with the Marketplace role
+        #[clap(long, env = "COORDINATOR_IDENTITY")]
+        identity: PathBuf,
+        #[clap(long, env = "IC_URL", default_value = "https://ic0.app")]
+        ic_url: String,
+        #[clap(long, env = "ICP_CANISTER_ID")]
+        canister_id: Principal,
+        /// Seconds between polls of the indexed escrows
+        #[clap(long, default_value = "15")]
+        poll_interval: u64,
+        /// Address serving `/metrics` and `/healthz`
+        #[clap(long, env = "METRICS_ADDR", default_value = "0.0.0.0:9104")]
+        metrics_addr: SocketAddr,
+    },
+    /// Start the saga of a new order; the buyer funds its escrow next
+    Start {
+        order_id: String,
+        #[clap(long)]
+        nft_id: u64,
+        /// Buyer's Solana address
+        #[clap(long)]
+        buyer: String,
+        /// Seller's Solana address; must be linked to the token's owner
+        #[clap(long)]
+        seller: String,
+        #[clap(long)]
+        amount_lamports: u64,
+        /// Minutes the buyer has to fund the escrow
+        #[clap(long, default_value = "30")]
+        escrow_timeout: u64,
+    },
+    /// Show a sale and its step log
+    Status { order_id: String },
+    /// Cancel a sale whose escrow is not funded yet; the token is unlocked
+    Cancel { order_id: String },
+    /// Retry failed sales from the step they failed at
+    Retry {
+        /// Only this sale
+        #[clap(conflicts_with = "failed")]
+        order_id: Option<String>,
+        /// Every failed sale
+        #[clap(long)]
+        failed: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run() -> Result<(), String> {
+    let cli = Cli::parse();
+    let mut db = db::connect(&cli.database_url).await?;
+    db::migrate(&mut db).await.map_err(|e| e.to_string())?;
+
+    match cli.command {
+        Command::Run { identity, ic_url, canister_id, poll_interval, metrics_addr } => {
+            let identity = identity::from_pem_file(&identity).map_err(|e| e.to_string())?;
+            let nft = Arc::new(
+                NftClient::connect(&ic_url, identity, canister_id)
+                    .await
+                    .map_err(|e| e.to_string())?,
+            );
+            let metrics = Arc::new(metrics::Metrics::default());
+            let health = Arc::new(metrics::Health { db: db::connect(&cli.database_url).await?, nft: nft.clone() });
+            let rendered = metrics.clone();
+            let telemetry = async {
+                proofcart_telemetry::serve(
+                    metrics_addr,
+                    move || rendered.render(),
+                    move || {
+                        let health = health.clone();
+                        async move { health.report().await }
+                    },
+                )
+                .await
+                .map_err(|e| format!("Metrics server on {}: {}", metrics_addr, e))
+            };
+            let poll_interval = Duration::from_secs(poll_interval);
+            tokio::try_join!(run_until_stopped(&db, &nft, poll_interval, &metrics), telemetry)?;
+        }
+        Command::Start { order_id, nft_id, buyer, seller, amount_lamports, escrow_timeout } => {
+            let timeout = (escrow_timeout * 60) as f64;
+            db.execute(
+                "INSERT INTO sales (order_id, nft_id, buyer, seller, amount_lamports, escrow_deadline)
+                 VALUES ($1, $2, $3, $4, $5, now() + make_interval(secs => $6))",
+                &[&order_id, &(nft_id as i64), &buyer, &seller, &(amount_lamports as i64), &timeout],
+            )
+            .await
+            .map_err(|e| match e.code() {
+                Some(code) if *code == tokio_postgres::error::SqlState::UNIQUE_VIOLATION => {
+                    format!("Order {} or NFT {} already has an open sale", order_id, nft_id)
+                }
+                _ => e.to_string(),
+            })?;
+            println!("Started {}", order_id);
+        }
+        Command::Status { order_id } => {
+            let sale = db
+                .query_opt(
+                    "SELECT nft_id, step, outcome, failed, attempts, last_error, escrow_deadline::text AS deadline
+                     FROM sales WHERE order_id = $1",
+                    &[&order_id],
+                )
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("No sale {}", order_id))?;
+            println!("NFT       {}", sale.get::<_, i64>("nft_id"));
+            println!("Step      {}", sale.get::<_, String>("step"));
+            if let Some(outcome) = sale.get::<_, Option<String>>("outcome") {
+                println!("Outcome   {}", outcome);
+            }
+            println!("Deadline  {}", sale.get::<_, String>("deadline"));
+            if sale.get::<_, bool>("failed") {
+                println!("Failed    after {} attempts", sale.get::<_, i32>("attempts"));
+            }
+            if let Some(error) = sale.get::<_, Option<String>>("last_error") {
+                println!("Error     {}", error);
+            }
+            let events = db
+                .query(
+                    "SELECT created_at::text AS at, step, next_step, error FROM sale_events
+                     WHERE order_id = $1 ORDER BY id",
+                    &[&order_id],
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            for event in events {
+                let next: Option<String> = event.get("next_step");
+                let error: Option<String> = event.get("error");
+                println!(
+                    "{}  {} -> {}{}",
+                    event.get::<_, String>("at"),
+                    event.get::<_, String>("step"),
+                    next.as_deref().unwrap_or("(retry)"),
+                    error.map(|e| format!(": {}", e)).unwrap_or_default()
+                );
+            }
+        }
+        Command::Cancel { order_id } => {
+            let cancelled = db
+                .execute(
+                    "UPDATE sales SET step = 'unlock', outcome = 'cancelled', failed = FALSE, attempts = 0,
+                            next_attempt_at = now(), updated_at = now()
+                     WHERE order_id = $1 AND step IN ('lock', 'await_escrow')
+                       AND NOT EXISTS (
+                           SELECT 1 FROM escrow_orders e WHERE e.marketplace = '' AND e.order_id = sales.order_id
+                       )",
+                    &[&order_id],
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            if cancelled == 0 {
+                return Err(format!("Sale {} does not exist or its escrow is already funded", order_id));
+            }
+            db.execute(
+                "INSERT INTO sale_events (order_id, step, next_step, error) VALUES ($1, 'cancel', 'unlock', 'Cancelled by an operator')",
+                &[&order_id],
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+        Command::Retry { order_id, failed } => {
+            if order_id.is_none() && !failed {
+                return Err("Pass an order id or --failed".to_string());
+            }
+            let retried = db
+                .execute(
+                    "UPDATE sales SET failed = FALSE, attempts = 0, next_attempt_at = now(), updated_at = now()
+                     WHERE failed AND ($1::text IS NULL OR order_id = $1)",
+                    &[&order_id],
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("Retrying {} sales", retried);
+        }
+    }
+    Ok(())
+}
+
+/// Run due steps; sleep only when a pass advanced nothing.
+async fn run_until_stopped(
+    db: &tokio_postgres::Client,
+    nft: &NftClient,
+    poll_interval: Duration,
+    metrics: &metrics::Metrics,
+) -> Result<(), String> {
+    loop {
+        let advanced = steps::run_due(db, nft, poll_interval, metrics)
+            .await
+            .map_err(|e| e.to_string())?;
+        metrics.refresh(db).await.map_err(|e| e.to_string())?;
+        if advanced == 0 {
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+}
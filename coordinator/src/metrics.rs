@@ -0,0 +1,92 @@
+//! Step counters and sale gauges on `GET /metrics`; the canister and the
+//! database on `GET /healthz`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use proofcart_icp_client::NftClient;
+use proofcart_telemetry::health::{self, Report};
+use proofcart_telemetry::{Exposition, Family};
+use tokio_postgres::Client;
+
+pub struct Metrics {
+    /// Steps run, by step and outcome: `advanced`, `compensated`, `waiting`
+    /// or `failed`. Failures are almost always canister calls.
+    pub steps: Family,
+    /// Refreshed after each pass.
+    pub open_sales: AtomicU64,
+    pub failed_sales: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            steps: Family::new(&["step", "outcome"]),
+            open_sales: AtomicU64::default(),
+            failed_sales: AtomicU64::default(),
+        }
+    }
+}
+
+impl Metrics {
+    pub async fn refresh(&self, db: &Client) -> Result<(), tokio_postgres::Error> {
+        let row = db
+            .query_one(
+                "SELECT count(*) FILTER (WHERE NOT failed), count(*) FILTER (WHERE failed)
+                 FROM sales WHERE step NOT IN ('completed', 'refunded', 'cancelled')",
+                &[],
+            )
+            .await?;
+        self.open_sales.store(row.get::<_, i64>(0) as u64, Ordering::Relaxed);
+        self.failed_sales.store(row.get::<_, i64>(1) as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = Exposition::new();
+        out.family(
+            "proofcart_coordinator_steps_total",
+            "Saga steps run, by step and outcome.",
+            &self.steps,
+        )
+        .gauge(
+            "proofcart_coordinator_open_sales",
+            "Sales in progress and not failed.",
+            self.open_sales.load(Ordering::Relaxed),
+        )
+        .gauge(
+            "proofcart_coordinator_failed_sales",
+            "Sales that ran out of attempts and await a retry.",
+            self.failed_sales.load(Ordering::Relaxed),
+        );
+        out.finish()
+    }
+}
+
+pub struct Health {
+    pub db: Client,
+    pub nft: Arc<NftClient>,
+}
+
+impl Health {
+    pub async fn report(&self) -> Report {
+        Report::new(vec![health::postgres(&self.db).await, health::icp(self.nft.agent()).await])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_steps_by_outcome() {
+        let metrics = Metrics::default();
+        metrics.steps.inc(&["transfer", "failed"]);
+        metrics.steps.inc(&["lock", "advanced"]);
+        metrics.failed_sales.store(1, Ordering::Relaxed);
+
+        let text = metrics.render();
+        assert!(text.contains("\nproofcart_coordinator_steps_total{step=\"transfer\",outcome=\"failed\"} 1\n"));
+        assert!(text.contains("\nproofcart_coordinator_failed_sales 1\n"));
+    }
+}
@@ -0,0 +1,214 @@
+//! Steps of the purchase saga and the decisions that depend only on the
+//! indexed escrow.
+//!
+//! ```text
+//! lock ──► await_escrow ──► await_settlement ──► transfer ──► completed
+//!              │                   │
+//!              ▼                   ▼
+//!           unlock ──► cancelled   unlock ──► refunded
+//! ```
+//!
+//! `lock`, `transfer` and `unlock` call the canister and are retried with
+//! backoff. The two `await_` steps poll the indexer and never fail. `unlock`
+//! is the compensation: it undoes `lock` and ends in the saga's `outcome`.
+
+use std::time::Duration;
+
+/// Attempts of a canister step before the sale is marked failed.
+pub const MAX_ATTEMPTS: i32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// Lock the token for the order on ICP.
+    Lock,
+    /// Wait for the buyer to fund the escrow on Solana.
+    AwaitEscrow,
+    /// Wait for the escrow to be released or refunded.
+    AwaitSettlement,
+    /// Transfer the token to the buyer's linked principal.
+    Transfer,
+    /// Release the sale lock; ends in `Refunded` or `Cancelled`.
+    Unlock,
+    Completed,
+    Refunded,
+    Cancelled,
+}
+
+impl Step {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Step::Lock => "lock",
+            Step::AwaitEscrow => "await_escrow",
+            Step::AwaitSettlement => "await_settlement",
+            Step::Transfer => "transfer",
+            Step::Unlock => "unlock",
+            Step::Completed => "completed",
+            Step::Refunded => "refunded",
+            Step::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(step: &str) -> Option<Step> {
+        Some(match step {
+            "lock" => Step::Lock,
+            "await_escrow" => Step::AwaitEscrow,
+            "await_settlement" => Step::AwaitSettlement,
+            "transfer" => Step::Transfer,
+            "unlock" => Step::Unlock,
+            "completed" => Step::Completed,
+            "refunded" => Step::Refunded,
+            "cancelled" => Step::Cancelled,
+            _ => return None,
+        })
+    }
+
+    pub fn is_final(self) -> bool {
+        matches!(self, Step::Completed | Step::Refunded | Step::Cancelled)
+    }
+}
+
+/// The parts of a sale the escrow is checked against.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Terms {
+    pub buyer: String,
+    pub seller: String,
+    pub amount_lamports: i64,
+}
+
+/// An `escrow_orders` row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Escrow {
+    pub terms: Terms,
+    /// `created`, `locked`, `released` or `refunded`.
+    pub status: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// Poll again later.
+    Wait,
+    Advance(Step),
+    /// Unlock the token, then end in `outcome`.
+    Compensate { outcome: Step, reason: String },
+}
+
+/// Next move of a sale in `step` (one of the `await_` steps), given its
+/// escrow as indexed so far and whether the funding deadline has passed.
+pub fn decide(step: Step, terms: &Terms, escrow: Option<&Escrow>, deadline_passed: bool) -> Decision {
+    let Some(escrow) = escrow else {
+        return if step == Step::AwaitEscrow && deadline_passed {
+            Decision::Compensate {
+                outcome: Step::Cancelled,
+                reason: "The escrow was not funded before the deadline".to_string(),
+            }
+        } else {
+            Decision::Wait
+        };
+    };
+    if escrow.terms != *terms {
+        return Decision::Compensate {
+            outcome: Step::Cancelled,
+            reason: format!(
+                "The escrow for this order is {} -> {} for {} lamports, not {} -> {} for {}",
+                escrow.terms.buyer,
+                escrow.terms.seller,
+                escrow.terms.amount_lamports,
+                terms.buyer,
+                terms.seller,
+                terms.amount_lamports
+            ),
+        };
+    }
+    match escrow.status.as_str() {
+        "released" => Decision::Advance(Step::Transfer),
+        "refunded" => Decision::Compensate {
+            outcome: Step::Refunded,
+            reason: "The escrow was refunded".to_string(),
+        },
+        _ if step == Step::AwaitEscrow => Decision::Advance(Step::AwaitSettlement),
+        _ => Decision::Wait,
+    }
+}
+
+/// Delay before retry `attempt` (1-based): 30s, 1m, 2m, ... capped at 6h.
+pub fn backoff(attempt: i32) -> Duration {
+    let secs = 30u64.saturating_mul(1 << (attempt - 1).clamp(0, 20));
+    Duration::from_secs(secs.min(6 * 60 * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms() -> Terms {
+        Terms {
+            buyer: "Buyer".to_string(),
+            seller: "Seller".to_string(),
+            amount_lamports: 250_000_000,
+        }
+    }
+
+    fn escrow(status: &str) -> Escrow {
+        Escrow {
+            terms: terms(),
+            status: status.to_string(),
+        }
+    }
+
+    #[test]
+    fn steps_round_trip() {
+        for step in [
+            Step::Lock,
+            Step::AwaitEscrow,
+            Step::AwaitSettlement,
+            Step::Transfer,
+            Step::Unlock,
+            Step::Completed,
+            Step::Refunded,
+            Step::Cancelled,
+        ] {
+            assert_eq!(Step::parse(step.as_str()), Some(step));
+        }
+    }
+
+    #[test]
+    fn waits_for_funding_until_the_deadline() {
+        assert_eq!(decide(Step::AwaitEscrow, &terms(), None, false), Decision::Wait);
+        assert!(matches!(
+            decide(Step::AwaitEscrow, &terms(), None, true),
+            Decision::Compensate { outcome: Step::Cancelled, .. }
+        ));
+        assert_eq!(
+            decide(Step::AwaitEscrow, &terms(), Some(&escrow("created")), true),
+            Decision::Advance(Step::AwaitSettlement)
+        );
+    }
+
+    #[test]
+    fn settles_on_release_and_compensates_on_refund() {
+        assert_eq!(decide(Step::AwaitSettlement, &terms(), Some(&escrow("locked")), true), Decision::Wait);
+        assert_eq!(
+            decide(Step::AwaitSettlement, &terms(), Some(&escrow("released")), false),
+            Decision::Advance(Step::Transfer)
+        );
+        // Settled before the coordinator saw it funded.
+        assert_eq!(
+            decide(Step::AwaitEscrow, &terms(), Some(&escrow("released")), false),
+            Decision::Advance(Step::Transfer)
+        );
+        assert!(matches!(
+            decide(Step::AwaitSettlement, &terms(), Some(&escrow("refunded")), false),
+            Decision::Compensate { outcome: Step::Refunded, .. }
+        ));
+    }
+
+    #[test]
+    fn cancels_when_the_escrow_does_not_match() {
+        let mut other = escrow("created");
+        other.terms.amount_lamports = 1;
+        assert!(matches!(
+            decide(Step::AwaitEscrow, &terms(), Some(&other), false),
+            Decision::Compensate { outcome: Step::Cancelled, .. }
+        ));
+    }
+}
@@ -0,0 +1,307 @@
+//! Claims due sales and runs their current step.
+//!
+//! Every canister step checks the token first and skips the call when its
+//! effect is already there, so a step that ran but was not recorded (a lost
+//! reply, a crash) is safe to run again.
+
+use std::time::Duration;
+
+use candid::Principal;
+use proofcart_icp_client::types::{ProductNFT, SalePrice};
+use proofcart_icp_client::NftClient;
+use tokio_postgres::{Client, Row};
+
+use crate::metrics::Metrics;
+use crate::saga::{self, Decision, Escrow, Step, Terms};
+
+const BATCH_SIZE: i64 = 50;
+
+/// How long a claimed sale is hidden from other coordinators. Longer than any
+/// canister call, so a step is never run by two of them at once.
+const LEASE: &str = "5 minutes";
+
+struct Sale {
+    order_id: String,
+    nft_id: u64,
+    terms: Terms,
+    seller_principal: Option<Principal>,
+    step: Step,
+    outcome: Option<Step>,
+    attempts: i32,
+    deadline_passed: bool,
+}
+
+impl Sale {
+    fn from_row(row: &Row) -> Result<Self, String> {
+        let step: String = row.get("step");
+        Ok(Sale {
+            order_id: row.get("order_id"),
+            nft_id: row.get::<_, i64>("nft_id") as u64,
+            terms: Terms {
+                buyer: row.get("buyer"),
+                seller: row.get("seller"),
+                amount_lamports: row.get("amount_lamports"),
+            },
+            seller_principal: row
+                .get::<_, Option<String>>("seller_principal")
+                .map(|p| Principal::from_text(p).map_err(|e| e.to_string()))
+                .transpose()?,
+            step: Step::parse(&step).ok_or_else(|| format!("Unknown step {}", step))?,
+            outcome: row.get::<_, Option<String>>("outcome").as_deref().and_then(Step::parse),
+            attempts: row.get("attempts"),
+            deadline_passed: row.get("deadline_passed"),
+        })
+    }
+}
+
+/// What running a step did.
+enum Outcome {
+    Advance(Step),
+    /// Start compensating; the token is unlocked next.
+    Compensate { outcome: Step, reason: String },
+    /// Nothing to do yet.
+    Wait,
+    /// The step failed and is retried with backoff.
+    Failed(String),
+}
+
+impl Outcome {
+    /// The `outcome` label of `proofcart_coordinator_steps_total`.
+    fn label(&self) -> &'static str {
+        match self {
+            Outcome::Advance(_) => "advanced",
+            Outcome::Compensate { .. } => "compensated",
+            Outcome::Wait => "waiting",
+            Outcome::Failed(_) => "failed",
+        }
+    }
+}
+
+/// Claim due sales and run one step of each. Returns how many advanced.
+pub async fn run_due(
+    db: &Client,
+    nft: &NftClient,
+    poll_interval: Duration,
+    metrics: &Metrics,
+) -> Result<usize, tokio_postgres::Error> {
+    let claimed = db
+        .query(
+            &format!(
+                "UPDATE sales SET next_attempt_at = now() + interval '{}'
+                 WHERE order_id IN (
+                     SELECT order_id FROM sales
+                     WHERE NOT failed AND step NOT IN ('completed', 'refunded', 'cancelled')
+                       AND next_attempt_at <= now()
+                     ORDER BY next_attempt_at LIMIT $1 FOR UPDATE SKIP LOCKED)
+                 RETURNING *, escrow_deadline <= now() AS deadline_passed",
+                LEASE
+            ),
+            &[&BATCH_SIZE],
+        )
+        .await?;
+
+    let mut advanced = 0;
+    for row in &claimed {
+        let sale = match Sale::from_row(row) {
+            Ok(sale) => sale,
+            Err(e) => {
+                eprintln!("Sale {}: {}", row.get::<_, String>("order_id"), e);
+                continue;
+            }
+        };
+        let outcome = match sale.step {
+            Step::Lock => lock(db, nft, &sale).await?,
+            Step::AwaitEscrow | Step::AwaitSettlement => {
+                let escrow = indexed_escrow(db, &sale.order_id).await?;
+                match saga::decide(sale.step, &sale.terms, escrow.as_ref(), sale.deadline_passed) {
+                    Decision::Wait => Outcome::Wait,
+                    Decision::Advance(step) => Outcome::Advance(step),
+                    Decision::Compensate { outcome, reason } => Outcome::Compensate { outcome, reason },
+                }
+            }
+            Step::Transfer => transfer(db, nft, &sale).await?,
+            Step::Unlock => unlock(nft, &sale).await,
+            Step::Completed | Step::Refunded | Step::Cancelled => continue,
+        };
+        metrics.steps.inc(&[sale.step.as_str(), outcome.label()]);
+        if !matches!(outcome, Outcome::Wait | Outcome::Failed(_)) {
+            advanced += 1;
+        }
+        record(db, &sale, outcome, poll_interval).await?;
+    }
+    Ok(advanced)
+}
+
+/// The platform's escrow for the order. Sales are the platform's own, and an
+/// order id of a partner marketplace's escrow may be the same.
+async fn indexed_escrow(db: &Client, order_id: &str) -> Result<Option<Escrow>, tokio_postgres::Error> {
+    let row = db
+        .query_opt(
+            "SELECT buyer, seller, amount_lamports, status FROM escrow_orders
+             WHERE marketplace = '' AND order_id = $1",
+            &[&order_id],
+        )
+        .await?;
+    Ok(row.map(|row| Escrow {
+        terms: Terms {
+            buyer: row.get("buyer"),
+            seller: row.get("seller"),
+            amount_lamports: row.get("amount_lamports"),
+        },
+        status: row.get("status"),
+    }))
+}
+
+fn locked_for(nft: &ProductNFT, order_id: &str) -> bool {
+    nft.sale_lock.as_ref().is_some_and(|lock| lock.order_id == order_id)
+}
+
+/// Lock the token for the order, once its owner is known to be the seller.
+async fn lock(db: &Client, client: &NftClient, sale: &Sale) -> Result<Outcome, tokio_postgres::Error> {
+    let token = match client.get_nft(sale.nft_id).await {
+        Ok(token) => token,
+        Err(e) => return Ok(Outcome::Failed(e.to_string())),
+    };
+    if !locked_for(&token, &sale.order_id) {
+        match client.principal_for_solana_address(&sale.terms.seller).await {
+            Ok(Some(seller)) if seller == token.owner => {}
+            Ok(_) => {
+                return Ok(Outcome::Compensate {
+                    outcome: Step::Cancelled,
+                    reason: format!(
+                        "NFT {} is owned by {}, which has not linked the seller's address {}",
+                        sale.nft_id, token.owner, sale.terms.seller
+                    ),
+                })
+            }
+            Err(e) => return Ok(Outcome::Failed(e.to_string())),
+        }
+        if let Err(e) = client.lock_for_sale(sale.nft_id, &sale.order_id).await {
+            return Ok(Outcome::Failed(e.to_string()));
+        }
+    }
+    db.execute(
+        "UPDATE sales SET seller_principal = $2 WHERE order_id = $1",
+        &[&sale.order_id, &token.owner.to_text()],
+    )
+    .await?;
+    Ok(Outcome::Advance(Step::AwaitEscrow))
+}
+
+/// Transfer the token to the principal the buyer linked to their address.
+async fn transfer(db: &Client, client: &NftClient, sale: &Sale) -> Result<Outcome, tokio_postgres::Error> {
+    let Some(seller) = sale.seller_principal else {
+        return Ok(Outcome::Failed("The seller's principal was not recorded when locking".to_string()));
+    };
+    let buyer = match client.principal_for_solana_address(&sale.terms.buyer).await {
+        Ok(Some(buyer)) => buyer,
+        Ok(None) => {
+            return Ok(Outcome::Failed(format!(
+                "The buyer's address {} is not linked to an ICP principal",
+                sale.terms.buyer
+            )))
+        }
+        Err(e) => return Ok(Outcome::Failed(e.to_string())),
+    };
+    db.execute(
+        "UPDATE sales SET buyer_principal = $2 WHERE order_id = $1",
+        &[&sale.order_id, &buyer.to_text()],
+    )
+    .await?;
+
+    let token = match client.get_nft(sale.nft_id).await {
+        Ok(token) => token,
+        Err(e) => return Ok(Outcome::Failed(e.to_string())),
+    };
+    if token.owner == buyer && token.sale_lock.is_none() {
+        return Ok(Outcome::Advance(Step::Completed));
+    }
+    let price = SalePrice {
+        amount: sale.terms.amount_lamports as u64,
+        currency: "SOL".to_string(),
+        order_id: Some(sale.order_id.clone()),
+    };
+    match client
+        .transfer_from(sale.nft_id, seller, buyer, &sale.order_id, Some(price))
+        .await
+    {
+        Ok(_) => Ok(Outcome::Advance(Step::Completed)),
+        Err(e) => Ok(Outcome::Failed(e.to_string())),
+    }
+}
+
+/// Release the sale lock, if the token is still locked for this order.
+async fn unlock(client: &NftClient, sale: &Sale) -> Outcome {
+    let outcome = sale.outcome.unwrap_or(Step::Cancelled);
+    let token = match client.get_nft(sale.nft_id).await {
+        Ok(token) => token,
+        Err(e) => return Outcome::Failed(e.to_string()),
+    };
+    if !locked_for(&token, &sale.order_id) {
+        return Outcome::Advance(outcome);
+    }
+    match client.unlock(sale.nft_id).await {
+        Ok(_) => Outcome::Advance(outcome),
+        Err(e) => Outcome::Failed(e.to_string()),
+    }
+}
+
+async fn record(db: &Client, sale: &Sale, outcome: Outcome, poll_interval: Duration) -> Result<(), tokio_postgres::Error> {
+    let poll = poll_interval.as_secs_f64();
+    match outcome {
+        Outcome::Wait => {
+            db.execute(
+                "UPDATE sales SET next_attempt_at = now() + make_interval(secs => $2) WHERE order_id = $1",
+                &[&sale.order_id, &poll],
+            )
+            .await?;
+        }
+        Outcome::Advance(next) => {
+            db.execute(
+                "UPDATE sales SET step = $2, attempts = 0, last_error = NULL, next_attempt_at = now(), updated_at = now()
+                 WHERE order_id = $1",
+                &[&sale.order_id, &next.as_str()],
+            )
+            .await?;
+            log(db, sale, Some(next), None).await?;
+        }
+        Outcome::Compensate { outcome, reason } => {
+            // Nothing is locked before `lock` has succeeded.
+            let next = if sale.step == Step::Lock { outcome } else { Step::Unlock };
+            db.execute(
+                "UPDATE sales SET step = $2, outcome = $3, attempts = 0, last_error = $4, next_attempt_at = now(),
+                        updated_at = now()
+                 WHERE order_id = $1",
+                &[&sale.order_id, &next.as_str(), &outcome.as_str(), &reason],
+            )
+            .await?;
+            log(db, sale, Some(next), Some(&reason)).await?;
+        }
+        Outcome::Failed(error) => {
+            let attempt = sale.attempts + 1;
+            let failed = attempt >= saga::MAX_ATTEMPTS;
+            let delay = saga::backoff(attempt).as_secs_f64();
+            db.execute(
+                "UPDATE sales SET attempts = $2, failed = $3, last_error = $4,
+                        next_attempt_at = now() + make_interval(secs => $5), updated_at = now()
+                 WHERE order_id = $1",
+                &[&sale.order_id, &attempt, &failed, &error, &delay],
+            )
+            .await?;
+            log(db, sale, None, Some(&error)).await?;
+            if failed {
+                eprintln!("Sale {} failed at {} after {} attempts: {}", sale.order_id, sale.step.as_str(), attempt, error);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn log(db: &Client, sale: &Sale, next: Option<Step>, error: Option<&str>) -> Result<(), tokio_postgres::Error> {
+    db.execute(
+        "INSERT INTO sale_events (order_id, step, next_step, error) VALUES ($1, $2, $3, $4)",
+        &[&sale.order_id, &sale.step.as_str(), &next.map(Step::as_str), &error],
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,110 @@
+//! Running the tools the environment is made of.
+
+use std::fs::File;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use tokio::process::{Child, Command};
+
+/// How long a process gets to exit after SIGTERM before it is killed.
+const STOP_GRACE: Duration = Duration::from_secs(10);
+
+/// Run `program` to completion in `dir`, failing on a non-zero exit. Its
+/// output goes to the terminal.
+pub async fn run(dir: &Path, program: &str, args: &[&str]) -> Result<(), String> {
+    let status = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .await
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !status.success() {
+        return Err(format!("{} {} failed: {}", program, args.join(" "), status));
+    }
+    Ok(())
+}
+
+/// Run `program` and return its trimmed stdout.
+pub async fn output(dir: &Path, program: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!("{} {} failed: {}", program, args.join(" "), output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Start a long-running process with its output appended to `log`.
+pub fn spawn(dir: &Path, program: &Path, args: &[&str], env: &[(&str, String)], log: &Path) -> Result<Child, String> {
+    let open = || {
+        File::options()
+            .create(true)
+            .append(true)
+            .open(log)
+            .map_err(|e| format!("Failed to open {}: {}", log.display(), e))
+    };
+    Command::new(program)
+        .args(args)
+        .envs(env.iter().map(|(name, value)| (*name, value.as_str())))
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(open()?)
+        .stderr(open()?)
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", program.display(), e))
+}
+
+fn alive(pid: u32) -> bool {
+    kill(Pid::from_raw(pid as i32), None).is_ok()
+}
+
+/// Like [`stop`], for a process this one started. It has to be waited on
+/// here, since it is not reaped until then.
+pub async fn stop_child(child: &mut Child) {
+    if let Some(pid) = child.id() {
+        let _ = kill(Pid::from_raw(pid as i32), Signal::SIGTERM);
+    }
+    if tokio::time::timeout(STOP_GRACE, child.wait()).await.is_err() {
+        let _ = child.kill().await;
+    }
+}
+
+/// SIGTERM `pid`, then SIGKILL it if it is still running after the grace
+/// period. A process that already exited is fine. For processes started by
+/// an earlier `up`, which are no longer this one's children.
+pub async fn stop(pid: u32) {
+    if kill(Pid::from_raw(pid as i32), Signal::SIGTERM).is_err() {
+        return;
+    }
+    let started = Instant::now();
+    while alive(pid) && started.elapsed() < STOP_GRACE {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    if alive(pid) {
+        let _ = kill(Pid::from_raw(pid as i32), Signal::SIGKILL);
+    }
+}
+
+/// Poll `check` every half second until it passes or `timeout` runs out.
+pub async fn wait_for<F, Fut>(what: &str, timeout: Duration, mut check: F) -> Result<(), String>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    let started = Instant::now();
+    while !check().await {
+        if started.elapsed() > timeout {
+            return Err(format!("{} not ready after {}s", what, timeout.as_secs()));
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+-- Dispute cases, their evidence, arbitrators and a log of every change.
+-- Lives in the indexer's database: cases are opened from the indexer's
+-- escrow_disputes and closed from its escrow_resolutions.
+
+CREATE TABLE arbitrators (
+    name             TEXT PRIMARY KEY,
+    -- SHA-256 of the API token; the token is shown once when added.
+    token_sha256     BYTEA NOT NULL UNIQUE,
+    active           BOOLEAN NOT NULL DEFAULT TRUE,
+    max_open_cases   INTEGER NOT NULL DEFAULT 20,
+    last_assigned_at TIMESTAMPTZ,
+    created_at       TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE dispute_cases (
+    order_id          TEXT PRIMARY KEY,
+    -- Base58 Solana addresses and lamports, as in escrow_orders.
+    buyer             TEXT NOT NULL,
+    seller            TEXT NOT NULL,
+    amount_lamports   BIGINT NOT NULL,
+    -- Unix seconds from the block of `lock_dispute`.
+    opened_at         BIGINT,
+    status            TEXT NOT NULL DEFAULT 'open'
+                      CHECK (status IN ('open', 'assigned', 'decided', 'resolved')),
+    arbitrator        TEXT REFERENCES arbitrators (name),
+    assigned_at       TIMESTAMPTZ,
+    -- SLA deadlines: a case must be assigned by `assign_due_at` and decided
+    -- by `decide_due_at`, which is set on assignment.
+    assign_due_at     TIMESTAMPTZ NOT NULL,
+    decide_due_at     TIMESTAMPTZ,
+    sla_breached      BOOLEAN NOT NULL DEFAULT FALSE,
+    decision          TEXT CHECK (decision IN ('refund', 'release')),
+    outcome           TEXT CHECK (outcome IN (
+                          'counterfeit_confirmed', 'not_as_described', 'rejected_for_seller', 'withdrawn')),
+    decided_at        TIMESTAMPTZ,
+    -- The product, for recording the outcome on the NFT canister.
+    serial_number     TEXT,
+    -- SHA-256 over the evidence files' digests, frozen at the decision.
+    evidence_hash     BYTEA,
+    -- How the escrow was actually settled, from escrow_resolutions.
+    resolution        TEXT CHECK (resolution IN ('refund', 'release')),
+    resolution_signature TEXT,
+    outcome_recorded  BOOLEAN NOT NULL DEFAULT FALSE,
+    last_error        TEXT,
+    created_at        TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at        TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX dispute_cases_status ON dispute_cases (status);
+CREATE INDEX dispute_cases_arbitrator ON dispute_cases (arbitrator) WHERE status = 'assigned';
+
+CREATE TABLE dispute_evidence (
+    id           BIGSERIAL PRIMARY KEY,
+    order_id     TEXT NOT NULL REFERENCES dispute_cases (order_id),
+    submitted_by TEXT NOT NULL CHECK (submitted_by IN ('buyer', 'seller', 'arbitrator')),
+    file_name    TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    sha256       BYTEA NOT NULL,
+    size_bytes   BIGINT NOT NULL,
+    submitted_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (order_id, sha256)
+);
+
+CREATE TABLE dispute_events (
+    id         BIGSERIAL PRIMARY KEY,
+    order_id   TEXT NOT NULL REFERENCES dispute_cases (order_id),
+    -- 'opened', 'assigned', 'evidence', 'sla_breached', 'decided',
+    -- 'resolved', 'outcome_recorded' or 'error'.
+    kind       TEXT NOT NULL,
+    actor      TEXT,
+    detail     TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX dispute_events_order ON dispute_events (order_id, id);
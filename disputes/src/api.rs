@@ -0,0 +1,486 @@
+//! The arbitrators' HTTP API.
+//!
+//! Every request carries `Authorization: Bearer <token>`: an arbitrator's
+//! token, or the admin token, which the marketplace backend uses to submit
+//! the parties' evidence and operators use to reassign cases.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Multipart, Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::{Client, Row};
+
+use crate::cases;
+use crate::evidence::{self, Store};
+
+/// Shared by every handler.
+pub struct AppState {
+    pub db: Client,
+    pub evidence: Store,
+    pub admin_token: String,
+    pub decide_sla: Duration,
+}
+
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, message)
+    }
+
+    fn not_found(order_id: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, format!("No dispute case for order {}", order_id))
+    }
+
+    fn conflict(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, message)
+    }
+}
+
+impl From<tokio_postgres::Error> for ApiError {
+    fn from(e: tokio_postgres::Error) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Database: {}", e))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(serde_json::json!({"error": self.message}))).into_response()
+    }
+}
+
+enum Caller {
+    Admin,
+    Arbitrator(String),
+}
+
+impl Caller {
+    fn name(&self) -> &str {
+        match self {
+            Caller::Admin => "admin",
+            Caller::Arbitrator(name) => name,
+        }
+    }
+}
+
+async fn authenticate(state: &AppState, headers: &HeaderMap) -> Result<Caller, ApiError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, "Missing API token"))?;
+    if token == state.admin_token {
+        return Ok(Caller::Admin);
+    }
+    let digest = evidence::sha256(token.as_bytes()).to_vec();
+    state
+        .db
+        .query_opt("SELECT name FROM arbitrators WHERE token_sha256 = $1 AND active", &[&digest])
+        .await?
+        .map(|row| Caller::Arbitrator(row.get(0)))
+        .ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, "Unknown API token"))
+}
+
+const CASE_COLUMNS: &str = "order_id, buyer, seller, amount_lamports, opened_at, status, arbitrator,
+    extract(epoch FROM assign_due_at)::bigint AS assign_due_at,
+    extract(epoch FROM decide_due_at)::bigint AS decide_due_at,
+    sla_breached, decision, outcome, serial_number, evidence_hash, resolution, resolution_signature,
+    outcome_recorded, last_error";
+
+/// A case; deadlines are Unix seconds.
+#[derive(Serialize)]
+pub struct Case {
+    pub order_id: String,
+    pub buyer: String,
+    pub seller: String,
+    pub amount_lamports: i64,
+    pub opened_at: Option<i64>,
+    /// `open`, `assigned`, `decided` or `resolved`.
+    pub status: String,
+    pub arbitrator: Option<String>,
+    pub assign_due_at: i64,
+    pub decide_due_at: Option<i64>,
+    pub sla_breached: bool,
+    /// `refund` or `release`.
+    pub decision: Option<String>,
+    pub outcome: Option<String>,
+    pub serial_number: Option<String>,
+    /// Hex; set at the decision.
+    pub evidence_hash: Option<String>,
+    /// How the escrow was settled on-chain.
+    pub resolution: Option<String>,
+    pub resolution_signature: Option<String>,
+    pub outcome_recorded: bool,
+    pub last_error: Option<String>,
+}
+
+impl From<&Row> for Case {
+    fn from(row: &Row) -> Self {
+        Self {
+            order_id: row.get("order_id"),
+            buyer: row.get("buyer"),
+            seller: row.get("seller"),
+            amount_lamports: row.get("amount_lamports"),
+            opened_at: row.get("opened_at"),
+            status: row.get("status"),
+            arbitrator: row.get("arbitrator"),
+            assign_due_at: row.get("assign_due_at"),
+            decide_due_at: row.get("decide_due_at"),
+            sla_breached: row.get("sla_breached"),
+            decision: row.get("decision"),
+            outcome: row.get("outcome"),
+            serial_number: row.get("serial_number"),
+            evidence_hash: row.get::<_, Option<Vec<u8>>>("evidence_hash").map(hex::encode),
+            resolution: row.get("resolution"),
+            resolution_signature: row.get("resolution_signature"),
+            outcome_recorded: row.get("outcome_recorded"),
+            last_error: row.get("last_error"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Evidence {
+    pub sha256: String,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    /// `buyer`, `seller` or `arbitrator`.
+    pub submitted_by: String,
+    pub submitted_at: i64,
+}
+
+#[derive(Serialize)]
+pub struct CaseEvent {
+    pub kind: String,
+    pub actor: Option<String>,
+    pub detail: Option<String>,
+    pub at: i64,
+}
+
+#[derive(Serialize)]
+pub struct CaseDetail {
+    #[serde(flatten)]
+    pub case: Case,
+    pub evidence: Vec<Evidence>,
+    /// Hex evidence hash of the files so far.
+    pub current_evidence_hash: String,
+    pub events: Vec<CaseEvent>,
+}
+
+async fn fetch_case(db: &Client, order_id: &str) -> Result<Case, ApiError> {
+    let query = format!("SELECT {} FROM dispute_cases WHERE order_id = $1", CASE_COLUMNS);
+    db.query_opt(&query, &[&order_id])
+        .await?
+        .map(|row| Case::from(&row))
+        .ok_or_else(|| ApiError::not_found(order_id))
+}
+
+async fn fetch_evidence(db: &Client, order_id: &str) -> Result<Vec<(Evidence, [u8; 32])>, ApiError> {
+    let rows = db
+        .query(
+            "SELECT sha256, file_name, content_type, size_bytes, submitted_by,
+                    extract(epoch FROM submitted_at)::bigint AS submitted_at
+             FROM dispute_evidence WHERE order_id = $1 ORDER BY id",
+            &[&order_id],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let digest: [u8; 32] = row
+                .get::<_, &[u8]>("sha256")
+                .try_into()
+                .expect("Evidence digests are SHA-256");
+            let evidence = Evidence {
+                sha256: hex::encode(digest),
+                file_name: row.get("file_name"),
+                content_type: row.get("content_type"),
+                size_bytes: row.get("size_bytes"),
+                submitted_by: row.get("submitted_by"),
+                submitted_at: row.get("submitted_at"),
+            };
+            (evidence, digest)
+        })
+        .collect())
+}
+
+#[derive(Deserialize)]
+pub struct CaseFilter {
+    status: Option<String>,
+    arbitrator: Option<String>,
+    breached: Option<bool>,
+}
+
+/// `GET /cases`: newest first, optionally filtered.
+pub async fn list_cases(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(filter): Query<CaseFilter>,
+) -> Result<Json<Vec<Case>>, ApiError> {
+    authenticate(&state, &headers).await?;
+    let query = format!(
+        "SELECT {} FROM dispute_cases
+         WHERE ($1::text IS NULL OR status = $1)
+           AND ($2::text IS NULL OR arbitrator = $2)
+           AND ($3::boolean IS NULL OR sla_breached = $3)
+         ORDER BY created_at DESC LIMIT 500",
+        CASE_COLUMNS
+    );
+    let rows = state
+        .db
+        .query(&query, &[&filter.status, &filter.arbitrator, &filter.breached])
+        .await?;
+    Ok(Json(rows.iter().map(Case::from).collect()))
+}
+
+/// `GET /cases/{order_id}`: the case, its evidence and its history.
+pub async fn get_case(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(order_id): Path<String>,
+) -> Result<Json<CaseDetail>, ApiError> {
+    authenticate(&state, &headers).await?;
+    let case = fetch_case(&state.db, &order_id).await?;
+    let evidence = fetch_evidence(&state.db, &order_id).await?;
+    let digests: Vec<[u8; 32]> = evidence.iter().map(|(_, digest)| *digest).collect();
+    let events = state
+        .db
+        .query(
+            "SELECT kind, actor, detail, extract(epoch FROM created_at)::bigint AS at
+             FROM dispute_events WHERE order_id = $1 ORDER BY id",
+            &[&order_id],
+        )
+        .await?
+        .iter()
+        .map(|row| CaseEvent {
+            kind: row.get("kind"),
+            actor: row.get("actor"),
+            detail: row.get("detail"),
+            at: row.get("at"),
+        })
+        .collect();
+    Ok(Json(CaseDetail {
+        case,
+        evidence: evidence.into_iter().map(|(evidence, _)| evidence).collect(),
+        current_evidence_hash: hex::encode(evidence::bundle_hash(&digests)),
+        events,
+    }))
+}
+
+/// `POST /cases/{order_id}/evidence`: a multipart form with a
+/// `submitted_by` field (`buyer`, `seller` or `arbitrator`) followed by one
+/// or more `file` parts. Arbitrators may only submit as `arbitrator`, on
+/// their own cases. Evidence is closed once the case is decided.
+pub async fn upload_evidence(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(order_id): Path<String>,
+    mut form: Multipart,
+) -> Result<Json<Vec<Evidence>>, ApiError> {
+    let caller = authenticate(&state, &headers).await?;
+    let case = fetch_case(&state.db, &order_id).await?;
+    if matches!(case.status.as_str(), "decided" | "resolved") {
+        return Err(ApiError::conflict("Evidence is closed once the case is decided"));
+    }
+
+    let mut submitted_by = None;
+    let mut stored = 0;
+    while let Some(field) = form.next_field().await.map_err(|e| ApiError::bad_request(e.to_string()))? {
+        match field.name() {
+            Some("submitted_by") => {
+                let party = field.text().await.map_err(|e| ApiError::bad_request(e.to_string()))?;
+                if !matches!(party.as_str(), "buyer" | "seller" | "arbitrator") {
+                    return Err(ApiError::bad_request("submitted_by must be buyer, seller or arbitrator"));
+                }
+                if let Caller::Arbitrator(name) = &caller {
+                    if party != "arbitrator" || case.arbitrator.as_deref() != Some(name.as_str()) {
+                        return Err(ApiError::forbidden("Arbitrators submit their own evidence, on their own cases"));
+                    }
+                }
+                submitted_by = Some(party);
+            }
+            Some("file") => {
+                let party = submitted_by
+                    .clone()
+                    .ok_or_else(|| ApiError::bad_request("submitted_by must come before the files"))?;
+                let file_name = field.file_name().unwrap_or("evidence").to_string();
+                let content_type = field.content_type().unwrap_or("application/octet-stream").to_string();
+                let bytes = field.bytes().await.map_err(|e| ApiError::bad_request(e.to_string()))?;
+                let digest = state
+                    .evidence
+                    .put(&order_id, &bytes)
+                    .await
+                    .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+                state
+                    .db
+                    .execute(
+                        "INSERT INTO dispute_evidence (order_id, submitted_by, file_name, content_type, sha256, size_bytes)
+                         VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (order_id, sha256) DO NOTHING",
+                        &[&order_id, &party, &file_name, &content_type, &digest.to_vec(), &(bytes.len() as i64)],
+                    )
+                    .await?;
+                let detail = format!("{} {}", file_name, hex::encode(digest));
+                cases::event(&state.db, &order_id, "evidence", Some(caller.name()), Some(&detail)).await?;
+                stored += 1;
+            }
+            _ => {}
+        }
+    }
+    if stored == 0 {
+        return Err(ApiError::bad_request("No file parts in the form"));
+    }
+    let evidence = fetch_evidence(&state.db, &order_id).await?;
+    Ok(Json(evidence.into_iter().map(|(evidence, _)| evidence).collect()))
+}
+
+/// `GET /cases/{order_id}/evidence/{sha256}`: one file.
+pub async fn download_evidence(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((order_id, sha256)): Path<(String, String)>,
+) -> Result<Response, ApiError> {
+    authenticate(&state, &headers).await?;
+    let digest: [u8; 32] = hex::decode(&sha256)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| ApiError::bad_request("Not a hex SHA-256 digest"))?;
+    let row = state
+        .db
+        .query_opt(
+            "SELECT content_type FROM dispute_evidence WHERE order_id = $1 AND sha256 = $2",
+            &[&order_id, &digest.to_vec()],
+        )
+        .await?
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "No such evidence for this case"))?;
+    let content_type: String = row.get(0);
+    let bytes = state
+        .evidence
+        .get(&order_id, &digest)
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(([(header::CONTENT_TYPE, content_type)], bytes).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct Assignment {
+    arbitrator: String,
+}
+
+/// `POST /cases/{order_id}/assign`: admin reassignment of an undecided case.
+pub async fn assign(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(order_id): Path<String>,
+    Json(request): Json<Assignment>,
+) -> Result<Json<Case>, ApiError> {
+    if !matches!(authenticate(&state, &headers).await?, Caller::Admin) {
+        return Err(ApiError::forbidden("Only the admin can reassign cases"));
+    }
+    let active = state
+        .db
+        .query_opt("SELECT 1 FROM arbitrators WHERE name = $1 AND active", &[&request.arbitrator])
+        .await?
+        .is_some();
+    if !active {
+        return Err(ApiError::bad_request(format!("No active arbitrator {}", request.arbitrator)));
+    }
+    fetch_case(&state.db, &order_id).await?;
+    if !cases::assign(&state.db, &order_id, &request.arbitrator, state.decide_sla, Some("admin")).await? {
+        return Err(ApiError::conflict("The case is already decided"));
+    }
+    Ok(Json(fetch_case(&state.db, &order_id).await?))
+}
+
+#[derive(Deserialize)]
+pub struct Decision {
+    /// `refund` or `release`.
+    decision: String,
+    /// `counterfeit_confirmed`, `not_as_described`, `rejected_for_seller`
+    /// or `withdrawn`.
+    outcome: String,
+    /// The disputed product; looked up from the coordinator's sale if
+    /// omitted.
+    serial_number: Option<String>,
+    note: Option<String>,
+}
+
+/// `POST /cases/{order_id}/decision`: the assigned arbitrator's ruling.
+/// Freezes the evidence hash; the resolution follows in the background.
+pub async fn decide(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(order_id): Path<String>,
+    Json(request): Json<Decision>,
+) -> Result<Json<Case>, ApiError> {
+    let Caller::Arbitrator(name) = authenticate(&state, &headers).await? else {
+        return Err(ApiError::forbidden("Only the assigned arbitrator can decide a case"));
+    };
+    if !matches!(request.decision.as_str(), "refund" | "release") {
+        return Err(ApiError::bad_request("decision must be refund or release"));
+    }
+    if cases::outcome(&request.outcome).is_none() {
+        return Err(ApiError::bad_request(
+            "outcome must be counterfeit_confirmed, not_as_described, rejected_for_seller or withdrawn",
+        ));
+    }
+    let case = fetch_case(&state.db, &order_id).await?;
+    if case.status != "assigned" || case.arbitrator.as_deref() != Some(name.as_str()) {
+        return Err(ApiError::forbidden("Only the assigned arbitrator can decide a case, once"));
+    }
+
+    let serial_number = match request.serial_number {
+        Some(serial) => Some(serial),
+        // The coordinator's table is absent when it is not deployed.
+        None => state
+            .db
+            .query_opt(
+                "SELECT t.serial_number FROM sales s JOIN nft_tokens t ON t.nft_id = s.nft_id WHERE s.order_id = $1",
+                &[&order_id],
+            )
+            .await
+            .ok()
+            .flatten()
+            .map(|row| row.get(0)),
+    };
+    let digests: Vec<[u8; 32]> = fetch_evidence(&state.db, &order_id)
+        .await?
+        .into_iter()
+        .map(|(_, digest)| digest)
+        .collect();
+    let evidence_hash = evidence::bundle_hash(&digests).to_vec();
+    let updated = state
+        .db
+        .execute(
+            "UPDATE dispute_cases SET status = 'decided', decision = $2, outcome = $3, serial_number = $4,
+                    evidence_hash = $5, decided_at = now(), updated_at = now()
+             WHERE order_id = $1 AND status = 'assigned' AND arbitrator = $6",
+            &[&order_id, &request.decision, &request.outcome, &serial_number, &evidence_hash, &name],
+        )
+        .await?;
+    if updated == 0 {
+        return Err(ApiError::conflict("The case changed; reload it"));
+    }
+    let detail = match &request.note {
+        Some(note) => format!("{} ({}): {}", request.decision, request.outcome, note),
+        None => format!("{} ({})", request.decision, request.outcome),
+    };
+    cases::event(&state.db, &order_id, "decided", Some(&name), Some(&detail)).await?;
+    Ok(Json(fetch_case(&state.db, &order_id).await?))
+}
@@ -0,0 +1,303 @@
+//! The case lifecycle, driven from the indexer's tables:
+//!
+//! ```text
+//! open ──► assigned ──► decided ──► resolved ──► outcome recorded
+//! ```
+//!
+//! - `open`: the buyer's `lock_dispute` has been indexed.
+//! - `assigned`: given to the active arbitrator with the fewest open cases.
+//! - `decided`: the arbitrator chose refund or release (`api.rs`).
+//! - `resolved`: `resolve_refund` or `resolve_release` has been indexed,
+//!   whether this service sent it or the arbiter canister did.
+//! - Outcome recorded: the NFT canister has the outcome and evidence hash.
+//!
+//! Each step is a single conditional update, so a step that ran but was not
+//! recorded is found again on the next pass.
+//!
+//! Only the platform's own escrows get cases. A partner marketplace's
+//! disputes are resolved by the marketplace's admin.
+
+use std::time::Duration;
+
+use proofcart_icp_client::types::DisputeOutcome;
+use proofcart_icp_client::NftClient;
+use proofcart_solana_client::{EscrowClient, Resolution};
+use solana_sdk::signature::Keypair;
+use tokio_postgres::Client;
+
+/// Settings of the background passes.
+pub struct Worker {
+    pub escrow: EscrowClient,
+    /// The escrow program's admin. Without it the service waits for the
+    /// resolution to be sent elsewhere (the arbiter canister or the CLI).
+    pub admin: Option<Keypair>,
+    /// A principal with the Marketplace role, to record outcomes.
+    pub nft: Option<NftClient>,
+    pub assign_sla: Duration,
+    pub decide_sla: Duration,
+}
+
+pub async fn event(
+    db: &Client,
+    order_id: &str,
+    kind: &str,
+    actor: Option<&str>,
+    detail: Option<&str>,
+) -> Result<(), tokio_postgres::Error> {
+    db.execute(
+        "INSERT INTO dispute_events (order_id, kind, actor, detail) VALUES ($1, $2, $3, $4)",
+        &[&order_id, &kind, &actor, &detail],
+    )
+    .await
+    .map(|_| ())
+}
+
+async fn fail(db: &Client, order_id: &str, error: &str) -> Result<(), tokio_postgres::Error> {
+    db.execute(
+        "UPDATE dispute_cases SET last_error = $2, updated_at = now() WHERE order_id = $1",
+        &[&order_id, &error],
+    )
+    .await?;
+    event(db, order_id, "error", None, Some(error)).await
+}
+
+pub fn outcome(name: &str) -> Option<DisputeOutcome> {
+    match name {
+        "counterfeit_confirmed" => Some(DisputeOutcome::CounterfeitConfirmed),
+        "not_as_described" => Some(DisputeOutcome::NotAsDescribed),
+        "rejected_for_seller" => Some(DisputeOutcome::RejectedForSeller),
+        "withdrawn" => Some(DisputeOutcome::Withdrawn),
+        _ => None,
+    }
+}
+
+impl Worker {
+    /// One pass of every step; returns how many cases moved.
+    pub async fn run_once(&self, db: &Client) -> Result<u64, tokio_postgres::Error> {
+        Ok(self.open_cases(db).await?
+            + self.assign(db).await?
+            + self.check_slas(db).await?
+            + self.resolve(db).await?
+            + self.sync_resolutions(db).await?
+            + self.record_outcomes(db).await?)
+    }
+
+    /// A case for every indexed dispute.
+    async fn open_cases(&self, db: &Client) -> Result<u64, tokio_postgres::Error> {
+        let rows = db
+            .query(
+                "INSERT INTO dispute_cases (order_id, buyer, seller, amount_lamports, opened_at, assign_due_at)
+                 SELECT d.order_id, e.buyer, e.seller, e.amount_lamports, d.opened_at,
+                        now() + make_interval(secs => $1)
+                 FROM escrow_disputes d JOIN escrow_orders e USING (marketplace, order_id)
+                 WHERE d.marketplace = ''
+                 ON CONFLICT (order_id) DO NOTHING
+                 RETURNING order_id",
+                &[&self.assign_sla.as_secs_f64()],
+            )
+            .await?;
+        for row in &rows {
+            event(db, row.get(0), "opened", None, None).await?;
+        }
+        Ok(rows.len() as u64)
+    }
+
+    /// Give each open case to the active arbitrator with the fewest
+    /// assigned cases, below their limit; ties go to whoever waited longest.
+    async fn assign(&self, db: &Client) -> Result<u64, tokio_postgres::Error> {
+        let open = db
+            .query("SELECT order_id FROM dispute_cases WHERE status = 'open' ORDER BY created_at", &[])
+            .await?;
+        let mut assigned = 0;
+        for case in open {
+            let order_id: String = case.get(0);
+            let Some(arbitrator) = db
+                .query_opt(
+                    "SELECT a.name FROM arbitrators a
+                     LEFT JOIN dispute_cases c ON c.arbitrator = a.name AND c.status = 'assigned'
+                     WHERE a.active
+                     GROUP BY a.name, a.max_open_cases, a.last_assigned_at
+                     HAVING count(c.order_id) < a.max_open_cases
+                     ORDER BY count(c.order_id), a.last_assigned_at NULLS FIRST
+                     LIMIT 1",
+                    &[],
+                )
+                .await?
+            else {
+                // Everyone is at their limit; the SLA check flags the wait.
+                break;
+            };
+            let name: String = arbitrator.get(0);
+            if assign(db, &order_id, &name, self.decide_sla, None).await? {
+                assigned += 1;
+            }
+        }
+        Ok(assigned)
+    }
+
+    /// Flag cases past their assignment or decision deadline, once.
+    async fn check_slas(&self, db: &Client) -> Result<u64, tokio_postgres::Error> {
+        let rows = db
+            .query(
+                "UPDATE dispute_cases SET sla_breached = TRUE, updated_at = now()
+                 WHERE NOT sla_breached
+                   AND ((status = 'open' AND assign_due_at < now())
+                     OR (status = 'assigned' AND decide_due_at < now()))
+                 RETURNING order_id, status, arbitrator",
+                &[],
+            )
+            .await?;
+        for row in &rows {
+            let order_id: &str = row.get("order_id");
+            let status: &str = row.get("status");
+            let arbitrator: Option<&str> = row.get("arbitrator");
+            let detail = match status {
+                "open" => "No arbitrator available before the assignment deadline".to_string(),
+                _ => format!("Not decided by {} before the decision deadline", arbitrator.unwrap_or("-")),
+            };
+            eprintln!("SLA breached on {}: {}", order_id, detail);
+            event(db, order_id, "sla_breached", arbitrator, Some(&detail)).await?;
+        }
+        Ok(rows.len() as u64)
+    }
+
+    /// Send the decided resolution, when this service is the escrow admin.
+    async fn resolve(&self, db: &Client) -> Result<u64, tokio_postgres::Error> {
+        let Some(admin) = &self.admin else {
+            return Ok(0);
+        };
+        let decided = db
+            .query(
+                "SELECT order_id, decision FROM dispute_cases c
+                 WHERE status = 'decided'
+                   AND NOT EXISTS (
+                       SELECT 1 FROM escrow_resolutions r WHERE r.marketplace = '' AND r.order_id = c.order_id
+                   )",
+                &[],
+            )
+            .await?;
+        let mut sent = 0;
+        for case in decided {
+            let order_id: &str = case.get("order_id");
+            let resolution = match case.get::<_, &str>("decision") {
+                "refund" => Resolution::Refund,
+                _ => Resolution::Release,
+            };
+            match self.escrow.resolve(admin, order_id, resolution).await {
+                Ok(signature) => {
+                    let signature = signature.to_string();
+                    db.execute(
+                        "UPDATE dispute_cases SET status = 'resolved', resolution = decision,
+                                resolution_signature = $2, last_error = NULL, updated_at = now()
+                         WHERE order_id = $1 AND status = 'decided'",
+                        &[&order_id, &signature],
+                    )
+                    .await?;
+                    event(db, order_id, "resolved", None, Some(&signature)).await?;
+                    sent += 1;
+                }
+                Err(e) => fail(db, order_id, &format!("Resolution failed: {}", e)).await?,
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Close cases whose escrow was settled, by this service or anyone else.
+    async fn sync_resolutions(&self, db: &Client) -> Result<u64, tokio_postgres::Error> {
+        let rows = db
+            .query(
+                "UPDATE dispute_cases c SET status = 'resolved', resolution = r.outcome,
+                        resolution_signature = r.signature, last_error = NULL, updated_at = now()
+                 FROM escrow_resolutions r
+                 WHERE r.marketplace = '' AND r.order_id = c.order_id AND c.status <> 'resolved'
+                 RETURNING c.order_id, c.decision, r.outcome, r.resolved_by",
+                &[],
+            )
+            .await?;
+        for row in &rows {
+            let order_id: &str = row.get("order_id");
+            let decision: Option<&str> = row.get("decision");
+            let settled: &str = row.get("outcome");
+            let detail = match decision {
+                Some(decision) if decision == settled => format!("Settled as {}", settled),
+                Some(decision) => format!("Settled as {}, but the decision was {}", settled, decision),
+                None => format!("Settled as {} without a decision in this service", settled),
+            };
+            event(db, order_id, "resolved", Some(row.get("resolved_by")), Some(&detail)).await?;
+        }
+        Ok(rows.len() as u64)
+    }
+
+    /// Record decided outcomes against the product on the NFT canister.
+    async fn record_outcomes(&self, db: &Client) -> Result<u64, tokio_postgres::Error> {
+        let Some(nft) = &self.nft else {
+            return Ok(0);
+        };
+        let pending = db
+            .query(
+                "SELECT order_id, serial_number, outcome, evidence_hash FROM dispute_cases
+                 WHERE status = 'resolved' AND NOT outcome_recorded
+                   AND outcome IS NOT NULL AND serial_number IS NOT NULL AND evidence_hash IS NOT NULL",
+                &[],
+            )
+            .await?;
+        let mut recorded = 0;
+        for case in pending {
+            let order_id: &str = case.get("order_id");
+            let serial_number: &str = case.get("serial_number");
+            let outcome = outcome(case.get("outcome")).expect("The table only holds known outcomes");
+            let evidence_hash: [u8; 32] = case
+                .get::<_, &[u8]>("evidence_hash")
+                .try_into()
+                .expect("Evidence hashes are SHA-256 digests");
+            let result = nft
+                .record_dispute_outcome(serial_number, order_id, outcome, evidence_hash)
+                .await
+                .map(|_| ());
+            match result {
+                // A lost reply: the first call went through.
+                Err(proofcart_icp_client::Error::Canister(message)) if message.contains("already recorded") => {}
+                Err(e) => {
+                    fail(db, order_id, &format!("Recording the outcome failed: {}", e)).await?;
+                    continue;
+                }
+                Ok(()) => {}
+            }
+            db.execute(
+                "UPDATE dispute_cases SET outcome_recorded = TRUE, last_error = NULL, updated_at = now()
+                 WHERE order_id = $1",
+                &[&order_id],
+            )
+            .await?;
+            event(db, order_id, "outcome_recorded", None, Some(serial_number)).await?;
+            recorded += 1;
+        }
+        Ok(recorded)
+    }
+}
+
+/// Assign an open or assigned case, restarting its decision deadline.
+/// False if the case is past assignment.
+pub async fn assign(
+    db: &Client,
+    order_id: &str,
+    arbitrator: &str,
+    decide_sla: Duration,
+    actor: Option<&str>,
+) -> Result<bool, tokio_postgres::Error> {
+    let updated = db
+        .execute(
+            "UPDATE dispute_cases SET status = 'assigned', arbitrator = $2, assigned_at = now(),
+                    decide_due_at = now() + make_interval(secs => $3), updated_at = now()
+             WHERE order_id = $1 AND status IN ('open', 'assigned')",
+            &[&order_id, &arbitrator, &decide_sla.as_secs_f64()],
+        )
+        .await?;
+    if updated == 0 {
+        return Ok(false);
+    }
+    db.execute("UPDATE arbitrators SET last_assigned_at = now() WHERE name = $1", &[&arbitrator])
+        .await?;
+    event(db, order_id, "assigned", actor, Some(arbitrator)).await?;
+    Ok(true)
+}
@@ -0,0 +1,89 @@
+//! Evidence files, stored on disk by content hash.
+//!
+//! A case's evidence hash is SHA-256 over the SHA-256 digests of its files,
+//! in submission order. It is frozen when the arbitrator decides, and is the
+//! `evidence_hash` recorded with the outcome on the NFT canister, so anyone
+//! holding the files can recompute it and check it against the chain.
+
+use std::path::PathBuf;
+
+use sha2::{Digest, Sha256};
+
+pub fn sha256(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(bytes).into()
+}
+
+/// The evidence hash of files with these digests, in submission order.
+pub fn bundle_hash(digests: &[[u8; 32]]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for digest in digests {
+        hasher.update(digest);
+    }
+    hasher.finalize().into()
+}
+
+pub struct Store {
+    dir: PathBuf,
+}
+
+impl Store {
+    pub fn new(dir: PathBuf) -> Result<Self, String> {
+        std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        Ok(Self { dir })
+    }
+
+    /// Order ids are free text, so their directory is named in hex.
+    fn path(&self, order_id: &str, digest: &[u8; 32]) -> PathBuf {
+        self.dir.join(hex::encode(order_id)).join(hex::encode(digest))
+    }
+
+    /// Store a file and return its digest. Storing the same file twice is a
+    /// no-op.
+    pub async fn put(&self, order_id: &str, bytes: &[u8]) -> Result<[u8; 32], String> {
+        let digest = sha256(bytes);
+        let path = self.path(order_id, &digest);
+        if tokio::fs::try_exists(&path).await.unwrap_or(false) {
+            return Ok(digest);
+        }
+        let parent = path.parent().expect("Evidence paths have a parent");
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("{}: {}", parent.display(), e))?;
+        // Written aside and renamed, so a crash never leaves a partial file
+        // under its digest.
+        let partial = path.with_extension("partial");
+        tokio::fs::write(&partial, bytes)
+            .await
+            .map_err(|e| format!("{}: {}", partial.display(), e))?;
+        tokio::fs::rename(&partial, &path)
+            .await
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(digest)
+    }
+
+    pub async fn get(&self, order_id: &str, digest: &[u8; 32]) -> Result<Vec<u8>, String> {
+        let path = self.path(order_id, digest);
+        tokio::fs::read(&path).await.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundle_hash_follows_submission_order() {
+        let photo = sha256(b"photo");
+        let receipt = sha256(b"receipt");
+        assert_eq!(bundle_hash(&[photo, receipt]), sha256(&[photo, receipt].concat()));
+        assert_ne!(bundle_hash(&[photo, receipt]), bundle_hash(&[receipt, photo]));
+        assert_eq!(bundle_hash(&[]), sha256(b""));
+    }
+
+    #[test]
+    fn order_ids_cannot_escape_the_store() {
+        let store = Store { dir: PathBuf::from("/evidence") };
+        let path = store.path("../../etc", &[0; 32]);
+        assert_eq!(path.parent().unwrap(), PathBuf::from("/evidence/2e2e2f2e2e2f657463"));
+    }
+}
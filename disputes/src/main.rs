@@ -0,0 +1,231 @@
+//! `proofcart-disputes`: case management for escrow disputes. Opens a case
+//! for every indexed `lock_dispute`, assigns it to an arbitrator, keeps the
+//! parties' evidence, enforces SLAs and drives the resolution on-chain.
+//!
+//! ```text
+//! GET  /cases?status=&arbitrator=&breached=
+//! GET  /cases/{order_id}
+//! POST /cases/{order_id}/evidence            multipart: submitted_by, file...
+//! GET  /cases/{order_id}/evidence/{sha256}
+//! POST /cases/{order_id}/assign              {"arbitrator"}               (admin)
+//! POST /cases/{order_id}/decision            {"decision", "outcome", "serial_number"?, "note"?}
+//!
+//! proofcart-disputes add-arbitrator alice --max-open-cases 10
+//! proofcart-disputes serve --admin-keypair escrow-admin.json --identity marketplace.pem
+//! ```
+
+mod api;
+mod cases;
+mod db;
+mod evidence;
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::DefaultBodyLimit;
+use axum::routing::{get, post};
+use axum::Router;
+use candid::Principal;
+use clap::{Args, Parser, Subcommand};
+use proofcart_icp_client::{identity, NftClient};
+use proofcart_solana_client::EscrowClient;
+use rand::RngCore;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::read_keypair_file;
+
+use api::AppState;
+use cases::Worker;
+use evidence::Store;
+
+/// Largest evidence upload: a few photos or a short video.
+const MAX_UPLOAD_BYTES: usize = 25 * 1024 * 1024;
+
+#[derive(Parser)]
+#[clap(name = "proofcart-disputes", version, about = "Manage ProofCart escrow disputes")]
+struct Cli {
+    /// Postgres URL of the indexer database
+    #[clap(long, env = "DATABASE_URL")]
+    database_url: String,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve the arbitrators' API and advance cases until stopped
+    Serve {
+        #[clap(long, env = "DISPUTES_ADDR", default_value = "0.0.0.0:8095")]
+        listen_addr: SocketAddr,
+        /// Bearer token of the marketplace backend and operators
+        #[clap(long, env = "DISPUTES_ADMIN_TOKEN", hide_env_values = true)]
+        admin_token: String,
+        #[clap(long, env = "EVIDENCE_DIR", default_value = "evidence")]
+        evidence_dir: PathBuf,
+        #[clap(flatten)]
+        settlement: SettlementArgs,
+        /// Hours an open case may wait for an arbitrator
+        #[clap(long, default_value = "4")]
+        assign_sla_hours: u64,
+        /// Hours an arbitrator has to decide an assigned case
+        #[clap(long, default_value = "72")]
+        decide_sla_hours: u64,
+        /// Seconds between passes over the cases
+        #[clap(long, default_value = "30")]
+        poll_interval: u64,
+    },
+    /// Add an arbitrator and print their API token
+    AddArbitrator {
+        name: String,
+        /// Cases they may hold undecided at once
+        #[clap(long, default_value = "20")]
+        max_open_cases: i32,
+    },
+    /// Stop assigning cases to an arbitrator and revoke their token; their
+    /// undecided cases stay with them until reassigned
+    DeactivateArbitrator { name: String },
+}
+
+#[derive(Args)]
+struct SettlementArgs {
+    #[clap(long, env = "SOLANA_RPC_URL", default_value = "https://api.devnet.solana.com")]
+    rpc_url: String,
+    #[clap(long, env = "SOLANA_PROGRAM_ID")]
+    program_id: Pubkey,
+    /// Keypair file of the escrow admin, to send `resolve_*` for decided
+    /// cases; unset when the arbiter canister or the CLI is the admin
+    #[clap(long, env = "ESCROW_ADMIN_KEYPAIR")]
+    admin_keypair: Option<PathBuf>,
+    /// PEM file of a principal with the Marketplace role, to record
+    /// outcomes on the NFT canister; unset skips recording
+    #[clap(long, env = "DISPUTES_IDENTITY", requires = "canister_id")]
+    identity: Option<PathBuf>,
+    #[clap(long, env = "IC_URL", default_value = "https://ic0.app")]
+    ic_url: String,
+    #[clap(long, env = "ICP_CANISTER_ID")]
+    canister_id: Option<Principal>,
+}
+
+fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/cases", get(api::list_cases))
+        .route("/cases/:order_id", get(api::get_case))
+        .route("/cases/:order_id/evidence", post(api::upload_evidence))
+        .route("/cases/:order_id/evidence/:sha256", get(api::download_evidence))
+        .route("/cases/:order_id/assign", post(api::assign))
+        .route("/cases/:order_id/decision", post(api::decide))
+        .layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES))
+        .with_state(state)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run() -> Result<(), String> {
+    let cli = Cli::parse();
+    let mut db = db::connect(&cli.database_url).await?;
+    db::migrate(&mut db).await.map_err(|e| e.to_string())?;
+
+    match cli.command {
+        Command::Serve {
+            listen_addr,
+            admin_token,
+            evidence_dir,
+            settlement,
+            assign_sla_hours,
+            decide_sla_hours,
+            poll_interval,
+        } => {
+            let decide_sla = Duration::from_secs(decide_sla_hours * 3600);
+            let admin = match &settlement.admin_keypair {
+                Some(path) => Some(read_keypair_file(path).map_err(|e| format!("{}: {}", path.display(), e))?),
+                None => None,
+            };
+            let nft = match (&settlement.identity, settlement.canister_id) {
+                (Some(path), Some(canister_id)) => {
+                    let identity = identity::from_pem_file(path).map_err(|e| e.to_string())?;
+                    Some(
+                        NftClient::connect(&settlement.ic_url, identity, canister_id)
+                            .await
+                            .map_err(|e| e.to_string())?,
+                    )
+                }
+                _ => None,
+            };
+            let rpc = RpcClient::new_with_commitment(settlement.rpc_url, CommitmentConfig::confirmed());
+            let worker = Worker {
+                escrow: EscrowClient::new(rpc, settlement.program_id),
+                admin,
+                nft,
+                assign_sla: Duration::from_secs(assign_sla_hours * 3600),
+                decide_sla,
+            };
+
+            // The worker's queries would queue behind requests on a shared
+            // connection, so it has its own.
+            let worker_db = db::connect(&cli.database_url).await?;
+            tokio::spawn(async move {
+                let poll_interval = Duration::from_secs(poll_interval);
+                loop {
+                    match worker.run_once(&worker_db).await {
+                        Ok(0) => tokio::time::sleep(poll_interval).await,
+                        Ok(_) => {}
+                        Err(e) => {
+                            eprintln!("Case pass failed: {}", e);
+                            tokio::time::sleep(poll_interval).await;
+                        }
+                    }
+                }
+            });
+
+            let state = Arc::new(AppState { db, evidence: Store::new(evidence_dir)?, admin_token, decide_sla });
+            let listener = tokio::net::TcpListener::bind(listen_addr)
+                .await
+                .map_err(|e| format!("{}: {}", listen_addr, e))?;
+            println!("Listening on {}", listen_addr);
+            axum::serve(listener, router(state)).await.map_err(|e| e.to_string())
+        }
+        Command::AddArbitrator { name, max_open_cases } => {
+            let mut token = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut token);
+            let token = format!("arb_{}", hex::encode(token));
+            let digest = evidence::sha256(token.as_bytes()).to_vec();
+            db.execute(
+                "INSERT INTO arbitrators (name, token_sha256, max_open_cases) VALUES ($1, $2, $3)",
+                &[&name, &digest, &max_open_cases],
+            )
+            .await
+            .map_err(|e| match e.code() {
+                Some(code) if *code == tokio_postgres::error::SqlState::UNIQUE_VIOLATION => {
+                    format!("Arbitrator {} already exists", name)
+                }
+                _ => e.to_string(),
+            })?;
+            println!("Arbitrator {}", name);
+            println!("Token      {}", token);
+        }
+        Command::DeactivateArbitrator { name } => {
+            let updated = db
+                .execute("UPDATE arbitrators SET active = FALSE WHERE name = $1", &[&name])
+                .await
+                .map_err(|e| e.to_string())?;
+            if updated == 0 {
+                return Err(format!("No arbitrator {}", name));
+            }
+        }
+    }
+    Ok(())
+}
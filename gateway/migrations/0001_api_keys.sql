@@ -0,0 +1,24 @@
+-- API keys for partners calling /verify at volume. Keys are issued through
+-- the admin API (admin-api/); only a SHA-256 digest of each is kept.
+CREATE TABLE api_keys (
+    id                  BIGSERIAL PRIMARY KEY,
+    name                TEXT NOT NULL UNIQUE,
+    contact             TEXT NOT NULL,
+    key_sha256          BYTEA NOT NULL UNIQUE,
+    requests_per_minute INTEGER NOT NULL CHECK (requests_per_minute > 0),
+    -- NULL: no daily cap.
+    daily_quota         BIGINT CHECK (daily_quota > 0),
+    active              BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by          TEXT NOT NULL,
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Requests per key and UTC day, added to by every gateway replica.
+-- `throttled` counts requests answered 429.
+CREATE TABLE api_key_usage (
+    key_id    BIGINT NOT NULL REFERENCES api_keys (id),
+    day       DATE NOT NULL,
+    requests  BIGINT NOT NULL DEFAULT 0,
+    throttled BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (key_id, day)
+);
@@ -0,0 +1,101 @@
+use std::env;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+use candid::Principal;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair};
+
+use crate::oracle::{Switchboard, PYTH_SOL_USD, PYTH_USDC_USD};
+
+/// Settings read from the environment.
+pub struct Config {
+    pub listen_addr: SocketAddr,
+    pub solana_rpc_url: String,
+    pub program_id: Pubkey,
+    /// Pays the fees of relayed buyer transactions. Without it the relay
+    /// endpoints answer 503.
+    pub relayer: Option<Keypair>,
+    pub ic_url: String,
+    pub nft_canister_id: Principal,
+    /// The indexer's database, for live order updates. Without it the
+    /// WebSocket endpoint answers 503.
+    pub database_url: Option<String>,
+    pub pyth_hermes_url: String,
+    pub pyth_sol_usd_feed: String,
+    pub pyth_usdc_usd_feed: String,
+    /// Crossbar URL and SOL/USD and USDC/USD feed hashes. Without them
+    /// quotes rely on Pyth alone.
+    pub switchboard: Option<Switchboard>,
+    /// Oldest oracle price that may be quoted.
+    pub max_price_age_secs: u64,
+    /// Widest confidence interval that may be quoted, relative to the price.
+    pub max_confidence_bps: u64,
+    /// Added to the oracle rate in the seller's favour.
+    pub quote_spread_bps: u64,
+    pub quote_ttl_secs: u64,
+    /// `/verify` requests a minute allowed per client address without an
+    /// API key.
+    pub anonymous_verify_per_minute: u32,
+    /// Take client addresses from `X-Forwarded-For`; set only behind a load
+    /// balancer that appends it.
+    pub trust_proxy: bool,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, String> {
+        let listen_addr = env::var("GATEWAY_ADDR")
+            .unwrap_or_else(|_| "0.0.0.0:8080".to_string())
+            .parse()
+            .map_err(|e| format!("GATEWAY_ADDR: {}", e))?;
+        let solana_rpc_url = env::var("SOLANA_RPC_URL").unwrap_or_else(|_| "https://api.devnet.solana.com".to_string());
+        let program_id =
+            Pubkey::from_str(&required("SOLANA_PROGRAM_ID")?).map_err(|e| format!("SOLANA_PROGRAM_ID: {}", e))?;
+        let relayer = match env::var("RELAYER_KEYPAIR") {
+            Ok(path) => Some(read_keypair_file(&path).map_err(|e| format!("RELAYER_KEYPAIR {}: {}", path, e))?),
+            Err(_) => None,
+        };
+        let ic_url = env::var("IC_URL").unwrap_or_else(|_| "https://ic0.app".to_string());
+        let nft_canister_id =
+            Principal::from_text(required("ICP_CANISTER_ID")?).map_err(|e| format!("ICP_CANISTER_ID: {}", e))?;
+        let database_url = env::var("DATABASE_URL").ok();
+        let switchboard = match env::var("SWITCHBOARD_CROSSBAR_URL") {
+            Ok(crossbar_url) => Some(Switchboard {
+                crossbar_url,
+                sol_usd_feed: required("SWITCHBOARD_SOL_USD_FEED")?,
+                usdc_usd_feed: required("SWITCHBOARD_USDC_USD_FEED")?,
+            }),
+            Err(_) => None,
+        };
+        Ok(Self {
+            listen_addr,
+            solana_rpc_url,
+            program_id,
+            relayer,
+            ic_url,
+            nft_canister_id,
+            database_url,
+            pyth_hermes_url: env::var("PYTH_HERMES_URL").unwrap_or_else(|_| "https://hermes.pyth.network".to_string()),
+            pyth_sol_usd_feed: env::var("PYTH_SOL_USD_FEED").unwrap_or_else(|_| PYTH_SOL_USD.to_string()),
+            pyth_usdc_usd_feed: env::var("PYTH_USDC_USD_FEED").unwrap_or_else(|_| PYTH_USDC_USD.to_string()),
+            switchboard,
+            max_price_age_secs: number("MAX_PRICE_AGE_SECS", 60)?,
+            max_confidence_bps: number("MAX_PRICE_CONFIDENCE_BPS", 100)?,
+            quote_spread_bps: number("QUOTE_SPREAD_BPS", 50)?,
+            quote_ttl_secs: number("QUOTE_TTL_SECS", 120)?,
+            anonymous_verify_per_minute: number("ANONYMOUS_VERIFY_PER_MINUTE", 60)? as u32,
+            trust_proxy: matches!(env::var("TRUST_PROXY").as_deref(), Ok("1" | "true")),
+        })
+    }
+}
+
+fn required(name: &str) -> Result<String, String> {
+    env::var(name).map_err(|_| format!("{} is not set", name))
+}
+
+fn number(name: &str, default: u64) -> Result<u64, String> {
+    match env::var(name) {
+        Ok(value) => value.parse().map_err(|e| format!("{}: {}", name, e)),
+        Err(_) => Ok(default),
+    }
+}
@@ -0,0 +1,26 @@
+//! The gateway's own migrations, applied when `DATABASE_URL` is set.
+
+use tokio_postgres::Client;
+
+/// Recorded in `gateway_migrations`, apart from the indexer's versions.
+const MIGRATIONS: &[(i32, &str)] = &[(1, include_str!("../migrations/0001_api_keys.sql"))];
+
+pub async fn migrate(db: &mut Client) -> Result<(), tokio_postgres::Error> {
+    db.batch_execute("CREATE TABLE IF NOT EXISTS gateway_migrations (version INTEGER PRIMARY KEY)")
+        .await?;
+    for (version, sql) in MIGRATIONS {
+        let tx = db.transaction().await?;
+        tx.batch_execute("LOCK TABLE gateway_migrations IN EXCLUSIVE MODE").await?;
+        let applied = tx
+            .query_opt("SELECT 1 FROM gateway_migrations WHERE version = $1", &[version])
+            .await?
+            .is_some();
+        if !applied {
+            tx.batch_execute(sql).await?;
+            tx.execute("INSERT INTO gateway_migrations (version) VALUES ($1)", &[version])
+                .await?;
+        }
+        tx.commit().await?;
+    }
+    Ok(())
+}
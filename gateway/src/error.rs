@@ -0,0 +1,74 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use solana_client::client_error::ClientError;
+use utoipa::ToSchema;
+
+/// Body of every error response.
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+}
+
+/// A handler failure, answered with its status and message.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    fn upstream(message: impl ToString) -> Self {
+        Self::new(StatusCode::BAD_GATEWAY, message.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(ErrorBody { error: self.message })).into_response()
+    }
+}
+
+impl From<proofcart_solana_client::Error> for ApiError {
+    fn from(e: proofcart_solana_client::Error) -> Self {
+        use proofcart_solana_client::Error;
+        match e {
+            Error::InvalidOrderId { .. } => Self::bad_request(e.to_string()),
+            Error::EscrowNotFound(_) => Self::not_found(e.to_string()),
+            _ => Self::upstream(e),
+        }
+    }
+}
+
+/// The canister rejects a lookup when the serial is unknown or burned; its
+/// message says which.
+impl From<proofcart_icp_client::Error> for ApiError {
+    fn from(e: proofcart_icp_client::Error) -> Self {
+        match e {
+            proofcart_icp_client::Error::Canister(message) => Self::not_found(message),
+            _ => Self::upstream(e),
+        }
+    }
+}
+
+impl From<ClientError> for ApiError {
+    fn from(e: ClientError) -> Self {
+        Self::upstream(e)
+    }
+}
@@ -0,0 +1,566 @@
+//! API keys and rate limits on `/verify`.
+//!
+//! Partners send `X-API-Key: pk_...` and get their key's own per-minute
+//! limit and daily quota, so a price-comparison crawl never competes with
+//! shoppers scanning QR codes. Requests without a key get a smaller limit
+//! per client address. Keys are issued through the admin API
+//! (`admin-api/`).
+//!
+//! Limits are token buckets held in memory, so each replica enforces its
+//! own. Keys are cached for a minute and usage is added to `api_key_usage`
+//! every few seconds; a revoked key or a spent quota takes effect within a
+//! minute. A key not known to be valid is charged to its address's
+//! anonymous limit before it is looked up, so made-up keys cannot buy
+//! unlimited database queries; unknown keys are cached too, in a cache of
+//! bounded size.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use deadpool_postgres::Pool;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+use crate::error::{ApiError, ErrorBody};
+use crate::AppState;
+
+const HEADER: &str = "x-api-key";
+
+/// How long a key's limits and the day's usage are trusted before they are
+/// read again.
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// How often usage is added to `api_key_usage`.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A bucket left alone this long is full again, so it is dropped.
+const IDLE: Duration = Duration::from_secs(120);
+
+/// Valid keys are kept this long after their last read, as a fallback
+/// while the database is unreachable.
+const KEY_IDLE: Duration = Duration::from_secs(3600);
+
+/// Most keys cached at once, known and unknown.
+const MAX_CACHED_KEYS: usize = 10_000;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// A token bucket holding up to a minute's worth of requests.
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(per_minute: u32, now: Instant) -> Self {
+        Self { tokens: per_minute as f64, updated: now }
+    }
+
+    /// Take one request. `Ok` carries the requests left, `Err` the seconds
+    /// until the next one is allowed.
+    fn take(&mut self, per_minute: u32, now: Instant) -> Result<u32, u64> {
+        let rate = per_minute as f64 / 60.0;
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(per_minute as f64);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(self.tokens as u32)
+        } else {
+            Err(((1.0 - self.tokens) / rate).ceil() as u64)
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Caller {
+    Key(i64),
+    Address(IpAddr),
+}
+
+/// A key's limits and the day's usage, as last read.
+struct Key {
+    id: i64,
+    requests_per_minute: u32,
+    daily_quota: Option<i64>,
+    /// Every replica's requests today when read, plus this one's since.
+    used_today: i64,
+}
+
+struct Cached {
+    /// `None` for an unknown or revoked key.
+    key: Option<Key>,
+    fetched: Instant,
+    day: u64,
+}
+
+impl Cached {
+    fn fresh(&self, today: u64) -> bool {
+        self.day == today && self.fetched.elapsed() < CACHE_TTL
+    }
+
+    /// Whether `prune` may drop it: unknown keys once they need reading
+    /// again, valid ones once too old to fall back on.
+    fn expired(&self) -> bool {
+        let ttl = if self.key.is_some() { KEY_IDLE } else { CACHE_TTL };
+        self.fetched.elapsed() >= ttl
+    }
+}
+
+#[derive(Default)]
+struct Usage {
+    requests: i64,
+    throttled: i64,
+}
+
+/// What `/verify` tells the caller about their limits.
+#[derive(Debug, PartialEq)]
+enum Verdict {
+    Allow { limit: u32, remaining: u32, quota_remaining: Option<i64> },
+    Throttle { keyed: bool, limit: u32, retry_after: u64, message: String },
+}
+
+/// Keys, buckets and unflushed usage.
+pub struct Access {
+    pool: Option<Pool>,
+    anonymous_per_minute: u32,
+    /// Take the client address from the last `X-Forwarded-For` hop, which
+    /// the load balancer in front of the gateway appends.
+    trust_proxy: bool,
+    /// By SHA-256 of the key.
+    keys: Mutex<HashMap<Vec<u8>, Cached>>,
+    buckets: Mutex<HashMap<Caller, Bucket>>,
+    /// By key id, since the last flush.
+    usage: Mutex<HashMap<i64, Usage>>,
+}
+
+impl Access {
+    /// Without a pool, requests with a key are refused with 503 and only
+    /// the anonymous limit applies.
+    pub fn new(pool: Option<Pool>, anonymous_per_minute: u32, trust_proxy: bool) -> Self {
+        Self {
+            pool,
+            anonymous_per_minute,
+            trust_proxy,
+            keys: Mutex::new(HashMap::new()),
+            buckets: Mutex::new(HashMap::new()),
+            usage: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn pool(&self) -> Result<&Pool, ApiError> {
+        self.pool
+            .as_ref()
+            .ok_or_else(|| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "API keys are not enabled on this gateway"))
+    }
+
+    fn client_address(&self, headers: &HeaderMap, peer: IpAddr) -> IpAddr {
+        if !self.trust_proxy {
+            return peer;
+        }
+        headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .last()
+            .and_then(|hop| hop.trim().parse().ok())
+            .unwrap_or(peer)
+    }
+
+    async fn admit(&self, headers: &HeaderMap, peer: IpAddr) -> Result<Verdict, ApiError> {
+        let Some(key) = headers.get(HEADER) else {
+            let caller = Caller::Address(self.client_address(headers, peer));
+            return Ok(self.take(caller, self.anonymous_per_minute, None));
+        };
+        let digest = key_digest(key.to_str().unwrap_or_default());
+        if !self.known(&digest) {
+            // A lookup costs a query, so it is paid from the address's
+            // anonymous limit until the key proves valid.
+            let caller = Caller::Address(self.client_address(headers, peer));
+            if let throttled @ Verdict::Throttle { .. } = self.take(caller, self.anonymous_per_minute, None) {
+                return Ok(throttled);
+            }
+        }
+        self.load(&digest).await?;
+
+        let mut keys = self.keys.lock().expect("keys lock poisoned");
+        let key = keys
+            .get_mut(&digest)
+            .and_then(|cached| cached.key.as_mut())
+            .ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, "Unknown or revoked API key"))?;
+        let quota_remaining = key.daily_quota.map(|quota| (quota - key.used_today).max(0));
+        let verdict = if quota_remaining == Some(0) {
+            Verdict::Throttle {
+                keyed: true,
+                limit: key.requests_per_minute,
+                retry_after: SECONDS_PER_DAY - unix_seconds() % SECONDS_PER_DAY,
+                message: "Daily quota used up; it resets at 00:00 UTC".to_string(),
+            }
+        } else {
+            self.take(Caller::Key(key.id), key.requests_per_minute, quota_remaining.map(|left| left - 1))
+        };
+        let mut usage = self.usage.lock().expect("usage lock poisoned");
+        let usage = usage.entry(key.id).or_default();
+        match verdict {
+            Verdict::Allow { .. } => {
+                key.used_today += 1;
+                usage.requests += 1;
+            }
+            Verdict::Throttle { .. } => usage.throttled += 1,
+        }
+        Ok(verdict)
+    }
+
+    fn take(&self, caller: Caller, per_minute: u32, quota_remaining: Option<i64>) -> Verdict {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().expect("buckets lock poisoned");
+        let bucket = buckets.entry(caller).or_insert_with(|| Bucket::full(per_minute, now));
+        match bucket.take(per_minute, now) {
+            Ok(remaining) => Verdict::Allow { limit: per_minute, remaining, quota_remaining },
+            Err(retry_after) => Verdict::Throttle {
+                keyed: matches!(caller, Caller::Key(_)),
+                limit: per_minute,
+                retry_after,
+                message: format!("Rate limit of {} requests a minute exceeded", per_minute),
+            },
+        }
+    }
+
+    /// Whether a fresh entry says the key is valid.
+    fn known(&self, digest: &[u8]) -> bool {
+        let today = unix_seconds() / SECONDS_PER_DAY;
+        let keys = self.keys.lock().expect("keys lock poisoned");
+        keys.get(digest).is_some_and(|cached| cached.key.is_some() && cached.fresh(today))
+    }
+
+    /// Read the key unless a fresh entry is cached. If the database is
+    /// unreachable, a stale entry is kept rather than failing its partner.
+    async fn load(&self, digest: &[u8]) -> Result<(), ApiError> {
+        let today = unix_seconds() / SECONDS_PER_DAY;
+        let stale = match self.keys.lock().expect("keys lock poisoned").get(digest) {
+            Some(cached) if cached.fresh(today) => return Ok(()),
+            Some(_) => true,
+            None => false,
+        };
+        match self.fetch(digest).await {
+            Ok(key) => {
+                self.remember(digest, Cached { key, fetched: Instant::now(), day: today });
+                Ok(())
+            }
+            Err(e) if stale => {
+                eprintln!("API key lookup: {}; using the cached limits", e.message);
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Cache a lookup. When the cache is full, expired entries go first and
+    /// then the least recently read.
+    fn remember(&self, digest: &[u8], cached: Cached) {
+        let mut keys = self.keys.lock().expect("keys lock poisoned");
+        if keys.len() >= MAX_CACHED_KEYS && !keys.contains_key(digest) {
+            keys.retain(|_, entry| !entry.expired());
+            if keys.len() >= MAX_CACHED_KEYS {
+                let oldest = keys.iter().min_by_key(|(_, entry)| entry.fetched).map(|(digest, _)| digest.clone());
+                if let Some(oldest) = oldest {
+                    keys.remove(&oldest);
+                }
+            }
+        }
+        keys.insert(digest.to_vec(), cached);
+    }
+
+    async fn fetch(&self, digest: &[u8]) -> Result<Option<Key>, ApiError> {
+        let client = self
+            .pool()?
+            .get()
+            .await
+            .map_err(|e| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?;
+        let row = client
+            .query_opt(
+                "SELECT k.id, k.requests_per_minute, k.daily_quota, COALESCE(u.requests, 0) AS used_today
+                 FROM api_keys k
+                 LEFT JOIN api_key_usage u ON u.key_id = k.id AND u.day = (now() AT TIME ZONE 'UTC')::date
+                 WHERE k.key_sha256 = $1 AND k.active",
+                &[&digest],
+            )
+            .await
+            .map_err(database_error)?;
+        Ok(row.map(|row| Key {
+            id: row.get("id"),
+            requests_per_minute: row.get::<_, i32>("requests_per_minute") as u32,
+            daily_quota: row.get("daily_quota"),
+            used_today: row.get("used_today"),
+        }))
+    }
+
+    /// Add the usage counted since the last flush to `api_key_usage`. Counts
+    /// that fail to write are kept for the next flush.
+    async fn flush(&self) -> Result<(), ApiError> {
+        let pending = std::mem::take(&mut *self.usage.lock().expect("usage lock poisoned"));
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let written = self.write(&pending).await;
+        if written.is_err() {
+            let mut usage = self.usage.lock().expect("usage lock poisoned");
+            for (id, counts) in pending {
+                let entry = usage.entry(id).or_default();
+                entry.requests += counts.requests;
+                entry.throttled += counts.throttled;
+            }
+        }
+        written
+    }
+
+    async fn write(&self, pending: &HashMap<i64, Usage>) -> Result<(), ApiError> {
+        let mut client = self
+            .pool()?
+            .get()
+            .await
+            .map_err(|e| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?;
+        let tx = client.transaction().await.map_err(database_error)?;
+        for (id, counts) in pending {
+            tx.execute(
+                "INSERT INTO api_key_usage (key_id, day, requests, throttled)
+                 VALUES ($1, (now() AT TIME ZONE 'UTC')::date, $2, $3)
+                 ON CONFLICT (key_id, day) DO UPDATE
+                 SET requests = api_key_usage.requests + excluded.requests,
+                     throttled = api_key_usage.throttled + excluded.throttled",
+                &[id, &counts.requests, &counts.throttled],
+            )
+            .await
+            .map_err(database_error)?;
+        }
+        tx.commit().await.map_err(database_error)
+    }
+
+    fn prune(&self) {
+        self.buckets
+            .lock()
+            .expect("buckets lock poisoned")
+            .retain(|_, bucket| bucket.updated.elapsed() < IDLE);
+        self.keys.lock().expect("keys lock poisoned").retain(|_, cached| !cached.expired());
+    }
+}
+
+fn key_digest(key: &str) -> Vec<u8> {
+    Sha256::digest(key.as_bytes()).to_vec()
+}
+
+fn unix_seconds() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn database_error(e: tokio_postgres::Error) -> ApiError {
+    ApiError::new(StatusCode::BAD_GATEWAY, format!("Database error: {}", e))
+}
+
+/// Write usage and drop idle buckets and expired keys until the process exits.
+pub async fn maintain(state: Arc<AppState>) {
+    loop {
+        tokio::time::sleep(FLUSH_INTERVAL).await;
+        if let Err(e) = state.access.flush().await {
+            eprintln!("API key usage: {}", e.message);
+        }
+        state.access.prune();
+    }
+}
+
+/// Middleware on `/verify`: admit the request or answer 429, with the
+/// caller's limits in `X-RateLimit-*` headers either way.
+pub async fn meter(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match state.access.admit(request.headers(), peer.ip()).await {
+        Err(e) => e.into_response(),
+        Ok(Verdict::Allow { limit, remaining, quota_remaining }) => {
+            let mut response = next.run(request).await;
+            let headers = response.headers_mut();
+            set(headers, "x-ratelimit-limit", limit);
+            set(headers, "x-ratelimit-remaining", remaining);
+            if let Some(left) = quota_remaining {
+                set(headers, "x-quota-remaining", left);
+            }
+            response
+        }
+        Ok(Verdict::Throttle { keyed, limit, retry_after, message }) => {
+            state.metrics.throttled.inc(&[if keyed { "key" } else { "anonymous" }]);
+            let mut response = ApiError::new(StatusCode::TOO_MANY_REQUESTS, message).into_response();
+            let headers = response.headers_mut();
+            set(headers, "retry-after", retry_after);
+            set(headers, "x-ratelimit-limit", limit);
+            set(headers, "x-ratelimit-remaining", 0);
+            response
+        }
+    }
+}
+
+fn set(headers: &mut HeaderMap, name: &'static str, value: impl ToString) {
+    let value = HeaderValue::from_str(&value.to_string()).expect("Numbers are valid header values");
+    headers.insert(HeaderName::from_static(name), value);
+}
+
+/// A key's limits and its last 30 days of usage.
+#[derive(Serialize, ToSchema)]
+pub struct KeyUsage {
+    pub name: String,
+    pub requests_per_minute: i32,
+    /// Requests allowed per UTC day; `null` when uncapped.
+    pub daily_quota: Option<i64>,
+    /// Newest first. Lags the gateway by up to 10 seconds.
+    pub days: Vec<DayUsage>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DayUsage {
+    /// `YYYY-MM-DD`, UTC.
+    pub day: String,
+    pub requests: i64,
+    /// Requests answered 429.
+    pub throttled: i64,
+}
+
+/// Usage and limits of the caller's API key.
+#[utoipa::path(
+    get,
+    path = "/usage",
+    tag = "verification",
+    params(("X-API-Key" = String, Header, description = "Partner API key")),
+    responses(
+        (status = 200, description = "The key's limits and usage", body = KeyUsage),
+        (status = 401, description = "Missing, unknown or revoked key", body = ErrorBody),
+        (status = 503, description = "API keys are not enabled on this gateway", body = ErrorBody),
+    )
+)]
+pub async fn usage(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Result<Json<KeyUsage>, ApiError> {
+    let key = headers
+        .get(HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, "Missing X-API-Key"))?;
+    let client = state
+        .access
+        .pool()?
+        .get()
+        .await
+        .map_err(|e| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?;
+    let row = client
+        .query_opt(
+            "SELECT id, name, requests_per_minute, daily_quota FROM api_keys WHERE key_sha256 = $1 AND active",
+            &[&key_digest(key)],
+        )
+        .await
+        .map_err(database_error)?
+        .ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, "Unknown or revoked API key"))?;
+    let days = client
+        .query(
+            "SELECT day::text AS day, requests, throttled FROM api_key_usage
+             WHERE key_id = $1 ORDER BY day DESC LIMIT 30",
+            &[&row.get::<_, i64>("id")],
+        )
+        .await
+        .map_err(database_error)?;
+    Ok(Json(KeyUsage {
+        name: row.get("name"),
+        requests_per_minute: row.get("requests_per_minute"),
+        daily_quota: row.get("daily_quota"),
+        days: days
+            .iter()
+            .map(|day| DayUsage { day: day.get("day"), requests: day.get("requests"), throttled: day.get("throttled") })
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_refills_at_its_rate() {
+        let start = Instant::now();
+        let mut bucket = Bucket::full(60, start);
+        for _ in 0..60 {
+            assert!(bucket.take(60, start).is_ok());
+        }
+        assert_eq!(bucket.take(60, start), Err(1));
+        assert_eq!(bucket.take(60, start + Duration::from_secs(1)), Ok(0));
+        assert_eq!(bucket.take(60, start + Duration::from_secs(600)), Ok(59));
+    }
+
+    #[tokio::test]
+    async fn anonymous_callers_are_limited_per_address() {
+        let access = Access::new(None, 2, false);
+        let headers = HeaderMap::new();
+        let one: IpAddr = "203.0.113.1".parse().unwrap();
+        let two: IpAddr = "203.0.113.2".parse().unwrap();
+        assert!(matches!(access.admit(&headers, one).await, Ok(Verdict::Allow { remaining: 1, .. })));
+        assert!(matches!(access.admit(&headers, one).await, Ok(Verdict::Allow { remaining: 0, .. })));
+        assert!(matches!(
+            access.admit(&headers, one).await,
+            Ok(Verdict::Throttle { keyed: false, limit: 2, .. })
+        ));
+        assert!(matches!(access.admit(&headers, two).await, Ok(Verdict::Allow { .. })));
+    }
+
+    #[tokio::test]
+    async fn keys_need_a_database() {
+        let access = Access::new(None, 2, false);
+        let mut headers = HeaderMap::new();
+        headers.insert(HEADER, HeaderValue::from_static("pk_test"));
+        let error = access.admit(&headers, IpAddr::from([127, 0, 0, 1])).await.unwrap_err();
+        assert_eq!(error.status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn unproven_keys_are_charged_to_their_address() {
+        let access = Access::new(None, 1, false);
+        let mut headers = HeaderMap::new();
+        headers.insert(HEADER, HeaderValue::from_static("pk_made_up"));
+        let peer = IpAddr::from([203, 0, 113, 1]);
+        assert!(access.admit(&headers, peer).await.is_err());
+        assert!(matches!(
+            access.admit(&headers, peer).await,
+            Ok(Verdict::Throttle { keyed: false, limit: 1, .. })
+        ));
+        assert!(matches!(access.admit(&HeaderMap::new(), peer).await, Ok(Verdict::Throttle { .. })));
+    }
+
+    #[test]
+    fn key_cache_is_bounded() {
+        let access = Access::new(None, 60, false);
+        let today = unix_seconds() / SECONDS_PER_DAY;
+        for n in 0..MAX_CACHED_KEYS + 10 {
+            let cached = Cached { key: None, fetched: Instant::now(), day: today };
+            access.remember(&key_digest(&format!("pk_{}", n)), cached);
+        }
+        let keys = access.keys.lock().unwrap();
+        assert_eq!(keys.len(), MAX_CACHED_KEYS);
+        assert!(keys.contains_key(&key_digest(&format!("pk_{}", MAX_CACHED_KEYS + 9))));
+    }
+
+    #[test]
+    fn forwarded_address_is_used_only_behind_a_trusted_proxy() {
+        let peer = IpAddr::from([10, 0, 0, 5]);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static("198.51.100.7, 203.0.113.9"));
+        assert_eq!(Access::new(None, 60, false).client_address(&headers, peer), peer);
+        assert_eq!(
+            Access::new(None, 60, true).client_address(&headers, peer),
+            "203.0.113.9".parse::<IpAddr>().unwrap()
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Live order status over WebSocket: `GET /ws/orders/{order_id}`.
+//!
+//! The indexer notifies the `proofcart_orders` channel with an order id in
+//! each commit that changes the order. One listener connection fans those
+//! out to the open sockets; each socket re-reads its order from the indexer
+//! database and pushes the snapshot if it changed. A snapshot is also sent on
+//! connect, so clients never need to poll.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use deadpool_postgres::Pool;
+use futures_util::{stream, StreamExt};
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio_postgres::{AsyncMessage, NoTls};
+
+use crate::error::ApiError;
+use crate::orders::EscrowView;
+use crate::AppState;
+
+/// Must match `ORDER_CHANNEL` in the indexer.
+const ORDER_CHANNEL: &str = "proofcart_orders";
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Order ids changed by the indexer, and the database to read them from.
+pub struct Live {
+    pub pool: Pool,
+    updates: broadcast::Sender<String>,
+}
+
+impl Live {
+    /// Start listening on `database_url`; the listener reconnects on its own.
+    pub fn start(database_url: String, pool: Pool) -> Self {
+        let (updates, _) = broadcast::channel(1024);
+        let sender = updates.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = listen(&database_url, &sender).await {
+                    eprintln!("Order listener: {}; reconnecting in {}s", e, RECONNECT_DELAY.as_secs());
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        });
+        Self { pool, updates }
+    }
+}
+
+async fn listen(database_url: &str, updates: &broadcast::Sender<String>) -> Result<(), tokio_postgres::Error> {
+    let (client, mut connection) = tokio_postgres::connect(database_url, NoTls).await?;
+    let messages = stream::poll_fn(move |cx| connection.poll_message(cx));
+    tokio::pin!(messages);
+    let listen = client.batch_execute(&format!("LISTEN {}", ORDER_CHANNEL));
+    // The connection must be polled for LISTEN to complete.
+    tokio::pin!(listen);
+    loop {
+        tokio::select! {
+            result = &mut listen => {
+                result?;
+                break;
+            }
+            message = messages.next() => match message {
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e),
+                None => return Ok(()),
+            },
+        }
+    }
+    while let Some(message) = messages.next().await {
+        if let AsyncMessage::Notification(notification) = message? {
+            // No receivers is fine: nobody is watching that order.
+            let _ = updates.send(notification.payload().to_string());
+        }
+    }
+    Ok(())
+}
+
+/// What a tracking page shows: the escrow, and the NFT once delivered.
+#[derive(Serialize, PartialEq)]
+pub struct OrderStatus {
+    pub order_id: String,
+    pub escrow: Option<EscrowView>,
+    pub nft: Option<NftDelivery>,
+}
+
+/// The sale transfer that settled the order on the NFT canister.
+#[derive(Serialize, PartialEq)]
+pub struct NftDelivery {
+    pub nft_id: i64,
+    pub serial_number: String,
+    pub owner: String,
+    /// Nanoseconds since the Unix epoch.
+    pub transferred_at: i64,
+}
+
+async fn snapshot(pool: &Pool, order_id: &str) -> Result<OrderStatus, ApiError> {
+    let db = pool
+        .get()
+        .await
+        .map_err(|e| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?;
+    let escrow = db
+        .query_opt(
+            "SELECT order_id, escrow_address, buyer, seller, amount_lamports, status,
+                    created_at, locked_at, released_at, resolved_at
+             FROM escrow_orders WHERE marketplace = '' AND order_id = $1",
+            &[&order_id],
+        )
+        .await
+        .map_err(database_error)?
+        .map(|row| EscrowView {
+            order_id: row.get("order_id"),
+            address: row.get("escrow_address"),
+            buyer: row.get("buyer"),
+            seller: row.get("seller"),
+            amount_lamports: row.get::<_, i64>("amount_lamports") as u64,
+            status: row.get("status"),
+            created_at: row.get::<_, Option<i64>>("created_at").unwrap_or_default(),
+            locked_at: row.get("locked_at"),
+            released_at: row.get("released_at"),
+            resolved_at: row.get("resolved_at"),
+        });
+    let nft = db
+        .query_opt(
+            "SELECT t.nft_id, n.serial_number, t.to_owner, t.timestamp_ns
+             FROM nft_transfers t JOIN nft_tokens n ON n.nft_id = t.nft_id
+             WHERE t.order_id = $1 ORDER BY t.block_index DESC LIMIT 1",
+            &[&order_id],
+        )
+        .await
+        .map_err(database_error)?
+        .map(|row| NftDelivery {
+            nft_id: row.get("nft_id"),
+            serial_number: row.get("serial_number"),
+            owner: row.get("to_owner"),
+            transferred_at: row.get("timestamp_ns"),
+        });
+    Ok(OrderStatus {
+        order_id: order_id.to_string(),
+        escrow,
+        nft,
+    })
+}
+
+fn database_error(e: tokio_postgres::Error) -> ApiError {
+    ApiError::new(StatusCode::BAD_GATEWAY, format!("Database error: {}", e))
+}
+
+/// Upgrade to a WebSocket streaming the order's status as JSON text frames.
+pub async fn order_socket(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+    Path(order_id): Path<String>,
+) -> Response {
+    if state.live.is_none() {
+        return ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "Live updates are not enabled on this gateway")
+            .into_response();
+    }
+    ws.on_upgrade(move |socket| stream_order(socket, state, order_id))
+}
+
+async fn stream_order(mut socket: WebSocket, state: Arc<AppState>, order_id: String) {
+    let live = state.live.as_ref().expect("Checked before upgrading");
+    // Subscribe before the first read, so no change slips in between.
+    let mut updates = live.updates.subscribe();
+    let mut last = None;
+    let mut refresh = true;
+    loop {
+        if refresh {
+            let status = match snapshot(&live.pool, &order_id).await {
+                Ok(status) => status,
+                Err(e) => {
+                    let error = serde_json::json!({ "error": e.message }).to_string();
+                    let _ = socket.send(Message::Text(error)).await;
+                    return;
+                }
+            };
+            if last.as_ref() != Some(&status) {
+                let text = serde_json::to_string(&status).expect("Serializing a status cannot fail");
+                if socket.send(Message::Text(text)).await.is_err() {
+                    return;
+                }
+                last = Some(status);
+            }
+        }
+        tokio::select! {
+            update = updates.recv() => {
+                refresh = match update {
+                    Ok(changed) => changed == order_id,
+                    // Missed some notifications; one of them may be ours.
+                    Err(broadcast::error::RecvError::Lagged(_)) => true,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => refresh = false,
+            },
+        }
+    }
+}
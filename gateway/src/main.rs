@@ -0,0 +1,196 @@
+//! `proofcart-gateway`: HTTP API over ProofCart verification and escrows,
+//! so web and mobile clients integrate without embedding chain SDKs.
+//!
+//! ```text
+//! GET  /verify/{serial}
+//! GET  /usage
+//! POST /metadata/validate
+//! GET  /orders/{order_id}/escrow
+//! POST /orders/{order_id}/dispute/prepare
+//! POST /orders/{order_id}/dispute
+//! POST /orders/{order_id}/quote
+//! GET  /orders/{order_id}/quote
+//! GET  /ws/orders/{order_id}              (WebSocket)
+//! GET  /openapi.json
+//! GET  /metrics
+//! GET  /healthz
+//! ```
+
+mod config;
+mod db;
+mod error;
+mod keys;
+mod live;
+mod metadata;
+mod metrics;
+mod oracle;
+mod orders;
+mod quotes;
+mod verify;
+
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::middleware;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use deadpool_postgres::{Manager, Pool};
+use proofcart_icp_client::{identity, NftClient};
+use proofcart_solana_client::EscrowClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Keypair;
+use utoipa::OpenApi;
+
+use config::Config;
+use live::Live;
+use oracle::Oracle;
+use quotes::Pricer;
+
+/// Shared by every handler.
+pub struct AppState {
+    pub nft: NftClient,
+    pub escrow: EscrowClient,
+    pub relayer: Option<Keypair>,
+    pub live: Option<Live>,
+    pub pricer: Pricer,
+    pub metrics: metrics::Metrics,
+    pub access: keys::Access,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "ProofCart gateway"),
+    paths(
+        verify::verify,
+        keys::usage,
+        metadata::validate,
+        orders::escrow,
+        orders::prepare_dispute,
+        orders::dispute,
+        quotes::quote,
+        quotes::latest_quote,
+    ),
+    components(schemas(
+        error::ErrorBody,
+        verify::Verification,
+        keys::KeyUsage,
+        keys::DayUsage,
+        metadata::MetadataDraft,
+        metadata::MetadataCheck,
+        orders::EscrowView,
+        orders::PrepareDispute,
+        orders::RelayTransaction,
+        orders::DisputeOpened,
+        quotes::Currency,
+        quotes::QuoteRequest,
+        quotes::Quote,
+    ))
+)]
+struct ApiDoc;
+
+fn router(state: Arc<AppState>) -> Router {
+    let verify = Router::new()
+        .route("/verify/:serial", get(verify::verify))
+        .route_layer(middleware::from_fn_with_state(state.clone(), keys::meter));
+    Router::new()
+        .merge(verify)
+        .route("/usage", get(keys::usage))
+        .route("/metadata/validate", post(metadata::validate))
+        .route("/orders/:order_id/escrow", get(orders::escrow))
+        .route("/orders/:order_id/dispute/prepare", post(orders::prepare_dispute))
+        .route("/orders/:order_id/dispute", post(orders::dispute))
+        .route("/orders/:order_id/quote", post(quotes::quote).get(quotes::latest_quote))
+        .route("/ws/orders/:order_id", get(live::order_socket))
+        .route("/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+        .route("/metrics", get(metrics::metrics))
+        .route("/healthz", get(metrics::healthz))
+        .route_layer(middleware::from_fn_with_state(state.clone(), metrics::track))
+        .with_state(state)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run() -> Result<(), String> {
+    let config = Config::from_env()?;
+    let nft = NftClient::connect(&config.ic_url, identity::anonymous(), config.nft_canister_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let rpc = RpcClient::new_with_commitment(config.solana_rpc_url.clone(), CommitmentConfig::confirmed());
+    let live = match config.database_url {
+        Some(url) => {
+            let pg_config = tokio_postgres::Config::from_str(&url).map_err(|e| format!("DATABASE_URL: {}", e))?;
+            let pool = Pool::builder(Manager::new(pg_config, tokio_postgres::NoTls))
+                .max_size(16)
+                .build()
+                .map_err(|e| e.to_string())?;
+            let mut client = pool.get().await.map_err(|e| format!("Database: {}", e))?;
+            db::migrate(&mut client).await.map_err(|e| e.to_string())?;
+            Some(Live::start(url, pool))
+        }
+        None => None,
+    };
+    let access = keys::Access::new(
+        live.as_ref().map(|live| live.pool.clone()),
+        config.anonymous_verify_per_minute,
+        config.trust_proxy,
+    );
+    let oracle = Oracle::new(
+        &config.pyth_hermes_url,
+        config.pyth_sol_usd_feed,
+        config.pyth_usdc_usd_feed,
+        config.switchboard,
+        config.max_price_age_secs,
+        config.max_confidence_bps,
+    );
+    let state = Arc::new(AppState {
+        nft,
+        escrow: EscrowClient::new(rpc, config.program_id),
+        relayer: config.relayer,
+        live,
+        pricer: Pricer::new(oracle, config.quote_spread_bps, config.quote_ttl_secs),
+        metrics: metrics::Metrics::default(),
+        access,
+    });
+    tokio::spawn(keys::maintain(state.clone()));
+
+    let listener = tokio::net::TcpListener::bind(config.listen_addr)
+        .await
+        .map_err(|e| format!("{}: {}", config.listen_addr, e))?;
+    println!("Listening on {}", config.listen_addr);
+    axum::serve(listener, router(state).into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openapi_documents_every_route() {
+        let doc = ApiDoc::openapi();
+        for path in [
+            "/verify/{serial}",
+            "/usage",
+            "/metadata/validate",
+            "/orders/{order_id}/escrow",
+            "/orders/{order_id}/dispute/prepare",
+            "/orders/{order_id}/dispute",
+            "/orders/{order_id}/quote",
+        ] {
+            assert!(doc.paths.paths.contains_key(path), "{} is not documented", path);
+        }
+    }
+}
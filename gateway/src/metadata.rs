@@ -0,0 +1,77 @@
+//! Metadata validation, so a minting form can check its input against the
+//! canister's rules before anything is uploaded or signed.
+
+use axum::Json;
+use proofcart_icp_client::types::{LocalizedText, NFTMetadata};
+use proofcart_metadata_schema::Caps;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::ErrorBody;
+
+/// Product metadata as it would be minted. Omitted fields are empty.
+#[derive(Deserialize, Default, ToSchema)]
+#[serde(default)]
+pub struct MetadataDraft {
+    pub serial_number: String,
+    pub product_name: String,
+    pub manufacturer: String,
+    /// `YYYY-MM-DD`.
+    pub manufacture_date: String,
+    pub category: String,
+    pub description: String,
+    /// A JSON object, as text.
+    pub specifications: String,
+    pub warranty_info: String,
+    pub certifications: Vec<String>,
+    /// `ipfs://`, `ar://` or `https://`.
+    pub ipfs_metadata_uri: String,
+    pub arweave_tx_id: Option<String>,
+    /// Translations: language tag, then `product_name`, `description` and
+    /// `warranty_info`, each optional.
+    #[schema(value_type = Option<Vec<Object>>)]
+    pub localized: Option<Vec<(String, LocalizedText)>>,
+}
+
+impl From<MetadataDraft> for NFTMetadata {
+    fn from(draft: MetadataDraft) -> Self {
+        NFTMetadata {
+            serial_number: draft.serial_number,
+            product_name: draft.product_name,
+            manufacturer: draft.manufacturer,
+            manufacture_date: draft.manufacture_date,
+            category: draft.category,
+            description: draft.description,
+            specifications: draft.specifications,
+            warranty_info: draft.warranty_info,
+            certifications: draft.certifications,
+            ipfs_metadata_uri: draft.ipfs_metadata_uri,
+            arweave_tx_id: draft.arweave_tx_id,
+            localized: draft.localized,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MetadataCheck {
+    pub valid: bool,
+    /// The first rule the metadata breaks.
+    pub error: Option<String>,
+}
+
+/// Check product metadata against the rules a mint applies.
+#[utoipa::path(
+    post,
+    path = "/metadata/validate",
+    tag = "metadata",
+    request_body = MetadataDraft,
+    responses(
+        (status = 200, description = "Whether the metadata would be accepted", body = MetadataCheck),
+        (status = 400, description = "Not a JSON object of metadata fields", body = ErrorBody),
+    )
+)]
+pub async fn validate(Json(draft): Json<MetadataDraft>) -> Json<MetadataCheck> {
+    // The canister may run with lower size limits than the defaults.
+    let error = proofcart_metadata_schema::validate(&draft.into(), &Caps::default()).err();
+    Json(MetadataCheck { valid: error.is_none(), error: error.map(|e| e.to_string()) })
+}
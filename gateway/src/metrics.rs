@@ -0,0 +1,110 @@
+//! `GET /metrics` and `GET /healthz`.
+
+use std::sync::Arc;
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use proofcart_telemetry::health::{self, Check, Report};
+use proofcart_telemetry::{Exposition, Family};
+
+use crate::AppState;
+
+pub struct Metrics {
+    /// Responses by route template and status code.
+    requests: Family,
+    /// 502s by route: a Solana RPC, canister or oracle call failed.
+    upstream_errors: Family,
+    /// 429s from `/verify`, by caller: `key` or `anonymous`.
+    pub throttled: Family,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            requests: Family::new(&["route", "status"]),
+            upstream_errors: Family::new(&["route"]),
+            throttled: Family::new(&["caller"]),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn render(&self) -> String {
+        let mut out = Exposition::new();
+        out.family("proofcart_gateway_requests_total", "Responses by route and status.", &self.requests)
+            .family(
+                "proofcart_gateway_upstream_errors_total",
+                "Requests failed by a Solana RPC, canister or oracle error.",
+                &self.upstream_errors,
+            )
+            .family(
+                "proofcart_gateway_throttled_total",
+                "Verification requests refused by a rate limit or quota, by caller.",
+                &self.throttled,
+            );
+        out.finish()
+    }
+
+    fn record(&self, route: &str, status: StatusCode) {
+        self.requests.inc(&[route, status.as_str()]);
+        if status == StatusCode::BAD_GATEWAY {
+            self.upstream_errors.inc(&[route]);
+        }
+    }
+}
+
+/// Middleware counting every routed response. Routes are labelled by
+/// template, e.g. `/verify/:serial`, so serials never become labels.
+pub async fn track(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let response = next.run(request).await;
+    state.metrics.record(&route, response.status());
+    response
+}
+
+pub async fn metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    ([("content-type", "text/plain; version=0.0.4")], state.metrics.render())
+}
+
+/// The Solana RPC node, the ICP replica and, when live updates are on, the
+/// indexer's database.
+pub async fn healthz(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Report>) {
+    let mut checks = vec![
+        health::solana(state.escrow.rpc()).await,
+        health::icp(state.nft.agent()).await,
+    ];
+    if let Some(live) = &state.live {
+        checks.push(match live.pool.get().await {
+            Ok(client) => health::postgres(&client).await,
+            Err(e) => Check::run("postgres", async move { Err(e.to_string()) }).await,
+        });
+    }
+    let report = Report::new(checks);
+    let status = StatusCode::from_u16(report.status()).expect("Health statuses are valid");
+    (status, Json(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_upstream_failures_per_route() {
+        let metrics = Metrics::default();
+        metrics.record("/verify/:serial", StatusCode::OK);
+        metrics.record("/verify/:serial", StatusCode::BAD_GATEWAY);
+        metrics.record("/orders/:order_id/escrow", StatusCode::NOT_FOUND);
+
+        let text = metrics.render();
+        assert!(text.contains("proofcart_gateway_requests_total{route=\"/verify/:serial\",status=\"502\"} 1\n"));
+        assert!(text.contains("proofcart_gateway_upstream_errors_total{route=\"/verify/:serial\"} 1\n"));
+        assert!(!text.contains("proofcart_gateway_upstream_errors_total{route=\"/orders/:order_id/escrow\"}"));
+    }
+}
@@ -0,0 +1,256 @@
+//! USD prices of SOL and USDC from Pyth, with Switchboard as a fallback.
+//!
+//! Pyth prices come from Hermes, its HTTP price service, with the publish
+//! time and confidence interval of the on-chain update. Switchboard prices
+//! come from a Crossbar server, which runs the feed's jobs on request, so
+//! they are always fresh; the spread of the oracle results stands in for a
+//! confidence interval. A price older than the staleness limit, or less
+//! certain than the confidence limit, is refused rather than quoted.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+
+use crate::quotes::Currency;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pyth's SOL/USD and USDC/USD price feeds.
+pub const PYTH_SOL_USD: &str = "ef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d";
+pub const PYTH_USDC_USD: &str = "eaa020c61cc479712813461ce153894a96a6c00b21ed0cfc2798d1f9a9e9c94a";
+
+/// Switchboard results are floats; they are scaled to this exponent.
+const SWITCHBOARD_EXPO: i32 = -8;
+
+/// A USD price of one whole token: `value * 10^expo`, give or take
+/// `conf * 10^expo`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Price {
+    pub value: i64,
+    pub conf: u64,
+    pub expo: i32,
+    /// Unix seconds.
+    pub published_at: i64,
+    pub source: &'static str,
+}
+
+impl Price {
+    /// Refuse a price that is stale, non-positive or too uncertain to quote.
+    pub fn check(&self, now: i64, max_age_secs: u64, max_conf_bps: u64) -> Result<(), String> {
+        if self.value <= 0 {
+            return Err(format!("{} returned a non-positive price", self.source));
+        }
+        let age = now.saturating_sub(self.published_at);
+        if age > max_age_secs as i64 {
+            return Err(format!("{} price is {}s old (limit {}s)", self.source, age, max_age_secs));
+        }
+        if u128::from(self.conf) * 10_000 > u128::from(max_conf_bps) * self.value as u128 {
+            return Err(format!("{} price is too uncertain to quote", self.source));
+        }
+        Ok(())
+    }
+}
+
+/// Switchboard feeds, by their Crossbar feed hash.
+pub struct Switchboard {
+    pub crossbar_url: String,
+    pub sol_usd_feed: String,
+    pub usdc_usd_feed: String,
+}
+
+pub struct Oracle {
+    http: reqwest::Client,
+    hermes_url: String,
+    sol_usd_feed: String,
+    usdc_usd_feed: String,
+    switchboard: Option<Switchboard>,
+    max_age_secs: u64,
+    max_conf_bps: u64,
+}
+
+#[derive(Deserialize)]
+struct HermesUpdate {
+    parsed: Vec<HermesFeed>,
+}
+
+#[derive(Deserialize)]
+struct HermesFeed {
+    price: HermesPrice,
+}
+
+/// Hermes sends 64-bit integers as strings.
+#[derive(Deserialize)]
+struct HermesPrice {
+    price: String,
+    conf: String,
+    expo: i32,
+    publish_time: i64,
+}
+
+#[derive(Deserialize)]
+struct CrossbarFeed {
+    results: Vec<f64>,
+}
+
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+impl Oracle {
+    pub fn new(
+        hermes_url: &str,
+        sol_usd_feed: String,
+        usdc_usd_feed: String,
+        switchboard: Option<Switchboard>,
+        max_age_secs: u64,
+        max_conf_bps: u64,
+    ) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent("proofcart-gateway")
+            .build()
+            .expect("Failed to build the HTTP client");
+        Self {
+            http,
+            hermes_url: hermes_url.trim_end_matches('/').to_string(),
+            sol_usd_feed,
+            usdc_usd_feed,
+            switchboard,
+            max_age_secs,
+            max_conf_bps,
+        }
+    }
+
+    /// A usable USD price of `currency`: Pyth's, or Switchboard's when
+    /// Pyth's is unavailable or fails the checks.
+    pub async fn price(&self, currency: Currency) -> Result<Price, String> {
+        let pyth = self.pyth(currency).await.and_then(|price| self.checked(price));
+        let Some(switchboard) = &self.switchboard else {
+            return pyth;
+        };
+        match pyth {
+            Ok(price) => Ok(price),
+            Err(pyth) => self
+                .switchboard(switchboard, currency)
+                .await
+                .and_then(|price| self.checked(price))
+                .map_err(|switchboard| format!("{}; {}", pyth, switchboard)),
+        }
+    }
+
+    fn checked(&self, price: Price) -> Result<Price, String> {
+        price.check(now(), self.max_age_secs, self.max_conf_bps).map(|()| price)
+    }
+
+    async fn pyth(&self, currency: Currency) -> Result<Price, String> {
+        let feed = match currency {
+            Currency::Sol => &self.sol_usd_feed,
+            Currency::Usdc => &self.usdc_usd_feed,
+        };
+        let url = format!("{}/v2/updates/price/latest", self.hermes_url);
+        let update: HermesUpdate = self
+            .http
+            .get(&url)
+            .query(&[("ids[]", feed.as_str()), ("parsed", "true")])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Pyth: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Pyth: {}", e))?;
+        let price = update
+            .parsed
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("Pyth has no feed {}", feed))?
+            .price;
+        Ok(Price {
+            value: price.price.parse().map_err(|_| "Pyth: invalid price".to_string())?,
+            conf: price.conf.parse().map_err(|_| "Pyth: invalid confidence".to_string())?,
+            expo: price.expo,
+            published_at: price.publish_time,
+            source: "pyth",
+        })
+    }
+
+    async fn switchboard(&self, switchboard: &Switchboard, currency: Currency) -> Result<Price, String> {
+        let feed = match currency {
+            Currency::Sol => &switchboard.sol_usd_feed,
+            Currency::Usdc => &switchboard.usdc_usd_feed,
+        };
+        let url = format!("{}/simulate/{}", switchboard.crossbar_url.trim_end_matches('/'), feed);
+        let feeds: Vec<CrossbarFeed> = self
+            .http
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Switchboard: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Switchboard: {}", e))?;
+        let results = feeds.into_iter().next().map(|feed| feed.results).unwrap_or_default();
+        switchboard_price(&results, now()).ok_or_else(|| format!("Switchboard has no results for feed {}", feed))
+    }
+}
+
+/// The median of the oracle results, with half their range as confidence.
+fn switchboard_price(results: &[f64], now: i64) -> Option<Price> {
+    let mut results: Vec<f64> = results.iter().copied().filter(|r| r.is_finite()).collect();
+    if results.is_empty() {
+        return None;
+    }
+    results.sort_by(f64::total_cmp);
+    let mid = results.len() / 2;
+    let median = if results.len() % 2 == 0 {
+        (results[mid - 1] + results[mid]) / 2.0
+    } else {
+        results[mid]
+    };
+    let scale = 10f64.powi(-SWITCHBOARD_EXPO);
+    Some(Price {
+        value: (median * scale).round() as i64,
+        conf: ((results[results.len() - 1] - results[0]) / 2.0 * scale).round() as u64,
+        expo: SWITCHBOARD_EXPO,
+        published_at: now,
+        source: "switchboard",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(value: i64, conf: u64, published_at: i64) -> Price {
+        Price {
+            value,
+            conf,
+            expo: -8,
+            published_at,
+            source: "pyth",
+        }
+    }
+
+    #[test]
+    fn refuses_stale_and_uncertain_prices() {
+        // $150.00 +/- $0.15, 10s old
+        assert!(price(150_0000_0000, 1500_0000, 990).check(1000, 60, 100).is_ok());
+        assert!(price(150_0000_0000, 1500_0000, 900).check(1000, 60, 100).is_err());
+        // +/- $3.00 is 2%
+        assert!(price(150_0000_0000, 3_0000_0000, 990).check(1000, 60, 100).is_err());
+        assert!(price(0, 0, 1000).check(1000, 60, 100).is_err());
+    }
+
+    #[test]
+    fn takes_the_median_switchboard_result() {
+        let price = switchboard_price(&[151.0, 149.0, f64::NAN, 150.0], 1000).unwrap();
+        assert_eq!(price.value, 150_0000_0000);
+        assert_eq!(price.conf, 1_0000_0000);
+        assert_eq!(price.published_at, 1000);
+        assert_eq!(switchboard_price(&[], 1000), None);
+    }
+}
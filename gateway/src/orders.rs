@@ -0,0 +1,233 @@
+//! Escrow status and relayed disputes.
+//!
+//! `lock_dispute` must be signed by the buyer, so the gateway cannot open a
+//! dispute on its own. It relays instead: `prepare` returns the transaction
+//! with the relayer as fee payer, the buyer's wallet signs it, and `dispute`
+//! checks it is exactly that transaction, adds the relayer's signature and
+//! submits it. Buyers need no SOL for fees.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use proofcart_solana_client::{instructions, pda, Escrow, EscrowStatus};
+use serde::{Deserialize, Serialize};
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+use utoipa::ToSchema;
+
+use crate::error::{ApiError, ErrorBody};
+use crate::AppState;
+
+/// The escrow account of an order. Amounts are lamports, times Unix seconds.
+#[derive(Serialize, ToSchema, PartialEq)]
+pub struct EscrowView {
+    pub order_id: String,
+    pub address: String,
+    pub buyer: String,
+    pub seller: String,
+    pub amount_lamports: u64,
+    /// `created`, `locked`, `released` or `refunded`.
+    pub status: String,
+    pub created_at: i64,
+    pub locked_at: Option<i64>,
+    pub released_at: Option<i64>,
+    pub resolved_at: Option<i64>,
+}
+
+impl EscrowView {
+    fn new(address: Pubkey, escrow: Escrow) -> Self {
+        Self {
+            order_id: escrow.order_id,
+            address: address.to_string(),
+            buyer: escrow.buyer.to_string(),
+            seller: escrow.seller.to_string(),
+            amount_lamports: escrow.amount,
+            status: match escrow.status {
+                EscrowStatus::Created => "created",
+                EscrowStatus::Locked => "locked",
+                EscrowStatus::Released => "released",
+                EscrowStatus::Refunded => "refunded",
+            }
+            .to_string(),
+            created_at: escrow.created_at,
+            locked_at: escrow.locked_at,
+            released_at: escrow.released_at,
+            resolved_at: escrow.resolved_at,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PrepareDispute {
+    /// Base58 address of the buyer's wallet.
+    pub buyer: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct RelayTransaction {
+    /// Base64 of the bincode-serialized transaction.
+    pub transaction: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DisputeOpened {
+    pub signature: String,
+    pub escrow: EscrowView,
+}
+
+/// Escrow status of an order.
+#[utoipa::path(
+    get,
+    path = "/orders/{order_id}/escrow",
+    tag = "escrow",
+    params(("order_id" = String, Path, description = "Marketplace order id, 1-32 bytes")),
+    responses(
+        (status = 200, description = "The order's escrow", body = EscrowView),
+        (status = 400, description = "Malformed order id", body = ErrorBody),
+        (status = 404, description = "No escrow for this order", body = ErrorBody),
+    )
+)]
+pub async fn escrow(State(state): State<Arc<AppState>>, Path(order_id): Path<String>) -> Result<Json<EscrowView>, ApiError> {
+    let (address, escrow) = fetch(&state, &order_id).await?;
+    Ok(Json(EscrowView::new(address, escrow)))
+}
+
+/// Build the buyer's dispute transaction, fee paid by the relayer, for the
+/// buyer's wallet to sign.
+#[utoipa::path(
+    post,
+    path = "/orders/{order_id}/dispute/prepare",
+    tag = "escrow",
+    params(("order_id" = String, Path, description = "Marketplace order id")),
+    request_body = PrepareDispute,
+    responses(
+        (status = 200, description = "Unsigned transaction; sign as the buyer and submit to /orders/{order_id}/dispute", body = RelayTransaction),
+        (status = 403, description = "Not the order's buyer", body = ErrorBody),
+        (status = 404, description = "No escrow for this order", body = ErrorBody),
+        (status = 409, description = "The escrow can no longer be disputed", body = ErrorBody),
+        (status = 503, description = "No relayer is configured", body = ErrorBody),
+    )
+)]
+pub async fn prepare_dispute(
+    State(state): State<Arc<AppState>>,
+    Path(order_id): Path<String>,
+    Json(request): Json<PrepareDispute>,
+) -> Result<Json<RelayTransaction>, ApiError> {
+    let relayer = relayer(&state)?;
+    let buyer = Pubkey::from_str(&request.buyer).map_err(|e| ApiError::bad_request(format!("buyer: {}", e)))?;
+    let (_, escrow) = fetch(&state, &order_id).await?;
+    check_disputable(&escrow, &buyer)?;
+
+    let blockhash = state.escrow.rpc().get_latest_blockhash().await?;
+    let message = dispute_message(&state, relayer, &buyer, &order_id, blockhash)?;
+    let transaction = Transaction::new_unsigned(message);
+    let bytes = bincode::serialize(&transaction).expect("Serializing a transaction cannot fail");
+    Ok(Json(RelayTransaction {
+        transaction: BASE64.encode(bytes),
+    }))
+}
+
+/// Submit a dispute transaction from `prepare`, signed by the buyer.
+#[utoipa::path(
+    post,
+    path = "/orders/{order_id}/dispute",
+    tag = "escrow",
+    params(("order_id" = String, Path, description = "Marketplace order id")),
+    request_body = RelayTransaction,
+    responses(
+        (status = 200, description = "The escrow is locked for admin resolution", body = DisputeOpened),
+        (status = 400, description = "Not the prepared transaction, or not signed by the buyer", body = ErrorBody),
+        (status = 409, description = "The escrow can no longer be disputed", body = ErrorBody),
+        (status = 503, description = "No relayer is configured", body = ErrorBody),
+    )
+)]
+pub async fn dispute(
+    State(state): State<Arc<AppState>>,
+    Path(order_id): Path<String>,
+    Json(request): Json<RelayTransaction>,
+) -> Result<Json<DisputeOpened>, ApiError> {
+    let relayer = relayer(&state)?;
+    let bytes = BASE64
+        .decode(&request.transaction)
+        .map_err(|e| ApiError::bad_request(format!("transaction: {}", e)))?;
+    let mut transaction: Transaction =
+        bincode::deserialize(&bytes).map_err(|e| ApiError::bad_request(format!("transaction: {}", e)))?;
+    let (address, escrow) = fetch(&state, &order_id).await?;
+    check_disputable(&escrow, &escrow.buyer)?;
+
+    // The relayer signs nothing but the dispute it prepared.
+    let blockhash = transaction.message.recent_blockhash;
+    let expected = dispute_message(&state, relayer, &escrow.buyer, &order_id, blockhash)?;
+    if transaction.message != expected {
+        return Err(ApiError::bad_request("Not the transaction prepared for this order"));
+    }
+    let buyer_index = transaction
+        .message
+        .account_keys
+        .iter()
+        .position(|key| *key == escrow.buyer)
+        .expect("The prepared message names the buyer");
+    let signed = transaction
+        .signatures
+        .get(buyer_index)
+        .is_some_and(|signature| signature.verify(escrow.buyer.as_ref(), &transaction.message_data()));
+    if !signed {
+        return Err(ApiError::bad_request("Missing or invalid buyer signature"));
+    }
+    transaction
+        .try_partial_sign(&[relayer], blockhash)
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+    let signature = state.escrow.rpc().send_and_confirm_transaction(&transaction).await?;
+    let (_, escrow) = fetch(&state, &order_id).await?;
+    Ok(Json(DisputeOpened {
+        signature: signature.to_string(),
+        escrow: EscrowView::new(address, escrow),
+    }))
+}
+
+async fn fetch(state: &AppState, order_id: &str) -> Result<(Pubkey, Escrow), ApiError> {
+    let (address, _) = pda::escrow_address(&state.escrow.program_id(), order_id)?;
+    match state.escrow.fetch_escrow(order_id).await? {
+        Some(escrow) => Ok((address, escrow)),
+        None => Err(ApiError::not_found(format!("No escrow found for order {}", order_id))),
+    }
+}
+
+fn relayer(state: &AppState) -> Result<&Keypair, ApiError> {
+    state
+        .relayer
+        .as_ref()
+        .ok_or_else(|| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "Relaying is not enabled on this gateway"))
+}
+
+fn check_disputable(escrow: &Escrow, buyer: &Pubkey) -> Result<(), ApiError> {
+    if escrow.buyer != *buyer {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "Only the order's buyer can dispute it"));
+    }
+    if escrow.status != EscrowStatus::Created {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            format!("Escrow is {:?}; only a funded escrow can be disputed", escrow.status),
+        ));
+    }
+    Ok(())
+}
+
+fn dispute_message(
+    state: &AppState,
+    relayer: &Keypair,
+    buyer: &Pubkey,
+    order_id: &str,
+    blockhash: solana_sdk::hash::Hash,
+) -> Result<Message, ApiError> {
+    let instruction = instructions::lock_dispute(&state.escrow.program_id(), buyer, order_id)?;
+    Ok(Message::new_with_blockhash(&[instruction], Some(&relayer.pubkey()), &blockhash))
+}
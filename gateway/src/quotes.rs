@@ -0,0 +1,224 @@
+//! Price quotes: a USD listing price in lamports or USDC, for checkout.
+//!
+//! Listings are priced in USD but `create_escrow` takes an amount in
+//! lamports, so checkout asks for a quote and passes its `amount` to
+//! `create_escrow` unchanged. The rate comes from the oracle (`oracle.rs`)
+//! and is widened by the configured spread in the seller's favour, so the
+//! escrow covers the listing price even if the rate moves before the
+//! transaction lands. A quote is binding until `expires_at`; the latest
+//! quote of each order is kept for a day, so support and the seller's
+//! backend can check an escrow's amount against it.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use proofcart_solana_client::pda;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::{ApiError, ErrorBody};
+use crate::oracle::{self, Oracle, Price};
+use crate::AppState;
+
+/// $1,000,000, far above any listing; keeps the arithmetic in range.
+const MAX_USD_CENTS: u64 = 100_000_000;
+
+/// How long an order's latest quote is kept after it was made.
+const RETENTION_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Currency {
+    /// Paid into the escrow program, in lamports.
+    Sol,
+    /// In micro-USDC, the SPL token's base unit.
+    Usdc,
+}
+
+impl Currency {
+    fn decimals(self) -> u32 {
+        match self {
+            Currency::Sol => 9,
+            Currency::Usdc => 6,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct QuoteRequest {
+    /// The listing price in US cents.
+    pub usd_cents: u64,
+    pub currency: Currency,
+}
+
+/// A binding price for an order. Times are Unix seconds.
+#[derive(Serialize, Clone, ToSchema)]
+pub struct Quote {
+    pub order_id: String,
+    pub usd_cents: u64,
+    pub currency: Currency,
+    /// What checkout must pass to `create_escrow`: lamports for `sol`,
+    /// micro-USDC for `usdc`.
+    pub amount: u64,
+    /// The oracle's USD price of one SOL or USDC, before the spread.
+    pub usd_rate: f64,
+    pub spread_bps: u64,
+    /// `pyth` or `switchboard`.
+    pub source: String,
+    pub price_published_at: i64,
+    pub quoted_at: i64,
+    pub expires_at: i64,
+}
+
+/// Quotes orders from the oracle and remembers the latest one per order.
+pub struct Pricer {
+    pub oracle: Oracle,
+    pub spread_bps: u64,
+    pub ttl_secs: u64,
+    quotes: Mutex<HashMap<String, Quote>>,
+}
+
+impl Pricer {
+    pub fn new(oracle: Oracle, spread_bps: u64, ttl_secs: u64) -> Self {
+        Self {
+            oracle,
+            spread_bps,
+            ttl_secs,
+            quotes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn remember(&self, quote: Quote) {
+        let mut quotes = self.quotes.lock().expect("quote store poisoned");
+        quotes.retain(|_, kept| quote.quoted_at - kept.quoted_at < RETENTION_SECS);
+        quotes.insert(quote.order_id.clone(), quote);
+    }
+
+    fn latest(&self, order_id: &str) -> Option<Quote> {
+        self.quotes.lock().expect("quote store poisoned").get(order_id).cloned()
+    }
+}
+
+/// `usd_cents` in base units of a token priced at `price`, widened by
+/// `spread_bps` and rounded up. `None` if it does not fit a `u64`.
+pub fn amount(usd_cents: u64, price: &Price, decimals: u32, spread_bps: u64) -> Option<u64> {
+    let pow10 = |exp: u32| 10u128.checked_pow(exp);
+    let mut numerator = u128::from(usd_cents)
+        .checked_mul(pow10(decimals)?)?
+        .checked_mul(10_000 + u128::from(spread_bps))?;
+    let mut denominator = 100 * 10_000 * u128::try_from(price.value).ok().filter(|v| *v > 0)?;
+    if price.expo < 0 {
+        numerator = numerator.checked_mul(pow10(price.expo.unsigned_abs())?)?;
+    } else {
+        denominator = denominator.checked_mul(pow10(price.expo as u32)?)?;
+    }
+    u64::try_from(numerator.div_ceil(denominator)).ok()
+}
+
+/// Quote an order's USD price in SOL or USDC.
+#[utoipa::path(
+    post,
+    path = "/orders/{order_id}/quote",
+    tag = "pricing",
+    params(("order_id" = String, Path, description = "Marketplace order id, 1-32 bytes")),
+    request_body = QuoteRequest,
+    responses(
+        (status = 200, description = "The amount to escrow, binding until expires_at", body = Quote),
+        (status = 400, description = "Malformed order id or price", body = ErrorBody),
+        (status = 503, description = "No fresh, confident oracle price", body = ErrorBody),
+    )
+)]
+pub async fn quote(
+    State(state): State<Arc<AppState>>,
+    Path(order_id): Path<String>,
+    Json(request): Json<QuoteRequest>,
+) -> Result<Json<Quote>, ApiError> {
+    pda::check_order_id(&order_id)?;
+    if request.usd_cents == 0 || request.usd_cents > MAX_USD_CENTS {
+        return Err(ApiError::bad_request(format!(
+            "usd_cents must be between 1 and {}",
+            MAX_USD_CENTS
+        )));
+    }
+    let pricer = &state.pricer;
+    let price = pricer
+        .oracle
+        .price(request.currency)
+        .await
+        .map_err(|e| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, format!("No usable price: {}", e)))?;
+    let amount = amount(request.usd_cents, &price, request.currency.decimals(), pricer.spread_bps)
+        .ok_or_else(|| ApiError::bad_request("The quoted amount is out of range"))?;
+
+    let quoted_at = oracle::now();
+    let quote = Quote {
+        order_id,
+        usd_cents: request.usd_cents,
+        currency: request.currency,
+        amount,
+        usd_rate: price.value as f64 * 10f64.powi(price.expo),
+        spread_bps: pricer.spread_bps,
+        source: price.source.to_string(),
+        price_published_at: price.published_at,
+        quoted_at,
+        expires_at: quoted_at + pricer.ttl_secs as i64,
+    };
+    pricer.remember(quote.clone());
+    Ok(Json(quote))
+}
+
+/// The latest quote of an order, expired or not.
+#[utoipa::path(
+    get,
+    path = "/orders/{order_id}/quote",
+    tag = "pricing",
+    params(("order_id" = String, Path, description = "Marketplace order id")),
+    responses(
+        (status = 200, description = "The order's latest quote", body = Quote),
+        (status = 404, description = "No quote for this order in the last day", body = ErrorBody),
+    )
+)]
+pub async fn latest_quote(State(state): State<Arc<AppState>>, Path(order_id): Path<String>) -> Result<Json<Quote>, ApiError> {
+    state
+        .pricer
+        .latest(&order_id)
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("No quote for order {}", order_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(value: i64, expo: i32) -> Price {
+        Price {
+            value,
+            conf: 0,
+            expo,
+            published_at: 0,
+            source: "pyth",
+        }
+    }
+
+    #[test]
+    fn converts_usd_to_base_units() {
+        // $25.00 at $150.00/SOL is 1/6 SOL, rounded up.
+        let sol = price(150_0000_0000, -8);
+        assert_eq!(amount(2500, &sol, 9, 0), Some(166_666_667));
+        // A 1% spread in the seller's favour.
+        assert_eq!(amount(2500, &sol, 9, 100), Some(168_333_334));
+        // USDC slightly under the peg costs slightly more.
+        assert_eq!(amount(2500, &price(9999_0000, -8), 6, 0), Some(25_002_501));
+        assert_eq!(amount(2500, &price(150, 0), 9, 0), Some(166_666_667));
+    }
+
+    #[test]
+    fn rejects_unusable_prices() {
+        assert_eq!(amount(2500, &price(0, -8), 9, 0), None);
+        assert_eq!(amount(2500, &price(-1, -8), 9, 0), None);
+        // Too many lamports for a u64.
+        assert_eq!(amount(MAX_USD_CENTS, &price(1, -8), 9, 0), None);
+    }
+}
@@ -0,0 +1,87 @@
+//! Product verification.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::Json;
+use proofcart_icp_client::types::{LossKind, ProductNFT, VerificationLevel};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::error::{ApiError, ErrorBody};
+use crate::AppState;
+
+/// What a buyer needs to judge a product, flattened from the canister's
+/// `ProductNFT`. Times are nanoseconds since the Unix epoch.
+#[derive(Serialize, ToSchema)]
+pub struct Verification {
+    pub nft_id: u64,
+    pub serial_number: String,
+    pub product_name: String,
+    pub manufacturer: String,
+    pub category: String,
+    pub manufacture_date: String,
+    /// ICP principal of the current owner.
+    pub owner: String,
+    pub minted_at: u64,
+    /// `self_attested`, `manufacturer_verified` or `third_party_audited`.
+    pub verification_level: String,
+    pub revoked: bool,
+    pub recalled: bool,
+    /// `stolen` or `lost` while a report is open.
+    pub reported: Option<String>,
+    /// False once the product is revoked, recalled or reported.
+    pub authentic: bool,
+}
+
+impl From<ProductNFT> for Verification {
+    fn from(nft: ProductNFT) -> Self {
+        let reported = nft.stolen.as_ref().map(|report| match report.kind {
+            LossKind::Stolen => "stolen".to_string(),
+            LossKind::Lost => "lost".to_string(),
+        });
+        let recalled = nft.recall.is_some();
+        Self {
+            nft_id: nft.nft_id,
+            serial_number: nft.serial_number,
+            product_name: nft.metadata.product_name,
+            manufacturer: nft.metadata.manufacturer,
+            category: nft.metadata.category,
+            manufacture_date: nft.metadata.manufacture_date,
+            owner: nft.owner.to_text(),
+            minted_at: nft.minted_at,
+            verification_level: match nft.verification_level {
+                VerificationLevel::SelfAttested => "self_attested",
+                VerificationLevel::ManufacturerVerified => "manufacturer_verified",
+                VerificationLevel::ThirdPartyAudited => "third_party_audited",
+            }
+            .to_string(),
+            revoked: nft.revoked,
+            recalled,
+            authentic: !nft.revoked && !recalled && reported.is_none(),
+            reported,
+        }
+    }
+}
+
+/// Verify a product by its serial number.
+#[utoipa::path(
+    get,
+    path = "/verify/{serial}",
+    tag = "verification",
+    params(
+        ("serial" = String, Path, description = "Product serial number, as printed or encoded in its QR code"),
+        ("X-API-Key" = Option<String>, Header, description = "Partner API key; without one a lower per-address limit applies"),
+    ),
+    responses(
+        (status = 200, description = "The product is registered", body = Verification),
+        (status = 401, description = "Unknown or revoked API key", body = ErrorBody),
+        (status = 404, description = "Unknown or burned serial", body = ErrorBody),
+        (status = 429, description = "Rate limit or daily quota exceeded; see Retry-After", body = ErrorBody),
+        (status = 502, description = "The canister could not be reached", body = ErrorBody),
+    )
+)]
+pub async fn verify(State(state): State<Arc<AppState>>, Path(serial): Path<String>) -> Result<Json<Verification>, ApiError> {
+    let nft = state.nft.verify_product(&serial).await?;
+    Ok(Json(nft.into()))
+}
@@ -0,0 +1,8 @@
+use async_graphql::{Context, Error, Result};
+use deadpool_postgres::{Object, Pool};
+
+/// A pooled connection for one resolver.
+pub async fn client(ctx: &Context<'_>) -> Result<Object> {
+    let pool = ctx.data::<Pool>()?;
+    pool.get().await.map_err(|e| Error::new(format!("Database unavailable: {}", e)))
+}
@@ -0,0 +1,55 @@
+//! `proofcart-graphql`: GraphQL over the indexer's Postgres database, for
+//! frontend views that need nested, cross-chain data in one request:
+//!
+//! ```graphql
+//! { nft(serialNumber: "SN-1001") { owner transfers { to order { escrow { status amountLamports } } } } }
+//! ```
+
+mod db;
+mod model;
+mod schema;
+
+use std::env;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use async_graphql::http::GraphiQLSource;
+use async_graphql_axum::GraphQL;
+use axum::response::Html;
+use axum::routing::get;
+use axum::Router;
+use deadpool_postgres::{Manager, Pool};
+use tokio_postgres::NoTls;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run() -> Result<(), String> {
+    let database_url = env::var("DATABASE_URL").map_err(|_| "DATABASE_URL is not set".to_string())?;
+    let addr = env::var("GRAPHQL_ADDR").unwrap_or_else(|_| "0.0.0.0:8081".to_string());
+
+    let config = tokio_postgres::Config::from_str(&database_url).map_err(|e| format!("DATABASE_URL: {}", e))?;
+    let pool = Pool::builder(Manager::new(config, NoTls))
+        .max_size(16)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let app = Router::new().route(
+        "/graphql",
+        get(|| async { Html(GraphiQLSource::build().endpoint("/graphql").finish()) })
+            .post_service(GraphQL::new(schema::build(pool))),
+    );
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .map_err(|e| format!("{}: {}", addr, e))?;
+    println!("GraphQL on http://{}/graphql", addr);
+    axum::serve(listener, app).await.map_err(|e| e.to_string())
+}
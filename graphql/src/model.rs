@@ -0,0 +1,410 @@
+//! GraphQL object types, one per indexer table, with resolvers for the
+//! nested and cross-chain fields.
+//!
+//! Times from Solana are Unix seconds; times from the canister are
+//! nanoseconds since the epoch, as the indexer stores them.
+
+use async_graphql::{ComplexObject, Context, Enum, Result, SimpleObject};
+use tokio_postgres::Row;
+
+use crate::db;
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EscrowStatus {
+    Created,
+    Locked,
+    Released,
+    Refunded,
+}
+
+impl EscrowStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EscrowStatus::Created => "created",
+            EscrowStatus::Locked => "locked",
+            EscrowStatus::Released => "released",
+            EscrowStatus::Refunded => "refunded",
+        }
+    }
+
+    fn parse(status: &str) -> Self {
+        match status {
+            "locked" => EscrowStatus::Locked,
+            "released" => EscrowStatus::Released,
+            "refunded" => EscrowStatus::Refunded,
+            _ => EscrowStatus::Created,
+        }
+    }
+}
+
+/// A marketplace order paid through the escrow program.
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct Order {
+    /// The partner marketplace's account address; null for the platform's
+    /// own orders. Order ids are only unique within a marketplace.
+    pub marketplace: Option<String>,
+    pub order_id: String,
+    pub escrow: Escrow,
+}
+
+/// The escrow account holding an order's payment.
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct Escrow {
+    #[graphql(skip)]
+    pub marketplace: Option<String>,
+    #[graphql(skip)]
+    pub order_id: String,
+    pub address: String,
+    pub buyer: String,
+    pub seller: String,
+    pub amount_lamports: i64,
+    pub status: EscrowStatus,
+    pub created_at: Option<i64>,
+    pub locked_at: Option<i64>,
+    pub released_at: Option<i64>,
+    pub resolved_at: Option<i64>,
+}
+
+impl Order {
+    pub const COLUMNS: &'static str = "marketplace, order_id, escrow_address, buyer, seller, amount_lamports, \
+         status, created_at, locked_at, released_at, resolved_at";
+
+    pub fn from_row(row: &Row) -> Self {
+        let marketplace = marketplace(row);
+        let order_id: String = row.get("order_id");
+        Self {
+            escrow: Escrow {
+                marketplace: marketplace.clone(),
+                order_id: order_id.clone(),
+                address: row.get("escrow_address"),
+                buyer: row.get("buyer"),
+                seller: row.get("seller"),
+                amount_lamports: row.get("amount_lamports"),
+                status: EscrowStatus::parse(row.get("status")),
+                created_at: row.get("created_at"),
+                locked_at: row.get("locked_at"),
+                released_at: row.get("released_at"),
+                resolved_at: row.get("resolved_at"),
+            },
+            marketplace,
+            order_id,
+        }
+    }
+}
+
+/// The `marketplace` column, which is '' for the platform's own orders.
+fn marketplace(row: &Row) -> Option<String> {
+    Some(row.get::<_, String>("marketplace")).filter(|marketplace| !marketplace.is_empty())
+}
+
+/// The `marketplace` column value of an order of `marketplace`.
+pub fn marketplace_key(marketplace: &Option<String>) -> &str {
+    marketplace.as_deref().unwrap_or_default()
+}
+
+#[ComplexObject]
+impl Order {
+    /// The dispute case, if the buyer opened one.
+    async fn dispute(&self, ctx: &Context<'_>) -> Result<Option<Dispute>> {
+        let sql = format!("SELECT {} WHERE d.marketplace = $1 AND d.order_id = $2", Dispute::SELECT);
+        let row = db::client(ctx)
+            .await?
+            .query_opt(sql.as_str(), &[&marketplace_key(&self.marketplace), &self.order_id])
+            .await?;
+        Ok(row.as_ref().map(Dispute::from_row))
+    }
+
+    /// The product NFT this order bought, once the sale is settled on the
+    /// canister. Only the platform's own orders sell NFTs.
+    async fn nft(&self, ctx: &Context<'_>) -> Result<Option<Nft>> {
+        if self.marketplace.is_some() {
+            return Ok(None);
+        }
+        let sql = format!(
+            "SELECT {} FROM nft_tokens WHERE nft_id = (SELECT nft_id FROM nft_transfers WHERE order_id = $1 \
+             ORDER BY block_index DESC LIMIT 1)",
+            Nft::COLUMNS
+        );
+        let row = db::client(ctx).await?.query_opt(sql.as_str(), &[&self.order_id]).await?;
+        Ok(row.as_ref().map(Nft::from_row))
+    }
+
+    /// The NFT transfer that settled this order.
+    async fn settlement(&self, ctx: &Context<'_>) -> Result<Option<Transfer>> {
+        if self.marketplace.is_some() {
+            return Ok(None);
+        }
+        let sql = format!(
+            "SELECT {} FROM nft_transfers WHERE order_id = $1 ORDER BY block_index DESC LIMIT 1",
+            Transfer::COLUMNS
+        );
+        let row = db::client(ctx).await?.query_opt(sql.as_str(), &[&self.order_id]).await?;
+        Ok(row.as_ref().map(Transfer::from_row))
+    }
+}
+
+#[ComplexObject]
+impl Escrow {
+    /// Network fees of the order's transactions.
+    async fn fees(&self, ctx: &Context<'_>) -> Result<Vec<Fee>> {
+        let rows = db::client(ctx)
+            .await?
+            .query(
+                "SELECT signature, payer, fee_lamports, slot FROM escrow_fees
+                 WHERE marketplace = $1 AND order_id = $2 ORDER BY slot",
+                &[&marketplace_key(&self.marketplace), &self.order_id],
+            )
+            .await?;
+        Ok(rows.iter().map(Fee::from_row).collect())
+    }
+
+    /// Every program instruction applied to the escrow, oldest first.
+    async fn events(&self, ctx: &Context<'_>) -> Result<Vec<EscrowEvent>> {
+        let rows = db::client(ctx)
+            .await?
+            .query(
+                "SELECT signature, instruction, kind, signer, slot, block_time FROM escrow_events
+                 WHERE marketplace = $1 AND order_id = $2 ORDER BY slot, instruction",
+                &[&marketplace_key(&self.marketplace), &self.order_id],
+            )
+            .await?;
+        Ok(rows.iter().map(EscrowEvent::from_row).collect())
+    }
+}
+
+#[derive(SimpleObject, Clone)]
+pub struct Fee {
+    pub signature: String,
+    pub payer: String,
+    pub fee_lamports: i64,
+    pub slot: i64,
+}
+
+impl Fee {
+    fn from_row(row: &Row) -> Self {
+        Self {
+            signature: row.get("signature"),
+            payer: row.get("payer"),
+            fee_lamports: row.get("fee_lamports"),
+            slot: row.get("slot"),
+        }
+    }
+}
+
+#[derive(SimpleObject, Clone)]
+pub struct EscrowEvent {
+    pub signature: String,
+    /// Position among the transaction's instructions.
+    pub instruction: i32,
+    /// Program instruction name, e.g. `lock_dispute`.
+    pub kind: String,
+    pub signer: String,
+    pub slot: i64,
+    pub block_time: Option<i64>,
+}
+
+impl EscrowEvent {
+    fn from_row(row: &Row) -> Self {
+        Self {
+            signature: row.get("signature"),
+            instruction: row.get::<_, i16>("instruction").into(),
+            kind: row.get("kind"),
+            signer: row.get("signer"),
+            slot: row.get("slot"),
+            block_time: row.get("block_time"),
+        }
+    }
+}
+
+/// A dispute case: opened by the buyer, closed by an admin resolution.
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct Dispute {
+    /// As on `Order`.
+    pub marketplace: Option<String>,
+    pub order_id: String,
+    pub opened_by: String,
+    pub opened_at: Option<i64>,
+    pub opened_signature: String,
+    pub resolution: Option<Resolution>,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Refund,
+    Release,
+}
+
+#[derive(SimpleObject, Clone)]
+pub struct Resolution {
+    pub outcome: Outcome,
+    pub resolved_by: String,
+    pub resolved_at: Option<i64>,
+    pub signature: String,
+}
+
+impl Dispute {
+    /// Columns of `escrow_disputes d` left-joined to `escrow_resolutions r`.
+    pub const SELECT: &'static str = "d.marketplace, d.order_id, d.opened_by, d.opened_at, d.opened_signature, \
+         r.outcome, r.resolved_by, r.resolved_at, r.signature \
+         FROM escrow_disputes d \
+         LEFT JOIN escrow_resolutions r ON r.marketplace = d.marketplace AND r.order_id = d.order_id";
+
+    pub fn from_row(row: &Row) -> Self {
+        let outcome: Option<String> = row.get("outcome");
+        Self {
+            marketplace: marketplace(row),
+            order_id: row.get("order_id"),
+            opened_by: row.get("opened_by"),
+            opened_at: row.get("opened_at"),
+            opened_signature: row.get("opened_signature"),
+            resolution: outcome.map(|outcome| Resolution {
+                outcome: if outcome == "refund" { Outcome::Refund } else { Outcome::Release },
+                resolved_by: row.get("resolved_by"),
+                resolved_at: row.get("resolved_at"),
+                signature: row.get("signature"),
+            }),
+        }
+    }
+}
+
+#[ComplexObject]
+impl Dispute {
+    async fn order(&self, ctx: &Context<'_>) -> Result<Order> {
+        let sql = format!("SELECT {} FROM escrow_orders WHERE marketplace = $1 AND order_id = $2", Order::COLUMNS);
+        let row = db::client(ctx)
+            .await?
+            .query_one(sql.as_str(), &[&marketplace_key(&self.marketplace), &self.order_id])
+            .await?;
+        Ok(Order::from_row(&row))
+    }
+}
+
+/// A product NFT as mirrored from the canister's transaction log.
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct Nft {
+    pub nft_id: i64,
+    pub serial_number: String,
+    /// Current owner's principal; none once burned.
+    pub owner: Option<String>,
+    pub minted_by: String,
+    pub minted_at: i64,
+    pub revoked: bool,
+    pub burned_at: Option<i64>,
+}
+
+impl Nft {
+    pub const COLUMNS: &'static str = "nft_id, serial_number, owner, minted_by, minted_at, revoked, burned_at";
+
+    pub fn from_row(row: &Row) -> Self {
+        Self {
+            nft_id: row.get("nft_id"),
+            serial_number: row.get("serial_number"),
+            owner: row.get("owner"),
+            minted_by: row.get("minted_by"),
+            minted_at: row.get("minted_at"),
+            revoked: row.get("revoked"),
+            burned_at: row.get("burned_at"),
+        }
+    }
+}
+
+#[ComplexObject]
+impl Nft {
+    /// Ownership history, oldest transfer first.
+    async fn transfers(&self, ctx: &Context<'_>) -> Result<Vec<Transfer>> {
+        let sql = format!(
+            "SELECT {} FROM nft_transfers WHERE nft_id = $1 ORDER BY block_index",
+            Transfer::COLUMNS
+        );
+        let rows = db::client(ctx).await?.query(sql.as_str(), &[&self.nft_id]).await?;
+        Ok(rows.iter().map(Transfer::from_row).collect())
+    }
+
+    /// Verification revocations and restorations, oldest first.
+    async fn revocations(&self, ctx: &Context<'_>) -> Result<Vec<Revocation>> {
+        let rows = db::client(ctx)
+            .await?
+            .query(
+                "SELECT action, reason, moderator, timestamp_ns FROM nft_revocations
+                 WHERE nft_id = $1 ORDER BY block_index",
+                &[&self.nft_id],
+            )
+            .await?;
+        Ok(rows.iter().map(Revocation::from_row).collect())
+    }
+
+    /// Escrow orders the product was sold through, oldest first.
+    async fn orders(&self, ctx: &Context<'_>) -> Result<Vec<Order>> {
+        let sql = format!(
+            "SELECT o.{} FROM nft_transfers t JOIN escrow_orders o ON o.marketplace = '' AND o.order_id = t.order_id
+             WHERE t.nft_id = $1 ORDER BY t.block_index",
+            Order::COLUMNS.replace(", ", ", o.")
+        );
+        let rows = db::client(ctx).await?.query(sql.as_str(), &[&self.nft_id]).await?;
+        Ok(rows.iter().map(Order::from_row).collect())
+    }
+}
+
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct Transfer {
+    pub block_index: i64,
+    pub from: String,
+    pub to: String,
+    pub timestamp: i64,
+    pub memo: Option<String>,
+    /// Escrow order this transfer settled, for marketplace sales.
+    pub order_id: Option<String>,
+}
+
+impl Transfer {
+    const COLUMNS: &'static str = "block_index, from_owner, to_owner, timestamp_ns, memo, order_id";
+
+    fn from_row(row: &Row) -> Self {
+        Self {
+            block_index: row.get("block_index"),
+            from: row.get("from_owner"),
+            to: row.get("to_owner"),
+            timestamp: row.get("timestamp_ns"),
+            memo: row.get("memo"),
+            order_id: row.get("order_id"),
+        }
+    }
+}
+
+#[ComplexObject]
+impl Transfer {
+    async fn order(&self, ctx: &Context<'_>) -> Result<Option<Order>> {
+        let Some(order_id) = &self.order_id else {
+            return Ok(None);
+        };
+        let sql = format!("SELECT {} FROM escrow_orders WHERE marketplace = '' AND order_id = $1", Order::COLUMNS);
+        let row = db::client(ctx).await?.query_opt(sql.as_str(), &[order_id]).await?;
+        Ok(row.as_ref().map(Order::from_row))
+    }
+}
+
+#[derive(SimpleObject, Clone)]
+pub struct Revocation {
+    /// `revoked` or `restored`.
+    pub action: String,
+    /// The canister's revocation reason, e.g. `Counterfeit`.
+    pub reason: Option<String>,
+    pub moderator: String,
+    pub timestamp: i64,
+}
+
+impl Revocation {
+    fn from_row(row: &Row) -> Self {
+        Self {
+            action: row.get("action"),
+            reason: row.get("reason"),
+            moderator: row.get("moderator"),
+            timestamp: row.get("timestamp_ns"),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Query root.
+
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Result, Schema};
+
+use crate::db;
+use crate::model::{marketplace_key, Dispute, EscrowStatus, Nft, Order};
+
+pub type ProofCartSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Largest page a list field returns.
+const MAX_PAGE_SIZE: i64 = 100;
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// An order of the platform, or of the partner marketplace at the
+    /// `marketplace` address.
+    async fn order(&self, ctx: &Context<'_>, order_id: String, marketplace: Option<String>) -> Result<Option<Order>> {
+        let sql = format!("SELECT {} FROM escrow_orders WHERE marketplace = $1 AND order_id = $2", Order::COLUMNS);
+        let row = db::client(ctx)
+            .await?
+            .query_opt(sql.as_str(), &[&marketplace_key(&marketplace), &order_id])
+            .await?;
+        Ok(row.as_ref().map(Order::from_row))
+    }
+
+    /// Orders, newest first, optionally of one buyer or seller (base58) or
+    /// in one status.
+    async fn orders(
+        &self,
+        ctx: &Context<'_>,
+        buyer: Option<String>,
+        seller: Option<String>,
+        status: Option<EscrowStatus>,
+        #[graphql(default = 20)] first: i64,
+        #[graphql(default = 0)] offset: i64,
+    ) -> Result<Vec<Order>> {
+        let sql = format!(
+            "SELECT {} FROM escrow_orders
+             WHERE ($1::text IS NULL OR buyer = $1) AND ($2::text IS NULL OR seller = $2)
+               AND ($3::text IS NULL OR status = $3)
+             ORDER BY last_slot DESC, order_id LIMIT $4 OFFSET $5",
+            Order::COLUMNS
+        );
+        let status = status.map(EscrowStatus::as_str);
+        let rows = db::client(ctx)
+            .await?
+            .query(sql.as_str(), &[&buyer, &seller, &status, &page_size(first), &offset.max(0)])
+            .await?;
+        Ok(rows.iter().map(Order::from_row).collect())
+    }
+
+    /// Dispute cases, newest first; `open` selects those awaiting or past
+    /// resolution.
+    async fn disputes(
+        &self,
+        ctx: &Context<'_>,
+        open: Option<bool>,
+        #[graphql(default = 20)] first: i64,
+        #[graphql(default = 0)] offset: i64,
+    ) -> Result<Vec<Dispute>> {
+        let sql = format!(
+            "SELECT {} WHERE ($1::bool IS NULL OR (r.order_id IS NULL) = $1)
+             ORDER BY d.opened_slot DESC LIMIT $2 OFFSET $3",
+            Dispute::SELECT
+        );
+        let rows = db::client(ctx)
+            .await?
+            .query(sql.as_str(), &[&open, &page_size(first), &offset.max(0)])
+            .await?;
+        Ok(rows.iter().map(Dispute::from_row).collect())
+    }
+
+    async fn nft(&self, ctx: &Context<'_>, serial_number: String) -> Result<Option<Nft>> {
+        let sql = format!("SELECT {} FROM nft_tokens WHERE serial_number = $1", Nft::COLUMNS);
+        let row = db::client(ctx).await?.query_opt(sql.as_str(), &[&serial_number]).await?;
+        Ok(row.as_ref().map(Nft::from_row))
+    }
+
+    /// Tokens in id order, optionally of one owner (principal text) or only
+    /// revoked ones.
+    async fn nfts(
+        &self,
+        ctx: &Context<'_>,
+        owner: Option<String>,
+        revoked: Option<bool>,
+        #[graphql(default = 20)] first: i64,
+        #[graphql(default = 0)] offset: i64,
+    ) -> Result<Vec<Nft>> {
+        let sql = format!(
+            "SELECT {} FROM nft_tokens
+             WHERE ($1::text IS NULL OR owner = $1) AND ($2::bool IS NULL OR revoked = $2)
+             ORDER BY nft_id LIMIT $3 OFFSET $4",
+            Nft::COLUMNS
+        );
+        let rows = db::client(ctx)
+            .await?
+            .query(sql.as_str(), &[&owner, &revoked, &page_size(first), &offset.max(0)])
+            .await?;
+        Ok(rows.iter().map(Nft::from_row).collect())
+    }
+}
+
+fn page_size(first: i64) -> i64 {
+    first.clamp(0, MAX_PAGE_SIZE)
+}
+
+pub fn build(pool: deadpool_postgres::Pool) -> ProofCartSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .data(pool)
+        .limit_depth(8)
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_joins_orders_and_nfts() {
+        let sdl = Schema::build(Query, EmptyMutation, EmptySubscription).finish().sdl();
+        for field in ["nft: Nft", "settlement: Transfer", "orders: [Order!]!", "dispute: Dispute"] {
+            assert!(sdl.contains(field), "schema lacks {}", field);
+        }
+    }
+}
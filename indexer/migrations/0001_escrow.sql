@@ -0,0 +1,70 @@
+-- Escrows of the Solana escrow program. Addresses and signatures are base58,
+-- amounts and fees are lamports, times are Unix seconds from the block.
+
+CREATE TABLE escrow_orders (
+    order_id        TEXT PRIMARY KEY,
+    escrow_address  TEXT NOT NULL UNIQUE,
+    buyer           TEXT NOT NULL,
+    seller          TEXT NOT NULL,
+    amount_lamports BIGINT NOT NULL,
+    status          TEXT NOT NULL CHECK (status IN ('created', 'locked', 'released', 'refunded')),
+    created_at      BIGINT,
+    locked_at       BIGINT,
+    released_at     BIGINT,
+    resolved_at     BIGINT,
+    last_slot       BIGINT NOT NULL,
+    last_signature  TEXT NOT NULL
+);
+
+CREATE INDEX escrow_orders_buyer ON escrow_orders (buyer);
+CREATE INDEX escrow_orders_seller ON escrow_orders (seller);
+CREATE INDEX escrow_orders_status ON escrow_orders (status);
+
+CREATE TABLE escrow_disputes (
+    order_id         TEXT PRIMARY KEY REFERENCES escrow_orders (order_id),
+    opened_by        TEXT NOT NULL,
+    opened_at        BIGINT,
+    opened_slot      BIGINT NOT NULL,
+    opened_signature TEXT NOT NULL
+);
+
+CREATE TABLE escrow_resolutions (
+    order_id    TEXT PRIMARY KEY REFERENCES escrow_orders (order_id),
+    outcome     TEXT NOT NULL CHECK (outcome IN ('refund', 'release')),
+    resolved_by TEXT NOT NULL,
+    resolved_at BIGINT,
+    slot        BIGINT NOT NULL,
+    signature   TEXT NOT NULL
+);
+
+-- Network fee of each indexed transaction, charged to its fee payer.
+CREATE TABLE escrow_fees (
+    signature    TEXT PRIMARY KEY,
+    order_id     TEXT NOT NULL REFERENCES escrow_orders (order_id),
+    payer        TEXT NOT NULL,
+    fee_lamports BIGINT NOT NULL,
+    slot         BIGINT NOT NULL
+);
+
+CREATE INDEX escrow_fees_order ON escrow_fees (order_id);
+
+-- One row per applied instruction. Its key makes every write exactly-once.
+CREATE TABLE escrow_events (
+    signature   TEXT NOT NULL,
+    instruction SMALLINT NOT NULL,
+    order_id    TEXT NOT NULL REFERENCES escrow_orders (order_id),
+    kind        TEXT NOT NULL,
+    signer      TEXT NOT NULL,
+    slot        BIGINT NOT NULL,
+    block_time  BIGINT,
+    PRIMARY KEY (signature, instruction)
+);
+
+CREATE INDEX escrow_events_order ON escrow_events (order_id, slot);
+
+-- Newest transaction each source has processed.
+CREATE TABLE indexer_cursors (
+    source         TEXT PRIMARY KEY,
+    last_signature TEXT NOT NULL,
+    last_slot      BIGINT NOT NULL
+);
@@ -0,0 +1,57 @@
+-- Product NFTs, mirrored from the canister's transaction log. Principals are
+-- text, times are nanoseconds since the Unix epoch as the canister reports
+-- them.
+
+-- Every block of the log, in order. Its index makes every write exactly-once.
+CREATE TABLE nft_blocks (
+    block_index   BIGINT PRIMARY KEY,
+    kind          TEXT NOT NULL,
+    nft_id        BIGINT NOT NULL,
+    serial_number TEXT NOT NULL,
+    from_owner    TEXT,
+    to_owner      TEXT,
+    caller        TEXT NOT NULL,
+    timestamp_ns  BIGINT NOT NULL,
+    memo          TEXT
+);
+
+CREATE INDEX nft_blocks_nft ON nft_blocks (nft_id, block_index);
+
+-- Tokens migrated from the legacy canister have no mint block, so their
+-- transfers and revocations can precede any row here.
+CREATE TABLE nft_tokens (
+    nft_id        BIGINT PRIMARY KEY,
+    serial_number TEXT NOT NULL UNIQUE,
+    owner         TEXT,
+    minted_by     TEXT NOT NULL,
+    minted_at     BIGINT NOT NULL,
+    revoked       BOOLEAN NOT NULL DEFAULT FALSE,
+    burned_at     BIGINT,
+    last_block    BIGINT NOT NULL
+);
+
+CREATE INDEX nft_tokens_owner ON nft_tokens (owner);
+
+CREATE TABLE nft_transfers (
+    block_index  BIGINT PRIMARY KEY REFERENCES nft_blocks (block_index),
+    nft_id       BIGINT NOT NULL,
+    from_owner   TEXT NOT NULL,
+    to_owner     TEXT NOT NULL,
+    timestamp_ns BIGINT NOT NULL,
+    memo         TEXT
+);
+
+CREATE INDEX nft_transfers_nft ON nft_transfers (nft_id);
+
+-- Verification revocations and restorations.
+CREATE TABLE nft_revocations (
+    block_index  BIGINT PRIMARY KEY REFERENCES nft_blocks (block_index),
+    nft_id       BIGINT NOT NULL,
+    action       TEXT NOT NULL CHECK (action IN ('revoked', 'restored')),
+    -- The canister's RevocationReason; NULL for a restoration.
+    reason       TEXT,
+    moderator    TEXT NOT NULL,
+    timestamp_ns BIGINT NOT NULL
+);
+
+CREATE INDEX nft_revocations_nft ON nft_revocations (nft_id);
@@ -0,0 +1,10 @@
+-- Escrow order settled by a sale transfer, from the memo the canister's
+-- `transfer_from` writes. Joins product NFTs to escrow_orders.
+
+ALTER TABLE nft_transfers ADD COLUMN order_id TEXT;
+
+UPDATE nft_transfers
+SET order_id = substr(memo, length('sale for order ') + 1)
+WHERE memo LIKE 'sale for order %';
+
+CREATE INDEX nft_transfers_order ON nft_transfers (order_id) WHERE order_id IS NOT NULL;
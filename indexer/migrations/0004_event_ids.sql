@@ -0,0 +1,7 @@
+-- Insertion order of escrow events, so consumers such as the notifier can
+-- follow the table with a cursor. The indexer is the only writer and applies
+-- transactions oldest first, so ids increase in commit order.
+
+ALTER TABLE escrow_events ADD COLUMN id BIGSERIAL;
+
+CREATE UNIQUE INDEX escrow_events_id ON escrow_events (id);
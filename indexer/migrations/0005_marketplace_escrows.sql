@@ -0,0 +1,52 @@
+-- Order ids are unique per marketplace, not across them: two partner
+-- marketplaces may each open an escrow for "ORD-1". Escrows are keyed by
+-- their marketplace's account address, or '' for the platform's own.
+
+ALTER TABLE escrow_disputes DROP CONSTRAINT escrow_disputes_order_id_fkey;
+ALTER TABLE escrow_resolutions DROP CONSTRAINT escrow_resolutions_order_id_fkey;
+ALTER TABLE escrow_fees DROP CONSTRAINT escrow_fees_order_id_fkey;
+ALTER TABLE escrow_events DROP CONSTRAINT escrow_events_order_id_fkey;
+
+ALTER TABLE escrow_orders
+    ADD COLUMN marketplace TEXT NOT NULL DEFAULT '',
+    DROP CONSTRAINT escrow_orders_pkey,
+    ADD PRIMARY KEY (marketplace, order_id);
+
+ALTER TABLE escrow_disputes
+    ADD COLUMN marketplace TEXT NOT NULL DEFAULT '',
+    DROP CONSTRAINT escrow_disputes_pkey,
+    ADD PRIMARY KEY (marketplace, order_id),
+    ADD FOREIGN KEY (marketplace, order_id) REFERENCES escrow_orders (marketplace, order_id);
+
+ALTER TABLE escrow_resolutions
+    ADD COLUMN marketplace TEXT NOT NULL DEFAULT '',
+    DROP CONSTRAINT escrow_resolutions_pkey,
+    ADD PRIMARY KEY (marketplace, order_id),
+    ADD FOREIGN KEY (marketplace, order_id) REFERENCES escrow_orders (marketplace, order_id);
+
+ALTER TABLE escrow_fees
+    ADD COLUMN marketplace TEXT NOT NULL DEFAULT '',
+    ADD FOREIGN KEY (marketplace, order_id) REFERENCES escrow_orders (marketplace, order_id);
+
+ALTER TABLE escrow_events
+    ADD COLUMN marketplace TEXT NOT NULL DEFAULT '',
+    ADD FOREIGN KEY (marketplace, order_id) REFERENCES escrow_orders (marketplace, order_id);
+
+DROP INDEX escrow_fees_order;
+CREATE INDEX escrow_fees_order ON escrow_fees (marketplace, order_id);
+DROP INDEX escrow_events_order;
+CREATE INDEX escrow_events_order ON escrow_events (marketplace, order_id, slot);
+
+-- Partner marketplaces, from `create_marketplace` and `update_marketplace`.
+CREATE TABLE escrow_marketplaces (
+    address        TEXT PRIMARY KEY,
+    marketplace_id TEXT NOT NULL UNIQUE,
+    admin          TEXT NOT NULL,
+    fee_bps        INTEGER NOT NULL,
+    fee_recipient  TEXT NOT NULL,
+    mints          TEXT[] NOT NULL,
+    created_at     BIGINT,
+    updated_at     BIGINT,
+    last_slot      BIGINT NOT NULL,
+    last_signature TEXT NOT NULL
+);
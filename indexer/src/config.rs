@@ -0,0 +1,125 @@
+use std::env;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use candid::Principal;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::Error;
+
+/// Settings read from the environment. Each source is indexed when its
+/// program or canister id is set; at least one must be.
+pub struct Config {
+    pub database_url: String,
+    pub metrics_addr: SocketAddr,
+    pub solana_rpc_url: String,
+    /// Defaults to the RPC URL with its scheme swapped for `ws`/`wss`.
+    pub solana_ws_url: String,
+    pub program_id: Option<Pubkey>,
+    pub ic_url: String,
+    pub nft_canister_id: Option<Principal>,
+    /// How often to poll for new signatures when no log notification
+    /// arrives, so a dropped websocket never stalls the indexer, and for new
+    /// blocks once the canister's log is caught up.
+    pub poll_interval: Duration,
+    /// Only finalized transactions are indexed by default, so rows are never
+    /// written for a fork that is later abandoned.
+    pub commitment: CommitmentConfig,
+    /// Lag beyond which `/healthz` reports the source unhealthy.
+    pub max_solana_lag_slots: u64,
+    pub max_icp_lag_blocks: u64,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, Error> {
+        let database_url = required("DATABASE_URL")?;
+        let metrics_addr = env::var("METRICS_ADDR")
+            .unwrap_or_else(|_| "0.0.0.0:9102".to_string())
+            .parse()
+            .map_err(|e| Error::Config(format!("METRICS_ADDR: {}", e)))?;
+        let solana_rpc_url = env::var("SOLANA_RPC_URL").unwrap_or_else(|_| "https://api.devnet.solana.com".to_string());
+        let solana_ws_url = env::var("SOLANA_WS_URL").unwrap_or_else(|_| websocket_url(&solana_rpc_url));
+        let program_id = optional("SOLANA_PROGRAM_ID", |id| Pubkey::from_str(id).map_err(|e| e.to_string()))?;
+        let ic_url = env::var("IC_URL").unwrap_or_else(|_| "https://ic0.app".to_string());
+        let nft_canister_id = optional("ICP_CANISTER_ID", |id| Principal::from_text(id).map_err(|e| e.to_string()))?;
+        if program_id.is_none() && nft_canister_id.is_none() {
+            return Err(Error::Config("set SOLANA_PROGRAM_ID, ICP_CANISTER_ID or both".to_string()));
+        }
+        let poll_interval = match env::var("POLL_INTERVAL_SECS") {
+            Ok(secs) => Duration::from_secs(
+                secs.parse()
+                    .map_err(|e| Error::Config(format!("POLL_INTERVAL_SECS: {}", e)))?,
+            ),
+            Err(_) => Duration::from_secs(30),
+        };
+        let commitment = match env::var("SOLANA_COMMITMENT") {
+            Ok(level) => CommitmentConfig::from_str(&level)
+                .map_err(|e| Error::Config(format!("SOLANA_COMMITMENT: {}", e)))?,
+            Err(_) => CommitmentConfig::finalized(),
+        };
+        let max_solana_lag_slots = number("HEALTH_MAX_SOLANA_LAG_SLOTS", 300)?;
+        let max_icp_lag_blocks = number("HEALTH_MAX_ICP_LAG_BLOCKS", 1_000)?;
+        Ok(Self {
+            database_url,
+            metrics_addr,
+            solana_rpc_url,
+            solana_ws_url,
+            program_id,
+            ic_url,
+            nft_canister_id,
+            poll_interval,
+            commitment,
+            max_solana_lag_slots,
+            max_icp_lag_blocks,
+        })
+    }
+}
+
+fn required(name: &str) -> Result<String, Error> {
+    env::var(name).map_err(|_| Error::Config(format!("{} is not set", name)))
+}
+
+fn optional<T>(name: &str, parse: impl Fn(&str) -> Result<T, String>) -> Result<Option<T>, Error> {
+    match env::var(name) {
+        Ok(value) => parse(&value)
+            .map(Some)
+            .map_err(|e| Error::Config(format!("{}: {}", name, e))),
+        Err(_) => Ok(None),
+    }
+}
+
+fn number(name: &str, default: u64) -> Result<u64, Error> {
+    match env::var(name) {
+        Ok(value) => value.parse().map_err(|e| Error::Config(format!("{}: {}", name, e))),
+        Err(_) => Ok(default),
+    }
+}
+
+fn websocket_url(rpc_url: &str) -> String {
+    if let Some(rest) = rpc_url.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = rpc_url.strip_prefix("http://") {
+        // solana-test-validator serves websockets on the RPC port + 1.
+        match rest.trim_end_matches('/').rsplit_once(':') {
+            Some((host, port)) if port.parse::<u16>().is_ok() => {
+                format!("ws://{}:{}", host, port.parse::<u16>().unwrap() + 1)
+            }
+            _ => format!("ws://{}", rest),
+        }
+    } else {
+        rpc_url.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_websocket_url() {
+        assert_eq!(websocket_url("https://api.devnet.solana.com"), "wss://api.devnet.solana.com");
+        assert_eq!(websocket_url("http://127.0.0.1:8899"), "ws://127.0.0.1:8900");
+    }
+}
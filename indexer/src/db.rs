@@ -0,0 +1,82 @@
+//! Connection and schema migrations.
+
+use tokio_postgres::{Client, NoTls};
+
+use crate::Error;
+
+/// Applied in order, each once, recorded in `schema_migrations`.
+const MIGRATIONS: &[(i32, &str)] = &[
+    (1, include_str!("../migrations/0001_escrow.sql")),
+    (2, include_str!("../migrations/0002_nft.sql")),
+    (3, include_str!("../migrations/0003_sale_orders.sql")),
+    (4, include_str!("../migrations/0004_event_ids.sql")),
+    (5, include_str!("../migrations/0005_marketplace_escrows.sql")),
+];
+
+/// Connect and drive the connection on a background task.
+pub async fn connect(url: &str) -> Result<Client, Error> {
+    let (client, connection) = tokio_postgres::connect(url, NoTls).await?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("Postgres connection closed: {}", e);
+        }
+    });
+    Ok(client)
+}
+
+pub async fn migrate(db: &mut Client) -> Result<(), Error> {
+    db.batch_execute("CREATE TABLE IF NOT EXISTS schema_migrations (version INTEGER PRIMARY KEY)")
+        .await?;
+    for (version, sql) in MIGRATIONS {
+        let tx = db.transaction().await?;
+        // Serializes concurrent indexers starting against an empty database.
+        tx.batch_execute("LOCK TABLE schema_migrations IN EXCLUSIVE MODE").await?;
+        let applied = tx
+            .query_opt("SELECT 1 FROM schema_migrations WHERE version = $1", &[version])
+            .await?
+            .is_some();
+        if !applied {
+            tx.batch_execute(sql).await?;
+            tx.execute("INSERT INTO schema_migrations (version) VALUES ($1)", &[version])
+                .await?;
+        }
+        tx.commit().await?;
+    }
+    Ok(())
+}
+
+/// Channel notified with an order id whenever a commit changes the order's
+/// escrow or settles its NFT sale, for live views to refresh.
+pub const ORDER_CHANNEL: &str = "proofcart_orders";
+
+/// Notify listeners of `order_id` once the current transaction commits.
+pub async fn notify_order(db: &impl tokio_postgres::GenericClient, order_id: &str) -> Result<(), Error> {
+    db.execute("SELECT pg_notify($1, $2)", &[&ORDER_CHANNEL, &order_id]).await?;
+    Ok(())
+}
+
+/// Last transaction processed by `source`.
+pub async fn cursor(db: &Client, source: &str) -> Result<Option<(String, i64)>, Error> {
+    let row = db
+        .query_opt(
+            "SELECT last_signature, last_slot FROM indexer_cursors WHERE source = $1",
+            &[&source],
+        )
+        .await?;
+    Ok(row.map(|row| (row.get(0), row.get(1))))
+}
+
+pub async fn set_cursor(
+    db: &impl tokio_postgres::GenericClient,
+    source: &str,
+    signature: &str,
+    slot: i64,
+) -> Result<(), Error> {
+    db.execute(
+        "INSERT INTO indexer_cursors (source, last_signature, last_slot) VALUES ($1, $2, $3)
+         ON CONFLICT (source) DO UPDATE SET last_signature = EXCLUDED.last_signature, last_slot = EXCLUDED.last_slot",
+        &[&source, &signature, &slot],
+    )
+    .await?;
+    Ok(())
+}
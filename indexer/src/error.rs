@@ -0,0 +1,68 @@
+use std::fmt;
+
+use solana_client::client_error::ClientError;
+use solana_client::nonblocking::pubsub_client::PubsubClientError;
+
+#[derive(Debug)]
+pub enum Error {
+    /// A required setting is missing or malformed.
+    Config(String),
+    Db(tokio_postgres::Error),
+    Escrow(proofcart_solana_client::Error),
+    Nft(proofcart_icp_client::Error),
+    Io(std::io::Error),
+    Rpc(Box<ClientError>),
+    Pubsub(Box<PubsubClientError>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(msg) => write!(f, "Invalid configuration: {}", msg),
+            Error::Db(e) => write!(f, "Database error: {}", e),
+            Error::Escrow(e) => write!(f, "{}", e),
+            Error::Nft(e) => write!(f, "{}", e),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Rpc(e) => write!(f, "Solana RPC error: {}", e),
+            Error::Pubsub(e) => write!(f, "Solana websocket error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<tokio_postgres::Error> for Error {
+    fn from(e: tokio_postgres::Error) -> Self {
+        Error::Db(e)
+    }
+}
+
+impl From<proofcart_solana_client::Error> for Error {
+    fn from(e: proofcart_solana_client::Error) -> Self {
+        Error::Escrow(e)
+    }
+}
+
+impl From<proofcart_icp_client::Error> for Error {
+    fn from(e: proofcart_icp_client::Error) -> Self {
+        Error::Nft(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ClientError> for Error {
+    fn from(e: ClientError) -> Self {
+        Error::Rpc(Box::new(e))
+    }
+}
+
+impl From<PubsubClientError> for Error {
+    fn from(e: PubsubClientError) -> Self {
+        Error::Pubsub(Box::new(e))
+    }
+}
@@ -0,0 +1,86 @@
+//! Indexer for the NFT canister's transaction log.
+//!
+//! The log is append-only with contiguous indexes, so the next block to
+//! fetch is always one past the highest stored; no separate cursor is kept.
+//! Blocks are read with anonymous queries, page by page, from the canister
+//! or, for old blocks, from the archive canister holding them.
+
+mod store;
+
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use candid::Principal;
+use proofcart_icp_client::{identity, NftClient};
+use tokio_postgres::Client;
+
+use crate::metrics::Metrics;
+use crate::{Config, Error};
+
+/// Largest page the canister returns.
+const PAGE_SIZE: u64 = 1_000;
+
+/// Index until a database error. Canister errors are retried after the poll
+/// interval.
+pub async fn run(config: &Config, canister_id: Principal, db: &mut Client, metrics: Arc<Metrics>) -> Result<(), Error> {
+    let client = NftClient::connect(&config.ic_url, identity::anonymous(), canister_id).await?;
+    loop {
+        match sync_page(&client, db, &metrics).await {
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(Error::Db(e)) => return Err(Error::Db(e)),
+            Err(e) => {
+                metrics.rpc_errors.inc(&["icp"]);
+                eprintln!("{}; retrying in {}s", e, config.poll_interval.as_secs());
+            }
+        }
+        tokio::time::sleep(config.poll_interval).await;
+    }
+}
+
+/// Apply the next page of blocks. Returns whether more are waiting.
+async fn sync_page(client: &NftClient, db: &mut Client, metrics: &Metrics) -> Result<bool, Error> {
+    let next = store::next_index(db).await?;
+    let response = client.get_transactions(next, PAGE_SIZE).await?;
+    metrics.icp_indexed_blocks.store(next, Ordering::Relaxed);
+    metrics.icp_log_length.store(response.log_length, Ordering::Relaxed);
+
+    let blocks = if next < response.first_index {
+        // Archived ranges are reported clipped to the requested start.
+        match response.archived.iter().find(|range| range.start == next) {
+            Some(range) => {
+                client
+                    .get_archived_transactions(range.canister_id, range.start, range.length.min(PAGE_SIZE))
+                    .await?
+            }
+            None => return Err(missing_block(next)),
+        }
+    } else {
+        response.transactions
+    };
+    if let Some((offset, _)) = blocks
+        .iter()
+        .enumerate()
+        .find(|(offset, block)| block.index != next + *offset as u64)
+    {
+        return Err(missing_block(next + offset as u64));
+    }
+    let Some(last) = blocks.last() else {
+        return Ok(false);
+    };
+
+    let applied = store::apply(db, &blocks).await?;
+    metrics.events.add(&["icp"], applied);
+    let indexed = last.index + 1;
+    metrics.icp_indexed_blocks.store(indexed, Ordering::Relaxed);
+    metrics.icp_last_block_ns.store(last.timestamp, Ordering::Relaxed);
+    println!("Indexed NFT blocks {}..{} ({} new)", next, indexed, applied);
+    Ok(indexed < response.log_length)
+}
+
+fn missing_block(index: u64) -> Error {
+    Error::Nft(proofcart_icp_client::Error::Canister(format!(
+        "Transaction log did not return block {}",
+        index
+    )))
+}
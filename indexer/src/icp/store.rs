@@ -0,0 +1,157 @@
+//! Writes transaction log blocks to Postgres.
+//!
+//! A page is applied in one database transaction. Each block is first
+//! inserted into `nft_blocks` keyed by its index; a block already there is
+//! skipped, so a page fetched twice changes nothing.
+
+use proofcart_icp_client::types::{Block, TxKind};
+use tokio_postgres::{Client, GenericClient};
+
+use crate::{db, Error};
+
+/// Memo prefixes of the canister's moderation blocks (`moderation.rs`) and
+/// settled sales (`sale_lock.rs`).
+const REVOKED_MEMO: &str = "verification revoked: ";
+const RESTORED_MEMO: &str = "verification restored";
+const SALE_MEMO: &str = "sale for order ";
+
+/// Index of the first block not yet stored.
+pub async fn next_index(db: &Client) -> Result<u64, Error> {
+    let row = db
+        .query_one("SELECT COALESCE(MAX(block_index) + 1, 0) FROM nft_blocks", &[])
+        .await?;
+    Ok(row.get::<_, i64>(0) as u64)
+}
+
+/// Apply `blocks` in order; returns how many were new.
+pub async fn apply(db: &mut Client, blocks: &[Block]) -> Result<u64, Error> {
+    let tx = db.transaction().await?;
+    let mut applied = 0;
+    for block in blocks {
+        if apply_block(&tx, block).await? {
+            applied += 1;
+        }
+    }
+    tx.commit().await?;
+    Ok(applied)
+}
+
+async fn apply_block(db: &impl GenericClient, block: &Block) -> Result<bool, Error> {
+    let index = block.index as i64;
+    let nft_id = block.nft_id as i64;
+    let timestamp = block.timestamp as i64;
+    let from = block.from.map(|p| p.to_text());
+    let to = block.to.map(|p| p.to_text());
+    let caller = block.caller.to_text();
+
+    let inserted = db
+        .execute(
+            "INSERT INTO nft_blocks
+                (block_index, kind, nft_id, serial_number, from_owner, to_owner, caller, timestamp_ns, memo)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+             ON CONFLICT (block_index) DO NOTHING",
+            &[
+                &index,
+                &kind_name(block.kind),
+                &nft_id,
+                &block.serial_number,
+                &from,
+                &to,
+                &caller,
+                &timestamp,
+                &block.memo,
+            ],
+        )
+        .await?;
+    if inserted == 0 {
+        return Ok(false);
+    }
+
+    match block.kind {
+        TxKind::Mint => {
+            db.execute(
+                "INSERT INTO nft_tokens (nft_id, serial_number, owner, minted_by, minted_at, last_block)
+                 VALUES ($1, $2, $3, $4, $5, $6)
+                 ON CONFLICT (nft_id) DO NOTHING",
+                &[&nft_id, &block.serial_number, &to, &caller, &timestamp, &index],
+            )
+            .await?;
+        }
+        TxKind::Transfer => {
+            db.execute(
+                "UPDATE nft_tokens SET owner = $2, last_block = $3 WHERE nft_id = $1",
+                &[&nft_id, &to, &index],
+            )
+            .await?;
+            let order_id = block.memo.as_deref().and_then(|memo| memo.strip_prefix(SALE_MEMO));
+            db.execute(
+                "INSERT INTO nft_transfers (block_index, nft_id, from_owner, to_owner, timestamp_ns, memo, order_id)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                &[&index, &nft_id, &from, &to, &timestamp, &block.memo, &order_id],
+            )
+            .await?;
+            if let Some(order_id) = order_id {
+                db::notify_order(db, order_id).await?;
+            }
+        }
+        TxKind::Burn => {
+            db.execute(
+                "UPDATE nft_tokens SET owner = NULL, burned_at = $2, last_block = $3 WHERE nft_id = $1",
+                &[&nft_id, &timestamp, &index],
+            )
+            .await?;
+        }
+        TxKind::MetadataUpdate => {
+            if let Some((action, reason)) = block.memo.as_deref().and_then(revocation) {
+                db.execute(
+                    "UPDATE nft_tokens SET revoked = $2, last_block = $3 WHERE nft_id = $1",
+                    &[&nft_id, &(action == "revoked"), &index],
+                )
+                .await?;
+                db.execute(
+                    "INSERT INTO nft_revocations (block_index, nft_id, action, reason, moderator, timestamp_ns)
+                     VALUES ($1, $2, $3, $4, $5, $6)",
+                    &[&index, &nft_id, &action, &reason, &caller, &timestamp],
+                )
+                .await?;
+            }
+        }
+        // ICRC-37 approvals are kept in `nft_blocks` only.
+        TxKind::Approve | TxKind::Revoke => {}
+    }
+    Ok(true)
+}
+
+/// The action and reason of a moderation block's memo, if it is one.
+fn revocation(memo: &str) -> Option<(&'static str, Option<&str>)> {
+    if let Some(reason) = memo.strip_prefix(REVOKED_MEMO) {
+        Some(("revoked", Some(reason)))
+    } else if memo == RESTORED_MEMO {
+        Some(("restored", None))
+    } else {
+        None
+    }
+}
+
+fn kind_name(kind: TxKind) -> &'static str {
+    match kind {
+        TxKind::Mint => "mint",
+        TxKind::Transfer => "transfer",
+        TxKind::Burn => "burn",
+        TxKind::MetadataUpdate => "metadata_update",
+        TxKind::Approve => "approve",
+        TxKind::Revoke => "revoke_approval",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_moderation_memos() {
+        assert_eq!(revocation("verification revoked: Counterfeit"), Some(("revoked", Some("Counterfeit"))));
+        assert_eq!(revocation("verification restored"), Some(("restored", None)));
+        assert_eq!(revocation("endorsed by auditor"), None);
+    }
+}
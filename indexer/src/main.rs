@@ -0,0 +1,90 @@
+//! `proofcart-indexer`: mirrors ProofCart escrows and product NFTs into
+//! Postgres so the marketplace backend, catalog and analytics can query
+//! orders, disputes, ownership and revocations without calling the chains.
+//!
+//! ```text
+//! DATABASE_URL=postgres://proofcart@localhost/proofcart \
+//! SOLANA_PROGRAM_ID=<PROGRAM_ID> \
+//! ICP_CANISTER_ID=<CANISTER_ID> \
+//! proofcart-indexer
+//! ```
+
+mod config;
+mod db;
+mod error;
+mod icp;
+mod metrics;
+mod solana;
+
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use proofcart_icp_client::{identity, NftClient};
+use solana_client::nonblocking::rpc_client::RpcClient;
+
+use config::Config;
+use error::Error;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Each source gets its own connection, since a connection runs one
+/// database transaction at a time. Returns when any task fails.
+async fn run() -> Result<(), Error> {
+    let config = Config::from_env()?;
+    let metrics = Arc::new(metrics::Metrics::default());
+    let mut db = db::connect(&config.database_url).await?;
+    db::migrate(&mut db).await?;
+
+    let escrows = async {
+        match config.program_id {
+            Some(program_id) => solana::run(&config, program_id, &mut db, metrics.clone()).await,
+            None => std::future::pending().await,
+        }
+    };
+    let nfts = async {
+        match config.nft_canister_id {
+            Some(canister_id) => {
+                let mut db = db::connect(&config.database_url).await?;
+                icp::run(&config, canister_id, &mut db, metrics.clone()).await
+            }
+            None => std::future::pending().await,
+        }
+    };
+    let health = Arc::new(metrics::Health {
+        metrics: metrics.clone(),
+        db: db::connect(&config.database_url).await?,
+        solana: config
+            .program_id
+            .map(|_| RpcClient::new_with_commitment(config.solana_rpc_url.clone(), config.commitment)),
+        icp: match config.nft_canister_id {
+            Some(canister_id) => Some(NftClient::connect(&config.ic_url, identity::anonymous(), canister_id).await?),
+            None => None,
+        },
+        max_solana_lag_slots: config.max_solana_lag_slots,
+        max_icp_lag_blocks: config.max_icp_lag_blocks,
+    });
+    let rendered = metrics.clone();
+    let telemetry = async {
+        proofcart_telemetry::serve(
+            config.metrics_addr,
+            move || rendered.render(),
+            move || {
+                let health = health.clone();
+                async move { health.report().await }
+            },
+        )
+        .await
+        .map_err(Error::from)
+    };
+    tokio::try_join!(escrows, nfts, telemetry)?;
+    Ok(())
+}
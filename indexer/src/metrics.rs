@@ -0,0 +1,152 @@
+//! Lag gauges and throughput counters, served in the Prometheus text format
+//! on `GET /metrics`, and dependency checks on `GET /healthz`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use proofcart_icp_client::NftClient;
+use proofcart_telemetry::health::{self, Check, Report};
+use proofcart_telemetry::{Exposition, Family};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use tokio_postgres::Client;
+
+/// Updated by the sources after each pass; zero until the first one.
+pub struct Metrics {
+    /// Slot of the last escrow transaction applied.
+    pub solana_indexed_slot: AtomicU64,
+    /// Slot of the cluster at the indexer's commitment.
+    pub solana_tip_slot: AtomicU64,
+    /// Blocks of the canister's log applied so far.
+    pub icp_indexed_blocks: AtomicU64,
+    pub icp_log_length: AtomicU64,
+    /// Canister timestamp of the last block applied, in nanoseconds.
+    pub icp_last_block_ns: AtomicU64,
+    /// Escrow instructions and log blocks written, by source.
+    pub events: Family,
+    /// Failed passes, by source; each is retried.
+    pub rpc_errors: Family,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            solana_indexed_slot: AtomicU64::default(),
+            solana_tip_slot: AtomicU64::default(),
+            icp_indexed_blocks: AtomicU64::default(),
+            icp_log_length: AtomicU64::default(),
+            icp_last_block_ns: AtomicU64::default(),
+            events: Family::new(&["source"]),
+            rpc_errors: Family::new(&["source"]),
+        }
+    }
+}
+
+fn get(gauge: &AtomicU64) -> u64 {
+    gauge.load(Ordering::Relaxed)
+}
+
+impl Metrics {
+    pub fn solana_lag(&self) -> u64 {
+        get(&self.solana_tip_slot).saturating_sub(get(&self.solana_indexed_slot))
+    }
+
+    pub fn icp_lag(&self) -> u64 {
+        get(&self.icp_log_length).saturating_sub(get(&self.icp_indexed_blocks))
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = Exposition::new();
+        out.gauge(
+            "proofcart_indexer_solana_indexed_slot",
+            "Slot of the last escrow transaction written.",
+            get(&self.solana_indexed_slot),
+        )
+        .gauge(
+            "proofcart_indexer_solana_lag_slots",
+            "Slots between the cluster tip and the last escrow transaction written.",
+            self.solana_lag(),
+        )
+        .gauge(
+            "proofcart_indexer_icp_indexed_blocks",
+            "Transaction log blocks written.",
+            get(&self.icp_indexed_blocks),
+        )
+        .gauge(
+            "proofcart_indexer_icp_lag_blocks",
+            "Transaction log blocks not yet written.",
+            self.icp_lag(),
+        )
+        .gauge(
+            "proofcart_indexer_icp_last_block_timestamp_seconds",
+            "Canister time of the last block written.",
+            get(&self.icp_last_block_ns) / 1_000_000_000,
+        )
+        .family(
+            "proofcart_indexer_events_total",
+            "Escrow instructions (solana) and log blocks (icp) written.",
+            &self.events,
+        )
+        .family(
+            "proofcart_indexer_rpc_errors_total",
+            "Failed passes against the chain, each retried.",
+            &self.rpc_errors,
+        );
+        out.finish()
+    }
+}
+
+/// What `/healthz` checks: each configured chain, the database, and how far
+/// behind each source is.
+pub struct Health {
+    pub metrics: Arc<Metrics>,
+    pub db: Client,
+    pub solana: Option<RpcClient>,
+    pub icp: Option<NftClient>,
+    pub max_solana_lag_slots: u64,
+    pub max_icp_lag_blocks: u64,
+}
+
+impl Health {
+    pub async fn report(&self) -> Report {
+        let mut checks = vec![health::postgres(&self.db).await];
+        if let Some(rpc) = &self.solana {
+            checks.push(health::solana(rpc).await);
+            checks.push(Check::lag("solana_lag", self.metrics.solana_lag(), self.max_solana_lag_slots, "slots"));
+        }
+        if let Some(nft) = &self.icp {
+            checks.push(health::icp(nft.agent()).await);
+            checks.push(Check::lag("icp_lag", self.metrics.icp_lag(), self.max_icp_lag_blocks, "blocks"));
+        }
+        Report::new(checks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_lag() {
+        let metrics = Metrics::default();
+        metrics.icp_log_length.store(120, Ordering::Relaxed);
+        metrics.icp_indexed_blocks.store(100, Ordering::Relaxed);
+        metrics.solana_indexed_slot.store(50, Ordering::Relaxed);
+        metrics.solana_tip_slot.store(40, Ordering::Relaxed);
+
+        let text = metrics.render();
+        assert!(text.contains("\nproofcart_indexer_icp_lag_blocks 20\n"));
+        // A tip read before the last write never reports negative lag.
+        assert!(text.contains("\nproofcart_indexer_solana_lag_slots 0\n"));
+    }
+
+    #[test]
+    fn counts_events_by_source() {
+        let metrics = Metrics::default();
+        metrics.events.add(&["solana"], 3);
+        metrics.rpc_errors.inc(&["icp"]);
+
+        let text = metrics.render();
+        assert!(text.contains("\nproofcart_indexer_events_total{source=\"solana\"} 3\n"));
+        assert!(text.contains("\nproofcart_indexer_rpc_errors_total{source=\"icp\"} 1\n"));
+    }
+}
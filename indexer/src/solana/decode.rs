@@ -0,0 +1,153 @@
+//! Escrow instructions found in a confirmed transaction.
+
+use proofcart_solana_client::{instructions, EscrowInstruction};
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::pubkey::Pubkey;
+
+/// One escrow instruction of a transaction, with the accounts it names.
+/// Every program instruction lists the escrow PDA first and its signer (the
+/// buyer, or the admin for resolutions) second; `create_escrow` names the
+/// seller third, and `create_marketplace_escrow` the marketplace sixth. The
+/// marketplace instructions list the marketplace account where the others
+/// list the escrow.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EscrowEvent {
+    /// Position among the transaction's top-level instructions.
+    pub index: u16,
+    pub escrow: Pubkey,
+    pub signer: Pubkey,
+    pub instruction: EscrowInstruction,
+    pub seller: Option<Pubkey>,
+    /// The partner marketplace a created escrow belongs to.
+    pub marketplace: Option<Pubkey>,
+}
+
+/// Every top-level escrow instruction of `message`, in order. Instructions
+/// invoked through another program (CPI) and accounts loaded from address
+/// lookup tables are not seen; no ProofCart client sends either.
+pub fn escrow_events(program_id: &Pubkey, message: &VersionedMessage) -> Vec<EscrowEvent> {
+    let keys = message.static_account_keys();
+    let key = |index: u8| keys.get(index as usize).copied();
+    message
+        .instructions()
+        .iter()
+        .enumerate()
+        .filter(|(_, ix)| key(ix.program_id_index).as_ref() == Some(program_id))
+        .filter_map(|(index, ix)| {
+            let instruction = match instructions::decode(&ix.data) {
+                Ok(instruction) => instruction,
+                Err(e) => {
+                    eprintln!("Skipping escrow instruction {}: {}", index, e);
+                    return None;
+                }
+            };
+            let seller = match instruction {
+                EscrowInstruction::CreateEscrow { .. } => Some(key(*ix.accounts.get(2)?)?),
+                _ => None,
+            };
+            // `create_marketplace_escrow` decodes as `CreateEscrow`.
+            let marketplace = if ix.data.starts_with(&instructions::discriminator("create_marketplace_escrow")) {
+                Some(key(*ix.accounts.get(5)?)?)
+            } else {
+                None
+            };
+            Some(EscrowEvent {
+                index: index as u16,
+                escrow: key(*ix.accounts.first()?)?,
+                signer: key(*ix.accounts.get(1)?)?,
+                instruction,
+                seller,
+                marketplace,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proofcart_solana_client::{pda, MarketplaceSettings, Resolution};
+    use solana_sdk::message::Message;
+    use solana_sdk::system_instruction;
+
+    #[test]
+    fn finds_escrow_instructions_among_others() {
+        let program_id = Pubkey::new_unique();
+        let buyer = Pubkey::new_unique();
+        let seller = Pubkey::new_unique();
+        let (escrow, _) = pda::escrow_address(&program_id, "ORD-7").unwrap();
+        let message = Message::new(
+            &[
+                system_instruction::transfer(&buyer, &seller, 1),
+                instructions::create_escrow(&program_id, &buyer, &seller, "ORD-7", 500).unwrap(),
+                instructions::lock_dispute(&program_id, &buyer, "ORD-7").unwrap(),
+            ],
+            Some(&buyer),
+        );
+
+        let events = escrow_events(&program_id, &VersionedMessage::Legacy(message));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].index, 1);
+        assert_eq!(events[0].escrow, escrow);
+        assert_eq!(events[0].signer, buyer);
+        assert_eq!(events[0].seller, Some(seller));
+        assert!(matches!(
+            &events[0].instruction,
+            EscrowInstruction::CreateEscrow { order_id, amount: 500, .. } if order_id == "ORD-7"
+        ));
+        assert_eq!(events[1].index, 2);
+        assert_eq!(events[1].instruction, EscrowInstruction::LockDispute);
+        assert_eq!(events[1].seller, None);
+    }
+
+    #[test]
+    fn reads_the_marketplace_of_created_escrows() {
+        let program_id = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+        let buyer = Pubkey::new_unique();
+        let settings = MarketplaceSettings {
+            admin,
+            fee_bps: 250,
+            fee_recipient: Pubkey::new_unique(),
+            mints: Vec::new(),
+        };
+        let (marketplace, _) = pda::marketplace_address(&program_id, "acme").unwrap();
+        let create_marketplace =
+            instructions::create_marketplace(&program_id, &admin, "acme", &settings).unwrap();
+        let message = Message::new(&[create_marketplace], Some(&admin));
+        let events = escrow_events(&program_id, &VersionedMessage::Legacy(message));
+        assert_eq!(events[0].escrow, marketplace);
+        assert_eq!(events[0].marketplace, None);
+        assert_eq!(
+            events[0].instruction,
+            EscrowInstruction::CreateMarketplace { marketplace_id: "acme".to_string(), settings }
+        );
+
+        let seller = Pubkey::new_unique();
+        let create_escrow =
+            instructions::create_escrow_in(&program_id, Some(&marketplace), &buyer, &seller, "ORD-9", 1).unwrap();
+        let message = Message::new(&[create_escrow], Some(&buyer));
+        let events = escrow_events(&program_id, &VersionedMessage::Legacy(message));
+        assert_eq!(events[0].marketplace, Some(marketplace));
+    }
+
+    #[test]
+    fn reads_resolution_signer() {
+        let program_id = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+        let resolve = instructions::resolve(
+            &program_id,
+            &admin,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            "ORD-8",
+            Resolution::Refund,
+        )
+        .unwrap();
+        let message = Message::new(&[resolve], Some(&admin));
+
+        let events = escrow_events(&program_id, &VersionedMessage::Legacy(message));
+        assert_eq!(events[0].signer, admin);
+        assert_eq!(events[0].instruction, EscrowInstruction::Resolve(Resolution::Refund));
+    }
+}
@@ -0,0 +1,219 @@
+//! Indexer for the Solana escrow program.
+//!
+//! New transactions are always found through `getSignaturesForAddress`,
+//! starting after the stored cursor and applied oldest first. The log
+//! subscription and a poll timer only decide when to look, so a missed
+//! notification or a dropped websocket delays rows but never loses them.
+
+mod decode;
+mod store;
+
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use proofcart_solana_client::Escrow;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::{RpcTransactionConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter};
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::UiTransactionEncoding;
+use tokio_postgres::Client;
+
+use crate::metrics::Metrics;
+use crate::{db, Config, Error};
+use store::IndexedTransaction;
+
+/// Cursor name in `indexer_cursors`.
+const SOURCE: &str = "solana-escrow";
+
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Index until a database error. RPC and websocket failures are retried.
+pub async fn run(config: &Config, program_id: Pubkey, db: &mut Client, metrics: Arc<Metrics>) -> Result<(), Error> {
+    let indexer = Indexer {
+        rpc: RpcClient::new_with_commitment(config.solana_rpc_url.clone(), config.commitment),
+        ws_url: config.solana_ws_url.clone(),
+        program_id,
+        poll_interval: config.poll_interval,
+        commitment: config.commitment,
+        metrics,
+    };
+    loop {
+        match indexer.follow(db).await {
+            Ok(()) => eprintln!("Log subscription closed; reconnecting"),
+            Err(Error::Db(e)) => return Err(Error::Db(e)),
+            Err(e) => {
+                indexer.metrics.rpc_errors.inc(&["solana"]);
+                eprintln!("{}; retrying in {}s", e, RETRY_DELAY.as_secs());
+            }
+        }
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+}
+
+struct Indexer {
+    rpc: RpcClient,
+    ws_url: String,
+    program_id: Pubkey,
+    poll_interval: Duration,
+    commitment: CommitmentConfig,
+    metrics: Arc<Metrics>,
+}
+
+impl Indexer {
+    /// Subscribe to the program's logs and catch up on every notification
+    /// and poll tick. Returns when the subscription ends.
+    async fn follow(&self, db: &mut Client) -> Result<(), Error> {
+        let pubsub = PubsubClient::new(&self.ws_url).await?;
+        let (mut logs, _unsubscribe) = pubsub
+            .logs_subscribe(
+                RpcTransactionLogsFilter::Mentions(vec![self.program_id.to_string()]),
+                RpcTransactionLogsConfig {
+                    commitment: Some(self.commitment),
+                },
+            )
+            .await?;
+        // Subscribed first, so nothing lands between the backfill and the
+        // first notification.
+        self.catch_up(db).await?;
+
+        let mut poll = tokio::time::interval(self.poll_interval);
+        loop {
+            tokio::select! {
+                notification = logs.next() => {
+                    if notification.is_none() {
+                        return Ok(());
+                    }
+                }
+                _ = poll.tick() => {}
+            }
+            self.catch_up(db).await?;
+        }
+    }
+
+    /// Apply every program transaction after the cursor, oldest first.
+    async fn catch_up(&self, db: &mut Client) -> Result<(), Error> {
+        let until = match db::cursor(db, SOURCE).await? {
+            Some((signature, slot)) => {
+                self.metrics.solana_indexed_slot.store(slot as u64, Ordering::Relaxed);
+                Some(parse_signature(&signature)?)
+            }
+            None => None,
+        };
+
+        // The RPC pages newest first; collect back to the cursor, then replay.
+        let mut pending: Vec<RpcConfirmedTransactionStatusWithSignature> = Vec::new();
+        loop {
+            let before = match pending.last() {
+                Some(status) => Some(parse_signature(&status.signature)?),
+                None => None,
+            };
+            let page = self
+                .rpc
+                .get_signatures_for_address_with_config(
+                    &self.program_id,
+                    GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        until,
+                        limit: None,
+                        commitment: Some(self.commitment),
+                    },
+                )
+                .await?;
+            if page.is_empty() {
+                break;
+            }
+            pending.extend(page);
+        }
+
+        for status in pending.iter().rev() {
+            self.process(db, status).await?;
+            self.metrics.solana_indexed_slot.store(status.slot, Ordering::Relaxed);
+        }
+        let tip = self.rpc.get_slot_with_commitment(self.commitment).await?;
+        self.metrics.solana_tip_slot.store(tip, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn process(&self, db: &mut Client, status: &RpcConfirmedTransactionStatusWithSignature) -> Result<(), Error> {
+        let slot = status.slot as i64;
+        let Some(tx) = self.fetch(status).await? else {
+            return db::set_cursor(&*db, SOURCE, &status.signature, slot).await;
+        };
+        if tx.events.is_empty() {
+            return db::set_cursor(&*db, SOURCE, &status.signature, slot).await;
+        }
+
+        let mut seeds = Vec::new();
+        for address in store::unknown_escrows(db, &tx).await? {
+            let account = self
+                .rpc
+                .get_account_with_commitment(&address, self.commitment)
+                .await?
+                .value;
+            match account {
+                Some(account) => seeds.push((address, Escrow::try_from_account_data(&account.data)?)),
+                None => eprintln!("Escrow {} named by {} no longer exists", address, tx.signature),
+            }
+        }
+
+        if store::apply(db, SOURCE, &tx, &seeds).await? {
+            self.metrics.events.add(&["solana"], tx.events.len() as u64);
+            println!("Indexed {} ({} escrow instructions)", tx.signature, tx.events.len());
+        }
+        Ok(())
+    }
+
+    /// The escrow instructions of a successful transaction; None for a
+    /// failed one, which changed nothing.
+    async fn fetch(&self, status: &RpcConfirmedTransactionStatusWithSignature) -> Result<Option<IndexedTransaction>, Error> {
+        if status.err.is_some() {
+            return Ok(None);
+        }
+        let signature = parse_signature(&status.signature)?;
+        let confirmed = self
+            .rpc
+            .get_transaction_with_config(
+                &signature,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    commitment: Some(self.commitment),
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .await?;
+        let Some(meta) = confirmed.transaction.meta else {
+            return Ok(None);
+        };
+        if meta.err.is_some() {
+            return Ok(None);
+        }
+        let Some(transaction) = confirmed.transaction.transaction.decode() else {
+            eprintln!("Skipping {}: transaction does not decode", status.signature);
+            return Ok(None);
+        };
+        let message = &transaction.message;
+        Ok(Some(IndexedTransaction {
+            signature: status.signature.clone(),
+            slot: confirmed.slot,
+            block_time: confirmed.block_time,
+            payer: message.static_account_keys()[0],
+            fee: meta.fee,
+            events: decode::escrow_events(&self.program_id, message),
+        }))
+    }
+}
+
+fn parse_signature(signature: &str) -> Result<Signature, Error> {
+    Signature::from_str(signature).map_err(|e| {
+        ClientError::from(ClientErrorKind::Custom(format!("Invalid signature {}: {}", signature, e))).into()
+    })
+}
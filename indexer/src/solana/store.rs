@@ -0,0 +1,349 @@
+//! Writes decoded escrow transactions to Postgres.
+//!
+//! Each transaction is applied in one database transaction together with the
+//! cursor. Its `escrow_events` rows are keyed by (signature, instruction), so
+//! a transaction seen twice, from a replayed backfill or a restart between
+//! commit and cursor read, is recognized and skipped.
+//!
+//! Order ids are unique per marketplace, so orders are keyed by their
+//! marketplace's address as well, '' for the platform's own.
+
+use std::collections::HashMap;
+
+use proofcart_solana_client::{Escrow, EscrowInstruction, EscrowStatus, MarketplaceSettings, Resolution};
+use solana_sdk::pubkey::Pubkey;
+use tokio_postgres::{Client, GenericClient};
+
+use super::decode::EscrowEvent;
+use crate::{db, Error};
+
+/// An indexed order's key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Order {
+    marketplace: String,
+    id: String,
+}
+
+/// A successful transaction that invoked the escrow program.
+pub struct IndexedTransaction {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub payer: Pubkey,
+    pub fee: u64,
+    pub events: Vec<EscrowEvent>,
+}
+
+/// Escrows `tx` acts on that it does not create and that are not indexed
+/// yet: escrows created before the RPC node's history starts.
+pub async fn unknown_escrows(db: &Client, tx: &IndexedTransaction) -> Result<Vec<Pubkey>, Error> {
+    let mut unknown = Vec::new();
+    for event in &tx.events {
+        if !acts_on_escrow(&event.instruction)
+            || matches!(event.instruction, EscrowInstruction::CreateEscrow { .. })
+            || tx.events.iter().any(|e| created_here(e, &event.escrow))
+            || unknown.contains(&event.escrow)
+        {
+            continue;
+        }
+        if lookup_order(db, &event.escrow).await?.is_none() {
+            unknown.push(event.escrow);
+        }
+    }
+    Ok(unknown)
+}
+
+/// Apply `tx` and advance `source`'s cursor to it. `seeds` holds the current
+/// state of each escrow returned by [`unknown_escrows`]; their orders are
+/// inserted from it before the transaction's own changes. Returns false if
+/// `tx` had already been applied.
+pub async fn apply(
+    db: &mut Client,
+    source: &str,
+    tx: &IndexedTransaction,
+    seeds: &[(Pubkey, Escrow)],
+) -> Result<bool, Error> {
+    let dbtx = db.transaction().await?;
+    let seen = dbtx
+        .query_opt("SELECT 1 FROM escrow_events WHERE signature = $1 LIMIT 1", &[&tx.signature])
+        .await?
+        .is_some();
+    if seen {
+        return Ok(false);
+    }
+
+    let slot = tx.slot as i64;
+    for (address, escrow) in seeds {
+        seed_order(&dbtx, address, escrow, slot, &tx.signature).await?;
+    }
+
+    let mut orders: HashMap<Pubkey, Order> = HashMap::new();
+    for event in &tx.events {
+        match &event.instruction {
+            EscrowInstruction::CreateMarketplace { marketplace_id, settings } => {
+                upsert_marketplace(&dbtx, tx, &event.escrow, Some(marketplace_id.as_str()), settings).await?;
+                continue;
+            }
+            EscrowInstruction::UpdateMarketplace(settings) => {
+                upsert_marketplace(&dbtx, tx, &event.escrow, None, settings).await?;
+                continue;
+            }
+            EscrowInstruction::CreateEscrow { order_id, amount, .. } => {
+                let order = Order {
+                    marketplace: event.marketplace.map(|m| m.to_string()).unwrap_or_default(),
+                    id: order_id.clone(),
+                };
+                dbtx.execute(
+                    "INSERT INTO escrow_orders
+                        (marketplace, order_id, escrow_address, buyer, seller, amount_lamports, status, created_at,
+                         last_slot, last_signature)
+                     VALUES ($1, $2, $3, $4, $5, $6, 'created', $7, $8, $9)
+                     ON CONFLICT (marketplace, order_id) DO NOTHING",
+                    &[
+                        &order.marketplace,
+                        &order.id,
+                        &event.escrow.to_string(),
+                        &event.signer.to_string(),
+                        &event.seller.unwrap_or_default().to_string(),
+                        &(*amount as i64),
+                        &tx.block_time,
+                        &slot,
+                        &tx.signature,
+                    ],
+                )
+                .await?;
+                orders.insert(event.escrow, order);
+            }
+            _ => {}
+        }
+        let order = match orders.get(&event.escrow) {
+            Some(order) => order.clone(),
+            None => match lookup_order(&dbtx, &event.escrow).await? {
+                Some(order) => order,
+                None => {
+                    eprintln!(
+                        "Skipping instruction {} of {}: escrow {} is not indexed",
+                        event.index, tx.signature, event.escrow
+                    );
+                    continue;
+                }
+            },
+        };
+        orders.insert(event.escrow, order.clone());
+        apply_event(&dbtx, tx, event, &order).await?;
+    }
+
+    if let Some(order) = tx.events.first().and_then(|e| orders.get(&e.escrow)) {
+        dbtx.execute(
+            "INSERT INTO escrow_fees (signature, marketplace, order_id, payer, fee_lamports, slot)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             ON CONFLICT (signature) DO NOTHING",
+            &[&tx.signature, &order.marketplace, &order.id, &tx.payer.to_string(), &(tx.fee as i64), &slot],
+        )
+        .await?;
+    }
+    // The channel carries platform order ids; partner marketplaces' orders
+    // have no live view.
+    for order in orders.values().filter(|order| order.marketplace.is_empty()) {
+        db::notify_order(&dbtx, &order.id).await?;
+    }
+    db::set_cursor(&dbtx, source, &tx.signature, slot).await?;
+    dbtx.commit().await?;
+    Ok(true)
+}
+
+async fn apply_event(
+    db: &impl GenericClient,
+    tx: &IndexedTransaction,
+    event: &EscrowEvent,
+    order: &Order,
+) -> Result<(), Error> {
+    let slot = tx.slot as i64;
+    let signer = event.signer.to_string();
+    let kind = match &event.instruction {
+        EscrowInstruction::CreateEscrow { .. } => "create_escrow",
+        EscrowInstruction::CreateMarketplace { .. } | EscrowInstruction::UpdateMarketplace(_) => {
+            unreachable!("Marketplace instructions are applied by `apply`")
+        }
+        EscrowInstruction::ConfirmDelivery => {
+            update_order(db, order, "released", "released_at", tx).await?;
+            "confirm_delivery"
+        }
+        EscrowInstruction::LockDispute => {
+            update_order(db, order, "locked", "locked_at", tx).await?;
+            db.execute(
+                "INSERT INTO escrow_disputes
+                    (marketplace, order_id, opened_by, opened_at, opened_slot, opened_signature)
+                 VALUES ($1, $2, $3, $4, $5, $6)
+                 ON CONFLICT (marketplace, order_id) DO NOTHING",
+                &[&order.marketplace, &order.id, &signer, &tx.block_time, &slot, &tx.signature],
+            )
+            .await?;
+            "lock_dispute"
+        }
+        EscrowInstruction::Resolve(resolution) => {
+            let (status, outcome, kind) = match resolution {
+                Resolution::Refund => ("refunded", "refund", "resolve_refund"),
+                Resolution::Release => ("released", "release", "resolve_release"),
+            };
+            update_order(db, order, status, "resolved_at", tx).await?;
+            db.execute(
+                "INSERT INTO escrow_resolutions
+                    (marketplace, order_id, outcome, resolved_by, resolved_at, slot, signature)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7)
+                 ON CONFLICT (marketplace, order_id) DO NOTHING",
+                &[&order.marketplace, &order.id, &outcome, &signer, &tx.block_time, &slot, &tx.signature],
+            )
+            .await?;
+            kind
+        }
+    };
+    db.execute(
+        "INSERT INTO escrow_events (signature, instruction, marketplace, order_id, kind, signer, slot, block_time)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        &[
+            &tx.signature,
+            &(event.index as i16),
+            &order.marketplace,
+            &order.id,
+            &kind,
+            &signer,
+            &slot,
+            &tx.block_time,
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Set an order's status and the timestamp column for the transition.
+/// `timestamp_column` is one of a fixed set of names, never user input.
+async fn update_order(
+    db: &impl GenericClient,
+    order: &Order,
+    status: &str,
+    timestamp_column: &str,
+    tx: &IndexedTransaction,
+) -> Result<(), Error> {
+    let sql = format!(
+        "UPDATE escrow_orders SET status = $3, {} = $4, last_slot = $5, last_signature = $6
+         WHERE marketplace = $1 AND order_id = $2",
+        timestamp_column
+    );
+    db.execute(
+        sql.as_str(),
+        &[&order.marketplace, &order.id, &status, &tx.block_time, &(tx.slot as i64), &tx.signature],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Insert an order from the escrow account's current state.
+async fn seed_order(
+    db: &impl GenericClient,
+    address: &Pubkey,
+    escrow: &Escrow,
+    slot: i64,
+    signature: &str,
+) -> Result<(), Error> {
+    let status = match escrow.status {
+        EscrowStatus::Created => "created",
+        EscrowStatus::Locked => "locked",
+        EscrowStatus::Released => "released",
+        EscrowStatus::Refunded => "refunded",
+    };
+    db.execute(
+        "INSERT INTO escrow_orders
+            (marketplace, order_id, escrow_address, buyer, seller, amount_lamports, status,
+             created_at, locked_at, released_at, resolved_at, last_slot, last_signature)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+         ON CONFLICT (marketplace, order_id) DO NOTHING",
+        &[
+            &escrow.marketplace.map(|m| m.to_string()).unwrap_or_default(),
+            &escrow.order_id,
+            &address.to_string(),
+            &escrow.buyer.to_string(),
+            &escrow.seller.to_string(),
+            &(escrow.amount as i64),
+            &status,
+            &Some(escrow.created_at),
+            &escrow.locked_at,
+            &escrow.released_at,
+            &escrow.resolved_at,
+            &slot,
+            &signature,
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+async fn lookup_order(db: &impl GenericClient, escrow: &Pubkey) -> Result<Option<Order>, Error> {
+    let row = db
+        .query_opt(
+            "SELECT marketplace, order_id FROM escrow_orders WHERE escrow_address = $1",
+            &[&escrow.to_string()],
+        )
+        .await?;
+    Ok(row.map(|row| Order { marketplace: row.get(0), id: row.get(1) }))
+}
+
+/// Record a marketplace's settings. Its id is only known from
+/// `create_marketplace`, so an update of a marketplace created before the
+/// indexer's history starts is skipped.
+async fn upsert_marketplace(
+    db: &impl GenericClient,
+    tx: &IndexedTransaction,
+    address: &Pubkey,
+    marketplace_id: Option<&str>,
+    settings: &MarketplaceSettings,
+) -> Result<(), Error> {
+    let address = address.to_string();
+    let admin = settings.admin.to_string();
+    let fee_bps = settings.fee_bps as i32;
+    let fee_recipient = settings.fee_recipient.to_string();
+    let mints: Vec<String> = settings.mints.iter().map(|mint| mint.to_string()).collect();
+    let slot = tx.slot as i64;
+    let Some(marketplace_id) = marketplace_id else {
+        db.execute(
+            "UPDATE escrow_marketplaces
+             SET admin = $2, fee_bps = $3, fee_recipient = $4, mints = $5, updated_at = $6, last_slot = $7,
+                 last_signature = $8
+             WHERE address = $1 AND last_slot <= $7",
+            &[&address, &admin, &fee_bps, &fee_recipient, &mints, &tx.block_time, &slot, &tx.signature],
+        )
+        .await?;
+        return Ok(());
+    };
+    db.execute(
+        "INSERT INTO escrow_marketplaces
+            (address, marketplace_id, admin, fee_bps, fee_recipient, mints, created_at, last_slot, last_signature)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+         ON CONFLICT (address) DO NOTHING",
+        &[
+            &address,
+            &marketplace_id,
+            &admin,
+            &fee_bps,
+            &fee_recipient,
+            &mints,
+            &tx.block_time,
+            &slot,
+            &tx.signature,
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+/// False for the marketplace instructions, which name no escrow.
+fn acts_on_escrow(instruction: &EscrowInstruction) -> bool {
+    !matches!(
+        instruction,
+        EscrowInstruction::CreateMarketplace { .. } | EscrowInstruction::UpdateMarketplace(_)
+    )
+}
+
+fn created_here(event: &EscrowEvent, escrow: &Pubkey) -> bool {
+    event.escrow == *escrow && matches!(event.instruction, EscrowInstruction::CreateEscrow { .. })
+}
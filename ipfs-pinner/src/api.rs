@@ -0,0 +1,193 @@
+//! Upload endpoints. Each upload is added to the cluster and pinned at the
+//! provider before its CID is returned, so a CID handed to a minter is
+//! already stored in two places. Certification documents can also go to
+//! Arweave, where they are stored permanently.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::{Multipart, Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::arweave::Document;
+use crate::AppState;
+
+/// Images accepted by `/images`, which product pages can display.
+const IMAGE_TYPES: &[&str] = &["image/png", "image/jpeg", "image/webp", "image/gif"];
+
+/// Certification documents accepted by `/certificates`.
+const DOCUMENT_TYPES: &[&str] = &["application/pdf", "image/png", "image/jpeg"];
+
+/// A handler failure, answered as `{"error": "<message>"}`.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    fn upstream(message: String) -> Self {
+        Self::new(StatusCode::BAD_GATEWAY, message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(serde_json::json!({"error": self.message}))).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UploadParams {
+    /// Name recorded with the pins, e.g. the serial number.
+    name: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Pinned {
+    pub cid: String,
+    /// `ipfs://<cid>`, ready for `ipfs_metadata_uri`.
+    pub uri: String,
+}
+
+#[derive(Serialize)]
+pub struct Archived {
+    /// The manifest id, ready for `arweave_tx_id`.
+    pub arweave_tx_id: String,
+    /// `ar://<id>`; each document is at `ar://<id>/<file name>`.
+    pub uri: String,
+    /// Each document's own transaction id, by file name.
+    pub files: BTreeMap<String, String>,
+}
+
+fn authorize(state: &AppState, headers: &HeaderMap) -> Result<(), ApiError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if token == Some(state.api_token.as_str()) {
+        Ok(())
+    } else {
+        Err(ApiError::new(StatusCode::UNAUTHORIZED, "Missing or wrong API token"))
+    }
+}
+
+/// Add to the cluster, then pin at the provider.
+async fn pin(state: &AppState, name: &str, bytes: Vec<u8>, mime: &str) -> Result<Pinned, ApiError> {
+    let cid = state.cluster.add(name, bytes, mime).await.map_err(ApiError::upstream)?;
+    state.provider.pin(&cid, name).await.map_err(ApiError::upstream)?;
+    Ok(Pinned { uri: format!("ipfs://{}", cid), cid })
+}
+
+/// `POST /metadata`: a product metadata JSON object.
+pub async fn upload_metadata(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<UploadParams>,
+    Json(metadata): Json<Value>,
+) -> Result<Json<Pinned>, ApiError> {
+    authorize(&state, &headers)?;
+    if !metadata.is_object() {
+        return Err(ApiError::bad_request("Metadata must be a JSON object"));
+    }
+    let name = params.name.unwrap_or_else(|| "metadata.json".to_string());
+    let bytes = serde_json::to_vec(&metadata).expect("A JSON value always serializes");
+    Ok(Json(pin(&state, &name, bytes, "application/json").await?))
+}
+
+/// `POST /images`: the raw image, with its `Content-Type`.
+pub async fn upload_image(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<UploadParams>,
+    body: Bytes,
+) -> Result<Json<Pinned>, ApiError> {
+    authorize(&state, &headers)?;
+    let mime = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|mime| IMAGE_TYPES.contains(mime))
+        .ok_or_else(|| ApiError::bad_request(format!("Content-Type must be one of {}", IMAGE_TYPES.join(", "))))?;
+    if body.is_empty() {
+        return Err(ApiError::bad_request("Image is empty"));
+    }
+    let name = params.name.unwrap_or_else(|| "image".to_string());
+    Ok(Json(pin(&state, &name, body.to_vec(), mime).await?))
+}
+
+/// `POST /certificates`: multipart, one part per document, named by its
+/// file name.
+pub async fn upload_certificates(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<UploadParams>,
+    mut multipart: Multipart,
+) -> Result<Json<Archived>, ApiError> {
+    authorize(&state, &headers)?;
+    let bundler = state
+        .bundler
+        .as_ref()
+        .ok_or_else(|| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "Arweave uploads are not configured"))?;
+
+    let mut documents: Vec<Document> = Vec::new();
+    while let Some(field) = multipart.next_field().await.map_err(|e| ApiError::bad_request(e.to_string()))? {
+        let name = field
+            .file_name()
+            .filter(|name| !name.is_empty() && !name.contains('/'))
+            .ok_or_else(|| ApiError::bad_request("Each part needs a file name without '/'"))?
+            .to_string();
+        let content_type = field
+            .content_type()
+            .filter(|mime| DOCUMENT_TYPES.contains(mime))
+            .ok_or_else(|| {
+                ApiError::bad_request(format!("{}: Content-Type must be one of {}", name, DOCUMENT_TYPES.join(", ")))
+            })?
+            .to_string();
+        let bytes = field.bytes().await.map_err(|e| ApiError::bad_request(e.to_string()))?;
+        if bytes.is_empty() {
+            return Err(ApiError::bad_request(format!("{} is empty", name)));
+        }
+        if documents.iter().any(|d| d.name == name) {
+            return Err(ApiError::bad_request(format!("{} is uploaded twice", name)));
+        }
+        documents.push(Document { name, content_type, bytes: bytes.to_vec() });
+    }
+    if documents.is_empty() {
+        return Err(ApiError::bad_request("No documents"));
+    }
+
+    let serial = params.name.unwrap_or_default();
+    let uploaded = bundler.upload(&serial, &documents).await.map_err(ApiError::upstream)?;
+    Ok(Json(Archived {
+        uri: format!("ar://{}", uploaded.manifest_id),
+        arweave_tx_id: uploaded.manifest_id,
+        files: uploaded.documents,
+    }))
+}
+
+/// `GET /pins/{cid}`: where the CID stands at each location.
+pub async fn pin_status(
+    State(state): State<Arc<AppState>>,
+    Path(cid): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    let cluster = state.cluster.status(&cid).await.map_err(ApiError::upstream)?;
+    let provider = state.provider.status(&cid).await.map_err(ApiError::upstream)?;
+    Ok(Json(serde_json::json!({
+        "cid": cid,
+        "cluster": cluster.as_str(),
+        "provider": provider.as_str(),
+    })))
+}
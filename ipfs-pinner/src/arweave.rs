@@ -0,0 +1,235 @@
+//! Permanent storage of certification documents on Arweave.
+//!
+//! Each document becomes an ANS-104 data item signed with an Ed25519 key (a
+//! Solana keypair, which bundlers such as Turbo and Irys accept), and a path
+//! manifest ties them together under one id: `https://arweave.net/<id>/<name>`
+//! serves each document. The bundler posts the items to Arweave in a bundle
+//! and its upload fees are charged to the signing key.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ed25519_dalek::{Signer, SigningKey};
+use serde_json::json;
+use sha2::{Digest, Sha256, Sha384};
+
+/// ANS-104 signature type of Ed25519 keys.
+const SIGNATURE_TYPE_ED25519: u16 = 2;
+
+const MANIFEST_CONTENT_TYPE: &str = "application/x.arweave-manifest+json";
+
+/// A signed ANS-104 data item, ready for a bundler.
+pub struct DataItem {
+    /// Arweave id: base64url of `sha256(signature)`.
+    pub id: String,
+    pub bytes: Vec<u8>,
+}
+
+/// One document of a certificate bundle.
+pub struct Document {
+    pub name: String,
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Ids of an uploaded bundle.
+pub struct Uploaded {
+    /// The manifest; store it as the token's `arweave_tx_id`.
+    pub manifest_id: String,
+    /// Each document's own id, by name.
+    pub documents: BTreeMap<String, String>,
+}
+
+/// ANS-104 deep hash of a list of byte strings.
+fn deep_hash(items: &[&[u8]]) -> [u8; 48] {
+    let blob = |data: &[u8]| -> [u8; 48] {
+        let tag = Sha384::digest(format!("blob{}", data.len()));
+        Sha384::new().chain_update(tag).chain_update(Sha384::digest(data)).finalize().into()
+    };
+    let mut acc: [u8; 48] = Sha384::digest(format!("list{}", items.len())).into();
+    for item in items {
+        acc = Sha384::new().chain_update(acc).chain_update(blob(item)).finalize().into();
+    }
+    acc
+}
+
+/// Avro zig-zag varint of a `long`.
+fn push_avro_long(out: &mut Vec<u8>, n: i64) {
+    let mut z = ((n << 1) ^ (n >> 63)) as u64;
+    loop {
+        let byte = (z & 0x7f) as u8;
+        z >>= 7;
+        if z == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Tags in ANS-104's Avro encoding: one array block of `{name, value}`
+/// byte records, then the end marker. No tags encode as no bytes.
+fn encode_tags(tags: &[(&str, &str)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    if tags.is_empty() {
+        return out;
+    }
+    push_avro_long(&mut out, tags.len() as i64);
+    for (name, value) in tags {
+        push_avro_long(&mut out, name.len() as i64);
+        out.extend_from_slice(name.as_bytes());
+        push_avro_long(&mut out, value.len() as i64);
+        out.extend_from_slice(value.as_bytes());
+    }
+    out.push(0);
+    out
+}
+
+/// Sign `data` as a data item with no target or anchor.
+pub fn data_item(key: &SigningKey, data: &[u8], tags: &[(&str, &str)]) -> DataItem {
+    let owner = key.verifying_key().to_bytes();
+    let raw_tags = encode_tags(tags);
+    let signature_type = SIGNATURE_TYPE_ED25519.to_string();
+    let message = deep_hash(&[b"dataitem", b"1", signature_type.as_bytes(), &owner, b"", b"", &raw_tags, data]);
+    let signature = key.sign(&message).to_bytes();
+
+    let mut bytes = Vec::with_capacity(2 + 64 + 32 + 2 + 16 + raw_tags.len() + data.len());
+    bytes.extend_from_slice(&SIGNATURE_TYPE_ED25519.to_le_bytes());
+    bytes.extend_from_slice(&signature);
+    bytes.extend_from_slice(&owner);
+    // No target, no anchor.
+    bytes.extend_from_slice(&[0, 0]);
+    bytes.extend_from_slice(&(tags.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&(raw_tags.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&raw_tags);
+    bytes.extend_from_slice(data);
+
+    DataItem { id: URL_SAFE_NO_PAD.encode(Sha256::digest(signature)), bytes }
+}
+
+/// Arweave path manifest mapping each name to its id; the first is the index.
+fn manifest(documents: &BTreeMap<String, String>) -> Vec<u8> {
+    let paths: serde_json::Map<_, _> = documents
+        .iter()
+        .map(|(name, id)| (name.clone(), json!({"id": id})))
+        .collect();
+    let index = documents.keys().next().map(|name| json!({"path": name}));
+    let mut manifest = json!({"manifest": "arweave/paths", "version": "0.1.0", "paths": paths});
+    if let Some(index) = index {
+        manifest["index"] = index;
+    }
+    serde_json::to_vec(&manifest).expect("A JSON value always serializes")
+}
+
+/// Load a Solana keypair file: a JSON array of the 64 secret and public key
+/// bytes, as written by `solana-keygen`.
+pub fn load_key(path: &Path) -> Result<SigningKey, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let bytes: Vec<u8> = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let bytes: [u8; 64] = bytes
+        .try_into()
+        .map_err(|_| format!("{}: not a 64-byte keypair", path.display()))?;
+    SigningKey::from_keypair_bytes(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Uploads data items to an ANS-104 bundler.
+pub struct Bundler {
+    http: reqwest::Client,
+    /// Endpoint taking one raw data item per POST, e.g.
+    /// `https://upload.ardrive.io/v1/tx`.
+    url: String,
+    key: SigningKey,
+}
+
+impl Bundler {
+    pub fn new(http: reqwest::Client, url: &str, key: SigningKey) -> Self {
+        Self { http, url: url.to_string(), key }
+    }
+
+    async fn post(&self, item: &DataItem) -> Result<(), String> {
+        self.http
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .body(item.bytes.clone())
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Bundler upload of {} failed: {}", item.id, e))?;
+        Ok(())
+    }
+
+    /// Upload `documents` and a manifest naming them. `serial` is recorded
+    /// in the tags so the bundle can be found by product.
+    pub async fn upload(&self, serial: &str, documents: &[Document]) -> Result<Uploaded, String> {
+        let mut ids = BTreeMap::new();
+        for document in documents {
+            let tags = [
+                ("Content-Type", document.content_type.as_str()),
+                ("App-Name", "ProofCart"),
+                ("Serial-Number", serial),
+            ];
+            let item = data_item(&self.key, &document.bytes, &tags);
+            self.post(&item).await?;
+            ids.insert(document.name.clone(), item.id);
+        }
+        let tags = [
+            ("Content-Type", MANIFEST_CONTENT_TYPE),
+            ("App-Name", "ProofCart"),
+            ("Serial-Number", serial),
+        ];
+        let item = data_item(&self.key, &manifest(&ids), &tags);
+        self.post(&item).await?;
+        Ok(Uploaded { manifest_id: item.id, documents: ids })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signature, Verifier};
+
+    #[test]
+    fn avro_longs_are_zigzag_varints() {
+        for (n, expected) in [(0i64, vec![0x00]), (1, vec![0x02]), (-1, vec![0x01]), (64, vec![0x80, 0x01])] {
+            let mut out = Vec::new();
+            push_avro_long(&mut out, n);
+            assert_eq!(out, expected);
+        }
+        assert!(encode_tags(&[]).is_empty());
+        assert_eq!(encode_tags(&[("a", "bc")]), vec![0x02, 0x02, b'a', 0x04, b'b', b'c', 0x00]);
+    }
+
+    #[test]
+    fn data_items_carry_a_verifiable_signature() {
+        let key = SigningKey::from_bytes(&[3; 32]);
+        let tags = [("Content-Type", "application/pdf")];
+        let item = data_item(&key, b"%PDF-1.7", &tags);
+
+        assert_eq!(&item.bytes[..2], &[2, 0]);
+        let signature: [u8; 64] = item.bytes[2..66].try_into().unwrap();
+        assert_eq!(&item.bytes[66..98], key.verifying_key().as_bytes());
+        assert_eq!(&item.bytes[98..100], &[0, 0]);
+        assert_eq!(&item.bytes[100..108], &1u64.to_le_bytes());
+        let raw_tags = encode_tags(&tags);
+        assert_eq!(&item.bytes[108..116], &(raw_tags.len() as u64).to_le_bytes());
+        assert!(item.bytes.ends_with(b"%PDF-1.7"));
+        assert_eq!(item.id, URL_SAFE_NO_PAD.encode(Sha256::digest(signature)));
+        assert_eq!(item.id.len(), 43);
+
+        let message = deep_hash(&[b"dataitem", b"1", b"2", key.verifying_key().as_bytes(), b"", b"", &raw_tags, b"%PDF-1.7"]);
+        key.verifying_key()
+            .verify(&message, &Signature::from_bytes(&signature))
+            .unwrap();
+    }
+
+    #[test]
+    fn manifest_indexes_the_first_document() {
+        let ids = BTreeMap::from([("ce.pdf".to_string(), "A".repeat(43)), ("fcc.pdf".to_string(), "B".repeat(43))]);
+        let manifest: serde_json::Value = serde_json::from_slice(&manifest(&ids)).unwrap();
+        assert_eq!(manifest["manifest"], "arweave/paths");
+        assert_eq!(manifest["index"]["path"], "ce.pdf");
+        assert_eq!(manifest["paths"]["fcc.pdf"]["id"], "B".repeat(43));
+    }
+}
@@ -0,0 +1,174 @@
+//! Pin health of every product in the registry. A sweep lists the tokens,
+//! checks each `ipfs_metadata_uri` CID at both locations, re-pins what is
+//! missing and alerts when content goes missing or comes back.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use proofcart_icp_client::types::NFTFilter;
+use proofcart_icp_client::NftClient;
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::ipfs::{cid_from_uri, Cluster, PinState, PinningService};
+
+/// A CID that is not pinned somewhere it should be.
+#[derive(Debug)]
+pub struct MissingPin {
+    pub cid: String,
+    /// Serials whose metadata URI names it.
+    pub serials: Vec<String>,
+    pub cluster: PinState,
+    pub provider: PinState,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub checked: usize,
+    /// Tokens whose URI is not on IPFS, or is blank because it is restricted.
+    pub skipped: usize,
+    pub missing: Vec<MissingPin>,
+    /// CIDs whose status could not be read this time.
+    pub errors: Vec<String>,
+}
+
+/// Where the registry's tokens are listed from.
+pub struct Registry {
+    pub nft: NftClient,
+    /// The identity is an admin: use `export_all`, which also returns
+    /// restricted metadata URIs. Otherwise `search_all`.
+    pub admin: bool,
+}
+
+/// CID -> serials naming it; plus how many tokens had no IPFS URI.
+fn cids_by_serial(nfts: &[(String, String)]) -> (BTreeMap<String, Vec<String>>, usize) {
+    let mut cids: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut skipped = 0;
+    for (serial, uri) in nfts {
+        match cid_from_uri(uri) {
+            Some(cid) => cids.entry(cid.to_string()).or_default().push(serial.clone()),
+            None => skipped += 1,
+        }
+    }
+    (cids, skipped)
+}
+
+pub async fn sweep(registry: &Registry, cluster: &Cluster, provider: &PinningService) -> Result<Report, String> {
+    let nfts = if registry.admin {
+        registry.nft.export_all().await
+    } else {
+        registry.nft.search_all(&NFTFilter::default()).await
+    }
+    .map_err(|e| format!("Listing the registry failed: {}", e))?;
+    let uris: Vec<(String, String)> = nfts
+        .into_iter()
+        .map(|nft| (nft.serial_number, nft.metadata.ipfs_metadata_uri))
+        .collect();
+    let (cids, skipped) = cids_by_serial(&uris);
+
+    let mut report = Report { skipped, ..Default::default() };
+    for (cid, serials) in cids {
+        report.checked += 1;
+        let (cluster_state, provider_state) = match (cluster.status(&cid).await, provider.status(&cid).await) {
+            (Ok(c), Ok(p)) => (c, p),
+            (Err(e), _) | (_, Err(e)) => {
+                report.errors.push(e);
+                continue;
+            }
+        };
+        // Pinning counts as healthy; it becomes missing if it fails.
+        if cluster_state == PinState::Missing {
+            if let Err(e) = cluster.pin(&cid).await {
+                report.errors.push(e);
+            }
+        }
+        if provider_state == PinState::Missing {
+            if let Err(e) = provider.pin(&cid, &serials[0]).await {
+                report.errors.push(e);
+            }
+        }
+        if cluster_state == PinState::Missing || provider_state == PinState::Missing {
+            report.missing.push(MissingPin {
+                cid,
+                serials,
+                cluster: cluster_state,
+                provider: provider_state,
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Sends alerts to a Slack-compatible webhook (`{"text": ...}`), once when a
+/// CID goes missing and once when it is pinned again.
+pub struct Alerter {
+    http: reqwest::Client,
+    webhook_url: Option<String>,
+    open: Mutex<BTreeSet<String>>,
+}
+
+impl Alerter {
+    pub fn new(http: reqwest::Client, webhook_url: Option<String>) -> Self {
+        Self { http, webhook_url, open: Mutex::new(BTreeSet::new()) }
+    }
+
+    pub async fn report(&self, report: &Report) {
+        let mut open = self.open.lock().await;
+        let missing: BTreeSet<String> = report.missing.iter().map(|pin| pin.cid.clone()).collect();
+
+        for pin in report.missing.iter().filter(|pin| !open.contains(&pin.cid)) {
+            self.send(format!(
+                "IPFS content missing: {} (cluster {}, provider {}), used by {}. Re-pin requested.",
+                pin.cid,
+                pin.cluster.as_str(),
+                pin.provider.as_str(),
+                pin.serials.join(", ")
+            ))
+            .await;
+        }
+        // A CID that could not be checked stays open until it is.
+        let unchecked = |cid: &String| report.errors.iter().any(|e| e.contains(cid.as_str()));
+        for cid in open.iter().filter(|cid| !missing.contains(*cid) && !unchecked(cid)) {
+            self.send(format!("IPFS content pinned again: {}", cid)).await;
+        }
+        open.retain(|cid| missing.contains(cid) || unchecked(cid));
+        open.extend(missing);
+    }
+
+    async fn send(&self, text: String) {
+        eprintln!("{}", text);
+        let Some(url) = &self.webhook_url else {
+            return;
+        };
+        let sent = self
+            .http
+            .post(url)
+            .json(&json!({"text": text}))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = sent {
+            eprintln!("Alert webhook failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_serials_by_cid() {
+        let nfts = [
+            ("SN-1".to_string(), "ipfs://bafyA/metadata.json".to_string()),
+            ("SN-2".to_string(), "ipfs://bafyA".to_string()),
+            ("SN-3".to_string(), "https://ipfs.io/ipfs/bafyB".to_string()),
+            ("SN-4".to_string(), "https://example.com/m.json".to_string()),
+            ("SN-5".to_string(), String::new()),
+        ];
+        let (cids, skipped) = cids_by_serial(&nfts);
+        assert_eq!(cids["bafyA"], vec!["SN-1", "SN-2"]);
+        assert_eq!(cids["bafyB"], vec!["SN-3"]);
+        assert_eq!(cids.len(), 2);
+        assert_eq!(skipped, 2);
+    }
+}
@@ -0,0 +1,243 @@
+//! Clients for the two places a CID is pinned: our IPFS Cluster, through its
+//! REST API, and a remote provider speaking the IPFS Pinning Service API.
+
+use std::time::Duration;
+
+use reqwest::multipart::{Form, Part};
+use serde_json::{json, Value};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Where a CID stands at one pinning location.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinState {
+    Pinned,
+    /// Queued or being fetched.
+    Pinning,
+    /// Not pinned, or the pin failed.
+    Missing,
+}
+
+impl PinState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PinState::Pinned => "pinned",
+            PinState::Pinning => "pinning",
+            PinState::Missing => "missing",
+        }
+    }
+}
+
+pub fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent("proofcart-ipfs-pinner")
+        .build()
+        .expect("Failed to build the HTTP client")
+}
+
+/// The root CID of an `ipfs://<cid>/...` URI or a gateway URL
+/// (`https://<host>/ipfs/<cid>/...`). Other URIs have none.
+pub fn cid_from_uri(uri: &str) -> Option<&str> {
+    let path = match uri.strip_prefix("ipfs://") {
+        Some(path) => path.strip_prefix("ipfs/").unwrap_or(path),
+        None => uri.split_once("/ipfs/")?.1,
+    };
+    let cid = path.split(['/', '?', '#']).next()?;
+    (!cid.is_empty()).then_some(cid)
+}
+
+/// The CID in one line of the cluster's `/add` output. Newer clusters write
+/// it as a string, older ones as `{"/": "<cid>"}`.
+fn added_cid(line: &Value) -> Option<String> {
+    match &line["cid"] {
+        Value::String(cid) => Some(cid.clone()),
+        cid => cid["/"].as_str().map(str::to_string),
+    }
+}
+
+/// Pinned if any cluster peer has it, pinning if any is fetching it.
+fn cluster_state(status: &Value) -> PinState {
+    let states: Vec<&str> = status["peer_map"]
+        .as_object()
+        .map(|peers| peers.values().filter_map(|peer| peer["status"].as_str()).collect())
+        .unwrap_or_default();
+    if states.contains(&"pinned") {
+        PinState::Pinned
+    } else if states.iter().any(|s| matches!(*s, "pinning" | "pin_queued" | "queued")) {
+        PinState::Pinning
+    } else {
+        PinState::Missing
+    }
+}
+
+/// The best status among a provider's pin requests for one CID.
+fn provider_state(body: &Value) -> PinState {
+    let states: Vec<&str> = body["results"]
+        .as_array()
+        .map(|pins| pins.iter().filter_map(|pin| pin["status"].as_str()).collect())
+        .unwrap_or_default();
+    if states.contains(&"pinned") {
+        PinState::Pinned
+    } else if states.iter().any(|s| matches!(*s, "queued" | "pinning")) {
+        PinState::Pinning
+    } else {
+        PinState::Missing
+    }
+}
+
+/// Our IPFS Cluster's REST API (default port 9094).
+pub struct Cluster {
+    http: reqwest::Client,
+    url: String,
+}
+
+impl Cluster {
+    pub fn new(http: reqwest::Client, url: &str) -> Self {
+        Self { http, url: url.trim_end_matches('/').to_string() }
+    }
+
+    /// Add `bytes` as one file, which the cluster pins at its replication
+    /// factor, and return its CID (v1).
+    pub async fn add(&self, name: &str, bytes: Vec<u8>, mime: &str) -> Result<String, String> {
+        let part = Part::bytes(bytes)
+            .file_name(name.to_string())
+            .mime_str(mime)
+            .map_err(|e| e.to_string())?;
+        let response = self
+            .http
+            .post(format!("{}/add", self.url))
+            .query(&[("name", name), ("cid-version", "1")])
+            .multipart(Form::new().part("file", part))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Cluster add failed: {}", e))?;
+        let body = response.text().await.map_err(|e| format!("Cluster add failed: {}", e))?;
+        // One JSON object per line; the last is the file itself.
+        body.lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .filter_map(|line| added_cid(&line))
+            .last()
+            .ok_or_else(|| "Cluster add returned no CID".to_string())
+    }
+
+    pub async fn pin(&self, cid: &str) -> Result<(), String> {
+        self.http
+            .post(format!("{}/pins/{}", self.url, cid))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Cluster pin of {} failed: {}", cid, e))?;
+        Ok(())
+    }
+
+    pub async fn status(&self, cid: &str) -> Result<PinState, String> {
+        let response = self
+            .http
+            .get(format!("{}/pins/{}", self.url, cid))
+            .send()
+            .await
+            .map_err(|e| format!("Cluster status of {} failed: {}", cid, e))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(PinState::Missing);
+        }
+        let status: Value = response
+            .error_for_status()
+            .map_err(|e| format!("Cluster status of {} failed: {}", cid, e))?
+            .json()
+            .await
+            .map_err(|e| format!("Cluster status of {} failed: {}", cid, e))?;
+        Ok(cluster_state(&status))
+    }
+}
+
+/// A remote pinning provider (Pinata, web3.storage, Filebase, ...) through
+/// the IPFS Pinning Service API.
+pub struct PinningService {
+    http: reqwest::Client,
+    url: String,
+    token: String,
+}
+
+impl PinningService {
+    pub fn new(http: reqwest::Client, url: &str, token: String) -> Self {
+        Self { http, url: url.trim_end_matches('/').to_string(), token }
+    }
+
+    /// Ask the provider to pin `cid`. It fetches the content from the IPFS
+    /// network, so our cluster must be reachable from it.
+    pub async fn pin(&self, cid: &str, name: &str) -> Result<(), String> {
+        self.http
+            .post(format!("{}/pins", self.url))
+            .bearer_auth(&self.token)
+            .json(&json!({"cid": cid, "name": name}))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Provider pin of {} failed: {}", cid, e))?;
+        Ok(())
+    }
+
+    pub async fn status(&self, cid: &str) -> Result<PinState, String> {
+        let body: Value = self
+            .http
+            .get(format!("{}/pins", self.url))
+            .bearer_auth(&self.token)
+            .query(&[("cid", cid), ("status", "queued,pinning,pinned,failed")])
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Provider status of {} failed: {}", cid, e))?
+            .json()
+            .await
+            .map_err(|e| format!("Provider status of {} failed: {}", cid, e))?;
+        Ok(provider_state(&body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CID: &str = "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi";
+
+    #[test]
+    fn reads_the_cid_of_ipfs_uris() {
+        assert_eq!(cid_from_uri(&format!("ipfs://{}", CID)), Some(CID));
+        assert_eq!(cid_from_uri(&format!("ipfs://{}/metadata.json", CID)), Some(CID));
+        assert_eq!(cid_from_uri(&format!("ipfs://ipfs/{}", CID)), Some(CID));
+        assert_eq!(cid_from_uri(&format!("https://ipfs.io/ipfs/{}?filename=a.json", CID)), Some(CID));
+        assert_eq!(cid_from_uri("https://example.com/metadata.json"), None);
+        assert_eq!(cid_from_uri("ipfs://"), None);
+        assert_eq!(cid_from_uri(""), None);
+    }
+
+    #[test]
+    fn reads_both_add_output_formats() {
+        assert_eq!(added_cid(&json!({"name": "a", "cid": CID})), Some(CID.to_string()));
+        assert_eq!(added_cid(&json!({"name": "a", "cid": {"/": CID}})), Some(CID.to_string()));
+        assert_eq!(added_cid(&json!({"name": "a"})), None);
+    }
+
+    #[test]
+    fn one_pinned_peer_is_enough() {
+        let status = |peers: Value| cluster_state(&json!({"cid": CID, "peer_map": peers}));
+        assert_eq!(status(json!({"a": {"status": "pin_error"}, "b": {"status": "pinned"}})), PinState::Pinned);
+        assert_eq!(status(json!({"a": {"status": "pin_error"}, "b": {"status": "pinning"}})), PinState::Pinning);
+        assert_eq!(status(json!({"a": {"status": "pin_error"}, "b": {"status": "unpinned"}})), PinState::Missing);
+        assert_eq!(status(json!({})), PinState::Missing);
+    }
+
+    #[test]
+    fn failed_provider_pins_are_missing() {
+        let state = |statuses: &[&str]| {
+            let results: Vec<Value> = statuses.iter().map(|s| json!({"status": s})).collect();
+            provider_state(&json!({"count": results.len(), "results": results}))
+        };
+        assert_eq!(state(&["failed", "pinned"]), PinState::Pinned);
+        assert_eq!(state(&["queued"]), PinState::Pinning);
+        assert_eq!(state(&["failed"]), PinState::Missing);
+        assert_eq!(state(&[]), PinState::Missing);
+    }
+}
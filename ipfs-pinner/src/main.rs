@@ -0,0 +1,211 @@
+//! `proofcart-ipfs-pinner`: uploads product metadata and images to IPFS,
+//! pinned on our cluster and at a pinning provider, and watches the pins of
+//! every product in the registry.
+//!
+//! ```text
+//! POST /metadata?name=<name>      JSON object -> {"cid", "uri"}
+//! POST /images?name=<name>        raw image   -> {"cid", "uri"}
+//! POST /certificates?name=<name>  multipart   -> {"arweave_tx_id", "uri", "files"}
+//! GET  /pins/{cid}
+//!
+//! proofcart-ipfs-pinner serve --check-interval 3600
+//! proofcart-ipfs-pinner check
+//! ```
+
+mod api;
+mod arweave;
+mod health;
+mod ipfs;
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::DefaultBodyLimit;
+use axum::routing::{get, post};
+use axum::Router;
+use candid::Principal;
+use clap::{Args, Parser, Subcommand};
+use proofcart_icp_client::{identity, NftClient};
+
+use arweave::Bundler;
+use health::{Alerter, Registry, Report};
+use ipfs::{Cluster, PinningService};
+
+/// Largest upload accepted: an image, or all documents of a certificate.
+const MAX_UPLOAD_BYTES: usize = 10 * 1024 * 1024;
+
+#[derive(Parser)]
+#[clap(name = "proofcart-ipfs-pinner", version, about = "Pin ProofCart metadata to IPFS and watch it")]
+struct Cli {
+    #[clap(flatten)]
+    pinning: PinningArgs,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct PinningArgs {
+    /// REST API of our IPFS Cluster
+    #[clap(long, env = "IPFS_CLUSTER_URL", default_value = "http://127.0.0.1:9094")]
+    cluster_url: String,
+    /// IPFS Pinning Service API endpoint of the provider
+    #[clap(long, env = "PINNING_SERVICE_URL")]
+    provider_url: String,
+    #[clap(long, env = "PINNING_SERVICE_TOKEN", hide_env_values = true)]
+    provider_token: String,
+    #[clap(long, env = "IC_URL", default_value = "https://ic0.app")]
+    ic_url: String,
+    #[clap(long, env = "ICP_CANISTER_ID")]
+    canister_id: Principal,
+    /// PEM file of a SuperAdmin, to also check restricted metadata URIs;
+    /// anonymous otherwise
+    #[clap(long, env = "PINNER_IDENTITY")]
+    identity: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve the upload API and check pin health periodically
+    Serve {
+        #[clap(long, env = "PINNER_ADDR", default_value = "0.0.0.0:8090")]
+        listen_addr: SocketAddr,
+        /// Bearer token uploads must carry
+        #[clap(long, env = "PINNER_API_TOKEN", hide_env_values = true)]
+        api_token: String,
+        /// Seconds between health checks
+        #[clap(long, default_value = "3600")]
+        check_interval: u64,
+        /// Slack-compatible webhook for missing-content alerts
+        #[clap(long, env = "ALERT_WEBHOOK_URL")]
+        alert_webhook: Option<String>,
+        #[clap(flatten)]
+        arweave: ArweaveArgs,
+    },
+    /// Check pin health once; exit with an error if content is missing
+    Check,
+}
+
+#[derive(Args)]
+struct ArweaveArgs {
+    /// Solana keypair file signing Arweave uploads, whose account at the
+    /// bundler pays for them; unset disables `/certificates`
+    #[clap(long, env = "ARWEAVE_SIGNER_KEYPAIR")]
+    arweave_keypair: Option<PathBuf>,
+    /// ANS-104 bundler endpoint taking one data item per POST
+    #[clap(long, env = "ARWEAVE_BUNDLER_URL", default_value = "https://upload.ardrive.io/v1/tx")]
+    bundler_url: String,
+}
+
+/// Shared by every handler.
+pub struct AppState {
+    pub cluster: Cluster,
+    pub provider: PinningService,
+    pub api_token: String,
+    /// Set when Arweave uploads are configured.
+    pub bundler: Option<Bundler>,
+}
+
+fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/metadata", post(api::upload_metadata))
+        .route("/images", post(api::upload_image))
+        .route("/certificates", post(api::upload_certificates))
+        .route("/pins/:cid", get(api::pin_status))
+        .layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES))
+        .with_state(state)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn print_report(report: &Report) {
+    println!(
+        "Checked {} CIDs ({} tokens without an IPFS URI): {} missing, {} unreadable",
+        report.checked,
+        report.skipped,
+        report.missing.len(),
+        report.errors.len()
+    );
+    for pin in &report.missing {
+        println!(
+            "  {} cluster={} provider={} serials={}",
+            pin.cid,
+            pin.cluster.as_str(),
+            pin.provider.as_str(),
+            pin.serials.join(",")
+        );
+    }
+    for e in &report.errors {
+        println!("  {}", e);
+    }
+}
+
+async fn run() -> Result<(), String> {
+    let cli = Cli::parse();
+    let args = cli.pinning;
+    let http = ipfs::http_client();
+    let cluster = Cluster::new(http.clone(), &args.cluster_url);
+    let provider = PinningService::new(http.clone(), &args.provider_url, args.provider_token);
+    let identity = match &args.identity {
+        Some(path) => identity::from_pem_file(path).map_err(|e| format!("{}: {}", path.display(), e))?,
+        None => identity::anonymous(),
+    };
+    let registry = Registry {
+        nft: NftClient::connect(&args.ic_url, identity, args.canister_id)
+            .await
+            .map_err(|e| e.to_string())?,
+        admin: args.identity.is_some(),
+    };
+
+    match cli.command {
+        Command::Check => {
+            let report = health::sweep(&registry, &cluster, &provider).await?;
+            print_report(&report);
+            if !report.missing.is_empty() {
+                return Err(format!("{} CIDs are missing", report.missing.len()));
+            }
+            Ok(())
+        }
+        Command::Serve { listen_addr, api_token, check_interval, alert_webhook, arweave: storage } => {
+            let bundler = match &storage.arweave_keypair {
+                Some(path) => Some(Bundler::new(http.clone(), &storage.bundler_url, arweave::load_key(path)?)),
+                None => None,
+            };
+            let state = Arc::new(AppState { cluster, provider, api_token, bundler });
+            let alerter = Alerter::new(http, alert_webhook);
+
+            let checker = state.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(check_interval));
+                loop {
+                    interval.tick().await;
+                    match health::sweep(&registry, &checker.cluster, &checker.provider).await {
+                        Ok(report) => {
+                            print_report(&report);
+                            alerter.report(&report).await;
+                        }
+                        Err(e) => eprintln!("Health check failed: {}", e),
+                    }
+                }
+            });
+
+            let listener = tokio::net::TcpListener::bind(listen_addr)
+                .await
+                .map_err(|e| format!("{}: {}", listen_addr, e))?;
+            println!("Listening on {}", listen_addr);
+            axum::serve(listener, router(state)).await.map_err(|e| e.to_string())
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Category-specific requirements.
+//!
+//! Some categories cannot be verified without a few identifying details:
+//! a phone by its IMEI, a medicine by its batch and expiry. Those must be
+//! keys of the `specifications` JSON object, with non-empty values. Other
+//! categories have no extra requirements. Categories match case-insensitively.
+
+use proofcart_types::NFTMetadata;
+use serde_json::{Map, Value};
+
+use crate::SchemaError;
+
+pub struct CategoryRule {
+    pub category: &'static str,
+    /// Keys `specifications` must have.
+    pub required_specifications: &'static [&'static str],
+    /// At least one entry in `certifications`.
+    pub requires_certification: bool,
+}
+
+pub const RULES: &[CategoryRule] = &[
+    CategoryRule { category: "Smartphones", required_specifications: &["imei"], requires_certification: false },
+    CategoryRule {
+        category: "Pharmaceuticals",
+        required_specifications: &["batch_number", "expiry_date"],
+        requires_certification: true,
+    },
+    CategoryRule {
+        category: "Food",
+        required_specifications: &["batch_number", "best_before"],
+        requires_certification: false,
+    },
+    CategoryRule { category: "Cosmetics", required_specifications: &["batch_number"], requires_certification: false },
+];
+
+/// The rule of `category`, if it has one.
+pub fn rule(category: &str) -> Option<&'static CategoryRule> {
+    RULES.iter().find(|rule| rule.category.eq_ignore_ascii_case(category.trim()))
+}
+
+fn specifications(text: &str) -> Result<Map<String, Value>, SchemaError> {
+    if text.trim().is_empty() {
+        return Ok(Map::new());
+    }
+    match serde_json::from_str(text) {
+        Ok(Value::Object(map)) => Ok(map),
+        Ok(_) => Err(SchemaError::InvalidSpecifications("not an object".to_string())),
+        Err(e) => Err(SchemaError::InvalidSpecifications(e.to_string())),
+    }
+}
+
+fn present(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::Null) => false,
+        Some(Value::String(s)) => !s.trim().is_empty(),
+        Some(_) => true,
+    }
+}
+
+/// `specifications` is a JSON object, or empty, and holds what the
+/// category requires.
+pub fn check(metadata: &NFTMetadata) -> Result<(), SchemaError> {
+    let specifications = specifications(&metadata.specifications)?;
+    let Some(rule) = rule(&metadata.category) else {
+        return Ok(());
+    };
+    for key in rule.required_specifications {
+        if !present(specifications.get(*key)) {
+            return Err(SchemaError::MissingSpecification { category: rule.category, key });
+        }
+    }
+    if rule.requires_certification && metadata.certifications.iter().all(|c| c.trim().is_empty()) {
+        return Err(SchemaError::MissingCertification { category: rule.category });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(category: &str, specifications: &str) -> NFTMetadata {
+        NFTMetadata {
+            category: category.to_string(),
+            specifications: specifications.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn requires_the_category_keys() {
+        assert_eq!(check(&metadata("Electronics", "")), Ok(()));
+        assert_eq!(check(&metadata("smartphones", r#"{"imei": "356938035643809"}"#)), Ok(()));
+        assert_eq!(
+            check(&metadata("Smartphones", r#"{"imei": ""}"#)),
+            Err(SchemaError::MissingSpecification { category: "Smartphones", key: "imei" })
+        );
+
+        let mut medicine = metadata("Pharmaceuticals", r#"{"batch_number": "B1", "expiry_date": "2027-01-01"}"#);
+        assert_eq!(check(&medicine), Err(SchemaError::MissingCertification { category: "Pharmaceuticals" }));
+        medicine.certifications = vec!["WHO-GMP".to_string()];
+        assert_eq!(check(&medicine), Ok(()));
+    }
+
+    #[test]
+    fn specifications_must_be_an_object() {
+        assert!(matches!(check(&metadata("Electronics", "[1]")), Err(SchemaError::InvalidSpecifications(_))));
+        assert!(matches!(check(&metadata("Electronics", "256GB")), Err(SchemaError::InvalidSpecifications(_))));
+    }
+}
@@ -0,0 +1,305 @@
+//! Validation rules for product metadata, shared by every place metadata
+//! enters ProofCart: the NFT canister (compiled into its wasm), the CLI and
+//! the gateway. All of them reject the same metadata with the same message.
+//!
+//! - [`check_lengths`]: the size caps the canister's bounded storage relies
+//!   on. Records that arrive fully formed (imports, restores) only get this.
+//! - [`validate`]: the caps plus required fields, URI and date formats and
+//!   the rules of the product's category ([`categories`]). New mints get this.
+
+pub mod categories;
+
+use std::fmt;
+
+use proofcart_types::{LocalizedText, NFTMetadata};
+
+pub use categories::CategoryRule;
+
+pub const MAX_SERIAL_BYTES: usize = 64;
+pub const MAX_PRODUCT_NAME_BYTES: usize = 256;
+pub const MAX_MANUFACTURER_BYTES: usize = 128;
+pub const MAX_MANUFACTURE_DATE_BYTES: usize = 32;
+pub const MAX_CATEGORY_BYTES: usize = 64;
+pub const MAX_DESCRIPTION_BYTES: usize = 4 * 1024;
+pub const MAX_SPECIFICATIONS_BYTES: usize = 16 * 1024;
+pub const MAX_WARRANTY_INFO_BYTES: usize = 2 * 1024;
+pub const MAX_CERTIFICATIONS: usize = 32;
+pub const MAX_CERTIFICATION_BYTES: usize = 128;
+pub const MAX_URI_BYTES: usize = 512;
+/// An Arweave id is 32 bytes in unpadded base64url.
+pub const MAX_ARWEAVE_TX_ID_BYTES: usize = 43;
+/// Translations per token, and the caps on each translated field.
+pub const MAX_LOCALES: usize = 8;
+pub const MAX_LOCALIZED_DESCRIPTION_BYTES: usize = 2 * 1024;
+pub const MAX_LOCALIZED_WARRANTY_INFO_BYTES: usize = 1024;
+
+/// The caps an operator may lower (the canister's `set_limits`). The
+/// default is the ceiling of each.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Caps {
+    pub description_bytes: usize,
+    pub specifications_bytes: usize,
+    pub warranty_info_bytes: usize,
+    pub certifications: usize,
+}
+
+impl Default for Caps {
+    fn default() -> Self {
+        Caps {
+            description_bytes: MAX_DESCRIPTION_BYTES,
+            specifications_bytes: MAX_SPECIFICATIONS_BYTES,
+            warranty_info_bytes: MAX_WARRANTY_INFO_BYTES,
+            certifications: MAX_CERTIFICATIONS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaError {
+    /// A text field is longer than its cap, in bytes.
+    TooLong { field: &'static str, max: usize, actual: usize },
+    /// A list has more entries than its cap.
+    TooMany { field: &'static str, max: usize, actual: usize },
+    Missing { field: &'static str },
+    /// Not an `ipfs://`, `ar://` or `https://` URI.
+    InvalidUri { field: &'static str, uri: String },
+    InvalidArweaveTxId(String),
+    /// `manufacture_date` is not `YYYY-MM-DD`.
+    InvalidDate(String),
+    /// `specifications` is not a JSON object.
+    InvalidSpecifications(String),
+    /// The category requires a key in `specifications`.
+    MissingSpecification { category: &'static str, key: &'static str },
+    /// The category requires at least one certification.
+    MissingCertification { category: &'static str },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::TooLong { field, max, actual } => {
+                write!(f, "{} is {} bytes; the limit is {}", field, actual, max)
+            }
+            SchemaError::TooMany { field, max, actual } => {
+                write!(f, "{} has {} entries; the limit is {}", field, actual, max)
+            }
+            SchemaError::Missing { field } => write!(f, "{} is required", field),
+            SchemaError::InvalidUri { field, uri } => {
+                write!(f, "{} must be an ipfs://, ar:// or https:// URI, not {}", field, uri)
+            }
+            SchemaError::InvalidArweaveTxId(id) => write!(f, "Invalid Arweave transaction id {}", id),
+            SchemaError::InvalidDate(date) => write!(f, "manufacture_date must be YYYY-MM-DD, not {}", date),
+            SchemaError::InvalidSpecifications(e) => write!(f, "specifications must be a JSON object: {}", e),
+            SchemaError::MissingSpecification { category, key } => {
+                write!(f, "{} products need {} in specifications", category, key)
+            }
+            SchemaError::MissingCertification { category } => {
+                write!(f, "{} products need at least one certification", category)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<SchemaError> for String {
+    fn from(e: SchemaError) -> String {
+        e.to_string()
+    }
+}
+
+pub fn check_len(field: &'static str, value: &str, max: usize) -> Result<(), SchemaError> {
+    if value.len() > max {
+        return Err(SchemaError::TooLong { field, max, actual: value.len() });
+    }
+    Ok(())
+}
+
+pub fn check_count<T>(field: &'static str, items: &[T], max: usize) -> Result<(), SchemaError> {
+    if items.len() > max {
+        return Err(SchemaError::TooMany { field, max, actual: items.len() });
+    }
+    Ok(())
+}
+
+/// Caps on a token's translations.
+pub fn check_localized(entries: &[(String, LocalizedText)]) -> Result<(), SchemaError> {
+    check_count("localized", entries, MAX_LOCALES)?;
+    for (_, text) in entries {
+        check_len("localized product_name", text.product_name.as_deref().unwrap_or_default(), MAX_PRODUCT_NAME_BYTES)?;
+        check_len(
+            "localized description",
+            text.description.as_deref().unwrap_or_default(),
+            MAX_LOCALIZED_DESCRIPTION_BYTES,
+        )?;
+        check_len(
+            "localized warranty_info",
+            text.warranty_info.as_deref().unwrap_or_default(),
+            MAX_LOCALIZED_WARRANTY_INFO_BYTES,
+        )?;
+    }
+    Ok(())
+}
+
+/// Size caps on every field.
+pub fn check_lengths(metadata: &NFTMetadata, caps: &Caps) -> Result<(), SchemaError> {
+    check_len("serial_number", &metadata.serial_number, MAX_SERIAL_BYTES)?;
+    check_len("product_name", &metadata.product_name, MAX_PRODUCT_NAME_BYTES)?;
+    check_len("manufacturer", &metadata.manufacturer, MAX_MANUFACTURER_BYTES)?;
+    check_len("manufacture_date", &metadata.manufacture_date, MAX_MANUFACTURE_DATE_BYTES)?;
+    check_len("category", &metadata.category, MAX_CATEGORY_BYTES)?;
+    check_len("description", &metadata.description, caps.description_bytes)?;
+    check_len("specifications", &metadata.specifications, caps.specifications_bytes)?;
+    check_len("warranty_info", &metadata.warranty_info, caps.warranty_info_bytes)?;
+    check_count("certifications", &metadata.certifications, caps.certifications)?;
+    for certification in &metadata.certifications {
+        check_len("certification", certification, MAX_CERTIFICATION_BYTES)?;
+    }
+    check_localized(metadata.localized.as_deref().unwrap_or_default())?;
+    check_len("arweave_tx_id", metadata.arweave_tx_id.as_deref().unwrap_or_default(), MAX_ARWEAVE_TX_ID_BYTES)?;
+    check_len("ipfs_metadata_uri", &metadata.ipfs_metadata_uri, MAX_URI_BYTES)
+}
+
+/// `ipfs://<cid>[/path]`, `ar://<id>[/path]` or `https://<host>[/path]`.
+pub fn check_uri(field: &'static str, uri: &str) -> Result<(), SchemaError> {
+    check_len(field, uri, MAX_URI_BYTES)?;
+    let rest = ["ipfs://", "ar://", "https://"].iter().find_map(|scheme| uri.strip_prefix(scheme));
+    let valid = match rest {
+        Some(rest) => {
+            let authority = rest.split('/').next().unwrap_or_default();
+            !authority.is_empty() && !uri.bytes().any(|b| b.is_ascii_whitespace() || b.is_ascii_control())
+        }
+        None => false,
+    };
+    if !valid {
+        return Err(SchemaError::InvalidUri { field, uri: uri.to_string() });
+    }
+    Ok(())
+}
+
+/// 32 bytes in unpadded base64url.
+pub fn check_arweave_tx_id(id: &str) -> Result<(), SchemaError> {
+    let valid = id.len() == MAX_ARWEAVE_TX_ID_BYTES
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if !valid {
+        return Err(SchemaError::InvalidArweaveTxId(id.to_string()));
+    }
+    Ok(())
+}
+
+/// A calendar date, `YYYY-MM-DD`.
+pub fn check_date(date: &str) -> Result<(), SchemaError> {
+    let invalid = || SchemaError::InvalidDate(date.to_string());
+    let parts: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return Err(invalid());
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return Err(invalid());
+    }
+    let number = |s: &str| s.bytes().all(|b| b.is_ascii_digit()).then(|| s.parse::<u32>().ok()).flatten();
+    let (Some(year), Some(month), Some(day)) = (number(year), number(month), number(day)) else {
+        return Err(invalid());
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return Err(invalid()),
+    };
+    if day == 0 || day > days {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+/// Every rule for metadata about to be minted. Empty optional fields
+/// (`manufacture_date`, `specifications`, `ipfs_metadata_uri`) are allowed.
+pub fn validate(metadata: &NFTMetadata, caps: &Caps) -> Result<(), SchemaError> {
+    if metadata.serial_number.is_empty() {
+        return Err(SchemaError::Missing { field: "serial_number" });
+    }
+    check_lengths(metadata, caps)?;
+    if metadata.product_name.trim().is_empty() {
+        return Err(SchemaError::Missing { field: "product_name" });
+    }
+    if metadata.manufacturer.trim().is_empty() {
+        return Err(SchemaError::Missing { field: "manufacturer" });
+    }
+    if !metadata.manufacture_date.is_empty() {
+        check_date(&metadata.manufacture_date)?;
+    }
+    if !metadata.ipfs_metadata_uri.is_empty() {
+        check_uri("ipfs_metadata_uri", &metadata.ipfs_metadata_uri)?;
+    }
+    if let Some(id) = &metadata.arweave_tx_id {
+        check_arweave_tx_id(id)?;
+    }
+    categories::check(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> NFTMetadata {
+        NFTMetadata {
+            serial_number: "SN-1".to_string(),
+            product_name: "Redmi Note 14 Pro".to_string(),
+            manufacturer: "Xiaomi".to_string(),
+            manufacture_date: "2025-01-15".to_string(),
+            category: "Electronics".to_string(),
+            specifications: "{}".to_string(),
+            ipfs_metadata_uri: "ipfs://bafy-test".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accepts_complete_metadata() {
+        assert_eq!(validate(&metadata(), &Caps::default()), Ok(()));
+        let minimal = NFTMetadata {
+            serial_number: "SN-1".to_string(),
+            product_name: "Widget".to_string(),
+            manufacturer: "Acme".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(validate(&minimal, &Caps::default()), Ok(()));
+    }
+
+    #[test]
+    fn rejects_missing_fields_and_lowered_caps() {
+        let m = NFTMetadata { manufacturer: " ".to_string(), ..metadata() };
+        assert_eq!(validate(&m, &Caps::default()), Err(SchemaError::Missing { field: "manufacturer" }));
+
+        let m = NFTMetadata { description: "x".repeat(11), ..metadata() };
+        let caps = Caps { description_bytes: 10, ..Caps::default() };
+        assert_eq!(
+            validate(&m, &caps).unwrap_err().to_string(),
+            "description is 11 bytes; the limit is 10"
+        );
+    }
+
+    #[test]
+    fn checks_uris() {
+        for uri in ["ipfs://bafy/metadata.json", "ar://abc", "https://example.com/m.json"] {
+            assert_eq!(check_uri("uri", uri), Ok(()), "{}", uri);
+        }
+        for uri in ["http://example.com", "ipfs://", "https:///path", "ipfs://bafy metadata", "bafy"] {
+            assert!(check_uri("uri", uri).is_err(), "{}", uri);
+        }
+    }
+
+    #[test]
+    fn checks_dates_and_arweave_ids() {
+        assert_eq!(check_date("2024-02-29"), Ok(()));
+        for date in ["2023-02-29", "2025-13-01", "2025-1-15", "15/01/2025", "2025-01-+1"] {
+            assert!(check_date(date).is_err(), "{}", date);
+        }
+        assert_eq!(check_arweave_tx_id(&"a-_9".repeat(11)[..43]), Ok(()));
+        assert!(check_arweave_tx_id("short").is_err());
+        assert!(check_arweave_tx_id(&"=".repeat(43)).is_err());
+    }
+}
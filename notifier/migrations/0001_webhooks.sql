@@ -0,0 +1,54 @@
+-- Webhook endpoints, their queued deliveries and every delivery attempt.
+-- Lives in the indexer's database; the notifier only reads the indexer's
+-- tables.
+
+CREATE TABLE webhook_endpoints (
+    id         BIGSERIAL PRIMARY KEY,
+    -- Solana address of the seller for escrow events, principal of the
+    -- minter for NFT events.
+    merchant   TEXT NOT NULL,
+    url        TEXT NOT NULL,
+    -- HMAC-SHA256 key; shown once when the endpoint is added.
+    secret     TEXT NOT NULL,
+    events     TEXT[] NOT NULL,
+    active     BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX webhook_endpoints_merchant ON webhook_endpoints (merchant) WHERE active;
+
+CREATE TABLE webhook_deliveries (
+    id              BIGSERIAL PRIMARY KEY,
+    endpoint_id     BIGINT NOT NULL REFERENCES webhook_endpoints (id),
+    -- Stable across retries and redrives, for receivers to deduplicate.
+    event_id        TEXT NOT NULL,
+    event_type      TEXT NOT NULL,
+    payload         JSONB NOT NULL,
+    -- 'dead' after the last retry fails; redrive sets it back to pending.
+    status          TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'delivered', 'dead')),
+    attempts        INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+    delivered_at    TIMESTAMPTZ,
+    UNIQUE (endpoint_id, event_id)
+);
+
+CREATE INDEX webhook_deliveries_due ON webhook_deliveries (next_attempt_at) WHERE status = 'pending';
+
+CREATE TABLE webhook_attempts (
+    id           BIGSERIAL PRIMARY KEY,
+    delivery_id  BIGINT NOT NULL REFERENCES webhook_deliveries (id),
+    attempted_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- NULL when no response arrived.
+    status_code  INTEGER,
+    error        TEXT,
+    duration_ms  INTEGER NOT NULL
+);
+
+CREATE INDEX webhook_attempts_delivery ON webhook_attempts (delivery_id);
+
+-- Last indexer row turned into deliveries, per source table.
+CREATE TABLE notifier_cursors (
+    source   TEXT PRIMARY KEY,
+    position BIGINT NOT NULL
+);
@@ -0,0 +1,38 @@
+//! Connection and the notifier's own migrations.
+
+use tokio_postgres::{Client, NoTls};
+
+/// Recorded in `notifier_migrations`, apart from the indexer's versions.
+const MIGRATIONS: &[(i32, &str)] = &[(1, include_str!("../migrations/0001_webhooks.sql"))];
+
+pub async fn connect(url: &str) -> Result<Client, String> {
+    let (client, connection) = tokio_postgres::connect(url, NoTls)
+        .await
+        .map_err(|e| format!("Database: {}", e))?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("Postgres connection closed: {}", e);
+        }
+    });
+    Ok(client)
+}
+
+pub async fn migrate(db: &mut Client) -> Result<(), tokio_postgres::Error> {
+    db.batch_execute("CREATE TABLE IF NOT EXISTS notifier_migrations (version INTEGER PRIMARY KEY)")
+        .await?;
+    for (version, sql) in MIGRATIONS {
+        let tx = db.transaction().await?;
+        tx.batch_execute("LOCK TABLE notifier_migrations IN EXCLUSIVE MODE").await?;
+        let applied = tx
+            .query_opt("SELECT 1 FROM notifier_migrations WHERE version = $1", &[version])
+            .await?
+            .is_some();
+        if !applied {
+            tx.batch_execute(sql).await?;
+            tx.execute("INSERT INTO notifier_migrations (version) VALUES ($1)", &[version])
+                .await?;
+        }
+        tx.commit().await?;
+    }
+    Ok(())
+}
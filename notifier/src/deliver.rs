@@ -0,0 +1,172 @@
+//! Sends queued deliveries, retrying with backoff until they succeed or are
+//! dead-lettered, and logs every attempt.
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+use tokio_postgres::Client;
+
+use crate::metrics::Metrics;
+
+/// Attempts before a delivery is marked dead.
+pub const MAX_ATTEMPTS: i32 = 10;
+
+const BATCH_SIZE: i64 = 50;
+
+/// How long a claimed delivery is hidden from other notifiers. Longer than
+/// the request timeout, so a delivery in flight is never sent twice.
+const LEASE: &str = "5 minutes";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Delay before retry `attempt` (1-based): 30s, 1m, 2m, ... capped at 6h.
+pub fn backoff(attempt: i32) -> Duration {
+    let secs = 30u64.saturating_mul(1 << (attempt - 1).clamp(0, 20));
+    Duration::from_secs(secs.min(6 * 60 * 60))
+}
+
+/// `ProofCart-Signature` header value: `t=<unix seconds>,v1=<hex>` where the
+/// MAC is HMAC-SHA256 over `"<t>.<body>"` keyed with the endpoint's secret.
+/// Receivers should reject timestamps more than a few minutes old.
+pub fn sign(secret: &str, timestamp: u64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("t={},v1={}", timestamp, hex::encode(mac.finalize().into_bytes()))
+}
+
+pub fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent("proofcart-notifier")
+        .build()
+        .expect("Failed to build the HTTP client")
+}
+
+/// Claim due deliveries and send them. Returns how many were attempted.
+pub async fn deliver_due(db: &Client, http: &reqwest::Client, metrics: &Metrics) -> Result<usize, tokio_postgres::Error> {
+    let claimed = db
+        .query(
+            &format!(
+                "UPDATE webhook_deliveries d SET next_attempt_at = now() + interval '{}'
+                 FROM webhook_endpoints e
+                 WHERE e.id = d.endpoint_id AND e.active AND d.id IN (
+                     SELECT id FROM webhook_deliveries
+                     WHERE status = 'pending' AND next_attempt_at <= now()
+                     ORDER BY next_attempt_at LIMIT $1 FOR UPDATE SKIP LOCKED)
+                 RETURNING d.id, d.event_id, d.event_type, d.payload, d.attempts, e.url, e.secret",
+                LEASE
+            ),
+            &[&BATCH_SIZE],
+        )
+        .await?;
+
+    for row in &claimed {
+        let id: i64 = row.get("id");
+        let attempt = row.get::<_, i32>("attempts") + 1;
+        let payload: Value = row.get("payload");
+        let (status_code, error, elapsed) = send(
+            http,
+            row.get("url"),
+            row.get("secret"),
+            row.get("event_id"),
+            row.get("event_type"),
+            &payload.to_string(),
+        )
+        .await;
+
+        db.execute(
+            "INSERT INTO webhook_attempts (delivery_id, status_code, error, duration_ms) VALUES ($1, $2, $3, $4)",
+            &[&id, &status_code, &error, &(elapsed.as_millis().min(i32::MAX as u128) as i32)],
+        )
+        .await?;
+        if error.is_none() {
+            db.execute(
+                "UPDATE webhook_deliveries SET status = 'delivered', attempts = $2, delivered_at = now() WHERE id = $1",
+                &[&id, &attempt],
+            )
+            .await?;
+            metrics.attempts.inc(&["delivered"]);
+        } else if attempt >= MAX_ATTEMPTS {
+            db.execute(
+                "UPDATE webhook_deliveries SET status = 'dead', attempts = $2 WHERE id = $1",
+                &[&id, &attempt],
+            )
+            .await?;
+            metrics.attempts.inc(&["dead"]);
+            eprintln!("Delivery {} is dead after {} attempts", id, attempt);
+        } else {
+            let delay = backoff(attempt).as_secs() as f64;
+            db.execute(
+                "UPDATE webhook_deliveries SET attempts = $2, next_attempt_at = now() + make_interval(secs => $3)
+                 WHERE id = $1",
+                &[&id, &attempt, &delay],
+            )
+            .await?;
+            metrics.attempts.inc(&["retry"]);
+        }
+    }
+    Ok(claimed.len())
+}
+
+/// POST one payload. Any 2xx is success; otherwise the error is returned
+/// with the status code, if a response arrived.
+async fn send(
+    http: &reqwest::Client,
+    url: &str,
+    secret: &str,
+    event_id: &str,
+    event_type: &str,
+    body: &str,
+) -> (Option<i32>, Option<String>, Duration) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Clock is before 1970")
+        .as_secs();
+    let started = Instant::now();
+    let result = http
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header("ProofCart-Event", event_type)
+        .header("ProofCart-Event-Id", event_id)
+        .header("ProofCart-Signature", sign(secret, timestamp, body))
+        .body(body.to_string())
+        .send()
+        .await;
+    let elapsed = started.elapsed();
+    match result {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16().into()), None, elapsed),
+        Ok(response) => (
+            Some(response.status().as_u16().into()),
+            Some(format!("HTTP {}", response.status())),
+            elapsed,
+        ),
+        Err(e) => (None, Some(e.to_string()), elapsed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_timestamp_and_body() {
+        // Independently computed with
+        // printf '1700000000.{"id":"nft:7"}' | openssl dgst -sha256 -hmac whsec_test
+        assert_eq!(
+            sign("whsec_test", 1_700_000_000, r#"{"id":"nft:7"}"#),
+            "t=1700000000,v1=ef8c4e730e9358105f9c19528fd4b8fdbd0ad684fa16d5c0df45080ea254c394"
+        );
+    }
+
+    #[test]
+    fn backs_off_exponentially_to_a_cap() {
+        assert_eq!(backoff(1), Duration::from_secs(30));
+        assert_eq!(backoff(2), Duration::from_secs(60));
+        assert_eq!(backoff(4), Duration::from_secs(240));
+        assert_eq!(backoff(20), Duration::from_secs(6 * 60 * 60));
+    }
+}
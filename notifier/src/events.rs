@@ -0,0 +1,155 @@
+//! Turns rows the indexer wrote into queued deliveries.
+//!
+//! Each source table is followed by a cursor in `notifier_cursors`. A batch
+//! of deliveries is inserted in the same transaction that advances the
+//! cursor, and `(endpoint_id, event_id)` is unique, so an event is queued
+//! once per endpoint even if a batch is replayed.
+
+use serde_json::{json, Value};
+use tokio_postgres::{Client, Transaction};
+
+const BATCH_SIZE: i64 = 500;
+
+/// Event types merchants subscribe to.
+pub const EVENT_TYPES: &[&str] = &["order_released", "dispute_opened", "refund_issued", "nft_transferred"];
+
+/// Queue deliveries for new rows of both sources. Returns how many events
+/// were read.
+pub async fn enqueue(db: &mut Client) -> Result<usize, tokio_postgres::Error> {
+    Ok(enqueue_escrow_events(db).await? + enqueue_nft_transfers(db).await?)
+}
+
+/// The webhook event type of an escrow program instruction, if merchants are
+/// notified of it.
+fn escrow_event_type(kind: &str) -> Option<&'static str> {
+    match kind {
+        "confirm_delivery" | "resolve_release" => Some("order_released"),
+        "lock_dispute" => Some("dispute_opened"),
+        "resolve_refund" => Some("refund_issued"),
+        _ => None,
+    }
+}
+
+async fn enqueue_escrow_events(db: &mut Client) -> Result<usize, tokio_postgres::Error> {
+    let tx = db.transaction().await?;
+    let after = cursor(&tx, "escrow_events").await?;
+    let rows = tx
+        .query(
+            "SELECT e.id, e.signature, e.instruction, e.kind, e.slot, e.block_time,
+                    o.order_id, o.escrow_address, o.buyer, o.seller, o.amount_lamports
+             FROM escrow_events e
+             JOIN escrow_orders o ON o.marketplace = e.marketplace AND o.order_id = e.order_id
+             WHERE e.id > $1 ORDER BY e.id LIMIT $2",
+            &[&after, &BATCH_SIZE],
+        )
+        .await?;
+    for row in &rows {
+        let Some(event_type) = escrow_event_type(row.get("kind")) else {
+            continue;
+        };
+        let signature: String = row.get("signature");
+        let event_id = format!("escrow:{}:{}", signature, row.get::<_, i16>("instruction"));
+        let data = json!({
+            "order_id": row.get::<_, String>("order_id"),
+            "escrow_address": row.get::<_, String>("escrow_address"),
+            "buyer": row.get::<_, String>("buyer"),
+            "seller": row.get::<_, String>("seller"),
+            "amount_lamports": row.get::<_, i64>("amount_lamports"),
+            "instruction": row.get::<_, String>("kind"),
+            "signature": signature,
+            "slot": row.get::<_, i64>("slot"),
+            "block_time": row.get::<_, Option<i64>>("block_time"),
+        });
+        queue(&tx, row.get("seller"), &event_id, event_type, data).await?;
+    }
+    if let Some(last) = rows.last() {
+        set_cursor(&tx, "escrow_events", last.get("id")).await?;
+    }
+    tx.commit().await?;
+    Ok(rows.len())
+}
+
+async fn enqueue_nft_transfers(db: &mut Client) -> Result<usize, tokio_postgres::Error> {
+    let tx = db.transaction().await?;
+    let after = cursor(&tx, "nft_transfers").await?;
+    let rows = tx
+        .query(
+            "SELECT t.block_index, t.nft_id, t.from_owner, t.to_owner, t.timestamp_ns, t.order_id,
+                    n.serial_number, n.minted_by
+             FROM nft_transfers t JOIN nft_tokens n ON n.nft_id = t.nft_id
+             WHERE t.block_index > $1 ORDER BY t.block_index LIMIT $2",
+            &[&after, &BATCH_SIZE],
+        )
+        .await?;
+    for row in &rows {
+        let block_index: i64 = row.get("block_index");
+        let data = json!({
+            "nft_id": row.get::<_, i64>("nft_id"),
+            "serial_number": row.get::<_, String>("serial_number"),
+            "from": row.get::<_, String>("from_owner"),
+            "to": row.get::<_, String>("to_owner"),
+            "order_id": row.get::<_, Option<String>>("order_id"),
+            "block_index": block_index,
+            "timestamp_ns": row.get::<_, i64>("timestamp_ns"),
+        });
+        let event_id = format!("nft:{}", block_index);
+        queue(&tx, row.get("minted_by"), &event_id, "nft_transferred", data).await?;
+    }
+    if let Some(last) = rows.last() {
+        set_cursor(&tx, "nft_transfers", last.get("block_index")).await?;
+    }
+    tx.commit().await?;
+    Ok(rows.len())
+}
+
+/// Queue the event for every active endpoint of `merchant` subscribed to it.
+async fn queue(
+    tx: &Transaction<'_>,
+    merchant: &str,
+    event_id: &str,
+    event_type: &str,
+    data: Value,
+) -> Result<(), tokio_postgres::Error> {
+    let payload = json!({ "id": event_id, "type": event_type, "data": data });
+    tx.execute(
+        "INSERT INTO webhook_deliveries (endpoint_id, event_id, event_type, payload)
+         SELECT id, $2, $3, $4 FROM webhook_endpoints WHERE active AND merchant = $1 AND $3 = ANY(events)
+         ON CONFLICT (endpoint_id, event_id) DO NOTHING",
+        &[&merchant, &event_id, &event_type, &payload],
+    )
+    .await?;
+    Ok(())
+}
+
+async fn cursor(tx: &Transaction<'_>, source: &str) -> Result<i64, tokio_postgres::Error> {
+    // Locks the cursor row, so concurrent notifiers take turns per source.
+    tx.execute(
+        "INSERT INTO notifier_cursors (source, position) VALUES ($1, -1) ON CONFLICT (source) DO NOTHING",
+        &[&source],
+    )
+    .await?;
+    let row = tx
+        .query_one("SELECT position FROM notifier_cursors WHERE source = $1 FOR UPDATE", &[&source])
+        .await?;
+    Ok(row.get(0))
+}
+
+async fn set_cursor(tx: &Transaction<'_>, source: &str, position: i64) -> Result<(), tokio_postgres::Error> {
+    tx.execute("UPDATE notifier_cursors SET position = $2 WHERE source = $1", &[&source, &position])
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_instructions_to_event_types() {
+        assert_eq!(escrow_event_type("confirm_delivery"), Some("order_released"));
+        assert_eq!(escrow_event_type("resolve_release"), Some("order_released"));
+        assert_eq!(escrow_event_type("lock_dispute"), Some("dispute_opened"));
+        assert_eq!(escrow_event_type("resolve_refund"), Some("refund_issued"));
+        assert_eq!(escrow_event_type("create_escrow"), None);
+    }
+}
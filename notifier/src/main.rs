@@ -0,0 +1,179 @@
+//! `proofcart-notifier`: delivers signed webhooks to merchants when the
+//! indexer records escrow and NFT events.
+//!
+//! ```text
+//! proofcart-notifier add-endpoint <SELLER_PUBKEY> https://shop.example/hooks --events order_released,refund_issued
+//! proofcart-notifier run
+//! proofcart-notifier redrive --dead
+//! ```
+
+mod db;
+mod deliver;
+mod events;
+mod metrics;
+
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand};
+use proofcart_telemetry::health::{self, Report};
+use rand::RngCore;
+
+#[derive(Parser)]
+#[clap(name = "proofcart-notifier", version, about = "Deliver ProofCart webhooks to merchants")]
+struct Cli {
+    /// Postgres URL of the indexer database
+    #[clap(long, env = "DATABASE_URL")]
+    database_url: String,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Queue and deliver webhooks until stopped
+    Run {
+        /// Seconds between polls when there is nothing to do
+        #[clap(long, default_value = "5")]
+        poll_interval: u64,
+        /// Address serving `/metrics` and `/healthz`
+        #[clap(long, env = "METRICS_ADDR", default_value = "0.0.0.0:9103")]
+        metrics_addr: SocketAddr,
+    },
+    /// Register a merchant URL and print its signing secret
+    AddEndpoint {
+        /// Seller's Solana address for escrow events, minter's principal for
+        /// NFT events
+        merchant: String,
+        url: String,
+        /// Event types to send; all by default
+        #[clap(long, use_value_delimiter = true)]
+        events: Vec<String>,
+    },
+    /// Stop sending to an endpoint; pending deliveries stay queued
+    DisableEndpoint { id: i64 },
+    /// Queue dead deliveries for another round of retries
+    Redrive {
+        /// Only this delivery
+        #[clap(long, conflicts_with = "dead")]
+        id: Option<i64>,
+        /// Every dead delivery
+        #[clap(long)]
+        dead: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run() -> Result<(), String> {
+    let cli = Cli::parse();
+    let mut db = db::connect(&cli.database_url).await?;
+    db::migrate(&mut db).await.map_err(|e| e.to_string())?;
+
+    match cli.command {
+        Command::Run { poll_interval, metrics_addr } => {
+            let http = deliver::http_client();
+            let metrics = Arc::new(metrics::Metrics::default());
+            // Health checks get their own connection so a long delivery pass
+            // does not make the probe time out.
+            let health_db = Arc::new(db::connect(&cli.database_url).await?);
+            let rendered = metrics.clone();
+            let telemetry = async {
+                proofcart_telemetry::serve(
+                    metrics_addr,
+                    move || rendered.render(),
+                    move || {
+                        let health_db = health_db.clone();
+                        async move { Report::new(vec![health::postgres(&health_db).await]) }
+                    },
+                )
+                .await
+                .map_err(|e| format!("Metrics server on {}: {}", metrics_addr, e))
+            };
+            tokio::try_join!(deliver_until_stopped(&mut db, &http, &metrics, poll_interval), telemetry)?;
+        }
+        Command::AddEndpoint { merchant, url, events } => {
+            let events = if events.is_empty() {
+                events::EVENT_TYPES.iter().map(|e| e.to_string()).collect()
+            } else {
+                events
+            };
+            if let Some(unknown) = events.iter().find(|e| !events::EVENT_TYPES.contains(&e.as_str())) {
+                return Err(format!(
+                    "Unknown event type {}; expected one of {}",
+                    unknown,
+                    events::EVENT_TYPES.join(", ")
+                ));
+            }
+            let mut secret = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut secret);
+            let secret = format!("whsec_{}", hex::encode(secret));
+            let row = db
+                .query_one(
+                    "INSERT INTO webhook_endpoints (merchant, url, secret, events) VALUES ($1, $2, $3, $4) RETURNING id",
+                    &[&merchant, &url, &secret, &events],
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("Endpoint {}", row.get::<_, i64>(0));
+            println!("Secret   {}", secret);
+            println!("Events   {}", events.join(", "));
+        }
+        Command::DisableEndpoint { id } => {
+            let updated = db
+                .execute("UPDATE webhook_endpoints SET active = FALSE WHERE id = $1", &[&id])
+                .await
+                .map_err(|e| e.to_string())?;
+            if updated == 0 {
+                return Err(format!("No endpoint {}", id));
+            }
+        }
+        Command::Redrive { id, dead } => {
+            if id.is_none() && !dead {
+                return Err("Pass --id or --dead".to_string());
+            }
+            let redriven = db
+                .execute(
+                    "UPDATE webhook_deliveries SET status = 'pending', attempts = 0, next_attempt_at = now()
+                     WHERE status = 'dead' AND ($1::bigint IS NULL OR id = $1)",
+                    &[&id],
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("Requeued {} deliveries", redriven);
+        }
+    }
+    Ok(())
+}
+
+/// Queue new events and deliver what is due; sleep only when a pass found
+/// nothing to do.
+async fn deliver_until_stopped(
+    db: &mut tokio_postgres::Client,
+    http: &reqwest::Client,
+    metrics: &metrics::Metrics,
+    poll_interval: u64,
+) -> Result<(), String> {
+    loop {
+        let queued = events::enqueue(db).await.map_err(|e| e.to_string())?;
+        metrics.queued.fetch_add(queued as u64, Ordering::Relaxed);
+        let sent = deliver::deliver_due(db, http, metrics).await.map_err(|e| e.to_string())?;
+        metrics.refresh(db).await.map_err(|e| e.to_string())?;
+        if queued == 0 && sent == 0 {
+            tokio::time::sleep(Duration::from_secs(poll_interval)).await;
+        }
+    }
+}
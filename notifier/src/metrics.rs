@@ -0,0 +1,88 @@
+//! Delivery counters and queue gauges, served on `GET /metrics`, with a
+//! database check on `GET /healthz`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use proofcart_telemetry::{Exposition, Family};
+use tokio_postgres::Client;
+
+pub struct Metrics {
+    /// Deliveries queued from indexer events.
+    pub queued: AtomicU64,
+    /// Attempts by outcome: `delivered`, `retry` (failed, will retry) or
+    /// `dead` (failed for the last time).
+    pub attempts: Family,
+    /// Refreshed after each pass.
+    pub pending: AtomicU64,
+    pub dead: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            queued: AtomicU64::default(),
+            attempts: Family::new(&["outcome"]),
+            pending: AtomicU64::default(),
+            dead: AtomicU64::default(),
+        }
+    }
+}
+
+impl Metrics {
+    /// Read the queue's size; a pass only knows what it touched.
+    pub async fn refresh(&self, db: &Client) -> Result<(), tokio_postgres::Error> {
+        let row = db
+            .query_one(
+                "SELECT count(*) FILTER (WHERE status = 'pending'), count(*) FILTER (WHERE status = 'dead')
+                 FROM webhook_deliveries",
+                &[],
+            )
+            .await?;
+        self.pending.store(row.get::<_, i64>(0) as u64, Ordering::Relaxed);
+        self.dead.store(row.get::<_, i64>(1) as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = Exposition::new();
+        out.counter(
+            "proofcart_notifier_queued_total",
+            "Deliveries queued from indexer events.",
+            self.queued.load(Ordering::Relaxed),
+        )
+        .family(
+            "proofcart_notifier_attempts_total",
+            "Delivery attempts by outcome: delivered, retry or dead.",
+            &self.attempts,
+        )
+        .gauge(
+            "proofcart_notifier_pending_deliveries",
+            "Deliveries waiting to be sent or retried.",
+            self.pending.load(Ordering::Relaxed),
+        )
+        .gauge(
+            "proofcart_notifier_dead_deliveries",
+            "Deliveries that ran out of attempts and await a redrive.",
+            self.dead.load(Ordering::Relaxed),
+        );
+        out.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_attempts_by_outcome() {
+        let metrics = Metrics::default();
+        metrics.attempts.inc(&["delivered"]);
+        metrics.attempts.inc(&["retry"]);
+        metrics.attempts.inc(&["retry"]);
+        metrics.pending.store(4, Ordering::Relaxed);
+
+        let text = metrics.render();
+        assert!(text.contains("\nproofcart_notifier_attempts_total{outcome=\"retry\"} 2\n"));
+        assert!(text.contains("\nproofcart_notifier_pending_deliveries 4\n"));
+    }
+}
@@ -0,0 +1,147 @@
+//! The canister's certified verification payload, as returned by
+//! `generate_verification_payload`. It is generated on demand rather than
+//! printed: it embeds the subnet certificate and a witness for the serial,
+//! so a scanner can check it offline against the IC root key.
+//!
+//! Layout (version 1, all integers big-endian):
+//!
+//! ```text
+//! u8        version
+//! u64       nft_id
+//! u64       issued_at (ns since epoch)
+//! [u8; 32]  sha256(owner principal)
+//! u8        flags (bit 0 = verified)
+//! u8        serial length, then serial bytes (UTF-8)
+//! u32       certificate length, then certificate (CBOR)
+//! u32       witness length, then witness (CBOR hash tree)
+//! ```
+//!
+//! The witness's `nfts/<serial>` leaf is [`leaf_hash`].
+
+use sha2::{Digest, Sha256};
+
+pub const PAYLOAD_VERSION: u8 = 1;
+const FLAG_VERIFIED: u8 = 0b0000_0001;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerificationPayload {
+    pub nft_id: u64,
+    pub issued_at: u64,
+    pub owner_hash: [u8; 32],
+    pub verified: bool,
+    pub serial_number: String,
+    pub certificate: Vec<u8>,
+    pub witness: Vec<u8>,
+}
+
+/// SHA-256 of the owner's principal bytes. Payloads and leaves carry this
+/// instead of the raw principal so a printed QR code does not dox the owner.
+pub fn owner_hash(owner_principal: &[u8]) -> [u8; 32] {
+    Sha256::digest(owner_principal).into()
+}
+
+/// Leaf value committed for an NFT: `sha256(nft_id_be || owner_hash || verified)`.
+pub fn leaf_hash(nft_id: u64, owner_hash: &[u8; 32], verified: bool) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(nft_id.to_be_bytes());
+    hasher.update(owner_hash);
+    hasher.update([verified as u8]);
+    hasher.finalize().into()
+}
+
+impl VerificationPayload {
+    pub fn encode(&self) -> Result<Vec<u8>, String> {
+        let serial = self.serial_number.as_bytes();
+        if serial.len() > u8::MAX as usize {
+            return Err("Serial number too long for verification payload".to_string());
+        }
+
+        let mut out = Vec::with_capacity(
+            1 + 8 + 8 + 32 + 1 + 1 + serial.len() + 4 + self.certificate.len() + 4 + self.witness.len(),
+        );
+        out.push(PAYLOAD_VERSION);
+        out.extend_from_slice(&self.nft_id.to_be_bytes());
+        out.extend_from_slice(&self.issued_at.to_be_bytes());
+        out.extend_from_slice(&self.owner_hash);
+        out.push(if self.verified { FLAG_VERIFIED } else { 0 });
+        out.push(serial.len() as u8);
+        out.extend_from_slice(serial);
+        out.extend_from_slice(&(self.certificate.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.certificate);
+        out.extend_from_slice(&(self.witness.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.witness);
+        Ok(out)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { bytes, pos: 0 };
+
+        let version = reader.u8()?;
+        if version != PAYLOAD_VERSION {
+            return Err(format!("Unsupported payload version {}", version));
+        }
+
+        let nft_id = reader.u64()?;
+        let issued_at = reader.u64()?;
+        let mut owner_hash = [0u8; 32];
+        owner_hash.copy_from_slice(reader.take(32)?);
+        let flags = reader.u8()?;
+
+        let serial_len = reader.u8()? as usize;
+        let serial_number = String::from_utf8(reader.take(serial_len)?.to_vec())
+            .map_err(|_| "Serial number is not valid UTF-8".to_string())?;
+
+        let cert_len = reader.u32()? as usize;
+        let certificate = reader.take(cert_len)?.to_vec();
+        let witness_len = reader.u32()? as usize;
+        let witness = reader.take(witness_len)?.to_vec();
+
+        if reader.pos != bytes.len() {
+            return Err("Trailing bytes after verification payload".to_string());
+        }
+
+        Ok(VerificationPayload {
+            nft_id,
+            issued_at,
+            owner_hash,
+            verified: flags & FLAG_VERIFIED != 0,
+            serial_number,
+            certificate,
+            witness,
+        })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| "Truncated verification payload".to_string())?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_be_bytes(buf))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(buf))
+    }
+}
@@ -0,0 +1,236 @@
+//! The payload printed in a product's QR code at the factory.
+//!
+//! Unlike the canister's certified verification payload (`payload.rs` in
+//! `blockchain/icp-nft`), which embeds a subnet certificate and is
+//! generated on demand, this one is static and small enough for a label. It
+//! names the token and the canister holding it, and is signed by the
+//! manufacturer's QR key, so a scanner can tell a genuine label from a
+//! copied serial before it looks the token up.
+//!
+//! Layout (version 1, integers big-endian):
+//!
+//! ```text
+//! u8        version
+//! u64       nft_id
+//! u8        canister id length, then canister id bytes
+//! u8        serial length, then serial bytes (UTF-8)
+//! [u8; 64]  Ed25519 signature over "proofcart-qr" || the bytes above
+//! ```
+//!
+//! In the QR code the payload is unpadded base64url text, alone or at the
+//! end of a URL (`https://.../q/<payload>` or `...#<payload>`).
+//!
+//! [`certified`] holds the canister's on-demand payload, for scanners that
+//! check both.
+
+pub mod certified;
+
+use std::fmt;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use candid::Principal;
+use ed25519_dalek::{Signature, Signer, Verifier};
+
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+pub const VERSION: u8 = 1;
+/// Prefixed to the signed bytes, so a QR signature is never valid for
+/// anything else the key signs.
+const DOMAIN: &[u8] = b"proofcart-qr";
+const SIGNATURE_LEN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    UnsupportedVersion(u8),
+    Truncated,
+    TrailingBytes,
+    SerialTooLong(usize),
+    InvalidSerial,
+    InvalidCanisterId,
+    InvalidText,
+    BadSignature,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnsupportedVersion(v) => write!(f, "Unsupported QR payload version {}", v),
+            Error::Truncated => write!(f, "Truncated QR payload"),
+            Error::TrailingBytes => write!(f, "Trailing bytes after QR payload"),
+            Error::SerialTooLong(len) => write!(f, "Serial number is {} bytes; a QR payload holds at most 255", len),
+            Error::InvalidSerial => write!(f, "Serial number is not valid UTF-8"),
+            Error::InvalidCanisterId => write!(f, "Invalid canister id in QR payload"),
+            Error::InvalidText => write!(f, "QR text is not a base64url payload"),
+            Error::BadSignature => write!(f, "QR payload signature does not match the key"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for String {
+    fn from(e: Error) -> String {
+        e.to_string()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QrPayload {
+    pub nft_id: u64,
+    /// The NFT canister, or registry shard, holding the token.
+    pub canister_id: Principal,
+    pub serial_number: String,
+    pub signature: [u8; SIGNATURE_LEN],
+}
+
+/// The signed part of the layout.
+fn body(nft_id: u64, canister_id: &Principal, serial_number: &str) -> Result<Vec<u8>, Error> {
+    let canister = canister_id.as_slice();
+    let serial = serial_number.as_bytes();
+    if serial.len() > u8::MAX as usize {
+        return Err(Error::SerialTooLong(serial.len()));
+    }
+    let mut out = Vec::with_capacity(1 + 8 + 1 + canister.len() + 1 + serial.len() + SIGNATURE_LEN);
+    out.push(VERSION);
+    out.extend_from_slice(&nft_id.to_be_bytes());
+    // A principal is at most 29 bytes.
+    out.push(canister.len() as u8);
+    out.extend_from_slice(canister);
+    out.push(serial.len() as u8);
+    out.extend_from_slice(serial);
+    Ok(out)
+}
+
+fn signed_message(body: &[u8]) -> Vec<u8> {
+    [DOMAIN, body].concat()
+}
+
+impl QrPayload {
+    pub fn sign(
+        nft_id: u64,
+        canister_id: Principal,
+        serial_number: &str,
+        key: &SigningKey,
+    ) -> Result<Self, Error> {
+        let body = body(nft_id, &canister_id, serial_number)?;
+        Ok(QrPayload {
+            nft_id,
+            canister_id,
+            serial_number: serial_number.to_string(),
+            signature: key.sign(&signed_message(&body)).to_bytes(),
+        })
+    }
+
+    /// Check the signature against the manufacturer's QR key.
+    pub fn verify(&self, key: &VerifyingKey) -> Result<(), Error> {
+        let body = body(self.nft_id, &self.canister_id, &self.serial_number)?;
+        key.verify(&signed_message(&body), &Signature::from_bytes(&self.signature))
+            .map_err(|_| Error::BadSignature)
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let mut out = body(self.nft_id, &self.canister_id, &self.serial_number)?;
+        out.extend_from_slice(&self.signature);
+        Ok(out)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let nft_id = u64::from_be_bytes(reader.take(8)?.try_into().expect("8 bytes"));
+        let canister_len = reader.take(1)?[0] as usize;
+        let canister_id = Principal::try_from_slice(reader.take(canister_len)?).map_err(|_| Error::InvalidCanisterId)?;
+        let serial_len = reader.take(1)?[0] as usize;
+        let serial_number =
+            String::from_utf8(reader.take(serial_len)?.to_vec()).map_err(|_| Error::InvalidSerial)?;
+        let signature = reader.take(SIGNATURE_LEN)?.try_into().expect("64 bytes");
+        if reader.pos != bytes.len() {
+            return Err(Error::TrailingBytes);
+        }
+        Ok(QrPayload { nft_id, canister_id, serial_number, signature })
+    }
+
+    /// Unpadded base64url, the form printed in the QR code.
+    pub fn to_text(&self) -> Result<String, Error> {
+        Ok(URL_SAFE_NO_PAD.encode(self.encode()?))
+    }
+
+    /// Parse a scanned QR code: the payload text, or a URL ending in
+    /// `/<payload>` or `#<payload>`.
+    pub fn from_text(text: &str) -> Result<Self, Error> {
+        let text = text.trim();
+        let payload = text.rsplit(['/', '#']).next().unwrap_or(text);
+        let bytes = URL_SAFE_NO_PAD.decode(payload).map_err(|_| Error::InvalidText)?;
+        Self::decode(&bytes)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(Error::Truncated)?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canister() -> Principal {
+        Principal::from_text("uxrrr-q7777-77774-qaaaq-cai").unwrap()
+    }
+
+    #[test]
+    fn round_trips_and_verifies() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let payload = QrPayload::sign(42, canister(), "8645REDMI14PRO", &key).unwrap();
+
+        let bytes = payload.encode().unwrap();
+        // version, id, 10-byte canister id, 14-byte serial, signature
+        assert_eq!(bytes.len(), 1 + 8 + 1 + 10 + 1 + 14 + 64);
+        assert_eq!(QrPayload::decode(&bytes).unwrap(), payload);
+
+        let text = payload.to_text().unwrap();
+        assert_eq!(QrPayload::from_text(&text).unwrap(), payload);
+        let url = format!("https://proofcart.example/q/{}", text);
+        assert_eq!(QrPayload::from_text(&url).unwrap(), payload);
+
+        payload.verify(&key.verifying_key()).unwrap();
+        let other = SigningKey::from_bytes(&[8; 32]);
+        assert_eq!(payload.verify(&other.verifying_key()), Err(Error::BadSignature));
+    }
+
+    #[test]
+    fn rejects_altered_payloads() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let payload = QrPayload::sign(42, canister(), "SN-1", &key).unwrap();
+        let forged = QrPayload { nft_id: 43, ..payload.clone() };
+        assert_eq!(forged.verify(&key.verifying_key()), Err(Error::BadSignature));
+
+        let mut bytes = payload.encode().unwrap();
+        assert_eq!(QrPayload::decode(&bytes[..bytes.len() - 1]), Err(Error::Truncated));
+        bytes.push(0);
+        assert_eq!(QrPayload::decode(&bytes), Err(Error::TrailingBytes));
+        bytes[0] = 2;
+        assert_eq!(QrPayload::decode(&bytes), Err(Error::UnsupportedVersion(2)));
+        assert_eq!(
+            QrPayload::sign(1, canister(), &"x".repeat(256), &key),
+            Err(Error::SerialTooLong(256))
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! `proofcart-simulator`: drives thousands of synthetic orders through the
+//! escrow program and the NFT canister on a local validator and replica,
+//! and reports latency, compute units and cycles, to size fees and limits
+//! before launch.
+//!
+//! ```text
+//! proofcart-simulator --program-id <PROGRAM_ID> --canister-id <CANISTER_ID> \
+//!     --identity marketplace.pem --escrow-admin-keypair escrow-admin.json \
+//!     --orders 5000 --rate 25 --dispute-rate 0.1 --json run.json
+//! ```
+
+mod report;
+mod scenario;
+mod wallets;
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use candid::Principal;
+use clap::Parser;
+use proofcart_icp_client::{identity, NftClient};
+use proofcart_solana_client::EscrowClient;
+use rand::RngCore;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::native_token::sol_to_lamports;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use scenario::{Plan, Simulation};
+use wallets::Buyer;
+
+#[derive(Parser)]
+#[clap(name = "proofcart-simulator", version, about = "Load test ProofCart order lifecycles on localnet")]
+struct Cli {
+    #[clap(long, env = "SOLANA_RPC_URL", default_value = "http://127.0.0.1:8899")]
+    url: String,
+    #[clap(long, env = "SOLANA_PROGRAM_ID")]
+    program_id: Pubkey,
+    #[clap(long, env = "IC_URL", default_value = "http://127.0.0.1:4943")]
+    ic_url: String,
+    #[clap(long, env = "ICP_CANISTER_ID")]
+    canister_id: Principal,
+    /// PEM file of a principal with the Marketplace role; it mints and
+    /// sells every token
+    #[clap(long, env = "SIMULATOR_IDENTITY")]
+    identity: PathBuf,
+    /// Keypair file of the escrow admin; needed when orders are disputed
+    #[clap(long, env = "ESCROW_ADMIN_KEYPAIR")]
+    escrow_admin_keypair: Option<PathBuf>,
+    /// Keypair file paying the buyers' SOL; by default a new keypair is
+    /// funded by airdrop
+    #[clap(long)]
+    funder_keypair: Option<PathBuf>,
+
+    /// Orders to run
+    #[clap(long, default_value = "1000")]
+    orders: u64,
+    /// Orders started per second
+    #[clap(long, default_value = "10")]
+    rate: f64,
+    /// Most orders in flight at once
+    #[clap(long, default_value = "200")]
+    concurrency: usize,
+    #[clap(long, default_value = "1000")]
+    buyers: usize,
+    #[clap(long, default_value = "1000")]
+    sellers: usize,
+    /// SOL given to each buyer for escrows, rent and fees
+    #[clap(long, default_value = "2")]
+    buyer_sol: f64,
+    /// Escrow amount of each order
+    #[clap(long, default_value = "10000000")]
+    amount_lamports: u64,
+    /// Milliseconds between funding the escrow and the buyer confirming or
+    /// disputing
+    #[clap(long, default_value = "2000")]
+    ship_delay_ms: u64,
+    /// Share of orders disputed, 0 to 1
+    #[clap(long, default_value = "0.1")]
+    dispute_rate: f64,
+    /// Share of disputes refunded, 0 to 1; the rest are released
+    #[clap(long, default_value = "0.5")]
+    refund_rate: f64,
+    /// Seed of each order's buyer, seller and dispute choices
+    #[clap(long, default_value = "0")]
+    seed: u64,
+    /// Skip reading compute units and fees of each transaction
+    #[clap(long)]
+    skip_compute_units: bool,
+    /// Also write the report as JSON
+    #[clap(long)]
+    json: Option<PathBuf>,
+    /// Allow URLs other than localhost, e.g. devnet
+    #[clap(long)]
+    allow_remote: bool,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn is_local(url: &str) -> bool {
+    url.contains("localhost") || url.contains("127.0.0.1")
+}
+
+/// Refuse mainnet outright, and anything remote unless asked: a run spends
+/// real SOL and cycles there.
+fn check_targets(cli: &Cli) -> Result<(), String> {
+    if cli.url.contains("mainnet") {
+        return Err(format!("{} is mainnet; the simulator never runs there", cli.url));
+    }
+    if !cli.allow_remote && !(is_local(&cli.url) && is_local(&cli.ic_url)) {
+        return Err("Remote URLs need --allow-remote".to_string());
+    }
+    for (name, rate) in [("--dispute-rate", cli.dispute_rate), ("--refund-rate", cli.refund_rate)] {
+        if !(0.0..=1.0).contains(&rate) {
+            return Err(format!("{} must be between 0 and 1", name));
+        }
+    }
+    if cli.rate <= 0.0 || cli.concurrency == 0 || cli.buyers == 0 || cli.sellers == 0 {
+        return Err("--rate, --concurrency, --buyers and --sellers must be positive".to_string());
+    }
+    if cli.dispute_rate > 0.0 && cli.escrow_admin_keypair.is_none() {
+        return Err("Disputed orders need --escrow-admin-keypair; pass --dispute-rate 0 to run without".to_string());
+    }
+    Ok(())
+}
+
+async fn run() -> Result<(), String> {
+    let cli = Cli::parse();
+    check_targets(&cli)?;
+
+    let escrow_admin = match &cli.escrow_admin_keypair {
+        Some(path) => Some(read_keypair_file(path).map_err(|e| format!("{}: {}", path.display(), e))?),
+        None => None,
+    };
+    let identity = identity::from_pem_file(&cli.identity).map_err(|e| e.to_string())?;
+    let principal = identity.sender()?;
+    let nft = NftClient::connect(&cli.ic_url, identity, cli.canister_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let rpc = RpcClient::new_with_commitment(cli.url.clone(), CommitmentConfig::confirmed());
+
+    eprintln!("Funding {} buyers with {} SOL each", cli.buyers, cli.buyer_sol);
+    let buyers: Vec<Buyer> = (0..cli.buyers).map(|_| Buyer::generate()).collect();
+    let each = sol_to_lamports(cli.buyer_sol);
+    let funder = match &cli.funder_keypair {
+        Some(path) => read_keypair_file(path).map_err(|e| format!("{}: {}", path.display(), e))?,
+        None => {
+            let funder = Keypair::new();
+            // A little over, for the funding transactions' own fees.
+            wallets::airdrop(&rpc, &funder.pubkey(), each * cli.buyers as u64 + sol_to_lamports(1.0)).await?;
+            funder
+        }
+    };
+    wallets::fund(&rpc, &funder, &buyers, each).await?;
+
+    let cycles_before = cycles(&nft).await;
+    let mut run_id = [0u8; 3];
+    rand::thread_rng().fill_bytes(&mut run_id);
+    let simulation = Arc::new(Simulation {
+        escrow: EscrowClient::new(rpc, cli.program_id),
+        nft,
+        principal,
+        escrow_admin,
+        buyers,
+        sellers: wallets::sellers(cli.sellers),
+        plan: Plan {
+            amount_lamports: cli.amount_lamports,
+            ship_delay: Duration::from_millis(cli.ship_delay_ms),
+            dispute_rate: cli.dispute_rate,
+            refund_rate: cli.refund_rate,
+            seed: cli.seed,
+            compute_units: !cli.skip_compute_units,
+        },
+        run_id: format!("SIM{}", run_id.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+        recorder: Default::default(),
+    });
+
+    eprintln!(
+        "Run {}: {} orders at {}/s, at most {} in flight",
+        simulation.run_id, cli.orders, cli.rate, cli.concurrency
+    );
+    let started = Instant::now();
+    let slots = Arc::new(Semaphore::new(cli.concurrency));
+    let mut ticks = tokio::time::interval(Duration::from_secs_f64(1.0 / cli.rate));
+    let mut tasks = JoinSet::new();
+    for n in 0..cli.orders {
+        ticks.tick().await;
+        // Past the concurrency limit, arrivals wait rather than pile up.
+        let slot = slots.clone().acquire_owned().await.expect("The semaphore is never closed");
+        let simulation = simulation.clone();
+        tasks.spawn(async move {
+            simulation.order(n).await;
+            drop(slot);
+        });
+        while tasks.try_join_next().is_some() {}
+        let finished = simulation.recorder.finished();
+        if finished > 0 && finished % 100 == 0 {
+            eprintln!("{} of {} orders finished", finished, cli.orders);
+        }
+    }
+    while tasks.join_next().await.is_some() {}
+    let elapsed = started.elapsed();
+
+    let cycles_after = cycles(&simulation.nft).await;
+    let report = simulation
+        .recorder
+        .report(elapsed, cli.seed, cycles_before.zip(cycles_after));
+    print!("{}", report.render());
+    if let Some(path) = &cli.json {
+        let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// The canister's cycle balance, if it can be read.
+async fn cycles(nft: &NftClient) -> Option<u128> {
+    match nft.canister_status_summary().await {
+        Ok(status) => Some(status.cycles_balance),
+        Err(e) => {
+            eprintln!("canister_status_summary: {}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! What a run measured: latency per step, compute units per Solana
+//! instruction, fees, and the canister's cycle burn.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// One call of an order's lifecycle.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    /// `mint_product_nft` (ICP)
+    Mint,
+    /// `lock_for_sale` (ICP)
+    Lock,
+    /// `create_escrow` (Solana)
+    CreateEscrow,
+    /// `confirm_delivery` (Solana)
+    ConfirmDelivery,
+    /// `lock_dispute` (Solana)
+    Dispute,
+    /// `resolve_refund` or `resolve_release` (Solana)
+    Resolve,
+    /// `transfer_from` (ICP)
+    Transfer,
+    /// `unlock` (ICP)
+    Unlock,
+}
+
+impl Step {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Step::Mint => "mint",
+            Step::Lock => "lock",
+            Step::CreateEscrow => "create_escrow",
+            Step::ConfirmDelivery => "confirm_delivery",
+            Step::Dispute => "dispute",
+            Step::Resolve => "resolve",
+            Step::Transfer => "transfer",
+            Step::Unlock => "unlock",
+        }
+    }
+}
+
+/// How an order ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The token reached the buyer, after a delivery or a released dispute.
+    Completed,
+    /// A dispute was refunded and the token unlocked.
+    Refunded,
+    /// A step failed; the order was abandoned where it stopped.
+    Failed,
+}
+
+#[derive(Default)]
+struct Samples {
+    latencies_ms: Vec<u64>,
+    errors: u64,
+    compute_units: Vec<u64>,
+    fees_lamports: u64,
+}
+
+#[derive(Default)]
+struct Counts {
+    completed: u64,
+    refunded: u64,
+    failed: u64,
+}
+
+/// Collects samples from every order task.
+#[derive(Default)]
+pub struct Recorder {
+    steps: Mutex<BTreeMap<Step, Samples>>,
+    outcomes: Mutex<Counts>,
+}
+
+impl Recorder {
+    pub fn call(&self, step: Step, latency: Duration) {
+        let mut steps = self.steps.lock().expect("recorder lock poisoned");
+        steps.entry(step).or_default().latencies_ms.push(latency.as_millis() as u64);
+    }
+
+    pub fn error(&self, step: Step) {
+        self.steps.lock().expect("recorder lock poisoned").entry(step).or_default().errors += 1;
+    }
+
+    /// Compute units and fee of a confirmed Solana transaction.
+    pub fn cost(&self, step: Step, compute_units: Option<u64>, fee_lamports: u64) {
+        let mut steps = self.steps.lock().expect("recorder lock poisoned");
+        let samples = steps.entry(step).or_default();
+        samples.compute_units.extend(compute_units);
+        samples.fees_lamports += fee_lamports;
+    }
+
+    pub fn outcome(&self, outcome: Outcome) {
+        let mut counts = self.outcomes.lock().expect("recorder lock poisoned");
+        match outcome {
+            Outcome::Completed => counts.completed += 1,
+            Outcome::Refunded => counts.refunded += 1,
+            Outcome::Failed => counts.failed += 1,
+        }
+    }
+
+    pub fn finished(&self) -> u64 {
+        let counts = self.outcomes.lock().expect("recorder lock poisoned");
+        counts.completed + counts.refunded + counts.failed
+    }
+
+    /// `cycles` is the canister's balance before and after the run, when
+    /// both could be read.
+    pub fn report(&self, elapsed: Duration, seed: u64, cycles: Option<(u128, u128)>) -> Report {
+        let counts = self.outcomes.lock().expect("recorder lock poisoned");
+        let steps = self.steps.lock().expect("recorder lock poisoned");
+        let orders = counts.completed + counts.refunded + counts.failed;
+        let cycles_burned = cycles.map(|(before, after)| before.saturating_sub(after));
+        Report {
+            seed,
+            orders,
+            completed: counts.completed,
+            refunded: counts.refunded,
+            failed: counts.failed,
+            elapsed_secs: elapsed.as_secs_f64(),
+            orders_per_sec: if elapsed.is_zero() { 0.0 } else { orders as f64 / elapsed.as_secs_f64() },
+            fees_lamports: steps.values().map(|samples| samples.fees_lamports).sum(),
+            cycles_burned,
+            cycles_per_order: cycles_burned.filter(|_| orders > 0).map(|burned| burned / orders as u128),
+            steps: steps.iter().map(|(step, samples)| StepReport::new(*step, samples)).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct StepReport {
+    pub step: Step,
+    pub calls: u64,
+    pub errors: u64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+    /// Solana steps only.
+    pub mean_compute_units: Option<u64>,
+    pub max_compute_units: Option<u64>,
+    pub fees_lamports: u64,
+}
+
+impl StepReport {
+    fn new(step: Step, samples: &Samples) -> Self {
+        let mut latencies = samples.latencies_ms.clone();
+        latencies.sort_unstable();
+        let units = &samples.compute_units;
+        Self {
+            step,
+            calls: latencies.len() as u64,
+            errors: samples.errors,
+            p50_ms: percentile(&latencies, 50.0),
+            p95_ms: percentile(&latencies, 95.0),
+            p99_ms: percentile(&latencies, 99.0),
+            max_ms: latencies.last().copied().unwrap_or(0),
+            mean_compute_units: (!units.is_empty()).then(|| units.iter().sum::<u64>() / units.len() as u64),
+            max_compute_units: units.iter().max().copied(),
+            fees_lamports: samples.fees_lamports,
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted samples; 0 when there are none.
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[derive(Serialize, Debug)]
+pub struct Report {
+    /// Replays the same buyers, sellers and dispute decisions.
+    pub seed: u64,
+    pub orders: u64,
+    pub completed: u64,
+    pub refunded: u64,
+    pub failed: u64,
+    pub elapsed_secs: f64,
+    pub orders_per_sec: f64,
+    pub fees_lamports: u64,
+    /// Includes anything else the canister did meanwhile; on a local
+    /// replica that is nothing.
+    pub cycles_burned: Option<u128>,
+    pub cycles_per_order: Option<u128>,
+    pub steps: Vec<StepReport>,
+}
+
+impl Report {
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "Orders  {} ({} completed, {} refunded, {} failed) in {:.1}s, {:.2}/s",
+            self.orders, self.completed, self.refunded, self.failed, self.elapsed_secs, self.orders_per_sec
+        );
+        let _ = writeln!(out, "Fees    {} lamports", self.fees_lamports);
+        match (self.cycles_burned, self.cycles_per_order) {
+            (Some(burned), Some(per_order)) => {
+                let _ = writeln!(out, "Cycles  {} burned, {} per order", burned, per_order);
+            }
+            (Some(burned), None) => {
+                let _ = writeln!(out, "Cycles  {} burned", burned);
+            }
+            _ => {
+                let _ = writeln!(out, "Cycles  unknown: canister_status_summary could not be read");
+            }
+        }
+        let _ = writeln!(out, "Seed    {}", self.seed);
+        let _ = writeln!(
+            out,
+            "\n{:<17} {:>7} {:>7} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8}",
+            "step", "calls", "errors", "p50 ms", "p95 ms", "p99 ms", "max ms", "CU mean", "CU max"
+        );
+        for step in &self.steps {
+            let units = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+            let _ = writeln!(
+                out,
+                "{:<17} {:>7} {:>7} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8}",
+                step.step.as_str(),
+                step.calls,
+                step.errors,
+                step.p50_ms,
+                step.p95_ms,
+                step.p99_ms,
+                step.max_ms,
+                units(step.mean_compute_units),
+                units(step.max_compute_units)
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let samples: Vec<u64> = (1..=100).collect();
+        assert_eq!(percentile(&samples, 50.0), 50);
+        assert_eq!(percentile(&samples, 99.0), 99);
+        assert_eq!(percentile(&[7], 95.0), 7);
+        assert_eq!(percentile(&[], 50.0), 0);
+    }
+
+    #[test]
+    fn report_summarizes_steps_and_cycles() {
+        let recorder = Recorder::default();
+        for ms in [10, 20, 30, 40] {
+            recorder.call(Step::CreateEscrow, Duration::from_millis(ms));
+        }
+        recorder.cost(Step::CreateEscrow, Some(12_000), 5_000);
+        recorder.cost(Step::CreateEscrow, Some(14_000), 5_000);
+        recorder.error(Step::Mint);
+        recorder.outcome(Outcome::Completed);
+        recorder.outcome(Outcome::Failed);
+
+        let report = recorder.report(Duration::from_secs(2), 7, Some((1_000_000, 400_000)));
+        assert_eq!(report.orders, 2);
+        assert_eq!(report.cycles_per_order, Some(300_000));
+        assert_eq!(report.fees_lamports, 10_000);
+        let escrow = report.steps.iter().find(|s| s.step == Step::CreateEscrow).unwrap();
+        assert_eq!((escrow.calls, escrow.p50_ms, escrow.max_ms), (4, 20, 40));
+        assert_eq!(escrow.mean_compute_units, Some(13_000));
+        let mint = report.steps.iter().find(|s| s.step == Step::Mint).unwrap();
+        assert_eq!((mint.calls, mint.errors, mint.mean_compute_units), (0, 1, None));
+        assert!(report.render().contains("create_escrow"));
+    }
+}
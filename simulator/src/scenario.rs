@@ -0,0 +1,166 @@
+//! One order's lifecycle, as the marketplace would drive it:
+//!
+//! ```text
+//! mint ─► lock ─► create_escrow ─► (ship) ─► confirm_delivery ─────────────► transfer
+//!                                        └─► dispute ─► resolve ─► release ─► transfer
+//!                                                                └ refund ──► unlock
+//! ```
+//!
+//! The simulator's identity mints and sells every token on ICP, so it needs
+//! the `Marketplace` role for `transfer_from`. Buyers are funded Solana
+//! keypairs; sellers are fresh addresses.
+
+use std::fmt::Display;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use candid::Principal;
+use proofcart_icp_client::types::{MintRequest, SalePrice};
+use proofcart_icp_client::NftClient;
+use proofcart_solana_client::{EscrowClient, Resolution};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_transaction_status::UiTransactionEncoding;
+
+use crate::report::{Outcome, Recorder, Step};
+use crate::wallets::Buyer;
+
+/// What each order does, beyond who takes part.
+pub struct Plan {
+    pub amount_lamports: u64,
+    /// Between funding the escrow and the buyer's next move.
+    pub ship_delay: Duration,
+    /// Share of orders the buyer disputes.
+    pub dispute_rate: f64,
+    /// Share of disputes resolved as refunds; the rest are released.
+    pub refund_rate: f64,
+    /// Seeds each order's choices, so a run can be replayed.
+    pub seed: u64,
+    /// Fetch each Solana transaction after it lands to read its compute
+    /// units and fee.
+    pub compute_units: bool,
+}
+
+/// Everything the order tasks share.
+pub struct Simulation {
+    pub escrow: EscrowClient,
+    pub nft: NftClient,
+    /// The simulator's ICP principal: minter and seller of every token.
+    pub principal: Principal,
+    /// Resolves disputes; required when `dispute_rate` is above zero.
+    pub escrow_admin: Option<Keypair>,
+    pub buyers: Vec<Buyer>,
+    pub sellers: Vec<Pubkey>,
+    pub plan: Plan,
+    /// Prefix of serials and order ids, unique per run.
+    pub run_id: String,
+    pub recorder: Recorder,
+}
+
+impl Simulation {
+    /// Run order `n` to its end and record how it went.
+    pub async fn order(&self, n: u64) {
+        let outcome = match self.lifecycle(n).await {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                eprintln!("Order {}-{}: {}", self.run_id, n, e);
+                Outcome::Failed
+            }
+        };
+        self.recorder.outcome(outcome);
+    }
+
+    async fn lifecycle(&self, n: u64) -> Result<Outcome, String> {
+        let mut rng = StdRng::seed_from_u64(self.plan.seed.wrapping_add(n));
+        let buyer = &self.buyers[rng.gen_range(0..self.buyers.len())];
+        let seller = self.sellers[rng.gen_range(0..self.sellers.len())];
+        let disputed = rng.gen_bool(self.plan.dispute_rate);
+        let refunded = rng.gen_bool(self.plan.refund_rate);
+        // Order ids are escrow PDA seeds, at most 32 bytes.
+        let order_id = format!("{}-{}", self.run_id, n);
+
+        let request = MintRequest {
+            serial_number: order_id.clone(),
+            product_name: format!("Simulated product {}", n),
+            manufacturer: "ProofCart Simulator".to_string(),
+            manufacture_date: "2024-01-01".to_string(),
+            category: "Simulation".to_string(),
+            description: "Minted by proofcart-simulator".to_string(),
+            idempotency_key: Some(order_id.clone()),
+            ..MintRequest::default()
+        };
+        let nft = self.timed(Step::Mint, self.nft.mint_product_nft(&request)).await?;
+        self.timed(Step::Lock, self.nft.lock_for_sale(nft.nft_id, &order_id)).await?;
+        let amount = self.plan.amount_lamports;
+        self.solana(Step::CreateEscrow, self.escrow.create_escrow(&buyer.keypair, seller, &order_id, amount))
+            .await?;
+
+        tokio::time::sleep(self.plan.ship_delay).await;
+
+        if disputed {
+            self.solana(Step::Dispute, self.escrow.dispute(&buyer.keypair, &order_id)).await?;
+            let admin = self.escrow_admin.as_ref().ok_or("Disputes need an escrow admin keypair")?;
+            let resolution = if refunded { Resolution::Refund } else { Resolution::Release };
+            self.solana(Step::Resolve, self.escrow.resolve(admin, &order_id, resolution)).await?;
+            if refunded {
+                self.timed(Step::Unlock, self.nft.unlock(nft.nft_id)).await?;
+                return Ok(Outcome::Refunded);
+            }
+        } else {
+            self.solana(Step::ConfirmDelivery, self.escrow.release(&buyer.keypair, &order_id)).await?;
+        }
+
+        let price = SalePrice { amount, currency: "SOL".to_string(), order_id: Some(order_id.clone()) };
+        self.timed(
+            Step::Transfer,
+            self.nft.transfer_from(nft.nft_id, self.principal, buyer.principal, &order_id, Some(price)),
+        )
+        .await?;
+        Ok(Outcome::Completed)
+    }
+
+    /// Await `call`, recording its latency or its failure.
+    async fn timed<T, E: Display>(&self, step: Step, call: impl Future<Output = Result<T, E>>) -> Result<T, String> {
+        let started = Instant::now();
+        match call.await {
+            Ok(value) => {
+                self.recorder.call(step, started.elapsed());
+                Ok(value)
+            }
+            Err(e) => {
+                self.recorder.error(step);
+                Err(format!("{}: {}", step.as_str(), e))
+            }
+        }
+    }
+
+    /// Like [`timed`](Self::timed), then read what the transaction cost.
+    /// The lookup is not part of the latency.
+    async fn solana<E: Display>(
+        &self,
+        step: Step,
+        call: impl Future<Output = Result<Signature, E>>,
+    ) -> Result<Signature, String> {
+        let signature = self.timed(step, call).await?;
+        if self.plan.compute_units {
+            let config = RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            };
+            match self.escrow.rpc().get_transaction_with_config(&signature, config).await {
+                Ok(transaction) => {
+                    if let Some(meta) = transaction.transaction.meta {
+                        self.recorder.cost(step, meta.compute_units_consumed.into(), meta.fee);
+                    }
+                }
+                Err(e) => eprintln!("Reading {} for its compute units: {}", signature, e),
+            }
+        }
+        Ok(signature)
+    }
+}
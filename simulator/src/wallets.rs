@@ -0,0 +1,74 @@
+//! Synthetic buyers and sellers, and the SOL the buyers spend.
+
+use std::time::Duration;
+
+use candid::Principal;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::Transaction;
+
+/// System transfers per funding transaction, well under the size limit.
+const TRANSFERS_PER_TRANSACTION: usize = 20;
+
+/// Largest airdrop requested at once; local faucets cap single requests.
+const AIRDROP_CHUNK: u64 = 500 * LAMPORTS_PER_SOL;
+
+pub struct Buyer {
+    pub keypair: Keypair,
+    /// Receives the token. Derived from the buyer's key; nobody signs as it.
+    pub principal: Principal,
+}
+
+impl Buyer {
+    pub fn generate() -> Self {
+        let keypair = Keypair::new();
+        let principal = Principal::self_authenticating(keypair.pubkey().to_bytes());
+        Self { keypair, principal }
+    }
+}
+
+/// Sellers only receive SOL, so an address is enough. Escrow amounts are
+/// above the rent-exempt minimum, so the first payment creates the account.
+pub fn sellers(count: usize) -> Vec<Pubkey> {
+    (0..count).map(|_| Keypair::new().pubkey()).collect()
+}
+
+/// Airdrop `lamports` to `to` in faucet-sized requests, waiting for each.
+pub async fn airdrop(rpc: &RpcClient, to: &Pubkey, lamports: u64) -> Result<(), String> {
+    let mut left = lamports;
+    while left > 0 {
+        let chunk = left.min(AIRDROP_CHUNK);
+        let signature = rpc
+            .request_airdrop(to, chunk)
+            .await
+            .map_err(|e| format!("Airdrop to {}: {}", to, e))?;
+        loop {
+            if rpc.confirm_transaction(&signature).await.map_err(|e| e.to_string())? {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+        left -= chunk;
+    }
+    Ok(())
+}
+
+/// Give every buyer `lamports` from `funder`, several buyers per
+/// transaction.
+pub async fn fund(rpc: &RpcClient, funder: &Keypair, buyers: &[Buyer], lamports: u64) -> Result<(), String> {
+    for batch in buyers.chunks(TRANSFERS_PER_TRANSACTION) {
+        let transfers: Vec<_> = batch
+            .iter()
+            .map(|buyer| system_instruction::transfer(&funder.pubkey(), &buyer.keypair.pubkey(), lamports))
+            .collect();
+        let blockhash = rpc.get_latest_blockhash().await.map_err(|e| e.to_string())?;
+        let transaction = Transaction::new_signed_with_payer(&transfers, Some(&funder.pubkey()), &[funder], blockhash);
+        rpc.send_and_confirm_transaction(&transaction)
+            .await
+            .map_err(|e| format!("Funding buyers: {}", e))?;
+    }
+    Ok(())
+}
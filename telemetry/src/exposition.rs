@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Write};
+use std::sync::Mutex;
+
+/// Builds a `/metrics` body in the Prometheus text format, version 0.0.4.
+#[derive(Default)]
+pub struct Exposition {
+    out: String,
+}
+
+impl Exposition {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn header(&mut self, name: &str, help: &str, kind: &str) {
+        let _ = writeln!(self.out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+    }
+
+    pub fn gauge(&mut self, name: &str, help: &str, value: impl Display) -> &mut Self {
+        self.header(name, help, "gauge");
+        let _ = writeln!(self.out, "{} {}", name, value);
+        self
+    }
+
+    pub fn counter(&mut self, name: &str, help: &str, value: u64) -> &mut Self {
+        self.header(name, help, "counter");
+        let _ = writeln!(self.out, "{} {}", name, value);
+        self
+    }
+
+    /// One sample per label combination seen so far.
+    pub fn family(&mut self, name: &str, help: &str, family: &Family) -> &mut Self {
+        self.header(name, help, "counter");
+        for (values, count) in family.snapshot() {
+            let labels: Vec<String> = family
+                .labels
+                .iter()
+                .zip(&values)
+                .map(|(label, value)| format!("{}=\"{}\"", label, escape(value)))
+                .collect();
+            let _ = writeln!(self.out, "{}{{{}}} {}", name, labels.join(","), count);
+        }
+        self
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// A counter split by a fixed set of labels, e.g. route and status.
+pub struct Family {
+    labels: &'static [&'static str],
+    counts: Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+impl Family {
+    pub fn new(labels: &'static [&'static str]) -> Self {
+        Self { labels, counts: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Add one to the sample with these label values, given in the order of
+    /// the family's labels.
+    pub fn inc(&self, values: &[&str]) {
+        self.add(values, 1);
+    }
+
+    pub fn add(&self, values: &[&str], n: u64) {
+        debug_assert_eq!(values.len(), self.labels.len(), "label values must match the family's labels");
+        let key = values.iter().map(|v| v.to_string()).collect();
+        *self.counts.lock().expect("metrics lock poisoned").entry(key).or_default() += n;
+    }
+
+    /// Current value of one sample; zero if never incremented.
+    pub fn get(&self, values: &[&str]) -> u64 {
+        let key: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        self.counts.lock().expect("metrics lock poisoned").get(&key).copied().unwrap_or(0)
+    }
+
+    fn snapshot(&self) -> Vec<(Vec<String>, u64)> {
+        self.counts
+            .lock()
+            .expect("metrics lock poisoned")
+            .iter()
+            .map(|(values, count)| (values.clone(), *count))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_the_text_format() {
+        let requests = Family::new(&["route", "status"]);
+        requests.inc(&["/verify/:serial", "200"]);
+        requests.add(&["/verify/:serial", "502"], 2);
+        let mut metrics = Exposition::new();
+        metrics
+            .gauge("lag_slots", "Slots behind.", 3)
+            .counter("events_total", "Events applied.", 7)
+            .family("requests_total", "Requests answered.", &requests);
+        assert_eq!(
+            metrics.finish(),
+            "# HELP lag_slots Slots behind.\n# TYPE lag_slots gauge\nlag_slots 3\n\
+             # HELP events_total Events applied.\n# TYPE events_total counter\nevents_total 7\n\
+             # HELP requests_total Requests answered.\n# TYPE requests_total counter\n\
+             requests_total{route=\"/verify/:serial\",status=\"200\"} 1\n\
+             requests_total{route=\"/verify/:serial\",status=\"502\"} 2\n"
+        );
+        assert_eq!(requests.get(&["/verify/:serial", "502"]), 2);
+        assert_eq!(requests.get(&["/metrics", "200"]), 0);
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        let errors = Family::new(&["error"]);
+        errors.inc(&["say \"hi\"\n"]);
+        let mut metrics = Exposition::new();
+        metrics.family("errors_total", "Errors.", &errors);
+        assert!(metrics.finish().contains("errors_total{error=\"say \\\"hi\\\"\\n\"} 1\n"));
+    }
+}
@@ -0,0 +1,133 @@
+//! Dependency checks behind `/healthz`.
+//!
+//! A service is healthy when every check passes. The body lists each check,
+//! so the failing dependency is visible without reading logs:
+//!
+//! ```json
+//! {"healthy": false, "checks": [
+//!   {"name": "solana", "healthy": true, "latency_ms": 84, "detail": "slot 301245567"},
+//!   {"name": "icp", "healthy": false, "latency_ms": 5000, "detail": "timed out after 5s"}
+//! ]}
+//! ```
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Longest a check may take before it counts as failed.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Check {
+    pub name: String,
+    pub healthy: bool,
+    pub latency_ms: u64,
+    pub detail: Option<String>,
+}
+
+impl Check {
+    /// Run `probe` under [`TIMEOUT`]. `Ok` carries an optional detail, e.g.
+    /// the slot seen.
+    pub async fn run<F>(name: &str, probe: F) -> Self
+    where
+        F: Future<Output = Result<Option<String>, String>>,
+    {
+        let started = Instant::now();
+        let outcome = tokio::time::timeout(TIMEOUT, probe)
+            .await
+            .unwrap_or_else(|_| Err(format!("timed out after {}s", TIMEOUT.as_secs())));
+        let latency_ms = started.elapsed().as_millis() as u64;
+        match outcome {
+            Ok(detail) => Self { name: name.to_string(), healthy: true, latency_ms, detail },
+            Err(error) => Self { name: name.to_string(), healthy: false, latency_ms, detail: Some(error) },
+        }
+    }
+
+    /// A lag that must stay within `max`, e.g. an indexer behind the chain.
+    pub fn lag(name: &str, lag: u64, max: u64, unit: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            healthy: lag <= max,
+            latency_ms: 0,
+            detail: Some(format!("{} {} behind (max {})", lag, unit, max)),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Report {
+    pub healthy: bool,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    pub fn new(checks: Vec<Check>) -> Self {
+        Self { healthy: checks.iter().all(|check| check.healthy), checks }
+    }
+
+    /// 200 when healthy, 503 otherwise, so load balancers and probes need
+    /// not parse the body.
+    pub fn status(&self) -> u16 {
+        if self.healthy {
+            200
+        } else {
+            503
+        }
+    }
+}
+
+/// The RPC node's own health (`getHealth`), which fails when it is behind
+/// the cluster, and its current slot.
+#[cfg(feature = "solana")]
+pub async fn solana(rpc: &solana_client::nonblocking::rpc_client::RpcClient) -> Check {
+    Check::run("solana", async {
+        rpc.get_health().await.map_err(|e| e.to_string())?;
+        let slot = rpc.get_slot().await.map_err(|e| e.to_string())?;
+        Ok(Some(format!("slot {}", slot)))
+    })
+    .await
+}
+
+/// The replica's `/api/v2/status`, healthy once it reports `healthy`.
+#[cfg(feature = "icp")]
+pub async fn icp(agent: &ic_agent::Agent) -> Check {
+    Check::run("icp", async {
+        let status = agent.status().await.map_err(|e| e.to_string())?;
+        match status.replica_health_status.as_deref() {
+            Some("healthy") | None => Ok(status.impl_version.map(|version| format!("replica {}", version))),
+            Some(other) => Err(format!("replica is {}", other)),
+        }
+    })
+    .await
+}
+
+#[cfg(feature = "postgres")]
+pub async fn postgres(db: &tokio_postgres::Client) -> Check {
+    Check::run("postgres", async {
+        db.simple_query("SELECT 1").await.map_err(|e| e.to_string())?;
+        Ok(None)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn failing_check_makes_the_report_unhealthy() {
+        let ok = Check::run("ok", async { Ok(None) }).await;
+        let failed = Check::run("down", async { Err("connection refused".to_string()) }).await;
+        assert_eq!(Report::new(vec![ok.clone()]).status(), 200);
+        let report = Report::new(vec![ok, failed]);
+        assert_eq!(report.status(), 503);
+        assert_eq!(report.checks[1].detail.as_deref(), Some("connection refused"));
+    }
+
+    #[test]
+    fn lag_within_its_limit_is_healthy() {
+        assert!(Check::lag("solana_lag", 150, 150, "slots").healthy);
+        assert!(!Check::lag("solana_lag", 151, 150, "slots").healthy);
+    }
+}
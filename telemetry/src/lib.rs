@@ -0,0 +1,16 @@
+//! Prometheus metrics and `/healthz` checks shared by the ProofCart
+//! services, so each exposes them the same way.
+//!
+//! - [`Exposition`] writes the Prometheus text format; [`Family`] is a
+//!   counter split by labels.
+//! - [`health`] checks the dependencies a service talks to. Each check is
+//!   behind a feature: `solana`, `icp` and `postgres`.
+//! - [`serve`] answers `GET /metrics` and `GET /healthz` for services
+//!   without an HTTP server of their own.
+
+mod exposition;
+pub mod health;
+mod server;
+
+pub use exposition::{Exposition, Family};
+pub use server::serve;
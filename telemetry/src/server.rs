@@ -0,0 +1,79 @@
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use crate::health::Report;
+
+/// Serve `GET /metrics` and `GET /healthz` on `addr` until an accept fails.
+/// Only the request line is read; probes and scrapers send nothing else
+/// that matters.
+pub async fn serve<M, H, F>(addr: SocketAddr, metrics: M, health: H) -> io::Result<()>
+where
+    M: Fn() -> String + Send + Sync + 'static,
+    H: Fn() -> F + Send + Sync + 'static,
+    F: Future<Output = Report> + Send,
+{
+    let listener = TcpListener::bind(addr).await?;
+    let metrics = Arc::new(metrics);
+    let health = Arc::new(health);
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let metrics = metrics.clone();
+        let health = health.clone();
+        tokio::spawn(async move {
+            let mut request = [0u8; 1024];
+            let read = stream.read(&mut request).await.unwrap_or(0);
+            let (status, content_type, body) = match path(&request[..read]) {
+                Some("/metrics") => (200, "text/plain; version=0.0.4", metrics()),
+                Some("/healthz") => {
+                    let report = health().await;
+                    let body = serde_json::to_string(&report).expect("Reports serialize");
+                    (report.status(), "application/json", body)
+                }
+                _ => (404, "text/plain", "Not found\n".to_string()),
+            };
+            let reason = match status {
+                200 => "OK",
+                404 => "Not Found",
+                _ => "Service Unavailable",
+            };
+            let response = format!(
+                "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                reason,
+                content_type,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+/// The path of a `GET` request line, without its query string.
+fn path(request: &[u8]) -> Option<&str> {
+    let line = std::str::from_utf8(request).ok()?.lines().next()?;
+    let mut parts = line.split(' ');
+    if parts.next()? != "GET" {
+        return None;
+    }
+    let target = parts.next()?;
+    Some(target.split('?').next().unwrap_or(target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_request_path() {
+        assert_eq!(path(b"GET /healthz HTTP/1.1\r\nHost: x\r\n\r\n"), Some("/healthz"));
+        assert_eq!(path(b"GET /metrics?name[]=up HTTP/1.1\r\n"), Some("/metrics"));
+        assert_eq!(path(b"POST /metrics HTTP/1.1\r\n"), None);
+        assert_eq!(path(b""), None);
+    }
+}
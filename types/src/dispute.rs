@@ -0,0 +1,23 @@
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "candid")]
+use candid::CandidType;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Outcome of a marketplace's arbitration of a disputed sale, as recorded
+/// against the product on the canister.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "candid", derive(CandidType))]
+pub enum DisputeOutcome {
+    /// The item was found to be counterfeit.
+    CounterfeitConfirmed,
+    /// Genuine, but not as described (condition, missing parts).
+    NotAsDescribed,
+    /// The claim was rejected; the sale stands.
+    RejectedForSeller,
+    /// The buyer withdrew the dispute.
+    Withdrawn,
+}
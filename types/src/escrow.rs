@@ -0,0 +1,28 @@
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "candid")]
+use candid::CandidType;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Status of an escrow account. Variant order is the program's `EscrowStatus`
+/// and must not change: borsh encodes the index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "candid", derive(CandidType))]
+pub enum EscrowState {
+    /// Funded, awaiting delivery.
+    Created,
+    /// Disputed, awaiting admin resolution.
+    Locked,
+    Released,
+    Refunded,
+}
+
+impl EscrowState {
+    /// The funds have left the escrow, to the seller or back to the buyer.
+    pub fn is_settled(self) -> bool {
+        matches!(self, EscrowState::Released | EscrowState::Refunded)
+    }
+}
@@ -0,0 +1,31 @@
+//! ProofCart domain types shared by the Solana escrow program, the NFT
+//! canister, the client SDKs and the off-chain services, so each stops
+//! declaring its own copy.
+//!
+//! Encodings are opt-in:
+//!
+//! - `serde`: JSON and other serde formats
+//! - `borsh`: the escrow program's account and instruction encoding
+//! - `candid`: the canister's interface; it also enables the types that
+//!   hold a `Principal`
+//!
+//! Without `std` (on by default) the crate is `no_std` and needs only
+//! `alloc`. Type and variant names match `proofcart_nft.did` and the
+//! program's Anchor definitions, so each encoding is wire-compatible with
+//! the chain it comes from.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+mod dispute;
+mod escrow;
+mod nft;
+mod order;
+
+pub use dispute::DisputeOutcome;
+pub use escrow::EscrowState;
+#[cfg(feature = "candid")]
+pub use nft::OwnershipRecord;
+pub use nft::{LocalizedText, NFTMetadata, SalePrice, TransactionType};
+pub use order::OrderStatus;
@@ -0,0 +1,81 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "candid")]
+use candid::{CandidType, Principal};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Descriptive fields of a product NFT.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "candid", derive(CandidType))]
+pub struct NFTMetadata {
+    pub serial_number: String,
+    pub product_name: String,
+    pub manufacturer: String,
+    pub manufacture_date: String,
+    pub category: String,
+    pub description: String,
+    pub specifications: String,
+    pub warranty_info: String,
+    pub certifications: Vec<String>,
+    pub ipfs_metadata_uri: String,
+    /// Arweave id of the certification documents, kept for long-term
+    /// retention; a path manifest when there are several.
+    pub arweave_tx_id: Option<String>,
+    /// Translations keyed by language tag.
+    pub localized: Option<Vec<(String, LocalizedText)>>,
+}
+
+/// Translated metadata fields; unset fields fall back to the original.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "candid", derive(CandidType))]
+pub struct LocalizedText {
+    pub product_name: Option<String>,
+    pub description: Option<String>,
+    pub warranty_info: Option<String>,
+}
+
+/// Why a token changed hands. `Transfer` is an unclassified transfer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "candid", derive(CandidType))]
+pub enum TransactionType {
+    Mint,
+    Transfer,
+    Sale,
+    Gift,
+    WarrantyReplacement,
+    ReturnToManufacturer,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "candid", derive(CandidType))]
+pub struct SalePrice {
+    /// In the smallest unit of `currency` (lamports for `SOL`).
+    pub amount: u64,
+    pub currency: String,
+    /// Solana escrow order that settled the sale.
+    pub order_id: Option<String>,
+}
+
+/// One entry of a token's ownership history.
+#[cfg(feature = "candid")]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OwnershipRecord {
+    pub owner: Principal,
+    pub timestamp: u64,
+    pub transaction_type: TransactionType,
+    pub memo: Option<String>,
+    /// Price paid, on `Sale` records only.
+    pub price: Option<SalePrice>,
+}
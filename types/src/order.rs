@@ -0,0 +1,63 @@
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "candid")]
+use candid::CandidType;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::EscrowState;
+
+/// Where a marketplace order stands across both chains: its escrow on
+/// Solana and the sale transfer of its product NFT on the canister.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "candid", derive(CandidType))]
+pub enum OrderStatus {
+    /// No escrow has been created yet.
+    AwaitingPayment,
+    /// Paid into escrow, awaiting delivery.
+    Funded,
+    /// The buyer disputed; an admin will refund or release.
+    Disputed,
+    /// The seller was paid; the NFT has not moved to the buyer yet.
+    Released,
+    /// The seller was paid and the NFT transferred to the buyer.
+    Completed,
+    Refunded,
+}
+
+impl OrderStatus {
+    /// Combine the escrow's state, if it exists, with whether the NFT sale
+    /// for the order has been settled on the canister.
+    pub fn new(escrow: Option<EscrowState>, nft_transferred: bool) -> Self {
+        match escrow {
+            None => OrderStatus::AwaitingPayment,
+            Some(EscrowState::Created) => OrderStatus::Funded,
+            Some(EscrowState::Locked) => OrderStatus::Disputed,
+            Some(EscrowState::Released) if nft_transferred => OrderStatus::Completed,
+            Some(EscrowState::Released) => OrderStatus::Released,
+            Some(EscrowState::Refunded) => OrderStatus::Refunded,
+        }
+    }
+
+    /// No further change is expected.
+    pub fn is_final(self) -> bool {
+        matches!(self, OrderStatus::Completed | OrderStatus::Refunded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn release_completes_once_the_nft_moves() {
+        assert_eq!(OrderStatus::new(None, false), OrderStatus::AwaitingPayment);
+        assert_eq!(OrderStatus::new(Some(EscrowState::Released), false), OrderStatus::Released);
+        assert_eq!(OrderStatus::new(Some(EscrowState::Released), true), OrderStatus::Completed);
+        assert!(!OrderStatus::Released.is_final());
+        assert!(OrderStatus::new(Some(EscrowState::Refunded), false).is_final());
+    }
+}